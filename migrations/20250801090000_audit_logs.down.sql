@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+DELETE FROM permissions WHERE name = 'admin:user-history';
+DROP TABLE IF EXISTS audit_logs;
+DROP TYPE IF EXISTS audit_category;
\ No newline at end of file
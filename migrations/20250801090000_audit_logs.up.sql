@@ -0,0 +1,23 @@
+-- Add up migration script here
+
+CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
+CREATE TYPE audit_category AS ENUM ('audit', 'login', 'email', 'moderation');
+
+CREATE TABLE IF NOT EXISTS audit_logs (
+    id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    actor_id UUID,
+    target_user_id UUID NOT NULL,
+    category audit_category NOT NULL,
+    action VARCHAR(50) NOT NULL,
+    metadata JSONB NOT NULL DEFAULT '{}'::jsonb,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX audit_logs_target_user_idx ON audit_logs (target_user_id, created_at);
+
+INSERT INTO permissions (id, name, description)
+VALUES
+    ('f1d2c7a4-5b0e-4c1a-9e58-3a7d2b6c9e01', 'admin:user-history', 'Get the action history of a user account.');
+
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', 'f1d2c7a4-5b0e-4c1a-9e58-3a7d2b6c9e01');
\ No newline at end of file
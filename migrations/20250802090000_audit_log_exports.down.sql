@@ -0,0 +1,6 @@
+-- Add down migration script here
+
+DROP INDEX IF EXISTS audit_logs_unexported_idx;
+ALTER TABLE audit_logs
+    DROP COLUMN IF EXISTS exported_at,
+    DROP COLUMN IF EXISTS export_attempts;
\ No newline at end of file
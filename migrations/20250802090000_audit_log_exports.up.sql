@@ -0,0 +1,6 @@
+-- Add up migration script here
+
+ALTER TABLE audit_logs
+    ADD COLUMN exported_at TIMESTAMPTZ,
+    ADD COLUMN export_attempts INTEGER NOT NULL DEFAULT 0;
+CREATE INDEX audit_logs_unexported_idx ON audit_logs (created_at) WHERE exported_at IS NULL;
\ No newline at end of file
@@ -0,0 +1,9 @@
+-- Add down migration script here
+
+DELETE FROM permissions WHERE name IN ('report:create', 'admin:report-list', 'admin:report-resolve');
+DROP TABLE IF EXISTS reports;
+ALTER TABLE comments DROP COLUMN IF EXISTS is_hidden;
+ALTER TABLE posts DROP COLUMN IF EXISTS is_hidden;
+ALTER TABLE users DROP COLUMN IF EXISTS is_banned;
+DROP TYPE IF EXISTS report_status;
+DROP TYPE IF EXISTS report_reason;
\ No newline at end of file
@@ -0,0 +1,42 @@
+-- Add up migration script here
+
+CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
+CREATE TYPE report_reason AS ENUM ('spam', 'harassment', 'hate-speech', 'misinformation', 'nsfw', 'other');
+CREATE TYPE report_status AS ENUM ('open', 'dismissed', 'actioned');
+
+ALTER TABLE users ADD COLUMN is_banned BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE posts ADD COLUMN is_hidden BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE comments ADD COLUMN is_hidden BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE IF NOT EXISTS reports (
+    id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    reporter_id UUID NOT NULL,
+    post_id UUID,
+    comment_id UUID,
+    reason report_reason NOT NULL,
+    details VARCHAR(500),
+    status report_status NOT NULL DEFAULT 'open',
+    resolved_by UUID,
+    resolved_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    FOREIGN KEY (reporter_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (post_id) REFERENCES posts(id) ON DELETE CASCADE,
+    FOREIGN KEY (comment_id) REFERENCES comments(id) ON DELETE CASCADE,
+    FOREIGN KEY (resolved_by) REFERENCES users(id) ON DELETE SET NULL,
+    CONSTRAINT report_single_target CHECK ((post_id IS NULL) <> (comment_id IS NULL))
+);
+CREATE INDEX reports_status_idx ON reports (status, created_at);
+
+INSERT INTO permissions (id, name, description)
+VALUES
+    ('2c8e4f10-7a3b-4d6e-9f21-5b7c3e8a1d02', 'report:create', 'Report a post or a comment.'),
+    ('6a1f9d3e-2b7c-4e58-a04d-8c3b5e7f2a03', 'admin:report-list', 'Get list of content reports.'),
+    ('9e4b2a7c-5d1f-4c36-b8e2-1f6a3d9c7b04', 'admin:report-resolve', 'Dismiss or take action on a content report.');
+
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES
+    ('e3488ac6-7012-4d95-a002-663b9a6f879a', '2c8e4f10-7a3b-4d6e-9f21-5b7c3e8a1d02'),
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', '2c8e4f10-7a3b-4d6e-9f21-5b7c3e8a1d02'),
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', '6a1f9d3e-2b7c-4e58-a04d-8c3b5e7f2a03'),
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', '9e4b2a7c-5d1f-4c36-b8e2-1f6a3d9c7b04');
\ No newline at end of file
@@ -0,0 +1,6 @@
+-- Add down migration script here
+
+DELETE FROM permissions WHERE name IN ('post:trending', 'admin:stats', 'admin:refresh-aggregates');
+DROP MATERIALIZED VIEW IF EXISTS admin_stats;
+DROP MATERIALIZED VIEW IF EXISTS user_leaderboard;
+DROP MATERIALIZED VIEW IF EXISTS trending_posts;
\ No newline at end of file
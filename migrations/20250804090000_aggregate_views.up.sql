@@ -0,0 +1,57 @@
+-- Add up migration script here
+
+CREATE MATERIALIZED VIEW IF NOT EXISTS trending_posts AS
+SELECT p.id AS post_id,
+       p.user_id,
+       p.created_at,
+       COUNT(c.id) AS comment_count,
+       ((COUNT(c.id) + 1) / POWER(EXTRACT(EPOCH FROM (NOW() - p.created_at)) / 3600 + 2, 1.5))::DOUBLE PRECISION AS score,
+       NOW() AS refreshed_at
+FROM posts AS p
+JOIN users AS u ON u.id = p.user_id
+LEFT JOIN comments AS c ON c.post_id = p.id AND c.is_hidden = false
+WHERE p.is_hidden = false AND u.is_banned = false AND p.created_at >= NOW() - INTERVAL '7 days'
+GROUP BY p.id;
+CREATE UNIQUE INDEX trending_posts_post_id_idx ON trending_posts (post_id);
+CREATE INDEX trending_posts_score_idx ON trending_posts (score DESC);
+
+CREATE MATERIALIZED VIEW IF NOT EXISTS user_leaderboard AS
+SELECT u.id AS user_id,
+       u.name,
+       (SELECT COUNT(*) FROM user_followers AS f WHERE f.following_id = u.id) AS followers_count,
+       (SELECT COUNT(*) FROM posts AS p
+        WHERE p.user_id = u.id AND p.is_hidden = false AND p.created_at >= NOW() - INTERVAL '7 days') AS posts_this_week,
+       NOW() AS refreshed_at
+FROM users AS u
+WHERE u.is_verified = true AND u.is_banned = false;
+CREATE UNIQUE INDEX user_leaderboard_user_id_idx ON user_leaderboard (user_id);
+CREATE INDEX user_leaderboard_followers_idx ON user_leaderboard (followers_count DESC);
+CREATE INDEX user_leaderboard_posts_idx ON user_leaderboard (posts_this_week DESC);
+
+CREATE MATERIALIZED VIEW IF NOT EXISTS admin_stats AS
+SELECT 1 AS id,
+       (SELECT COUNT(*) FROM users) AS total_users,
+       (SELECT COUNT(*) FROM users WHERE is_verified = true) AS verified_users,
+       (SELECT COUNT(*) FROM users WHERE is_banned = true) AS banned_users,
+       (SELECT COUNT(*) FROM users WHERE created_at >= NOW() - INTERVAL '7 days') AS new_users_this_week,
+       (SELECT COUNT(*) FROM posts) AS total_posts,
+       (SELECT COUNT(*) FROM posts WHERE is_hidden = true) AS hidden_posts,
+       (SELECT COUNT(*) FROM posts WHERE created_at >= NOW() - INTERVAL '7 days') AS new_posts_this_week,
+       (SELECT COUNT(*) FROM comments) AS total_comments,
+       (SELECT COUNT(*) FROM comments WHERE is_hidden = true) AS hidden_comments,
+       (SELECT COUNT(*) FROM reports WHERE status = 'open') AS open_reports,
+       NOW() AS refreshed_at;
+CREATE UNIQUE INDEX admin_stats_id_idx ON admin_stats (id);
+
+INSERT INTO permissions (id, name, description)
+VALUES
+    ('b7d3e9a1-4c2f-4e86-9a15-2e8f6c1d3b05', 'post:trending', 'Get list of trending posts.'),
+    ('d4a8c2e6-1f3b-4a97-8c5d-7b2e9f4a6c06', 'admin:stats', 'Get platform statistics.'),
+    ('e8c1f5b3-9a2d-4f74-b6e3-4d9a2c7e1f07', 'admin:refresh-aggregates', 'Force a refresh of the aggregate views.');
+
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES
+    ('e3488ac6-7012-4d95-a002-663b9a6f879a', 'b7d3e9a1-4c2f-4e86-9a15-2e8f6c1d3b05'),
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', 'b7d3e9a1-4c2f-4e86-9a15-2e8f6c1d3b05'),
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', 'd4a8c2e6-1f3b-4a97-8c5d-7b2e9f4a6c06'),
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', 'e8c1f5b3-9a2d-4f74-b6e3-4d9a2c7e1f07');
\ No newline at end of file
@@ -0,0 +1,10 @@
+-- Add up migration script here
+
+INSERT INTO permissions (id, name, description)
+VALUES
+    ('f2a6d8c4-3b1e-4f95-a7c2-6e9b1d4f8a08', 'user:leaderboard', 'Get most-followed users and top posters of the week.');
+
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES
+    ('e3488ac6-7012-4d95-a002-663b9a6f879a', 'f2a6d8c4-3b1e-4f95-a7c2-6e9b1d4f8a08'),
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', 'f2a6d8c4-3b1e-4f95-a7c2-6e9b1d4f8a08');
\ No newline at end of file
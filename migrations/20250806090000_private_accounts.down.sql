@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+DELETE FROM permissions WHERE name IN ('user:follow-request-list', 'user:follow-request-respond');
+DROP TABLE IF EXISTS follow_requests;
+ALTER TABLE users DROP COLUMN IF EXISTS is_private;
\ No newline at end of file
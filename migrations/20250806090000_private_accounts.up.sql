@@ -0,0 +1,25 @@
+-- Add up migration script here
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS is_private BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE IF NOT EXISTS follow_requests (
+    requester_id UUID NOT NULL,
+    target_id UUID NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (requester_id, target_id),
+    FOREIGN KEY (requester_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (target_id) REFERENCES users(id) ON DELETE CASCADE
+);
+CREATE INDEX follow_requests_target_idx ON follow_requests (target_id, created_at);
+
+INSERT INTO permissions (id, name, description)
+VALUES
+    ('a3c7e1f9-2d4b-4a68-b5e1-8f2c6d9a4e09', 'user:follow-request-list', 'Get list of pending follow requests.'),
+    ('c9e2a6d4-7f1b-4c35-9d8a-3b6e1f5c2a10', 'user:follow-request-respond', 'Accept or reject a follow request.');
+
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES
+    ('e3488ac6-7012-4d95-a002-663b9a6f879a', 'a3c7e1f9-2d4b-4a68-b5e1-8f2c6d9a4e09'),
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', 'a3c7e1f9-2d4b-4a68-b5e1-8f2c6d9a4e09'),
+    ('e3488ac6-7012-4d95-a002-663b9a6f879a', 'c9e2a6d4-7f1b-4c35-9d8a-3b6e1f5c2a10'),
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', 'c9e2a6d4-7f1b-4c35-9d8a-3b6e1f5c2a10');
\ No newline at end of file
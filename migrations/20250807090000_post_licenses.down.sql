@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+ALTER TABLE posts DROP COLUMN IF EXISTS license;
+ALTER TABLE users DROP COLUMN IF EXISTS default_license;
+DROP TYPE IF EXISTS post_license;
\ No newline at end of file
@@ -0,0 +1,6 @@
+-- Add up migration script here
+
+CREATE TYPE post_license AS ENUM ('all-rights-reserved', 'cc0', 'cc-by', 'cc-by-sa', 'cc-by-nd', 'cc-by-nc', 'cc-by-nc-sa', 'cc-by-nc-nd');
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS default_license post_license NOT NULL DEFAULT 'all-rights-reserved';
+ALTER TABLE posts ADD COLUMN IF NOT EXISTS license post_license NOT NULL DEFAULT 'all-rights-reserved';
\ No newline at end of file
@@ -0,0 +1,10 @@
+-- Add up migration script here
+
+INSERT INTO permissions (id, name, description)
+VALUES
+    ('d7b3f1a9-6e2c-4d84-a1f5-9c4e2b8d6a11', 'post:embed', 'Create an embed token for a post.');
+
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES
+    ('e3488ac6-7012-4d95-a002-663b9a6f879a', 'd7b3f1a9-6e2c-4d84-a1f5-9c4e2b8d6a11'),
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', 'd7b3f1a9-6e2c-4d84-a1f5-9c4e2b8d6a11');
\ No newline at end of file
@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+DELETE FROM permissions WHERE name = 'admin:waitlist-approve';
+DROP INDEX IF EXISTS users_waitlist_idx;
+ALTER TABLE users DROP COLUMN IF EXISTS waitlisted_at;
\ No newline at end of file
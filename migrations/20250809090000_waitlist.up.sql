@@ -0,0 +1,12 @@
+-- Add up migration script here
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS waitlisted_at TIMESTAMPTZ;
+CREATE INDEX users_waitlist_idx ON users (waitlisted_at, id) WHERE waitlisted_at IS NOT NULL;
+
+INSERT INTO permissions (id, name, description)
+VALUES
+    ('b2e8d4f6-3a1c-4b97-9e25-7d1f3c8a5b12', 'admin:waitlist-approve', 'Approve the next batch of waitlisted accounts.');
+
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', 'b2e8d4f6-3a1c-4b97-9e25-7d1f3c8a5b12');
\ No newline at end of file
@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DELETE FROM permissions WHERE name = 'user:settings';
+DROP TABLE IF EXISTS user_settings;
\ No newline at end of file
@@ -0,0 +1,16 @@
+-- Add up migration script here
+
+CREATE TABLE IF NOT EXISTS user_settings (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    settings JSONB NOT NULL DEFAULT '{}'::jsonb,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT Now()
+);
+
+INSERT INTO permissions (id, name, description)
+VALUES
+    ('c4f1a7e2-8d3b-4a69-b5e0-2f7c9d1a6e34', 'user:settings', 'Get and replace the signed in user''s preferences.');
+
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES
+    ('e3488ac6-7012-4d95-a002-663b9a6f879a', 'c4f1a7e2-8d3b-4a69-b5e0-2f7c9d1a6e34'),
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', 'c4f1a7e2-8d3b-4a69-b5e0-2f7c9d1a6e34');
\ No newline at end of file
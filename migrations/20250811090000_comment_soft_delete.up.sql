@@ -0,0 +1,19 @@
+-- Add up migration script here
+
+ALTER TABLE comments ADD COLUMN deleted_at TIMESTAMPTZ;
+
+DROP MATERIALIZED VIEW IF EXISTS admin_stats;
+CREATE MATERIALIZED VIEW admin_stats AS
+SELECT 1 AS id,
+       (SELECT COUNT(*) FROM users) AS total_users,
+       (SELECT COUNT(*) FROM users WHERE is_verified = true) AS verified_users,
+       (SELECT COUNT(*) FROM users WHERE is_banned = true) AS banned_users,
+       (SELECT COUNT(*) FROM users WHERE created_at >= NOW() - INTERVAL '7 days') AS new_users_this_week,
+       (SELECT COUNT(*) FROM posts) AS total_posts,
+       (SELECT COUNT(*) FROM posts WHERE is_hidden = true) AS hidden_posts,
+       (SELECT COUNT(*) FROM posts WHERE created_at >= NOW() - INTERVAL '7 days') AS new_posts_this_week,
+       (SELECT COUNT(*) FROM comments WHERE deleted_at IS NULL) AS total_comments,
+       (SELECT COUNT(*) FROM comments WHERE is_hidden = true AND deleted_at IS NULL) AS hidden_comments,
+       (SELECT COUNT(*) FROM reports WHERE status = 'open') AS open_reports,
+       NOW() AS refreshed_at;
+CREATE UNIQUE INDEX admin_stats_id_idx ON admin_stats (id);
\ No newline at end of file
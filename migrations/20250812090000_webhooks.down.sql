@@ -0,0 +1,6 @@
+-- Add down migration script here
+
+DELETE FROM permissions WHERE name IN ('admin:webhook-manage', 'admin:webhook-deliveries');
+DROP TABLE IF EXISTS webhook_deliveries;
+DROP TABLE IF EXISTS webhook_endpoints;
+DROP TYPE IF EXISTS webhook_delivery_status;
\ No newline at end of file
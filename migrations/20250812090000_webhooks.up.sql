@@ -0,0 +1,42 @@
+-- Add up migration script here
+
+CREATE TYPE webhook_delivery_status AS ENUM ('pending', 'delivered', 'dead');
+
+CREATE TABLE IF NOT EXISTS webhook_endpoints (
+    id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    url VARCHAR(500) NOT NULL,
+    secret VARCHAR(255) NOT NULL,
+    events TEXT[] NOT NULL,
+    created_by UUID,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    endpoint_id UUID NOT NULL,
+    event VARCHAR(100) NOT NULL,
+    payload JSONB NOT NULL,
+    status webhook_delivery_status NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_status_code INTEGER,
+    last_error TEXT,
+    delivered_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    FOREIGN KEY (endpoint_id) REFERENCES webhook_endpoints(id) ON DELETE CASCADE
+);
+CREATE INDEX webhook_deliveries_due_idx ON webhook_deliveries (next_attempt_at) WHERE status = 'pending';
+CREATE INDEX webhook_deliveries_endpoint_idx ON webhook_deliveries (endpoint_id, created_at);
+
+INSERT INTO permissions (id, name, description)
+VALUES
+    ('5d2a8c41-9e6f-4b3a-8c17-2f4e6b9d1a05', 'admin:webhook-manage', 'Register, list and remove outgoing webhook endpoints.'),
+    ('c7f13e92-4a5b-4d08-b6e3-9a1d5c2f8e06', 'admin:webhook-deliveries', 'Get the outgoing webhook delivery log and retry dead deliveries.');
+
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', '5d2a8c41-9e6f-4b3a-8c17-2f4e6b9d1a05'),
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', 'c7f13e92-4a5b-4d08-b6e3-9a1d5c2f8e06');
\ No newline at end of file
@@ -0,0 +1,7 @@
+-- Add down migration script here
+
+DROP INDEX IF EXISTS user_followers_follower_id_created_at_idx;
+DROP INDEX IF EXISTS user_followers_following_id_created_at_idx;
+ALTER TABLE users
+    DROP COLUMN IF EXISTS following_count,
+    DROP COLUMN IF EXISTS followers_count;
\ No newline at end of file
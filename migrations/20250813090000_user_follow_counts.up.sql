@@ -0,0 +1,12 @@
+-- Add up migration script here
+
+ALTER TABLE users
+    ADD COLUMN followers_count BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN following_count BIGINT NOT NULL DEFAULT 0;
+
+UPDATE users AS u
+SET followers_count = (SELECT COUNT(*) FROM user_followers WHERE following_id = u.id),
+    following_count = (SELECT COUNT(*) FROM user_followers WHERE follower_id = u.id);
+
+CREATE INDEX IF NOT EXISTS user_followers_following_id_created_at_idx ON user_followers (following_id, created_at);
+CREATE INDEX IF NOT EXISTS user_followers_follower_id_created_at_idx ON user_followers (follower_id, created_at);
\ No newline at end of file
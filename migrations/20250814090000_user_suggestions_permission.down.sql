@@ -0,0 +1,3 @@
+-- Add down migration script here
+
+DELETE FROM permissions WHERE name = 'user:suggestions';
\ No newline at end of file
@@ -0,0 +1,10 @@
+-- Add up migration script here
+
+INSERT INTO permissions (id, name, description)
+VALUES
+    ('8e4b2f7a-1c9d-4a63-b5e8-2d7f9c1a3e07', 'user:suggestions', 'Get accounts to follow, ranked by mutual connections and shared tags.');
+
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES
+    ('e3488ac6-7012-4d95-a002-663b9a6f879a', '8e4b2f7a-1c9d-4a63-b5e8-2d7f9c1a3e07'),
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', '8e4b2f7a-1c9d-4a63-b5e8-2d7f9c1a3e07');
\ No newline at end of file
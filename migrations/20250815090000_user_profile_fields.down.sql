@@ -0,0 +1,8 @@
+-- Add down migration script here
+
+ALTER TABLE users
+    DROP CONSTRAINT IF EXISTS users_username_key,
+    DROP COLUMN IF EXISTS website,
+    DROP COLUMN IF EXISTS avatar_url,
+    DROP COLUMN IF EXISTS bio,
+    DROP COLUMN IF EXISTS username;
\ No newline at end of file
@@ -0,0 +1,8 @@
+-- Add up migration script here
+
+ALTER TABLE users
+    ADD COLUMN username VARCHAR(30),
+    ADD COLUMN bio VARCHAR(160),
+    ADD COLUMN avatar_url VARCHAR(500),
+    ADD COLUMN website VARCHAR(500),
+    ADD CONSTRAINT users_username_key UNIQUE (username);
\ No newline at end of file
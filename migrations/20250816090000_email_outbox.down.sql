@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP TABLE IF EXISTS email_outbox;
+DROP TYPE IF EXISTS email_outbox_status;
\ No newline at end of file
@@ -0,0 +1,18 @@
+-- Add up migration script here
+
+CREATE TYPE email_outbox_status AS ENUM ('pending', 'sent', 'dead');
+
+CREATE TABLE IF NOT EXISTS email_outbox (
+    id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    to_email VARCHAR(255) NOT NULL,
+    subject VARCHAR(255) NOT NULL,
+    html TEXT NOT NULL,
+    status email_outbox_status NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_error TEXT,
+    sent_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX email_outbox_due_idx ON email_outbox (next_attempt_at) WHERE status = 'pending';
\ No newline at end of file
@@ -0,0 +1,9 @@
+-- Add up migration script here
+
+INSERT INTO permissions (id, name, description)
+VALUES
+    ('27b1eaa5-bee5-4749-8e6d-a4fe16e350e2', 'admin:impersonate', 'Act as another user with a short-lived, audited token.');
+
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', '27b1eaa5-bee5-4749-8e6d-a4fe16e350e2');
\ No newline at end of file
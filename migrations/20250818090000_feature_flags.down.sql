@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DELETE FROM permissions WHERE name = 'admin:feature-flag-manage';
+DROP TABLE IF EXISTS feature_flags;
\ No newline at end of file
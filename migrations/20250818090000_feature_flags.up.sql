@@ -0,0 +1,21 @@
+-- Add up migration script here
+
+CREATE TABLE IF NOT EXISTS feature_flags (
+    key VARCHAR(100) NOT NULL PRIMARY KEY,
+    description VARCHAR(255) NOT NULL DEFAULT '',
+    enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    roles TEXT[] NOT NULL DEFAULT '{}',
+    percentage INTEGER NOT NULL DEFAULT 100 CHECK (percentage BETWEEN 0 AND 100),
+    created_by UUID,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+INSERT INTO permissions (id, name, description)
+VALUES
+    ('8e4b2f17-3c6d-4a59-9f0e-b1d7a3c5e208', 'admin:feature-flag-manage', 'Create, list, change and remove feature flags.');
+
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', '8e4b2f17-3c6d-4a59-9f0e-b1d7a3c5e208');
\ No newline at end of file
@@ -0,0 +1,3 @@
+-- Add down migration script here
+
+DELETE FROM permissions WHERE name IN ('admin:user-import', 'admin:user-export');
\ No newline at end of file
@@ -0,0 +1,11 @@
+-- Add up migration script here
+
+INSERT INTO permissions (id, name, description)
+VALUES
+    ('b3f6d2a9-71c4-4e8b-a5d0-6c29e8f14b37', 'admin:user-import', 'Create user accounts in bulk from a CSV or NDJSON upload.'),
+    ('4a7e91c5-d2b8-4f36-9e0a-18c5b7f3d6e2', 'admin:user-export', 'Download every user account as CSV.');
+
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', 'b3f6d2a9-71c4-4e8b-a5d0-6c29e8f14b37'),
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', '4a7e91c5-d2b8-4f36-9e0a-18c5b7f3d6e2');
\ No newline at end of file
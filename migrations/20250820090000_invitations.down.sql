@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DELETE FROM permissions WHERE name = 'admin:invitation-create';
+DROP TABLE IF EXISTS invitations;
\ No newline at end of file
@@ -0,0 +1,22 @@
+-- Add up migration script here
+
+CREATE TABLE IF NOT EXISTS invitations (
+    id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    email VARCHAR(255) NOT NULL,
+    token VARCHAR(64) NOT NULL UNIQUE,
+    invited_by UUID,
+    expires_at TIMESTAMPTZ NOT NULL,
+    consumed_at TIMESTAMPTZ,
+    consumed_by UUID,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    FOREIGN KEY (invited_by) REFERENCES users(id) ON DELETE SET NULL,
+    FOREIGN KEY (consumed_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+INSERT INTO permissions (id, name, description)
+VALUES
+    ('c71d3e94-5b2a-4f08-8e6c-2a9f4d7b1e53', 'admin:invitation-create', 'Invite people to sign up while registration is invite only.');
+
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', 'c71d3e94-5b2a-4f08-8e6c-2a9f4d7b1e53');
\ No newline at end of file
@@ -0,0 +1,6 @@
+-- Add down migration script here
+
+DROP INDEX IF EXISTS user_action_tokens_token_hash_idx;
+UPDATE user_action_tokens SET token_hash = NULL, expires_at = NULL WHERE token_hash IS NOT NULL;
+ALTER TABLE user_action_tokens ALTER COLUMN token_hash TYPE VARCHAR(32);
+ALTER TABLE user_action_tokens RENAME COLUMN token_hash TO token;
\ No newline at end of file
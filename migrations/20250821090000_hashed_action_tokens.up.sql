@@ -0,0 +1,8 @@
+-- Add up migration script here
+
+-- Plain tokens can't be turned into peppered hashes here, outstanding links stop working and
+-- have to be requested again.
+UPDATE user_action_tokens SET token = NULL, expires_at = NULL WHERE token IS NOT NULL;
+ALTER TABLE user_action_tokens RENAME COLUMN token TO token_hash;
+ALTER TABLE user_action_tokens ALTER COLUMN token_hash TYPE VARCHAR(64);
+CREATE UNIQUE INDEX user_action_tokens_token_hash_idx ON user_action_tokens (token_hash) WHERE token_hash IS NOT NULL;
\ No newline at end of file
@@ -0,0 +1,9 @@
+-- Add up migration script here
+
+INSERT INTO permissions (id, name, description)
+VALUES
+    ('e5c82b17-9f3d-4a61-b0e4-73d1a6f29c58', 'admin:post-export', 'Stream every post as NDJSON.');
+
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', 'e5c82b17-9f3d-4a61-b0e4-73d1a6f29c58');
\ No newline at end of file
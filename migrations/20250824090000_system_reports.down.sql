@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DELETE FROM reports WHERE reporter_id IS NULL;
+ALTER TABLE reports ALTER COLUMN reporter_id SET NOT NULL;
\ No newline at end of file
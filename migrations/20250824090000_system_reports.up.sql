@@ -0,0 +1,4 @@
+-- Add up migration script here
+
+-- Reports filed by the content moderator have no reporter.
+ALTER TABLE reports ALTER COLUMN reporter_id DROP NOT NULL;
\ No newline at end of file
@@ -0,0 +1,6 @@
+-- Add down migration script here
+
+DELETE FROM permissions WHERE name IN ('admin:velocity-limit-manage', 'post:velocity-exempt');
+DROP TABLE IF EXISTS velocity_limits;
+DROP TYPE IF EXISTS velocity_action;
+DROP TYPE IF EXISTS velocity_kind;
\ No newline at end of file
@@ -0,0 +1,29 @@
+-- Add up migration script here
+
+CREATE TYPE velocity_kind AS ENUM ('post', 'comment');
+CREATE TYPE velocity_action AS ENUM ('throttle', 'flag');
+
+CREATE TABLE IF NOT EXISTS velocity_limits (
+    kind velocity_kind NOT NULL PRIMARY KEY,
+    max_count INTEGER NOT NULL CHECK (max_count >= 1),
+    window_secs INTEGER NOT NULL CHECK (window_secs >= 1),
+    action velocity_action NOT NULL DEFAULT 'throttle',
+    updated_by UUID,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    FOREIGN KEY (updated_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+INSERT INTO velocity_limits (kind, max_count, window_secs)
+VALUES
+    ('post', 5, 600),
+    ('comment', 10, 60);
+
+INSERT INTO permissions (id, name, description)
+VALUES
+    ('3d7f1c92-6a4e-4b18-8c05-e9b2d4f7a611', 'admin:velocity-limit-manage', 'List and change the posting velocity limits.'),
+    ('a51e8b3c-0f27-4d9a-b6c4-72e1f5d8c912', 'post:velocity-exempt', 'Post and comment without the posting velocity limits.');
+
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', '3d7f1c92-6a4e-4b18-8c05-e9b2d4f7a611'),
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', 'a51e8b3c-0f27-4d9a-b6c4-72e1f5d8c912');
\ No newline at end of file
@@ -0,0 +1,20 @@
+-- Add down migration script here
+
+DROP MATERIALIZED VIEW IF EXISTS trending_posts;
+CREATE MATERIALIZED VIEW trending_posts AS
+SELECT p.id AS post_id,
+       p.user_id,
+       p.created_at,
+       COUNT(c.id) AS comment_count,
+       ((COUNT(c.id) + 1) / POWER(EXTRACT(EPOCH FROM (NOW() - p.created_at)) / 3600 + 2, 1.5))::DOUBLE PRECISION AS score,
+       NOW() AS refreshed_at
+FROM posts AS p
+JOIN users AS u ON u.id = p.user_id
+LEFT JOIN comments AS c ON c.post_id = p.id AND c.is_hidden = false
+WHERE p.is_hidden = false AND u.is_banned = false AND p.created_at >= NOW() - INTERVAL '7 days'
+GROUP BY p.id;
+CREATE UNIQUE INDEX trending_posts_post_id_idx ON trending_posts (post_id);
+CREATE INDEX trending_posts_score_idx ON trending_posts (score DESC);
+
+DROP TABLE IF EXISTS post_views;
+ALTER TABLE posts DROP COLUMN IF EXISTS views_count;
\ No newline at end of file
@@ -0,0 +1,34 @@
+-- Add up migration script here
+
+ALTER TABLE posts ADD COLUMN views_count BIGINT NOT NULL DEFAULT 0;
+
+CREATE TABLE IF NOT EXISTS post_views (
+    post_id UUID NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+    hour TIMESTAMPTZ NOT NULL,
+    views BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (post_id, hour)
+);
+CREATE INDEX post_views_hour_idx ON post_views (hour);
+
+DROP MATERIALIZED VIEW IF EXISTS trending_posts;
+CREATE MATERIALIZED VIEW trending_posts AS
+SELECT p.id AS post_id,
+       p.user_id,
+       p.created_at,
+       COUNT(c.id) AS comment_count,
+       COALESCE(v.views, 0)::BIGINT AS view_count,
+       ((COUNT(c.id) + COALESCE(v.views, 0) / 10.0 + 1) / POWER(EXTRACT(EPOCH FROM (NOW() - p.created_at)) / 3600 + 2, 1.5))::DOUBLE PRECISION AS score,
+       NOW() AS refreshed_at
+FROM posts AS p
+JOIN users AS u ON u.id = p.user_id
+LEFT JOIN comments AS c ON c.post_id = p.id AND c.is_hidden = false
+LEFT JOIN (
+    SELECT post_id, SUM(views) AS views FROM post_views
+    WHERE hour >= NOW() - INTERVAL '7 days'
+    GROUP BY post_id
+) AS v ON v.post_id = p.id
+WHERE p.is_hidden = false AND u.is_banned = false AND p.created_at >= NOW() - INTERVAL '7 days'
+GROUP BY p.id, v.views;
+CREATE UNIQUE INDEX trending_posts_post_id_idx ON trending_posts (post_id);
+CREATE INDEX trending_posts_score_idx ON trending_posts (score DESC);
+CREATE INDEX trending_posts_view_count_idx ON trending_posts (view_count DESC);
\ No newline at end of file
@@ -0,0 +1,6 @@
+-- Add up migration script here
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX IF NOT EXISTS users_username_prefix_idx ON users (username text_pattern_ops);
+CREATE INDEX IF NOT EXISTS users_name_trgm_idx ON users USING GIN (lower(name) gin_trgm_ops);
\ No newline at end of file
@@ -0,0 +1,6 @@
+-- Add down migration script here
+
+DELETE FROM permissions WHERE name = 'search:query';
+DROP INDEX IF EXISTS posts_tags_idx;
+DROP INDEX IF EXISTS posts_search_vector_idx;
+ALTER TABLE posts DROP COLUMN IF EXISTS search_vector;
\ No newline at end of file
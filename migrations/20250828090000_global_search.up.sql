@@ -0,0 +1,16 @@
+-- Add up migration script here
+
+ALTER TABLE posts ADD COLUMN search_vector TSVECTOR GENERATED ALWAYS AS (
+    setweight(to_tsvector('english', title), 'A') || setweight(to_tsvector('english', content), 'B')
+) STORED;
+CREATE INDEX posts_search_vector_idx ON posts USING GIN (search_vector);
+CREATE INDEX posts_tags_idx ON posts USING GIN (tags);
+
+INSERT INTO permissions (id, name, description)
+VALUES
+    ('df952c54-5e24-4b58-8ba0-33695a12d8b7', 'search:query', 'Search posts, users and tags.');
+
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES
+    ('e3488ac6-7012-4d95-a002-663b9a6f879a', 'df952c54-5e24-4b58-8ba0-33695a12d8b7'),
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', 'df952c54-5e24-4b58-8ba0-33695a12d8b7');
\ No newline at end of file
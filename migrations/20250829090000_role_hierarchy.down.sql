@@ -0,0 +1,8 @@
+-- Add down migration script here
+
+INSERT INTO role_permissions (role_id, permission_id)
+SELECT '4b30ed16-06bc-4f7f-8293-6cb8a040267e', permission_id FROM role_permissions
+WHERE role_id = 'e3488ac6-7012-4d95-a002-663b9a6f879a'
+ON CONFLICT DO NOTHING;
+
+ALTER TABLE roles DROP COLUMN parent_role_id;
\ No newline at end of file
@@ -0,0 +1,15 @@
+-- Add up migration script here
+
+ALTER TABLE roles
+    ADD COLUMN parent_role_id UUID REFERENCES roles (id) ON DELETE SET NULL,
+    ADD CONSTRAINT roles_parent_role_not_self CHECK (parent_role_id <> id);
+
+-- Admins inherit every permission of users, their own grants keep only what users don't have.
+UPDATE roles SET parent_role_id = 'e3488ac6-7012-4d95-a002-663b9a6f879a'
+WHERE id = '4b30ed16-06bc-4f7f-8293-6cb8a040267e';
+
+DELETE FROM role_permissions AS admin_grant
+USING role_permissions AS user_grant
+WHERE admin_grant.role_id = '4b30ed16-06bc-4f7f-8293-6cb8a040267e'
+    AND user_grant.role_id = 'e3488ac6-7012-4d95-a002-663b9a6f879a'
+    AND admin_grant.permission_id = user_grant.permission_id;
\ No newline at end of file
@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+DELETE FROM permissions WHERE name = 'post:collaborator-manage';
+
+DROP TABLE IF EXISTS post_collaborators;
\ No newline at end of file
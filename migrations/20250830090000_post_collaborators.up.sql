@@ -0,0 +1,20 @@
+-- Add up migration script here
+
+CREATE TABLE IF NOT EXISTS post_collaborators (
+    post_id UUID NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    added_by UUID REFERENCES users (id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (post_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS post_collaborators_user_id_idx ON post_collaborators (user_id);
+
+INSERT INTO permissions (id, name, description)
+VALUES
+    ('6c0d4f3e-8a51-4e0b-9d7a-2f6b1c93e4a8', 'post:collaborator-manage', 'Add and remove the collaborators of a post.');
+
+-- Admins inherit it from the user role.
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES
+    ('e3488ac6-7012-4d95-a002-663b9a6f879a', '6c0d4f3e-8a51-4e0b-9d7a-2f6b1c93e4a8');
\ No newline at end of file
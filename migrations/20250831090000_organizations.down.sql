@@ -0,0 +1,12 @@
+-- Add down migration script here
+
+DELETE FROM permissions WHERE name IN ('organization:create', 'organization:detail', 'organization:manage', 'organization:join');
+
+DROP INDEX IF EXISTS posts_organization_id_idx;
+ALTER TABLE posts DROP COLUMN IF EXISTS organization_id;
+
+DROP TABLE IF EXISTS organization_followers;
+DROP TABLE IF EXISTS organization_invitations;
+DROP TABLE IF EXISTS organization_members;
+DROP TABLE IF EXISTS organizations;
+DROP TYPE IF EXISTS organization_role;
\ No newline at end of file
@@ -0,0 +1,63 @@
+-- Add up migration script here
+
+CREATE TYPE organization_role AS ENUM ('owner', 'editor', 'viewer');
+
+CREATE TABLE IF NOT EXISTS organizations (
+    id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    name VARCHAR(100) NOT NULL,
+    description VARCHAR(255) NOT NULL DEFAULT '',
+    created_by UUID,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE TABLE IF NOT EXISTS organization_members (
+    organization_id UUID NOT NULL REFERENCES organizations (id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    role organization_role NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (organization_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS organization_members_user_id_idx ON organization_members (user_id);
+
+CREATE TABLE IF NOT EXISTS organization_invitations (
+    organization_id UUID NOT NULL REFERENCES organizations (id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    role organization_role NOT NULL,
+    invited_by UUID REFERENCES users (id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (organization_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS organization_invitations_user_id_idx ON organization_invitations (user_id);
+
+CREATE TABLE IF NOT EXISTS organization_followers (
+    organization_id UUID NOT NULL REFERENCES organizations (id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (organization_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS organization_followers_user_id_idx ON organization_followers (user_id);
+
+-- Deleting an organization hands its posts back to their authors.
+ALTER TABLE posts ADD COLUMN IF NOT EXISTS organization_id UUID REFERENCES organizations (id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS posts_organization_id_idx ON posts (organization_id) WHERE organization_id IS NOT NULL;
+
+INSERT INTO permissions (id, name, description)
+VALUES
+    ('0b7e2c94-61d3-4f8a-a5e9-3d8c1f406b72', 'organization:create', 'Create organizations.'),
+    ('5a91d3e7-2f4b-4c68-8e0a-c7b35d9f1e24', 'organization:detail', 'See organizations and their members.'),
+    ('e84f0a26-9c1b-47d5-b3e8-6f2a7d05c9b1', 'organization:manage', 'Change, invite to and remove members of organizations the user owns.'),
+    ('93c6b5d8-7e2a-4b01-9f4c-1a8e6d3b07f5', 'organization:join', 'Accept organization invitations, leave and follow organizations.');
+
+-- Admins inherit them from the user role.
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES
+    ('e3488ac6-7012-4d95-a002-663b9a6f879a', '0b7e2c94-61d3-4f8a-a5e9-3d8c1f406b72'),
+    ('e3488ac6-7012-4d95-a002-663b9a6f879a', '5a91d3e7-2f4b-4c68-8e0a-c7b35d9f1e24'),
+    ('e3488ac6-7012-4d95-a002-663b9a6f879a', 'e84f0a26-9c1b-47d5-b3e8-6f2a7d05c9b1'),
+    ('e3488ac6-7012-4d95-a002-663b9a6f879a', '93c6b5d8-7e2a-4b01-9f4c-1a8e6d3b07f5');
\ No newline at end of file
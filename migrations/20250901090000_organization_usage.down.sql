@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+DELETE FROM permissions WHERE name = 'admin:organization-quota';
+DROP TABLE IF EXISTS organization_usage;
+ALTER TABLE organizations DROP COLUMN IF EXISTS monthly_request_quota;
\ No newline at end of file
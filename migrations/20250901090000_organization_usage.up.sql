@@ -0,0 +1,20 @@
+-- Add up migration script here
+
+-- NULL follows ORGANIZATION_MONTHLY_REQUEST_QUOTA, 0 is unlimited.
+ALTER TABLE organizations ADD COLUMN IF NOT EXISTS monthly_request_quota BIGINT CHECK (monthly_request_quota >= 0);
+
+CREATE TABLE IF NOT EXISTS organization_usage (
+    organization_id UUID NOT NULL,
+    month DATE NOT NULL,
+    requests BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (organization_id, month),
+    FOREIGN KEY (organization_id) REFERENCES organizations(id) ON DELETE CASCADE
+);
+
+INSERT INTO permissions (id, name, description)
+VALUES
+    ('c4f2a8e1-3b7d-4e96-a0d5-8e1b7c3f9a52', 'admin:organization-quota', 'Set the monthly request quota of an organization.');
+
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', 'c4f2a8e1-3b7d-4e96-a0d5-8e1b7c3f9a52');
\ No newline at end of file
@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP INDEX IF EXISTS user_settings_email_digest_idx;
+DROP TABLE IF EXISTS email_digests;
\ No newline at end of file
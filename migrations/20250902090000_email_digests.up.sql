@@ -0,0 +1,10 @@
+-- Add up migration script here
+
+CREATE TABLE IF NOT EXISTS email_digests (
+    user_id UUID NOT NULL PRIMARY KEY,
+    sent_at TIMESTAMPTZ NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS user_settings_email_digest_idx ON user_settings (user_id)
+WHERE settings->>'email_digest' IN ('daily', 'weekly');
\ No newline at end of file
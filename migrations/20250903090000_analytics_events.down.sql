@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DELETE FROM permissions WHERE name = 'analytics:track';
+DROP TABLE IF EXISTS analytics_events;
\ No newline at end of file
@@ -0,0 +1,24 @@
+-- Add up migration script here
+
+-- Client events without the user: `subject` is a keyed hash of the user and the day it was sent on,
+-- so a day's events go together but can't be traced back to an account or across days.
+CREATE TABLE IF NOT EXISTS analytics_events (
+    id UUID PRIMARY KEY,
+    subject TEXT NOT NULL,
+    kind TEXT NOT NULL CHECK (kind IN ('screen_view', 'interaction')),
+    name TEXT NOT NULL,
+    screen TEXT,
+    properties JSONB NOT NULL DEFAULT '{}',
+    occurred_at TIMESTAMPTZ NOT NULL,
+    received_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS analytics_events_name_occurred_at_idx ON analytics_events (name, occurred_at);
+
+INSERT INTO permissions (id, name, description)
+VALUES
+    ('7d3e9b14-52c8-4a6f-b1e0-9f4c2a8d6e31', 'analytics:track', 'Send anonymized usage events from the client.');
+
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES
+    ('e3488ac6-7012-4d95-a002-663b9a6f879a', '7d3e9b14-52c8-4a6f-b1e0-9f4c2a8d6e31');
\ No newline at end of file
@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+DELETE FROM permissions WHERE name = 'admin:announcement-manage';
+DROP TABLE IF EXISTS announcements;
+DROP TYPE IF EXISTS announcement_level;
\ No newline at end of file
@@ -0,0 +1,29 @@
+-- Add up migration script here
+
+CREATE TYPE announcement_level AS ENUM ('info', 'warning', 'critical');
+
+CREATE TABLE IF NOT EXISTS announcements (
+    id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    title VARCHAR(150) NOT NULL,
+    message TEXT NOT NULL,
+    level announcement_level NOT NULL DEFAULT 'info',
+    -- Role names the announcement is shown to, empty for everyone, signed out visitors included.
+    roles TEXT[] NOT NULL DEFAULT '{}',
+    starts_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    ends_at TIMESTAMPTZ,
+    created_by UUID,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (ends_at IS NULL OR ends_at > starts_at),
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS announcements_ends_at_idx ON announcements (ends_at);
+
+INSERT INTO permissions (id, name, description)
+VALUES
+    ('a9c1e5f3-6d28-4b7e-8f14-2e0b7d4c9a61', 'admin:announcement-manage', 'Create, list, change and remove announcements.');
+
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', 'a9c1e5f3-6d28-4b7e-8f14-2e0b7d4c9a61');
\ No newline at end of file
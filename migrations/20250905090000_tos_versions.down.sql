@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+DELETE FROM permissions WHERE name IN ('admin:tos-publish', 'user:accept-tos');
+DROP TABLE IF EXISTS user_tos_acceptance;
+DROP TABLE IF EXISTS tos_versions;
\ No newline at end of file
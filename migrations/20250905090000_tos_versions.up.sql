@@ -0,0 +1,33 @@
+-- Add up migration script here
+
+-- The latest published version is the current one, users who signed up before it have to accept it.
+CREATE TABLE IF NOT EXISTS tos_versions (
+    id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    version VARCHAR(50) NOT NULL UNIQUE,
+    url VARCHAR(500) NOT NULL,
+    summary TEXT NOT NULL DEFAULT '',
+    published_by UUID,
+    published_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    FOREIGN KEY (published_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS tos_versions_published_at_idx ON tos_versions (published_at DESC);
+
+CREATE TABLE IF NOT EXISTS user_tos_acceptance (
+    user_id UUID NOT NULL,
+    tos_version_id UUID NOT NULL,
+    accepted_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, tos_version_id),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (tos_version_id) REFERENCES tos_versions(id) ON DELETE CASCADE
+);
+
+INSERT INTO permissions (id, name, description)
+VALUES
+    ('3f8a6c21-9e4b-4d17-b5a0-7c2e1f9d8b43', 'admin:tos-publish', 'Publish and list the terms of service versions.'),
+    ('5b2d7e94-1c3a-4f68-9d05-e8a4c6b1f270', 'user:accept-tos', 'Accept the current terms of service.');
+
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', '3f8a6c21-9e4b-4d17-b5a0-7c2e1f9d8b43'),
+    ('e3488ac6-7012-4d95-a002-663b9a6f879a', '5b2d7e94-1c3a-4f68-9d05-e8a4c6b1f270');
\ No newline at end of file
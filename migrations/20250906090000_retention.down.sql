@@ -0,0 +1,7 @@
+-- Add down migration script here
+
+DELETE FROM permissions WHERE name = 'admin:retention-view';
+DROP INDEX IF EXISTS analytics_events_received_at_idx;
+DROP INDEX IF EXISTS comments_deleted_at_idx;
+DROP INDEX IF EXISTS audit_logs_retention_idx;
+ALTER TABLE audit_logs DROP COLUMN IF EXISTS anonymized_at;
\ No newline at end of file
@@ -0,0 +1,15 @@
+-- Add up migration script here
+
+-- Set when the retention worker stripped the log of who it was about, the action and time stay.
+ALTER TABLE audit_logs ADD COLUMN anonymized_at TIMESTAMPTZ;
+CREATE INDEX IF NOT EXISTS audit_logs_retention_idx ON audit_logs (created_at) WHERE anonymized_at IS NULL;
+CREATE INDEX IF NOT EXISTS comments_deleted_at_idx ON comments (deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX IF NOT EXISTS analytics_events_received_at_idx ON analytics_events (received_at);
+
+INSERT INTO permissions (id, name, description)
+VALUES
+    ('c4e7a2d9-3b18-4f05-a6c2-8d1f9e0b5a37', 'admin:retention-view', 'See what the data retention rules would purge or anonymize.');
+
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', 'c4e7a2d9-3b18-4f05-a6c2-8d1f9e0b5a37');
\ No newline at end of file
@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+DELETE FROM permissions WHERE name = 'admin:data-export';
+DROP TABLE IF EXISTS data_exports;
+DROP TYPE IF EXISTS data_export_status;
\ No newline at end of file
@@ -0,0 +1,33 @@
+-- Add up migration script here
+
+CREATE TYPE data_export_status AS ENUM ('pending', 'running', 'completed', 'failed');
+
+-- Dumps of whole tables to the export storage, made by the data export worker. `files` lists the
+-- tables written so far, `updated_at` doubles as the worker's heartbeat.
+CREATE TABLE IF NOT EXISTS data_exports (
+    id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    tables TEXT[] NOT NULL,
+    format VARCHAR(10) NOT NULL CHECK (format IN ('ndjson', 'csv')),
+    status data_export_status NOT NULL DEFAULT 'pending',
+    files JSONB NOT NULL DEFAULT '[]'::jsonb,
+    rows_total BIGINT,
+    rows_exported BIGINT NOT NULL DEFAULT 0,
+    bytes_exported BIGINT NOT NULL DEFAULT 0,
+    error TEXT,
+    requested_by UUID,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    started_at TIMESTAMPTZ,
+    finished_at TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    FOREIGN KEY (requested_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS data_exports_unfinished_idx ON data_exports (created_at) WHERE status IN ('pending', 'running');
+
+INSERT INTO permissions (id, name, description)
+VALUES
+    ('8e1b4f72-a93c-4d60-b7e5-2c9d0a6f3e18', 'admin:data-export', 'Export whole tables to the export storage and download them.');
+
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', '8e1b4f72-a93c-4d60-b7e5-2c9d0a6f3e18');
\ No newline at end of file
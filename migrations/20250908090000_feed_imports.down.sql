@@ -0,0 +1,6 @@
+-- Add down migration script here
+
+DELETE FROM permissions WHERE name = 'feed:import';
+DROP TABLE IF EXISTS feed_drafts;
+DROP TABLE IF EXISTS feed_sources;
+DROP TYPE IF EXISTS feed_draft_status;
\ No newline at end of file
@@ -0,0 +1,56 @@
+-- Add up migration script here
+
+CREATE TYPE feed_draft_status AS ENUM ('draft', 'published', 'dismissed');
+
+-- RSS or Atom feeds a user mirrors into posts. The fetcher takes the ones whose `next_fetch_at` has
+-- passed and asks again with `etag` and `last_modified`, failures push the next fetch further out.
+CREATE TABLE IF NOT EXISTS feed_sources (
+    id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    user_id UUID NOT NULL,
+    url VARCHAR(2048) NOT NULL,
+    title VARCHAR(200),
+    etag VARCHAR(500),
+    last_modified VARCHAR(100),
+    last_fetched_at TIMESTAMPTZ,
+    last_error TEXT,
+    failure_count INTEGER NOT NULL DEFAULT 0,
+    next_fetch_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (user_id, url),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS feed_sources_next_fetch_at_idx ON feed_sources (next_fetch_at);
+
+-- One row per feed entry ever seen, keyed by its GUID so an entry is only imported once, also after
+-- its draft was published or dismissed. Title, content and tags are cut to what a post allows.
+CREATE TABLE IF NOT EXISTS feed_drafts (
+    id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    source_id UUID NOT NULL,
+    user_id UUID NOT NULL,
+    guid VARCHAR(1000) NOT NULL,
+    link VARCHAR(2048),
+    title VARCHAR(20) NOT NULL,
+    content TEXT NOT NULL,
+    tags VARCHAR(20)[] NOT NULL,
+    status feed_draft_status NOT NULL DEFAULT 'draft',
+    post_id UUID,
+    entry_published_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (source_id, guid),
+    FOREIGN KEY (source_id) REFERENCES feed_sources(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (post_id) REFERENCES posts(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS feed_drafts_user_id_idx ON feed_drafts (user_id, created_at DESC) WHERE status = 'draft';
+
+INSERT INTO permissions (id, name, description)
+VALUES
+    ('3d7f92c1-5b8e-4a06-9c2d-e41a6b0f8d57', 'feed:import', 'Mirror RSS or Atom feeds into draft posts.');
+
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES
+    ('e3488ac6-7012-4d95-a002-663b9a6f879a', '3d7f92c1-5b8e-4a06-9c2d-e41a6b0f8d57');
\ No newline at end of file
@@ -0,0 +1,16 @@
+-- Add down migration script here
+
+DELETE FROM permissions WHERE name = 'admin:organization-data';
+ALTER TABLE data_exports DROP COLUMN IF EXISTS organization_id;
+DROP TRIGGER IF EXISTS posts_region ON posts;
+DROP TRIGGER IF EXISTS organization_usage_region ON organization_usage;
+DROP TRIGGER IF EXISTS organization_followers_region ON organization_followers;
+DROP TRIGGER IF EXISTS organization_invitations_region ON organization_invitations;
+DROP TRIGGER IF EXISTS organization_members_region ON organization_members;
+DROP FUNCTION IF EXISTS set_organization_region();
+ALTER TABLE posts DROP COLUMN IF EXISTS region;
+ALTER TABLE organization_usage DROP COLUMN IF EXISTS region;
+ALTER TABLE organization_followers DROP COLUMN IF EXISTS region;
+ALTER TABLE organization_invitations DROP COLUMN IF EXISTS region;
+ALTER TABLE organization_members DROP COLUMN IF EXISTS region;
+ALTER TABLE organizations DROP COLUMN IF EXISTS region;
@@ -0,0 +1,53 @@
+-- Add up migration script here
+
+-- The region an organization's data is kept in, one of DATA_REGIONS, chosen when it is created.
+ALTER TABLE organizations ADD COLUMN IF NOT EXISTS region VARCHAR(50) NOT NULL DEFAULT 'default';
+
+-- Every row belonging to an organization carries its region, copied from the organization when
+-- the row is written. Posts handed back to their authors lose it with their organization_id.
+CREATE OR REPLACE FUNCTION set_organization_region() RETURNS TRIGGER AS $$
+BEGIN
+    NEW.region := (SELECT region FROM organizations WHERE id = NEW.organization_id);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+ALTER TABLE organization_members ADD COLUMN IF NOT EXISTS region VARCHAR(50);
+ALTER TABLE organization_invitations ADD COLUMN IF NOT EXISTS region VARCHAR(50);
+ALTER TABLE organization_followers ADD COLUMN IF NOT EXISTS region VARCHAR(50);
+ALTER TABLE organization_usage ADD COLUMN IF NOT EXISTS region VARCHAR(50);
+ALTER TABLE posts ADD COLUMN IF NOT EXISTS region VARCHAR(50);
+
+UPDATE organization_members AS t SET region = o.region FROM organizations AS o WHERE o.id = t.organization_id;
+UPDATE organization_invitations AS t SET region = o.region FROM organizations AS o WHERE o.id = t.organization_id;
+UPDATE organization_followers AS t SET region = o.region FROM organizations AS o WHERE o.id = t.organization_id;
+UPDATE organization_usage AS t SET region = o.region FROM organizations AS o WHERE o.id = t.organization_id;
+UPDATE posts AS t SET region = o.region FROM organizations AS o WHERE o.id = t.organization_id;
+
+ALTER TABLE organization_members ALTER COLUMN region SET NOT NULL;
+ALTER TABLE organization_invitations ALTER COLUMN region SET NOT NULL;
+ALTER TABLE organization_followers ALTER COLUMN region SET NOT NULL;
+ALTER TABLE organization_usage ALTER COLUMN region SET NOT NULL;
+
+CREATE TRIGGER organization_members_region BEFORE INSERT OR UPDATE OF organization_id ON organization_members
+    FOR EACH ROW EXECUTE FUNCTION set_organization_region();
+CREATE TRIGGER organization_invitations_region BEFORE INSERT OR UPDATE OF organization_id ON organization_invitations
+    FOR EACH ROW EXECUTE FUNCTION set_organization_region();
+CREATE TRIGGER organization_followers_region BEFORE INSERT OR UPDATE OF organization_id ON organization_followers
+    FOR EACH ROW EXECUTE FUNCTION set_organization_region();
+CREATE TRIGGER organization_usage_region BEFORE INSERT OR UPDATE OF organization_id ON organization_usage
+    FOR EACH ROW EXECUTE FUNCTION set_organization_region();
+CREATE TRIGGER posts_region BEFORE INSERT OR UPDATE OF organization_id ON posts
+    FOR EACH ROW EXECUTE FUNCTION set_organization_region();
+
+-- Exports of a single organization. No foreign key, the export outlives the organization it was
+-- taken of.
+ALTER TABLE data_exports ADD COLUMN IF NOT EXISTS organization_id UUID;
+
+INSERT INTO permissions (id, name, description)
+VALUES
+    ('6d2f9a41-8c3e-4b75-a1f0-e59b7c2d4a86', 'admin:organization-data', 'Export or delete all data of an organization.');
+
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES
+    ('4b30ed16-06bc-4f7f-8293-6cb8a040267e', '6d2f9a41-8c3e-4b75-a1f0-e59b7c2d4a86');
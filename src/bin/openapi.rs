@@ -0,0 +1,8 @@
+use axum_restful_api::openapi::ApiDoc;
+use utoipa::OpenApi;
+
+// cargo run --bin openapi > openapi.json
+fn main() {
+    let spec = ApiDoc::openapi().to_pretty_json().expect("Failed to serialize the OpenAPI document");
+    println!("{}", spec);
+}
\ No newline at end of file
@@ -0,0 +1,333 @@
+use chrono::{DateTime, Utc};
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode, header::{COOKIE, IF_MATCH, SET_COOKIE}};
+use serde::{de::DeserializeOwned, Deserialize};
+use thiserror::Error;
+use uuid::Uuid;
+use crate::{
+    dto::{BatchData, BatchRequest, PaginatedData},
+    error::IfMatch,
+    modules::{
+        activity::{dto::UserActivityParams, model::ActivityItem},
+        analytics::dto::{AnalyticsBatchRequest, AnalyticsBatchResponse},
+        announcement::dto::ActiveAnnouncement,
+        auth::dto::{AvailabilityQuery, AvailabilityResponse, SignInRequest, SignInResponse, SignUpRequest, SignUpResponse, TokenResponse},
+        comment::{dto::CommentRequest, model::{Comment, CommentDetail, CommentsByPost}},
+        organization::{
+            dto::{OrganizationInvitationRequest, OrganizationMemberRequest, OrganizationRequest, OrganizationUpdateRequest, OrganizationUsageResponse},
+            model::{Organization, OrganizationDetail, OrganizationInvitation, OrganizationMember},
+        },
+        post::{dto::{PostCollaboratorRequest, PostPatchRequest, PostRequest}, model::{Post, PostCollaborator, PostDetail, PostListByUser}},
+        search::{dto::SearchParams, model::SearchResults},
+        tos::{dto::TosAcceptRequest, model::{TosAcceptance, TosVersion}},
+        user::{
+            dto::{FollowUnfollowResponse, UserFeedParams, UserFeeds, UserResponse, UserSearchParams, UserSettings, UserSuggestionParams, UserPatchRequest, UserUpdateRequest},
+            model::{UserDetail, UserSearchResult, UserSuggestion, UserSummary},
+        },
+    },
+};
+
+#[derive(Deserialize)]
+pub struct ApiResponse<T> {
+    pub status: String,
+    pub message: String,
+    pub data: Option<T>,
+}
+
+#[derive(Deserialize)]
+struct ApiErrorBody {
+    message: String,
+    code: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("Request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("API error {status}: {message}")]
+    Api {
+        status: StatusCode,
+        code: Option<String>,
+        message: String,
+    },
+    #[error("Response has no data")]
+    MissingData,
+}
+
+/// Typed client for the endpoints in `openapi::ApiDoc`. Bodies are the server's own DTOs,
+/// so a change to a handler's types breaks the client at compile time rather than at runtime.
+pub struct ApiClient {
+    http: Client,
+    base_url: String,
+    access_token: Option<String>,
+    refresh_token: Option<String>,
+}
+
+impl ApiClient {
+    /// `base_url` is the server root, e.g. `http://localhost:4000`. The refresh token cookie is kept
+    /// by the client itself rather than a cookie store, so `refresh_token` works over plain http too.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            http: Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            access_token: None,
+            refresh_token: None,
+        }
+    }
+    pub fn with_access_token(mut self, access_token: impl Into<String>) -> Self {
+        self.access_token = Some(access_token.into());
+        self
+    }
+    pub fn access_token(&self) -> Option<&str> {
+        self.access_token.as_deref()
+    }
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let builder = self.http.request(method, format!("{}/api{}", self.base_url, path));
+        match &self.access_token {
+            Some(token) => builder.bearer_auth(token),
+            None => builder,
+        }
+    }
+    async fn execute<T: DeserializeOwned>(builder: RequestBuilder) -> Result<ApiResponse<T>, ClientError> {
+        Self::parse(builder.send().await?).await
+    }
+    async fn parse<T: DeserializeOwned>(response: Response) -> Result<ApiResponse<T>, ClientError> {
+        let status = response.status();
+        if !status.is_success() {
+            let (message, code) = match response.json::<ApiErrorBody>().await {
+                Ok(body) => (body.message, body.code),
+                Err(_) => (status.to_string(), None),
+            };
+            return Err(ClientError::Api { status, code, message });
+        }
+        Ok(response.json::<ApiResponse<T>>().await?)
+    }
+    /// Makes an update conditional on the record still being at `expected_version`, its `updated_at`.
+    fn if_match(builder: RequestBuilder, expected_version: Option<DateTime<Utc>>) -> RequestBuilder {
+        match expected_version {
+            Some(version) => builder.header(IF_MATCH, IfMatch::etag(version)),
+            None => builder,
+        }
+    }
+    async fn data<T: DeserializeOwned>(builder: RequestBuilder) -> Result<T, ClientError> {
+        Self::execute(builder).await?.data.ok_or(ClientError::MissingData)
+    }
+
+    pub async fn availability(&self, query: &AvailabilityQuery) -> Result<AvailabilityResponse, ClientError> {
+        Self::data(self.request(Method::GET, "/auth/availability").query(query)).await
+    }
+    pub async fn sign_up(&self, body: &SignUpRequest) -> Result<SignUpResponse, ClientError> {
+        Self::data(self.request(Method::POST, "/auth/sign-up").json(body)).await
+    }
+    /// Keeps the access token for every following request, and the refresh token cookie for
+    /// `refresh_token`.
+    pub async fn sign_in(&mut self, body: &SignInRequest) -> Result<SignInResponse, ClientError> {
+        let response = self.request(Method::POST, "/auth/sign-in").json(body).send().await?;
+        self.keep_refresh_token(&response);
+        let response: SignInResponse = Self::parse(response).await?.data.ok_or(ClientError::MissingData)?;
+        self.access_token = Some(response.token.access_token.clone());
+        Ok(response)
+    }
+    /// Trades the refresh token for a new pair, the old refresh token stops working.
+    pub async fn refresh_token(&mut self) -> Result<TokenResponse, ClientError> {
+        let mut builder = self.request(Method::POST, "/auth/refresh");
+        if let Some(refresh_token) = &self.refresh_token {
+            builder = builder.header(COOKIE, format!("refresh_token={}", refresh_token));
+        }
+        let response = builder.send().await?;
+        self.keep_refresh_token(&response);
+        let response: TokenResponse = Self::parse(response).await?.data.ok_or(ClientError::MissingData)?;
+        self.access_token = Some(response.access_token.clone());
+        Ok(response)
+    }
+    fn keep_refresh_token(&mut self, response: &Response) {
+        let refresh_token = response.headers().get_all(SET_COOKIE).iter()
+            .filter_map(|value| value.to_str().ok()?.split(';').next()?.trim().strip_prefix("refresh_token="))
+            .next_back();
+        if let Some(refresh_token) = refresh_token {
+            self.refresh_token = Some(refresh_token.to_string()).filter(|token| !token.is_empty());
+        }
+    }
+    pub async fn sign_out(&mut self) -> Result<(), ClientError> {
+        Self::execute::<()>(self.request(Method::POST, "/auth/sign-out")).await?;
+        self.access_token = None;
+        self.refresh_token = None;
+        Ok(())
+    }
+
+    pub async fn user_self(&self) -> Result<UserResponse, ClientError> {
+        Self::data(self.request(Method::GET, "/user/self")).await
+    }
+    pub async fn user_settings(&self) -> Result<UserSettings, ClientError> {
+        Self::data(self.request(Method::GET, "/user/settings")).await
+    }
+    pub async fn user_settings_update(&self, body: &UserSettings) -> Result<UserSettings, ClientError> {
+        Self::data(self.request(Method::PUT, "/user/settings").json(body)).await
+    }
+    pub async fn user_detail(&self, user_id: Uuid) -> Result<UserDetail, ClientError> {
+        Self::data(self.request(Method::GET, &format!("/user/{}", user_id))).await
+    }
+    pub async fn user_by_username(&self, username: &str) -> Result<UserDetail, ClientError> {
+        Self::data(self.request(Method::GET, &format!("/user/by-username/{}", username))).await
+    }
+    pub async fn user_batch(&self, body: &BatchRequest) -> Result<BatchData<UserSummary>, ClientError> {
+        Self::data(self.request(Method::POST, "/user/batch").json(body)).await
+    }
+    pub async fn user_update(&self, user_id: Uuid, body: &UserUpdateRequest, expected_version: Option<DateTime<Utc>>) -> Result<UserResponse, ClientError> {
+        Self::data(Self::if_match(self.request(Method::PUT, &format!("/user/{}", user_id)), expected_version).json(body)).await
+    }
+    pub async fn user_patch(&self, user_id: Uuid, body: &UserPatchRequest, expected_version: Option<DateTime<Utc>>) -> Result<UserResponse, ClientError> {
+        Self::data(Self::if_match(self.request(Method::PATCH, &format!("/user/{}", user_id)), expected_version).json(body)).await
+    }
+    pub async fn user_follow_unfollow(&self, user_id: Uuid) -> Result<FollowUnfollowResponse, ClientError> {
+        Self::data(self.request(Method::POST, &format!("/user/{}/follow", user_id))).await
+    }
+    pub async fn user_feeds(&self, params: &UserFeedParams) -> Result<PaginatedData<UserFeeds>, ClientError> {
+        Self::data(self.request(Method::GET, "/user/feed").query(params)).await
+    }
+    pub async fn user_activity(&self, user_id: Uuid, params: &UserActivityParams) -> Result<PaginatedData<ActivityItem>, ClientError> {
+        Self::data(self.request(Method::GET, &format!("/user/{}/activity", user_id)).query(params)).await
+    }
+    pub async fn user_search(&self, params: &UserSearchParams) -> Result<Vec<UserSearchResult>, ClientError> {
+        Self::data(self.request(Method::GET, "/user/search").query(params)).await
+    }
+    pub async fn user_suggestions(&self, params: &UserSuggestionParams) -> Result<PaginatedData<UserSuggestion>, ClientError> {
+        Self::data(self.request(Method::GET, "/user/suggestions").query(params)).await
+    }
+
+    pub async fn post_create(&self, body: &PostRequest) -> Result<Post, ClientError> {
+        Self::data(self.request(Method::POST, "/post").json(body)).await
+    }
+    pub async fn post_detail(&self, post_id: Uuid) -> Result<PostDetail, ClientError> {
+        Self::data(self.request(Method::GET, &format!("/post/{}", post_id))).await
+    }
+    pub async fn post_batch(&self, body: &BatchRequest) -> Result<BatchData<Post>, ClientError> {
+        Self::data(self.request(Method::POST, "/post/batch").json(body)).await
+    }
+    pub async fn post_list_by_user(&self, user_id: Uuid) -> Result<PostListByUser, ClientError> {
+        Self::data(self.request(Method::GET, &format!("/post/user/{}", user_id))).await
+    }
+    pub async fn post_update(&self, post_id: Uuid, body: &PostRequest, expected_version: Option<DateTime<Utc>>) -> Result<Post, ClientError> {
+        Self::data(Self::if_match(self.request(Method::PUT, &format!("/post/{}", post_id)), expected_version).json(body)).await
+    }
+    pub async fn post_patch(&self, post_id: Uuid, body: &PostPatchRequest, expected_version: Option<DateTime<Utc>>) -> Result<Post, ClientError> {
+        Self::data(Self::if_match(self.request(Method::PATCH, &format!("/post/{}", post_id)), expected_version).json(body)).await
+    }
+    pub async fn post_delete(&self, post_id: Uuid) -> Result<(), ClientError> {
+        Self::execute::<()>(self.request(Method::DELETE, &format!("/post/{}", post_id))).await?;
+        Ok(())
+    }
+    pub async fn post_view(&self, post_id: Uuid) -> Result<(), ClientError> {
+        Self::execute::<()>(self.request(Method::POST, &format!("/post/{}/view", post_id))).await?;
+        Ok(())
+    }
+    pub async fn post_collaborators(&self, post_id: Uuid) -> Result<Vec<PostCollaborator>, ClientError> {
+        Self::data(self.request(Method::GET, &format!("/post/{}/collaborators", post_id))).await
+    }
+    pub async fn post_collaborator_add(&self, post_id: Uuid, body: &PostCollaboratorRequest) -> Result<PostCollaborator, ClientError> {
+        Self::data(self.request(Method::POST, &format!("/post/{}/collaborators", post_id)).json(body)).await
+    }
+    pub async fn post_collaborator_remove(&self, post_id: Uuid, user_id: Uuid) -> Result<(), ClientError> {
+        Self::execute::<()>(self.request(Method::DELETE, &format!("/post/{}/collaborators/{}", post_id, user_id))).await?;
+        Ok(())
+    }
+
+    pub async fn comment_create(&self, post_id: Uuid, body: &CommentRequest) -> Result<Comment, ClientError> {
+        Self::data(self.request(Method::POST, &format!("/comment/{}", post_id)).json(body)).await
+    }
+    pub async fn comment_detail(&self, post_id: Uuid, comment_id: Uuid) -> Result<CommentDetail, ClientError> {
+        Self::data(self.request(Method::GET, &format!("/comment/{}/{}", post_id, comment_id))).await
+    }
+    pub async fn comment_list_by_post(&self, post_id: Uuid) -> Result<CommentsByPost, ClientError> {
+        Self::data(self.request(Method::GET, &format!("/comment/{}", post_id))).await
+    }
+    pub async fn comment_update(&self, comment_id: Uuid, body: &CommentRequest, expected_version: Option<DateTime<Utc>>) -> Result<Comment, ClientError> {
+        Self::data(Self::if_match(self.request(Method::PUT, &format!("/comment/{}/update", comment_id)), expected_version).json(body)).await
+    }
+    pub async fn comment_delete(&self, comment_id: Uuid) -> Result<(), ClientError> {
+        Self::execute::<()>(self.request(Method::DELETE, &format!("/comment/{}/delete", comment_id))).await?;
+        Ok(())
+    }
+
+    pub async fn organization_create(&self, body: &OrganizationRequest) -> Result<Organization, ClientError> {
+        Self::data(self.request(Method::POST, "/organization").json(body)).await
+    }
+    pub async fn organization_detail(&self, organization_id: Uuid) -> Result<OrganizationDetail, ClientError> {
+        Self::data(self.request(Method::GET, &format!("/organization/{}", organization_id))).await
+    }
+    pub async fn organization_update(&self, organization_id: Uuid, body: &OrganizationUpdateRequest) -> Result<Organization, ClientError> {
+        Self::data(self.request(Method::PUT, &format!("/organization/{}", organization_id)).json(body)).await
+    }
+    pub async fn organization_delete(&self, organization_id: Uuid) -> Result<(), ClientError> {
+        Self::execute::<()>(self.request(Method::DELETE, &format!("/organization/{}", organization_id))).await?;
+        Ok(())
+    }
+    pub async fn organization_members(&self, organization_id: Uuid) -> Result<Vec<OrganizationMember>, ClientError> {
+        Self::data(self.request(Method::GET, &format!("/organization/{}/members", organization_id))).await
+    }
+    pub async fn organization_member_update(&self, organization_id: Uuid, user_id: Uuid, body: &OrganizationMemberRequest) -> Result<OrganizationMember, ClientError> {
+        Self::data(self.request(Method::PUT, &format!("/organization/{}/members/{}", organization_id, user_id)).json(body)).await
+    }
+    pub async fn organization_member_remove(&self, organization_id: Uuid, user_id: Uuid) -> Result<(), ClientError> {
+        Self::execute::<()>(self.request(Method::DELETE, &format!("/organization/{}/members/{}", organization_id, user_id))).await?;
+        Ok(())
+    }
+    pub async fn organization_leave(&self, organization_id: Uuid) -> Result<(), ClientError> {
+        Self::execute::<()>(self.request(Method::POST, &format!("/organization/{}/leave", organization_id))).await?;
+        Ok(())
+    }
+    pub async fn organization_invite(&self, organization_id: Uuid, body: &OrganizationInvitationRequest) -> Result<OrganizationInvitation, ClientError> {
+        Self::data(self.request(Method::POST, &format!("/organization/{}/invitations", organization_id)).json(body)).await
+    }
+    pub async fn organization_invitation_revoke(&self, organization_id: Uuid, user_id: Uuid) -> Result<(), ClientError> {
+        Self::execute::<()>(self.request(Method::DELETE, &format!("/organization/{}/invitations/{}", organization_id, user_id))).await?;
+        Ok(())
+    }
+    pub async fn organization_invitations(&self) -> Result<Vec<OrganizationInvitation>, ClientError> {
+        Self::data(self.request(Method::GET, "/organization/invitations")).await
+    }
+    pub async fn organization_invitation_accept(&self, organization_id: Uuid) -> Result<OrganizationMember, ClientError> {
+        Self::data(self.request(Method::POST, &format!("/organization/invitations/{}/accept", organization_id))).await
+    }
+    pub async fn organization_invitation_decline(&self, organization_id: Uuid) -> Result<(), ClientError> {
+        Self::execute::<()>(self.request(Method::POST, &format!("/organization/invitations/{}/decline", organization_id))).await?;
+        Ok(())
+    }
+    pub async fn organization_follow(&self, organization_id: Uuid) -> Result<(), ClientError> {
+        Self::execute::<()>(self.request(Method::POST, &format!("/organization/{}/follow", organization_id))).await?;
+        Ok(())
+    }
+    pub async fn organization_unfollow(&self, organization_id: Uuid) -> Result<(), ClientError> {
+        Self::execute::<()>(self.request(Method::DELETE, &format!("/organization/{}/follow", organization_id))).await?;
+        Ok(())
+    }
+    pub async fn organization_usage(&self, organization_id: Uuid) -> Result<OrganizationUsageResponse, ClientError> {
+        Self::data(self.request(Method::GET, &format!("/organization/{}/usage", organization_id))).await
+    }
+
+    /// Events for the analytics sink, `accepted` is 0 when the user turned analytics off.
+    pub async fn analytics_events(&self, body: &AnalyticsBatchRequest) -> Result<AnalyticsBatchResponse, ClientError> {
+        Self::data(self.request(Method::POST, "/analytics/events").json(body)).await
+    }
+    /// The announcements to show now, those for the user's role included when signed in.
+    pub async fn active_announcements(&self) -> Result<Vec<ActiveAnnouncement>, ClientError> {
+        Self::data(self.request(Method::GET, "/announcements/active")).await
+    }
+    /// The terms of service version in force, `X-Tos-Required` on any response names the one the
+    /// user still has to accept.
+    pub async fn current_tos(&self) -> Result<TosVersion, ClientError> {
+        Self::data(self.request(Method::GET, "/tos/current")).await
+    }
+    pub async fn accept_tos(&self, body: &TosAcceptRequest) -> Result<TosAcceptance, ClientError> {
+        Self::data(self.request(Method::POST, "/user/accept-tos").json(body)).await
+    }
+
+    /// The top matches of every group, for `params` without a `kind`.
+    pub async fn search(&self, params: &SearchParams) -> Result<SearchResults, ClientError> {
+        Self::data(self.request(Method::GET, "/search").query(params)).await
+    }
+    /// A page of the group `params.kind` names, `UserSearchResult`, `PostSearchResult` or
+    /// `TagSearchResult` items.
+    pub async fn search_group<T: DeserializeOwned>(&self, params: &SearchParams) -> Result<PaginatedData<T>, ClientError> {
+        Self::data(self.request(Method::GET, "/search").query(params)).await
+    }
+}
\ No newline at end of file
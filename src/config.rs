@@ -1,60 +1,807 @@
-use std::env::var;
+use std::{collections::HashMap, env::vars, fmt::Display, fs, str::FromStr};
+use axum::http::HeaderValue;
+use jsonwebtoken::Algorithm;
+use crate::{modules::{audit_log::sink::AuditSink, data_export::storage::ExportStorage}, utils::{captcha::CAPTCHA_ENDPOINTS, error_sink::ErrorSink, jwt::JwtKeys}};
 
 #[derive(Clone)]
 pub struct Config {
     pub port: u16,
+    pub tls_cert_path: Option<String>,
+    pub tls_key_path: Option<String>,
+    pub http_redirect_port: Option<u16>,
     pub database_url: String,
     pub frontend_url: String,
+    /// `FRONTEND_URL` as the origin CORS allows.
+    pub frontend_origin: HeaderValue,
     pub jwt_secret: String,
+    pub jwt_keys: JwtKeys,
     pub jwt_max_age: i64,
+    pub jwt_leeway: u64,
+    pub jwt_refresh_grace: u64,
     pub refresh_token_age: i64,
+    pub impersonation_token_age: i64,
+    pub password_hash_memory_kib: u32,
+    pub password_hash_iterations: u32,
+    pub password_hash_parallelism: u32,
     pub max_connections: u32,
     pub min_connections: u32,
     pub acquire_timeout: u64,
     pub idle_timeout: u64,
+    pub slow_query_threshold: u64,
+    pub db_admission_timeout: u64,
+    pub http_log_level: String,
+    pub http_log_routes: Vec<String>,
+    pub http_log_body_sample_percent: u32,
+    pub http_log_body_max_bytes: usize,
     pub auth_basic_username: String,
     pub auth_basic_password: String,
     pub redis_url: String,
     pub redis_db: u32,
+    pub redis_command_timeout: u64,
+    pub redis_breaker_threshold: u32,
+    pub redis_breaker_cooldown: u64,
+    pub cache_compression_min_size: usize,
     pub rate_limiter_max: u32,
     pub rate_limiter_duration: i64,
+    pub rate_limiter_dry_run: bool,
+    pub rate_limiter_fail_open: bool,
+    pub rate_limiter_degraded_latency: u64,
+    pub rate_limiter_degraded_pool_wait: u64,
+    pub rate_limiter_load_window: u64,
+    pub rate_limiter_degraded_percent: u32,
+    pub availability_rate_limit_max: u32,
+    pub availability_rate_limit_window: i64,
+    pub availability_min_response_ms: u64,
+    pub conceal_accounts: bool,
+    pub account_disclosure_min_response_ms: u64,
+    pub email_rate_limit_max: u32,
+    pub email_rate_limit_window: i64,
+    pub sign_in_free_attempts: u32,
+    pub sign_in_failure_window: i64,
+    pub sign_in_delay_base: u64,
+    pub sign_in_delay_max: u64,
+    pub audit_sink: String,
+    pub audit_sink_url: Option<String>,
+    pub audit_s3_bucket: Option<String>,
+    pub audit_s3_region: String,
+    pub audit_s3_access_key: Option<String>,
+    pub audit_s3_secret_key: Option<String>,
+    pub audit_export_interval: u64,
+    pub error_sink: String,
+    pub error_sink_dsn: Option<String>,
+    pub captcha_provider: String,
+    pub captcha_secret: Option<String>,
+    pub captcha_verify_url: Option<String>,
+    pub captcha_endpoints: Vec<String>,
+    pub audit_export_batch_size: i64,
+    pub aggregate_refresh_interval: u64,
+    pub post_view_flush_interval: u64,
+    pub organization_monthly_request_quota: i64,
+    pub organization_usage_flush_interval: u64,
+    pub data_regions: Vec<String>,
+    pub email_digest_interval: u64,
+    pub email_digest_batch_size: i64,
+    pub email_digest_batch_pause: u64,
+    pub analytics_sink: String,
+    pub analytics_flush_interval: u64,
+    pub retention_interval: u64,
+    pub retention_batch_size: i64,
+    pub retention_deleted_comment_days: u32,
+    pub retention_audit_log_days: u32,
+    pub retention_analytics_event_days: u32,
+    pub export_storage: String,
+    pub export_storage_path: String,
+    pub export_s3_endpoint: Option<String>,
+    pub export_s3_bucket: Option<String>,
+    pub export_s3_region: String,
+    pub export_s3_access_key: Option<String>,
+    pub export_s3_secret_key: Option<String>,
+    pub export_interval: u64,
+    pub feed_import_interval: u64,
+    pub feed_import_fetch_interval: u64,
+    pub feed_import_batch_size: i64,
+    pub feed_import_max_sources: i64,
+    pub feed_import_max_entries: usize,
+    pub feed_import_max_bytes: usize,
+    pub feed_import_private_hosts: bool,
+    pub leaderboard_cache_ttl: u64,
+    pub feed_cache_ttl: u64,
+    pub post_cache_ttl: u64,
+    pub default_page_size: usize,
+    pub max_page_size: usize,
+    pub embed_token_age: i64,
+    pub signed_url_age: i64,
+    pub avatar_cache_ttl: u64,
+    pub avatar_max_bytes: usize,
+    pub avatar_private_hosts: bool,
+    pub content_moderation: String,
+    pub moderation_wordlist: Vec<String>,
+    pub moderation_api_url: Option<String>,
+    pub moderation_api_key: Option<String>,
+    pub moderation_report: bool,
+    pub embed_frame_ancestors: String,
+    pub syndication_limit: i64,
+    pub syndication_cache_ttl: u64,
+    pub user_cache_ttl: u64,
+    pub permission_cache_ttl: u64,
+    pub permission_refresh_ahead: u64,
+    pub feature_flag_cache_ttl: u64,
+    pub announcement_cache_ttl: u64,
+    pub tos_cache_ttl: u64,
+    pub stripe_webhook_secret: Option<String>,
+    pub stripe_webhook_tolerance: i64,
+    pub email_webhook_secret: Option<String>,
+    pub email_webhook_tolerance: i64,
+    pub waitlist_enabled: bool,
+    pub invite_only: bool,
+    pub invitation_token_age: i64,
+    pub action_token_pepper: String,
+    pub verify_link_redirect: bool,
+    pub comment_soft_delete: bool,
+    pub webhook_delivery_interval: u64,
+    pub webhook_delivery_batch_size: i64,
+    pub webhook_timeout: u64,
+    pub webhook_max_attempts: i32,
+    pub webhook_retry_backoff: i64,
+    pub mail_timeout: u64,
+    pub mail_max_attempts: u32,
+    pub mail_retry_backoff: u64,
+    pub mail_outbox_interval: u64,
+    pub mail_outbox_max_attempts: i32,
+    pub mail_outbox_backoff: i64,
+    pub event_stream: String,
+    pub event_stream_url: Option<String>,
+    pub event_stream_topic: String,
+    pub event_stream_buffer: usize,
+    pub seed_admin_name: String,
+    pub seed_admin_email: Option<String>,
+    pub seed_admin_password: Option<String>,
+}
+
+/// Every missing or malformed setting found while loading, so one failed start lists all of them.
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid configuration:\n  - {}", .0.join("\n  - "))]
+pub struct ConfigError(pub Vec<String>);
+
+/// Settings are read from the environment first and fall back to the config file (`CONFIG_FILE`,
+/// default `config.toml`). Its keys are the lowercase variable names, e.g. `jwt_max_age = 3600`;
+/// a `[jwt]` table holding `max_age = 3600` means the same, and arrays become comma-separated lists.
+struct ConfigSource {
+    env: HashMap<String, String>,
+    file: HashMap<String, String>,
+    problems: Vec<String>,
+}
+
+impl ConfigSource {
+    fn load() -> Self {
+        let mut source = Self {
+            env: vars().collect(),
+            file: HashMap::new(),
+            problems: Vec::new(),
+        };
+        let (path, explicit) = match source.env.get("CONFIG_FILE") {
+            Some(path) => (path.clone(), true),
+            None => ("config.toml".to_string(), false),
+        };
+        match fs::read_to_string(&path) {
+            Ok(content) => source.read_file(&path, &content),
+            Err(e) if explicit => source.problems.push(format!("CONFIG_FILE {} cannot be read: {}", path, e)),
+            Err(_) => {}
+        }
+        source
+    }
+    fn read_file(&mut self, path: &str, content: &str) {
+        match content.parse::<toml::Table>() {
+            Ok(table) => self.read_table("", table),
+            Err(e) => self.problems.push(format!("{}: {}", path, e)),
+        }
+    }
+    fn read_table(&mut self, prefix: &str, table: toml::Table) {
+        for (key, value) in table {
+            let key = format!("{}{}", prefix, key.to_lowercase());
+            let value = match value {
+                toml::Value::Table(table) => {
+                    self.read_table(&format!("{}_", key), table);
+                    continue;
+                }
+                toml::Value::String(value) => value,
+                toml::Value::Array(values) => values
+                    .into_iter()
+                    .map(|value| match value {
+                        toml::Value::String(value) => value,
+                        other => other.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(","),
+                other => other.to_string(),
+            };
+            self.file.insert(key, value);
+        }
+    }
+    fn lookup(&self, key: &str) -> Option<String> {
+        self.env.get(key).or_else(|| self.file.get(&key.to_lowercase())).cloned()
+    }
+    fn parse<T>(&mut self, key: &str, value: &str) -> T
+    where
+        T: FromStr + Default,
+        T::Err: Display,
+    {
+        match value.trim().parse::<T>() {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                self.problems.push(format!("{} has an invalid value {:?}: {}", key, value, e));
+                T::default()
+            }
+        }
+    }
+    fn required<T>(&mut self, key: &str) -> T
+    where
+        T: FromStr + Default,
+        T::Err: Display,
+    {
+        match self.lookup(key).filter(|value| !value.is_empty()) {
+            Some(value) => self.parse(key, &value),
+            None => {
+                self.problems.push(format!("{} must be set", key));
+                T::default()
+            }
+        }
+    }
+    fn optional<T>(&mut self, key: &str, default: T) -> T
+    where
+        T: FromStr + Default,
+        T::Err: Display,
+    {
+        match self.lookup(key) {
+            Some(value) => self.parse(key, &value),
+            None => default,
+        }
+    }
+    fn optional_string(&self, key: &str) -> Option<String> {
+        self.lookup(key).filter(|value| !value.is_empty())
+    }
+    fn optional_parsed<T>(&mut self, key: &str) -> Option<T>
+    where
+        T: FromStr + Default,
+        T::Err: Display,
+    {
+        self.optional_string(key).map(|value| self.parse(key, &value))
+    }
+    fn choice(&mut self, key: &str, allowed: &[&str], default: &str) -> String {
+        let value = self.lookup(key).unwrap_or_else(|| default.to_string());
+        if !allowed.contains(&value.as_str()) {
+            self.problems.push(format!("{} must be one of {}, got {:?}", key, allowed.join(", "), value));
+        }
+        value
+    }
+    /// Access token keys from `JWT_ALGORITHM`, `JWT_PRIVATE_KEY_PATH`, `JWT_VERIFY_KEY_PATHS` (comma
+    /// separated) and `JWT_ACCEPT_HS256`. Falls back to the secret alone when they can't be loaded.
+    fn jwt_keys(&mut self, secret: &str) -> JwtKeys {
+        let algorithm = match self.choice("JWT_ALGORITHM", &["HS256", "RS256", "EdDSA"], "HS256").as_str() {
+            "RS256" => Algorithm::RS256,
+            "EdDSA" => Algorithm::EdDSA,
+            _ => Algorithm::HS256,
+        };
+        let private_key_path = self.optional_string("JWT_PRIVATE_KEY_PATH");
+        let verify_key_paths = self.optional_string("JWT_VERIFY_KEY_PATHS").unwrap_or_default();
+        let verify_key_paths: Vec<&str> = verify_key_paths.split(',').map(str::trim).filter(|path| !path.is_empty()).collect();
+        let accept_hs256 = self.choice("JWT_ACCEPT_HS256", &["deny", "allow"], "deny") == "allow";
+        match JwtKeys::load(algorithm, secret.as_bytes(), private_key_path.as_deref(), &verify_key_paths, accept_hs256) {
+            Ok(keys) => keys,
+            Err(problem) => {
+                self.problems.push(problem);
+                JwtKeys::hs256(secret.as_bytes())
+            }
+        }
+    }
+    /// Cross-field rule, skipped when one of `keys` already failed so a bad value is reported once.
+    fn check(&mut self, keys: &[&str], valid: bool, problem: &str) {
+        let already_failed = keys.iter().any(|key| {
+            self.problems.iter().any(|reported| reported.starts_with(&format!("{} ", key)))
+        });
+        if !valid && !already_failed {
+            self.problems.push(problem.to_string());
+        }
+    }
 }
 
 impl Config {
-    pub fn init() -> Self {
-        let port = var("PORT").expect("PORT must be set");
-        let database_url = var("DATABASE_URL").expect("DATABASE_URL must be set");
-        let frontend_url = var("FRONTEND_URL").expect("FRONTEND_URL must be set");
-        let jwt_secret = var("JWT_SECRET_KEY").expect("JWT_SECRET_KEY must be set");
-        let jwt_max_age = var("JWT_MAX_AGE").expect("JWT_MAX_AGE must be set");
-        let refresh_token_age = var("REFRESH_TOKEN_AGE").expect("REFRESH_TOKEN_AGE must be set");
-        let max_connections = var("MAX_CONNECTIONS").expect("MAX_CONNECTIONS must be set");
-        let min_connections = var("MIN_CONNECTIONS").expect("MIN_CONNECTIONS must be set");
-        let acquire_timeout = var("ACQUIRE_TIMEOUT").expect("ACQUIRE_TIMEOUT must be set");
-        let idle_timeout = var("IDLE_TIMEOUT").expect("IDLE_TIMEOUT must be set");
-        let auth_basic_username = var("AUTH_BASIC_USERNAME").expect("AUTH_BASIC_USERNAME must be set");
-        let auth_basic_password = var("AUTH_BASIC_PASSWORD").expect("AUTH_BASIC_PASSWORD must be set");
-        let redis_url = var("REDIS_URL").expect("REDIS_URL must be set");
-        let redis_db = var("REDIS_DB").expect("REDIS_DB must be set");
-        let rate_limiter_max = var("RATE_LIMITER_MAX").expect("RATE_LIMITER_MAX must be set");
-        let rate_limiter_duration = var("RATE_LIMITER_DURATION").expect("RATE_LIMITER_DURATION must be set");
-        Self {
-            port: port.parse::<u16>().unwrap(),
-            database_url,
+    pub fn init() -> Result<Self, ConfigError> {
+        Self::from_source(ConfigSource::load())
+    }
+    /// Builds the configuration from `(VARIABLE, value)` pairs only, ignoring the environment and the
+    /// config file, with the same defaults and checks as `init`.
+    pub fn from_values(values: &[(&str, &str)]) -> Result<Self, ConfigError> {
+        Self::from_source(ConfigSource {
+            env: HashMap::new(),
+            file: values.iter().map(|(key, value)| (key.to_lowercase(), value.to_string())).collect(),
+            problems: Vec::new(),
+        })
+    }
+    fn from_source(mut source: ConfigSource) -> Result<Self, ConfigError> {
+        let jwt_secret: String = source.required("JWT_SECRET_KEY");
+        let frontend_url: String = source.required("FRONTEND_URL");
+        let frontend_origin = HeaderValue::from_str(&frontend_url);
+        source.check(&["FRONTEND_URL"], frontend_origin.is_ok(), "FRONTEND_URL must be usable as an HTTP header value");
+        let config = Self {
+            port: source.optional("PORT", 4000),
+            tls_cert_path: source.optional_string("TLS_CERT_PATH"),
+            tls_key_path: source.optional_string("TLS_KEY_PATH"),
+            http_redirect_port: source.optional_parsed("HTTP_REDIRECT_PORT"),
+            database_url: source.required("DATABASE_URL"),
+            frontend_origin: frontend_origin.unwrap_or(HeaderValue::from_static("")),
             frontend_url,
+            jwt_keys: source.jwt_keys(&jwt_secret),
             jwt_secret,
-            jwt_max_age: jwt_max_age.parse::<i64>().unwrap(),
-            refresh_token_age: refresh_token_age.parse::<i64>().unwrap(),
-            max_connections: max_connections.parse::<u32>().unwrap(),
-            min_connections: min_connections.parse::<u32>().unwrap(),
-            acquire_timeout: acquire_timeout.parse::<u64>().unwrap(),
-            idle_timeout: idle_timeout.parse::<u64>().unwrap(),
-            auth_basic_username,
-            auth_basic_password,
-            redis_url,
-            redis_db: redis_db.parse::<u32>().unwrap(),
-            rate_limiter_max: rate_limiter_max.parse::<u32>().unwrap(),
-            rate_limiter_duration: rate_limiter_duration.parse::<i64>().unwrap(),
-        }
-    }
-}
\ No newline at end of file
+            jwt_max_age: source.optional("JWT_MAX_AGE", 3600),
+            jwt_leeway: source.optional("JWT_LEEWAY", 30),
+            jwt_refresh_grace: source.optional("JWT_REFRESH_GRACE", 300),
+            refresh_token_age: source.optional("REFRESH_TOKEN_AGE", 7),
+            impersonation_token_age: source.optional("IMPERSONATION_TOKEN_AGE", 900),
+            password_hash_memory_kib: source.optional("PASSWORD_HASH_MEMORY_KIB", 19456),
+            password_hash_iterations: source.optional("PASSWORD_HASH_ITERATIONS", 2),
+            password_hash_parallelism: source.optional("PASSWORD_HASH_PARALLELISM", 1),
+            max_connections: source.optional("MAX_CONNECTIONS", 10),
+            min_connections: source.optional("MIN_CONNECTIONS", 5),
+            acquire_timeout: source.optional("ACQUIRE_TIMEOUT", 5),
+            idle_timeout: source.optional("IDLE_TIMEOUT", 60),
+            slow_query_threshold: source.optional("SLOW_QUERY_THRESHOLD_MS", 500),
+            db_admission_timeout: source.optional("DB_ADMISSION_TIMEOUT_MS", 250),
+            http_log_level: source.choice("HTTP_LOG_LEVEL", &["off", "info", "debug"], "off"),
+            http_log_routes: source.optional_string("HTTP_LOG_ROUTES").unwrap_or_default()
+                .split(',').map(str::trim).filter(|route| !route.is_empty()).map(String::from).collect(),
+            http_log_body_sample_percent: source.optional("HTTP_LOG_BODY_SAMPLE_PERCENT", 10),
+            http_log_body_max_bytes: source.optional("HTTP_LOG_BODY_MAX_BYTES", 2048),
+            auth_basic_username: source.required("AUTH_BASIC_USERNAME"),
+            auth_basic_password: source.required("AUTH_BASIC_PASSWORD"),
+            redis_url: source.required("REDIS_URL"),
+            redis_db: source.optional("REDIS_DB", 0),
+            redis_command_timeout: source.optional("REDIS_COMMAND_TIMEOUT_MS", 250),
+            redis_breaker_threshold: source.optional("REDIS_BREAKER_THRESHOLD", 5),
+            redis_breaker_cooldown: source.optional("REDIS_BREAKER_COOLDOWN_MS", 5000),
+            cache_compression_min_size: source.optional("CACHE_COMPRESSION_MIN_SIZE", 1024),
+            rate_limiter_max: source.optional("RATE_LIMITER_MAX", 5),
+            rate_limiter_duration: source.optional("RATE_LIMITER_DURATION", 1),
+            rate_limiter_dry_run: source.choice("RATE_LIMITER_MODE", &["enforce", "dry-run"], "enforce") == "dry-run",
+            rate_limiter_fail_open: source.choice("RATE_LIMITER_ON_REDIS_ERROR", &["allow", "reject"], "allow") == "allow",
+            rate_limiter_degraded_latency: source.optional("RATE_LIMITER_DEGRADED_LATENCY_MS", 0),
+            rate_limiter_degraded_pool_wait: source.optional("RATE_LIMITER_DEGRADED_POOL_WAIT_MS", 0),
+            rate_limiter_load_window: source.optional("RATE_LIMITER_LOAD_WINDOW", 30),
+            rate_limiter_degraded_percent: source.optional("RATE_LIMITER_DEGRADED_PERCENT", 25),
+            availability_rate_limit_max: source.optional("AVAILABILITY_RATE_LIMIT_MAX", 20),
+            availability_rate_limit_window: source.optional("AVAILABILITY_RATE_LIMIT_WINDOW", 60),
+            availability_min_response_ms: source.optional("AVAILABILITY_MIN_RESPONSE_MS", 150),
+            conceal_accounts: source.choice("ACCOUNT_DISCLOSURE", &["reveal", "conceal"], "reveal") == "conceal",
+            account_disclosure_min_response_ms: source.optional("ACCOUNT_DISCLOSURE_MIN_RESPONSE_MS", 500),
+            email_rate_limit_max: source.optional("EMAIL_RATE_LIMIT_MAX", 3),
+            email_rate_limit_window: source.optional("EMAIL_RATE_LIMIT_WINDOW", 900),
+            sign_in_free_attempts: source.optional("SIGN_IN_FREE_ATTEMPTS", 5),
+            sign_in_failure_window: source.optional("SIGN_IN_FAILURE_WINDOW", 900),
+            sign_in_delay_base: source.optional("SIGN_IN_DELAY_BASE", 1),
+            sign_in_delay_max: source.optional("SIGN_IN_DELAY_MAX", 900),
+            audit_sink: source.choice("AUDIT_SINK", &["none", "syslog", "http", "s3"], "none"),
+            audit_sink_url: source.optional_string("AUDIT_SINK_URL"),
+            audit_s3_bucket: source.optional_string("AUDIT_S3_BUCKET"),
+            audit_s3_region: source.optional("AUDIT_S3_REGION", "us-east-1".to_string()),
+            audit_s3_access_key: source.optional_string("AUDIT_S3_ACCESS_KEY"),
+            audit_s3_secret_key: source.optional_string("AUDIT_S3_SECRET_KEY"),
+            audit_export_interval: source.optional("AUDIT_EXPORT_INTERVAL", 60),
+            error_sink: source.choice("ERROR_SINK", &["none", "sentry", "http"], "none"),
+            error_sink_dsn: source.optional_string("ERROR_SINK_DSN"),
+            captcha_provider: source.choice("CAPTCHA_PROVIDER", &["none", "hcaptcha", "recaptcha"], "none"),
+            captcha_secret: source.optional_string("CAPTCHA_SECRET"),
+            captcha_verify_url: source.optional_string("CAPTCHA_VERIFY_URL"),
+            captcha_endpoints: source.optional_string("CAPTCHA_ENDPOINTS").unwrap_or_else(|| CAPTCHA_ENDPOINTS.join(","))
+                .split(',').map(str::trim).filter(|endpoint| !endpoint.is_empty()).map(String::from).collect(),
+            audit_export_batch_size: source.optional("AUDIT_EXPORT_BATCH_SIZE", 100),
+            aggregate_refresh_interval: source.optional("AGGREGATE_REFRESH_INTERVAL", 300),
+            post_view_flush_interval: source.optional("POST_VIEW_FLUSH_INTERVAL", 60),
+            organization_monthly_request_quota: source.optional("ORGANIZATION_MONTHLY_REQUEST_QUOTA", 0),
+            organization_usage_flush_interval: source.optional("ORGANIZATION_USAGE_FLUSH_INTERVAL", 60),
+            data_regions: source.optional("DATA_REGIONS", "default".to_string())
+                .split(',').map(str::trim).filter(|region| !region.is_empty()).map(String::from).collect(),
+            email_digest_interval: source.optional("EMAIL_DIGEST_INTERVAL", 3600),
+            email_digest_batch_size: source.optional("EMAIL_DIGEST_BATCH_SIZE", 50),
+            email_digest_batch_pause: source.optional("EMAIL_DIGEST_BATCH_PAUSE", 10),
+            analytics_sink: source.choice("ANALYTICS_SINK", &["postgres", "events"], "postgres"),
+            analytics_flush_interval: source.optional("ANALYTICS_FLUSH_INTERVAL", 30),
+            retention_interval: source.optional("RETENTION_INTERVAL", 3600),
+            retention_batch_size: source.optional("RETENTION_BATCH_SIZE", 1000),
+            retention_deleted_comment_days: source.optional("RETENTION_DELETED_COMMENT_DAYS", 30),
+            retention_audit_log_days: source.optional("RETENTION_AUDIT_LOG_DAYS", 365),
+            retention_analytics_event_days: source.optional("RETENTION_ANALYTICS_EVENT_DAYS", 0),
+            export_storage: source.choice("EXPORT_STORAGE", &["local", "s3"], "local"),
+            export_storage_path: source.optional("EXPORT_STORAGE_PATH", "exports".to_string()),
+            export_s3_endpoint: source.optional_string("EXPORT_S3_ENDPOINT"),
+            export_s3_bucket: source.optional_string("EXPORT_S3_BUCKET"),
+            export_s3_region: source.optional("EXPORT_S3_REGION", "us-east-1".to_string()),
+            export_s3_access_key: source.optional_string("EXPORT_S3_ACCESS_KEY"),
+            export_s3_secret_key: source.optional_string("EXPORT_S3_SECRET_KEY"),
+            export_interval: source.optional("EXPORT_INTERVAL", 10),
+            feed_import_interval: source.optional("FEED_IMPORT_INTERVAL", 300),
+            feed_import_fetch_interval: source.optional("FEED_IMPORT_FETCH_INTERVAL", 3600),
+            feed_import_batch_size: source.optional("FEED_IMPORT_BATCH_SIZE", 20),
+            feed_import_max_sources: source.optional("FEED_IMPORT_MAX_SOURCES", 10),
+            feed_import_max_entries: source.optional("FEED_IMPORT_MAX_ENTRIES", 50),
+            feed_import_max_bytes: source.optional("FEED_IMPORT_MAX_BYTES", 2 * 1024 * 1024),
+            feed_import_private_hosts: source.choice("FEED_IMPORT_PRIVATE_HOSTS", &["deny", "allow"], "deny") == "allow",
+            leaderboard_cache_ttl: source.optional("LEADERBOARD_CACHE_TTL", 60),
+            feed_cache_ttl: source.optional("FEED_CACHE_TTL", 30),
+            post_cache_ttl: source.optional("POST_CACHE_TTL", 30),
+            default_page_size: source.optional("DEFAULT_PAGE_SIZE", 5),
+            max_page_size: source.optional("MAX_PAGE_SIZE", 100),
+            embed_token_age: source.optional("EMBED_TOKEN_AGE", 2592000),
+            signed_url_age: source.optional("SIGNED_URL_AGE", 600),
+            avatar_cache_ttl: source.optional("AVATAR_CACHE_TTL", 86400),
+            avatar_max_bytes: source.optional("AVATAR_MAX_BYTES", 5 * 1024 * 1024),
+            avatar_private_hosts: source.choice("AVATAR_PRIVATE_HOSTS", &["deny", "allow"], "deny") == "allow",
+            content_moderation: source.choice("CONTENT_MODERATION", &["none", "wordlist", "api"], "none"),
+            moderation_wordlist: source.optional_string("MODERATION_WORDLIST").unwrap_or_default()
+                .split(',').map(str::trim).filter(|word| !word.is_empty()).map(String::from).collect(),
+            moderation_api_url: source.optional_string("MODERATION_API_URL"),
+            moderation_api_key: source.optional_string("MODERATION_API_KEY"),
+            moderation_report: source.choice("MODERATION_ACTION", &["reject", "report"], "reject") == "report",
+            embed_frame_ancestors: source.optional("EMBED_FRAME_ANCESTORS", "*".to_string()),
+            syndication_limit: source.optional("SYNDICATION_LIMIT", 20),
+            syndication_cache_ttl: source.optional("SYNDICATION_CACHE_TTL", 300),
+            user_cache_ttl: source.optional("USER_CACHE_TTL", 300),
+            permission_cache_ttl: source.optional("PERMISSION_CACHE_TTL", 300),
+            permission_refresh_ahead: source.optional("PERMISSION_REFRESH_AHEAD", 60),
+            feature_flag_cache_ttl: source.optional("FEATURE_FLAG_CACHE_TTL", 60),
+            announcement_cache_ttl: source.optional("ANNOUNCEMENT_CACHE_TTL", 60),
+            tos_cache_ttl: source.optional("TOS_CACHE_TTL", 60),
+            stripe_webhook_secret: source.optional_string("STRIPE_WEBHOOK_SECRET"),
+            stripe_webhook_tolerance: source.optional("STRIPE_WEBHOOK_TOLERANCE", 300),
+            email_webhook_secret: source.optional_string("EMAIL_WEBHOOK_SECRET"),
+            email_webhook_tolerance: source.optional("EMAIL_WEBHOOK_TOLERANCE", 300),
+            waitlist_enabled: source.choice("SIGNUP_MODE", &["open", "waitlist"], "open") == "waitlist",
+            invite_only: source.choice("REGISTRATION_MODE", &["open", "invite_only"], "open") == "invite_only",
+            invitation_token_age: source.optional("INVITATION_TOKEN_AGE", 604800),
+            action_token_pepper: source.required("ACTION_TOKEN_PEPPER"),
+            verify_link_redirect: source.choice("VERIFY_LINK_MODE", &["page", "redirect"], "page") == "redirect",
+            comment_soft_delete: source.choice("COMMENT_DELETE_MODE", &["hard", "soft"], "hard") == "soft",
+            webhook_delivery_interval: source.optional("WEBHOOK_DELIVERY_INTERVAL", 5),
+            webhook_delivery_batch_size: source.optional("WEBHOOK_DELIVERY_BATCH_SIZE", 50),
+            webhook_timeout: source.optional("WEBHOOK_TIMEOUT", 10),
+            webhook_max_attempts: source.optional("WEBHOOK_MAX_ATTEMPTS", 8),
+            webhook_retry_backoff: source.optional("WEBHOOK_RETRY_BACKOFF", 30),
+            mail_timeout: source.optional("MAIL_TIMEOUT", 10),
+            mail_max_attempts: source.optional("MAIL_MAX_ATTEMPTS", 3),
+            mail_retry_backoff: source.optional("MAIL_RETRY_BACKOFF_MS", 250),
+            mail_outbox_interval: source.optional("MAIL_OUTBOX_INTERVAL", 30),
+            mail_outbox_max_attempts: source.optional("MAIL_OUTBOX_MAX_ATTEMPTS", 6),
+            mail_outbox_backoff: source.optional("MAIL_OUTBOX_BACKOFF", 60),
+            event_stream: source.choice("EVENT_STREAM", &["none", "kafka", "nats"], "none"),
+            event_stream_url: source.optional_string("EVENT_STREAM_URL"),
+            event_stream_topic: source.optional("EVENT_STREAM_TOPIC", "axum-restful-api.events".to_string()),
+            event_stream_buffer: source.optional("EVENT_STREAM_BUFFER", 1000),
+            seed_admin_name: source.optional("SEED_ADMIN_NAME", "Administrator".to_string()),
+            seed_admin_email: source.optional_string("SEED_ADMIN_EMAIL"),
+            seed_admin_password: source.optional_string("SEED_ADMIN_PASSWORD"),
+        };
+        source.check(
+            &["TLS_CERT_PATH", "TLS_KEY_PATH"],
+            config.tls_cert_path.is_some() == config.tls_key_path.is_some(),
+            "TLS_CERT_PATH and TLS_KEY_PATH must be set together",
+        );
+        source.check(
+            &["HTTP_REDIRECT_PORT"],
+            config.http_redirect_port.is_none() || config.tls_cert_path.is_some(),
+            "HTTP_REDIRECT_PORT needs TLS_CERT_PATH and TLS_KEY_PATH",
+        );
+        source.check(
+            &["PASSWORD_HASH_ITERATIONS", "PASSWORD_HASH_PARALLELISM"],
+            config.password_hash_iterations >= 1 && config.password_hash_parallelism >= 1,
+            "PASSWORD_HASH_ITERATIONS and PASSWORD_HASH_PARALLELISM must be at least 1",
+        );
+        source.check(
+            &["PASSWORD_HASH_MEMORY_KIB", "PASSWORD_HASH_PARALLELISM"],
+            config.password_hash_memory_kib >= 8 * config.password_hash_parallelism,
+            "PASSWORD_HASH_MEMORY_KIB must be at least 8 times PASSWORD_HASH_PARALLELISM",
+        );
+        source.check(
+            &["SYNDICATION_LIMIT"],
+            (1..=100).contains(&config.syndication_limit),
+            "SYNDICATION_LIMIT must be between 1 and 100",
+        );
+        source.check(
+            &["DEFAULT_PAGE_SIZE", "MAX_PAGE_SIZE"],
+            config.default_page_size >= 1 && config.default_page_size <= config.max_page_size,
+            "DEFAULT_PAGE_SIZE must be at least 1 and not greater than MAX_PAGE_SIZE",
+        );
+        source.check(
+            &["MIN_CONNECTIONS", "MAX_CONNECTIONS"],
+            config.min_connections <= config.max_connections,
+            "MIN_CONNECTIONS must not be greater than MAX_CONNECTIONS",
+        );
+        source.check(
+            &["DB_ADMISSION_TIMEOUT_MS", "ACQUIRE_TIMEOUT"],
+            config.db_admission_timeout < config.acquire_timeout * 1000,
+            "DB_ADMISSION_TIMEOUT_MS must be shorter than ACQUIRE_TIMEOUT",
+        );
+        source.check(
+            &["HTTP_LOG_BODY_SAMPLE_PERCENT"],
+            config.http_log_body_sample_percent <= 100,
+            "HTTP_LOG_BODY_SAMPLE_PERCENT must be between 0 and 100",
+        );
+        source.check(
+            &["RATE_LIMITER_LOAD_WINDOW"],
+            config.rate_limiter_load_window >= 1,
+            "RATE_LIMITER_LOAD_WINDOW must be at least 1",
+        );
+        source.check(
+            &["RATE_LIMITER_DEGRADED_PERCENT"],
+            (1..=100).contains(&config.rate_limiter_degraded_percent),
+            "RATE_LIMITER_DEGRADED_PERCENT must be between 1 and 100",
+        );
+        source.check(
+            &["PERMISSION_REFRESH_AHEAD", "PERMISSION_CACHE_TTL"],
+            config.permission_refresh_ahead < config.permission_cache_ttl,
+            "PERMISSION_REFRESH_AHEAD must be less than PERMISSION_CACHE_TTL",
+        );
+        source.check(
+            &["POST_VIEW_FLUSH_INTERVAL"],
+            config.post_view_flush_interval >= 1,
+            "POST_VIEW_FLUSH_INTERVAL must be at least 1",
+        );
+        source.check(
+            &["ORGANIZATION_MONTHLY_REQUEST_QUOTA"],
+            config.organization_monthly_request_quota >= 0,
+            "ORGANIZATION_MONTHLY_REQUEST_QUOTA must not be negative",
+        );
+        source.check(
+            &["ORGANIZATION_USAGE_FLUSH_INTERVAL"],
+            config.organization_usage_flush_interval >= 1,
+            "ORGANIZATION_USAGE_FLUSH_INTERVAL must be at least 1",
+        );
+        source.check(
+            &["DATA_REGIONS"],
+            !config.data_regions.is_empty() && config.data_regions.iter().all(|region| region.len() <= 50),
+            "DATA_REGIONS must name at least one region, each at most 50 characters",
+        );
+        source.check(
+            &["EMAIL_DIGEST_INTERVAL"],
+            config.email_digest_interval >= 1,
+            "EMAIL_DIGEST_INTERVAL must be at least 1",
+        );
+        source.check(
+            &["EMAIL_DIGEST_BATCH_SIZE"],
+            config.email_digest_batch_size >= 1,
+            "EMAIL_DIGEST_BATCH_SIZE must be at least 1",
+        );
+        source.check(
+            &["ANALYTICS_FLUSH_INTERVAL"],
+            config.analytics_flush_interval >= 1,
+            "ANALYTICS_FLUSH_INTERVAL must be at least 1",
+        );
+        source.check(
+            &["RETENTION_INTERVAL"],
+            config.retention_interval >= 1,
+            "RETENTION_INTERVAL must be at least 1",
+        );
+        source.check(
+            &["RETENTION_BATCH_SIZE"],
+            config.retention_batch_size >= 1,
+            "RETENTION_BATCH_SIZE must be at least 1",
+        );
+        source.check(
+            &["EXPORT_INTERVAL"],
+            config.export_interval >= 1,
+            "EXPORT_INTERVAL must be at least 1",
+        );
+        source.check(
+            &["EXPORT_STORAGE"],
+            config.export_storage != "s3" || ExportStorage::from_config(&config).is_some(),
+            "EXPORT_S3_ENDPOINT, EXPORT_S3_BUCKET, EXPORT_S3_ACCESS_KEY and EXPORT_S3_SECRET_KEY must be set when EXPORT_STORAGE is s3, the endpoint as an http(s) url",
+        );
+        source.check(
+            &["EXPORT_STORAGE", "SIGNED_URL_AGE"],
+            config.export_storage != "s3" || config.signed_url_age <= 604800,
+            "SIGNED_URL_AGE must be at most 604800 (7 days) when EXPORT_STORAGE is s3",
+        );
+        source.check(
+            &["FEED_IMPORT_INTERVAL", "FEED_IMPORT_FETCH_INTERVAL", "FEED_IMPORT_BATCH_SIZE"],
+            config.feed_import_interval >= 1 && config.feed_import_fetch_interval >= 60 && config.feed_import_batch_size >= 1,
+            "FEED_IMPORT_INTERVAL and FEED_IMPORT_BATCH_SIZE must be at least 1, FEED_IMPORT_FETCH_INTERVAL at least 60",
+        );
+        source.check(
+            &["AUDIT_SINK"],
+            config.audit_sink == "none" || AuditSink::from_config(&config).is_some(),
+            "AUDIT_SINK_URL must be set when AUDIT_SINK is not none, for s3 as the bucket's http(s) endpoint along with AUDIT_S3_BUCKET, AUDIT_S3_ACCESS_KEY and AUDIT_S3_SECRET_KEY",
+        );
+        source.check(
+            &["ERROR_SINK"],
+            config.error_sink == "none" || ErrorSink::from_config(&config).is_some(),
+            "ERROR_SINK_DSN must be set when ERROR_SINK is not none, as a https://<key>@<host>/<project id> DSN for sentry",
+        );
+        source.check(
+            &["CAPTCHA_PROVIDER"],
+            config.captcha_provider == "none" || config.captcha_secret.is_some(),
+            "CAPTCHA_SECRET must be set when CAPTCHA_PROVIDER is not none",
+        );
+        source.check(
+            &["CAPTCHA_ENDPOINTS"],
+            config.captcha_endpoints.iter().all(|endpoint| CAPTCHA_ENDPOINTS.contains(&endpoint.as_str())),
+            &format!("CAPTCHA_ENDPOINTS must only list {}", CAPTCHA_ENDPOINTS.join(", ")),
+        );
+        source.check(
+            &["CONTENT_MODERATION"],
+            match config.content_moderation.as_str() {
+                "wordlist" => !config.moderation_wordlist.is_empty(),
+                "api" => config.moderation_api_url.is_some(),
+                _ => true,
+            },
+            "CONTENT_MODERATION=wordlist needs MODERATION_WORDLIST, and CONTENT_MODERATION=api needs MODERATION_API_URL",
+        );
+        source.check(
+            &["EVENT_STREAM"],
+            config.event_stream == "none" || config.event_stream_url.is_some(),
+            "EVENT_STREAM_URL must be set when EVENT_STREAM is not none",
+        );
+        source.check(
+            &["EVENT_STREAM_BUFFER"],
+            config.event_stream_buffer >= 1,
+            "EVENT_STREAM_BUFFER must be at least 1",
+        );
+        source.check(
+            &["EMAIL_RATE_LIMIT_MAX", "EMAIL_RATE_LIMIT_WINDOW"],
+            config.email_rate_limit_max >= 1 && config.email_rate_limit_window >= 1,
+            "EMAIL_RATE_LIMIT_MAX and EMAIL_RATE_LIMIT_WINDOW must be at least 1",
+        );
+        source.check(
+            &["SIGN_IN_FAILURE_WINDOW", "SIGN_IN_DELAY_BASE", "SIGN_IN_DELAY_MAX"],
+            config.sign_in_failure_window >= 1 && config.sign_in_delay_base >= 1 && config.sign_in_delay_max >= config.sign_in_delay_base,
+            "SIGN_IN_FAILURE_WINDOW and SIGN_IN_DELAY_BASE must be at least 1, and SIGN_IN_DELAY_MAX at least SIGN_IN_DELAY_BASE",
+        );
+        source.check(
+            &["INVITATION_TOKEN_AGE"],
+            config.invitation_token_age >= 1,
+            "INVITATION_TOKEN_AGE must be at least 1",
+        );
+        source.check(
+            &["STRIPE_WEBHOOK_TOLERANCE", "EMAIL_WEBHOOK_TOLERANCE"],
+            config.stripe_webhook_tolerance >= 1 && config.email_webhook_tolerance >= 1,
+            "STRIPE_WEBHOOK_TOLERANCE and EMAIL_WEBHOOK_TOLERANCE must be at least 1",
+        );
+        source.check(
+            &["WEBHOOK_MAX_ATTEMPTS"],
+            config.webhook_max_attempts >= 1,
+            "WEBHOOK_MAX_ATTEMPTS must be at least 1",
+        );
+        source.check(
+            &["MAIL_MAX_ATTEMPTS"],
+            config.mail_max_attempts >= 1,
+            "MAIL_MAX_ATTEMPTS must be at least 1",
+        );
+        source.check(
+            &["MAIL_OUTBOX_MAX_ATTEMPTS"],
+            config.mail_outbox_max_attempts >= 1,
+            "MAIL_OUTBOX_MAX_ATTEMPTS must be at least 1",
+        );
+        source.check(
+            &["SEED_ADMIN_EMAIL", "SEED_ADMIN_PASSWORD"],
+            config.seed_admin_email.is_some() == config.seed_admin_password.is_some(),
+            "SEED_ADMIN_EMAIL and SEED_ADMIN_PASSWORD must be set together",
+        );
+        source.check(
+            &["SEED_ADMIN_PASSWORD"],
+            config.seed_admin_password.as_ref().is_none_or(|password| password.len() >= 6),
+            "SEED_ADMIN_PASSWORD must be at least 6 characters",
+        );
+        source.check(
+            &["SEED_ADMIN_NAME"],
+            (4..=50).contains(&config.seed_admin_name.chars().count()),
+            "SEED_ADMIN_NAME must be between 4 and 50 characters",
+        );
+        if source.problems.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError(source.problems))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use crate::test_utils::base_config_values;
+    use super::{Config, ConfigSource};
+
+    #[test]
+    fn webhook_tolerances_must_be_positive() {
+        for key in ["STRIPE_WEBHOOK_TOLERANCE", "EMAIL_WEBHOOK_TOLERANCE"] {
+            let mut values = base_config_values();
+            values.push((key, "0"));
+            let error = Config::from_values(&values).err().expect("a zero tolerance is refused");
+            assert!(error.0.iter().any(|problem| problem.contains(key)), "{:?}", error.0);
+        }
+    }
+
+    #[test]
+    fn a_frontend_url_that_is_no_header_value_is_reported() {
+        let mut values = base_config_values();
+        values.push(("FRONTEND_URL", "http://local\u{1}host:3000"));
+        let error = Config::from_values(&values).err().expect("the CORS origin can't be built");
+        assert_eq!(error.0, ["FRONTEND_URL must be usable as an HTTP header value"]);
+    }
+
+    fn file_source(content: &str) -> ConfigSource {
+        let mut source = ConfigSource {
+            env: HashMap::new(),
+            file: HashMap::new(),
+            problems: Vec::new(),
+        };
+        source.read_file("config.toml", content);
+        source
+    }
+
+    #[test]
+    fn the_config_file_is_read_as_toml() {
+        let source = file_source(
+            r#"
+            # local development
+            redis_url = 'redis://localhost:6379/' # the compose service
+            data_regions = ["eu", "us"]
+
+            [jwt]
+            max_age = 3600
+            "#,
+        );
+        assert!(source.problems.is_empty(), "{:?}", source.problems);
+        assert_eq!(source.lookup("REDIS_URL").as_deref(), Some("redis://localhost:6379/"));
+        assert_eq!(source.lookup("DATA_REGIONS").as_deref(), Some("eu,us"));
+        assert_eq!(source.lookup("JWT_MAX_AGE").as_deref(), Some("3600"));
+    }
+
+    #[test]
+    fn a_config_file_that_is_no_toml_is_reported() {
+        let source = file_source("port 4000");
+        assert_eq!(source.problems.len(), 1);
+        assert!(source.problems[0].starts_with("config.toml: "), "{:?}", source.problems);
+    }
+
+    #[test]
+    fn every_problem_is_reported_at_once() {
+        let values: Vec<_> = base_config_values()
+            .into_iter()
+            .filter(|(key, _)| *key != "DATABASE_URL" && *key != "REDIS_URL")
+            .chain([("PORT", "http"), ("JWT_MAX_AGE", "an hour")])
+            .collect();
+        let error = Config::from_values(&values).err().expect("the configuration is refused");
+        assert_eq!(error.0.len(), 4, "{:?}", error.0);
+        for key in ["DATABASE_URL", "REDIS_URL", "PORT", "JWT_MAX_AGE"] {
+            assert!(error.0.iter().any(|problem| problem.starts_with(key)), "{} missing from {:?}", key, error.0);
+        }
+    }
+
+    #[test]
+    fn port_is_read_and_defaults_to_4000() {
+        let mut values = base_config_values();
+        assert_eq!(Config::from_values(&values).unwrap().port, 4000);
+        values.push(("PORT", "8080"));
+        assert_eq!(Config::from_values(&values).unwrap().port, 8080);
+    }
+
+    #[test]
+    fn optional_settings_fall_back_to_their_defaults() {
+        let config = Config::from_values(&base_config_values()).unwrap();
+        assert_eq!(config.jwt_max_age, 3600);
+        assert_eq!(config.rate_limiter_max, 5);
+        assert_eq!(config.rate_limiter_duration, 1);
+        assert!(!config.rate_limiter_dry_run);
+        assert_eq!(config.data_regions, ["default"]);
+        assert_eq!(config.tls_cert_path, None);
+    }
+
+    #[test]
+    fn the_environment_wins_over_the_file() {
+        let mut source = file_source("port = 5000\njwt_max_age = 60");
+        for (key, value) in base_config_values() {
+            source.file.insert(key.to_lowercase(), value.to_string());
+        }
+        source.env.insert("PORT".to_string(), "6000".to_string());
+        let config = Config::from_source(source).unwrap();
+        assert_eq!(config.port, 6000);
+        assert_eq!(config.jwt_max_age, 60);
+    }
+}
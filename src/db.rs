@@ -1,12 +1,190 @@
-use sqlx::{Pool, Postgres};
+use std::{sync::Arc, time::{Duration, Instant}};
+use futures_util::stream::BoxStream;
+use log::warn;
+use sqlx::{FromRow, Pool, Postgres};
+use crate::{
+    modules::{
+        activity::model::ActivityRepository,
+        analytics::model::AnalyticsRepository,
+        announcement::model::AnnouncementRepository,
+        aggregate::model::AggregateRepository,
+        audit_log::model::AuditLogRepository,
+        comment::model::CommentRepository,
+        digest::model::DigestRepository,
+        email::model::EmailOutboxRepository,
+        feature_flag::model::FeatureFlagRepository,
+        velocity::model::VelocityLimitRepository,
+        search::model::SearchRepository,
+        invitation::model::InvitationRepository,
+        organization::model::OrganizationRepository,
+        permission::model::PermissionRepository,
+        post::model::PostRepository,
+        refresh_token::model::RefreshTokenRepository,
+        report::model::ReportRepository,
+        retention::model::RetentionRepository,
+        data_export::model::DataExportRepository,
+        feed_import::model::FeedImportRepository,
+        role::model::{RoleCache, RoleRepository},
+        session::model::SessionRepository,
+        tos::model::TosRepository,
+        user::model::UserRepository,
+        user_action_token::model::UserActionTokenRepository,
+        waitlist::model::WaitlistRepository,
+        webhook::model::WebhookRepository,
+    },
+    error::RepositoryError,
+    utils::metrics,
+};
+
+#[cfg(any(test, feature = "test-utils"))]
+pub mod memory;
 
 #[derive(Clone)]
 pub struct DBClient {
     pub pool: Pool<Postgres>,
+    pub(crate) roles: RoleCache,
+    slow_query_threshold: Duration,
 }
 
 impl DBClient {
     pub fn new(pool: Pool<Postgres>) -> Self {
-        Self { pool }
+        Self { pool, roles: RoleCache::default(), slow_query_threshold: Duration::from_millis(500) }
+    }
+    pub fn with_slow_query_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_query_threshold = threshold;
+        self
+    }
+    /// Starts timing a repository method, named `<module>.<method>`. The time is recorded when the
+    /// returned guard drops, so every return path and `?` is covered.
+    pub(crate) fn time_query(&self, method: &'static str) -> QueryTimer {
+        QueryTimer { method, started: Instant::now(), slow_threshold: self.slow_query_threshold }
+    }
+}
+
+/// Records how long a repository method took as `db_query_duration_seconds{method=...}` at
+/// `/api/metrics`, and logs a warning when it took longer than `SLOW_QUERY_THRESHOLD_MS`.
+pub struct QueryTimer {
+    method: &'static str,
+    started: Instant,
+    slow_threshold: Duration,
+}
+
+impl Drop for QueryTimer {
+    fn drop(&mut self) {
+        let elapsed = self.started.elapsed();
+        metrics::observe_duration("db_query_duration_seconds", &[("method", self.method)], elapsed);
+        if elapsed >= self.slow_threshold {
+            metrics::increment_counter("db_slow_queries_total", &[("method", self.method)]);
+            warn!("Slow query in {}: {} ms", self.method, elapsed.as_millis());
+        }
+    }
+}
+
+/// Sets the `db_pool_*` gauges from the pool's current state, called when metrics are scraped.
+pub fn record_pool_metrics(pool: &Pool<Postgres>) {
+    let size = pool.size();
+    let idle = pool.num_idle() as u32;
+    metrics::set_gauge("db_pool_connections", &[], size as f64);
+    metrics::set_gauge("db_pool_idle_connections", &[], idle as f64);
+    metrics::set_gauge("db_pool_in_use_connections", &[], size.saturating_sub(idle) as f64);
+    metrics::set_gauge("db_pool_max_connections", &[], pool.options().get_max_connections() as f64);
+}
+
+/// Rows read one at a time as the stream is polled, for results too large to hold in memory. The
+/// stream holds its pool connection until it ends or is dropped.
+pub type RowStream<T> = BoxStream<'static, Result<T, RepositoryError>>;
+
+/// A row of a page query that also selects `COUNT(*) OVER () AS total_items`, so the page and
+/// the total come back in one round trip.
+#[derive(FromRow)]
+pub struct Counted<T> {
+    #[sqlx(flatten)]
+    pub item: T,
+    pub total_items: i64,
+}
+
+impl<T> Counted<T> {
+    /// The items and the total, `None` when the page is empty and the total wasn't read. Only a
+    /// page past the end is empty when there are matches, the caller counts separately then.
+    pub fn split(rows: Vec<Self>) -> (Vec<T>, Option<i64>) {
+        let total_items = rows.first().map(|row| row.total_items);
+        (rows.into_iter().map(|row| row.item).collect(), total_items)
+    }
+}
+
+/// A `LIKE` pattern matching values that start with `value`, its own `%`, `_` and `\` taken literally.
+pub fn like_prefix(value: &str) -> String {
+    format!("{}%", value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"))
+}
+
+/// The repositories services, handlers and jobs go through, as trait objects so tests can replace
+/// any of them with an in-memory implementation and leave the rest on Postgres.
+#[derive(Clone)]
+pub struct Repositories {
+    pub users: Arc<dyn UserRepository>,
+    pub posts: Arc<dyn PostRepository>,
+    pub comments: Arc<dyn CommentRepository>,
+    pub roles: Arc<dyn RoleRepository>,
+    pub permissions: Arc<dyn PermissionRepository>,
+    pub action_tokens: Arc<dyn UserActionTokenRepository>,
+    pub refresh_tokens: Arc<dyn RefreshTokenRepository>,
+    pub audit_logs: Arc<dyn AuditLogRepository>,
+    pub reports: Arc<dyn ReportRepository>,
+    pub aggregates: Arc<dyn AggregateRepository>,
+    pub waitlist: Arc<dyn WaitlistRepository>,
+    pub webhooks: Arc<dyn WebhookRepository>,
+    pub emails: Arc<dyn EmailOutboxRepository>,
+    pub feature_flags: Arc<dyn FeatureFlagRepository>,
+    pub invitations: Arc<dyn InvitationRepository>,
+    pub sessions: Arc<dyn SessionRepository>,
+    pub activity: Arc<dyn ActivityRepository>,
+    pub velocity_limits: Arc<dyn VelocityLimitRepository>,
+    pub search: Arc<dyn SearchRepository>,
+    pub organizations: Arc<dyn OrganizationRepository>,
+    pub digests: Arc<dyn DigestRepository>,
+    pub analytics: Arc<dyn AnalyticsRepository>,
+    pub announcements: Arc<dyn AnnouncementRepository>,
+    pub tos: Arc<dyn TosRepository>,
+    pub retention: Arc<dyn RetentionRepository>,
+    pub data_exports: Arc<dyn DataExportRepository>,
+    pub feed_imports: Arc<dyn FeedImportRepository>,
+    /// The pool behind the Postgres repositories, watched for its metrics and by `db_admission`.
+    pub pool: Pool<Postgres>,
+}
+
+impl Repositories {
+    /// Every repository backed by the same Postgres pool.
+    pub fn postgres(db_client: DBClient) -> Self {
+        let db_client = Arc::new(db_client);
+        Self {
+            pool: db_client.pool.clone(),
+            users: db_client.clone(),
+            posts: db_client.clone(),
+            comments: db_client.clone(),
+            roles: db_client.clone(),
+            permissions: db_client.clone(),
+            action_tokens: db_client.clone(),
+            refresh_tokens: db_client.clone(),
+            audit_logs: db_client.clone(),
+            reports: db_client.clone(),
+            aggregates: db_client.clone(),
+            waitlist: db_client.clone(),
+            webhooks: db_client.clone(),
+            emails: db_client.clone(),
+            feature_flags: db_client.clone(),
+            invitations: db_client.clone(),
+            sessions: db_client.clone(),
+            activity: db_client.clone(),
+            velocity_limits: db_client.clone(),
+            search: db_client.clone(),
+            organizations: db_client.clone(),
+            digests: db_client.clone(),
+            analytics: db_client.clone(),
+            announcements: db_client.clone(),
+            tos: db_client.clone(),
+            retention: db_client.clone(),
+            data_exports: db_client.clone(),
+            feed_imports: db_client,
+        }
     }
 }
\ No newline at end of file
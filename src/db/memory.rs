@@ -0,0 +1,2801 @@
+use std::{collections::{HashMap, HashSet}, sync::{Arc, Mutex, MutexGuard}, time::Duration};
+use async_trait::async_trait;
+use chrono::{DateTime, DurationRound, NaiveDate, TimeZone, Utc};
+use axum::body::Bytes;
+use futures_util::stream;
+use sqlx::{postgres::PgPoolOptions, types::Json};
+use uuid::Uuid;
+use crate::{
+    db::{Repositories, RowStream},
+    dto::{PaginatedData, Pagination, PaginationMeta},
+    error::RepositoryError,
+    middleware::permission::Permission,
+    modules::{
+        activity::{dto::UserActivityParams, model::{ActivityItem, ActivityRepository}},
+        admin::dto::UserHistoryParams,
+        aggregate::model::{AdminStats, AggregateRepository, DailyStats, Leaderboard, LeaderboardEntry, TopPost, TrendingPost},
+        analytics::model::{AnalyticsEvent, AnalyticsRepository},
+        announcement::{dto::{AnnouncementUpdateRequest, NewAnnouncement}, model::{Announcement, AnnouncementRepository}},
+        audit_log::model::{AuditLog, AuditLogRepository, NewAuditLog},
+        comment::{dto::NewComment, model::{Comment, CommentDetail, CommentRepository, CommentsByPost}},
+        data_export::{dto::NewDataExport, model::{DataExport, DataExportProgress, DataExportRepository, DataExportStatus, DumpChunk, ExportFormat, ExportTable}},
+        digest::model::{DigestContent, DigestPost, DigestRecipient, DigestRepository},
+        email::model::{EmailOutboxRepository, QueuedEmail},
+        feature_flag::{dto::{FeatureFlagUpdateRequest, NewFeatureFlag}, model::{FeatureFlag, FeatureFlagRepository}},
+        feed_import::model::{FeedDraft, FeedDraftStatus, FeedFetch, FeedImportRepository, FeedSource, NewFeedDraft},
+        invitation::model::{Invitation, InvitationRepository, NewInvitation},
+        organization::model::{
+            Organization, OrganizationDetail, OrganizationInvitation, OrganizationMember, OrganizationRepository, OrganizationRole,
+            OrganizationUsageAccess, OrganizationUsageMonth,
+        },
+        permission::model::PermissionRepository,
+        post::{
+            dto::{NewPost, PostPatchRequest},
+            model::{Post, PostCollaborator, PostComment, PostDetail, PostLicense, PostListByUser, PostRepository, PostUser, SyndicatedPost, UserPost},
+        },
+        refresh_token::model::{RefreshToken, RefreshTokenRepository},
+        report::{dto::{NewReport, ReportListParams, ReportTarget}, model::{Report, ReportRepository, ReportResolution, ReportStatus}},
+        retention::model::{RetentionCandidates, RetentionRepository, RetentionRule},
+        role::model::{RoleRepository, RoleType},
+        search::model::{PostSearchResult, SearchRepository, TagSearchResult},
+        session::model::{NewUserSession, SessionRepository, SessionSighting, UserSession},
+        tos::{dto::NewTosVersion, model::{TosRepository, TosVersion}},
+        user::{
+            dto::{FollowKind, UserConnectionParams, UserFeedParams, UserSuggestionParams, UserFeeds, UserListParams, UserResponse, UserSettings, UserPatchRequest},
+            model::{Connections, FollowRequest, NewUser, User, UserDetail, UserExportRow, UserRepository, UserSearchResult, UserSuggestion, UserSummary},
+        },
+        user_action_token::model::{ActionType, NewUserActionToken, UserActionToken, UserActionTokenRepository},
+        velocity::{dto::VelocityLimitUpdateRequest, model::{VelocityAction, VelocityKind, VelocityLimit, VelocityLimitRepository}},
+        waitlist::{dto::WaitlistApproval, model::WaitlistRepository},
+        webhook::{dto::{NewWebhookEndpoint, WebhookDeliveryListParams}, model::{DueWebhookDelivery, WebhookDelivery, WebhookDeliveryStatus, WebhookEndpoint, WebhookEvent, WebhookRepository}},
+    },
+    utils::{csv, hashtag, password::{Argon2idHasher, PasswordHasher}},
+};
+
+struct Role {
+    id: Uuid,
+    name: RoleType,
+    parent_role_id: Option<Uuid>,
+}
+
+#[derive(Default)]
+struct State {
+    roles: Vec<Role>,
+    permissions: HashMap<String, String>,
+    grants: HashSet<(Uuid, String)>,
+    users: HashMap<Uuid, User>,
+    /// (follower, following) -> followed at
+    followers: HashMap<(Uuid, Uuid), DateTime<Utc>>,
+    /// (requester, target, sent at)
+    follow_requests: Vec<(Uuid, Uuid, chrono::DateTime<Utc>)>,
+    settings: HashMap<Uuid, UserSettings>,
+    audit_logs: Vec<AuditLog>,
+    exported: HashSet<Uuid>,
+    anonymized: HashSet<Uuid>,
+    outbox: Vec<OutboxEmail>,
+    feature_flags: Vec<FeatureFlag>,
+    announcements: Vec<Announcement>,
+    tos_versions: Vec<TosVersion>,
+    /// (user, version) -> accepted at
+    tos_acceptances: HashMap<(Uuid, Uuid), DateTime<Utc>>,
+    invitations: Vec<Invitation>,
+    refresh_tokens: HashMap<Uuid, RefreshToken>,
+    sessions: Vec<UserSession>,
+    velocity_limits: Vec<VelocityLimit>,
+    data_exports: Vec<DataExport>,
+    feed_sources: Vec<FeedSource>,
+    feed_drafts: Vec<FeedDraft>,
+    action_tokens: Vec<UserActionToken>,
+    /// (endpoint, secret)
+    webhook_endpoints: Vec<(WebhookEndpoint, String)>,
+    webhook_deliveries: Vec<WebhookDelivery>,
+    /// Oldest first.
+    posts: Vec<PostRow>,
+    /// (post, user) -> (added by, added at)
+    post_collaborators: HashMap<(Uuid, Uuid), (Option<Uuid>, DateTime<Utc>)>,
+    /// (post, hour) -> views
+    post_views: HashMap<(Uuid, DateTime<Utc>), i64>,
+    /// Oldest first.
+    comments: Vec<CommentRow>,
+    reports: Vec<Report>,
+    organizations: Vec<OrganizationRow>,
+    /// (organization, user) -> (role, joined at)
+    organization_members: HashMap<(Uuid, Uuid), (OrganizationRole, DateTime<Utc>)>,
+    /// (organization, user) -> invitation
+    organization_invitations: HashMap<(Uuid, Uuid), InvitationRow>,
+    /// (organization, user)
+    organization_followers: HashSet<(Uuid, Uuid)>,
+    /// (organization, first day of the month) -> requests
+    organization_usage: HashMap<(Uuid, NaiveDate), i64>,
+    /// user -> last digest sent at
+    digests_sent: HashMap<Uuid, DateTime<Utc>>,
+    analytics_events: Vec<AnalyticsEvent>,
+}
+
+struct OutboxEmail {
+    email: QueuedEmail,
+    pending: bool,
+}
+
+struct PostRow {
+    post: Post,
+    is_hidden: bool,
+}
+
+struct CommentRow {
+    comment: Comment,
+    is_hidden: bool,
+    deleted_at: Option<DateTime<Utc>>,
+}
+
+struct OrganizationRow {
+    organization: Organization,
+    monthly_request_quota: Option<i64>,
+}
+
+struct InvitationRow {
+    role: OrganizationRole,
+    invited_by: Option<Uuid>,
+    created_at: DateTime<Utc>,
+}
+
+/// Every repository kept in memory for handler and service tests. The rules the SQL enforces
+/// (ownership, private accounts, follow requests, unique emails, organization owners, cascading
+/// deletes) are kept, what Postgres computes is approximated where the impls say so.
+#[derive(Default)]
+pub struct InMemoryDb {
+    state: Mutex<State>,
+}
+
+impl InMemoryDb {
+    /// Both roles with every permission granted as the seed command would, and the velocity limits
+    /// the migration starts with.
+    pub fn seeded() -> Arc<Self> {
+        let db = Self::default();
+        {
+            let mut state = db.state();
+            let user_role_id = Uuid::new_v4();
+            state.roles.push(Role { id: user_role_id, name: RoleType::User, parent_role_id: None });
+            state.roles.push(Role { id: Uuid::new_v4(), name: RoleType::Admin, parent_role_id: Some(user_role_id) });
+            for permission in Permission::ALL {
+                state.permissions.insert(permission.to_string(), permission.description().to_string());
+                for role in permission.roles() {
+                    let role_id = state.role_id(*role);
+                    state.grants.insert((role_id, permission.to_string()));
+                }
+            }
+            for (kind, max_count, window_secs) in [(VelocityKind::Post, 5, 600), (VelocityKind::Comment, 10, 60)] {
+                state.velocity_limits.push(VelocityLimit {
+                    kind,
+                    max_count,
+                    window_secs,
+                    action: VelocityAction::Throttle,
+                    updated_by: None,
+                    updated_at: Utc::now(),
+                });
+            }
+        }
+        Arc::new(db)
+    }
+    /// Every repository in memory. `pool` is a placeholder that never connects, only there for the
+    /// pool metrics and `db_admission`, which never find it saturated.
+    pub fn repositories(self: &Arc<Self>) -> Repositories {
+        let pool = PgPoolOptions::new()
+            .acquire_timeout(Duration::from_millis(100))
+            .connect_lazy("postgres://localhost:1/unused")
+            .expect("valid placeholder database url");
+        Repositories {
+            users: self.clone(),
+            posts: self.clone(),
+            comments: self.clone(),
+            roles: self.clone(),
+            permissions: self.clone(),
+            audit_logs: self.clone(),
+            emails: self.clone(),
+            feature_flags: self.clone(),
+            announcements: self.clone(),
+            tos: self.clone(),
+            retention: self.clone(),
+            data_exports: self.clone(),
+            feed_imports: self.clone(),
+            invitations: self.clone(),
+            action_tokens: self.clone(),
+            webhooks: self.clone(),
+            refresh_tokens: self.clone(),
+            waitlist: self.clone(),
+            sessions: self.clone(),
+            velocity_limits: self.clone(),
+            reports: self.clone(),
+            aggregates: self.clone(),
+            activity: self.clone(),
+            search: self.clone(),
+            organizations: self.clone(),
+            digests: self.clone(),
+            analytics: self.clone(),
+            pool,
+        }
+    }
+    /// Adds a verified account that can sign in with `password`.
+    pub fn add_user(&self, name: &str, email: &str, password: &str, role: RoleType) -> User {
+        let mut state = self.state();
+        let now = Utc::now();
+        let user = User {
+            id: Uuid::new_v4(),
+            role_id: state.role_id(role),
+            name: name.to_string(),
+            email: email.to_string(),
+            password: Argon2idHasher::default().hash(password).expect("hashable password"),
+            is_verified: true,
+            is_banned: false,
+            is_private: false,
+            username: None,
+            bio: None,
+            avatar_url: None,
+            website: None,
+            default_license: PostLicense::AllRightsReserved,
+            created_at: now,
+            updated_at: now,
+        };
+        state.users.insert(user.id, user.clone());
+        user
+    }
+    pub fn set_private(&self, user_id: Uuid, is_private: bool) {
+        if let Some(user) = self.state().users.get_mut(&user_id) {
+            user.is_private = is_private;
+        }
+    }
+    pub fn set_role(&self, user_id: Uuid, role: RoleType) {
+        let mut state = self.state();
+        let role_id = state.role_id(role);
+        if let Some(user) = state.users.get_mut(&user_id) {
+            user.role_id = role_id;
+        }
+    }
+    pub fn user(&self, user_id: Uuid) -> Option<User> {
+        self.state().users.get(&user_id).cloned()
+    }
+    /// Audit actions recorded for `user_id`, oldest first.
+    pub fn audit_actions(&self, user_id: Uuid) -> Vec<String> {
+        self.state().audit_logs.iter()
+            .filter(|log| log.target_user_id == user_id)
+            .map(|log| log.action.clone())
+            .collect()
+    }
+    /// Moves the audit logs about `user_id` `age` into the past, for the retention rules.
+    pub fn backdate_audit_logs(&self, user_id: Uuid, age: chrono::Duration) {
+        for log in self.state().audit_logs.iter_mut().filter(|log| log.target_user_id == user_id) {
+            log.created_at -= age;
+        }
+    }
+    /// Makes every feed source due for `jobs::feed_import` right away.
+    pub fn expire_feed_sources(&self) {
+        for source in self.state().feed_sources.iter_mut() {
+            source.next_fetch_at = Utc::now();
+        }
+    }
+    /// The stored hashes of the action tokens of `user_id`, `None` once a token was used.
+    pub fn action_token_hashes(&self, user_id: Uuid) -> Vec<Option<String>> {
+        self.state().action_tokens.iter()
+            .filter(|token| token.user_id == user_id)
+            .map(|token| token.token_hash.clone())
+            .collect()
+    }
+    /// Makes every pending webhook delivery due right away, as if its backoff or the lease of a
+    /// crashed instance had run out.
+    pub fn expire_webhook_deliveries(&self) {
+        for delivery in self.state().webhook_deliveries.iter_mut().filter(|delivery| delivery.status == WebhookDeliveryStatus::Pending) {
+            delivery.next_attempt_at = Utc::now();
+        }
+    }
+    /// Analytics events the sink has saved, in the order they came in.
+    pub fn analytics_events(&self) -> Vec<AnalyticsEvent> {
+        self.state().analytics_events.clone()
+    }
+    /// `(to, subject)` of the emails waiting in the outbox, oldest first.
+    pub fn queued_emails(&self) -> Vec<(String, String)> {
+        self.state().outbox.iter()
+            .filter(|queued| queued.pending)
+            .map(|queued| (queued.email.to_email.clone(), queued.email.subject.clone()))
+            .collect()
+    }
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+    /// Every user as an export row, oldest first.
+    fn export_rows(&self) -> Vec<UserExportRow> {
+        let state = self.state();
+        let mut users: Vec<&User> = state.users.values().collect();
+        users.sort_by_key(|user| (user.created_at, user.id));
+        users.into_iter()
+            .map(|user| UserExportRow {
+                id: user.id,
+                name: user.name.clone(),
+                email: user.email.clone(),
+                username: user.username.clone(),
+                role: state.role_name(user.role_id).expect("user with a known role"),
+                is_verified: user.is_verified,
+                is_banned: user.is_banned,
+                is_private: user.is_private,
+                created_at: user.created_at,
+            })
+            .collect()
+    }
+}
+
+impl State {
+    /// Marks the unused, unexpired token `id` used, the same check as the Postgres `UPDATE`.
+    fn use_action_token(&mut self, id: Uuid) -> Result<(), RepositoryError> {
+        let now = Utc::now();
+        let token = self.action_tokens.iter_mut()
+            .find(|token| token.id == id && token.used_at.is_none() && token.expires_at.is_some_and(|expires_at| expires_at > now))
+            .ok_or(RepositoryError::NotFound)?;
+        token.used_at = Some(now);
+        token.token_hash = None;
+        token.expires_at = None;
+        token.updated_at = now;
+        Ok(())
+    }
+    fn role_id(&self, name: RoleType) -> Uuid {
+        self.roles.iter().find(|role| role.name == name).map(|role| role.id).expect("seeded role")
+    }
+    /// Ids of the rows `rule` applies to with the time it goes by, the oldest first.
+    fn retention_candidates(&self, rule: RetentionRule, cutoff: DateTime<Utc>) -> Vec<(DateTime<Utc>, Uuid)> {
+        let mut candidates: Vec<(DateTime<Utc>, Uuid)> = match rule {
+            RetentionRule::DeletedComments => self.comments.iter()
+                .filter_map(|row| Some((row.deleted_at?, row.comment.id)))
+                .filter(|(deleted_at, id)| *deleted_at < cutoff && !self.has_open_reports(*id))
+                .collect(),
+            RetentionRule::AuditLogs => self.audit_logs.iter()
+                .filter(|log| log.created_at < cutoff && !self.anonymized.contains(&log.id))
+                .map(|log| (log.created_at, log.id))
+                .collect(),
+            RetentionRule::AnalyticsEvents => self.analytics_events.iter()
+                .filter(|event| event.received_at < cutoff)
+                .map(|event| (event.received_at, event.id))
+                .collect(),
+        };
+        candidates.sort();
+        candidates
+    }
+    fn role_name(&self, role_id: Uuid) -> Option<RoleType> {
+        self.roles.iter().find(|role| role.id == role_id).map(|role| role.name)
+    }
+    fn user_response(&self, user: &User) -> UserResponse {
+        UserResponse {
+            id: user.id,
+            name: user.name.clone(),
+            email: user.email.clone(),
+            role: self.role_name(user.role_id).expect("user with a known role"),
+            password: user.password.clone(),
+            is_verified: user.is_verified,
+            is_banned: user.is_banned,
+            is_private: user.is_private,
+            username: user.username.clone(),
+            bio: user.bio.clone(),
+            avatar_url: user.avatar_url.clone(),
+            website: user.website.clone(),
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+        }
+    }
+    fn connection(&self, user_id: Uuid) -> Option<Connections> {
+        let user = self.users.get(&user_id)?;
+        Some(Connections {
+            id: user.id,
+            name: user.name.clone(),
+            email: user.email.clone(),
+            role: self.role_name(user.role_id)?,
+            is_verified: user.is_verified,
+        })
+    }
+    fn following(&self, user_id: Uuid) -> Vec<Connections> {
+        self.followers.keys()
+            .filter(|(follower, _)| *follower == user_id)
+            .filter_map(|(_, following)| self.connection(*following))
+            .collect()
+    }
+    fn followers_of(&self, user_id: Uuid) -> Vec<Connections> {
+        self.followers.keys()
+            .filter(|(_, following)| *following == user_id)
+            .filter_map(|(follower, _)| self.connection(*follower))
+            .collect()
+    }
+    fn is_visible(&self, user_id: Uuid, viewer_id: Uuid) -> bool {
+        let Some(user) = self.users.get(&user_id) else {
+            return false;
+        };
+        let viewer_is_admin = self.users.get(&viewer_id)
+            .is_some_and(|viewer| self.role_name(viewer.role_id) == Some(RoleType::Admin));
+        !user.is_private || user_id == viewer_id || self.followers.contains_key(&(viewer_id, user_id)) || viewer_is_admin
+    }
+    /// `NotFound` for an unknown role, as `DBClient::is_admin`.
+    fn is_admin(&self, role_id: Uuid) -> Result<bool, RepositoryError> {
+        Ok(self.role_name(role_id).ok_or(RepositoryError::NotFound)? == RoleType::Admin)
+    }
+    fn post(&self, post_id: Uuid) -> Option<&PostRow> {
+        self.posts.iter().find(|row| row.post.id == post_id)
+    }
+    fn post_mut(&mut self, post_id: Uuid) -> Option<&mut PostRow> {
+        self.posts.iter_mut().find(|row| row.post.id == post_id)
+    }
+    fn comment(&self, comment_id: Uuid) -> Option<&CommentRow> {
+        self.comments.iter().find(|row| row.comment.id == comment_id)
+    }
+    /// The comments of the post that aren't hidden, oldest first.
+    fn visible_comments(&self, post_id: Uuid) -> impl Iterator<Item = &Comment> {
+        self.comments.iter()
+            .filter(move |row| row.comment.post_id == post_id && !row.is_hidden)
+            .map(|row| &row.comment)
+    }
+    /// Whether the post isn't hidden and its author, who isn't banned, is visible to `viewer_id`,
+    /// the conditions of `push_post_visibility`.
+    fn is_post_visible(&self, row: &PostRow, viewer_id: Uuid) -> bool {
+        !row.is_hidden
+            && self.users.get(&row.post.user_id).is_some_and(|author| !author.is_banned)
+            && self.is_visible(row.post.user_id, viewer_id)
+    }
+    /// The conditions of `DBClient::can_edit_post`.
+    fn can_edit_post(&self, post: &Post, user_id: Uuid, user_role_id: Uuid) -> Result<bool, RepositoryError> {
+        if post.user_id == user_id || self.is_admin(user_role_id)? {
+            return Ok(true);
+        }
+        let is_publisher = post.organization_id
+            .and_then(|organization_id| self.member_role(organization_id, user_id))
+            .is_some_and(|role| role.can_publish());
+        Ok(is_publisher || self.post_collaborators.contains_key(&(post.id, user_id)))
+    }
+    fn collaborator(&self, post_id: Uuid, user_id: Uuid) -> Option<PostCollaborator> {
+        let (added_by, created_at) = self.post_collaborators.get(&(post_id, user_id))?;
+        let user = self.users.get(&user_id)?;
+        Some(PostCollaborator {
+            user_id,
+            name: user.name.clone(),
+            username: user.username.clone(),
+            avatar_url: user.avatar_url.clone(),
+            added_by: *added_by,
+            created_at: *created_at,
+        })
+    }
+    fn syndicated_post(&self, post: &Post) -> Option<SyndicatedPost> {
+        Some(SyndicatedPost {
+            id: post.id,
+            title: post.title.clone(),
+            content: post.content.clone(),
+            tags: post.tags.clone(),
+            license: post.license,
+            author_name: self.users.get(&post.user_id)?.name.clone(),
+            created_at: post.created_at,
+            updated_at: post.updated_at,
+        })
+    }
+    /// Removes the posts with everything that cascades from them in Postgres.
+    fn remove_posts(&mut self, post_ids: &HashSet<Uuid>) {
+        self.posts.retain(|row| !post_ids.contains(&row.post.id));
+        let comment_ids: HashSet<Uuid> = self.comments.iter()
+            .filter(|row| post_ids.contains(&row.comment.post_id))
+            .map(|row| row.comment.id)
+            .collect();
+        self.remove_comments(&comment_ids);
+        self.reports.retain(|report| !report.post_id.is_some_and(|post_id| post_ids.contains(&post_id)));
+        self.post_collaborators.retain(|(post_id, _), _| !post_ids.contains(post_id));
+        self.post_views.retain(|(post_id, _), _| !post_ids.contains(post_id));
+        for draft in self.feed_drafts.iter_mut().filter(|draft| draft.post_id.is_some_and(|post_id| post_ids.contains(&post_id))) {
+            draft.post_id = None;
+        }
+    }
+    /// Removes the comments together with their reports.
+    fn remove_comments(&mut self, comment_ids: &HashSet<Uuid>) {
+        self.comments.retain(|row| !comment_ids.contains(&row.comment.id));
+        self.reports.retain(|report| !report.comment_id.is_some_and(|comment_id| comment_ids.contains(&comment_id)));
+    }
+    fn has_open_reports(&self, comment_id: Uuid) -> bool {
+        self.reports.iter().any(|report| report.comment_id == Some(comment_id) && report.status == ReportStatus::Open)
+    }
+    fn organization(&self, organization_id: Uuid) -> Option<&OrganizationRow> {
+        self.organizations.iter().find(|row| row.organization.id == organization_id)
+    }
+    fn member_role(&self, organization_id: Uuid, user_id: Uuid) -> Option<OrganizationRole> {
+        self.organization_members.get(&(organization_id, user_id)).map(|(role, _)| *role)
+    }
+    fn organization_member(&self, organization_id: Uuid, user_id: Uuid) -> Option<OrganizationMember> {
+        let (role, created_at) = self.organization_members.get(&(organization_id, user_id))?;
+        let user = self.users.get(&user_id)?;
+        Some(OrganizationMember {
+            user_id,
+            name: user.name.clone(),
+            username: user.username.clone(),
+            avatar_url: user.avatar_url.clone(),
+            role: *role,
+            created_at: *created_at,
+        })
+    }
+    /// The check of `DBClient::ensure_other_owner`.
+    fn ensure_other_owner(&self, organization_id: Uuid, user_id: Uuid) -> Result<(), RepositoryError> {
+        self.organization(organization_id).ok_or(RepositoryError::NotFound)?;
+        let has_other_owner = self.organization_members.iter()
+            .any(|((organization, member), (role, _))| *organization == organization_id && *member != user_id && *role == OrganizationRole::Owner);
+        if !has_other_owner {
+            return Err(RepositoryError::InvalidInput("An organization needs at least one owner".to_string()));
+        }
+        Ok(())
+    }
+    /// Removes the organization with its members, invitations, followers and usage. Its posts
+    /// become personal posts.
+    fn remove_organization(&mut self, organization_id: Uuid) -> bool {
+        let count = self.organizations.len();
+        self.organizations.retain(|row| row.organization.id != organization_id);
+        if self.organizations.len() == count {
+            return false;
+        }
+        self.organization_members.retain(|(organization, _), _| *organization != organization_id);
+        self.organization_invitations.retain(|(organization, _), _| *organization != organization_id);
+        self.organization_followers.retain(|(organization, _)| *organization != organization_id);
+        self.organization_usage.retain(|(organization, _), _| *organization != organization_id);
+        for row in self.posts.iter_mut().filter(|row| row.post.organization_id == Some(organization_id)) {
+            row.post.organization_id = None;
+        }
+        true
+    }
+}
+
+fn paginate<T>(items: Vec<T>, pagination: Pagination) -> PaginatedData<T> {
+    let total_items = items.len();
+    let items = items.into_iter().skip(pagination.offset()).take(pagination.limit).collect();
+    PaginatedData {
+        items,
+        pagination: PaginationMeta::new(pagination.page as i32, pagination.limit as i32, total_items as i64),
+    }
+}
+
+fn contains_ignore_case(value: &str, search: &str) -> bool {
+    value.to_lowercase().contains(&search.to_lowercase())
+}
+
+#[async_trait]
+impl UserRepository for InMemoryDb {
+    async fn get_user_by_id(&self, user_id: &Uuid) -> Result<Option<User>, RepositoryError> {
+        Ok(self.user(*user_id))
+    }
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<UserResponse>, RepositoryError> {
+        let state = self.state();
+        Ok(state.users.values()
+            .find(|user| user.email.eq_ignore_ascii_case(email))
+            .map(|user| state.user_response(user)))
+    }
+    async fn get_identifier_availability(&self, email: Option<&str>, username: Option<&str>) -> Result<(bool, bool), RepositoryError> {
+        let state = self.state();
+        let email_taken = state.users.values().any(|user| Some(user.email.as_str()) == email);
+        let username_taken = state.users.values().any(|user| user.username.is_some() && user.username.as_deref() == username);
+        Ok((!email_taken, !username_taken))
+    }
+    /// Waitlisting lives in the waitlist repository, which stays on Postgres.
+    async fn save_user<'a, 'b>(&self, user_data: NewUser<'a>, user_action_data: NewUserActionToken<'b>) -> Result<(User, RoleType), RepositoryError> {
+        let mut state = self.state();
+        if state.users.values().any(|user| user.email.eq_ignore_ascii_case(user_data.email)) {
+            return Err(RepositoryError::Conflict { constraint: "users_email_key".to_string() });
+        }
+        let role = state.role_name(user_data.role_id).ok_or(RepositoryError::NotFound)?;
+        let now = Utc::now();
+        let invitation = match user_data.invitation_id {
+            Some(invitation_id) => Some(state.invitations.iter()
+                .position(|invitation| invitation.id == invitation_id && invitation.consumed_at.is_none() && invitation.expires_at > now)
+                .ok_or(RepositoryError::NotFound)?),
+            None => None,
+        };
+        let user = User {
+            id: Uuid::new_v4(),
+            role_id: user_data.role_id,
+            name: user_data.name.to_string(),
+            email: user_data.email.to_string(),
+            password: user_data.password,
+            is_verified: false,
+            is_banned: false,
+            is_private: false,
+            username: None,
+            bio: None,
+            avatar_url: None,
+            website: None,
+            default_license: PostLicense::AllRightsReserved,
+            created_at: now,
+            updated_at: now,
+        };
+        if let Some(index) = invitation {
+            state.invitations[index].consumed_at = Some(now);
+            state.invitations[index].consumed_by = Some(user.id);
+        }
+        state.action_tokens.push(UserActionToken {
+            id: Uuid::new_v4(),
+            user_id: user.id,
+            token_hash: Some(user_action_data.token_hash.to_string()),
+            action_type: user_action_data.action_type,
+            used_at: None,
+            expires_at: Some(user_action_data.expires_at),
+            created_at: now,
+            updated_at: now,
+        });
+        state.users.insert(user.id, user.clone());
+        Ok((user, role))
+    }
+    async fn get_user_feeds(&self, user_id: Uuid, params: UserFeedParams) -> Result<PaginatedData<UserFeeds>, RepositoryError> {
+        let state = self.state();
+        let organization_ids: HashSet<Uuid> = match params.organizations {
+            Some(true) => state.organization_members.keys().copied()
+                .chain(state.organization_followers.iter().copied())
+                .filter(|(_, member)| *member == user_id)
+                .map(|(organization_id, _)| organization_id)
+                .collect(),
+            _ => HashSet::new(),
+        };
+        let period = match (&params.since, &params.until) {
+            (Some(since), Some(until)) => match (NaiveDate::parse_from_str(since, "%Y-%m-%d"), NaiveDate::parse_from_str(until, "%Y-%m-%d")) {
+                (Ok(since), Ok(until)) => Some((
+                    Utc.from_utc_datetime(&since.and_hms_opt(0, 0, 0).unwrap()),
+                    Utc.from_utc_datetime(&until.and_hms_opt(23, 59, 59).unwrap()),
+                )),
+                _ => None,
+            },
+            _ => None,
+        };
+        let now = Utc::now();
+        let mut feeds: Vec<(f64, UserFeeds)> = state.posts.iter()
+            .filter(|row| !row.is_hidden)
+            .map(|row| &row.post)
+            .filter(|post| {
+                post.user_id == user_id
+                    || state.followers.contains_key(&(user_id, post.user_id))
+                    || post.organization_id.is_some_and(|organization_id| organization_ids.contains(&organization_id))
+            })
+            .filter(|post| params.search.as_deref().is_none_or(|search| contains_ignore_case(&post.title, search) || contains_ignore_case(&post.content, search)))
+            .filter(|post| period.is_none_or(|(since, until)| since <= post.created_at && post.created_at <= until))
+            .filter_map(|post| {
+                let comments: Vec<Comment> = state.visible_comments(post.id).cloned().collect();
+                let comments_count = comments.len() as i64;
+                // The same keys as the Postgres feed.
+                let sort_key = match params.sort.as_deref() {
+                    Some("top") => comments_count as f64,
+                    Some("trending") => {
+                        let hours = (now - post.created_at).num_milliseconds() as f64 / 3_600_000.0;
+                        (comments_count + 1) as f64 / (hours + 2.0).powf(1.5)
+                    }
+                    _ => post.created_at.timestamp_micros() as f64,
+                };
+                Some((sort_key, UserFeeds {
+                    id: post.id,
+                    user_id: post.user_id,
+                    organization_id: post.organization_id,
+                    title: post.title.clone(),
+                    content: post.content.clone(),
+                    tags: post.tags.clone(),
+                    posted_by: state.users.get(&post.user_id)?.name.clone(),
+                    comments_count,
+                    created_at: post.created_at,
+                    updated_at: post.updated_at,
+                    comments,
+                }))
+            })
+            .collect();
+        let ascending = params.order_by.as_deref() == Some("ASC");
+        feeds.sort_by(|(a_key, a), (b_key, b)| {
+            let by_key = if ascending { a_key.total_cmp(b_key) } else { b_key.total_cmp(a_key) };
+            by_key.then(b.created_at.cmp(&a.created_at)).then(a.id.cmp(&b.id))
+        });
+        Ok(paginate(feeds.into_iter().map(|(_, feed)| feed).collect(), params.pagination))
+    }
+    async fn get_users(&self, params: UserListParams) -> Result<PaginatedData<UserResponse>, RepositoryError> {
+        let state = self.state();
+        let mut users: Vec<&User> = state.users.values()
+            .filter(|user| params.is_verified.is_none_or(|is_verified| user.is_verified == is_verified))
+            .filter(|user| params.search.as_deref().is_none_or(|search| {
+                contains_ignore_case(&user.name, search) || contains_ignore_case(&user.email, search)
+            }))
+            .collect();
+        users.sort_by_key(|user| user.created_at);
+        if params.order_by.as_deref() != Some("ASC") {
+            users.reverse();
+        }
+        let users = users.into_iter().map(|user| state.user_response(user)).collect();
+        Ok(paginate(users, params.pagination))
+    }
+    async fn get_user_detail(&self, user_id: &Uuid) -> Result<Option<UserDetail>, RepositoryError> {
+        let state = self.state();
+        let Some(user) = state.users.get(user_id) else {
+            return Ok(None);
+        };
+        Ok(Some(UserDetail {
+            id: user.id,
+            name: user.name.clone(),
+            email: user.email.clone(),
+            role: state.role_name(user.role_id).ok_or(RepositoryError::NotFound)?,
+            is_verified: user.is_verified,
+            is_private: user.is_private,
+            username: user.username.clone(),
+            bio: user.bio.clone(),
+            avatar_url: user.avatar_url.clone(),
+            website: user.website.clone(),
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+            followers_count: state.followers.keys().filter(|(_, following)| following == user_id).count() as i64,
+            following_count: state.followers.keys().filter(|(follower, _)| follower == user_id).count() as i64,
+        }))
+    }
+    async fn get_user_id_by_username(&self, username: &str) -> Result<Option<Uuid>, RepositoryError> {
+        Ok(self.state().users.values()
+            .find(|user| user.username.as_deref() == Some(username))
+            .map(|user| user.id))
+    }
+    async fn is_user_visible_to(&self, user_id: &Uuid, viewer_id: &Uuid) -> Result<bool, RepositoryError> {
+        Ok(self.state().is_visible(*user_id, *viewer_id))
+    }
+    async fn get_users_by_ids(&self, user_ids: &[Uuid]) -> Result<Vec<UserSummary>, RepositoryError> {
+        let state = self.state();
+        Ok(user_ids.iter()
+            .filter_map(|user_id| state.users.get(user_id))
+            .map(|user| UserSummary {
+                id: user.id,
+                name: user.name.clone(),
+                email: user.email.clone(),
+                role: state.role_name(user.role_id).expect("user with a known role"),
+                is_verified: user.is_verified,
+                is_private: user.is_private,
+                created_at: user.created_at,
+                updated_at: user.updated_at,
+            })
+            .collect())
+    }
+    async fn search_users(&self, query: &str, limit: i64) -> Result<Vec<UserSearchResult>, RepositoryError> {
+        let state = self.state();
+        let rank = |user: &User| {
+            let name = user.name.to_lowercase();
+            match user.username.as_deref() {
+                Some(username) if username == query => Some(0),
+                Some(username) if username.starts_with(query) => Some(1),
+                _ if name.starts_with(query) => Some(2),
+                _ if name.split(' ').skip(1).any(|word| word.starts_with(query)) => Some(3),
+                _ => None,
+            }
+        };
+        let mut matches: Vec<_> = state.users.values()
+            .filter(|user| !user.is_banned)
+            .filter_map(|user| Some((rank(user)?, user)))
+            .collect();
+        matches.sort_by(|(a_rank, a), (b_rank, b)| (a_rank, &a.name, a.id).cmp(&(b_rank, &b.name, b.id)));
+        Ok(matches.into_iter()
+            .take(limit as usize)
+            .map(|(_, user)| UserSearchResult {
+                id: user.id,
+                name: user.name.clone(),
+                username: user.username.clone(),
+                avatar_url: user.avatar_url.clone(),
+                is_verified: user.is_verified,
+                is_private: user.is_private,
+            })
+            .collect())
+    }
+    async fn update_user(&self, user_id: &Uuid, auth_user_id: &Uuid, patch: UserPatchRequest, expected_version: Option<DateTime<Utc>>) -> Result<User, RepositoryError> {
+        let mut state = self.state();
+        let current = state.users.get(user_id).ok_or(RepositoryError::NotFound)?;
+        if auth_user_id != user_id {
+            return Err(RepositoryError::Forbidden);
+        }
+        if expected_version.is_some_and(|version| version != current.updated_at) {
+            return Err(RepositoryError::version_mismatch(current));
+        }
+        if let Some(username) = patch.username.as_ref().and_then(|username| username.as_deref()).filter(|username| !username.is_empty())
+            && state.users.values().any(|user| user.id != *user_id && user.username.as_deref() == Some(username))
+        {
+            return Err(RepositoryError::Conflict { constraint: "users_username_key".to_string() });
+        }
+        let user = state.users.get_mut(user_id).ok_or(RepositoryError::NotFound)?;
+        if let Some(name) = patch.name {
+            user.name = name;
+        }
+        for (field, value) in [
+            (&mut user.username, patch.username),
+            (&mut user.bio, patch.bio),
+            (&mut user.avatar_url, patch.avatar_url),
+            (&mut user.website, patch.website),
+        ] {
+            if let Some(value) = value {
+                *field = value.filter(|value| !value.is_empty());
+            }
+        }
+        user.is_private = patch.is_private.unwrap_or(user.is_private);
+        user.default_license = patch.default_license.unwrap_or(user.default_license);
+        user.updated_at = Utc::now();
+        let user = user.clone();
+        if !user.is_private {
+            let pending: Vec<Uuid> = state.follow_requests.iter()
+                .filter(|(_, target, _)| *target == user.id)
+                .map(|(requester, _, _)| *requester)
+                .collect();
+            state.follow_requests.retain(|(_, target, _)| *target != user.id);
+            state.followers.extend(pending.into_iter().map(|requester| ((requester, user.id), Utc::now())));
+        }
+        Ok(user)
+    }
+    async fn update_user_password(&self, user_id: &Uuid, new_password: String) -> Result<User, RepositoryError> {
+        let mut state = self.state();
+        let user = state.users.get_mut(user_id).ok_or(RepositoryError::NotFound)?;
+        user.password = new_password;
+        user.updated_at = Utc::now();
+        Ok(user.clone())
+    }
+    async fn follow_unfollow_user(&self, user_target: Uuid, user_sender: Uuid) -> Result<String, RepositoryError> {
+        let mut state = self.state();
+        if state.followers.remove(&(user_sender, user_target)).is_some() {
+            return Ok(String::from("Successfully Unfollowed"));
+        }
+        let is_private = state.users.get(&user_target).ok_or(RepositoryError::NotFound)?.is_private;
+        if !is_private {
+            state.followers.insert((user_sender, user_target), Utc::now());
+            return Ok(String::from("Successfully Followed"));
+        }
+        let pending = state.follow_requests.len();
+        state.follow_requests.retain(|(requester, target, _)| (*requester, *target) != (user_sender, user_target));
+        if state.follow_requests.len() < pending {
+            return Ok(String::from("Follow Request Cancelled"));
+        }
+        state.follow_requests.push((user_sender, user_target, Utc::now()));
+        Ok(String::from("Follow Request Sent"))
+    }
+    async fn get_user_connections(&self, user_id: Uuid, kind: &FollowKind, params: UserConnectionParams) -> Result<PaginatedData<Connections>, RepositoryError> {
+        let state = self.state();
+        let connections = match kind {
+            FollowKind::Following => state.following(user_id),
+            FollowKind::Followers => state.followers_of(user_id),
+        };
+        Ok(paginate(connections, params.pagination))
+    }
+    async fn delete_user(&self, user_id: Uuid) -> Result<(), RepositoryError> {
+        let mut state = self.state();
+        state.users.remove(&user_id).ok_or(RepositoryError::NotFound)?;
+        state.followers.retain(|(follower, following), _| *follower != user_id && *following != user_id);
+        state.follow_requests.retain(|(requester, target, _)| *requester != user_id && *target != user_id);
+        state.settings.remove(&user_id);
+        let post_ids: HashSet<Uuid> = state.posts.iter().filter(|row| row.post.user_id == user_id).map(|row| row.post.id).collect();
+        state.remove_posts(&post_ids);
+        let comment_ids: HashSet<Uuid> = state.comments.iter().filter(|row| row.comment.user_id == user_id).map(|row| row.comment.id).collect();
+        state.remove_comments(&comment_ids);
+        state.reports.retain(|report| report.reporter_id != Some(user_id));
+        for report in state.reports.iter_mut().filter(|report| report.resolved_by == Some(user_id)) {
+            report.resolved_by = None;
+        }
+        state.post_collaborators.retain(|(_, collaborator), _| *collaborator != user_id);
+        for (added_by, _) in state.post_collaborators.values_mut().filter(|(added_by, _)| *added_by == Some(user_id)) {
+            *added_by = None;
+        }
+        for row in state.organizations.iter_mut().filter(|row| row.organization.created_by == Some(user_id)) {
+            row.organization.created_by = None;
+        }
+        state.organization_members.retain(|(_, member), _| *member != user_id);
+        state.organization_invitations.retain(|(_, invited), _| *invited != user_id);
+        for invitation in state.organization_invitations.values_mut().filter(|invitation| invitation.invited_by == Some(user_id)) {
+            invitation.invited_by = None;
+        }
+        state.organization_followers.retain(|(_, follower)| *follower != user_id);
+        state.digests_sent.remove(&user_id);
+        Ok(())
+    }
+    async fn get_follower_ids(&self, user_id: Uuid) -> Result<Vec<Uuid>, RepositoryError> {
+        Ok(self.state().followers.keys()
+            .filter(|(_, following)| *following == user_id)
+            .map(|(follower, _)| *follower)
+            .collect())
+    }
+    async fn get_user_suggestions(&self, _user_id: Uuid, params: UserSuggestionParams) -> Result<PaginatedData<UserSuggestion>, RepositoryError> {
+        Ok(paginate(Vec::new(), params.pagination))
+    }
+    async fn get_follow_requests(&self, user_id: Uuid) -> Result<Vec<FollowRequest>, RepositoryError> {
+        let state = self.state();
+        let mut requests: Vec<FollowRequest> = state.follow_requests.iter()
+            .filter(|(_, target, _)| *target == user_id)
+            .filter_map(|(requester, _, created_at)| {
+                let user = state.users.get(requester)?;
+                Some(FollowRequest {
+                    requester_id: user.id,
+                    name: user.name.clone(),
+                    email: user.email.clone(),
+                    created_at: *created_at,
+                })
+            })
+            .collect();
+        requests.sort_by_key(|request| std::cmp::Reverse(request.created_at));
+        Ok(requests)
+    }
+    async fn accept_follow_request(&self, user_id: Uuid, requester_id: Uuid) -> Result<(), RepositoryError> {
+        self.reject_follow_request(user_id, requester_id).await?;
+        self.state().followers.insert((requester_id, user_id), Utc::now());
+        Ok(())
+    }
+    async fn reject_follow_request(&self, user_id: Uuid, requester_id: Uuid) -> Result<(), RepositoryError> {
+        let mut state = self.state();
+        let pending = state.follow_requests.len();
+        state.follow_requests.retain(|(requester, target, _)| (*requester, *target) != (requester_id, user_id));
+        if state.follow_requests.len() == pending {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(())
+    }
+    async fn get_user_settings(&self, user_id: Uuid) -> Result<UserSettings, RepositoryError> {
+        Ok(self.state().settings.get(&user_id).cloned().unwrap_or_default())
+    }
+    async fn save_user_settings(&self, user_id: Uuid, settings: UserSettings) -> Result<UserSettings, RepositoryError> {
+        self.state().settings.insert(user_id, settings.clone());
+        Ok(settings)
+    }
+    async fn ensure_admin(&self, name: &str, email: &str, password: String, admin_role_id: Uuid) -> Result<bool, RepositoryError> {
+        let mut state = self.state();
+        if let Some(user) = state.users.values_mut().find(|user| user.email.eq_ignore_ascii_case(email)) {
+            user.role_id = admin_role_id;
+            user.is_verified = true;
+            user.updated_at = Utc::now();
+            return Ok(false);
+        }
+        let now = Utc::now();
+        let user = User {
+            id: Uuid::new_v4(),
+            role_id: admin_role_id,
+            name: name.to_string(),
+            email: email.to_string(),
+            password,
+            is_verified: true,
+            is_banned: false,
+            is_private: false,
+            username: None,
+            bio: None,
+            avatar_url: None,
+            website: None,
+            default_license: PostLicense::AllRightsReserved,
+            created_at: now,
+            updated_at: now,
+        };
+        state.users.insert(user.id, user);
+        Ok(true)
+    }
+    async fn import_user<'a>(&self, user_data: NewUser<'a>) -> Result<Option<User>, RepositoryError> {
+        let mut state = self.state();
+        if state.users.values().any(|user| user.email.eq_ignore_ascii_case(user_data.email)) {
+            return Ok(None);
+        }
+        let now = Utc::now();
+        let user = User {
+            id: Uuid::new_v4(),
+            role_id: user_data.role_id,
+            name: user_data.name.to_string(),
+            email: user_data.email.to_string(),
+            password: user_data.password,
+            is_verified: true,
+            is_banned: false,
+            is_private: false,
+            username: None,
+            bio: None,
+            avatar_url: None,
+            website: None,
+            default_license: PostLicense::AllRightsReserved,
+            created_at: now,
+            updated_at: now,
+        };
+        state.users.insert(user.id, user.clone());
+        Ok(Some(user))
+    }
+    async fn get_users_for_export(&self, after: Option<(DateTime<Utc>, Uuid)>, limit: i64) -> Result<Vec<UserExportRow>, RepositoryError> {
+        Ok(self.export_rows().into_iter()
+            .filter(|user| after.is_none_or(|cursor| (user.created_at, user.id) > cursor))
+            .take(limit.max(0) as usize)
+            .collect())
+    }
+    fn stream_users(&self) -> RowStream<UserExportRow> {
+        Box::pin(stream::iter(self.export_rows().into_iter().map(Ok)))
+    }
+}
+
+#[async_trait]
+impl RoleRepository for InMemoryDb {
+    async fn get_role_id_by_name(&self, name: RoleType) -> Result<Option<Uuid>, RepositoryError> {
+        Ok(self.state().roles.iter().find(|role| role.name == name).map(|role| role.id))
+    }
+    async fn get_role_name_by_id(&self, role_id: Uuid) -> Result<Option<RoleType>, RepositoryError> {
+        Ok(self.state().role_name(role_id))
+    }
+    async fn ensure_role(&self, name: RoleType, parent: Option<RoleType>, _description: &str) -> Result<bool, RepositoryError> {
+        let mut state = self.state();
+        if state.roles.iter().any(|role| role.name == name) {
+            return Ok(false);
+        }
+        let parent_role_id = parent.and_then(|parent| state.roles.iter().find(|role| role.name == parent).map(|role| role.id));
+        state.roles.push(Role { id: Uuid::new_v4(), name, parent_role_id });
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl PermissionRepository for InMemoryDb {
+    async fn get_permission_by_role(&self, role_id: &Uuid) -> Result<Vec<String>, RepositoryError> {
+        let state = self.state();
+        let mut chain = vec![*role_id];
+        while let Some(parent_role_id) = state.roles.iter()
+            .find(|role| Some(&role.id) == chain.last())
+            .and_then(|role| role.parent_role_id)
+            .filter(|parent_role_id| !chain.contains(parent_role_id))
+        {
+            chain.push(parent_role_id);
+        }
+        let permissions: HashSet<&String> = state.grants.iter()
+            .filter(|(granted_role, _)| chain.contains(granted_role))
+            .map(|(_, permission)| permission)
+            .collect();
+        Ok(permissions.into_iter().cloned().collect())
+    }
+    async fn ensure_permission(&self, name: &str, description: &str) -> Result<bool, RepositoryError> {
+        let mut state = self.state();
+        if state.permissions.contains_key(name) {
+            return Ok(false);
+        }
+        state.permissions.insert(name.to_string(), description.to_string());
+        Ok(true)
+    }
+    async fn grant_permission(&self, role: RoleType, permission: &str) -> Result<bool, RepositoryError> {
+        let mut state = self.state();
+        let Some(role_id) = state.roles.iter().find(|stored| stored.name == role).map(|stored| stored.id) else {
+            return Ok(false);
+        };
+        if !state.permissions.contains_key(permission) {
+            return Ok(false);
+        }
+        Ok(state.grants.insert((role_id, permission.to_string())))
+    }
+}
+
+#[async_trait]
+impl AuditLogRepository for InMemoryDb {
+    async fn save_audit_log(&self, data: NewAuditLog) -> Result<(), RepositoryError> {
+        self.state().audit_logs.push(AuditLog {
+            id: Uuid::new_v4(),
+            actor_id: data.actor_id,
+            target_user_id: data.target_user_id,
+            category: data.action.get_category(),
+            action: data.action.get_value().to_string(),
+            metadata: data.metadata,
+            created_at: Utc::now(),
+        });
+        Ok(())
+    }
+    async fn get_user_history(&self, user_id: Uuid, params: UserHistoryParams) -> Result<PaginatedData<AuditLog>, RepositoryError> {
+        let day = |value: &Option<String>| value.as_deref().and_then(|value| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok());
+        let range = match (day(&params.since), day(&params.until)) {
+            (Some(since), Some(until)) => Some((
+                Utc.from_utc_datetime(&since.and_hms_opt(0, 0, 0).unwrap()),
+                Utc.from_utc_datetime(&until.and_hms_opt(23, 59, 59).unwrap()),
+            )),
+            _ => None,
+        };
+        let state = self.state();
+        let mut logs: Vec<AuditLog> = state.audit_logs.iter()
+            .filter(|log| log.target_user_id == user_id)
+            .filter(|log| params.category.is_none_or(|category| log.category == category))
+            .filter(|log| params.search.as_deref().is_none_or(|search| {
+                contains_ignore_case(&log.action, search) || contains_ignore_case(&log.metadata.to_string(), search)
+            }))
+            .filter(|log| range.is_none_or(|(since, until)| log.created_at >= since && log.created_at <= until))
+            .map(|log| AuditLog { action: log.action.clone(), metadata: log.metadata.clone(), ..*log })
+            .collect();
+        if params.order_by.as_deref() != Some("ASC") {
+            logs.reverse();
+        }
+        Ok(paginate(logs, params.pagination))
+    }
+    async fn get_unexported_audit_logs(&self, limit: i64) -> Result<Vec<AuditLog>, RepositoryError> {
+        let state = self.state();
+        Ok(state.audit_logs.iter()
+            .filter(|log| !state.exported.contains(&log.id))
+            .take(limit.max(0) as usize)
+            .map(|log| AuditLog { action: log.action.clone(), metadata: log.metadata.clone(), ..*log })
+            .collect())
+    }
+    async fn mark_audit_logs_exported(&self, ids: &[Uuid]) -> Result<(), RepositoryError> {
+        self.state().exported.extend(ids.iter().copied());
+        Ok(())
+    }
+    /// Failed logs stay pending and are offered again on the next export.
+    async fn mark_audit_logs_failed(&self, _ids: &[Uuid]) -> Result<(), RepositoryError> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EmailOutboxRepository for InMemoryDb {
+    async fn queue_email(&self, to_email: &str, subject: &str, html: &str) -> Result<Uuid, RepositoryError> {
+        let id = Uuid::new_v4();
+        self.state().outbox.push(OutboxEmail {
+            email: QueuedEmail {
+                id,
+                to_email: to_email.to_string(),
+                subject: subject.to_string(),
+                html: html.to_string(),
+                attempts: 0,
+            },
+            pending: true,
+        });
+        Ok(id)
+    }
+    /// Every pending email is due, there is no lease.
+    async fn claim_emails(&self, limit: i64, _lease_secs: f64) -> Result<Vec<QueuedEmail>, RepositoryError> {
+        Ok(self.state().outbox.iter()
+            .filter(|queued| queued.pending)
+            .take(limit.max(0) as usize)
+            .map(|queued| QueuedEmail {
+                to_email: queued.email.to_email.clone(),
+                subject: queued.email.subject.clone(),
+                html: queued.email.html.clone(),
+                ..queued.email
+            })
+            .collect())
+    }
+    async fn mark_email_sent(&self, email_id: Uuid) -> Result<(), RepositoryError> {
+        if let Some(queued) = self.state().outbox.iter_mut().find(|queued| queued.email.id == email_id) {
+            queued.email.attempts += 1;
+            queued.pending = false;
+        }
+        Ok(())
+    }
+    async fn mark_email_failed(&self, email_id: Uuid, _error: &str, retry_at: Option<DateTime<Utc>>) -> Result<(), RepositoryError> {
+        if let Some(queued) = self.state().outbox.iter_mut().find(|queued| queued.email.id == email_id) {
+            queued.email.attempts += 1;
+            queued.pending = retry_at.is_some();
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl FeatureFlagRepository for InMemoryDb {
+    async fn get_feature_flags(&self) -> Result<Vec<FeatureFlag>, RepositoryError> {
+        let mut flags = self.state().feature_flags.clone();
+        flags.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(flags)
+    }
+    async fn save_feature_flag(&self, data: NewFeatureFlag) -> Result<FeatureFlag, RepositoryError> {
+        let mut state = self.state();
+        if state.feature_flags.iter().any(|flag| flag.key == data.key) {
+            return Err(RepositoryError::Conflict { constraint: "feature_flags_pkey".to_string() });
+        }
+        let now = Utc::now();
+        let flag = FeatureFlag {
+            key: data.key,
+            description: data.description,
+            enabled: data.enabled,
+            roles: data.roles.iter().map(|role| role.get_value().to_string()).collect(),
+            percentage: data.percentage,
+            created_by: Some(data.created_by),
+            created_at: now,
+            updated_at: now,
+        };
+        state.feature_flags.push(flag.clone());
+        Ok(flag)
+    }
+    async fn update_feature_flag(&self, key: &str, data: FeatureFlagUpdateRequest) -> Result<FeatureFlag, RepositoryError> {
+        let mut state = self.state();
+        let flag = state.feature_flags.iter_mut().find(|flag| flag.key == key).ok_or(RepositoryError::NotFound)?;
+        if let Some(description) = data.description {
+            flag.description = description;
+        }
+        if let Some(enabled) = data.enabled {
+            flag.enabled = enabled;
+        }
+        if let Some(roles) = data.roles {
+            flag.roles = roles.iter().map(|role| role.get_value().to_string()).collect();
+        }
+        if let Some(percentage) = data.percentage {
+            flag.percentage = percentage;
+        }
+        flag.updated_at = Utc::now();
+        Ok(flag.clone())
+    }
+    async fn delete_feature_flag(&self, key: &str) -> Result<(), RepositoryError> {
+        let mut state = self.state();
+        let count = state.feature_flags.len();
+        state.feature_flags.retain(|flag| flag.key != key);
+        if state.feature_flags.len() == count {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AnnouncementRepository for InMemoryDb {
+    async fn get_announcements(&self) -> Result<Vec<Announcement>, RepositoryError> {
+        let mut announcements = self.state().announcements.clone();
+        announcements.sort_by(|a, b| b.starts_at.cmp(&a.starts_at).then(a.id.cmp(&b.id)));
+        Ok(announcements)
+    }
+    async fn get_current_announcements(&self, now: DateTime<Utc>) -> Result<Vec<Announcement>, RepositoryError> {
+        let mut announcements: Vec<Announcement> = self.state().announcements.iter()
+            .filter(|announcement| announcement.ends_at.is_none_or(|ends_at| ends_at > now))
+            .cloned()
+            .collect();
+        announcements.sort_by(|a, b| b.level.cmp(&a.level).then(b.starts_at.cmp(&a.starts_at)).then(a.id.cmp(&b.id)));
+        Ok(announcements)
+    }
+    async fn get_announcement(&self, id: Uuid) -> Result<Option<Announcement>, RepositoryError> {
+        Ok(self.state().announcements.iter().find(|announcement| announcement.id == id).cloned())
+    }
+    async fn save_announcement(&self, data: NewAnnouncement) -> Result<Announcement, RepositoryError> {
+        let now = Utc::now();
+        let announcement = Announcement {
+            id: Uuid::new_v4(),
+            title: data.title,
+            message: data.message,
+            level: data.level,
+            roles: data.roles.iter().map(|role| role.get_value().to_string()).collect(),
+            starts_at: data.starts_at,
+            ends_at: data.ends_at,
+            created_by: Some(data.created_by),
+            created_at: now,
+            updated_at: now,
+        };
+        self.state().announcements.push(announcement.clone());
+        Ok(announcement)
+    }
+    async fn update_announcement(&self, id: Uuid, data: AnnouncementUpdateRequest) -> Result<Announcement, RepositoryError> {
+        let mut state = self.state();
+        let announcement = state.announcements.iter_mut().find(|announcement| announcement.id == id).ok_or(RepositoryError::NotFound)?;
+        if let Some(title) = data.title {
+            announcement.title = title;
+        }
+        if let Some(message) = data.message {
+            announcement.message = message;
+        }
+        if let Some(level) = data.level {
+            announcement.level = level;
+        }
+        if let Some(roles) = data.roles {
+            announcement.roles = roles.iter().map(|role| role.get_value().to_string()).collect();
+        }
+        if let Some(starts_at) = data.starts_at {
+            announcement.starts_at = starts_at;
+        }
+        if let Some(ends_at) = data.ends_at {
+            announcement.ends_at = ends_at;
+        }
+        announcement.updated_at = Utc::now();
+        Ok(announcement.clone())
+    }
+    async fn delete_announcement(&self, id: Uuid) -> Result<(), RepositoryError> {
+        let mut state = self.state();
+        let count = state.announcements.len();
+        state.announcements.retain(|announcement| announcement.id != id);
+        if state.announcements.len() == count {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TosRepository for InMemoryDb {
+    async fn get_current_tos_version(&self) -> Result<Option<TosVersion>, RepositoryError> {
+        Ok(self.get_tos_versions().await?.into_iter().next())
+    }
+    async fn get_tos_versions(&self) -> Result<Vec<TosVersion>, RepositoryError> {
+        let mut versions = self.state().tos_versions.clone();
+        versions.sort_by_key(|version| std::cmp::Reverse(version.published_at));
+        Ok(versions)
+    }
+    async fn save_tos_version(&self, data: NewTosVersion) -> Result<TosVersion, RepositoryError> {
+        let mut state = self.state();
+        if state.tos_versions.iter().any(|version| version.version == data.version) {
+            return Err(RepositoryError::Conflict { constraint: "tos_versions_version_key".to_string() });
+        }
+        let version = TosVersion {
+            id: Uuid::new_v4(),
+            version: data.version,
+            url: data.url,
+            summary: data.summary,
+            published_by: Some(data.published_by),
+            published_at: Utc::now(),
+        };
+        state.tos_versions.push(version.clone());
+        Ok(version)
+    }
+    async fn has_accepted_tos(&self, user_id: Uuid, tos_version_id: Uuid) -> Result<bool, RepositoryError> {
+        Ok(self.state().tos_acceptances.contains_key(&(user_id, tos_version_id)))
+    }
+    async fn accept_tos(&self, user_id: Uuid, tos_version_id: Uuid) -> Result<DateTime<Utc>, RepositoryError> {
+        Ok(*self.state().tos_acceptances.entry((user_id, tos_version_id)).or_insert_with(Utc::now))
+    }
+}
+
+#[async_trait]
+impl RetentionRepository for InMemoryDb {
+    async fn get_retention_candidates(&self, rule: RetentionRule, cutoff: DateTime<Utc>) -> Result<RetentionCandidates, RepositoryError> {
+        let candidates = self.state().retention_candidates(rule, cutoff);
+        Ok(RetentionCandidates { count: candidates.len() as i64, oldest: candidates.first().map(|(at, _)| *at) })
+    }
+    async fn apply_retention(&self, rule: RetentionRule, cutoff: DateTime<Utc>, limit: i64) -> Result<u64, RepositoryError> {
+        let mut state = self.state();
+        let ids: HashSet<Uuid> = state.retention_candidates(rule, cutoff).into_iter()
+            .take(limit as usize)
+            .map(|(_, id)| id)
+            .collect();
+        match rule {
+            RetentionRule::DeletedComments => state.remove_comments(&ids),
+            RetentionRule::AuditLogs => {
+                for log in state.audit_logs.iter_mut().filter(|log| ids.contains(&log.id)) {
+                    log.actor_id = None;
+                    log.target_user_id = Uuid::nil();
+                    log.metadata = serde_json::Value::Object(Default::default());
+                }
+                state.anonymized.extend(&ids);
+            }
+            RetentionRule::AnalyticsEvents => state.analytics_events.retain(|event| !ids.contains(&event.id)),
+        }
+        Ok(ids.len() as u64)
+    }
+}
+
+#[async_trait]
+impl InvitationRepository for InMemoryDb {
+    async fn save_invitation<'a>(&self, data: NewInvitation<'a>) -> Result<Invitation, RepositoryError> {
+        let invitation = Invitation {
+            id: Uuid::new_v4(),
+            email: data.email.to_string(),
+            token: data.token.to_string(),
+            invited_by: Some(data.invited_by),
+            expires_at: data.expires_at,
+            consumed_at: None,
+            consumed_by: None,
+            created_at: Utc::now(),
+        };
+        self.state().invitations.push(invitation.clone());
+        Ok(invitation)
+    }
+    async fn get_invitation_by_token(&self, token: &str) -> Result<Option<Invitation>, RepositoryError> {
+        Ok(self.state().invitations.iter().find(|invitation| invitation.token == token).cloned())
+    }
+}
+
+#[async_trait]
+impl UserActionTokenRepository for InMemoryDb {
+    async fn get_by_token(&self, token_hash: &str, action_type: ActionType) -> Result<Option<UserActionToken>, RepositoryError> {
+        Ok(self.state().action_tokens.iter()
+            .find(|token| token.token_hash.as_deref() == Some(token_hash) && token.action_type == action_type && token.used_at.is_none())
+            .cloned())
+    }
+    async fn verify_account(&self, user_id: Uuid, user_action_id: Uuid) -> Result<User, RepositoryError> {
+        let mut state = self.state();
+        state.use_action_token(user_action_id)?;
+        let user = state.users.get_mut(&user_id).ok_or(RepositoryError::NotFound)?;
+        user.is_verified = true;
+        user.updated_at = Utc::now();
+        Ok(user.clone())
+    }
+    async fn resend_activation(&self, user_id: Uuid, token_hash: &str, expires_at: DateTime<Utc>) -> Result<UserActionToken, RepositoryError> {
+        let mut state = self.state();
+        let token = state.action_tokens.iter_mut()
+            .find(|token| token.user_id == user_id && token.action_type == ActionType::VerifyAccount)
+            .ok_or(RepositoryError::NotFound)?;
+        token.token_hash = Some(token_hash.to_string());
+        token.expires_at = Some(expires_at);
+        token.used_at = None;
+        token.updated_at = Utc::now();
+        Ok(token.clone())
+    }
+    async fn forgot_password<'a>(&self, user_id: Uuid, user_action_data: NewUserActionToken<'a>) -> Result<UserActionToken, RepositoryError> {
+        let mut state = self.state();
+        let now = Utc::now();
+        let action_type = user_action_data.action_type;
+        let index = match state.action_tokens.iter().position(|token| token.user_id == user_id && token.action_type == action_type) {
+            Some(index) => index,
+            None => {
+                state.action_tokens.push(UserActionToken {
+                    id: Uuid::new_v4(),
+                    user_id,
+                    token_hash: None,
+                    action_type,
+                    used_at: None,
+                    expires_at: None,
+                    created_at: now,
+                    updated_at: now,
+                });
+                state.action_tokens.len() - 1
+            }
+        };
+        let token = &mut state.action_tokens[index];
+        token.token_hash = Some(user_action_data.token_hash.to_string());
+        token.expires_at = Some(user_action_data.expires_at);
+        token.used_at = None;
+        token.updated_at = now;
+        Ok(token.clone())
+    }
+    async fn reset_password(&self, user_id: Uuid, user_action_id: Uuid, new_password: String) -> Result<User, RepositoryError> {
+        let mut state = self.state();
+        state.use_action_token(user_action_id)?;
+        let user = state.users.get_mut(&user_id).ok_or(RepositoryError::NotFound)?;
+        user.password = new_password;
+        user.updated_at = Utc::now();
+        Ok(user.clone())
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for InMemoryDb {
+    async fn save_webhook_endpoint(&self, data: NewWebhookEndpoint) -> Result<WebhookEndpoint, RepositoryError> {
+        let now = Utc::now();
+        let endpoint = WebhookEndpoint {
+            id: Uuid::new_v4(),
+            url: data.url,
+            events: data.events.iter().map(|event| event.get_value().to_string()).collect(),
+            created_by: Some(data.created_by),
+            created_at: now,
+            updated_at: now,
+        };
+        self.state().webhook_endpoints.push((endpoint.clone(), data.secret));
+        Ok(endpoint)
+    }
+    async fn get_webhook_endpoints(&self) -> Result<Vec<WebhookEndpoint>, RepositoryError> {
+        Ok(self.state().webhook_endpoints.iter().map(|(endpoint, _)| endpoint.clone()).collect())
+    }
+    async fn delete_webhook_endpoint(&self, endpoint_id: Uuid) -> Result<(), RepositoryError> {
+        let mut state = self.state();
+        let count = state.webhook_endpoints.len();
+        state.webhook_endpoints.retain(|(endpoint, _)| endpoint.id != endpoint_id);
+        if state.webhook_endpoints.len() == count {
+            return Err(RepositoryError::NotFound);
+        }
+        state.webhook_deliveries.retain(|delivery| delivery.endpoint_id != endpoint_id);
+        Ok(())
+    }
+    async fn queue_webhook_event(&self, event: WebhookEvent, data: serde_json::Value) -> Result<u64, RepositoryError> {
+        let mut state = self.state();
+        let now = Utc::now();
+        let endpoint_ids: Vec<Uuid> = state.webhook_endpoints.iter()
+            .filter(|(endpoint, _)| endpoint.events.iter().any(|name| name == event.get_value()))
+            .map(|(endpoint, _)| endpoint.id)
+            .collect();
+        for endpoint_id in &endpoint_ids {
+            state.webhook_deliveries.push(WebhookDelivery {
+                id: Uuid::new_v4(),
+                endpoint_id: *endpoint_id,
+                event: event.get_value().to_string(),
+                payload: data.clone(),
+                status: WebhookDeliveryStatus::Pending,
+                attempts: 0,
+                next_attempt_at: now,
+                last_status_code: None,
+                last_error: None,
+                delivered_at: None,
+                created_at: now,
+                updated_at: now,
+            });
+        }
+        Ok(endpoint_ids.len() as u64)
+    }
+    async fn claim_webhook_deliveries(&self, limit: i64, lease_secs: f64) -> Result<Vec<DueWebhookDelivery>, RepositoryError> {
+        let mut state = self.state();
+        let now = Utc::now();
+        let State { webhook_endpoints, webhook_deliveries, .. } = &mut *state;
+        let mut due: Vec<&mut WebhookDelivery> = webhook_deliveries.iter_mut()
+            .filter(|delivery| delivery.status == WebhookDeliveryStatus::Pending && delivery.next_attempt_at <= now)
+            .collect();
+        due.sort_by_key(|delivery| delivery.next_attempt_at);
+        let mut claimed = Vec::new();
+        for delivery in due.into_iter().take(limit as usize) {
+            let Some((endpoint, secret)) = webhook_endpoints.iter().find(|(endpoint, _)| endpoint.id == delivery.endpoint_id) else {
+                continue;
+            };
+            delivery.next_attempt_at = now + chrono::Duration::milliseconds((lease_secs * 1000.0) as i64);
+            delivery.updated_at = now;
+            claimed.push(DueWebhookDelivery {
+                id: delivery.id,
+                event: delivery.event.clone(),
+                payload: delivery.payload.clone(),
+                attempts: delivery.attempts,
+                created_at: delivery.created_at,
+                url: endpoint.url.clone(),
+                secret: secret.clone(),
+            });
+        }
+        Ok(claimed)
+    }
+    async fn mark_webhook_delivered(&self, delivery_id: Uuid, status_code: i32) -> Result<(), RepositoryError> {
+        let now = Utc::now();
+        if let Some(delivery) = self.state().webhook_deliveries.iter_mut().find(|delivery| delivery.id == delivery_id) {
+            delivery.status = WebhookDeliveryStatus::Delivered;
+            delivery.attempts += 1;
+            delivery.last_status_code = Some(status_code);
+            delivery.last_error = None;
+            delivery.delivered_at = Some(now);
+            delivery.updated_at = now;
+        }
+        Ok(())
+    }
+    async fn mark_webhook_failed(&self, delivery_id: Uuid, status_code: Option<i32>, error: &str, retry_at: Option<DateTime<Utc>>) -> Result<(), RepositoryError> {
+        if let Some(delivery) = self.state().webhook_deliveries.iter_mut().find(|delivery| delivery.id == delivery_id) {
+            delivery.status = match retry_at {
+                Some(_) => WebhookDeliveryStatus::Pending,
+                None => WebhookDeliveryStatus::Dead,
+            };
+            delivery.attempts += 1;
+            delivery.last_status_code = status_code;
+            delivery.last_error = Some(error.to_string());
+            delivery.next_attempt_at = retry_at.unwrap_or(delivery.next_attempt_at);
+            delivery.updated_at = Utc::now();
+        }
+        Ok(())
+    }
+    async fn get_webhook_deliveries(&self, params: WebhookDeliveryListParams) -> Result<PaginatedData<WebhookDelivery>, RepositoryError> {
+        let mut deliveries: Vec<WebhookDelivery> = self.state().webhook_deliveries.iter()
+            .filter(|delivery| params.endpoint_id.is_none_or(|endpoint_id| delivery.endpoint_id == endpoint_id))
+            .filter(|delivery| params.status.is_none_or(|status| delivery.status == status))
+            .filter(|delivery| params.event.is_none_or(|event| delivery.event == event.get_value()))
+            .cloned()
+            .collect();
+        deliveries.sort_by_key(|delivery| delivery.created_at);
+        if params.order_by.as_deref() != Some("ASC") {
+            deliveries.reverse();
+        }
+        Ok(paginate(deliveries, params.pagination))
+    }
+    async fn retry_webhook_delivery(&self, delivery_id: Uuid) -> Result<WebhookDelivery, RepositoryError> {
+        let mut state = self.state();
+        let delivery = state.webhook_deliveries.iter_mut()
+            .find(|delivery| delivery.id == delivery_id && delivery.status == WebhookDeliveryStatus::Dead)
+            .ok_or(RepositoryError::NotFound)?;
+        delivery.status = WebhookDeliveryStatus::Pending;
+        delivery.attempts = 0;
+        delivery.next_attempt_at = Utc::now();
+        delivery.updated_at = Utc::now();
+        Ok(delivery.clone())
+    }
+}
+
+#[async_trait]
+impl RefreshTokenRepository for InMemoryDb {
+    async fn refresh_token(&self, user_id: Uuid, token: &str, expires_at: DateTime<Utc>) -> Result<(), RepositoryError> {
+        let now = Utc::now();
+        let mut state = self.state();
+        let created_at = state.refresh_tokens.get(&user_id).map_or(now, |refresh_token| refresh_token.created_at);
+        state.refresh_tokens.insert(user_id, RefreshToken {
+            user_id,
+            token: token.to_string(),
+            revoked: false,
+            expires_at,
+            created_at,
+            updated_at: now,
+        });
+        Ok(())
+    }
+    async fn revoke_token(&self, user_id: Uuid) -> Result<(), RepositoryError> {
+        if let Some(refresh_token) = self.state().refresh_tokens.get_mut(&user_id) {
+            refresh_token.revoked = true;
+            refresh_token.updated_at = Utc::now();
+        }
+        Ok(())
+    }
+    async fn get_refresh_token(&self, token: &str) -> Result<Option<RefreshToken>, RepositoryError> {
+        Ok(self.state().refresh_tokens.values().find(|refresh_token| refresh_token.token == token).cloned())
+    }
+    async fn get_session_expiry(&self, user_id: Uuid) -> Result<Option<DateTime<Utc>>, RepositoryError> {
+        Ok(self.state().refresh_tokens.get(&user_id)
+            .filter(|refresh_token| !refresh_token.revoked && refresh_token.expires_at > Utc::now())
+            .map(|refresh_token| refresh_token.expires_at))
+    }
+}
+
+/// Accounts added in memory never wait on the waitlist.
+#[async_trait]
+impl WaitlistRepository for InMemoryDb {
+    async fn get_waitlist_position(&self, _user_id: Uuid) -> Result<Option<i64>, RepositoryError> {
+        Ok(None)
+    }
+    async fn approve_waitlist(&self, _count: i64) -> Result<WaitlistApproval, RepositoryError> {
+        Ok(WaitlistApproval {
+            approved: Vec::new(),
+            remaining: 0,
+        })
+    }
+}
+
+#[async_trait]
+impl SessionRepository for InMemoryDb {
+    async fn record_session<'a>(&self, data: NewUserSession<'a>) -> Result<SessionSighting, RepositoryError> {
+        let mut state = self.state();
+        let known_devices = state.sessions.iter().filter(|session| session.user_id == data.user_id).count() as i64;
+        let known = state.sessions.iter_mut().find(|session| {
+            session.user_id == data.user_id
+                && session.ip_address == data.device.ip_address
+                && session.user_agent == data.device.user_agent
+        });
+        if let Some(session) = known {
+            session.last_seen_at = Utc::now();
+            return Ok(SessionSighting { is_new: false, known_devices });
+        }
+        let now = Utc::now();
+        state.sessions.push(UserSession {
+            id: Uuid::new_v4(),
+            user_id: data.user_id,
+            ip_address: data.device.ip_address.clone(),
+            user_agent: data.device.user_agent.clone(),
+            revoke_token_hash: data.revoke_token_hash.to_string(),
+            created_at: now,
+            last_seen_at: now,
+        });
+        Ok(SessionSighting { is_new: true, known_devices })
+    }
+    async fn revoke_session(&self, revoke_token_hash: &str) -> Result<Option<UserSession>, RepositoryError> {
+        let mut state = self.state();
+        let position = state.sessions.iter().position(|session| session.revoke_token_hash == revoke_token_hash);
+        Ok(position.map(|position| state.sessions.remove(position)))
+    }
+}
+
+#[async_trait]
+impl VelocityLimitRepository for InMemoryDb {
+    async fn get_velocity_limits(&self) -> Result<Vec<VelocityLimit>, RepositoryError> {
+        Ok(self.state().velocity_limits.clone())
+    }
+    async fn update_velocity_limit(&self, kind: VelocityKind, data: VelocityLimitUpdateRequest, admin_id: Uuid) -> Result<VelocityLimit, RepositoryError> {
+        let mut state = self.state();
+        let limit = state.velocity_limits.iter_mut().find(|limit| limit.kind == kind).ok_or(RepositoryError::NotFound)?;
+        if let Some(max_count) = data.max_count {
+            limit.max_count = max_count;
+        }
+        if let Some(window_secs) = data.window_secs {
+            limit.window_secs = window_secs;
+        }
+        if let Some(action) = data.action {
+            limit.action = action;
+        }
+        limit.updated_by = Some(admin_id);
+        limit.updated_at = Utc::now();
+        Ok(limit.clone())
+    }
+}
+
+#[async_trait]
+impl DataExportRepository for InMemoryDb {
+    async fn save_data_export(&self, data: NewDataExport) -> Result<DataExport, RepositoryError> {
+        let now = Utc::now();
+        let export = DataExport {
+            id: Uuid::new_v4(),
+            tables: data.tables.iter().map(|table| table.get_value().to_string()).collect(),
+            format: data.format.get_value().to_string(),
+            organization_id: data.organization_id,
+            status: DataExportStatus::Pending,
+            files: Json(Vec::new()),
+            rows_total: None,
+            rows_exported: 0,
+            bytes_exported: 0,
+            error: None,
+            requested_by: Some(data.requested_by),
+            created_at: now,
+            started_at: None,
+            finished_at: None,
+            updated_at: now,
+        };
+        self.state().data_exports.push(export.clone());
+        Ok(export)
+    }
+    async fn get_data_export(&self, id: Uuid) -> Result<Option<DataExport>, RepositoryError> {
+        Ok(self.state().data_exports.iter().find(|export| export.id == id).cloned())
+    }
+    async fn get_data_exports(&self, limit: i64) -> Result<Vec<DataExport>, RepositoryError> {
+        Ok(self.state().data_exports.iter().rev().take(limit as usize).cloned().collect())
+    }
+    async fn claim_data_export(&self, stale_secs: i64) -> Result<Option<DataExport>, RepositoryError> {
+        let now = Utc::now();
+        let stale = now - chrono::Duration::seconds(stale_secs);
+        let mut state = self.state();
+        let Some(export) = state.data_exports.iter_mut().find(|export| {
+            export.status == DataExportStatus::Pending || (export.status == DataExportStatus::Running && export.updated_at < stale)
+        }) else {
+            return Ok(None);
+        };
+        export.status = DataExportStatus::Running;
+        export.files = Json(Vec::new());
+        export.rows_total = None;
+        export.rows_exported = 0;
+        export.bytes_exported = 0;
+        export.started_at = Some(now);
+        export.updated_at = now;
+        Ok(Some(export.clone()))
+    }
+    async fn update_data_export_progress(&self, id: Uuid, progress: &DataExportProgress) -> Result<(), RepositoryError> {
+        if let Some(export) = self.state().data_exports.iter_mut().find(|export| export.id == id) {
+            export.files = Json(progress.files.clone());
+            export.rows_total = Some(progress.rows_total);
+            export.rows_exported = progress.rows_exported;
+            export.bytes_exported = progress.bytes_exported;
+            export.updated_at = Utc::now();
+        }
+        Ok(())
+    }
+    async fn finish_data_export(&self, id: Uuid, error: Option<String>) -> Result<(), RepositoryError> {
+        if let Some(export) = self.state().data_exports.iter_mut().find(|export| export.id == id) {
+            export.status = if error.is_some() { DataExportStatus::Failed } else { DataExportStatus::Completed };
+            export.error = error;
+            export.finished_at = Some(Utc::now());
+            export.updated_at = Utc::now();
+        }
+        Ok(())
+    }
+    /// Only `users` is dumped here, as JSON objects of every column or as their id, name and email
+    /// in CSV. The other tables come out empty, and so does everything of an organization.
+    fn dump_tables(&self, tables: Vec<ExportTable>, format: ExportFormat, organization_id: Option<Uuid>) -> RowStream<DumpChunk> {
+        let tables: Vec<ExportTable> = match organization_id {
+            Some(organization_id) => tables.into_iter().filter(|table| table.organization_filter(organization_id).is_some()).collect(),
+            None => tables,
+        };
+        let mut users: Vec<User> = self.state().users.values().cloned().collect();
+        users.sort_by_key(|user| (user.created_at, user.id));
+        let mut chunks = vec![DumpChunk::Total(if tables.contains(&ExportTable::Users) { users.len() as i64 } else { 0 })];
+        for table in tables {
+            if table != ExportTable::Users {
+                chunks.push(DumpChunk::Table(table, 0));
+                continue;
+            }
+            chunks.push(DumpChunk::Table(table, users.len() as i64));
+            if format == ExportFormat::Csv {
+                chunks.push(DumpChunk::Data(Bytes::from_static(b"id,name,email\n")));
+            }
+            for user in &users {
+                let line = match format {
+                    ExportFormat::Ndjson => format!("{}\n", serde_json::to_string(user).expect("serializable user")),
+                    ExportFormat::Csv => format!("{},{},{}\n", user.id, csv::escape_field(&user.name), csv::escape_field(&user.email)),
+                };
+                chunks.push(DumpChunk::Data(Bytes::from(line)));
+            }
+        }
+        Box::pin(stream::iter(chunks.into_iter().map(Ok)))
+    }
+}
+
+#[async_trait]
+impl FeedImportRepository for InMemoryDb {
+    async fn save_feed_source(&self, user_id: Uuid, url: &str) -> Result<FeedSource, RepositoryError> {
+        let mut state = self.state();
+        if state.feed_sources.iter().any(|source| source.user_id == user_id && source.url == url) {
+            return Err(RepositoryError::Conflict { constraint: "feed_sources_user_id_url_key".to_string() });
+        }
+        let now = Utc::now();
+        let source = FeedSource {
+            id: Uuid::new_v4(),
+            user_id,
+            url: url.to_string(),
+            title: None,
+            etag: None,
+            last_modified: None,
+            last_fetched_at: None,
+            last_error: None,
+            failure_count: 0,
+            next_fetch_at: now,
+            created_at: now,
+            updated_at: now,
+        };
+        state.feed_sources.push(source.clone());
+        Ok(source)
+    }
+    async fn count_feed_sources(&self, user_id: Uuid) -> Result<i64, RepositoryError> {
+        Ok(self.state().feed_sources.iter().filter(|source| source.user_id == user_id).count() as i64)
+    }
+    async fn get_feed_sources(&self, user_id: Uuid) -> Result<Vec<FeedSource>, RepositoryError> {
+        Ok(self.state().feed_sources.iter().filter(|source| source.user_id == user_id).cloned().collect())
+    }
+    async fn delete_feed_source(&self, id: Uuid, user_id: Uuid) -> Result<bool, RepositoryError> {
+        let mut state = self.state();
+        let count = state.feed_sources.len();
+        state.feed_sources.retain(|source| !(source.id == id && source.user_id == user_id));
+        if state.feed_sources.len() == count {
+            return Ok(false);
+        }
+        state.feed_drafts.retain(|draft| draft.source_id != id);
+        Ok(true)
+    }
+    async fn claim_feed_sources(&self, limit: i64, lease_secs: i64) -> Result<Vec<FeedSource>, RepositoryError> {
+        let now = Utc::now();
+        let mut state = self.state();
+        let banned: HashSet<Uuid> = state.users.values().filter(|user| user.is_banned).map(|user| user.id).collect();
+        let mut due: Vec<&mut FeedSource> = state.feed_sources.iter_mut()
+            .filter(|source| source.next_fetch_at <= now && !banned.contains(&source.user_id))
+            .collect();
+        due.sort_by_key(|source| source.next_fetch_at);
+        Ok(due.into_iter().take(limit as usize)
+            .map(|source| {
+                source.next_fetch_at = now + chrono::Duration::seconds(lease_secs);
+                source.clone()
+            })
+            .collect())
+    }
+    async fn record_feed_success(&self, id: Uuid, fetch: FeedFetch) -> Result<(), RepositoryError> {
+        if let Some(source) = self.state().feed_sources.iter_mut().find(|source| source.id == id) {
+            source.title = fetch.title.or(source.title.take());
+            source.etag = fetch.etag;
+            source.last_modified = fetch.last_modified;
+            source.next_fetch_at = fetch.next_fetch_at;
+            source.last_fetched_at = Some(Utc::now());
+            source.last_error = None;
+            source.failure_count = 0;
+            source.updated_at = Utc::now();
+        }
+        Ok(())
+    }
+    async fn record_feed_failure(&self, id: Uuid, error: &str, next_fetch_at: DateTime<Utc>) -> Result<(), RepositoryError> {
+        if let Some(source) = self.state().feed_sources.iter_mut().find(|source| source.id == id) {
+            source.last_error = Some(error.to_string());
+            source.next_fetch_at = next_fetch_at;
+            source.last_fetched_at = Some(Utc::now());
+            source.failure_count += 1;
+            source.updated_at = Utc::now();
+        }
+        Ok(())
+    }
+    async fn save_feed_drafts(&self, source_id: Uuid, user_id: Uuid, drafts: Vec<NewFeedDraft>) -> Result<u64, RepositoryError> {
+        let mut state = self.state();
+        let mut saved = 0;
+        for draft in drafts {
+            if state.feed_drafts.iter().any(|known| known.source_id == source_id && known.guid == draft.guid) {
+                continue;
+            }
+            let now = Utc::now();
+            state.feed_drafts.push(FeedDraft {
+                id: Uuid::new_v4(),
+                source_id,
+                user_id,
+                guid: draft.guid,
+                link: draft.link,
+                title: draft.title,
+                content: draft.content,
+                tags: draft.tags,
+                status: FeedDraftStatus::Draft,
+                post_id: None,
+                entry_published_at: draft.entry_published_at,
+                created_at: now,
+                updated_at: now,
+            });
+            saved += 1;
+        }
+        Ok(saved)
+    }
+    async fn get_feed_drafts(&self, user_id: Uuid, limit: i64) -> Result<Vec<FeedDraft>, RepositoryError> {
+        Ok(self.state().feed_drafts.iter().rev()
+            .filter(|draft| draft.user_id == user_id && draft.status == FeedDraftStatus::Draft)
+            .take(limit as usize)
+            .cloned()
+            .collect())
+    }
+    async fn get_feed_draft(&self, id: Uuid, user_id: Uuid) -> Result<Option<FeedDraft>, RepositoryError> {
+        Ok(self.state().feed_drafts.iter().find(|draft| draft.id == id && draft.user_id == user_id).cloned())
+    }
+    async fn take_feed_draft(&self, id: Uuid, user_id: Uuid, status: FeedDraftStatus) -> Result<Option<FeedDraft>, RepositoryError> {
+        let mut state = self.state();
+        let Some(draft) = state.feed_drafts.iter_mut()
+            .find(|draft| draft.id == id && draft.user_id == user_id && draft.status == FeedDraftStatus::Draft)
+        else {
+            return Ok(None);
+        };
+        draft.status = status;
+        draft.updated_at = Utc::now();
+        Ok(Some(draft.clone()))
+    }
+    async fn release_feed_draft(&self, id: Uuid, post_id: Option<Uuid>) -> Result<(), RepositoryError> {
+        if let Some(draft) = self.state().feed_drafts.iter_mut().find(|draft| draft.id == id) {
+            if post_id.is_none() {
+                draft.status = FeedDraftStatus::Draft;
+            }
+            draft.post_id = post_id;
+            draft.updated_at = Utc::now();
+        }
+        Ok(())
+    }
+}
+#[async_trait]
+impl PostRepository for InMemoryDb {
+    async fn save_post(&self, data: NewPost) -> Result<Post, RepositoryError> {
+        let mut state = self.state();
+        let default_license = state.users.get(&data.user_id).ok_or(RepositoryError::NotFound)?.default_license;
+        if data.organization_id.is_some_and(|organization_id| state.organization(organization_id).is_none()) {
+            return Err(RepositoryError::NotFound);
+        }
+        let now = Utc::now();
+        let post = Post {
+            id: Uuid::new_v4(),
+            user_id: data.user_id,
+            organization_id: data.organization_id,
+            title: data.title,
+            tags: hashtag::merge(data.tags, &data.content),
+            content: data.content,
+            license: data.license.unwrap_or(default_license),
+            views_count: 0,
+            created_at: now,
+            updated_at: now,
+        };
+        state.posts.push(PostRow { post: post.clone(), is_hidden: false });
+        Ok(post)
+    }
+    async fn get_post_detail(&self, post_id: Uuid) -> Result<Option<PostDetail>, RepositoryError> {
+        let state = self.state();
+        let Some(post) = state.post(post_id).filter(|row| !row.is_hidden).map(|row| &row.post) else {
+            return Ok(None);
+        };
+        let user = state.users.get(&post.user_id).ok_or(RepositoryError::NotFound)?;
+        Ok(Some(PostDetail {
+            id: post.id,
+            title: post.title.clone(),
+            content: post.content.clone(),
+            tags: post.tags.clone(),
+            license: post.license,
+            views_count: post.views_count,
+            created_at: post.created_at,
+            updated_at: post.updated_at,
+            user: state.user_response(user),
+            comments: state.visible_comments(post.id)
+                .map(|comment| PostComment {
+                    id: comment.id,
+                    user_id: comment.user_id,
+                    content: comment.content.clone(),
+                    created_at: comment.created_at,
+                    updated_at: comment.updated_at,
+                })
+                .collect(),
+        }))
+    }
+    async fn get_post_list_by_user(&self, user_id: Uuid) -> Result<Option<PostListByUser>, RepositoryError> {
+        let state = self.state();
+        let Some(user) = state.users.get(&user_id) else {
+            return Ok(None);
+        };
+        let posts = state.posts.iter()
+            .filter(|row| row.post.user_id == user_id && !row.is_hidden)
+            .map(|row| PostUser {
+                id: row.post.id,
+                title: row.post.title.clone(),
+                content: row.post.content.clone(),
+                tags: row.post.tags.clone(),
+                license: row.post.license,
+                views_count: row.post.views_count,
+                created_at: row.post.created_at,
+                updated_at: row.post.updated_at,
+            })
+            .collect();
+        Ok(Some(PostListByUser {
+            user: UserPost {
+                id: user.id,
+                name: user.name.clone(),
+                email: user.email.clone(),
+                role: state.role_name(user.role_id).ok_or(RepositoryError::NotFound)?,
+                is_verified: user.is_verified,
+            },
+            posts,
+        }))
+    }
+    async fn get_posts_by_ids(&self, post_ids: &[Uuid], viewer_id: Uuid) -> Result<Vec<Post>, RepositoryError> {
+        let state = self.state();
+        Ok(post_ids.iter()
+            .filter_map(|post_id| state.post(*post_id))
+            .filter(|row| !row.is_hidden && state.is_visible(row.post.user_id, viewer_id))
+            .map(|row| row.post.clone())
+            .collect())
+    }
+    async fn get_post_organization_id(&self, post_id: Uuid) -> Result<Option<Uuid>, RepositoryError> {
+        Ok(self.state().post(post_id).and_then(|row| row.post.organization_id))
+    }
+    async fn update_post(&self, post_id: Uuid, user_id: Uuid, user_role_id: Uuid, patch: PostPatchRequest, expected_version: Option<DateTime<Utc>>) -> Result<Post, RepositoryError> {
+        let mut state = self.state();
+        let current = state.post(post_id).ok_or(RepositoryError::NotFound)?.post.clone();
+        if !state.can_edit_post(&current, user_id, user_role_id)? {
+            return Err(RepositoryError::Forbidden);
+        }
+        if expected_version.is_some_and(|version| version != current.updated_at) {
+            return Err(RepositoryError::version_mismatch(&current));
+        }
+        let tags = hashtag::merge(patch.tags.unwrap_or(current.tags), patch.content.as_deref().unwrap_or(&current.content));
+        let post = &mut state.post_mut(post_id).expect("post read above").post;
+        post.title = patch.title.unwrap_or(current.title);
+        post.content = patch.content.unwrap_or(current.content);
+        post.tags = tags;
+        post.license = patch.license.unwrap_or(current.license);
+        post.updated_at = Utc::now();
+        Ok(post.clone())
+    }
+    async fn delete_post(&self, post_id: Uuid, user_id: Uuid, user_role_id: Uuid) -> Result<Post, RepositoryError> {
+        let mut state = self.state();
+        let post = state.post(post_id).ok_or(RepositoryError::NotFound)?.post.clone();
+        let is_organization_owner = post.organization_id
+            .is_some_and(|organization_id| state.member_role(organization_id, user_id) == Some(OrganizationRole::Owner));
+        if post.user_id != user_id && !is_organization_owner && !state.is_admin(user_role_id)? {
+            return Err(RepositoryError::Forbidden);
+        }
+        state.remove_posts(&HashSet::from([post_id]));
+        Ok(post)
+    }
+    async fn get_post_collaborators(&self, post_id: Uuid, user_id: Uuid, user_role_id: Uuid) -> Result<Vec<PostCollaborator>, RepositoryError> {
+        let state = self.state();
+        let post = &state.post(post_id).ok_or(RepositoryError::NotFound)?.post;
+        if !state.can_edit_post(post, user_id, user_role_id)? {
+            return Err(RepositoryError::Forbidden);
+        }
+        let mut collaborators: Vec<PostCollaborator> = state.post_collaborators.keys()
+            .filter(|(collaborated_post_id, _)| *collaborated_post_id == post_id)
+            .filter_map(|(_, collaborator_id)| state.collaborator(post_id, *collaborator_id))
+            .collect();
+        collaborators.sort_by_key(|collaborator| (collaborator.created_at, collaborator.user_id));
+        Ok(collaborators)
+    }
+    async fn add_post_collaborator(&self, post_id: Uuid, collaborator_id: Uuid, user_id: Uuid, user_role_id: Uuid) -> Result<PostCollaborator, RepositoryError> {
+        let mut state = self.state();
+        let author_id = state.post(post_id).ok_or(RepositoryError::NotFound)?.post.user_id;
+        if author_id != user_id && !state.is_admin(user_role_id)? {
+            return Err(RepositoryError::Forbidden);
+        }
+        if collaborator_id == author_id {
+            return Err(RepositoryError::InvalidInput("The author of a post can't be its collaborator".to_string()));
+        }
+        if state.users.get(&collaborator_id).is_none_or(|user| user.is_banned) {
+            return Err(RepositoryError::NotFound);
+        }
+        if state.post_collaborators.contains_key(&(post_id, collaborator_id)) {
+            return Err(RepositoryError::Conflict { constraint: "post_collaborators_pkey".to_string() });
+        }
+        state.post_collaborators.insert((post_id, collaborator_id), (Some(user_id), Utc::now()));
+        Ok(state.collaborator(post_id, collaborator_id).expect("collaborator added above"))
+    }
+    async fn remove_post_collaborator(&self, post_id: Uuid, collaborator_id: Uuid, user_id: Uuid, user_role_id: Uuid) -> Result<(), RepositoryError> {
+        let mut state = self.state();
+        let author_id = state.post(post_id).ok_or(RepositoryError::NotFound)?.post.user_id;
+        if author_id != user_id && collaborator_id != user_id && !state.is_admin(user_role_id)? {
+            return Err(RepositoryError::Forbidden);
+        }
+        state.post_collaborators.remove(&(post_id, collaborator_id)).ok_or(RepositoryError::NotFound)?;
+        Ok(())
+    }
+    fn stream_posts(&self) -> RowStream<Post> {
+        let mut posts: Vec<Post> = self.state().posts.iter().map(|row| row.post.clone()).collect();
+        posts.sort_by_key(|post| (post.created_at, post.id));
+        Box::pin(stream::iter(posts.into_iter().map(Ok)))
+    }
+    async fn add_post_views(&self, views: &[(Uuid, i64)]) -> Result<(), RepositoryError> {
+        let mut state = self.state();
+        let hour = Utc::now().duration_trunc(chrono::Duration::hours(1)).expect("current time truncated to the hour");
+        for (post_id, count) in views {
+            let Some(row) = state.post_mut(*post_id) else {
+                continue;
+            };
+            row.post.views_count += count;
+            *state.post_views.entry((*post_id, hour)).or_default() += count;
+        }
+        Ok(())
+    }
+    async fn get_syndicated_posts_by_user(&self, user_id: Uuid, limit: i64) -> Result<Vec<SyndicatedPost>, RepositoryError> {
+        let state = self.state();
+        let mut posts: Vec<SyndicatedPost> = state.posts.iter()
+            .filter(|row| row.post.user_id == user_id && !row.is_hidden)
+            .filter_map(|row| state.syndicated_post(&row.post))
+            .collect();
+        posts.sort_by_key(|post| std::cmp::Reverse((post.created_at, post.id)));
+        posts.truncate(limit as usize);
+        Ok(posts)
+    }
+    async fn get_syndicated_posts_by_tag(&self, tag: &str, limit: i64) -> Result<Vec<SyndicatedPost>, RepositoryError> {
+        let state = self.state();
+        let mut posts: Vec<SyndicatedPost> = state.posts.iter()
+            .filter(|row| !row.is_hidden && row.post.tags.iter().any(|post_tag| post_tag == tag))
+            .filter(|row| state.users.get(&row.post.user_id).is_some_and(|author| !author.is_private && !author.is_banned))
+            .filter_map(|row| state.syndicated_post(&row.post))
+            .collect();
+        posts.sort_by_key(|post| std::cmp::Reverse((post.created_at, post.id)));
+        posts.truncate(limit as usize);
+        Ok(posts)
+    }
+}
+
+#[async_trait]
+impl CommentRepository for InMemoryDb {
+    async fn save_comment(&self, post_id: Uuid, data: NewComment) -> Result<Comment, RepositoryError> {
+        let mut state = self.state();
+        state.post(post_id).filter(|row| !row.is_hidden).ok_or(RepositoryError::NotFound)?;
+        let now = Utc::now();
+        let comment = Comment {
+            id: Uuid::new_v4(),
+            user_id: data.user_id,
+            post_id: data.post_id,
+            content: data.content,
+            created_at: now,
+            updated_at: now,
+        };
+        state.comments.push(CommentRow { comment: comment.clone(), is_hidden: false, deleted_at: None });
+        Ok(comment)
+    }
+    async fn get_comment_detail(&self, post_id: Uuid, comment_id: Uuid) -> Result<Option<CommentDetail>, RepositoryError> {
+        let state = self.state();
+        let Some(comment) = state.comment(comment_id).filter(|row| row.comment.post_id == post_id && !row.is_hidden).map(|row| &row.comment) else {
+            return Ok(None);
+        };
+        let Some(post) = state.post(post_id).filter(|row| !row.is_hidden).map(|row| &row.post) else {
+            return Ok(None);
+        };
+        Ok(Some(CommentDetail {
+            id: comment.id,
+            user_id: comment.user_id,
+            post_id: comment.post_id,
+            content: comment.content.clone(),
+            created_at: comment.created_at,
+            updated_at: comment.updated_at,
+            post: post.clone(),
+        }))
+    }
+    async fn get_comments_by_post(&self, post_id: Uuid) -> Result<CommentsByPost, RepositoryError> {
+        let state = self.state();
+        let post = state.post(post_id).filter(|row| !row.is_hidden).ok_or(RepositoryError::NotFound)?.post.clone();
+        Ok(CommentsByPost {
+            post,
+            comments: state.visible_comments(post_id).cloned().collect(),
+        })
+    }
+    async fn update_comment(&self, comment_id: Uuid, user_id: Uuid, user_role_id: Uuid, content: String, expected_version: Option<DateTime<Utc>>) -> Result<Comment, RepositoryError> {
+        let mut state = self.state();
+        let current = state.comment(comment_id).filter(|row| row.deleted_at.is_none()).ok_or(RepositoryError::NotFound)?.comment.clone();
+        if current.user_id != user_id && !state.is_admin(user_role_id)? {
+            return Err(RepositoryError::Forbidden);
+        }
+        if expected_version.is_some_and(|version| version != current.updated_at) {
+            return Err(RepositoryError::version_mismatch(&current));
+        }
+        let row = state.comments.iter_mut().find(|row| row.comment.id == comment_id).expect("comment read above");
+        row.comment.content = content;
+        row.comment.updated_at = Utc::now();
+        Ok(row.comment.clone())
+    }
+    async fn delete_comment(&self, comment_id: Uuid, user_id: Uuid, user_role_id: Uuid, soft: bool) -> Result<Uuid, RepositoryError> {
+        let mut state = self.state();
+        let comment = state.comment(comment_id).filter(|row| row.deleted_at.is_none()).ok_or(RepositoryError::NotFound)?.comment.clone();
+        let post_user_id = state.post(comment.post_id).ok_or(RepositoryError::NotFound)?.post.user_id;
+        if comment.user_id != user_id && !state.is_admin(user_role_id)? {
+            return Err(RepositoryError::Forbidden);
+        }
+        if soft || state.has_open_reports(comment_id) {
+            let row = state.comments.iter_mut().find(|row| row.comment.id == comment_id).expect("comment read above");
+            let now = Utc::now();
+            row.is_hidden = true;
+            row.deleted_at = Some(now);
+            row.comment.updated_at = now;
+        } else {
+            state.remove_comments(&HashSet::from([comment_id]));
+        }
+        Ok(post_user_id)
+    }
+}
+
+#[async_trait]
+impl ReportRepository for InMemoryDb {
+    async fn save_report(&self, data: NewReport) -> Result<Report, RepositoryError> {
+        let mut state = self.state();
+        let (post_id, comment_id) = match data.target {
+            ReportTarget::Post(post_id) => {
+                state.post(post_id).filter(|row| !row.is_hidden).ok_or(RepositoryError::NotFound)?;
+                (Some(post_id), None)
+            }
+            ReportTarget::Comment(comment_id) => {
+                state.comment(comment_id).filter(|row| !row.is_hidden).ok_or(RepositoryError::NotFound)?;
+                (None, Some(comment_id))
+            }
+        };
+        let now = Utc::now();
+        let report = Report {
+            id: Uuid::new_v4(),
+            reporter_id: data.reporter_id,
+            post_id,
+            comment_id,
+            reason: data.reason,
+            details: data.details,
+            status: ReportStatus::Open,
+            resolved_by: None,
+            resolved_at: None,
+            created_at: now,
+            updated_at: now,
+        };
+        state.reports.push(report.clone());
+        Ok(report)
+    }
+    async fn get_reports(&self, params: ReportListParams) -> Result<PaginatedData<Report>, RepositoryError> {
+        let mut reports: Vec<Report> = self.state().reports.iter()
+            .filter(|report| params.status.is_none_or(|status| report.status == status))
+            .filter(|report| params.reason.is_none_or(|reason| report.reason == reason))
+            .cloned()
+            .collect();
+        reports.sort_by_key(|report| report.created_at);
+        if params.order_by.as_deref() != Some("ASC") {
+            reports.reverse();
+        }
+        Ok(paginate(reports, params.pagination))
+    }
+    async fn dismiss_report(&self, report_id: Uuid, admin_id: Uuid) -> Result<ReportResolution, RepositoryError> {
+        let mut state = self.state();
+        let report = state.reports.iter_mut()
+            .find(|report| report.id == report_id && report.status == ReportStatus::Open)
+            .ok_or(RepositoryError::NotFound)?;
+        resolve(report, ReportStatus::Dismissed, admin_id);
+        let report = report.clone();
+        let author_id = report.post_id.and_then(|post_id| state.post(post_id)).map(|row| row.post.user_id)
+            .or_else(|| report.comment_id.and_then(|comment_id| state.comment(comment_id)).map(|row| row.comment.user_id))
+            .ok_or(RepositoryError::NotFound)?;
+        Ok(ReportResolution { report, author_id })
+    }
+    async fn action_report(&self, report_id: Uuid, admin_id: Uuid, hide_content: bool, ban_author: bool) -> Result<ReportResolution, RepositoryError> {
+        let mut state = self.state();
+        let target = state.reports.iter()
+            .find(|report| report.id == report_id && report.status == ReportStatus::Open)
+            .map(|report| (report.post_id, report.comment_id))
+            .ok_or(RepositoryError::NotFound)?;
+        let author_id = match target {
+            (Some(post_id), _) => {
+                let row = state.post_mut(post_id).ok_or(RepositoryError::NotFound)?;
+                row.is_hidden |= hide_content;
+                row.post.user_id
+            }
+            (None, Some(comment_id)) => {
+                let row = state.comments.iter_mut().find(|row| row.comment.id == comment_id).ok_or(RepositoryError::NotFound)?;
+                row.is_hidden |= hide_content;
+                row.comment.user_id
+            }
+            (None, None) => return Err(RepositoryError::NotFound),
+        };
+        if ban_author && let Some(author) = state.users.get_mut(&author_id) {
+            author.is_banned = true;
+            author.updated_at = Utc::now();
+        }
+        // Every open report on the same content is settled by the same decision.
+        let same_content = |report: &Report| {
+            (target.0.is_some() && report.post_id == target.0) || (target.1.is_some() && report.comment_id == target.1)
+        };
+        for report in state.reports.iter_mut().filter(|report| report.status == ReportStatus::Open && (report.id == report_id || same_content(report))) {
+            resolve(report, ReportStatus::Actioned, admin_id);
+        }
+        let report = state.reports.iter().find(|report| report.id == report_id).expect("report read above").clone();
+        Ok(ReportResolution { report, author_id })
+    }
+}
+
+fn resolve(report: &mut Report, status: ReportStatus, admin_id: Uuid) {
+    let now = Utc::now();
+    report.status = status;
+    report.resolved_by = Some(admin_id);
+    report.resolved_at = Some(now);
+    report.updated_at = now;
+}
+
+/// Who may do what is up to `OrganizationService`, as with Postgres.
+#[async_trait]
+impl OrganizationRepository for InMemoryDb {
+    async fn save_organization(&self, name: &str, description: &str, region: &str, owner_id: Uuid) -> Result<Organization, RepositoryError> {
+        let mut state = self.state();
+        let now = Utc::now();
+        let organization = Organization {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            description: description.to_string(),
+            region: region.to_string(),
+            created_by: Some(owner_id),
+            created_at: now,
+            updated_at: now,
+        };
+        state.organizations.push(OrganizationRow { organization: organization.clone(), monthly_request_quota: None });
+        state.organization_members.insert((organization.id, owner_id), (OrganizationRole::Owner, now));
+        Ok(organization)
+    }
+    async fn get_organization(&self, organization_id: Uuid) -> Result<Option<Organization>, RepositoryError> {
+        Ok(self.state().organization(organization_id).map(|row| row.organization.clone()))
+    }
+    async fn get_organization_detail(&self, organization_id: Uuid, viewer_id: Uuid) -> Result<Option<OrganizationDetail>, RepositoryError> {
+        let state = self.state();
+        let Some(organization) = state.organization(organization_id).map(|row| &row.organization) else {
+            return Ok(None);
+        };
+        Ok(Some(OrganizationDetail {
+            id: organization.id,
+            name: organization.name.clone(),
+            description: organization.description.clone(),
+            region: organization.region.clone(),
+            created_by: organization.created_by,
+            members_count: state.organization_members.keys().filter(|(organization, _)| *organization == organization_id).count() as i64,
+            followers_count: state.organization_followers.iter().filter(|(organization, _)| *organization == organization_id).count() as i64,
+            role: state.member_role(organization_id, viewer_id),
+            is_following: state.organization_followers.contains(&(organization_id, viewer_id)),
+            created_at: organization.created_at,
+            updated_at: organization.updated_at,
+        }))
+    }
+    async fn update_organization(&self, organization_id: Uuid, name: Option<&str>, description: Option<&str>) -> Result<Organization, RepositoryError> {
+        let mut state = self.state();
+        let organization = &mut state.organizations.iter_mut()
+            .find(|row| row.organization.id == organization_id)
+            .ok_or(RepositoryError::NotFound)?
+            .organization;
+        if let Some(name) = name {
+            organization.name = name.to_string();
+        }
+        if let Some(description) = description {
+            organization.description = description.to_string();
+        }
+        organization.updated_at = Utc::now();
+        Ok(organization.clone())
+    }
+    async fn delete_organization(&self, organization_id: Uuid) -> Result<(), RepositoryError> {
+        if !self.state().remove_organization(organization_id) {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(())
+    }
+    async fn purge_organization(&self, organization_id: Uuid) -> Result<Vec<Uuid>, RepositoryError> {
+        let mut state = self.state();
+        state.organization(organization_id).ok_or(RepositoryError::NotFound)?;
+        let (post_ids, author_ids): (HashSet<Uuid>, Vec<Uuid>) = state.posts.iter()
+            .filter(|row| row.post.organization_id == Some(organization_id))
+            .map(|row| (row.post.id, row.post.user_id))
+            .unzip();
+        state.remove_posts(&post_ids);
+        state.remove_organization(organization_id);
+        Ok(author_ids)
+    }
+    async fn get_member_role(&self, organization_id: Uuid, user_id: Uuid) -> Result<Option<OrganizationRole>, RepositoryError> {
+        let state = self.state();
+        state.organization(organization_id).ok_or(RepositoryError::NotFound)?;
+        Ok(state.member_role(organization_id, user_id))
+    }
+    async fn get_members(&self, organization_id: Uuid) -> Result<Vec<OrganizationMember>, RepositoryError> {
+        let state = self.state();
+        let mut members: Vec<OrganizationMember> = state.organization_members.keys()
+            .filter(|(organization, _)| *organization == organization_id)
+            .filter_map(|(_, user_id)| state.organization_member(organization_id, *user_id))
+            .collect();
+        // Postgres sorts the enum in declaration order: owners, editors, viewers.
+        members.sort_by_key(|member| (member.role as u8, member.created_at, member.user_id));
+        Ok(members)
+    }
+    async fn set_member_role(&self, organization_id: Uuid, user_id: Uuid, role: OrganizationRole) -> Result<OrganizationMember, RepositoryError> {
+        let mut state = self.state();
+        if role != OrganizationRole::Owner {
+            state.ensure_other_owner(organization_id, user_id)?;
+        }
+        let (member_role, _) = state.organization_members.get_mut(&(organization_id, user_id)).ok_or(RepositoryError::NotFound)?;
+        *member_role = role;
+        state.organization_member(organization_id, user_id).ok_or(RepositoryError::NotFound)
+    }
+    async fn remove_member(&self, organization_id: Uuid, user_id: Uuid) -> Result<(), RepositoryError> {
+        let mut state = self.state();
+        state.ensure_other_owner(organization_id, user_id)?;
+        state.organization_members.remove(&(organization_id, user_id)).ok_or(RepositoryError::NotFound)?;
+        Ok(())
+    }
+    async fn save_invitation(&self, organization_id: Uuid, user_id: Uuid, role: OrganizationRole, invited_by: Uuid) -> Result<OrganizationInvitation, RepositoryError> {
+        let mut state = self.state();
+        let organization_name = state.organization(organization_id).ok_or(RepositoryError::NotFound)?.organization.name.clone();
+        if state.users.get(&user_id).is_none_or(|user| user.is_banned) {
+            return Err(RepositoryError::NotFound);
+        }
+        if state.organization_invitations.contains_key(&(organization_id, user_id)) {
+            return Err(RepositoryError::Conflict { constraint: "organization_invitations_pkey".to_string() });
+        }
+        let now = Utc::now();
+        state.organization_invitations.insert((organization_id, user_id), InvitationRow { role, invited_by: Some(invited_by), created_at: now });
+        Ok(OrganizationInvitation {
+            organization_id,
+            organization_name,
+            user_id,
+            role,
+            invited_by: Some(invited_by),
+            created_at: now,
+        })
+    }
+    async fn get_invitations_for_user(&self, user_id: Uuid) -> Result<Vec<OrganizationInvitation>, RepositoryError> {
+        let state = self.state();
+        let mut invitations: Vec<OrganizationInvitation> = state.organization_invitations.iter()
+            .filter(|((_, invited), _)| *invited == user_id)
+            .filter_map(|((organization_id, _), invitation)| Some(OrganizationInvitation {
+                organization_id: *organization_id,
+                organization_name: state.organization(*organization_id)?.organization.name.clone(),
+                user_id,
+                role: invitation.role,
+                invited_by: invitation.invited_by,
+                created_at: invitation.created_at,
+            }))
+            .collect();
+        invitations.sort_by_key(|invitation| (std::cmp::Reverse(invitation.created_at), invitation.organization_id));
+        Ok(invitations)
+    }
+    async fn accept_invitation(&self, organization_id: Uuid, user_id: Uuid) -> Result<OrganizationMember, RepositoryError> {
+        let mut state = self.state();
+        let role = state.organization_invitations.get(&(organization_id, user_id)).ok_or(RepositoryError::NotFound)?.role;
+        if state.organization_members.contains_key(&(organization_id, user_id)) {
+            return Err(RepositoryError::Conflict { constraint: "organization_members_pkey".to_string() });
+        }
+        state.organization_invitations.remove(&(organization_id, user_id));
+        state.organization_members.insert((organization_id, user_id), (role, Utc::now()));
+        state.organization_member(organization_id, user_id).ok_or(RepositoryError::NotFound)
+    }
+    async fn delete_invitation(&self, organization_id: Uuid, user_id: Uuid) -> Result<(), RepositoryError> {
+        self.state().organization_invitations.remove(&(organization_id, user_id)).ok_or(RepositoryError::NotFound)?;
+        Ok(())
+    }
+    async fn follow_organization(&self, organization_id: Uuid, user_id: Uuid) -> Result<(), RepositoryError> {
+        let mut state = self.state();
+        state.organization(organization_id).ok_or(RepositoryError::NotFound)?;
+        if !state.organization_followers.insert((organization_id, user_id)) {
+            return Err(RepositoryError::Conflict { constraint: "organization_followers_pkey".to_string() });
+        }
+        Ok(())
+    }
+    async fn unfollow_organization(&self, organization_id: Uuid, user_id: Uuid) -> Result<(), RepositoryError> {
+        if !self.state().organization_followers.remove(&(organization_id, user_id)) {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(())
+    }
+    async fn get_audience_ids(&self, organization_id: Uuid) -> Result<Vec<Uuid>, RepositoryError> {
+        let state = self.state();
+        let mut user_ids: Vec<Uuid> = state.organization_members.keys().copied()
+            .chain(state.organization_followers.iter().copied())
+            .filter(|(organization, _)| *organization == organization_id)
+            .map(|(_, user_id)| user_id)
+            .collect();
+        user_ids.sort();
+        user_ids.dedup();
+        Ok(user_ids)
+    }
+    async fn get_usage_access(&self, organization_id: Uuid, user_id: Uuid, month: NaiveDate) -> Result<Option<OrganizationUsageAccess>, RepositoryError> {
+        let state = self.state();
+        Ok(state.organization(organization_id).map(|row| OrganizationUsageAccess {
+            role: state.member_role(organization_id, user_id),
+            monthly_request_quota: row.monthly_request_quota,
+            requests: state.organization_usage.get(&(organization_id, month)).copied().unwrap_or(0),
+        }))
+    }
+    async fn get_usage_history(&self, organization_id: Uuid, months: i64) -> Result<Vec<OrganizationUsageMonth>, RepositoryError> {
+        let mut history: Vec<OrganizationUsageMonth> = self.state().organization_usage.iter()
+            .filter(|((organization, _), _)| *organization == organization_id)
+            .map(|((_, month), requests)| OrganizationUsageMonth { month: *month, requests: *requests })
+            .collect();
+        history.sort_by_key(|usage| std::cmp::Reverse(usage.month));
+        history.truncate(months as usize);
+        Ok(history)
+    }
+    async fn set_request_quota(&self, organization_id: Uuid, quota: Option<i64>) -> Result<(), RepositoryError> {
+        let mut state = self.state();
+        let row = state.organizations.iter_mut()
+            .find(|row| row.organization.id == organization_id)
+            .ok_or(RepositoryError::NotFound)?;
+        row.monthly_request_quota = quota;
+        row.organization.updated_at = Utc::now();
+        Ok(())
+    }
+    async fn add_usage(&self, usage: &[(Uuid, NaiveDate, i64)]) -> Result<(), RepositoryError> {
+        let mut state = self.state();
+        for (organization_id, month, requests) in usage {
+            if state.organization(*organization_id).is_some() {
+                *state.organization_usage.entry((*organization_id, *month)).or_default() += requests;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Nothing is materialized here, every aggregate is computed when it is read with the formulas of
+/// the views, so there is nothing to refresh and `refreshed_at` is always now.
+#[async_trait]
+impl AggregateRepository for InMemoryDb {
+    async fn refresh_aggregate_views(&self) -> Result<(), RepositoryError> {
+        Ok(())
+    }
+    async fn get_trending_posts(&self, limit: i64, by_views: bool) -> Result<Vec<TrendingPost>, RepositoryError> {
+        let state = self.state();
+        let now = Utc::now();
+        let week_ago = now - chrono::Duration::days(7);
+        let mut posts: Vec<(i64, TrendingPost)> = state.posts.iter()
+            .filter(|row| !row.is_hidden && row.post.created_at >= week_ago)
+            .filter(|row| state.users.get(&row.post.user_id).is_some_and(|author| !author.is_banned && !author.is_private))
+            .map(|row| {
+                let post = &row.post;
+                let comment_count = state.visible_comments(post.id).count() as i64;
+                let view_count: i64 = state.post_views.iter()
+                    .filter(|((post_id, hour), _)| *post_id == post.id && *hour >= week_ago)
+                    .map(|(_, views)| views)
+                    .sum();
+                let hours = (now - post.created_at).num_milliseconds() as f64 / 3_600_000.0;
+                let score = (comment_count as f64 + view_count as f64 / 10.0 + 1.0) / (hours + 2.0).powf(1.5);
+                (view_count, TrendingPost {
+                    id: post.id,
+                    user_id: post.user_id,
+                    title: post.title.clone(),
+                    content: post.content.clone(),
+                    tags: post.tags.clone(),
+                    comment_count,
+                    views_count: post.views_count,
+                    score,
+                    created_at: post.created_at,
+                    refreshed_at: now,
+                })
+            })
+            .collect();
+        posts.sort_by(|(a_views, a), (b_views, b)| {
+            let by_views = if by_views { b_views.cmp(a_views) } else { std::cmp::Ordering::Equal };
+            by_views.then(b.score.total_cmp(&a.score))
+        });
+        Ok(posts.into_iter().take(limit as usize).map(|(_, post)| post).collect())
+    }
+    async fn get_user_leaderboard(&self, limit: i64) -> Result<Leaderboard, RepositoryError> {
+        let state = self.state();
+        let now = Utc::now();
+        let week_ago = now - chrono::Duration::days(7);
+        let entries: Vec<LeaderboardEntry> = state.users.values()
+            .filter(|user| user.is_verified && !user.is_banned)
+            .map(|user| LeaderboardEntry {
+                user_id: user.id,
+                name: user.name.clone(),
+                followers_count: state.followers.keys().filter(|(_, following)| *following == user.id).count() as i64,
+                posts_this_week: state.posts.iter()
+                    .filter(|row| row.post.user_id == user.id && !row.is_hidden && row.post.created_at >= week_ago)
+                    .count() as i64,
+            })
+            .collect();
+        let refreshed_at = (!entries.is_empty()).then_some(now);
+        let top = |key: fn(&LeaderboardEntry) -> i64| {
+            let mut top: Vec<&LeaderboardEntry> = entries.iter().filter(|entry| key(entry) > 0).collect();
+            top.sort_by(|a, b| key(b).cmp(&key(a)).then(a.name.cmp(&b.name)));
+            top.into_iter()
+                .take(limit as usize)
+                .map(|entry| LeaderboardEntry {
+                    user_id: entry.user_id,
+                    name: entry.name.clone(),
+                    followers_count: entry.followers_count,
+                    posts_this_week: entry.posts_this_week,
+                })
+                .collect()
+        };
+        Ok(Leaderboard {
+            top_followed: top(|entry| entry.followers_count),
+            top_posters: top(|entry| entry.posts_this_week),
+            refreshed_at,
+        })
+    }
+    async fn get_admin_stats(&self) -> Result<AdminStats, RepositoryError> {
+        let state = self.state();
+        let now = Utc::now();
+        let week_ago = now - chrono::Duration::days(7);
+        let comments = state.comments.iter().filter(|row| row.deleted_at.is_none());
+        Ok(AdminStats {
+            total_users: state.users.len() as i64,
+            verified_users: state.users.values().filter(|user| user.is_verified).count() as i64,
+            banned_users: state.users.values().filter(|user| user.is_banned).count() as i64,
+            new_users_this_week: state.users.values().filter(|user| user.created_at >= week_ago).count() as i64,
+            total_posts: state.posts.len() as i64,
+            hidden_posts: state.posts.iter().filter(|row| row.is_hidden).count() as i64,
+            new_posts_this_week: state.posts.iter().filter(|row| row.post.created_at >= week_ago).count() as i64,
+            total_comments: comments.clone().count() as i64,
+            hidden_comments: comments.filter(|row| row.is_hidden).count() as i64,
+            open_reports: state.reports.iter().filter(|report| report.status == ReportStatus::Open).count() as i64,
+            refreshed_at: now,
+        })
+    }
+    async fn get_daily_stats(&self, since: NaiveDate, until: NaiveDate) -> Result<Vec<DailyStats>, RepositoryError> {
+        let state = self.state();
+        let activity: Vec<(Uuid, DateTime<Utc>)> = state.posts.iter().map(|row| (row.post.user_id, row.post.created_at))
+            .chain(state.comments.iter().map(|row| (row.comment.user_id, row.comment.created_at)))
+            .chain(state.sessions.iter().flat_map(|session| [(session.user_id, session.created_at), (session.user_id, session.last_seen_at)]))
+            .collect();
+        Ok(since.iter_days().take_while(|day| *day <= until)
+            .map(|day| {
+                let on_day = |at: &DateTime<Utc>| at.date_naive() == day;
+                let active_users: HashSet<Uuid> = activity.iter().filter(|(_, at)| on_day(at)).map(|(user_id, _)| *user_id).collect();
+                DailyStats {
+                    day,
+                    sign_ups: state.users.values().filter(|user| on_day(&user.created_at)).count() as i64,
+                    posts: state.posts.iter().filter(|row| on_day(&row.post.created_at)).count() as i64,
+                    comments: state.comments.iter().filter(|row| on_day(&row.comment.created_at)).count() as i64,
+                    active_users: active_users.len() as i64,
+                }
+            })
+            .collect())
+    }
+    async fn get_top_posts(&self, since: NaiveDate, until: NaiveDate, limit: i64) -> Result<Vec<TopPost>, RepositoryError> {
+        let state = self.state();
+        let mut comment_counts: HashMap<Uuid, i64> = HashMap::new();
+        for row in state.comments.iter().filter(|row| (since..=until).contains(&row.comment.created_at.date_naive())) {
+            *comment_counts.entry(row.comment.post_id).or_default() += 1;
+        }
+        let mut posts: Vec<TopPost> = comment_counts.into_iter()
+            .filter_map(|(post_id, comment_count)| {
+                let row = state.post(post_id)?;
+                Some(TopPost {
+                    id: post_id,
+                    title: row.post.title.clone(),
+                    user_id: row.post.user_id,
+                    author_name: state.users.get(&row.post.user_id)?.name.clone(),
+                    comment_count,
+                    is_hidden: row.is_hidden,
+                    created_at: row.post.created_at,
+                })
+            })
+            .collect();
+        posts.sort_by_key(|post| std::cmp::Reverse((post.comment_count, post.created_at)));
+        posts.truncate(limit as usize);
+        Ok(posts)
+    }
+}
+
+#[async_trait]
+impl ActivityRepository for InMemoryDb {
+    async fn get_user_activity(&self, user_id: Uuid, viewer_id: Uuid, params: UserActivityParams) -> Result<PaginatedData<ActivityItem>, RepositoryError> {
+        let state = self.state();
+        let posts = state.posts.iter()
+            .filter(|row| row.post.user_id == user_id && !row.is_hidden)
+            .map(|row| ActivityItem {
+                kind: "post".to_string(),
+                id: row.post.id,
+                post_id: Some(row.post.id),
+                title: Some(row.post.title.clone()),
+                content: None,
+                target_user_id: None,
+                target_user_name: None,
+                created_at: row.post.created_at,
+            });
+        let comments = state.comments.iter()
+            .filter(|row| row.comment.user_id == user_id && !row.is_hidden)
+            .filter_map(|row| {
+                let post = state.post(row.comment.post_id).filter(|post| !post.is_hidden && state.is_visible(post.post.user_id, viewer_id))?;
+                let author = state.users.get(&post.post.user_id)?;
+                Some(ActivityItem {
+                    kind: "comment".to_string(),
+                    id: row.comment.id,
+                    post_id: Some(post.post.id),
+                    title: Some(post.post.title.clone()),
+                    content: Some(row.comment.content.clone()),
+                    target_user_id: Some(author.id),
+                    target_user_name: Some(author.name.clone()),
+                    created_at: row.comment.created_at,
+                })
+            });
+        let follows = state.followers.iter()
+            .filter(|((follower, _), _)| *follower == user_id)
+            .filter_map(|((_, following), followed_at)| {
+                let followed = state.users.get(following)?;
+                Some(ActivityItem {
+                    kind: "follow".to_string(),
+                    id: followed.id,
+                    post_id: None,
+                    title: None,
+                    content: None,
+                    target_user_id: Some(followed.id),
+                    target_user_name: Some(followed.name.clone()),
+                    created_at: *followed_at,
+                })
+            });
+        let mut items: Vec<ActivityItem> = posts.chain(comments).chain(follows).collect();
+        items.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(a.id.cmp(&b.id)));
+        Ok(paginate(items, params.pagination))
+    }
+}
+
+/// Full text search is approximated: a post matches when its title or content contains every word
+/// of `text` regardless of case, quotes are dropped and `or` and `-word` aren't understood. Posts
+/// tagged `tag` come first, then the newest.
+#[async_trait]
+impl SearchRepository for InMemoryDb {
+    async fn search_users(&self, query: &str, pagination: &Pagination) -> Result<PaginatedData<UserSearchResult>, RepositoryError> {
+        let users = UserRepository::search_users(self, query, i64::MAX).await?;
+        Ok(paginate(users, *pagination))
+    }
+    async fn search_posts(&self, text: &str, tag: &str, viewer_id: Uuid, pagination: &Pagination) -> Result<PaginatedData<PostSearchResult>, RepositoryError> {
+        let state = self.state();
+        let words: Vec<&str> = text.split_whitespace().map(|word| word.trim_matches('"')).filter(|word| !word.is_empty()).collect();
+        let matches_text = |post: &Post| {
+            !words.is_empty() && words.iter().all(|word| contains_ignore_case(&post.title, word) || contains_ignore_case(&post.content, word))
+        };
+        let mut posts: Vec<(bool, PostSearchResult)> = state.posts.iter()
+            .filter(|row| state.is_post_visible(row, viewer_id))
+            .map(|row| &row.post)
+            .filter_map(|post| {
+                let tagged = post.tags.iter().any(|post_tag| post_tag == tag);
+                if !tagged && !matches_text(post) {
+                    return None;
+                }
+                Some((tagged, PostSearchResult {
+                    id: post.id,
+                    user_id: post.user_id,
+                    author: state.users.get(&post.user_id)?.name.clone(),
+                    title: post.title.clone(),
+                    content: post.content.clone(),
+                    tags: post.tags.clone(),
+                    views_count: post.views_count,
+                    created_at: post.created_at,
+                }))
+            })
+            .collect();
+        posts.sort_by(|(a_tagged, a), (b_tagged, b)| b_tagged.cmp(a_tagged).then(b.created_at.cmp(&a.created_at)).then(a.id.cmp(&b.id)));
+        Ok(paginate(posts.into_iter().map(|(_, post)| post).collect(), *pagination))
+    }
+    async fn search_tags(&self, query: &str, viewer_id: Uuid, pagination: &Pagination) -> Result<PaginatedData<TagSearchResult>, RepositoryError> {
+        let state = self.state();
+        let mut counts: HashMap<String, i64> = HashMap::new();
+        for row in state.posts.iter().filter(|row| state.is_post_visible(row, viewer_id)) {
+            for tag in row.post.tags.iter().map(|tag| tag.to_lowercase()).filter(|tag| tag.starts_with(query)) {
+                *counts.entry(tag).or_default() += 1;
+            }
+        }
+        let mut tags: Vec<TagSearchResult> = counts.into_iter()
+            .map(|(name, posts_count)| TagSearchResult { name, posts_count })
+            .collect();
+        tags.sort_by(|a, b| (b.name == query).cmp(&(a.name == query)).then(b.posts_count.cmp(&a.posts_count)).then(a.name.cmp(&b.name)));
+        Ok(paginate(tags, *pagination))
+    }
+}
+
+/// Accounts added in memory are never waitlisted, so only the settings, the account and the last
+/// digest decide who is due.
+#[async_trait]
+impl DigestRepository for InMemoryDb {
+    async fn get_due_digests(&self, now: DateTime<Utc>, after: Option<Uuid>, limit: i64) -> Result<Vec<DigestRecipient>, RepositoryError> {
+        let state = self.state();
+        let mut recipients: Vec<DigestRecipient> = state.settings.iter()
+            .filter(|(user_id, _)| after.is_none_or(|after| **user_id > after))
+            .filter_map(|(user_id, settings)| {
+                let period = settings.email_digest.and_then(|frequency| frequency.period())?;
+                let user = state.users.get(user_id).filter(|user| user.is_verified && !user.is_banned)?;
+                let sent_at = state.digests_sent.get(user_id).copied();
+                if sent_at.is_some_and(|sent_at| sent_at > now + chrono::Duration::minutes(5) - period) {
+                    return None;
+                }
+                Some(DigestRecipient {
+                    user_id: user.id,
+                    name: user.name.clone(),
+                    email: user.email.clone(),
+                    settings: Json(settings.clone()),
+                    sent_at,
+                })
+            })
+            .collect();
+        recipients.sort_by_key(|recipient| recipient.user_id);
+        recipients.truncate(limit as usize);
+        Ok(recipients)
+    }
+    async fn get_digest_content(&self, user_id: Uuid, since: DateTime<Utc>, max_posts: i64) -> Result<DigestContent, RepositoryError> {
+        let state = self.state();
+        let mut posts: Vec<DigestPost> = state.posts.iter()
+            .filter(|row| !row.is_hidden && row.post.created_at > since && state.followers.contains_key(&(user_id, row.post.user_id)))
+            .filter_map(|row| {
+                let author = state.users.get(&row.post.user_id).filter(|author| !author.is_banned)?;
+                Some(DigestPost {
+                    id: row.post.id,
+                    title: row.post.title.clone(),
+                    author_name: author.name.clone(),
+                    created_at: row.post.created_at,
+                })
+            })
+            .collect();
+        posts.sort_by_key(|post| std::cmp::Reverse(post.created_at));
+        let posts_count = posts.len() as i64;
+        posts.truncate(max_posts as usize);
+        Ok(DigestContent {
+            posts,
+            posts_count,
+            follow_requests: state.follow_requests.iter().filter(|(_, target, _)| *target == user_id).count() as i64,
+            organization_invitations: state.organization_invitations.keys().filter(|(_, invited)| *invited == user_id).count() as i64,
+        })
+    }
+    async fn mark_digest_sent(&self, user_id: Uuid, sent_at: DateTime<Utc>) -> Result<(), RepositoryError> {
+        self.state().digests_sent.insert(user_id, sent_at);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AnalyticsRepository for InMemoryDb {
+    async fn save_analytics_events(&self, events: &[AnalyticsEvent]) -> Result<(), RepositoryError> {
+        let mut state = self.state();
+        for event in events {
+            if !state.analytics_events.iter().any(|saved| saved.id == event.id) {
+                state.analytics_events.push(event.clone());
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,8 +1,11 @@
-use axum::Json;
-use serde::{Serialize};
-use crate::error::{ErrorPayload, HttpError};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
+use crate::{config::Config, error::AppError, middleware::content_negotiation::Negotiated};
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct SuccessResponse<'a, T> {
     pub status: &'a str,
     pub message: &'a str,
@@ -10,26 +13,53 @@ pub struct SuccessResponse<'a, T> {
     pub data: Option<T>,
 }
 impl<'a, T> SuccessResponse<'a, T> where T: Serialize {
-    pub fn new(message: &'a str, data: Option<T>) -> Json<Self> {
-        Json(Self{
+    pub fn new(message: &'a str, data: Option<T>) -> Negotiated<Self> {
+        Negotiated(Self{
             status: "success",
             message,
             data,
         })
     }
 }
-#[derive(Serialize)]
-pub struct ErrorRouting {
-    pub status: String,
-    pub message: String,
-}
 
-pub type HttpResult<T> = Result<T, HttpError<ErrorPayload>>;
+pub type HttpResult<T> = Result<T, AppError>;
 
 pub fn default_limit() -> Option<usize> { Some(5) }
-pub fn default_page() -> Option<usize> { Some(1) }
 pub fn default_order_by() -> Option<String> { Some("DESC".to_string()) }
-#[derive(Serialize)]
+
+/// Page and page size a list query reads, see `Pagination::sanitize`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pagination {
+    pub page: usize,
+    pub limit: usize,
+}
+impl Default for Pagination {
+    fn default() -> Self {
+        Self {
+            page: 1,
+            limit: default_limit().unwrap_or(1),
+        }
+    }
+}
+impl Pagination {
+    /// The requested page, the first one by default, and the requested page size, `DEFAULT_PAGE_SIZE`
+    /// by default and clamped to `1..=MAX_PAGE_SIZE` instead of being rejected.
+    pub fn sanitize(page: Option<usize>, limit: Option<usize>, config: &Config) -> Self {
+        Self {
+            page: page.unwrap_or(1).max(1),
+            limit: limit.unwrap_or(config.default_page_size).clamp(1, config.max_page_size),
+        }
+    }
+    pub fn offset(&self) -> usize {
+        (self.page - 1) * self.limit
+    }
+}
+/// List parameters whose `pagination` is filled in by `PaginatedQuery`, from the raw `page` and
+/// `limit` of the query string.
+pub trait Paginated {
+    fn paginate(&mut self, config: &Config);
+}
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct PaginationMeta {
     page: i32,
     limit: i32,
@@ -53,8 +83,89 @@ impl PaginationMeta {
         }
     }
 }
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct PaginatedData<T> {
     pub items: Vec<T>,
     pub pagination: PaginationMeta,
+}
+
+/// Most ids one batch request may ask for.
+pub const BATCH_MAX_IDS: u64 = 100;
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
+pub struct BatchRequest {
+    #[validate(length(min = 1, max = BATCH_MAX_IDS, message = "Between 1 and 100 ids are allowed."))]
+    pub ids: Vec<Uuid>,
+}
+impl BatchRequest {
+    /// The requested ids in order, without duplicates.
+    pub fn unique_ids(&self) -> Vec<Uuid> {
+        let mut ids = Vec::with_capacity(self.ids.len());
+        for id in &self.ids {
+            if !ids.contains(id) {
+                ids.push(*id);
+            }
+        }
+        ids
+    }
+}
+/// Records found for a batch request in the requested order, plus the ids that don't exist or
+/// aren't visible to the caller.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BatchData<T> {
+    pub items: Vec<T>,
+    pub missing: Vec<Uuid>,
+}
+impl<T> BatchData<T> {
+    pub fn new(ids: &[Uuid], items: Vec<T>, id_of: impl Fn(&T) -> Uuid) -> Self {
+        let missing = ids.iter()
+            .filter(|id| !items.iter().any(|item| id_of(item) == **id))
+            .copied()
+            .collect();
+        Self { items, missing }
+    }
+}
+
+/// Checks a `fields=` query parameter, a comma separated list of item fields, against the fields
+/// the list endpoint can return.
+pub fn validate_fields(value: &str, allowed: &[&str]) -> Result<(), ValidationError> {
+    for field in value.split(',').map(str::trim) {
+        if !allowed.contains(&field) {
+            let mut error = ValidationError::new("invalid_field");
+            error.message = Some(format!("Unknown field '{}', fields are: {}", field, allowed.join(", ")).into());
+            return Err(error);
+        }
+    }
+    Ok(())
+}
+/// `deserialize_with` for the nullable fields of a PATCH body, used with `#[serde(default)]`: a
+/// missing field stays `None` while an explicit `null` becomes `Some(None)`.
+pub fn nullable<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+/// Schema check of PATCH bodies, an empty patch is more likely a client bug than a no-op.
+pub fn require_any_field(present: bool) -> Result<(), ValidationError> {
+    if present {
+        return Ok(());
+    }
+    let mut error = ValidationError::new("empty_patch");
+    error.message = Some("At least one field must be given".into());
+    Err(error)
+}
+/// Sparse fieldsets for list endpoints: keeps only the requested fields of every item, plus `id`
+/// so clients can still tell the items apart. Without `fields` the items are returned whole.
+pub fn select_fields<T: Serialize>(items: Vec<T>, fields: Option<&str>) -> Vec<Value> {
+    let fields: Option<Vec<&str>> = fields.map(|fields| fields.split(',').map(str::trim).collect());
+    items.into_iter()
+        .map(|item| {
+            let mut value = serde_json::to_value(item).unwrap_or(Value::Null);
+            if let (Some(fields), Value::Object(map)) = (&fields, &mut value) {
+                map.retain(|key, _| key == "id" || fields.contains(&key.as_str()));
+            }
+            value
+        })
+        .collect()
 }
\ No newline at end of file
@@ -1,5 +1,5 @@
 use axum::{
-    http::{StatusCode, request::Parts},
+    http::{HeaderValue, StatusCode, header::{IF_MATCH, RETRY_AFTER, WWW_AUTHENTICATE}, request::Parts},
     response::{IntoResponse, Response},
     extract::{
         FromRequest, 
@@ -11,16 +11,24 @@ use axum::{
     },
     Json,
 };
+use chrono::{DateTime, SecondsFormat, Utc};
 use serde::{Serialize, de::DeserializeOwned};
+use serde_json::Value;
 use std::{
-    fmt::{Debug, Display, Formatter, Result as FmtResult},
-    error::Error,
-    collections::BTreeMap
+    fmt::{Display, Formatter, Result as FmtResult},
+    collections::BTreeMap,
+    sync::Arc,
 };
-use validator::ValidationErrors;
+use jsonwebtoken::errors::{Error as JwtError, ErrorKind as JwtErrorKind};
+use log::error;
+use redis::RedisError;
+use validator::{Validate, ValidationErrors};
 use sqlx::{Error as SqlxError};
-use crate::dto::ErrorRouting;
+use crate::{AppState, dto::Paginated, middleware::content_negotiation::Negotiated, utils::metrics};
 
+/// Every message has a stable machine-readable code (see `get_code`), sent as `code` in error
+/// responses. Codes are part of the API contract: add new ones freely, never rename or reuse one.
+#[derive(Debug)]
 pub enum ErrorMessage {
     EmptyPassword,
     ExceededMaxPasswordLength(usize),
@@ -30,11 +38,21 @@ pub enum ErrorMessage {
     ServerError,
     WrongCredentials,
     EmailExist,
+    UsernameExist,
     UserNoLongerExist,
     TokenInvalid,
     TokenNotProvided,
     TokenExpired,
+    TokenNotYetValid,
+    TokenMalformed,
+    TokenRefreshTooEarly(u64),
     TooManyRequest,
+    EmailRateLimited(u64),
+    SignInThrottled(u64),
+    PostingTooFast(u64),
+    QuotaExceeded(u64),
+    ServiceUnavailable,
+    DatabaseBusy,
     TokenKeyExpired,
     TokenKeyInvalid,
     DataNotFound,
@@ -42,39 +60,87 @@ pub enum ErrorMessage {
     UserNotAuthenticated,
     AccountActive,
     AccountNotActive,
-    RequestInvalid
+    AccountBanned,
+    AccountWaitlisted(i64),
+    PrivateAccount,
+    AlreadyFollowing,
+    AlreadyCollaborator,
+    AlreadyMember,
+    OrganizationInvitationExist,
+    FollowRequestExist,
+    DataConflict,
+    VersionConflict,
+    RequestInvalid,
+    /// An extractor refused the body, query or path, with what it said about it.
+    RequestRejected(String),
+    /// `{method} {path}`
+    RouteNotFound(String),
+    /// `{method} {path}`
+    MethodNotAllowed(String),
+    WebhookNotConfigured,
+    WebhookSignatureInvalid,
+    WebhookTimestampInvalid,
+    WebhookReplayed,
+    UnsupportedMediaType,
+    ImportHeaderInvalid,
+    AnnouncementScheduleInvalid,
+    TosAcceptanceRequired(String),
+    TosVersionNotCurrent(String),
+    InvitationRequired,
+    InvitationInvalid,
+    CaptchaRequired,
+    CaptchaInvalid,
+    CaptchaUnavailable,
+    UrlSignatureInvalid,
+    UrlExpired,
+    AvatarUnavailable,
+    FeedSourceLimit(i64),
+    /// The regions there are, from `DATA_REGIONS`.
+    RegionUnavailable(String),
+    ContentRejected,
+    ValidationErrors,
 }
 #[derive(Serialize)]
 pub struct ErrorResponse<'a, T> {
     pub status: &'a str,
+    pub code: &'a str,
     pub message: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<T>,
 }
-#[derive(Debug)]
-pub struct HttpError<T> {
-    pub status: StatusCode,
-    pub message: String,
-    pub error: Option<T>,
-}
 #[derive(Debug, Serialize)]
 pub struct FieldError {
     pub field: String,
     pub messages: Vec<String>,
 }
-#[derive(Debug, Serialize)]
-#[serde(untagged)]
-pub enum ErrorPayload {
-    ValidationErrors(Vec<FieldError>),
-    // Message(String),
+/// Error type shared by handlers, middlewares and utils. Repository, Redis, validation and JWT
+/// errors convert into it with `?`; `into_response` decides what the client gets to see.
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("{message}")]
+    Http {
+        status: StatusCode,
+        message: ErrorMessage,
+    },
+    #[error("Validation Errors")]
+    Validation(Vec<FieldError>),
+    /// Flagged by the content moderator, with the reasons per field.
+    #[error("Content Rejected")]
+    ContentRejected(Vec<FieldError>),
+    #[error(transparent)]
+    Repository(#[from] RepositoryError),
+    #[error(transparent)]
+    Redis(#[from] RedisError),
+    #[error(transparent)]
+    Jwt(#[from] JwtError),
 }
-
 impl ErrorMessage {
     fn get_message(&self) -> String {
         match self {
             ErrorMessage::ServerError => "Internal Server Error. Please try again later.".to_string(),
             ErrorMessage::WrongCredentials => "Your credentials is wrong.".to_string(),
             ErrorMessage::EmailExist => "A user with this email already exists.".to_string(),
+            ErrorMessage::UsernameExist => "This username is already taken.".to_string(),
             ErrorMessage::UserNoLongerExist => "User belonging to this token no longer exists.".to_string(),
             ErrorMessage::EmptyPassword => "Password cannot be empty.".to_string(),
             ErrorMessage::HashingError => "Error while hashing password.".to_string(),
@@ -84,7 +150,16 @@ impl ErrorMessage {
             ErrorMessage::TokenInvalid => "Authentication token is invalid or expired.".to_string(),
             ErrorMessage::TokenNotProvided => "You are not logged in, please provide a token.".to_string(),
             ErrorMessage::TokenExpired => "Token has expired.".to_string(),
+            ErrorMessage::TokenNotYetValid => "Token is not valid yet.".to_string(),
+            ErrorMessage::TokenRefreshTooEarly(grace) => format!("An access token can only be exchanged within {} seconds of its expiry.", grace),
+            ErrorMessage::EmailRateLimited(retry_after) => format!("Too many emails were requested for this address, please try again in {} seconds.", retry_after),
+            ErrorMessage::SignInThrottled(retry_after) => format!("Too many failed sign-in attempts, please try again in {} seconds.", retry_after),
+            ErrorMessage::PostingTooFast(retry_after) => format!("You are posting too fast, please try again in {} seconds.", retry_after),
+            ErrorMessage::QuotaExceeded(retry_after) => format!("The organization has used its monthly request quota, it resets in {} seconds.", retry_after),
+            ErrorMessage::TokenMalformed => "Authentication token is malformed.".to_string(),
             ErrorMessage::TooManyRequest => "Request limit is exceeded, too many request.".to_string(),
+            ErrorMessage::ServiceUnavailable => "Service is temporarily unavailable, please try again later.".to_string(),
+            ErrorMessage::DatabaseBusy => "The server is busy right now, please try again in a moment.".to_string(),
             ErrorMessage::TokenKeyExpired => "Token key has expired. Please request a new key.".to_string(),
             ErrorMessage::TokenKeyInvalid => "Token key is invalid.".to_string(),
             ErrorMessage::DataNotFound => "Data is not found.".to_string(),
@@ -92,11 +167,128 @@ impl ErrorMessage {
             ErrorMessage::UserNotAuthenticated => "Authentication required. Please log in.".to_string(),
             ErrorMessage::AccountActive => "Activation failed. Your account is already active.".to_string(),
             ErrorMessage::AccountNotActive => "Your account is not active, please activate first.".to_string(),
+            ErrorMessage::AccountBanned => "Your account has been suspended.".to_string(),
+            ErrorMessage::AccountWaitlisted(position) => format!("Your account is on the waitlist at position {}, we will email you once it is open.", position),
+            ErrorMessage::PrivateAccount => "This account is private. Follow it to see its content.".to_string(),
+            ErrorMessage::AlreadyFollowing => "You are already following this user.".to_string(),
+            ErrorMessage::AlreadyCollaborator => "This user is already a collaborator of the post.".to_string(),
+            ErrorMessage::AlreadyMember => "This user is already a member of the organization.".to_string(),
+            ErrorMessage::OrganizationInvitationExist => "This user already has a pending invitation to the organization.".to_string(),
+            ErrorMessage::FollowRequestExist => "A follow request to this user is already pending.".to_string(),
+            ErrorMessage::DataConflict => "The data conflicts with an existing record.".to_string(),
+            ErrorMessage::VersionConflict => "The data was changed by someone else, the current version is attached.".to_string(),
             ErrorMessage::RequestInvalid => "The request is invalid.".to_string(),
+            ErrorMessage::RequestRejected(reason) => reason.to_string(),
+            ErrorMessage::RouteNotFound(route) => format!("Route {} is not exists", route),
+            ErrorMessage::MethodNotAllowed(route) => format!("{} is not valid", route),
+            ErrorMessage::AnnouncementScheduleInvalid => "The announcement must end after it starts.".to_string(),
+            ErrorMessage::TosAcceptanceRequired(version) => format!("Accept version {} of the terms of service with POST /api/user/accept-tos to continue.", version),
+            ErrorMessage::TosVersionNotCurrent(version) => format!("Only the current terms of service, version {}, can be accepted.", version),
+            ErrorMessage::WebhookNotConfigured => "Webhook receiver is not configured.".to_string(),
+            ErrorMessage::WebhookSignatureInvalid => "Webhook signature is missing or invalid.".to_string(),
+            ErrorMessage::WebhookTimestampInvalid => "Webhook timestamp is outside the allowed window.".to_string(),
+            ErrorMessage::WebhookReplayed => "Webhook delivery has already been processed.".to_string(),
+            ErrorMessage::UnsupportedMediaType => "The request body's content type is not supported here.".to_string(),
+            ErrorMessage::ImportHeaderInvalid => "The first line must be a header naming the name and email columns.".to_string(),
+            ErrorMessage::InvitationRequired => "Registration is invite only, an invite token is required.".to_string(),
+            ErrorMessage::InvitationInvalid => "The invite token is invalid, expired, already used or issued for another email.".to_string(),
+            ErrorMessage::CaptchaRequired => "Please complete the captcha.".to_string(),
+            ErrorMessage::CaptchaInvalid => "The captcha is invalid or expired, please try again.".to_string(),
+            ErrorMessage::CaptchaUnavailable => "The captcha could not be checked, please try again later.".to_string(),
+            ErrorMessage::UrlSignatureInvalid => "The link is not valid.".to_string(),
+            ErrorMessage::UrlExpired => "The link has expired, please request a new one.".to_string(),
+            ErrorMessage::AvatarUnavailable => "The avatar image could not be loaded.".to_string(),
+            ErrorMessage::FeedSourceLimit(max) => format!("You can import from at most {} feeds, remove one first.", max),
+            ErrorMessage::RegionUnavailable(regions) => format!("The region is not available, choose one of: {}.", regions),
+            ErrorMessage::ContentRejected => "The content was rejected by the content filter.".to_string(),
+            ErrorMessage::ValidationErrors => "Validation Errors".to_string(),
+        }
+    }
+    pub fn get_code(&self) -> &'static str {
+        match self {
+            ErrorMessage::EmptyPassword => "EMPTY_PASSWORD",
+            ErrorMessage::ExceededMaxPasswordLength(_) => "PASSWORD_TOO_LONG",
+            ErrorMessage::FailedSendEmail(_) => "EMAIL_SEND_FAILED",
+            ErrorMessage::InvalidHashFormat => "INVALID_HASH_FORMAT",
+            ErrorMessage::HashingError => "HASHING_FAILED",
+            ErrorMessage::ServerError => "INTERNAL_ERROR",
+            ErrorMessage::WrongCredentials => "WRONG_CREDENTIALS",
+            ErrorMessage::EmailExist => "EMAIL_EXISTS",
+            ErrorMessage::UsernameExist => "USERNAME_EXISTS",
+            ErrorMessage::UserNoLongerExist => "USER_NO_LONGER_EXISTS",
+            ErrorMessage::TokenInvalid => "TOKEN_INVALID",
+            ErrorMessage::TokenNotProvided => "TOKEN_NOT_PROVIDED",
+            ErrorMessage::TokenExpired => "TOKEN_EXPIRED",
+            ErrorMessage::TokenNotYetValid => "TOKEN_NOT_YET_VALID",
+            ErrorMessage::TokenRefreshTooEarly(_) => "TOKEN_REFRESH_TOO_EARLY",
+            ErrorMessage::EmailRateLimited(_) => "EMAIL_RATE_LIMITED",
+            ErrorMessage::SignInThrottled(_) => "SIGN_IN_THROTTLED",
+            ErrorMessage::PostingTooFast(_) => "POSTING_TOO_FAST",
+            ErrorMessage::QuotaExceeded(_) => "QUOTA_EXCEEDED",
+            ErrorMessage::TokenMalformed => "TOKEN_MALFORMED",
+            ErrorMessage::TooManyRequest => "RATE_LIMITED",
+            ErrorMessage::ServiceUnavailable => "SERVICE_UNAVAILABLE",
+            ErrorMessage::DatabaseBusy => "DATABASE_BUSY",
+            ErrorMessage::TokenKeyExpired => "TOKEN_KEY_EXPIRED",
+            ErrorMessage::TokenKeyInvalid => "TOKEN_KEY_INVALID",
+            ErrorMessage::DataNotFound => "NOT_FOUND",
+            ErrorMessage::PermissionDenied => "PERMISSION_DENIED",
+            ErrorMessage::UserNotAuthenticated => "NOT_AUTHENTICATED",
+            ErrorMessage::AccountActive => "ACCOUNT_ALREADY_ACTIVE",
+            ErrorMessage::AccountNotActive => "ACCOUNT_NOT_ACTIVE",
+            ErrorMessage::AccountBanned => "ACCOUNT_BANNED",
+            ErrorMessage::AccountWaitlisted(_) => "ACCOUNT_WAITLISTED",
+            ErrorMessage::PrivateAccount => "PRIVATE_ACCOUNT",
+            ErrorMessage::AlreadyFollowing => "ALREADY_FOLLOWING",
+            ErrorMessage::AlreadyCollaborator => "ALREADY_COLLABORATOR",
+            ErrorMessage::AlreadyMember => "ALREADY_MEMBER",
+            ErrorMessage::OrganizationInvitationExist => "INVITATION_EXISTS",
+            ErrorMessage::FollowRequestExist => "FOLLOW_REQUEST_EXISTS",
+            ErrorMessage::DataConflict => "CONFLICT",
+            ErrorMessage::VersionConflict => "VERSION_CONFLICT",
+            ErrorMessage::RequestInvalid | ErrorMessage::RequestRejected(_) => "INVALID_REQUEST",
+            ErrorMessage::RouteNotFound(_) => "ROUTE_NOT_FOUND",
+            ErrorMessage::MethodNotAllowed(_) => "METHOD_NOT_ALLOWED",
+            ErrorMessage::AnnouncementScheduleInvalid => "ANNOUNCEMENT_SCHEDULE_INVALID",
+            ErrorMessage::TosAcceptanceRequired(_) => "TOS_ACCEPTANCE_REQUIRED",
+            ErrorMessage::TosVersionNotCurrent(_) => "TOS_VERSION_NOT_CURRENT",
+            ErrorMessage::WebhookNotConfigured => "WEBHOOK_NOT_CONFIGURED",
+            ErrorMessage::WebhookSignatureInvalid => "WEBHOOK_SIGNATURE_INVALID",
+            ErrorMessage::WebhookTimestampInvalid => "WEBHOOK_TIMESTAMP_INVALID",
+            ErrorMessage::WebhookReplayed => "WEBHOOK_REPLAYED",
+            ErrorMessage::UnsupportedMediaType => "UNSUPPORTED_MEDIA_TYPE",
+            ErrorMessage::ImportHeaderInvalid => "IMPORT_HEADER_INVALID",
+            ErrorMessage::InvitationRequired => "INVITATION_REQUIRED",
+            ErrorMessage::InvitationInvalid => "INVITATION_INVALID",
+            ErrorMessage::CaptchaRequired => "CAPTCHA_REQUIRED",
+            ErrorMessage::CaptchaInvalid => "CAPTCHA_INVALID",
+            ErrorMessage::CaptchaUnavailable => "CAPTCHA_UNAVAILABLE",
+            ErrorMessage::UrlSignatureInvalid => "URL_SIGNATURE_INVALID",
+            ErrorMessage::UrlExpired => "URL_EXPIRED",
+            ErrorMessage::AvatarUnavailable => "AVATAR_UNAVAILABLE",
+            ErrorMessage::FeedSourceLimit(_) => "FEED_SOURCE_LIMIT",
+            ErrorMessage::RegionUnavailable(_) => "REGION_UNAVAILABLE",
+            ErrorMessage::ContentRejected => "CONTENT_REJECTED",
+            ErrorMessage::ValidationErrors => "VALIDATION_FAILED",
+        }
+    }
+    /// `WWW-Authenticate` value of a 401 answered with this message (RFC 6750): a bare challenge when
+    /// no token was sent, `invalid_token` when one was sent but can't be used. Webhook signatures are
+    /// not an HTTP auth scheme, so their 401s carry no challenge. `auth_basic` sets its own.
+    fn bearer_challenge(&self) -> Option<&'static str> {
+        match self {
+            ErrorMessage::WebhookSignatureInvalid | ErrorMessage::WebhookTimestampInvalid => None,
+            ErrorMessage::TokenNotProvided | ErrorMessage::UserNotAuthenticated => Some(BEARER_CHALLENGE),
+            _ => Some(INVALID_BEARER_CHALLENGE),
         }
     }
 }
 
+/// Challenge of the basic-auth routes, `/api/metrics` and `/api/auth/basic`.
+pub const BASIC_CHALLENGE: &str = "Basic realm=\"axum-restful-api\", charset=\"UTF-8\"";
+const BEARER_CHALLENGE: &str = "Bearer realm=\"axum-restful-api\"";
+const INVALID_BEARER_CHALLENGE: &str = "Bearer realm=\"axum-restful-api\", error=\"invalid_token\"";
+
 impl Display for ErrorMessage {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         write!(f, "{}", self.get_message().to_owned())
@@ -109,79 +301,164 @@ impl<'a, T> Display for ErrorResponse<'a, T> where T: Serialize {
     }
 }
 
-impl<T> HttpError<T> where T: Serialize {
-    // pub fn new(message: impl Into<String>, status: StatusCode) -> Self 
-    pub fn server_error(message: impl Into<String>, error: Option<T>) -> Self {
-        HttpError {
-            status: StatusCode::INTERNAL_SERVER_ERROR,
-            message: message.into(),
-            error,
-        }
+impl AppError {
+    pub fn server_error(message: ErrorMessage) -> Self {
+        AppError::Http { status: StatusCode::INTERNAL_SERVER_ERROR, message }
     }
-    pub fn too_many_request(message: impl Into<String>, error: Option<T>) -> Self {
-        HttpError {
-            status: StatusCode::TOO_MANY_REQUESTS,
-            message: message.into(),
-            error,
-        }
+    pub fn too_many_request(message: ErrorMessage) -> Self {
+        AppError::Http { status: StatusCode::TOO_MANY_REQUESTS, message }
     }
-    pub fn bad_request(message: impl Into<String>, error: Option<T>) -> Self {
-        HttpError {
-            status: StatusCode::BAD_REQUEST,
-            message: message.into(),
-            error,
-        }
+    pub fn bad_gateway(message: ErrorMessage) -> Self {
+        AppError::Http { status: StatusCode::BAD_GATEWAY, message }
     }
-    pub fn not_found(message: impl Into<String>, error: Option<T>) -> Self {
-        HttpError {
-            status: StatusCode::NOT_FOUND,
-            message: message.into(),
-            error,
-        }
+    pub fn service_unavailable(message: ErrorMessage) -> Self {
+        AppError::Http { status: StatusCode::SERVICE_UNAVAILABLE, message }
     }
-    pub fn unique_constraint_violation(message: impl Into<String>, error: Option<T>) -> Self {
-        HttpError {
-            status: StatusCode::CONFLICT,
-            message: message.into(),
-            error,
-        }
+    pub fn bad_request(message: ErrorMessage) -> Self {
+        AppError::Http { status: StatusCode::BAD_REQUEST, message }
     }
-    pub fn unauthorized(message: impl Into<String>, error: Option<T>) -> Self {
-        HttpError {
-            status: StatusCode::UNAUTHORIZED,
-            message: message.into(),
-            error,
-        }
+    pub fn not_found(message: ErrorMessage) -> Self {
+        AppError::Http { status: StatusCode::NOT_FOUND, message }
     }
-    pub fn forbidden(message: impl Into<String>, error: Option<T>) -> Self {
-        HttpError {
-            status: StatusCode::FORBIDDEN,
-            message: message.into(),
-            error,
-        }
+    pub fn method_not_allowed(message: ErrorMessage) -> Self {
+        AppError::Http { status: StatusCode::METHOD_NOT_ALLOWED, message }
+    }
+    pub fn unique_constraint_violation(message: ErrorMessage) -> Self {
+        AppError::Http { status: StatusCode::CONFLICT, message }
+    }
+    pub fn unauthorized(message: ErrorMessage) -> Self {
+        AppError::Http { status: StatusCode::UNAUTHORIZED, message }
+    }
+    pub fn forbidden(message: ErrorMessage) -> Self {
+        AppError::Http { status: StatusCode::FORBIDDEN, message }
+    }
+    pub fn unsupported_media_type(message: ErrorMessage) -> Self {
+        AppError::Http { status: StatusCode::UNSUPPORTED_MEDIA_TYPE, message }
+    }
+    pub fn unavailable_for_legal_reasons(message: ErrorMessage) -> Self {
+        AppError::Http { status: StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS, message }
     }
 }
 
-impl<T> Display for HttpError<T> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        write!(
-            f,
-            "HttpError: message: {}, status: {}",
-            self.message, self.status
-        )
+fn error_response<T: Serialize>(status: StatusCode, message: ErrorMessage, error: Option<T>) -> Response {
+    let retry_after = match message {
+        ErrorMessage::EmailRateLimited(retry_after)
+        | ErrorMessage::SignInThrottled(retry_after)
+        | ErrorMessage::PostingTooFast(retry_after)
+        | ErrorMessage::QuotaExceeded(retry_after) => Some(retry_after),
+        ErrorMessage::DatabaseBusy => Some(1),
+        _ => None,
+    };
+    let body = Negotiated(ErrorResponse {
+        status: "error",
+        code: message.get_code(),
+        message: message.to_string(),
+        error,
+    });
+    let challenge = (status == StatusCode::UNAUTHORIZED).then(|| message.bearer_challenge()).flatten();
+    let mut response = (status, body).into_response();
+    if let Some(retry_after) = retry_after {
+        response.headers_mut().insert(RETRY_AFTER, HeaderValue::from(retry_after));
+    }
+    if let Some(challenge) = challenge {
+        response.headers_mut().insert(WWW_AUTHENTICATE, HeaderValue::from_static(challenge));
     }
+    response
 }
 
-impl<T> Error for HttpError<T> where T: Debug {}
+impl AppError {
+    /// The status and message the error is answered with, plus the field errors of a failed validation.
+    pub fn into_parts(self) -> (StatusCode, ErrorMessage, Option<Vec<FieldError>>) {
+        match self {
+            AppError::Http { status, message } => (status, message, None),
+            AppError::Validation(errors) => (StatusCode::BAD_REQUEST, ErrorMessage::ValidationErrors, Some(errors)),
+            AppError::ContentRejected(errors) => (StatusCode::UNPROCESSABLE_ENTITY, ErrorMessage::ContentRejected, Some(errors)),
+            AppError::Repository(err) => {
+                let (status, message) = match err {
+                    RepositoryError::NotFound => (StatusCode::NOT_FOUND, ErrorMessage::DataNotFound),
+                    RepositoryError::Forbidden => (StatusCode::FORBIDDEN, ErrorMessage::PermissionDenied),
+                    RepositoryError::Conflict { constraint } => {
+                        let message = match constraint.as_str() {
+                            "users_email_key" => ErrorMessage::EmailExist,
+                            "users_username_key" => ErrorMessage::UsernameExist,
+                            "user_followers_pkey" => ErrorMessage::AlreadyFollowing,
+                            "post_collaborators_pkey" => ErrorMessage::AlreadyCollaborator,
+                            "organization_members_pkey" => ErrorMessage::AlreadyMember,
+                            "organization_invitations_pkey" => ErrorMessage::OrganizationInvitationExist,
+                            "follow_requests_pkey" => ErrorMessage::FollowRequestExist,
+                            _ => ErrorMessage::DataConflict,
+                        };
+                        (StatusCode::CONFLICT, message)
+                    }
+                    RepositoryError::VersionMismatch { .. } => (StatusCode::CONFLICT, ErrorMessage::VersionConflict),
+                    RepositoryError::InvalidInput(_) => (StatusCode::BAD_REQUEST, ErrorMessage::RequestInvalid),
+                    RepositoryError::Db(SqlxError::PoolTimedOut) => {
+                        metrics::increment_counter("db_pool_rejections_total", &[("stage", "query")]);
+                        (StatusCode::SERVICE_UNAVAILABLE, ErrorMessage::DatabaseBusy)
+                    }
+                    RepositoryError::Db(err) => {
+                        error!("Database error: {}", err);
+                        (StatusCode::INTERNAL_SERVER_ERROR, ErrorMessage::ServerError)
+                    }
+                };
+                (status, message, None)
+            }
+            AppError::Redis(err) => {
+                error!("Redis error: {}", err);
+                (StatusCode::INTERNAL_SERVER_ERROR, ErrorMessage::ServerError, None)
+            }
+            // Only token decoding goes through `?`, so every JWT error here is the client's token.
+            // Each kind has its own code so clients can tell whether refreshing the token will help.
+            AppError::Jwt(err) => {
+                let message = match err.kind() {
+                    JwtErrorKind::ExpiredSignature => ErrorMessage::TokenExpired,
+                    JwtErrorKind::ImmatureSignature => ErrorMessage::TokenNotYetValid,
+                    JwtErrorKind::InvalidToken
+                    | JwtErrorKind::Base64(_)
+                    | JwtErrorKind::Json(_)
+                    | JwtErrorKind::Utf8(_) => ErrorMessage::TokenMalformed,
+                    _ => ErrorMessage::TokenInvalid,
+                };
+                (StatusCode::UNAUTHORIZED, message, None)
+            }
+        }
+    }
+}
+
+/// Response extension on 5xx errors: what actually failed, which the client is not told, for
+/// `report_errors`.
+#[derive(Clone)]
+pub struct ErrorDetail {
+    pub message: String,
+    /// The handler panicked, see `catch_panic`.
+    pub panicked: bool,
+}
 
-impl<T> IntoResponse for HttpError<T> where T: Serialize + Debug {
+impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let body = Json(ErrorResponse {
-            status: "error",
-            message: self.message,
-            error: self.error,
-        });
-        (self.status, body).into_response()
+        // The client gets the version it lost against, so it can merge and retry.
+        if let AppError::Repository(RepositoryError::VersionMismatch { current }) = self {
+            return error_response(StatusCode::CONFLICT, ErrorMessage::VersionConflict, Some(current));
+        }
+        let detail = ErrorDetail { message: self.to_string(), panicked: false };
+        let (status, message, errors) = self.into_parts();
+        let mut response = error_response(status, message, errors);
+        if status.is_server_error() {
+            response.extensions_mut().insert(detail);
+        }
+        response
+    }
+}
+
+impl From<ValidationErrors> for AppError {
+    fn from(errors: ValidationErrors) -> Self {
+        AppError::Validation(FieldError::collect_errors(errors))
+    }
+}
+
+impl From<SqlxError> for AppError {
+    fn from(err: SqlxError) -> Self {
+        AppError::Repository(err.into())
     }
 }
 
@@ -204,10 +481,6 @@ impl FieldError {
             .map(|(field, messages)| FieldError { field, messages })
             .collect()
     }
-    pub fn populate_errors(err: ValidationErrors) -> HttpError<ErrorPayload> {
-        let errors = FieldError::collect_errors(err);
-        HttpError::bad_request("Validation Errors", Some(ErrorPayload::ValidationErrors(errors)))
-    }
 }
 
 pub struct BodyParser<T>(pub T);
@@ -216,20 +489,15 @@ where
     Json<T>: FromRequest<S, Rejection = JsonRejection>,
     S: Send + Sync,
 {
-    type Rejection = (StatusCode, Json<ErrorRouting>);
+    type Rejection = AppError;
 
     async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
         let (parts, body) = req.into_parts();
         let req_body = Request::from_parts(parts, body);
         match Json::<T>::from_request(req_body, state).await {
             Ok(value) => Ok(Self(value.0)),
-            Err(rejection) => {
-                let payload = ErrorRouting{
-                    status: "error".to_string(),
-                    message: rejection.body_text(),
-                };
-                Err((rejection.status(), Json(payload)))
-            }
+            Err(JsonRejection::MissingJsonContentType(_)) => Err(AppError::unsupported_media_type(ErrorMessage::UnsupportedMediaType)),
+            Err(rejection) => Err(AppError::Http { status: rejection.status(), message: ErrorMessage::RequestRejected(rejection.body_text()) }),
         }
     }
 }
@@ -240,17 +508,11 @@ where
     T: DeserializeOwned + Send + Sync,
     S: Send + Sync,
 {
-    type Rejection = (StatusCode, Json<ErrorRouting>);
+    type Rejection = AppError;
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         match Query::<T>::from_request_parts(parts, state).await {
             Ok(query) => Ok(Self(query.0)),
-            Err(rejection) => {
-                let payload = ErrorRouting {
-                    status: "error".to_string(),
-                    message: rejection.body_text(),
-                };
-                Err((rejection.status(), Json(payload)))
-            }
+            Err(rejection) => Err(AppError::Http { status: rejection.status(), message: ErrorMessage::RequestRejected(rejection.body_text()) }),
         }
     }
 }
@@ -261,25 +523,129 @@ where
     T: DeserializeOwned + Send + Sync,
     S: Send + Sync,
 {
-    type Rejection = (StatusCode, Json<ErrorRouting>);
+    type Rejection = AppError;
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         match Path::<T>::from_request_parts(parts, state).await {
             Ok(value) => Ok(Self(value.0)),
-            Err(rejection) => {
-                let payload = ErrorRouting {
-                    status: "error".to_string(),
-                    message: rejection.to_string(),
-                };
-                Err((StatusCode::BAD_REQUEST, Json(payload)))
-            }
+            Err(rejection) => Err(AppError::bad_request(ErrorMessage::RequestRejected(rejection.to_string()))),
+        }
+    }
+}
+
+/// `BodyParser` that also runs `validator::Validate`, so handlers receive a body that already passed
+/// validation. Failures are answered with the same `FieldError` payload as `AppError::Validation`.
+pub struct ValidatedJson<T>(pub T);
+impl<S, T> FromRequest<S> for ValidatedJson<T>
+where
+    T: Validate,
+    Json<T>: FromRequest<S, Rejection = JsonRejection>,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let BodyParser(value) = BodyParser::<T>::from_request(req, state).await
+            .map_err(IntoResponse::into_response)?;
+        value.validate().map_err(|e| AppError::from(e).into_response())?;
+        Ok(Self(value))
+    }
+}
+
+/// `QueryParser` counterpart of `ValidatedJson`.
+pub struct ValidatedQuery<T>(pub T);
+impl<S, T> FromRequestParts<S> for ValidatedQuery<T>
+where
+    T: DeserializeOwned + Validate + Send + Sync,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let QueryParser(value) = QueryParser::<T>::from_request_parts(parts, state).await
+            .map_err(IntoResponse::into_response)?;
+        value.validate().map_err(|e| AppError::from(e).into_response())?;
+        Ok(Self(value))
+    }
+}
+
+/// `ValidatedQuery` for list endpoints, it also sanitizes the page and page size with the
+/// configured defaults and limits.
+pub struct PaginatedQuery<T>(pub T);
+impl<S, T> FromRequestParts<S> for PaginatedQuery<T>
+where
+    T: DeserializeOwned + Validate + Paginated + Send + Sync,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let ValidatedQuery(mut value) = ValidatedQuery::<T>::from_request_parts(parts, state).await?;
+        let app_state = parts.extensions.get::<Arc<AppState>>()
+            .ok_or_else(|| AppError::server_error(ErrorMessage::ServerError).into_response())?;
+        value.paginate(&app_state.env);
+        Ok(Self(value))
+    }
+}
+
+/// The `If-Match` header of an update: the `updated_at` of the version the client edited, quoted
+/// the way `IfMatch::etag` renders it. Without the header, or with `*`, the update is unconditional.
+pub struct IfMatch(pub Option<DateTime<Utc>>);
+impl IfMatch {
+    pub fn etag(version: DateTime<Utc>) -> String {
+        format!("\"{}\"", version.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+    }
+}
+impl<S: Send + Sync> FromRequestParts<S> for IfMatch {
+    type Rejection = AppError;
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Some(value) = parts.headers.get(IF_MATCH) else {
+            return Ok(Self(None));
+        };
+        let value = value.to_str().map_err(|_| AppError::bad_request(ErrorMessage::RequestInvalid))?.trim();
+        if value == "*" {
+            return Ok(Self(None));
         }
+        DateTime::parse_from_rfc3339(value.trim_matches('"'))
+            .map(|version| Self(Some(version.with_timezone(&Utc))))
+            .map_err(|_| AppError::bad_request(ErrorMessage::RequestInvalid))
     }
 }
 
-pub fn map_sqlx_error(err: SqlxError) -> HttpError<ErrorPayload> {
-    match err {
-        SqlxError::RowNotFound => HttpError::not_found(ErrorMessage::DataNotFound.to_string(), None),
-        SqlxError::InvalidArgument(e) => HttpError::forbidden(e.to_string(), None),
-        _ => HttpError::server_error(ErrorMessage::ServerError.to_string(), None)
+/// Error returned by every repository. Handlers propagate it with `?` as `AppError::Repository`.
+#[derive(Debug, thiserror::Error)]
+pub enum RepositoryError {
+    #[error("row not found")]
+    NotFound,
+    #[error("permission denied")]
+    Forbidden,
+    #[error("unique constraint {constraint} violated")]
+    Conflict { constraint: String },
+    /// Postgres refused a value sent by the client, e.g. a NUL byte in text or an out of range number.
+    #[error("invalid input: {0}")]
+    InvalidInput(String),
+    /// The `If-Match` version of an update is not the stored one, `current` is the stored record.
+    #[error("record was modified since the expected version")]
+    VersionMismatch { current: Value },
+    #[error(transparent)]
+    Db(SqlxError),
+}
+
+impl RepositoryError {
+    pub fn version_mismatch<T: Serialize>(current: &T) -> Self {
+        RepositoryError::VersionMismatch { current: serde_json::to_value(current).unwrap_or_default() }
+    }
+}
+
+impl From<SqlxError> for RepositoryError {
+    fn from(err: SqlxError) -> Self {
+        match err {
+            SqlxError::RowNotFound => RepositoryError::NotFound,
+            SqlxError::Database(db_err) if db_err.is_unique_violation() => RepositoryError::Conflict {
+                constraint: db_err.constraint().unwrap_or_default().to_string(),
+            },
+            // SQLSTATE class 22 is "data exception", the value itself was rejected.
+            SqlxError::Database(db_err) if db_err.code().is_some_and(|code| code.starts_with("22")) => {
+                RepositoryError::InvalidInput(db_err.message().to_string())
+            }
+            err => RepositoryError::Db(err),
+        }
     }
 }
\ No newline at end of file
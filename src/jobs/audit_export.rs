@@ -0,0 +1,61 @@
+use std::{sync::Arc, time::Duration};
+use log::{info, warn};
+use uuid::Uuid;
+use crate::{
+    AppState,
+    jobs::run_exclusive,
+    modules::audit_log::sink::AuditSink,
+};
+
+pub fn spawn(app_state: Arc<AppState>) {
+    let Some(sink) = AuditSink::from_config(&app_state.env) else {
+        return;
+    };
+    let interval = Duration::from_secs(app_state.env.audit_export_interval);
+    info!("Audit log export to {} sink is enabled.", sink.get_value());
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            run_exclusive(&app_state, "audit_export", interval, || run(&app_state, &sink)).await;
+        }
+    });
+}
+
+/// Sends the unexported audit logs to `sink`, `AUDIT_EXPORT_BATCH_SIZE` at a time. Logs are only
+/// marked as exported after the sink took them, so a failed batch is sent again on the next tick:
+/// at-least-once, and the receiver may see a batch twice. The http sink acknowledges with its
+/// status code and the bucket by storing the object. Syslog has no acknowledgement of its own: a
+/// failed connection or write sends the batch again, messages a collector took and then dropped
+/// aren't noticed.
+pub async fn run(app_state: &AppState, sink: &AuditSink) {
+    let batch_size = app_state.env.audit_export_batch_size;
+    loop {
+        let logs = match app_state.db.audit_logs.get_unexported_audit_logs(batch_size).await {
+            Ok(logs) => logs,
+            Err(e) => {
+                warn!("Failed to load audit logs for export: {}", e);
+                return;
+            }
+        };
+        if logs.is_empty() {
+            return;
+        }
+        let ids: Vec<Uuid> = logs.iter().map(|log| log.id).collect();
+        if let Err(e) = sink.send(&logs).await {
+            warn!("Failed to export {} audit logs to {} sink: {}", ids.len(), sink.get_value(), e);
+            if let Err(e) = app_state.db.audit_logs.mark_audit_logs_failed(&ids).await {
+                warn!("Failed to record the failed export of {} audit logs: {}", ids.len(), e);
+            }
+            return;
+        }
+        // Unmarked logs would be loaded and sent again right away, so wait for the next tick.
+        if let Err(e) = app_state.db.audit_logs.mark_audit_logs_exported(&ids).await {
+            warn!("Exported {} audit logs but failed to mark them, they are sent again on the next run: {}", ids.len(), e);
+            return;
+        }
+        if (logs.len() as i64) < batch_size {
+            return;
+        }
+    }
+}
\ No newline at end of file
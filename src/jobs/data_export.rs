@@ -0,0 +1,31 @@
+use std::{sync::Arc, time::Duration};
+use log::warn;
+use crate::AppState;
+
+/// Every replica polls, the claim keeps them from taking the same export, so exports run side by
+/// side instead of behind one lock.
+pub fn spawn(app_state: Arc<AppState>) {
+    let interval = Duration::from_secs(app_state.env.export_interval);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            run(&app_state).await;
+        }
+    });
+}
+
+/// Runs the queued exports one after the other until none are left.
+pub async fn run(app_state: &Arc<AppState>) {
+    let service = app_state.data_export_service();
+    loop {
+        match service.run_next().await {
+            Ok(true) => {}
+            Ok(false) => return,
+            Err(e) => {
+                warn!("Failed to run a data export: {}", e);
+                return;
+            }
+        }
+    }
+}
\ No newline at end of file
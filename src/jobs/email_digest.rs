@@ -0,0 +1,96 @@
+use std::{sync::Arc, time::Duration};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use crate::{
+    AppState,
+    jobs::run_exclusive,
+    modules::{
+        digest::model::DigestRecipient,
+        email::mail_digest::send_digest_email,
+        user::dto::EmailKind,
+    },
+    utils::metrics,
+};
+
+/// Most posts a digest lists, the rest are only counted.
+const DIGEST_MAX_POSTS: i64 = 10;
+
+pub fn spawn(app_state: Arc<AppState>) {
+    let interval = Duration::from_secs(app_state.env.email_digest_interval);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            run_exclusive(&app_state, "email_digest", interval, || send_due(&app_state)).await;
+        }
+    });
+}
+
+/// One pass over the users whose digest is due, `EMAIL_DIGEST_BATCH_SIZE` at a time with
+/// `EMAIL_DIGEST_BATCH_PAUSE` seconds between batches. A digest that can't be sent is tried again
+/// on the next pass.
+pub async fn send_due(app_state: &AppState) {
+    let now = Utc::now();
+    let batch_size = app_state.env.email_digest_batch_size;
+    let mut after = None;
+    loop {
+        let recipients = match app_state.db.digests.get_due_digests(now, after, batch_size).await {
+            Ok(recipients) => recipients,
+            Err(e) => {
+                warn!("Failed to load the due digests: {}", e);
+                return;
+            }
+        };
+        after = recipients.last().map(|recipient| recipient.user_id);
+        let last_batch = (recipients.len() as i64) < batch_size;
+        for recipient in recipients {
+            send_digest(app_state, recipient, now).await;
+        }
+        if last_batch {
+            return;
+        }
+        tokio::time::sleep(Duration::from_secs(app_state.env.email_digest_batch_pause)).await;
+    }
+}
+
+/// A digest without news isn't sent, its period still counts as covered.
+async fn send_digest(app_state: &AppState, recipient: DigestRecipient, now: DateTime<Utc>) {
+    let settings = recipient.settings.0.with_defaults();
+    if !settings.allows_email(EmailKind::Digest) {
+        return;
+    }
+    let Some((frequency, period)) = settings.email_digest.and_then(|frequency| Some((frequency, frequency.period()?))) else {
+        return;
+    };
+    let since = recipient.sent_at.unwrap_or(now - period);
+    let content = match app_state.db.digests.get_digest_content(recipient.user_id, since, DIGEST_MAX_POSTS).await {
+        Ok(content) => content,
+        Err(e) => {
+            warn!("Failed to load the digest of user {}: {}", recipient.user_id, e);
+            return;
+        }
+    };
+    if !content.is_empty() {
+        let result = send_digest_email(
+            &*app_state.mailer,
+            &recipient.email,
+            &recipient.name,
+            &app_state.env.frontend_url,
+            frequency,
+            &content,
+            settings.time_zone(),
+        ).await;
+        if let Err(e) = result {
+            warn!("Failed to send the digest of user {}: {}", recipient.user_id, e);
+            metrics::increment_counter("email_digests_total", &[("outcome", "failed")]);
+            return;
+        }
+        info!("Sent the digest of user {}", recipient.user_id);
+        metrics::increment_counter("email_digests_total", &[("outcome", "sent")]);
+    } else {
+        metrics::increment_counter("email_digests_total", &[("outcome", "empty")]);
+    }
+    if let Err(e) = app_state.db.digests.mark_digest_sent(recipient.user_id, now).await {
+        warn!("Failed to record the digest of user {}: {}", recipient.user_id, e);
+    }
+}
\ No newline at end of file
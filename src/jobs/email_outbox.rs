@@ -0,0 +1,84 @@
+use std::{sync::Arc, time::Duration};
+use chrono::Utc;
+use log::{info, warn};
+use crate::{
+    AppState,
+    jobs::run_exclusive,
+    modules::email::{mailer::{Mailer, OUTBOX_QUEUED, send_with_timeout}, model::QueuedEmail},
+    utils::{metrics, rand::with_jitter},
+};
+
+/// Most queued emails claimed per poll.
+const OUTBOX_BATCH_SIZE: i64 = 20;
+
+/// Sends the emails `OutboxMailer` and `RetryingMailer` queued, on every poll and whenever
+/// `OutboxMailer` queues one. `mailer` is the one `RetryingMailer` wraps, a failure here is counted
+/// on the queued email and must not queue it again.
+pub fn spawn(app_state: Arc<AppState>, mailer: Arc<dyn Mailer>) {
+    let interval = Duration::from_secs(app_state.env.mail_outbox_interval);
+    // Claimed emails are leased, so the lock only saves replicas from polling the outbox together.
+    let lock_ttl = Duration::from_secs(lease_secs(&app_state) as u64);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = OUTBOX_QUEUED.notified() => {}
+            }
+            run_exclusive(&app_state, "email_outbox", lock_ttl, || send_due(&app_state, &*mailer)).await;
+        }
+    });
+}
+
+/// One pass over the due emails.
+pub async fn send_due(app_state: &AppState, mailer: &dyn Mailer) {
+    let send_timeout = Duration::from_secs(app_state.env.mail_timeout);
+    let emails = match app_state.db.emails.claim_emails(OUTBOX_BATCH_SIZE, lease_secs(app_state)).await {
+        Ok(emails) => emails,
+        Err(e) => {
+            warn!("Failed to load queued emails: {}", e);
+            return;
+        }
+    };
+    for email in emails {
+        send_queued(app_state, mailer, send_timeout, email).await;
+    }
+}
+
+/// Emails of a batch are sent one after another, the lease covers the whole batch timing out.
+fn lease_secs(app_state: &AppState) -> f64 {
+    (app_state.env.mail_timeout * OUTBOX_BATCH_SIZE as u64 + 30) as f64
+}
+
+async fn send_queued(app_state: &AppState, mailer: &dyn Mailer, send_timeout: Duration, email: QueuedEmail) {
+    let outbox = &app_state.db.emails;
+    let error = match send_with_timeout(mailer, send_timeout, &email.to_email, &email.subject, email.html).await {
+        Ok(()) => {
+            info!("Sent queued \"{}\" email {}", email.subject, email.id);
+            metrics::increment_counter("email_outbox_total", &[("outcome", "sent")]);
+            if let Err(e) = outbox.mark_email_sent(email.id).await {
+                warn!("Failed to mark queued email {} as sent: {}", email.id, e);
+            }
+            return;
+        }
+        Err(e) => e.to_string(),
+    };
+    let attempts = email.attempts + 1;
+    let retry_at = (attempts < app_state.env.mail_outbox_max_attempts).then(|| {
+        let backoff = Duration::from_secs(app_state.env.mail_outbox_backoff.saturating_mul(1 << (attempts - 1).min(20)) as u64);
+        Utc::now() + chrono::Duration::milliseconds(with_jitter(backoff).as_millis() as i64)
+    });
+    match retry_at {
+        Some(retry_at) => {
+            warn!("Queued email {} failed, retrying at {}: {}", email.id, retry_at, error);
+            metrics::increment_counter("email_outbox_total", &[("outcome", "retry")]);
+        }
+        None => {
+            warn!("Queued email {} failed {} times, dead-lettered: {}", email.id, attempts, error);
+            metrics::increment_counter("email_outbox_total", &[("outcome", "dead")]);
+        }
+    }
+    if let Err(e) = outbox.mark_email_failed(email.id, &error, retry_at).await {
+        warn!("Failed to record failed queued email {}: {}", email.id, e);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,23 @@
+use std::{sync::Arc, time::Duration};
+use log::{info, warn};
+use crate::{AppState, jobs::run_exclusive};
+
+pub fn spawn(app_state: Arc<AppState>) {
+    let interval = Duration::from_secs(app_state.env.feed_import_interval);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            run_exclusive(&app_state, "feed_import", interval, || run(&app_state)).await;
+        }
+    });
+}
+
+/// Fetches the feeds that are due, how each one went is kept on its source.
+pub async fn run(app_state: &Arc<AppState>) {
+    match app_state.feed_import_service().fetch_due().await {
+        Ok(0) => {}
+        Ok(count) => info!("Fetched {} imported feeds", count),
+        Err(e) => warn!("Failed to fetch the imported feeds: {}", e),
+    }
+}
\ No newline at end of file
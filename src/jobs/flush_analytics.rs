@@ -0,0 +1,33 @@
+use std::{sync::Arc, time::Duration};
+use log::warn;
+use crate::AppState;
+
+pub fn spawn(app_state: Arc<AppState>) {
+    let interval = Duration::from_secs(app_state.env.analytics_flush_interval);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            flush(&app_state).await;
+        }
+    });
+}
+
+/// Hands the events buffered in Redis to `ANALYTICS_SINK`. When the sink fails they go back into
+/// the buffer for the next run, the sink skips the ones it already had.
+pub async fn flush(app_state: &Arc<AppState>) {
+    let events = match app_state.redis_client.take_analytics_events().await {
+        Ok(events) if events.is_empty() => return,
+        Ok(events) => events,
+        Err(e) => {
+            warn!("Failed to read the buffered analytics events: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = app_state.analytics_service().deliver(events.clone()).await {
+        warn!("Failed to flush {} analytics events, keeping them for the next run: {}", events.len(), e);
+        if let Err(e) = app_state.redis_client.buffer_analytics_events(&events).await {
+            warn!("Failed to put back the analytics events, they are lost: {}", e);
+        }
+    }
+}
\ No newline at end of file
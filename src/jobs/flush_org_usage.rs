@@ -0,0 +1,27 @@
+use std::{sync::Arc, time::Duration};
+use log::warn;
+use crate::AppState;
+
+pub fn spawn(app_state: Arc<AppState>) {
+    let interval = Duration::from_secs(app_state.env.organization_usage_flush_interval);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let usage = match app_state.redis_client.take_org_usage().await {
+                Ok(usage) if usage.is_empty() => continue,
+                Ok(usage) => usage,
+                Err(e) => {
+                    warn!("Failed to read the pending organization usage: {}", e);
+                    continue;
+                }
+            };
+            if let Err(e) = app_state.db.organizations.add_usage(&usage).await {
+                warn!("Failed to flush {} organization usage counts, keeping them for the next run: {}", usage.len(), e);
+                if let Err(e) = app_state.redis_client.restore_org_usage(&usage).await {
+                    warn!("Failed to put back the organization usage counts, they are lost: {}", e);
+                }
+            }
+        }
+    });
+}
\ No newline at end of file
@@ -0,0 +1,27 @@
+use std::{sync::Arc, time::Duration};
+use log::warn;
+use crate::AppState;
+
+pub fn spawn(app_state: Arc<AppState>) {
+    let interval = Duration::from_secs(app_state.env.post_view_flush_interval);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let views = match app_state.redis_client.take_post_views().await {
+                Ok(views) if views.is_empty() => continue,
+                Ok(views) => views,
+                Err(e) => {
+                    warn!("Failed to read the pending post views: {}", e);
+                    continue;
+                }
+            };
+            if let Err(e) = app_state.db.posts.add_post_views(&views).await {
+                warn!("Failed to flush {} post view counts, keeping them for the next run: {}", views.len(), e);
+                if let Err(e) = app_state.redis_client.restore_post_views(&views).await {
+                    warn!("Failed to put back the post view counts, they are lost: {}", e);
+                }
+            }
+        }
+    });
+}
\ No newline at end of file
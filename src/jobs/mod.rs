@@ -0,0 +1,30 @@
+use std::{future::Future, time::Duration};
+use log::warn;
+use crate::AppState;
+
+pub mod audit_export;
+pub mod data_export;
+pub mod email_digest;
+pub mod email_outbox;
+pub mod feed_import;
+pub mod flush_analytics;
+pub mod flush_org_usage;
+pub mod flush_post_views;
+pub mod refresh_aggregates;
+pub mod retention;
+pub mod webhook_delivery;
+
+/// Runs one pass of a periodic job under the Redis lock `jobs:{job}`, skipping it while another
+/// replica runs its own. Without Redis every replica runs its passes, which the jobs tolerate, they
+/// only repeat work.
+pub async fn run_exclusive<F, Fut>(app_state: &AppState, job: &str, ttl: Duration, pass: F)
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let key = format!("jobs:{}", job);
+    if let Err(e) = app_state.redis_client.with_lock(&key, ttl, pass()).await {
+        warn!("Failed to take the {} lock, running without it: {}", job, e);
+        pass().await;
+    }
+}
\ No newline at end of file
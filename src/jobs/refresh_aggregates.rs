@@ -0,0 +1,18 @@
+use std::{sync::Arc, time::Duration};
+use log::warn;
+use crate::{AppState, jobs::run_exclusive};
+
+pub fn spawn(app_state: Arc<AppState>) {
+    let interval = Duration::from_secs(app_state.env.aggregate_refresh_interval);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            run_exclusive(&app_state, "refresh_aggregates", interval, || async {
+                if let Err(e) = app_state.db.aggregates.refresh_aggregate_views().await {
+                    warn!("Failed to refresh aggregate views: {}", e);
+                }
+            }).await;
+        }
+    });
+}
\ No newline at end of file
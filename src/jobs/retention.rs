@@ -0,0 +1,27 @@
+use std::{sync::Arc, time::Duration};
+use chrono::Utc;
+use log::{info, warn};
+use crate::{AppState, jobs::run_exclusive};
+
+pub fn spawn(app_state: Arc<AppState>) {
+    let interval = Duration::from_secs(app_state.env.retention_interval);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            run_exclusive(&app_state, "retention", interval, || run(&app_state)).await;
+        }
+    });
+}
+
+/// Applies every retention rule that is on, a failing rule doesn't hold up the others.
+pub async fn run(app_state: &Arc<AppState>) {
+    let service = app_state.retention_service();
+    for (rule, _, cutoff) in service.rules(Utc::now()) {
+        match service.apply(rule, cutoff).await {
+            Ok(0) => {}
+            Ok(count) => info!("Retention rule {} handled {} rows from before {}", rule.get_value(), count, cutoff),
+            Err(e) => warn!("Failed to apply the retention rule {}: {}", rule.get_value(), e),
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,112 @@
+use std::{sync::Arc, time::Duration};
+use chrono::Utc;
+use log::{info, warn};
+use reqwest::{Client, header::CONTENT_TYPE, redirect::Policy};
+use serde_json::json;
+use tokio::task::JoinSet;
+use crate::{
+    AppState,
+    modules::webhook::model::DueWebhookDelivery,
+    utils::{metrics, rand::with_jitter, webhook_verify::sign_standard_webhook},
+};
+
+pub fn spawn(app_state: Arc<AppState>) {
+    let interval = Duration::from_secs(app_state.env.webhook_delivery_interval);
+    let client = client(&app_state);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            run(&app_state, &client).await;
+        }
+    });
+}
+
+/// The client deliveries are sent with: `WEBHOOK_TIMEOUT` per request and redirects not followed.
+pub fn client(app_state: &AppState) -> Client {
+    Client::builder()
+        .timeout(Duration::from_secs(app_state.env.webhook_timeout))
+        .redirect(Policy::none())
+        .build()
+        .expect("Failed to build the webhook HTTP client.")
+}
+
+/// Sends the due deliveries, a batch of `WEBHOOK_DELIVERY_BATCH_SIZE` at a time until none are left.
+pub async fn run(app_state: &Arc<AppState>, client: &Client) {
+    let batch_size = app_state.env.webhook_delivery_batch_size;
+    // A claimed batch is sent concurrently, so it is done well within one timeout; the lease only
+    // runs out when this instance died mid-batch, and then another one picks the deliveries up.
+    let lease_secs = (app_state.env.webhook_timeout + 30) as f64;
+    loop {
+        let deliveries = match app_state.db.webhooks.claim_webhook_deliveries(batch_size, lease_secs).await {
+            Ok(deliveries) => deliveries,
+            Err(e) => {
+                warn!("Failed to load due webhook deliveries: {}", e);
+                break;
+            }
+        };
+        let claimed = deliveries.len() as i64;
+        let mut tasks = JoinSet::new();
+        for delivery in deliveries {
+            tasks.spawn(deliver(app_state.clone(), client.clone(), delivery));
+        }
+        tasks.join_all().await;
+        if claimed < batch_size {
+            break;
+        }
+    }
+}
+
+/// Sends one delivery signed like a Standard Webhooks message (`webhook-id`, `webhook-timestamp`,
+/// `webhook-signature`). Anything but a 2xx answer counts as a failed attempt, redirects included,
+/// and is retried after an exponential backoff with jitter.
+async fn deliver(app_state: Arc<AppState>, client: Client, delivery: DueWebhookDelivery) {
+    let webhooks = &app_state.db.webhooks;
+    let message_id = delivery.id.to_string();
+    let body = json!({
+        "id": delivery.id,
+        "event": delivery.event,
+        "created_at": delivery.created_at,
+        "data": delivery.payload,
+    }).to_string();
+    let timestamp = Utc::now().timestamp();
+    let signature = sign_standard_webhook(&delivery.secret, &message_id, timestamp, body.as_bytes());
+    let result = client.post(&delivery.url)
+        .header(CONTENT_TYPE, "application/json")
+        .header("webhook-id", &message_id)
+        .header("webhook-timestamp", timestamp.to_string())
+        .header("webhook-signature", signature)
+        .body(body)
+        .send()
+        .await;
+    let (status_code, error) = match result {
+        Ok(response) if response.status().is_success() => {
+            info!("Webhook delivery {} to {} delivered", delivery.id, delivery.url);
+            metrics::increment_counter("webhook_deliveries_total", &[("outcome", "delivered")]);
+            if let Err(e) = webhooks.mark_webhook_delivered(delivery.id, response.status().as_u16() as i32).await {
+                warn!("Failed to mark webhook delivery {} as delivered: {}", delivery.id, e);
+            }
+            return;
+        }
+        Ok(response) => (Some(response.status().as_u16() as i32), format!("Endpoint answered {}", response.status())),
+        Err(e) => (None, e.to_string()),
+    };
+    let attempts = delivery.attempts + 1;
+    let retry_at = (attempts < app_state.env.webhook_max_attempts).then(|| {
+        let backoff = Duration::from_secs(app_state.env.webhook_retry_backoff.saturating_mul(1 << (attempts - 1).min(20)) as u64);
+        Utc::now() + chrono::Duration::milliseconds(with_jitter(backoff).as_millis() as i64)
+    });
+    match retry_at {
+        Some(retry_at) => {
+            warn!("Webhook delivery {} to {} failed, retrying at {}: {}", delivery.id, delivery.url, retry_at, error);
+            metrics::increment_counter("webhook_deliveries_total", &[("outcome", "retry")]);
+        }
+        None => {
+            warn!("Webhook delivery {} to {} failed {} times, dead-lettered: {}", delivery.id, delivery.url, attempts, error);
+            metrics::increment_counter("webhook_deliveries_total", &[("outcome", "dead")]);
+        }
+    }
+    if let Err(e) = webhooks.mark_webhook_failed(delivery.id, status_code, &error, retry_at).await {
+        warn!("Failed to record failed webhook delivery {}: {}", delivery.id, e);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,89 @@
+pub mod dto;
+pub mod error;
+pub mod config;
+pub mod router;
+pub mod db;
+pub mod utils;
+pub mod modules;
+pub mod middleware;
+pub mod jobs;
+pub mod seed;
+pub mod openapi;
+// In-process server with in-memory repositories, a fake mailer and a fake Redis, for tests
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+#[cfg(feature = "client")]
+pub mod client;
+
+use std::sync::Arc;
+use config::Config;
+use db::Repositories;
+use modules::{
+    analytics::service::AnalyticsService,
+    announcement::service::AnnouncementService,
+    auth::service::AuthService,
+    data_export::service::DataExportService,
+    email::mailer::Mailer,
+    event::{domain_event::DomainEvent, publisher::EventPublisher},
+    feature_flag::service::FeatureFlags,
+    feed_import::service::FeedImportService,
+    organization::service::OrganizationService,
+    post::service::PostService,
+    redis::redis::RedisClient,
+    retention::service::RetentionService,
+    tos::service::TosService,
+    user::service::UserService,
+};
+use utils::{load::LoadMonitor, moderation::ContentModerator, password::PasswordHasher};
+
+#[derive(Clone)]
+pub struct AppState {
+    pub env: Config,
+    pub db: Repositories,
+    pub redis_client: RedisClient,
+    pub mailer: Arc<dyn Mailer>,
+    pub events: Arc<dyn EventPublisher>,
+    pub hasher: Arc<dyn PasswordHasher>,
+    pub moderator: Arc<dyn ContentModerator>,
+    pub load: Arc<LoadMonitor>,
+}
+
+/// Business rules live in the services; handlers, jobs and CLI commands all go through these.
+impl AppState {
+    pub fn auth_service(self: &Arc<Self>) -> AuthService {
+        AuthService::new(self.clone())
+    }
+    pub fn user_service(self: &Arc<Self>) -> UserService {
+        UserService::new(self.clone())
+    }
+    pub fn post_service(self: &Arc<Self>) -> PostService {
+        PostService::new(self.clone())
+    }
+    pub fn organization_service(self: &Arc<Self>) -> OrganizationService {
+        OrganizationService::new(self.clone())
+    }
+    pub fn analytics_service(self: &Arc<Self>) -> AnalyticsService {
+        AnalyticsService::new(self.clone())
+    }
+    pub fn announcement_service(self: &Arc<Self>) -> AnnouncementService {
+        AnnouncementService::new(self.clone())
+    }
+    pub fn tos_service(self: &Arc<Self>) -> TosService {
+        TosService::new(self.clone())
+    }
+    pub fn retention_service(self: &Arc<Self>) -> RetentionService {
+        RetentionService::new(self.clone())
+    }
+    pub fn data_export_service(self: &Arc<Self>) -> DataExportService {
+        DataExportService::new(self.clone())
+    }
+    pub fn feed_import_service(self: &Arc<Self>) -> FeedImportService {
+        FeedImportService::new(self.clone())
+    }
+    pub fn flags(self: &Arc<Self>) -> FeatureFlags {
+        FeatureFlags::new(self.clone())
+    }
+    pub async fn publish(self: &Arc<Self>, event: DomainEvent) {
+        self.events.publish(self, &event).await;
+    }
+}
\ No newline at end of file
@@ -1,51 +1,82 @@
-use std::{process::exit, sync::Arc, time::Duration};
+use std::{net::SocketAddr, process::exit, sync::Arc, time::Duration};
 use axum::http::{
-    header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE},
-    HeaderValue, 
+    header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE, ETAG, IF_MATCH, RETRY_AFTER},
+    HeaderName,
     Method,
 };
+use axum::serve::ListenerExt;
+use clap::{Parser, Subcommand};
 use dotenv::dotenv;
 use sqlx::postgres::PgPoolOptions;
-use config::Config;
 use tower_http::cors::CorsLayer;
 use tracing_subscriber::filter::LevelFilter;
-use db::DBClient;
-use crate::modules::redis::redis::RedisClient;
+use axum_restful_api::{
+    config::Config,
+    db::{DBClient, Repositories},
+    jobs,
+    middleware::{auth::TOKEN_EXPIRES_IN_HEADER, catch_panic::install_panic_hook, org_quota::{QUOTA_LIMIT_HEADER, QUOTA_REMAINING_HEADER, QUOTA_RESET_HEADER}, tos::TOS_REQUIRED_HEADER},
+    modules::{
+        email::mailer::{Mailer, RetryingMailer, SmtpMailer},
+        event::{publisher::{EventPublisher, LocalEventBus}, stream::{EventStream, StreamingEventBus}},
+        redis::redis::RedisClient,
+    },
+    router,
+    seed::{self, AdminAccount},
+    utils::{load::LoadMonitor, moderation, password::Argon2idHasher, tls},
+    AppState,
+};
 
-mod dto;
-mod error;
-mod config;
-mod router;
-mod db;
-mod utils;
-mod modules;
-mod middleware;
+#[derive(Parser)]
+#[command(about = "RESTful API built with Axum")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
 
-#[derive(Clone)]
-pub struct AppState {
-    pub env: Config,
-    pub db_client: DBClient,
-    pub redis_client: RedisClient,
+#[derive(Subcommand, PartialEq)]
+enum Command {
+    /// Run the HTTP server (the default when no command is given)
+    Serve,
+    /// Insert the roles, permissions and role grants the API needs, plus the SEED_ADMIN_* account
+    Seed,
 }
+
 #[tokio::main]
 async fn main() {
+    let cli = Cli::parse();
     tracing_subscriber::fmt()
         .with_max_level(LevelFilter::DEBUG)
         .init();
+    install_panic_hook();
     
     dotenv().ok();
-    let config = Config::init();
-    let frontend_url = &config.frontend_url;
+    let config = match Config::init() {
+        Ok(config) => config,
+        Err(err) => {
+            println!("🔥 {}", err);
+            exit(1);
+        }
+    };
     let max_connections = &config.max_connections;
     let min_connections = &config.min_connections;
     let acquire_timeout = &config.acquire_timeout;
     let idle_timeout = &config.idle_timeout;
     let redis_url = &config.redis_url;
     let cors = CorsLayer::new()
-        .allow_origin(frontend_url.parse::<HeaderValue>().unwrap())
-        .allow_headers([AUTHORIZATION, ACCEPT, CONTENT_TYPE])
+        .allow_origin(config.frontend_origin.clone())
+        .allow_headers([AUTHORIZATION, ACCEPT, CONTENT_TYPE, IF_MATCH])
+        .expose_headers([
+            HeaderName::from_static(TOKEN_EXPIRES_IN_HEADER),
+            HeaderName::from_static("x-request-id"),
+            ETAG,
+            RETRY_AFTER,
+            HeaderName::from_static(QUOTA_LIMIT_HEADER),
+            HeaderName::from_static(QUOTA_REMAINING_HEADER),
+            HeaderName::from_static(QUOTA_RESET_HEADER),
+            HeaderName::from_static(TOS_REQUIRED_HEADER),
+        ])
         .allow_credentials(true)
-        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE]);
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::PATCH, Method::DELETE]);
 
     let pool = match PgPoolOptions::new()
         .max_connections(*max_connections)
@@ -64,24 +95,101 @@ async fn main() {
             exit(1);
         }
     };
-    let db_client = DBClient::new(pool);
-    let redis_client = RedisClient::new(redis_url).await.expect("Failed to connect to Redis.");
+    let db = Repositories::postgres(
+        DBClient::new(pool).with_slow_query_threshold(Duration::from_millis(config.slow_query_threshold))
+    );
+    if cli.command == Some(Command::Seed) {
+        match seed::run(&db, &config).await {
+            Ok(report) => {
+                println!(
+                    "🌱 Seeded {} roles, {} permissions and {} role permissions",
+                    report.roles, report.permissions, report.grants,
+                );
+                match report.admin {
+                    Some(AdminAccount::Created(email)) => println!("🌱 Created admin account {}", email),
+                    Some(AdminAccount::Promoted(email)) => println!("🌱 Admin account {} is active", email),
+                    None => println!("🌱 SEED_ADMIN_EMAIL is not set, no admin account seeded"),
+                }
+            }
+            Err(err) => {
+                println!("🔥 Seeding failed: {}", err);
+                exit(1);
+            }
+        }
+        return;
+    }
+    let redis_client = RedisClient::new(
+        redis_url,
+        Duration::from_millis(config.redis_command_timeout),
+        config.cache_compression_min_size,
+    ).await.expect("Failed to connect to Redis.")
+        .with_circuit_breaker(config.redis_breaker_threshold, Duration::from_millis(config.redis_breaker_cooldown));
+    let events: Arc<dyn EventPublisher> = match EventStream::from_config(&config).await {
+        Ok(Some(stream)) => Arc::new(StreamingEventBus::spawn(stream, LocalEventBus::default(), config.event_stream_buffer)),
+        Ok(None) => Arc::new(LocalEventBus::default()),
+        Err(e) => panic!("Failed to connect to the {} event stream: {}", config.event_stream, e),
+    };
+    let smtp_mailer: Arc<dyn Mailer> = Arc::new(SmtpMailer);
+    let mailer = Arc::new(RetryingMailer::new(smtp_mailer.clone(), db.emails.clone(), &config));
     let app_state = Arc::new(AppState {
         env: config.clone(),
-        db_client,
+        db,
         redis_client,
+        mailer,
+        events,
+        hasher: Arc::new(Argon2idHasher::from_config(&config)),
+        moderator: moderation::from_config(&config),
+        load: Arc::new(LoadMonitor::default()),
     });
+    jobs::audit_export::spawn(app_state.clone());
+    jobs::refresh_aggregates::spawn(app_state.clone());
+    jobs::flush_post_views::spawn(app_state.clone());
+    jobs::flush_org_usage::spawn(app_state.clone());
+    jobs::flush_analytics::spawn(app_state.clone());
+    jobs::webhook_delivery::spawn(app_state.clone());
+    jobs::email_outbox::spawn(app_state.clone(), smtp_mailer);
+    jobs::email_digest::spawn(app_state.clone());
+    jobs::retention::spawn(app_state.clone());
+    jobs::data_export::spawn(app_state.clone());
+    jobs::feed_import::spawn(app_state.clone());
     let app = router::create_router(app_state).layer(cors);
-    println!("🚀 Server is running on http://localhost:{}", &config.port);
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", &config.port))
         .await.expect("Failed to bind address");
-    axum::serve(listener, app).await.expect("Failed to run server");
+    let (Some(cert_path), Some(key_path)) = (&config.tls_cert_path, &config.tls_key_path) else {
+        println!("🚀 Server is running on http://localhost:{}", &config.port);
+        axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.expect("Failed to run server");
+        return;
+    };
+    let tls_config = match tls::load_tls_config(cert_path, key_path) {
+        Ok(tls_config) => tls_config,
+        Err(err) => {
+            println!("🔥 {}", err);
+            exit(1);
+        }
+    };
+    if let Some(redirect_port) = config.http_redirect_port {
+        let redirect_listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", redirect_port))
+            .await.expect("Failed to bind HTTP redirect address");
+        let https_port = config.port;
+        tokio::spawn(async move {
+            if let Err(err) = tls::serve_https_redirect(redirect_listener, https_port).await {
+                println!("🔥 HTTP redirect server stopped: {:?}", err);
+            }
+        });
+        println!("↪️  Redirecting http://localhost:{} to HTTPS", redirect_port);
+    }
+    let listener = tls::TlsListener::new(listener, tls_config).expect("Failed to start TLS listener");
+    println!("🚀 Server is running on https://localhost:{}", &config.port);
+    // axum only hands the peer address of a custom listener to handlers through `TapIo`.
+    let listener = listener.tap_io(|_| {});
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.expect("Failed to run server");
 }
 
 #[cfg(test)]
 mod tests {
     use std::{error::Error, time::Duration};
     use axum::http::StatusCode;
+    use axum_restful_api::test_utils::spawn_app;
     use tokio::time;
 
     #[test]
@@ -90,27 +198,27 @@ mod tests {
         assert_eq!(result, 8, "wrong result")
     }
 
-    // YOU MUST RUN THE APPLICATION BEFORE RUNNING THE RATE LIMITER TEST
     #[tokio::test]
     async fn test_rate_limiter() -> Result<(), Box<dyn Error>> {
+        let app = spawn_app(&[("RATE_LIMITER_MAX", "5"), ("RATE_LIMITER_DURATION", "1")]).await;
         let http_client = reqwest::Client::new();
         for i in 1..=5 {
             let response = http_client
-                .get("http://localhost:4000/api/ping")
+                .get(app.url("/api/ping"))
                 .send()
                 .await?;
             let status = response.status();
             assert_eq!(status, StatusCode::OK, "Failed at request number {}", i);
         }
         let response = http_client
-            .get("http://localhost:4000/api/ping")
+            .get(app.url("/api/ping"))
             .send()
             .await?;
         let status = response.status();
         assert_eq!(status, StatusCode::TOO_MANY_REQUESTS, "Expected rate limiting on request #6");
         time::sleep(Duration::from_secs(1)).await;
         let response = http_client
-            .get("http://localhost:4000/api/ping")
+            .get(app.url("/api/ping"))
             .send()
             .await?;
         let status = response.status();
@@ -1,95 +1,162 @@
 use std::sync::Arc;
 use axum::{
     extract::Request,
-    http::{header},
+    http::{header, HeaderMap, HeaderValue, Method},
     middleware::Next,
-    response::IntoResponse,
+    response::{IntoResponse, Response},
     Extension
 };
+use chrono::Utc;
+use serde_json::json;
 use uuid::Uuid;
 use crate::{
-    modules::user::model::UserRepository,
-    error::{ErrorMessage, HttpError},
+    error::{ErrorMessage, AppError, BASIC_CHALLENGE},
+    modules::{audit_log::model::{AuditAction, NewAuditLog}, user::model::User},
     utils::jwt,
     AppState,
-    middleware::AuthenticatedUser
+    middleware::{permission::{has_permission, Permission}, ActingUser, AuthenticatedUser},
 };
 use base64::{Engine as _, engine::{general_purpose}};
+use log::warn;
 
-fn read_header(req: &Request) -> Option<String> {
-    let value = req.headers()
+/// The `x-token-expires-in` header on authenticated responses: seconds until the access token expires.
+pub const TOKEN_EXPIRES_IN_HEADER: &str = "x-token-expires-in";
+
+fn read_header(headers: &HeaderMap) -> Option<String> {
+    headers
         .get(header::AUTHORIZATION)
         .and_then(|auth_header| auth_header.to_str().ok())
-        .and_then(|auth_value| Some(auth_value.to_owned()));
-    value
+        .map(|auth_value| auth_value.to_owned())
 }
 
-pub async fn auth_token(
-    Extension(app_state): Extension<Arc<AppState>>,
-    mut req: Request,
-    next: Next,
-) -> Result<impl IntoResponse, HttpError<()>> {
-    let header_value = read_header(&req);
-    let header_authorization = header_value.ok_or(
-        HttpError::unauthorized(ErrorMessage::TokenNotProvided.to_string(), None)
+/// The token of a `Bearer` authorization header.
+pub fn bearer_token(headers: &HeaderMap) -> Result<String, AppError> {
+    let header_authorization = read_header(headers).ok_or(
+        AppError::unauthorized(ErrorMessage::TokenNotProvided)
     )?;
     if header_authorization.trim().is_empty() {
-        return Err(HttpError::unauthorized(ErrorMessage::TokenNotProvided.to_string(), None))
+        return Err(AppError::unauthorized(ErrorMessage::TokenNotProvided))
     }
     let parts: Vec<&str> = header_authorization.split_whitespace().collect();
     if parts.len() != 2 || parts[0] != "Bearer" {
-        return Err(HttpError::unauthorized(ErrorMessage::TokenInvalid.to_string(), None))
+        return Err(AppError::unauthorized(ErrorMessage::TokenInvalid))
     }
-    let token = parts[1].to_string();
-    let token_user_id = match jwt::parse_token(token, app_state.env.jwt_secret.as_bytes()) {
-        Ok(value) => value,
-        Err(_) => {
-            return Err(HttpError::unauthorized(ErrorMessage::TokenInvalid.to_string(), None));
+    Ok(parts[1].to_string())
+}
+
+/// Under an impersonation token (`act_as` claim) the request runs as the impersonated user, with the
+/// admin in `AuthenticatedUser::impersonator`, and every request that may change data is written to
+/// the audit log with the admin as the actor.
+pub async fn auth_token(
+    Extension(app_state): Extension<Arc<AppState>>,
+    mut req: Request,
+    next: Next,
+) -> Result<impl IntoResponse, AppError> {
+    let token = bearer_token(req.headers())?;
+    let claims = jwt::parse_token(token, &app_state.env.jwt_keys, app_state.env.jwt_leeway)?;
+    let user_id = parse_user_id(&claims.sub)?;
+    let user_data = load_user(&app_state, user_id).await?;
+    let authenticated_user = match claims.act_as.as_deref() {
+        Some(act_as) => {
+            // The token outlives a demotion, so the admin's grant is checked again on every request.
+            if !has_permission(&app_state, user_data.role_id, Permission::AdminImpersonate).await? {
+                return Err(AppError::forbidden(ErrorMessage::PermissionDenied));
+            }
+            let impersonated = load_user(&app_state, parse_user_id(act_as)?).await?;
+            AuthenticatedUser { user: impersonated, impersonator: Some(user_data) }
         }
+        None => AuthenticatedUser { user: user_data, impersonator: None },
     };
-    let user_id = Uuid::parse_str(token_user_id.as_str())
-        .map_err(|_| HttpError::unauthorized(ErrorMessage::TokenInvalid.to_string(), None))?;
+    let (method, path) = (req.method().clone(), req.uri().path().to_string());
+    let audited_impersonation = authenticated_user.impersonator.as_ref()
+        .filter(|_| !matches!(method, Method::GET | Method::HEAD | Method::OPTIONS))
+        .map(|admin| (admin.id, authenticated_user.user.id));
+    let acting_user = ActingUser(authenticated_user.user.id);
+    req.extensions_mut().insert(authenticated_user);
+    let mut response = next.run(req).await;
+    response.extensions_mut().insert(acting_user);
+    if let Some((admin_id, user_id)) = audited_impersonation {
+        let metadata = json!({ "method": method.as_str(), "path": path, "status": response.status().as_u16() });
+        let audit_log = NewAuditLog::new(Some(admin_id), user_id, AuditAction::ImpersonatedRequest).with_metadata(metadata);
+        if let Err(e) = app_state.db.audit_logs.save_audit_log(audit_log).await {
+            warn!("Failed to audit {} {} by admin {} as user {}: {}", method, path, admin_id, user_id, e);
+        }
+    }
+    let expires_in = (claims.exp as i64 - Utc::now().timestamp()).max(0);
+    response.headers_mut().insert(TOKEN_EXPIRES_IN_HEADER, HeaderValue::from(expires_in));
+    Ok(response)
+}
+
+/// The user a request acts as when it carries a usable access token, for public routes that show
+/// signed in users more. A missing, invalid or expired token is treated as a signed out visitor.
+pub async fn optional_user(app_state: &AppState, headers: &HeaderMap) -> Option<User> {
+    let token = bearer_token(headers).ok()?;
+    let claims = jwt::parse_token(token, &app_state.env.jwt_keys, app_state.env.jwt_leeway).ok()?;
+    let user_id = parse_user_id(claims.act_as.as_deref().unwrap_or(&claims.sub)).ok()?;
+    load_user(app_state, user_id).await.ok()
+}
+
+fn parse_user_id(value: &str) -> Result<Uuid, AppError> {
+    Uuid::parse_str(value).map_err(|_| AppError::unauthorized(ErrorMessage::TokenInvalid))
+}
+
+/// The user behind a token, from the cache when possible. Deleted and banned users are refused.
+async fn load_user(app_state: &AppState, user_id: Uuid) -> Result<User, AppError> {
+    // A Redis outage only costs a database round trip, it must not lock everyone out.
     let cached_user = app_state.redis_client.get_user(&user_id).await
-        .map_err(|e| HttpError::server_error(e.to_string(), None))?;
+        .unwrap_or_else(|e| {
+            warn!("Failed to read user {} from cache: {}", user_id, e);
+            None
+        });
     let user_data = match cached_user {
         Some(data) => data,
         None => {
-            let user = app_state.db_client.get_user_by_id(&user_id).await
-                .map_err(|_| HttpError::unauthorized(ErrorMessage::UserNoLongerExist.to_string(), None))?
-                .ok_or_else(|| HttpError::unauthorized(ErrorMessage::UserNoLongerExist.to_string(), None))?;
-            let _ = app_state.redis_client.set_user(&user, app_state.env.jwt_max_age as u64).await;
+            let user = app_state.db.users.get_user_by_id(&user_id).await
+                .map_err(|_| AppError::unauthorized(ErrorMessage::UserNoLongerExist))?
+                .ok_or_else(|| AppError::unauthorized(ErrorMessage::UserNoLongerExist))?;
+            let _ = app_state.redis_client.set_user(&user, app_state.env.user_cache_ttl).await;
             user
         }
     };
-    req.extensions_mut().insert(AuthenticatedUser {
-        user: user_data,
-    });
-    Ok(next.run(req).await)
+    if user_data.is_banned {
+        return Err(AppError::forbidden(ErrorMessage::AccountBanned));
+    }
+    Ok(user_data)
 }
 
+/// Guards a route with the `AUTH_BASIC_*` credentials. Its 401s challenge for Basic rather than Bearer.
 pub async fn auth_basic(
     Extension(app_state): Extension<Arc<AppState>>,
     req: Request,
     next: Next,
-) -> Result<impl IntoResponse, HttpError<()>> {
-    let header_value = read_header(&req);
-    let basic_value = header_value.ok_or(HttpError::unauthorized(ErrorMessage::TokenNotProvided.to_string(), None))?;
+) -> Response {
+    if let Err(err) = check_basic_credentials(&app_state, req.headers()) {
+        let mut response = err.into_response();
+        response.headers_mut().insert(header::WWW_AUTHENTICATE, HeaderValue::from_static(BASIC_CHALLENGE));
+        return response;
+    }
+    next.run(req).await
+}
+
+fn check_basic_credentials(app_state: &AppState, headers: &HeaderMap) -> Result<(), AppError> {
+    let header_value = read_header(headers);
+    let basic_value = header_value.ok_or(AppError::unauthorized(ErrorMessage::TokenNotProvided))?;
     if basic_value.trim().is_empty() {
-        return Err(HttpError::unauthorized(ErrorMessage::TokenNotProvided.to_string(), None))
+        return Err(AppError::unauthorized(ErrorMessage::TokenNotProvided))
     }
     let parts: Vec<&str> = basic_value.split_whitespace().collect();
     if parts.len() != 2 || parts[0] != "Basic" {
-        return Err(HttpError::unauthorized(ErrorMessage::TokenInvalid.to_string(), None))
+        return Err(AppError::unauthorized(ErrorMessage::TokenInvalid))
     }
     let decoded_bytes = general_purpose::STANDARD
         .decode(parts[1].as_bytes())
-        .map_err(|e| HttpError::unauthorized(e.to_string(), None))?;
+        .map_err(|_| AppError::unauthorized(ErrorMessage::TokenInvalid))?;
     let decoded_string = String::from_utf8(decoded_bytes)
-        .map_err(|_| HttpError::unauthorized(ErrorMessage::TokenInvalid.to_string(), None))?
+        .map_err(|_| AppError::unauthorized(ErrorMessage::TokenInvalid))?
         .to_string();
     let parts: Vec<&str> = decoded_string.split(':').collect();
     if parts.len() != 2 || parts[0] != app_state.env.auth_basic_username || parts[1] != app_state.env.auth_basic_password {
-        return Err(HttpError::unauthorized(ErrorMessage::WrongCredentials.to_string(), None))
+        return Err(AppError::unauthorized(ErrorMessage::WrongCredentials))
     }
-    Ok(next.run(req).await)
+    Ok(())
 }
\ No newline at end of file
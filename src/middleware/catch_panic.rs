@@ -0,0 +1,28 @@
+use std::{any::Any, backtrace::Backtrace, panic};
+use axum::response::{IntoResponse, Response};
+use log::error;
+use crate::error::{AppError, ErrorDetail, ErrorMessage};
+
+/// Responder for `tower_http::catch_panic::CatchPanicLayer`: a panicking handler is answered with the
+/// usual `INTERNAL_ERROR` body instead of a dropped connection, and `report_errors` gets the panic
+/// message as the response's `ErrorDetail`.
+pub fn handle_panic(panic: Box<dyn Any + Send + 'static>) -> Response {
+    let message = format!("panicked: {}", panic_message(&*panic));
+    let mut response = AppError::server_error(ErrorMessage::ServerError).into_response();
+    response.extensions_mut().insert(ErrorDetail { message, panicked: true });
+    response
+}
+
+/// Logs panics with their backtrace through the app's logger rather than to bare stderr. The hook
+/// runs before the stack unwinds, so the backtrace points at the panic, not at `handle_panic`.
+pub fn install_panic_hook() {
+    panic::set_hook(Box::new(|info| {
+        error!("{}\n{}", info, Backtrace::force_capture());
+    }));
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    panic.downcast_ref::<&str>().copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
\ No newline at end of file
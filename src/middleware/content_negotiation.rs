@@ -0,0 +1,114 @@
+use axum::{
+    extract::{FromRequestParts, Request},
+    http::{HeaderMap, HeaderValue, StatusCode, header::{ACCEPT, CONTENT_TYPE, VARY}, request::Parts},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use log::error;
+use serde::Serialize;
+
+tokio::task_local! {
+    static RESPONSE_FORMAT: ResponseFormat;
+}
+
+/// The body format picked from the `Accept` header. JSON unless the client prefers MessagePack or
+/// CBOR, an `Accept` naming none of the three also gets JSON rather than a 406.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ResponseFormat {
+    #[default]
+    Json,
+    MessagePack,
+    Cbor,
+}
+
+impl ResponseFormat {
+    fn from_media_type(media_type: &str) -> Option<Self> {
+        match media_type {
+            "application/json" | "application/*" | "*/*" => Some(ResponseFormat::Json),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => Some(ResponseFormat::MessagePack),
+            "application/cbor" => Some(ResponseFormat::Cbor),
+            _ => None,
+        }
+    }
+    /// The supported type with the highest `q`, the first one listed on a tie.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let mut best: Option<(Self, f32)> = None;
+        let accepted = headers.get_all(ACCEPT).iter().filter_map(|value| value.to_str().ok());
+        for media_range in accepted.flat_map(|value| value.split(',')) {
+            let mut params = media_range.split(';').map(str::trim);
+            let Some(format) = params.next().and_then(|media_type| Self::from_media_type(&media_type.to_ascii_lowercase())) else {
+                continue;
+            };
+            let quality = params
+                .find_map(|param| param.strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+                best = Some((format, quality));
+            }
+        }
+        best.map(|(format, _)| format).unwrap_or_default()
+    }
+    /// The format negotiated for the request being handled, JSON outside of `negotiate_format`.
+    pub fn current() -> Self {
+        RESPONSE_FORMAT.try_with(|format| *format).unwrap_or_default()
+    }
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ResponseFormat::Json => "application/json",
+            ResponseFormat::MessagePack => "application/msgpack",
+            ResponseFormat::Cbor => "application/cbor",
+        }
+    }
+    /// MessagePack and CBOR bodies are encoded from the JSON value, so they have the JSON body's
+    /// exact shape: uuids and timestamps stay strings instead of the binary forms both formats
+    /// would otherwise pick.
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, String> {
+        match self {
+            ResponseFormat::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+            ResponseFormat::MessagePack => {
+                let value = serde_json::to_value(value).map_err(|e| e.to_string())?;
+                rmp_serde::to_vec(&value).map_err(|e| e.to_string())
+            }
+            ResponseFormat::Cbor => {
+                let value = serde_json::to_value(value).map_err(|e| e.to_string())?;
+                let mut body = Vec::new();
+                ciborium::into_writer(&value, &mut body).map_err(|e| e.to_string())?;
+                Ok(body)
+            }
+        }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for ResponseFormat {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self::from_headers(&parts.headers))
+    }
+}
+
+/// Serializes `T` in the format negotiated for the request, `SuccessResponse` and the error
+/// envelopes go through it so handlers don't have to know about `Accept`.
+pub struct Negotiated<T>(pub T);
+
+impl<T: Serialize> IntoResponse for Negotiated<T> {
+    fn into_response(self) -> Response {
+        let format = ResponseFormat::current();
+        match format.encode(&self.0) {
+            Ok(body) => ([(CONTENT_TYPE, HeaderValue::from_static(format.content_type()))], body).into_response(),
+            Err(e) => {
+                error!("Failed to serialize a {} response: {}", format.content_type(), e);
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    }
+}
+
+/// Makes the `Accept` header's format the one `Negotiated` responses use for this request.
+pub async fn negotiate_format(req: Request, next: Next) -> Response {
+    let format = ResponseFormat::from_headers(req.headers());
+    let mut response = RESPONSE_FORMAT.scope(format, next.run(req)).await;
+    response.headers_mut().append(VARY, HeaderValue::from_static("accept"));
+    response
+}
\ No newline at end of file
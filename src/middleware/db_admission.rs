@@ -0,0 +1,38 @@
+use std::{sync::Arc, time::{Duration, Instant}};
+use axum::{Extension, extract::Request, middleware::Next, response::IntoResponse};
+use log::warn;
+use tokio::time::timeout;
+use crate::{AppState, error::{AppError, ErrorMessage}, utils::metrics};
+
+/// Turns requests away with 503 and `Retry-After` when every pool connection is busy and none frees
+/// up within `DB_ADMISSION_TIMEOUT_MS`, rather than letting them queue until `ACQUIRE_TIMEOUT` and
+/// fail with 500. While the pool has an idle connection or room to grow, requests pass unchecked.
+pub async fn db_admission(
+    Extension(app_state): Extension<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &app_state.db.pool;
+    let saturated = pool.num_idle() == 0 && pool.size() >= pool.options().get_max_connections();
+    if !saturated {
+        app_state.load.record_pool_wait(Duration::ZERO);
+        return Ok(next.run(req).await);
+    }
+    let started = Instant::now();
+    let wait = Duration::from_millis(app_state.env.db_admission_timeout);
+    match timeout(wait, pool.acquire()).await {
+        Ok(Ok(connection)) => {
+            drop(connection);
+            app_state.load.record_pool_wait(started.elapsed());
+            metrics::observe_duration("db_pool_admission_wait_seconds", &[], started.elapsed());
+            Ok(next.run(req).await)
+        }
+        _ => {
+            app_state.load.record_pool_wait(started.elapsed());
+            metrics::observe_duration("db_pool_admission_wait_seconds", &[], started.elapsed());
+            metrics::increment_counter("db_pool_rejections_total", &[("stage", "admission")]);
+            warn!("Database pool saturated, turned away {} {}", req.method(), req.uri().path());
+            Err(AppError::service_unavailable(ErrorMessage::DatabaseBusy))
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,61 @@
+use std::sync::Arc;
+use axum::{
+    Extension,
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use chrono::Utc;
+use log::warn;
+use uuid::Uuid;
+use crate::{
+    AppState,
+    error::ErrorDetail,
+    middleware::ActingUser,
+    utils::error_sink::{ErrorEvent, ErrorSink},
+};
+
+/// Reports every 5xx response, including the ones `catch_panic` made of a panic, to the `ERROR_SINK`,
+/// tagged with the request id, route and acting user. Reports are sent in the background so the
+/// response is not held up by the sink.
+pub async fn report_errors(
+    Extension(app_state): Extension<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let request_id = req.headers().get("x-request-id")
+        .and_then(|value| value.to_str().ok())
+        .map(String::from);
+    let method = req.method().to_string();
+    let route = req.extensions().get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| req.uri().path().to_string());
+    let response = next.run(req).await;
+    if !response.status().is_server_error() {
+        return response;
+    }
+    let detail = response.extensions().get::<ErrorDetail>();
+    let level = if detail.is_some_and(|detail| detail.panicked) { "fatal" } else { "error" };
+    let message = detail
+        .map(|detail| detail.message.clone())
+        .unwrap_or_else(|| response.status().to_string());
+    if let Some(sink) = ErrorSink::from_config(&app_state.env) {
+        let event = ErrorEvent {
+            event_id: Uuid::new_v4(),
+            timestamp: Utc::now(),
+            level,
+            message,
+            status: response.status().as_u16(),
+            request_id,
+            method,
+            route,
+            user_id: response.extensions().get::<ActingUser>().map(|user| user.0),
+        };
+        tokio::spawn(async move {
+            if let Err(e) = sink.send(&event).await {
+                warn!("Failed to report error event {}: {}", event.event_id, e);
+            }
+        });
+    }
+    response
+}
\ No newline at end of file
@@ -0,0 +1,14 @@
+use axum::{extract::Request, http::{HeaderValue, header::{CONTENT_SECURITY_POLICY, X_FRAME_OPTIONS}}, middleware::Next, response::Response};
+
+/// Forbids framing of every response, except the ones that declared their own `frame-ancestors` policy.
+pub async fn frame_options(req: Request, next: Next) -> Response {
+    let mut response = next.run(req).await;
+    let allows_framing = response.headers()
+        .get(CONTENT_SECURITY_POLICY)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains("frame-ancestors"));
+    if !allows_framing {
+        response.headers_mut().insert(X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+    }
+    response
+}
\ No newline at end of file
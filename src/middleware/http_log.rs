@@ -0,0 +1,165 @@
+use std::{sync::Arc, time::Instant};
+use axum::{
+    Extension,
+    body::{Body, Bytes, HttpBody, to_bytes},
+    extract::Request,
+    http::{HeaderMap, header::CONTENT_TYPE},
+    middleware::Next,
+    response::Response,
+};
+use log::{debug, info};
+use rand::Rng;
+use serde_json::Value;
+use crate::{AppState, config::Config, middleware::ActingUser};
+
+/// Bodies larger than this are never buffered for the log, whatever `HTTP_LOG_BODY_MAX_BYTES` says.
+const MAX_CAPTURED_BODY: u64 = 64 * 1024;
+/// JSON keys whose values are replaced before a body is logged, matched case-insensitively as substrings.
+const REDACTED_KEYS: &[&str] = &["password", "token", "secret", "authorization", "cookie", "api_key", "private_key"];
+
+/// Logs method, path, status, latency and user of the requests under `HTTP_LOG_ROUTES` when
+/// `HTTP_LOG_LEVEL` is `info` or `debug`. At `debug` a sample of `HTTP_LOG_BODY_SAMPLE_PERCENT` requests
+/// also logs its JSON request and response bodies, redacted and cut at `HTTP_LOG_BODY_MAX_BYTES`.
+/// Query strings are left out since some carry tokens, and so are streamed or non-JSON bodies.
+pub async fn http_log(
+    Extension(app_state): Extension<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let env = &app_state.env;
+    if env.http_log_level == "off" || !is_logged_route(env, req.uri().path()) {
+        return next.run(req).await;
+    }
+    let started = Instant::now();
+    let (method, path) = (req.method().clone(), req.uri().path().to_string());
+    let with_bodies = env.http_log_level == "debug"
+        && rand::rng().random_range(0..100) < env.http_log_body_sample_percent;
+    let (req, request_body) = if with_bodies { capture_request(req).await } else { (req, None) };
+    let response = next.run(req).await;
+    let (response, response_body) = if with_bodies { capture_response(response).await } else { (response, None) };
+    let user = response.extensions().get::<ActingUser>()
+        .map(|user| user.0.to_string())
+        .unwrap_or_else(|| "-".to_string());
+    let elapsed = started.elapsed().as_millis();
+    info!("HTTP {} {} {} {} ms user={}", method, path, response.status().as_u16(), elapsed, user);
+    if with_bodies {
+        let render = |body: Option<Bytes>| body.map(|body| render_body(&body, env.http_log_body_max_bytes)).unwrap_or_else(|| "-".to_string());
+        debug!("HTTP {} {} request={} response={}", method, path, render(request_body), render(response_body));
+    }
+    response
+}
+
+fn is_logged_route(env: &Config, path: &str) -> bool {
+    env.http_log_routes.is_empty() || env.http_log_routes.iter().any(|prefix| path.starts_with(prefix.as_str()))
+}
+
+/// Buffered JSON bodies of a known, small size. Anything else passes through untouched.
+fn capturable(headers: &HeaderMap, body: &Body) -> bool {
+    let is_json = headers.get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    is_json && body.size_hint().upper().is_some_and(|size| size <= MAX_CAPTURED_BODY)
+}
+
+async fn capture_request(req: Request) -> (Request, Option<Bytes>) {
+    if !capturable(req.headers(), req.body()) {
+        return (req, None);
+    }
+    let (parts, body) = req.into_parts();
+    match to_bytes(body, MAX_CAPTURED_BODY as usize).await {
+        Ok(bytes) => (Request::from_parts(parts, Body::from(bytes.clone())), Some(bytes)),
+        Err(_) => (Request::from_parts(parts, Body::empty()), None),
+    }
+}
+
+async fn capture_response(response: Response) -> (Response, Option<Bytes>) {
+    if !capturable(response.headers(), response.body()) {
+        return (response, None);
+    }
+    let (parts, body) = response.into_parts();
+    match to_bytes(body, MAX_CAPTURED_BODY as usize).await {
+        Ok(bytes) => (Response::from_parts(parts, Body::from(bytes.clone())), Some(bytes)),
+        Err(_) => (Response::from_parts(parts, Body::empty()), None),
+    }
+}
+
+fn render_body(body: &[u8], max_bytes: usize) -> String {
+    let mut rendered = match serde_json::from_slice::<Value>(body) {
+        Ok(mut value) => {
+            redact(&mut value);
+            value.to_string()
+        }
+        Err(_) => format!("<{} bytes, not JSON>", body.len()),
+    };
+    if rendered.len() > max_bytes {
+        let mut end = max_bytes;
+        while !rendered.is_char_boundary(end) {
+            end -= 1;
+        }
+        let total = rendered.len();
+        rendered.truncate(end);
+        rendered.push_str(&format!("...({} bytes)", total));
+    }
+    rendered
+}
+
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                let key = key.to_lowercase();
+                if REDACTED_KEYS.iter().any(|redacted| key.contains(redacted)) {
+                    *value = Value::String("[REDACTED]".to_string());
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn redacts_matching_keys_at_any_depth() {
+        let mut value = json!({
+            "email": "user@mail.com",
+            "Password": "hunter22",
+            "profile": { "NEW_PASSWORD": "x", "name": "user", "sessions": [{ "refresh_Token": "t", "id": 1 }] },
+            "items": [[{ "X-Api_Key": "k" }], "password"],
+        });
+        redact(&mut value);
+        assert_eq!(value, json!({
+            "email": "user@mail.com",
+            "Password": "[REDACTED]",
+            "profile": { "NEW_PASSWORD": "[REDACTED]", "name": "user", "sessions": [{ "refresh_Token": "[REDACTED]", "id": 1 }] },
+            "items": [[{ "X-Api_Key": "[REDACTED]" }], "password"],
+        }));
+    }
+
+    #[test]
+    fn redacts_whole_values_under_a_matching_key() {
+        let mut value = json!({ "secrets": { "name": "kept?" }, "cookies": ["a", "b"] });
+        redact(&mut value);
+        assert_eq!(value, json!({ "secrets": "[REDACTED]", "cookies": "[REDACTED]" }));
+    }
+
+    #[test]
+    fn renders_non_json_by_size_only() {
+        assert_eq!(render_body(b"password=hunter22", 100), "<17 bytes, not JSON>");
+    }
+
+    #[test]
+    fn truncates_at_a_char_boundary() {
+        // `"ééé"` is 8 bytes: quote, three two-byte letters, quote. Cutting at 4 would split the second letter.
+        let body = r#""ééé""#.as_bytes();
+        assert_eq!(render_body(body, 4), "\"é...(8 bytes)");
+        assert_eq!(render_body(body, 5), "\"éé...(8 bytes)");
+        assert_eq!(render_body(body, 8), "\"ééé\"");
+    }
+}
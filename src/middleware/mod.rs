@@ -1,11 +1,28 @@
 pub mod auth;
 pub mod permission;
 pub mod rate_limiter;
+pub mod frame_options;
+pub mod content_negotiation;
+pub mod db_admission;
+pub mod http_log;
+pub mod error_reporting;
+pub mod catch_panic;
+pub mod org_quota;
+pub mod tos;
 
 use serde::{Serialize};
+use uuid::Uuid;
 use crate::modules::user::model::{User};
 
 #[derive(Serialize, Clone)]
 pub struct AuthenticatedUser {
+    /// Who the request acts as, the impersonated user under an impersonation token.
     pub user: User,
-}
\ No newline at end of file
+    /// The admin behind an impersonation token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub impersonator: Option<User>,
+}
+/// Response extension set by `auth_token`: the user the request acted as, for outer layers like the
+/// HTTP log that run before the request is authenticated.
+#[derive(Clone, Copy)]
+pub struct ActingUser(pub Uuid);
\ No newline at end of file
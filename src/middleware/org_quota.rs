@@ -0,0 +1,138 @@
+use std::sync::Arc;
+use axum::{Extension, extract::Request, http::HeaderValue, middleware::Next, response::{IntoResponse, Response}};
+use chrono::{DateTime, Datelike, Months, NaiveDate, NaiveTime, Utc};
+use log::warn;
+use uuid::Uuid;
+use crate::{AppState, error::{AppError, ErrorMessage}, middleware::AuthenticatedUser, utils::metrics};
+
+pub const QUOTA_LIMIT_HEADER: &str = "x-quota-limit";
+pub const QUOTA_REMAINING_HEADER: &str = "x-quota-remaining";
+/// Seconds until the quota starts over with the next month.
+pub const QUOTA_RESET_HEADER: &str = "x-quota-reset";
+
+/// Where an organization's monthly quota stands after a request was charged to it.
+pub struct QuotaUsage {
+    /// `None` when the organization has no limit.
+    pub quota: Option<i64>,
+    pub count: i64,
+    pub reset_secs: u64,
+}
+
+pub enum Charge {
+    /// The request isn't the organization's: the user isn't a member or the organization doesn't
+    /// exist. Also when the database or Redis failed, the request goes through uncounted then.
+    Uncounted,
+    Counted(QuotaUsage),
+    /// The quota is used up, the request must be refused with `QUOTA_EXCEEDED`.
+    Exceeded(QuotaUsage),
+}
+
+/// Counts a request of `user_id` against the monthly quota of `organization_id`, when `user_id` is a
+/// member. Concurrent requests may overshoot the quota by a few.
+pub async fn charge(app_state: &AppState, organization_id: Uuid, user_id: Uuid) -> Charge {
+    let now = Utc::now();
+    let (month, resets_at) = usage_period(now);
+    // A database failure lets the request through to fail or succeed on its own, like Redis failures.
+    let access = match app_state.db.organizations.get_usage_access(organization_id, user_id, month).await {
+        Ok(access) => access,
+        Err(e) => {
+            warn!("Organization quota skipped for {}: {}", organization_id, e);
+            metrics::increment_counter("org_quota_decisions_total", &[("decision", "degraded")]);
+            return Charge::Uncounted;
+        }
+    };
+    let Some(access) = access.filter(|access| access.role.is_some()) else {
+        return Charge::Uncounted;
+    };
+    let quota = access.quota(app_state.env.organization_monthly_request_quota);
+    let reset_secs = (resets_at - now).num_seconds().max(1) as u64;
+    match app_state.redis_client.hit_org_usage(&organization_id, month, access.requests, quota).await {
+        Ok((true, count)) => Charge::Counted(QuotaUsage { quota, count, reset_secs }),
+        Ok((false, count)) => {
+            metrics::increment_counter("org_quota_decisions_total", &[("decision", "blocked")]);
+            Charge::Exceeded(QuotaUsage { quota, count, reset_secs })
+        }
+        Err(e) => {
+            warn!("Organization quota skipped for {}: {}", organization_id, e);
+            metrics::increment_counter("org_quota_decisions_total", &[("decision", "degraded")]);
+            Charge::Uncounted
+        }
+    }
+}
+
+/// `charge` for a write that names the organization in its body or through the post it changes,
+/// fails with 429 `QUOTA_EXCEEDED` past the quota.
+pub async fn charge_write(app_state: &AppState, organization_id: Uuid, user_id: Uuid) -> Result<(), AppError> {
+    match charge(app_state, organization_id, user_id).await {
+        Charge::Exceeded(usage) => Err(AppError::too_many_request(ErrorMessage::QuotaExceeded(usage.reset_secs))),
+        Charge::Counted(_) | Charge::Uncounted => Ok(()),
+    }
+}
+
+/// Counts the requests members make to their organization's routes, `/api/organization/{id}/...`,
+/// against its monthly quota, see `charge`. Layered on the organization routes after `auth_token`;
+/// the routes without an organization id, like creating one or the caller's invitations, aren't
+/// counted. Past the quota requests get 429 `QUOTA_EXCEEDED` until the month (UTC) ends.
+pub async fn org_quota(
+    Extension(app_state): Extension<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let Some(organization_id) = organization_in_path(req.uri().path()) else {
+        return Ok(next.run(req).await);
+    };
+    let user_id = req.extensions().get::<AuthenticatedUser>()
+        .map(|user_auth| user_auth.user.id)
+        .ok_or(AppError::unauthorized(ErrorMessage::UserNotAuthenticated))?;
+    match charge(&app_state, organization_id, user_id).await {
+        Charge::Uncounted => Ok(next.run(req).await),
+        Charge::Counted(usage) => {
+            let mut response = next.run(req).await;
+            insert_quota_headers(&mut response, &usage);
+            Ok(response)
+        }
+        Charge::Exceeded(usage) => {
+            let mut response = AppError::too_many_request(ErrorMessage::QuotaExceeded(usage.reset_secs)).into_response();
+            insert_quota_headers(&mut response, &usage);
+            Ok(response)
+        }
+    }
+}
+
+/// The organization id leading a path under `/api/organization`, as the nested router sees it.
+fn organization_in_path(path: &str) -> Option<Uuid> {
+    let segment = path.trim_start_matches('/').split('/').next()?;
+    Uuid::parse_str(segment).ok()
+}
+
+/// The month a request made at `now` counts in, as its first day, and when the next one starts.
+pub fn usage_period(now: DateTime<Utc>) -> (NaiveDate, DateTime<Utc>) {
+    let month = now.date_naive().with_day(1).expect("every month has a first day");
+    let next_month = month + Months::new(1);
+    (month, next_month.and_time(NaiveTime::MIN).and_utc())
+}
+
+fn insert_quota_headers(response: &mut Response, usage: &QuotaUsage) {
+    let Some(quota) = usage.quota else {
+        return;
+    };
+    let headers = response.headers_mut();
+    headers.insert(QUOTA_LIMIT_HEADER, HeaderValue::from(quota));
+    headers.insert(QUOTA_REMAINING_HEADER, HeaderValue::from((quota - usage.count).max(0)));
+    headers.insert(QUOTA_RESET_HEADER, HeaderValue::from(usage.reset_secs));
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+    use super::organization_in_path;
+
+    #[test]
+    fn only_paths_led_by_an_organization_id_are_counted() {
+        let id = Uuid::new_v4();
+        assert_eq!(organization_in_path(&format!("/{}", id)), Some(id));
+        assert_eq!(organization_in_path(&format!("/{}/members/{}", id, Uuid::new_v4())), Some(id));
+        assert_eq!(organization_in_path(&format!("/invitations/{}/accept", id)), None);
+        assert_eq!(organization_in_path("/"), None);
+    }
+}
@@ -1,14 +1,16 @@
-use std::sync::Arc;
+use std::{fmt::{Display, Formatter, Result as FmtResult}, sync::Arc};
 use axum::{
     extract::Request,
     middleware::Next,
     response::IntoResponse,
     Extension
 };
+use log::warn;
+use uuid::Uuid;
 use crate::{
-    error::{ErrorMessage, HttpError},
+    error::{ErrorMessage, AppError},
     middleware::AuthenticatedUser,
-    modules::permission::model::PermissionRepository,
+    modules::role::model::RoleType,
     AppState
 };
 
@@ -33,52 +35,326 @@ pub enum Permission {
     CommentUpdate,
     CommentDelete,
     CommentListByPost,
+    AdminUserHistory,
+    ReportCreate,
+    AdminReportList,
+    AdminReportResolve,
+    PostTrending,
+    AdminStats,
+    AdminRefreshAggregates,
+    UserLeaderboard,
+    UserFollowRequestList,
+    UserFollowRequestRespond,
+    PostEmbed,
+    AdminWaitlistApprove,
+    UserSettings,
+    AdminWebhookManage,
+    AdminWebhookDeliveries,
+    UserSuggestions,
+    AdminImpersonate,
+    AdminFeatureFlagManage,
+    AdminUserImport,
+    AdminUserExport,
+    AdminInvitationCreate,
+    AdminPostExport,
+    AdminVelocityLimitManage,
+    PostVelocityExempt,
+    SearchQuery,
+    PostCollaboratorManage,
+    OrganizationCreate,
+    OrganizationDetail,
+    OrganizationManage,
+    OrganizationJoin,
+    AdminOrganizationQuota,
+    AdminOrganizationData,
+    AnalyticsTrack,
+    AdminAnnouncementManage,
+    AdminTosPublish,
+    UserAcceptTos,
+    AdminRetentionView,
+    AdminDataExport,
+    FeedImport,
 }
 
 impl Permission {
-    pub fn to_string(&self) -> String {
+    /// Every permission the routes check, `cargo run -- seed` inserts and grants exactly these.
+    pub const ALL: [Permission; 59] = [
+        Permission::UserSelf,
+        Permission::UserUpdate,
+        Permission::UserList,
+        Permission::UserDetail,
+        Permission::UserFollow,
+        Permission::UserFollowers,
+        Permission::UserFollowing,
+        Permission::UserFeed,
+        Permission::UserDelete,
+        Permission::UserChangePassword,
+        Permission::PostCreate,
+        Permission::PostDetail,
+        Permission::PostUpdate,
+        Permission::PostDelete,
+        Permission::PostListByUser,
+        Permission::CommentCreate,
+        Permission::CommentDetail,
+        Permission::CommentUpdate,
+        Permission::CommentDelete,
+        Permission::CommentListByPost,
+        Permission::AdminUserHistory,
+        Permission::ReportCreate,
+        Permission::AdminReportList,
+        Permission::AdminReportResolve,
+        Permission::PostTrending,
+        Permission::AdminStats,
+        Permission::AdminRefreshAggregates,
+        Permission::UserLeaderboard,
+        Permission::UserFollowRequestList,
+        Permission::UserFollowRequestRespond,
+        Permission::PostEmbed,
+        Permission::AdminWaitlistApprove,
+        Permission::UserSettings,
+        Permission::AdminWebhookManage,
+        Permission::AdminWebhookDeliveries,
+        Permission::UserSuggestions,
+        Permission::AdminImpersonate,
+        Permission::AdminFeatureFlagManage,
+        Permission::AdminUserImport,
+        Permission::AdminUserExport,
+        Permission::AdminInvitationCreate,
+        Permission::AdminPostExport,
+        Permission::AdminVelocityLimitManage,
+        Permission::PostVelocityExempt,
+        Permission::SearchQuery,
+        Permission::PostCollaboratorManage,
+        Permission::OrganizationCreate,
+        Permission::OrganizationDetail,
+        Permission::OrganizationManage,
+        Permission::OrganizationJoin,
+        Permission::AdminOrganizationQuota,
+        Permission::AdminOrganizationData,
+        Permission::AnalyticsTrack,
+        Permission::AdminAnnouncementManage,
+        Permission::AdminTosPublish,
+        Permission::UserAcceptTos,
+        Permission::AdminRetentionView,
+        Permission::AdminDataExport,
+        Permission::FeedImport,
+    ];
+    pub fn description(&self) -> &'static str {
         match self {
-            Permission::UserSelf => "user:self".to_string(),
-            Permission::UserUpdate => "user:update".to_string(),
-            Permission::UserList => "user:list".to_string(),
-            Permission::UserDetail => "user:detail".to_string(),
-            Permission::UserFollow => "user:follow".to_string(),
-            Permission::UserFollowers => "user:followers".to_string(),
-            Permission::UserFollowing => "user:following".to_string(),
-            Permission::UserFeed => "user:feed".to_string(),
-            Permission::UserDelete => "user:delete".to_string(),
-            Permission::UserChangePassword => "user:change-password".to_string(),
-            Permission::PostCreate => "post:create".to_string(),
-            Permission::PostDetail => "post:detail".to_string(),
-            Permission::PostUpdate => "post:update".to_string(),
-            Permission::PostDelete => "post:delete".to_string(),
-            Permission::PostListByUser => "post:list-by-user".to_string(),
-            Permission::CommentCreate => "comment:create".to_string(),
-            Permission::CommentDetail => "comment:detail".to_string(),
-            Permission::CommentUpdate => "comment:update".to_string(),
-            Permission::CommentDelete => "comment:delete".to_string(),
-            Permission::CommentListByPost => "comment:list-by-post".to_string(),
+            Permission::UserSelf => "Get self information of a logged in user.",
+            Permission::UserUpdate => "Update a user account.",
+            Permission::UserList => "Get list of all users.",
+            Permission::UserDetail => "Get detail of a user account.",
+            Permission::UserFollow => "Follow or unfollow a user account.",
+            Permission::UserFollowers => "Get followers of a user account.",
+            Permission::UserFollowing => "Get following of a user account.",
+            Permission::UserFeed => "Get the feed of a user account.",
+            Permission::UserDelete => "Remove or delete a user account.",
+            Permission::UserChangePassword => "Change user password.",
+            Permission::PostCreate => "Create a new post.",
+            Permission::PostDetail => "Get detail of a post.",
+            Permission::PostUpdate => "Update or modify an existing post.",
+            Permission::PostDelete => "Remove or delete a post.",
+            Permission::PostListByUser => "Post list by user.",
+            Permission::CommentCreate => "Create a new comment for a post.",
+            Permission::CommentDetail => "Get a comment of a post.",
+            Permission::CommentUpdate => "Update a comment of a post.",
+            Permission::CommentDelete => "Delete a comment of a post.",
+            Permission::CommentListByPost => "Comment list of a post.",
+            Permission::AdminUserHistory => "Get the action history of a user account.",
+            Permission::ReportCreate => "Report a post or a comment.",
+            Permission::AdminReportList => "Get list of content reports.",
+            Permission::AdminReportResolve => "Dismiss or take action on a content report.",
+            Permission::PostTrending => "Get list of trending posts.",
+            Permission::AdminStats => "Get platform statistics.",
+            Permission::AdminRefreshAggregates => "Force a refresh of the aggregate views.",
+            Permission::UserLeaderboard => "Get most-followed users and top posters of the week.",
+            Permission::UserFollowRequestList => "Get list of pending follow requests.",
+            Permission::UserFollowRequestRespond => "Accept or reject a follow request.",
+            Permission::PostEmbed => "Create an embed token for a post.",
+            Permission::AdminWaitlistApprove => "Approve the next batch of waitlisted accounts.",
+            Permission::UserSettings => "Get and replace the signed in user's preferences.",
+            Permission::AdminWebhookManage => "Register, list and remove outgoing webhook endpoints.",
+            Permission::AdminWebhookDeliveries => "Get the outgoing webhook delivery log and retry dead deliveries.",
+            Permission::UserSuggestions => "Get accounts to follow, ranked by mutual connections and shared tags.",
+            Permission::AdminImpersonate => "Act as another user with a short-lived, audited token.",
+            Permission::AdminFeatureFlagManage => "Create, list, change and remove feature flags.",
+            Permission::AdminUserImport => "Create user accounts in bulk from a CSV or NDJSON upload.",
+            Permission::AdminUserExport => "Download every user account as CSV.",
+            Permission::AdminInvitationCreate => "Invite people to sign up while registration is invite only.",
+            Permission::AdminPostExport => "Stream every post as NDJSON.",
+            Permission::AdminVelocityLimitManage => "List and change the posting velocity limits.",
+            Permission::PostVelocityExempt => "Post and comment without the posting velocity limits.",
+            Permission::SearchQuery => "Search posts, users and tags.",
+            Permission::PostCollaboratorManage => "Add and remove the collaborators of a post.",
+            Permission::OrganizationCreate => "Create organizations.",
+            Permission::OrganizationDetail => "See organizations and their members.",
+            Permission::OrganizationManage => "Change, invite to and remove members of organizations the user owns.",
+            Permission::OrganizationJoin => "Accept organization invitations, leave and follow organizations.",
+            Permission::AdminOrganizationQuota => "Set the monthly request quota of an organization.",
+            Permission::AdminOrganizationData => "Export or delete all data of an organization.",
+            Permission::AnalyticsTrack => "Send anonymized usage events from the client.",
+            Permission::AdminAnnouncementManage => "Create, list, change and remove announcements.",
+            Permission::AdminTosPublish => "Publish and list the terms of service versions.",
+            Permission::UserAcceptTos => "Accept the current terms of service.",
+            Permission::AdminRetentionView => "See what the data retention rules would purge or anonymize.",
+            Permission::AdminDataExport => "Export whole tables to the export storage and download them.",
+            Permission::FeedImport => "Mirror RSS or Atom feeds into draft posts.",
+        }
+    }
+    /// Roles granted this permission by the seed command, roles inheriting from them have it too.
+    pub fn roles(&self) -> &'static [RoleType] {
+        match self {
+            Permission::UserList
+            | Permission::UserDelete
+            | Permission::AdminUserHistory
+            | Permission::AdminReportList
+            | Permission::AdminReportResolve
+            | Permission::AdminStats
+            | Permission::AdminRefreshAggregates
+            | Permission::AdminWaitlistApprove
+            | Permission::AdminWebhookManage
+            | Permission::AdminWebhookDeliveries
+            | Permission::AdminImpersonate
+            | Permission::AdminFeatureFlagManage
+            | Permission::AdminUserImport
+            | Permission::AdminUserExport
+            | Permission::AdminInvitationCreate
+            | Permission::AdminPostExport
+            | Permission::AdminVelocityLimitManage
+            | Permission::PostVelocityExempt
+            | Permission::AdminOrganizationQuota
+            | Permission::AdminOrganizationData
+            | Permission::AdminAnnouncementManage
+            | Permission::AdminTosPublish
+            | Permission::AdminRetentionView
+            | Permission::AdminDataExport => &[RoleType::Admin],
+            _ => &[RoleType::User],
         }
     }
 }
 
+impl Display for Permission {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let value = match self {
+            Permission::UserSelf => "user:self",
+            Permission::UserUpdate => "user:update",
+            Permission::UserList => "user:list",
+            Permission::UserDetail => "user:detail",
+            Permission::UserFollow => "user:follow",
+            Permission::UserFollowers => "user:followers",
+            Permission::UserFollowing => "user:following",
+            Permission::UserFeed => "user:feed",
+            Permission::UserDelete => "user:delete",
+            Permission::UserChangePassword => "user:change-password",
+            Permission::PostCreate => "post:create",
+            Permission::PostDetail => "post:detail",
+            Permission::PostUpdate => "post:update",
+            Permission::PostDelete => "post:delete",
+            Permission::PostListByUser => "post:list-by-user",
+            Permission::CommentCreate => "comment:create",
+            Permission::CommentDetail => "comment:detail",
+            Permission::CommentUpdate => "comment:update",
+            Permission::CommentDelete => "comment:delete",
+            Permission::CommentListByPost => "comment:list-by-post",
+            Permission::AdminUserHistory => "admin:user-history",
+            Permission::ReportCreate => "report:create",
+            Permission::AdminReportList => "admin:report-list",
+            Permission::AdminReportResolve => "admin:report-resolve",
+            Permission::PostTrending => "post:trending",
+            Permission::AdminStats => "admin:stats",
+            Permission::AdminRefreshAggregates => "admin:refresh-aggregates",
+            Permission::UserLeaderboard => "user:leaderboard",
+            Permission::UserFollowRequestList => "user:follow-request-list",
+            Permission::UserFollowRequestRespond => "user:follow-request-respond",
+            Permission::PostEmbed => "post:embed",
+            Permission::AdminWaitlistApprove => "admin:waitlist-approve",
+            Permission::UserSettings => "user:settings",
+            Permission::AdminWebhookManage => "admin:webhook-manage",
+            Permission::AdminWebhookDeliveries => "admin:webhook-deliveries",
+            Permission::UserSuggestions => "user:suggestions",
+            Permission::AdminImpersonate => "admin:impersonate",
+            Permission::AdminFeatureFlagManage => "admin:feature-flag-manage",
+            Permission::AdminUserImport => "admin:user-import",
+            Permission::AdminUserExport => "admin:user-export",
+            Permission::AdminInvitationCreate => "admin:invitation-create",
+            Permission::AdminPostExport => "admin:post-export",
+            Permission::AdminVelocityLimitManage => "admin:velocity-limit-manage",
+            Permission::PostVelocityExempt => "post:velocity-exempt",
+            Permission::SearchQuery => "search:query",
+            Permission::PostCollaboratorManage => "post:collaborator-manage",
+            Permission::OrganizationCreate => "organization:create",
+            Permission::OrganizationDetail => "organization:detail",
+            Permission::OrganizationManage => "organization:manage",
+            Permission::OrganizationJoin => "organization:join",
+            Permission::AdminOrganizationQuota => "admin:organization-quota",
+            Permission::AdminOrganizationData => "admin:organization-data",
+            Permission::AnalyticsTrack => "analytics:track",
+            Permission::AdminAnnouncementManage => "admin:announcement-manage",
+            Permission::AdminTosPublish => "admin:tos-publish",
+            Permission::UserAcceptTos => "user:accept-tos",
+            Permission::AdminRetentionView => "admin:retention-view",
+            Permission::AdminDataExport => "admin:data-export",
+            Permission::FeedImport => "feed:import",
+        };
+        write!(f, "{}", value)
+    }
+}
+
 pub async fn check_permission(
     Extension(app_state): Extension<Arc<AppState>>,
     req: Request,
     next: Next,
     permission: String,
-) -> Result<impl IntoResponse, HttpError<()>> {
+) -> Result<impl IntoResponse, AppError> {
     let authenticated_user = req
         .extensions()
         .get::<AuthenticatedUser>()
         .ok_or_else(|| {
-            HttpError::unauthorized(ErrorMessage::UserNotAuthenticated.to_string(), None)
+            AppError::unauthorized(ErrorMessage::UserNotAuthenticated)
         })?;
     let role_id = authenticated_user.user.role_id;
-    let permission_by_role = app_state.db_client.get_permission_by_role(&role_id).await
-        .map_err(|_| HttpError::server_error(ErrorMessage::ServerError.to_string(), None))?;
+    let permission_by_role = get_role_permissions(&app_state, role_id).await?;
     if !permission_by_role.contains(&permission) {
-        return Err(HttpError::forbidden(ErrorMessage::PermissionDenied.to_string(), None));
+        return Err(AppError::forbidden(ErrorMessage::PermissionDenied));
     }
     Ok(next.run(req).await)
+}
+
+/// Whether `role_id` is granted `permission`, for rules that depend on it inside a handler.
+pub async fn has_permission(app_state: &Arc<AppState>, role_id: Uuid, permission: Permission) -> Result<bool, AppError> {
+    Ok(get_role_permissions(app_state, role_id).await?.contains(&permission.to_string()))
+}
+
+/// Cached permissions are served until they expire, but once an entry is within
+/// `permission_refresh_ahead` seconds of expiring it is reloaded in the background,
+/// so requests never wait on Postgres just because the cache rolled over.
+async fn get_role_permissions(app_state: &Arc<AppState>, role_id: Uuid) -> Result<Vec<String>, AppError> {
+    match app_state.redis_client.get_permissions(&role_id).await {
+        Ok(Some(cached)) => {
+            if cached.ttl <= app_state.env.permission_refresh_ahead as i64 {
+                let lock = app_state.redis_client
+                    .lock_permission_refresh(&role_id, app_state.env.permission_refresh_ahead).await;
+                if let Ok(true) = lock {
+                    let app_state = app_state.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = load_role_permissions(&app_state, role_id).await {
+                            warn!("Failed to refresh permissions for role {}: {}", role_id, e);
+                        }
+                    });
+                }
+            }
+            return Ok(cached.permissions);
+        }
+        Ok(None) => {}
+        Err(e) => warn!("Failed to read permissions for role {} from cache: {}", role_id, e),
+    }
+    load_role_permissions(app_state, role_id).await
+}
+
+async fn load_role_permissions(app_state: &Arc<AppState>, role_id: Uuid) -> Result<Vec<String>, AppError> {
+    let permissions = app_state.db.permissions.get_permission_by_role(&role_id).await
+        .map_err(|_| AppError::server_error(ErrorMessage::ServerError))?;
+    let _ = app_state.redis_client.set_permissions(&role_id, &permissions, app_state.env.permission_cache_ttl).await;
+    Ok(permissions)
 }
\ No newline at end of file
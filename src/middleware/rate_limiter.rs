@@ -1,34 +1,173 @@
-use std::{net::{SocketAddr}, sync::Arc};
-use axum::{Extension, extract::Request, middleware::Next, response::IntoResponse};
-use redis::AsyncTypedCommands;
-use crate::{AppState, error::{ErrorMessage, HttpError}};
+use std::{net::{SocketAddr}, sync::Arc, time::Instant};
+use axum::{Extension, extract::{ConnectInfo, MatchedPath, Request}, middleware::Next, response::IntoResponse};
+use log::warn;
+use sha2::{Digest, Sha256};
+use crate::{AppState, error::{ErrorMessage, AppError}, middleware::auth::bearer_token, utils::{jwt, metrics}};
 
+/// Requests with a valid access token are counted per user, the others per client IP, so anonymous
+/// traffic can't use up the budget of signed-in users behind the same address. While `LoadMonitor`
+/// reports the service degraded, anonymous requests get `RATE_LIMITER_DEGRADED_PERCENT` of
+/// `RATE_LIMITER_MAX`, signed-in users keep the full limit. Every request that passes is timed for
+/// the monitor. Buckets are per route template, so changing an id in the path doesn't buy a fresh
+/// one, and every unmatched path shares a single bucket.
 pub async fn rate_limit(
     Extension(app_state): Extension<Arc<AppState>>,
     req: Request,
     next: Next,
-) -> Result<impl IntoResponse, HttpError<()>> {
-    let max_requests_per_sec: u32 = app_state.env.rate_limiter_max;
-    let window_secs: i64 = app_state.env.rate_limiter_duration;
-    let ip = req.extensions()
-        .get::<SocketAddr>()
-        .map(|addr| addr.ip().to_string())
-        .unwrap_or_else(|| "http://localhost:4000".to_string());
+) -> Result<impl IntoResponse, AppError> {
+    let env = &app_state.env;
+    let ip = client_ip(&req);
+    let route = req.extensions().get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let (key, max_requests) = match token_subject(&app_state, &req) {
+        Some(user_id) => (format!("rate_limit:{}:user-{}", route, user_id), env.rate_limiter_max),
+        None if app_state.load.is_degraded(env) => {
+            metrics::increment_counter("rate_limiter_tightened_total", &[]);
+            (format!("rate_limit:{}:ip-{}", route, ip), (env.rate_limiter_max * env.rate_limiter_degraded_percent / 100).max(1))
+        }
+        None => (format!("rate_limit:{}:ip-{}", route, ip), env.rate_limiter_max),
+    };
+    check_limit(&app_state, &key, max_requests, env.rate_limiter_duration, &ip, &route).await?;
+    let _in_flight = app_state.load.start_request();
+    let started = Instant::now();
+    let response = next.run(req).await;
+    app_state.load.record_latency(started.elapsed());
+    Ok(response)
+}
+
+/// The `sub` of a valid access token. Checks only the signature and expiry, `auth_token` still loads
+/// the user behind it.
+fn token_subject(app_state: &AppState, req: &Request) -> Option<String> {
+    let token = bearer_token(req.headers()).ok()?;
+    jwt::parse_token(token, &app_state.env.jwt_keys, app_state.env.jwt_leeway).ok().map(|claims| claims.sub)
+}
+
+/// A tighter limit for `GET /api/auth/availability`, on top of `rate_limit`: a sign-up form checks
+/// as the user types, but enumerating accounts needs far more lookups than that.
+pub async fn availability_rate_limit(
+    Extension(app_state): Extension<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Result<impl IntoResponse, AppError> {
+    let ip = client_ip(&req);
     let path = req.uri().path().to_string();
-    let key = format!("rate_limit:{}:ip-{}", path, ip);
-
-    let mut conn = app_state.redis_client.get_conn().await
-        .map_err(|e| {
-            HttpError::server_error(format!("Failed to get connection from the redis: {}", e), None)
-        })?;
-    let count: u32 = conn.incr(&key, 1).await
-        .map_err(|e| HttpError::server_error(format!("Redis incr error: {}", e), None))? as u32;
-    if count == 1 {
-        let _ = conn.expire(&key, window_secs).await
-            .map_err(|e| HttpError::server_error(format!("Failed to expire key: {}", e), None))?;
+    let key = format!("rate_limit:availability:ip-{}", ip);
+    let (max, window_secs) = (app_state.env.availability_rate_limit_max, app_state.env.availability_rate_limit_window);
+    check_limit(&app_state, &key, max, window_secs, &ip, &path).await?;
+    Ok(next.run(req).await)
+}
+
+/// Limits the verification and password reset emails sent to one address, whoever asks for them,
+/// so the endpoints can't be used to flood someone's inbox. The address is hashed for the key.
+pub async fn email_rate_limit(app_state: &AppState, email: &str, action: &str) -> Result<(), AppError> {
+    let digest = Sha256::digest(email.trim().to_lowercase().as_bytes());
+    let key = format!("rate_limit:email:{}", hex::encode(digest));
+    let (max, window_secs) = (app_state.env.email_rate_limit_max, app_state.env.email_rate_limit_window);
+    match check_limit(app_state, &key, max, window_secs, "an email address", action).await {
+        Err(AppError::Http { message: ErrorMessage::TooManyRequest, .. }) => {
+            let retry_after = app_state.redis_client.rate_limit_ttl(&key).await
+                .ok()
+                .flatten()
+                .unwrap_or(window_secs as u64);
+            Err(AppError::too_many_request(ErrorMessage::EmailRateLimited(retry_after)))
+        }
+        result => result,
+    }
+}
+
+/// The peer address from `into_make_service_with_connect_info`, "unknown" for a router served
+/// without it (e.g. in tests calling it directly), which then shares one bucket.
+fn client_ip(req: &Request) -> String {
+    req.extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+async fn check_limit(app_state: &AppState, key: &str, max_requests: u32, window_secs: i64, ip: &str, path: &str) -> Result<(), AppError> {
+    // When Redis is down or slow the request goes through unlimited rather than failing or waiting,
+    // unless `RATE_LIMITER_ON_REDIS_ERROR` asks for it to be turned away.
+    let count = match app_state.redis_client.hit_rate_limit(key, window_secs).await {
+        Ok(count) => count,
+        Err(e) if !app_state.env.rate_limiter_fail_open => {
+            warn!("Rate limiter rejected {} on {}: {}", ip, path, e);
+            metrics::increment_counter("rate_limiter_decisions_total", &[("decision", "unavailable")]);
+            return Err(AppError::service_unavailable(ErrorMessage::ServiceUnavailable));
+        }
+        Err(e) => {
+            warn!("Rate limiter skipped for {} on {}: {}", ip, path, e);
+            metrics::increment_counter("rate_limiter_decisions_total", &[("decision", "degraded")]);
+            return Ok(());
+        }
+    };
+    if count > max_requests {
+        if app_state.env.rate_limiter_dry_run {
+            warn!("Rate limiter (dry-run) would have blocked {} on {} ({} requests in {}s)", ip, path, count, window_secs);
+            metrics::increment_counter("rate_limiter_decisions_total", &[("decision", "would_block")]);
+        } else {
+            metrics::increment_counter("rate_limiter_decisions_total", &[("decision", "blocked")]);
+            return Err(AppError::too_many_request(ErrorMessage::TooManyRequest));
+        }
     }
-    if count > max_requests_per_sec {
-        return Err(HttpError::too_many_request(ErrorMessage::TooManyRequest.to_string(), None));
+    Ok(())
+}
+/// Brute-force guard of `POST /api/auth/sign-in`, keyed by the email and client IP together, so it
+/// doesn't lock the owner out from their own devices. After `SIGN_IN_FREE_ATTEMPTS` failures in the
+/// sliding `SIGN_IN_FAILURE_WINDOW`, only one attempt is let through per delay, which doubles with
+/// each further failure from `SIGN_IN_DELAY_BASE` up to `SIGN_IN_DELAY_MAX` seconds.
+pub async fn sign_in_throttle(app_state: &AppState, email: &str, ip: &str) -> Result<(), AppError> {
+    let env = &app_state.env;
+    let key = sign_in_key(email, ip);
+    let failures = match app_state.redis_client.sign_in_failures(&key, env.sign_in_failure_window).await {
+        Ok(failures) => failures.floor() as u32,
+        Err(e) if !env.rate_limiter_fail_open => {
+            warn!("Sign-in throttle rejected {}: {}", ip, e);
+            return Err(AppError::service_unavailable(ErrorMessage::ServiceUnavailable));
+        }
+        Err(e) => {
+            warn!("Sign-in throttle skipped for {}: {}", ip, e);
+            return Ok(());
+        }
+    };
+    if failures < env.sign_in_free_attempts {
+        return Ok(());
     }
-    Ok(next.run(req).await)
+    let doublings = (failures - env.sign_in_free_attempts).min(31);
+    let delay = env.sign_in_delay_base.saturating_mul(1u64 << doublings).min(env.sign_in_delay_max);
+    match app_state.redis_client.claim_sign_in_attempt(&key, delay).await {
+        Ok(Err(retry_after)) if env.rate_limiter_dry_run => {
+            warn!("Sign-in throttle (dry-run) would have delayed {} for {}s ({} failures)", ip, retry_after, failures);
+            metrics::increment_counter("rate_limiter_decisions_total", &[("decision", "would_block")]);
+            Ok(())
+        }
+        Ok(Err(retry_after)) => {
+            metrics::increment_counter("rate_limiter_decisions_total", &[("decision", "blocked")]);
+            Err(AppError::too_many_request(ErrorMessage::SignInThrottled(retry_after)))
+        }
+        Ok(Ok(())) => Ok(()),
+        Err(e) => {
+            warn!("Sign-in throttle skipped for {}: {}", ip, e);
+            Ok(())
+        }
+    }
+}
+
+/// Counts a sign-in with the wrong password, or for an unknown email, against `sign_in_throttle`.
+pub async fn sign_in_failed(app_state: &AppState, email: &str, ip: &str) {
+    let key = sign_in_key(email, ip);
+    if let Err(e) = app_state.redis_client.record_sign_in_failure(&key, app_state.env.sign_in_failure_window).await {
+        warn!("Failed sign-in from {} was not counted: {}", ip, e);
+    }
+}
+
+/// A successful sign-in starts the count over.
+pub async fn sign_in_succeeded(app_state: &AppState, email: &str, ip: &str) {
+    let key = sign_in_key(email, ip);
+    let _ = app_state.redis_client.clear_sign_in_failures(&key, app_state.env.sign_in_failure_window).await;
+}
+
+fn sign_in_key(email: &str, ip: &str) -> String {
+    let digest = Sha256::digest(format!("{}|{}", email.trim().to_lowercase(), ip).as_bytes());
+    format!("rate_limit:sign_in:{}", hex::encode(digest))
 }
\ No newline at end of file
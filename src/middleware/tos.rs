@@ -0,0 +1,50 @@
+use std::sync::Arc;
+use axum::{Extension, extract::{OriginalUri, Request}, http::{HeaderValue, Method}, middleware::Next, response::{IntoResponse, Response}};
+use log::warn;
+use crate::{AppState, error::{AppError, ErrorMessage}, middleware::AuthenticatedUser, utils::metrics};
+
+/// Names the terms of service version the user still has to accept.
+pub const TOS_REQUIRED_HEADER: &str = "x-tos-required";
+/// The one write left open to users who haven't accepted the current version.
+pub const ACCEPT_TOS_PATH: &str = "/api/user/accept-tos";
+
+/// Flags users who signed up before the current terms of service version and haven't accepted it
+/// yet. Runs after `auth_token`. Reads go through with `X-Tos-Required` set to the version, any
+/// other request gets 451 `TOS_ACCEPTANCE_REQUIRED` until the user calls `POST /api/user/accept-tos`.
+/// When the version or the acceptance can't be looked up requests go through unflagged.
+pub async fn tos_acceptance(
+    Extension(app_state): Extension<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let Some(user) = req.extensions().get::<AuthenticatedUser>().map(|user_auth| user_auth.user.clone()) else {
+        return Ok(next.run(req).await);
+    };
+    let version = match app_state.tos_service().pending_for(&user).await {
+        Ok(Some(version)) => version.version,
+        Ok(None) => return Ok(next.run(req).await),
+        Err(e) => {
+            warn!("Terms of service check skipped for {}: {}", user.id, e);
+            return Ok(next.run(req).await);
+        }
+    };
+    let path = req.extensions().get::<OriginalUri>()
+        .map(|uri| uri.path().to_string())
+        .unwrap_or_else(|| req.uri().path().to_string());
+    let is_read = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    if !is_read && path != ACCEPT_TOS_PATH {
+        metrics::increment_counter("tos_blocked_requests_total", &[]);
+        let mut response = AppError::unavailable_for_legal_reasons(ErrorMessage::TosAcceptanceRequired(version.clone())).into_response();
+        insert_tos_header(&mut response, &version);
+        return Ok(response);
+    }
+    let mut response = next.run(req).await;
+    insert_tos_header(&mut response, &version);
+    Ok(response)
+}
+
+fn insert_tos_header(response: &mut Response, version: &str) {
+    if let Ok(value) = HeaderValue::from_str(version) {
+        response.headers_mut().insert(TOS_REQUIRED_HEADER, value);
+    }
+}
\ No newline at end of file
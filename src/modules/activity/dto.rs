@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+use utoipa::IntoParams;
+use validator::Validate;
+use crate::{config::Config, dto::{Paginated, Pagination}};
+
+#[derive(Serialize, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UserActivityParams {
+    pub limit: Option<usize>,
+    #[validate(range(min = 1, max = 100000, message = "Page must be between 1 and 100000."))]
+    pub page: Option<usize>,
+    #[serde(skip)]
+    pub pagination: Pagination,
+}
+impl Paginated for UserActivityParams {
+    fn paginate(&mut self, config: &Config) {
+        self.pagination = Pagination::sanitize(self.page, self.limit, config);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,2 @@
+pub mod dto;
+pub mod model;
\ No newline at end of file
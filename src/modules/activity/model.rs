@@ -0,0 +1,104 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Postgres, QueryBuilder};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use crate::{
+    db::{Counted, DBClient},
+    dto::{PaginatedData, PaginationMeta},
+    error::RepositoryError,
+    modules::activity::dto::UserActivityParams,
+};
+
+/// One entry of a user's activity timeline.
+#[derive(Serialize, Deserialize, FromRow, ToSchema)]
+pub struct ActivityItem {
+    /// `post`, `comment` or `follow`.
+    pub kind: String,
+    /// The post, the comment or the followed account.
+    pub id: Uuid,
+    /// The post itself, or the post commented on.
+    pub post_id: Option<Uuid>,
+    pub title: Option<String>,
+    /// The comment's text, only set for comments.
+    pub content: Option<String>,
+    /// The author of the post commented on, or the followed account.
+    pub target_user_id: Option<Uuid>,
+    pub target_user_name: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait ActivityRepository: Send + Sync {
+    /// `user_id`'s posts, comments and follows, newest first, as `viewer_id` may see them: hidden
+    /// posts and comments are left out, and so are comments on posts of private accounts the viewer
+    /// doesn't follow.
+    async fn get_user_activity(&self, user_id: Uuid, viewer_id: Uuid, params: UserActivityParams) -> Result<PaginatedData<ActivityItem>, RepositoryError>;
+}
+
+/// The posts, comments and follows of the timeline as one `activity` relation.
+fn push_activity(query_builder: &mut QueryBuilder<'_, Postgres>, user_id: Uuid, viewer_id: Uuid) {
+    query_builder
+        .push("FROM (\
+            SELECT 'post' AS kind, p.id, p.id AS post_id, p.title, NULL::TEXT AS content, \
+                NULL::UUID AS target_user_id, NULL::TEXT AS target_user_name, p.created_at \
+            FROM posts AS p WHERE p.is_hidden = false AND p.user_id = ")
+        .push_bind(user_id)
+        .push(" UNION ALL \
+            SELECT 'comment', c.id, p.id, p.title, c.content, a.id, a.name, c.created_at \
+            FROM comments AS c \
+            JOIN posts AS p ON p.id = c.post_id \
+            JOIN users AS a ON a.id = p.user_id \
+            WHERE c.is_hidden = false AND p.is_hidden = false AND c.user_id = ")
+        .push_bind(user_id)
+        .push(" AND (NOT a.is_private OR a.id = ")
+        .push_bind(viewer_id)
+        .push(" OR EXISTS (SELECT 1 FROM user_followers WHERE following_id = a.id AND follower_id = ")
+        .push_bind(viewer_id)
+        .push(") OR EXISTS (SELECT 1 FROM users AS v JOIN roles AS r ON r.id = v.role_id WHERE r.name = 'admin' AND v.id = ")
+        .push_bind(viewer_id)
+        .push(")) UNION ALL \
+            SELECT 'follow', f.id, NULL, NULL, NULL, f.id, f.name, uf.created_at \
+            FROM user_followers AS uf \
+            JOIN users AS f ON f.id = uf.following_id \
+            WHERE uf.follower_id = ")
+        .push_bind(user_id)
+        .push(") AS activity ");
+}
+
+#[async_trait]
+impl ActivityRepository for DBClient {
+    async fn get_user_activity(&self, user_id: Uuid, viewer_id: Uuid, params: UserActivityParams) -> Result<PaginatedData<ActivityItem>, RepositoryError> {
+        let _timer = self.time_query("activity.get_user_activity");
+        let limit = params.pagination.limit as i64;
+        let offset = params.pagination.offset() as i64;
+        let mut query_builder_items: QueryBuilder<Postgres> = QueryBuilder::new(
+            "SELECT kind, id, post_id, title, content, target_user_id, target_user_name, created_at, \
+            COUNT(*) OVER () AS total_items "
+        );
+        push_activity(&mut query_builder_items, user_id, viewer_id);
+        query_builder_items
+            .push("ORDER BY created_at DESC, id LIMIT ")
+            .push_bind(limit)
+            .push(" OFFSET ")
+            .push_bind(offset);
+        let (items, total_items) = Counted::split(
+            query_builder_items.build_query_as::<Counted<ActivityItem>>().fetch_all(&self.pool).await?
+        );
+        let total_items = match total_items {
+            Some(total_items) => total_items,
+            None if offset == 0 => 0,
+            None => {
+                let mut query_builder_count: QueryBuilder<Postgres> = QueryBuilder::new("SELECT COUNT(*) ");
+                push_activity(&mut query_builder_count, user_id, viewer_id);
+                query_builder_count.build_query_scalar::<i64>().fetch_one(&self.pool).await?
+            }
+        };
+        let pagination = PaginationMeta::new(params.pagination.page as i32, params.pagination.limit as i32, total_items);
+        Ok(PaginatedData {
+            items,
+            pagination,
+        })
+    }
+}
\ No newline at end of file
@@ -0,0 +1,203 @@
+use std::{io, sync::Arc};
+use axum::body::{Body, BodyDataStream, Bytes};
+use futures_util::{StreamExt, stream};
+use log::warn;
+use crate::{
+    AppState,
+    error::{AppError, ErrorMessage},
+    modules::{
+        admin::dto::{UserImportError, UserImportRecord, UserImportSummary},
+        user::model::{User, UserExportRow},
+    },
+    utils::csv,
+};
+
+/// Longest line an import accepts, so a file without newlines can't grow the buffer unbounded.
+const MAX_IMPORT_LINE_LENGTH: usize = 16 * 1024;
+/// Most skipped and failed lines listed in the import summary.
+const MAX_IMPORT_ERRORS: usize = 100;
+/// Users read from the database per chunk of the export.
+const EXPORT_BATCH_SIZE: i64 = 500;
+const EXPORT_HEADER: &str = "id,name,email,username,role,is_verified,is_banned,is_private,created_at\n";
+
+pub enum ImportFormat {
+    /// A header line naming the `name` and `email` columns, in any order, then one account per line.
+    Csv,
+    /// One `{"name": ..., "email": ...}` object per line.
+    Ndjson,
+}
+
+impl ImportFormat {
+    pub fn from_content_type(content_type: Option<&str>) -> Result<Self, AppError> {
+        let media_type = content_type.unwrap_or_default().split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+        match media_type.as_str() {
+            "text/csv" => Ok(ImportFormat::Csv),
+            "application/x-ndjson" | "application/ndjson" | "application/jsonl" => Ok(ImportFormat::Ndjson),
+            _ => Err(AppError::unsupported_media_type(ErrorMessage::UnsupportedMediaType)),
+        }
+    }
+}
+
+/// Reads the body one line at a time, holding at most one line plus one chunk in memory.
+struct Lines {
+    stream: BodyDataStream,
+    buffer: Vec<u8>,
+    finished: bool,
+}
+
+impl Lines {
+    fn new(body: Body) -> Self {
+        Self { stream: body.into_data_stream(), buffer: Vec::new(), finished: false }
+    }
+    async fn next_line(&mut self) -> Option<Result<String, String>> {
+        loop {
+            if let Some(end) = self.buffer.iter().position(|byte| *byte == b'\n') {
+                let mut line: Vec<u8> = self.buffer.drain(..=end).collect();
+                line.pop();
+                return Some(String::from_utf8(line).map_err(|_| "line is not valid UTF-8".to_string()));
+            }
+            if self.buffer.len() > MAX_IMPORT_LINE_LENGTH {
+                return Some(Err(format!("line is longer than {} bytes", MAX_IMPORT_LINE_LENGTH)));
+            }
+            if self.finished {
+                if self.buffer.is_empty() {
+                    return None;
+                }
+                let line = std::mem::take(&mut self.buffer);
+                return Some(String::from_utf8(line).map_err(|_| "line is not valid UTF-8".to_string()));
+            }
+            match self.stream.next().await {
+                Some(Ok(chunk)) => self.buffer.extend_from_slice(&chunk),
+                Some(Err(e)) => return Some(Err(format!("failed to read the upload: {}", e))),
+                None => self.finished = true,
+            }
+        }
+    }
+}
+
+/// Imports the accounts in `body` one line at a time. A bad line is counted in the summary and the
+/// import goes on; an unreadable body or an oversized line ends it, the accounts created before stay.
+pub async fn import_users(app_state: &Arc<AppState>, admin: &User, format: ImportFormat, body: Body) -> Result<UserImportSummary, AppError> {
+    let auth_service = app_state.auth_service();
+    let mut lines = Lines::new(body);
+    let mut summary = UserImportSummary::default();
+    let mut line_number = 0;
+    // Indexes of the name and email columns, CSV only.
+    let mut columns: Option<(usize, usize)> = None;
+    while let Some(line) = lines.next_line().await {
+        line_number += 1;
+        let line = match line {
+            Ok(line) => line,
+            Err(reason) => {
+                summary.fail(UserImportError { line: line_number, email: None, reason });
+                break;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = match format {
+            ImportFormat::Csv => {
+                let Some((name_column, email_column)) = columns else {
+                    columns = Some(csv_columns(&line)?);
+                    continue;
+                };
+                csv::parse_record(&line).and_then(|fields| match (fields.get(name_column), fields.get(email_column)) {
+                    (Some(name), Some(email)) => Ok(UserImportRecord { name: name.trim().to_string(), email: email.trim().to_string() }),
+                    _ => Err("missing the name or email column".to_string()),
+                })
+            }
+            ImportFormat::Ndjson => serde_json::from_str::<UserImportRecord>(&line).map_err(|e| e.to_string()),
+        };
+        let record = match record {
+            Ok(record) => record,
+            Err(reason) => {
+                summary.fail(UserImportError { line: line_number, email: None, reason });
+                continue;
+            }
+        };
+        let email = record.email.clone();
+        match auth_service.import_user(admin, record).await {
+            Ok(Some(_)) => summary.created += 1,
+            Ok(None) => {
+                summary.skipped += 1;
+                summary.push_error(UserImportError { line: line_number, email: Some(email), reason: "email already exists".to_string() });
+            }
+            Err(AppError::Validation(errors)) => {
+                let reason = errors.iter()
+                    .flat_map(|error| error.messages.iter().map(move |message| format!("{}: {}", error.field, message)))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                summary.fail(UserImportError { line: line_number, email: Some(email), reason });
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    if matches!(format, ImportFormat::Csv) && columns.is_none() {
+        return Err(AppError::bad_request(ErrorMessage::ImportHeaderInvalid));
+    }
+    Ok(summary)
+}
+
+fn csv_columns(header: &str) -> Result<(usize, usize), AppError> {
+    let fields = csv::parse_record(header).map_err(|_| AppError::bad_request(ErrorMessage::ImportHeaderInvalid))?;
+    let position = |column: &str| fields.iter().position(|field| field.trim().eq_ignore_ascii_case(column));
+    match (position("name"), position("email")) {
+        (Some(name_column), Some(email_column)) => Ok((name_column, email_column)),
+        _ => Err(AppError::bad_request(ErrorMessage::ImportHeaderInvalid)),
+    }
+}
+
+impl UserImportSummary {
+    fn fail(&mut self, error: UserImportError) {
+        self.failed += 1;
+        self.push_error(error);
+    }
+    fn push_error(&mut self, error: UserImportError) {
+        if self.errors.len() < MAX_IMPORT_ERRORS {
+            self.errors.push(error);
+        }
+    }
+}
+
+/// Every user as CSV, oldest first, read `EXPORT_BATCH_SIZE` rows at a time while the body is sent.
+/// A database error mid-way aborts the body, so the client sees a failed download instead of a
+/// file that looks complete.
+pub fn export_users(app_state: Arc<AppState>) -> Body {
+    let header = stream::once(async { Ok::<_, io::Error>(Bytes::from_static(EXPORT_HEADER.as_bytes())) });
+    let rows = stream::unfold(Some(None), move |cursor| {
+        let app_state = app_state.clone();
+        async move {
+            let cursor = cursor?;
+            match app_state.db.users.get_users_for_export(cursor, EXPORT_BATCH_SIZE).await {
+                Ok(users) if users.is_empty() => None,
+                Ok(users) => {
+                    let next_cursor = (users.len() as i64 == EXPORT_BATCH_SIZE)
+                        .then(|| users.last().map(|user| (user.created_at, user.id)));
+                    let chunk: String = users.iter().map(export_line).collect();
+                    Some((Ok(Bytes::from(chunk)), next_cursor))
+                }
+                Err(e) => {
+                    warn!("Failed to read users for the export: {}", e);
+                    Some((Err(io::Error::other(e.to_string())), None))
+                }
+            }
+        }
+    });
+    Body::from_stream(header.chain(rows))
+}
+
+fn export_line(user: &UserExportRow) -> String {
+    format!(
+        "{},{},{},{},{},{},{},{},{}\n",
+        user.id,
+        csv::escape_field(&user.name),
+        csv::escape_field(&user.email),
+        csv::escape_field(user.username.as_deref().unwrap_or_default()),
+        user.role.get_value(),
+        user.is_verified,
+        user.is_banned,
+        user.is_private,
+        user.created_at.to_rfc3339(),
+    )
+}
\ No newline at end of file
@@ -0,0 +1,83 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use crate::{
+    config::Config,
+    dto::{default_order_by, Paginated, Pagination},
+    modules::{
+        audit_log::model::AuditCategory,
+        user::dto::{validate_order_by, validate_optional_date, UserResponse},
+    },
+};
+
+#[derive(Deserialize, Validate)]
+pub struct UserHistoryParams {
+    pub limit: Option<usize>,
+    #[validate(range(min = 1, max = 100000, message = "Page must be between 1 and 100000."))]
+    pub page: Option<usize>,
+    #[serde(default = "default_order_by")]
+    #[validate(custom(function = "validate_order_by"))]
+    pub order_by: Option<String>,
+    #[validate(length(min = 1, message = "Search must be at least 1 character."))]
+    pub search: Option<String>,
+    pub category: Option<AuditCategory>,
+    #[validate(custom(function = "validate_optional_date"))]
+    pub since: Option<String>,
+    #[validate(custom(function = "validate_optional_date"))]
+    pub until: Option<String>,
+    #[serde(skip)]
+    pub pagination: Pagination,
+}
+impl Paginated for UserHistoryParams {
+    fn paginate(&mut self, config: &Config) {
+        self.pagination = Pagination::sanitize(self.page, self.limit, config);
+    }
+}
+
+/// A Bearer token acting as `user` until `expires_at`, there is no refresh token.
+#[derive(Serialize)]
+pub struct ImpersonationResponse {
+    pub access_token: String,
+    pub expires_at: DateTime<Utc>,
+    pub user: UserResponse,
+}
+/// A link that downloads without a token until `expires_at`, see `utils::signing`.
+#[derive(Serialize)]
+pub struct SignedLinkResponse {
+    pub url: String,
+    pub expires_at: DateTime<Utc>,
+}
+/// One account of a bulk import, a CSV row or an NDJSON line. Imported accounts get the user role.
+#[derive(Deserialize, Validate)]
+pub struct UserImportRecord {
+    #[validate(length(
+        min = 4,
+        max = 50,
+        message = "Name must be between 4 and 50 characters"
+    ))]
+    pub name: String,
+    #[validate(
+        length(min = 1, message = "Email is required"),
+        email(message = "Email is invalid")
+    )]
+    pub email: String,
+}
+
+/// A line of the upload that didn't create an account. Line numbers count from 1, header included.
+#[derive(Serialize)]
+pub struct UserImportError {
+    pub line: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    pub reason: String,
+}
+
+#[derive(Serialize, Default)]
+pub struct UserImportSummary {
+    pub created: u64,
+    /// Lines whose email already belongs to an account.
+    pub skipped: u64,
+    pub failed: u64,
+    /// The first skipped and failed lines, the counts above cover all of them.
+    pub errors: Vec<UserImportError>,
+}
\ No newline at end of file
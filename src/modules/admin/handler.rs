@@ -0,0 +1,142 @@
+use std::sync::Arc;
+use axum::{
+    middleware, Router, routing::{get, post}, Extension, response::IntoResponse,
+    extract::Request,
+    http::header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+};
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+use crate::{
+    AppState,
+    dto::{HttpResult, SuccessResponse},
+    error::{PathParser, PaginatedQuery},
+    middleware::{AuthenticatedUser, permission::{check_permission, Permission}},
+    modules::{
+        admin::{bulk::{self, ImportFormat}, dto::{SignedLinkResponse, UserHistoryParams}},
+        report::handler::report_admin_router,
+        aggregate::handler::aggregate_admin_router,
+        waitlist::handler::waitlist_admin_router,
+        webhook::handler::webhook_admin_router,
+        feature_flag::handler::feature_flag_admin_router,
+        invitation::handler::invitation_admin_router,
+        velocity::handler::velocity_admin_router,
+        organization::handler::organization_admin_router,
+        announcement::handler::announcement_admin_router,
+        tos::handler::tos_admin_router,
+        retention::handler::retention_admin_router,
+        data_export::handler::{data_export_admin_router, data_export_public_router},
+    },
+    utils::{ndjson, signing::{self, SignedUrl, UrlScope, UsersExport}},
+};
+
+pub fn admin_router() -> Router {
+    Router::new()
+        .route("/users/{id}/history", get(user_history).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminUserHistory.to_string())
+        })))
+        .route("/impersonate/{user_id}", post(impersonate).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminImpersonate.to_string())
+        })))
+        .route("/users/import", post(user_import).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminUserImport.to_string())
+        })))
+        .route("/users/export", get(user_export).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminUserExport.to_string())
+        })))
+        .route("/users/export/link", post(user_export_link).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminUserExport.to_string())
+        })))
+        .route("/users/stream", get(user_stream).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminUserExport.to_string())
+        })))
+        .merge(report_admin_router())
+        .merge(aggregate_admin_router())
+        .merge(waitlist_admin_router())
+        .merge(webhook_admin_router())
+        .merge(feature_flag_admin_router())
+        .merge(invitation_admin_router())
+        .merge(velocity_admin_router())
+        .merge(organization_admin_router())
+        .merge(announcement_admin_router())
+        .merge(tos_admin_router())
+        .merge(retention_admin_router())
+        .merge(data_export_admin_router())
+}
+
+/// Routes that authenticate by a signed URL instead of a token.
+pub fn admin_public_router() -> Router {
+    Router::new()
+        .route("/users/export/download", get(user_export_download))
+        .merge(data_export_public_router())
+}
+
+async fn user_history(
+    Extension(app_state): Extension<Arc<AppState>>,
+    PathParser(user_id): PathParser<Uuid>,
+    PaginatedQuery(query_params): PaginatedQuery<UserHistoryParams>,
+) -> HttpResult<impl IntoResponse> {
+    let result = app_state.db.audit_logs.get_user_history(user_id, query_params).await?;
+    Ok(
+        SuccessResponse::new("Getting user history data", Some(result))
+    )
+}
+async fn impersonate(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(user_id): PathParser<Uuid>,
+) -> HttpResult<impl IntoResponse> {
+    let result = app_state.auth_service().impersonate(&user_auth.user, user_id).await?;
+    Ok(
+        SuccessResponse::new("Successfully started impersonating the user.", Some(result))
+    )
+}
+/// Takes `text/csv` or `application/x-ndjson`, the body is read line by line as it arrives.
+async fn user_import(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    request: Request,
+) -> HttpResult<impl IntoResponse> {
+    let content_type = request.headers().get(CONTENT_TYPE).and_then(|value| value.to_str().ok());
+    let format = ImportFormat::from_content_type(content_type)?;
+    let summary = bulk::import_users(&app_state, &user_auth.user, format, request.into_body()).await?;
+    Ok(
+        SuccessResponse::new("Finished importing the users.", Some(summary))
+    )
+}
+async fn user_export(
+    Extension(app_state): Extension<Arc<AppState>>,
+) -> impl IntoResponse {
+    (
+        [
+            (CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (CONTENT_DISPOSITION, "attachment; filename=\"users.csv\""),
+        ],
+        bulk::export_users(app_state),
+    )
+}
+/// A link to the CSV export, valid for `SIGNED_URL_AGE` seconds, for downloads that can't send the
+/// Bearer token, like a browser navigating to it.
+async fn user_export_link(
+    Extension(app_state): Extension<Arc<AppState>>,
+) -> HttpResult<impl IntoResponse> {
+    let age = app_state.env.signed_url_age;
+    let url = signing::sign_url(app_state.env.jwt_secret.as_bytes(), "/api/admin/users/export/download", UsersExport::SCOPE, age);
+    Ok(
+        SuccessResponse::new("Export link is ready.", Some(SignedLinkResponse { url, expires_at: Utc::now() + Duration::seconds(age) }))
+    )
+}
+async fn user_export_download(
+    _: SignedUrl<UsersExport>,
+    Extension(app_state): Extension<Arc<AppState>>,
+) -> impl IntoResponse {
+    user_export(Extension(app_state)).await
+}
+/// The same rows as the CSV export as NDJSON, read by one streamed query instead of in batches.
+async fn user_stream(
+    Extension(app_state): Extension<Arc<AppState>>,
+) -> impl IntoResponse {
+    (
+        [(CONTENT_TYPE, "application/x-ndjson")],
+        ndjson::body(app_state.db.users.stream_users()),
+    )
+}
\ No newline at end of file
@@ -0,0 +1,103 @@
+use chrono::{Duration, NaiveDate, Utc};
+use serde::Deserialize;
+use validator::{Validate, ValidationError};
+use crate::modules::user::dto::validate_optional_date;
+
+pub fn default_trending_limit() -> Option<i64> { Some(10) }
+
+/// Days the stats series covers when `since` isn't given, counting `until`.
+const DEFAULT_STATS_DAYS: i64 = 30;
+/// Longest range the stats series can be asked for.
+const MAX_STATS_DAYS: i64 = 366;
+
+#[derive(Deserialize, Validate)]
+pub struct TrendingParams {
+    #[serde(default = "default_trending_limit")]
+    #[validate(range(min = 1, max = 50, message = "Limit must be between 1 and 50."))]
+    pub limit: Option<i64>,
+    /// `score` (the default) weighs comments and views against the post's age, `views` ranks by
+    /// the views of the last 7 days.
+    #[validate(custom(function = "validate_trending_sort"))]
+    pub sort: Option<String>,
+}
+
+fn validate_trending_sort(value: &str) -> Result<(), ValidationError> {
+    match value {
+        "score" | "views" => Ok(()),
+        _ => {
+            let mut error = ValidationError::new("invalid_sort");
+            error.message = Some("Sort must be either 'score' or 'views'".into());
+            Err(error)
+        }
+    }
+}
+
+#[derive(Deserialize, Validate)]
+pub struct LeaderboardParams {
+    #[serde(default = "default_trending_limit")]
+    #[validate(range(min = 1, max = 50, message = "Limit must be between 1 and 50."))]
+    pub limit: Option<i64>,
+}
+
+/// The `(since, until)` UTC days, both included. Without `until` the range ends today, without
+/// `since` it covers the 30 days up to `until`. `None` when a date doesn't parse.
+fn stats_range(since: Option<&str>, until: Option<&str>) -> Option<(NaiveDate, NaiveDate)> {
+    let parse = |value: Option<&str>| value.map(|value| NaiveDate::parse_from_str(value, "%Y-%m-%d")).transpose();
+    let until = parse(until).ok()?.unwrap_or_else(|| Utc::now().date_naive());
+    let since = parse(since).ok()?.unwrap_or(until - Duration::days(DEFAULT_STATS_DAYS - 1));
+    Some((since, until))
+}
+fn check_stats_range(since: Option<&str>, until: Option<&str>) -> Result<(), ValidationError> {
+    let Some((since, until)) = stats_range(since, until) else {
+        return Ok(());
+    };
+    let message = if since > until {
+        "since must not be after until"
+    } else if (until - since).num_days() >= MAX_STATS_DAYS {
+        "The range can cover at most 366 days"
+    } else {
+        return Ok(());
+    };
+    let mut error = ValidationError::new("invalid_range");
+    error.message = Some(message.into());
+    Err(error)
+}
+fn validate_stats_range(params: &StatsParams) -> Result<(), ValidationError> {
+    check_stats_range(params.since.as_deref(), params.until.as_deref())
+}
+fn validate_top_posts_range(params: &TopPostsParams) -> Result<(), ValidationError> {
+    check_stats_range(params.since.as_deref(), params.until.as_deref())
+}
+
+#[derive(Deserialize, Validate)]
+#[validate(schema(function = "validate_stats_range", skip_on_field_errors = true))]
+pub struct StatsParams {
+    #[validate(custom(function = "validate_optional_date"))]
+    pub since: Option<String>,
+    #[validate(custom(function = "validate_optional_date"))]
+    pub until: Option<String>,
+}
+impl StatsParams {
+    /// The validated `(since, until)` days.
+    pub fn range(&self) -> (NaiveDate, NaiveDate) {
+        stats_range(self.since.as_deref(), self.until.as_deref()).expect("validated stats range")
+    }
+}
+
+#[derive(Deserialize, Validate)]
+#[validate(schema(function = "validate_top_posts_range", skip_on_field_errors = true))]
+pub struct TopPostsParams {
+    #[validate(custom(function = "validate_optional_date"))]
+    pub since: Option<String>,
+    #[validate(custom(function = "validate_optional_date"))]
+    pub until: Option<String>,
+    #[serde(default = "default_trending_limit")]
+    #[validate(range(min = 1, max = 50, message = "Limit must be between 1 and 50."))]
+    pub limit: Option<i64>,
+}
+impl TopPostsParams {
+    /// The validated `(since, until)` days.
+    pub fn range(&self) -> (NaiveDate, NaiveDate) {
+        stats_range(self.since.as_deref(), self.until.as_deref()).expect("validated stats range")
+    }
+}
\ No newline at end of file
@@ -0,0 +1,94 @@
+use std::sync::Arc;
+use axum::{middleware, Router, routing::{get, post}, Extension, response::IntoResponse};
+use crate::{
+    AppState,
+    dto::{HttpResult, SuccessResponse},
+    error::ValidatedQuery,
+    middleware::permission::{check_permission, Permission},
+    modules::aggregate::{
+        dto::{LeaderboardParams, StatsParams, TopPostsParams, TrendingParams},
+        model::StatsSeries,
+    },
+};
+
+pub fn aggregate_admin_router() -> Router {
+    Router::new()
+        .route("/stats", get(stats_series).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminStats.to_string())
+        })))
+        .route("/stats/top-posts", get(stats_top_posts).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminStats.to_string())
+        })))
+        .route("/stats/overview", get(stats_overview).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminStats.to_string())
+        })))
+        .route("/aggregates/refresh", post(aggregates_refresh).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminRefreshAggregates.to_string())
+        })))
+}
+
+pub async fn post_trending(
+    Extension(app_state): Extension<Arc<AppState>>,
+    ValidatedQuery(query_params): ValidatedQuery<TrendingParams>,
+) -> HttpResult<impl IntoResponse> {
+    let limit = query_params.limit.unwrap_or(10);
+    let by_views = query_params.sort.as_deref() == Some("views");
+    let posts = app_state.db.aggregates.get_trending_posts(limit, by_views).await?;
+    Ok(
+        SuccessResponse::new("Getting trending posts", Some(posts))
+    )
+}
+pub async fn user_leaderboard(
+    Extension(app_state): Extension<Arc<AppState>>,
+    ValidatedQuery(query_params): ValidatedQuery<LeaderboardParams>,
+) -> HttpResult<impl IntoResponse> {
+    let limit = query_params.limit.unwrap_or(10);
+    if let Ok(Some(leaderboard)) = app_state.redis_client.get_leaderboard(limit).await {
+        return Ok(
+            SuccessResponse::new("Getting user leaderboard", Some(leaderboard))
+        );
+    }
+    let leaderboard = app_state.db.aggregates.get_user_leaderboard(limit).await?;
+    let _ = app_state.redis_client.set_leaderboard(limit, &leaderboard, app_state.env.leaderboard_cache_ttl).await;
+    Ok(
+        SuccessResponse::new("Getting user leaderboard", Some(leaderboard))
+    )
+}
+async fn stats_overview(
+    Extension(app_state): Extension<Arc<AppState>>,
+) -> HttpResult<impl IntoResponse> {
+    let stats = app_state.db.aggregates.get_admin_stats().await?;
+    Ok(
+        SuccessResponse::new("Getting platform statistics", Some(stats))
+    )
+}
+async fn stats_series(
+    Extension(app_state): Extension<Arc<AppState>>,
+    ValidatedQuery(query_params): ValidatedQuery<StatsParams>,
+) -> HttpResult<impl IntoResponse> {
+    let (since, until) = query_params.range();
+    let days = app_state.db.aggregates.get_daily_stats(since, until).await?;
+    Ok(
+        SuccessResponse::new("Getting daily platform statistics", Some(StatsSeries { since, until, days }))
+    )
+}
+async fn stats_top_posts(
+    Extension(app_state): Extension<Arc<AppState>>,
+    ValidatedQuery(query_params): ValidatedQuery<TopPostsParams>,
+) -> HttpResult<impl IntoResponse> {
+    let (since, until) = query_params.range();
+    let limit = query_params.limit.unwrap_or(10);
+    let posts = app_state.db.aggregates.get_top_posts(since, until, limit).await?;
+    Ok(
+        SuccessResponse::new("Getting the most commented posts", Some(posts))
+    )
+}
+async fn aggregates_refresh(
+    Extension(app_state): Extension<Arc<AppState>>,
+) -> HttpResult<impl IntoResponse> {
+    app_state.db.aggregates.refresh_aggregate_views().await?;
+    let stats = app_state.db.aggregates.get_admin_stats().await?;
+    Ok(
+        SuccessResponse::new("Successfully refreshed the aggregate views.", Some(stats))
+    )
+}
\ No newline at end of file
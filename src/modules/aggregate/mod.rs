@@ -0,0 +1,3 @@
+pub mod dto;
+pub mod handler;
+pub mod model;
\ No newline at end of file
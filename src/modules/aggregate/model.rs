@@ -0,0 +1,253 @@
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, query_scalar};
+use uuid::Uuid;
+use crate::{db::DBClient, error::RepositoryError};
+
+#[derive(Serialize)]
+pub struct TrendingPost {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub title: String,
+    pub content: String,
+    pub tags: Vec<String>,
+    pub comment_count: i64,
+    pub views_count: i64,
+    pub score: f64,
+    pub created_at: DateTime<Utc>,
+    pub refreshed_at: DateTime<Utc>,
+}
+#[derive(Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub user_id: Uuid,
+    pub name: String,
+    pub followers_count: i64,
+    pub posts_this_week: i64,
+}
+#[derive(Serialize, Deserialize)]
+pub struct Leaderboard {
+    pub top_followed: Vec<LeaderboardEntry>,
+    pub top_posters: Vec<LeaderboardEntry>,
+    pub refreshed_at: Option<DateTime<Utc>>,
+}
+#[derive(Serialize)]
+pub struct AdminStats {
+    pub total_users: i64,
+    pub verified_users: i64,
+    pub banned_users: i64,
+    pub new_users_this_week: i64,
+    pub total_posts: i64,
+    pub hidden_posts: i64,
+    pub new_posts_this_week: i64,
+    pub total_comments: i64,
+    pub hidden_comments: i64,
+    pub open_reports: i64,
+    pub refreshed_at: DateTime<Utc>,
+}
+
+/// One UTC day of the stats series. `active_users` counts the distinct accounts that posted,
+/// commented or signed in that day.
+#[derive(Serialize)]
+pub struct DailyStats {
+    pub day: NaiveDate,
+    pub sign_ups: i64,
+    pub posts: i64,
+    pub comments: i64,
+    pub active_users: i64,
+}
+#[derive(Serialize)]
+pub struct StatsSeries {
+    pub since: NaiveDate,
+    pub until: NaiveDate,
+    pub days: Vec<DailyStats>,
+}
+/// A post ranked by the comments it got within the range, hidden posts included.
+#[derive(Serialize)]
+pub struct TopPost {
+    pub id: Uuid,
+    pub title: String,
+    pub user_id: Uuid,
+    pub author_name: String,
+    pub comment_count: i64,
+    pub is_hidden: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait AggregateRepository: Send + Sync {
+    async fn refresh_aggregate_views(&self) -> Result<(), RepositoryError>;
+    /// Ranked by `score`, or by the views of the last 7 days when `by_views` is set.
+    async fn get_trending_posts(&self, limit: i64, by_views: bool) -> Result<Vec<TrendingPost>, RepositoryError>;
+    async fn get_user_leaderboard(&self, limit: i64) -> Result<Leaderboard, RepositoryError>;
+    async fn get_admin_stats(&self) -> Result<AdminStats, RepositoryError>;
+    /// One row per day from `since` to `until`, days without activity included as zeros.
+    async fn get_daily_stats(&self, since: NaiveDate, until: NaiveDate) -> Result<Vec<DailyStats>, RepositoryError>;
+    async fn get_top_posts(&self, since: NaiveDate, until: NaiveDate, limit: i64) -> Result<Vec<TopPost>, RepositoryError>;
+}
+
+#[async_trait]
+impl AggregateRepository for DBClient {
+    async fn refresh_aggregate_views(&self) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("aggregate.refresh_aggregate_views");
+        // CONCURRENTLY keeps the views readable while they are rebuilt, it relies on the unique indexes.
+        query!("REFRESH MATERIALIZED VIEW CONCURRENTLY trending_posts;").execute(&self.pool).await?;
+        query!("REFRESH MATERIALIZED VIEW CONCURRENTLY user_leaderboard;").execute(&self.pool).await?;
+        query!("REFRESH MATERIALIZED VIEW CONCURRENTLY admin_stats;").execute(&self.pool).await?;
+        Ok(())
+    }
+    async fn get_trending_posts(&self, limit: i64, by_views: bool) -> Result<Vec<TrendingPost>, RepositoryError> {
+        let _timer = self.time_query("aggregate.get_trending_posts");
+        let posts = query_as!(
+            TrendingPost,
+            r#"
+                SELECT p.id, p.user_id, p.title, p.content, p.tags, t.comment_count AS "comment_count!",
+                       p.views_count, t.score AS "score!", p.created_at, t.refreshed_at AS "refreshed_at!"
+                FROM trending_posts AS t
+                JOIN posts AS p ON p.id = t.post_id
+                JOIN users AS u ON u.id = p.user_id
+                WHERE p.is_hidden = false AND u.is_private = false
+                ORDER BY CASE WHEN $2 THEN t.view_count ELSE 0 END DESC, t.score DESC
+                LIMIT $1;
+            "#,
+            limit,
+            by_views,
+        ).fetch_all(&self.pool).await?;
+        Ok(posts)
+    }
+    async fn get_user_leaderboard(&self, limit: i64) -> Result<Leaderboard, RepositoryError> {
+        let _timer = self.time_query("aggregate.get_user_leaderboard");
+        let mut transaction = self.pool.begin().await?;
+        let top_followed = query_as!(
+            LeaderboardEntry,
+            r#"
+                SELECT user_id AS "user_id!", name AS "name!", followers_count AS "followers_count!",
+                       posts_this_week AS "posts_this_week!"
+                FROM user_leaderboard
+                WHERE followers_count > 0
+                ORDER BY followers_count DESC, name ASC
+                LIMIT $1;
+            "#,
+            limit,
+        ).fetch_all(&mut *transaction).await?;
+        let top_posters = query_as!(
+            LeaderboardEntry,
+            r#"
+                SELECT user_id AS "user_id!", name AS "name!", followers_count AS "followers_count!",
+                       posts_this_week AS "posts_this_week!"
+                FROM user_leaderboard
+                WHERE posts_this_week > 0
+                ORDER BY posts_this_week DESC, name ASC
+                LIMIT $1;
+            "#,
+            limit,
+        ).fetch_all(&mut *transaction).await?;
+        let refreshed_at = query_scalar!(
+            r#"
+                SELECT MAX(refreshed_at) FROM user_leaderboard;
+            "#
+        ).fetch_one(&mut *transaction).await?;
+        transaction.commit().await?;
+        Ok(Leaderboard {
+            top_followed,
+            top_posters,
+            refreshed_at,
+        })
+    }
+    async fn get_admin_stats(&self) -> Result<AdminStats, RepositoryError> {
+        let _timer = self.time_query("aggregate.get_admin_stats");
+        let stats = query_as!(
+            AdminStats,
+            r#"
+                SELECT total_users AS "total_users!", verified_users AS "verified_users!", banned_users AS "banned_users!",
+                       new_users_this_week AS "new_users_this_week!", total_posts AS "total_posts!",
+                       hidden_posts AS "hidden_posts!", new_posts_this_week AS "new_posts_this_week!",
+                       total_comments AS "total_comments!", hidden_comments AS "hidden_comments!",
+                       open_reports AS "open_reports!", refreshed_at AS "refreshed_at!"
+                FROM admin_stats;
+            "#
+        ).fetch_one(&self.pool).await?;
+        Ok(stats)
+    }
+    async fn get_daily_stats(&self, since: NaiveDate, until: NaiveDate) -> Result<Vec<DailyStats>, RepositoryError> {
+        let _timer = self.time_query("aggregate.get_daily_stats");
+        let days = query_as!(
+            DailyStats,
+            r#"
+                WITH bounds AS (
+                    SELECT ($1::DATE)::TIMESTAMP AT TIME ZONE 'UTC' AS start_at,
+                           ($2::DATE + 1)::TIMESTAMP AT TIME ZONE 'UTC' AS end_at
+                ),
+                days AS (
+                    SELECT generate_series($1::DATE, $2::DATE, INTERVAL '1 day')::DATE AS day
+                ),
+                sign_ups AS (
+                    SELECT (u.created_at AT TIME ZONE 'UTC')::DATE AS day, COUNT(*) AS total
+                    FROM users AS u, bounds AS b
+                    WHERE u.created_at >= b.start_at AND u.created_at < b.end_at
+                    GROUP BY 1
+                ),
+                new_posts AS (
+                    SELECT (p.created_at AT TIME ZONE 'UTC')::DATE AS day, COUNT(*) AS total
+                    FROM posts AS p, bounds AS b
+                    WHERE p.created_at >= b.start_at AND p.created_at < b.end_at
+                    GROUP BY 1
+                ),
+                new_comments AS (
+                    SELECT (c.created_at AT TIME ZONE 'UTC')::DATE AS day, COUNT(*) AS total
+                    FROM comments AS c, bounds AS b
+                    WHERE c.created_at >= b.start_at AND c.created_at < b.end_at
+                    GROUP BY 1
+                ),
+                activity AS (
+                    SELECT user_id, created_at AS at FROM posts
+                    UNION ALL SELECT user_id, created_at FROM comments
+                    UNION ALL SELECT user_id, created_at FROM user_sessions
+                    UNION ALL SELECT user_id, last_seen_at FROM user_sessions
+                ),
+                active AS (
+                    SELECT (a.at AT TIME ZONE 'UTC')::DATE AS day, COUNT(DISTINCT a.user_id) AS total
+                    FROM activity AS a, bounds AS b
+                    WHERE a.at >= b.start_at AND a.at < b.end_at
+                    GROUP BY 1
+                )
+                SELECT d.day AS "day!",
+                       COALESCE(s.total, 0) AS "sign_ups!",
+                       COALESCE(p.total, 0) AS "posts!",
+                       COALESCE(c.total, 0) AS "comments!",
+                       COALESCE(a.total, 0) AS "active_users!"
+                FROM days AS d
+                LEFT JOIN sign_ups AS s ON s.day = d.day
+                LEFT JOIN new_posts AS p ON p.day = d.day
+                LEFT JOIN new_comments AS c ON c.day = d.day
+                LEFT JOIN active AS a ON a.day = d.day
+                ORDER BY d.day;
+            "#,
+            since,
+            until,
+        ).fetch_all(&self.pool).await?;
+        Ok(days)
+    }
+    async fn get_top_posts(&self, since: NaiveDate, until: NaiveDate, limit: i64) -> Result<Vec<TopPost>, RepositoryError> {
+        let _timer = self.time_query("aggregate.get_top_posts");
+        let posts = query_as!(
+            TopPost,
+            r#"
+                SELECT p.id, p.title, p.user_id, u.name AS author_name, COUNT(*) AS "comment_count!",
+                       p.is_hidden, p.created_at
+                FROM comments AS c
+                JOIN posts AS p ON p.id = c.post_id
+                JOIN users AS u ON u.id = p.user_id
+                WHERE c.created_at >= ($1::DATE)::TIMESTAMP AT TIME ZONE 'UTC'
+                  AND c.created_at < ($2::DATE + 1)::TIMESTAMP AT TIME ZONE 'UTC'
+                GROUP BY p.id, u.name
+                ORDER BY COUNT(*) DESC, p.created_at DESC
+                LIMIT $3;
+            "#,
+            since,
+            until,
+            limit,
+        ).fetch_all(&self.pool).await?;
+        Ok(posts)
+    }
+}
\ No newline at end of file
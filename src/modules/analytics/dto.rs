@@ -0,0 +1,91 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+use crate::modules::analytics::model::AnalyticsEventKind;
+
+const MAX_PROPERTIES: usize = 20;
+const MAX_PROPERTY_LENGTH: usize = 200;
+/// How far back an event may have happened, clients send what they queued while offline.
+const MAX_EVENT_AGE: Duration = Duration::days(7);
+/// How far ahead of the server's clock an event may be dated.
+const MAX_CLOCK_SKEW: Duration = Duration::minutes(5);
+
+/// One event of `POST /api/analytics/events`. Nothing identifying belongs in `properties`, they are
+/// kept as sent.
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AnalyticsEventRequest {
+    pub kind: AnalyticsEventKind,
+    /// Lowercase letters, digits, `_`, `.`, `:` and `-`, starting with a letter, at most 64
+    /// characters, such as `post.like`.
+    pub name: String,
+    /// The screen the event happened on, required for `screen_view`.
+    pub screen: Option<String>,
+    /// At most 20, keyed like `name`, each a string of at most 200 characters, a number, a boolean
+    /// or null.
+    #[serde(default)]
+    #[schema(value_type = Object)]
+    pub properties: Map<String, Value>,
+    pub occurred_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
+pub struct AnalyticsBatchRequest {
+    #[validate(length(min = 1, max = 50, message = "Events must hold between 1 and 50 events"))]
+    #[validate(custom(function = "validate_events"))]
+    pub events: Vec<AnalyticsEventRequest>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AnalyticsBatchResponse {
+    /// Events kept, 0 when the user turned analytics off in their settings.
+    pub accepted: usize,
+}
+
+fn event_error(index: usize, message: &str) -> ValidationError {
+    let mut error = ValidationError::new("invalid_event");
+    error.message = Some(format!("Event {}: {}", index, message).into());
+    error
+}
+fn is_event_name(value: &str) -> bool {
+    value.len() <= 64
+        && value.starts_with(|c: char| c.is_ascii_lowercase())
+        && value.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '.' | ':' | '-'))
+}
+fn is_property_value(value: &Value) -> bool {
+    match value {
+        Value::String(value) => value.chars().count() <= MAX_PROPERTY_LENGTH,
+        Value::Number(_) | Value::Bool(_) | Value::Null => true,
+        Value::Array(_) | Value::Object(_) => false,
+    }
+}
+/// Reports the first event that breaks the schema, by its position in the batch.
+fn validate_events(events: &[AnalyticsEventRequest]) -> Result<(), ValidationError> {
+    let now = Utc::now();
+    for (index, event) in events.iter().enumerate() {
+        if !is_event_name(&event.name) {
+            return Err(event_error(index, "name must start with a lowercase letter and hold at most 64 lowercase letters, digits, _ . : or -"));
+        }
+        match &event.screen {
+            Some(screen) if screen.trim().is_empty() || screen.chars().count() > 100 => {
+                return Err(event_error(index, "screen must be between 1 and 100 characters"));
+            }
+            None if event.kind == AnalyticsEventKind::ScreenView => {
+                return Err(event_error(index, "screen is required for a screen_view"));
+            }
+            _ => {}
+        }
+        if event.properties.len() > MAX_PROPERTIES {
+            return Err(event_error(index, "properties may hold at most 20 entries"));
+        }
+        if !event.properties.iter().all(|(key, value)| is_event_name(key) && is_property_value(value)) {
+            return Err(event_error(index, "properties must be keyed like names and hold strings of at most 200 characters, numbers, booleans or null"));
+        }
+        if event.occurred_at < now - MAX_EVENT_AGE || event.occurred_at > now + MAX_CLOCK_SKEW {
+            return Err(event_error(index, "occurred_at must be within the last 7 days"));
+        }
+    }
+    Ok(())
+}
\ No newline at end of file
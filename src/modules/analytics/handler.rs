@@ -0,0 +1,39 @@
+use std::sync::Arc;
+use axum::{middleware, Router, routing::post, Extension, response::IntoResponse, http::StatusCode};
+use crate::{
+    AppState,
+    dto::{HttpResult, SuccessResponse},
+    error::ValidatedJson,
+    middleware::{AuthenticatedUser, permission::{check_permission, Permission}},
+    modules::analytics::dto::{AnalyticsBatchRequest, AnalyticsBatchResponse},
+};
+
+pub fn analytics_router() -> Router {
+    Router::new()
+        .route("/events", post(analytics_events).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AnalyticsTrack.to_string())
+        })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/analytics/events",
+    tag = "analytics",
+    request_body = AnalyticsBatchRequest,
+    responses(
+        (status = 202, description = "Events kept for the analytics sink, without the user who sent them. None are kept when the user turned analytics off", body = SuccessResponse<AnalyticsBatchResponse>),
+        (status = 400, description = "An event doesn't match the schema, the message names it by its position"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn analytics_events(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    ValidatedJson(body): ValidatedJson<AnalyticsBatchRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let accepted = app_state.analytics_service().track(&user_auth.user, body).await?;
+    Ok((
+        StatusCode::ACCEPTED,
+        SuccessResponse::new("Analytics events received", Some(AnalyticsBatchResponse { accepted })),
+    ))
+}
\ No newline at end of file
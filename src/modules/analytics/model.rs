@@ -0,0 +1,75 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::query;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use crate::{db::DBClient, error::RepositoryError};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalyticsEventKind {
+    ScreenView,
+    Interaction,
+}
+
+impl AnalyticsEventKind {
+    pub fn get_value(&self) -> &'static str {
+        match self {
+            AnalyticsEventKind::ScreenView => "screen_view",
+            AnalyticsEventKind::Interaction => "interaction",
+        }
+    }
+}
+
+/// A client event as it is kept, without the user who sent it. `subject` is the same for the
+/// events a user sends on one day (UTC) and tells nothing about the user.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AnalyticsEvent {
+    pub id: Uuid,
+    pub subject: String,
+    pub kind: AnalyticsEventKind,
+    pub name: String,
+    pub screen: Option<String>,
+    pub properties: Map<String, Value>,
+    pub occurred_at: DateTime<Utc>,
+    pub received_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait AnalyticsRepository: Send + Sync {
+    /// Events already saved are skipped, so a batch handed over twice is saved once.
+    async fn save_analytics_events(&self, events: &[AnalyticsEvent]) -> Result<(), RepositoryError>;
+}
+
+#[async_trait]
+impl AnalyticsRepository for DBClient {
+    async fn save_analytics_events(&self, events: &[AnalyticsEvent]) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("analytics.save_analytics_events");
+        let ids: Vec<Uuid> = events.iter().map(|event| event.id).collect();
+        let subjects: Vec<String> = events.iter().map(|event| event.subject.clone()).collect();
+        let kinds: Vec<String> = events.iter().map(|event| event.kind.get_value().to_string()).collect();
+        let names: Vec<String> = events.iter().map(|event| event.name.clone()).collect();
+        let screens: Vec<Option<String>> = events.iter().map(|event| event.screen.clone()).collect();
+        let properties: Vec<Value> = events.iter().map(|event| Value::Object(event.properties.clone())).collect();
+        let occurred_at: Vec<DateTime<Utc>> = events.iter().map(|event| event.occurred_at).collect();
+        let received_at: Vec<DateTime<Utc>> = events.iter().map(|event| event.received_at).collect();
+        query!(
+            r#"
+                INSERT INTO analytics_events (id, subject, kind, name, screen, properties, occurred_at, received_at)
+                SELECT * FROM UNNEST($1::uuid[], $2::text[], $3::text[], $4::text[], $5::text[], $6::jsonb[], $7::timestamptz[], $8::timestamptz[])
+                ON CONFLICT (id) DO NOTHING;
+            "#,
+            &ids,
+            &subjects,
+            &kinds,
+            &names,
+            &screens as &[Option<String>],
+            &properties,
+            &occurred_at,
+            &received_at,
+        ).execute(&self.pool).await?;
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,69 @@
+use std::sync::Arc;
+use chrono::Utc;
+use log::warn;
+use uuid::Uuid;
+use crate::{
+    AppState,
+    error::AppError,
+    modules::{
+        analytics::{dto::AnalyticsBatchRequest, model::AnalyticsEvent},
+        event::domain_event::DomainEvent,
+        user::model::User,
+    },
+    utils::{metrics, signing},
+};
+
+/// Client analytics. Events are kept without the user who sent them, under a pseudonym that changes
+/// every day (UTC), and not at all for users who turned `analytics` off in their settings. They wait
+/// in Redis until the flush job hands them to `ANALYTICS_SINK`.
+pub struct AnalyticsService {
+    app_state: Arc<AppState>,
+}
+
+impl AnalyticsService {
+    pub fn new(app_state: Arc<AppState>) -> Self {
+        Self { app_state }
+    }
+    /// Returns how many events were kept. Without Redis they go to the sink right away.
+    pub async fn track(&self, actor: &User, body: AnalyticsBatchRequest) -> Result<usize, AppError> {
+        let settings = self.app_state.db.users.get_user_settings(actor.id).await?;
+        if settings.analytics == Some(false) {
+            metrics::increment_counter_by("analytics_events_total", &[("outcome", "opted_out")], body.events.len() as u64);
+            return Ok(0);
+        }
+        let now = Utc::now();
+        let subject = signing::pseudonym(
+            self.app_state.env.jwt_secret.as_bytes(),
+            "analytics",
+            &format!("{}:{}", actor.id, now.date_naive()),
+        );
+        let events: Vec<AnalyticsEvent> = body.events.into_iter()
+            .map(|event| AnalyticsEvent {
+                id: Uuid::new_v4(),
+                subject: subject.clone(),
+                kind: event.kind,
+                name: event.name,
+                screen: event.screen.map(|screen| screen.trim().to_string()),
+                properties: event.properties,
+                occurred_at: event.occurred_at,
+                received_at: now,
+            })
+            .collect();
+        if let Err(e) = self.app_state.redis_client.buffer_analytics_events(&events).await {
+            warn!("Failed to buffer {} analytics events, sending them on now: {}", events.len(), e);
+            self.deliver(events.clone()).await?;
+        }
+        metrics::increment_counter_by("analytics_events_total", &[("outcome", "accepted")], events.len() as u64);
+        Ok(events.len())
+    }
+    /// Hands `events` to `ANALYTICS_SINK`: the `analytics_events` table, or the domain event stream
+    /// as one `analytics_events_recorded` event.
+    pub async fn deliver(&self, events: Vec<AnalyticsEvent>) -> Result<(), AppError> {
+        if self.app_state.env.analytics_sink == "events" {
+            self.app_state.publish(DomainEvent::AnalyticsEventsRecorded { events }).await;
+            return Ok(());
+        }
+        self.app_state.db.analytics.save_analytics_events(&events).await?;
+        Ok(())
+    }
+}
\ No newline at end of file
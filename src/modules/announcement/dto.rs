@@ -0,0 +1,86 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
+use crate::{
+    dto::{nullable, require_any_field},
+    modules::{announcement::model::{Announcement, AnnouncementLevel}, role::model::RoleType},
+};
+
+#[derive(Serialize, Deserialize, Validate)]
+pub struct AnnouncementRequest {
+    #[validate(length(min = 1, max = 150, message = "Title must be between 1 and 150 characters"))]
+    pub title: String,
+    #[validate(length(min = 1, max = 2000, message = "Message must be between 1 and 2000 characters"))]
+    pub message: String,
+    #[serde(default)]
+    pub level: AnnouncementLevel,
+    /// Every role and signed out visitors when empty.
+    #[serde(default)]
+    pub roles: Vec<RoleType>,
+    /// Now when missing.
+    pub starts_at: Option<DateTime<Utc>>,
+    /// Shown until deleted when missing.
+    pub ends_at: Option<DateTime<Utc>>,
+}
+
+/// Missing fields keep their value, `"ends_at": null` shows the announcement until it is deleted.
+#[derive(Serialize, Deserialize, Validate)]
+#[validate(schema(function = "validate_announcement_update", skip_on_field_errors = true))]
+pub struct AnnouncementUpdateRequest {
+    #[validate(length(min = 1, max = 150, message = "Title must be between 1 and 150 characters"))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[validate(length(min = 1, max = 2000, message = "Message must be between 1 and 2000 characters"))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<AnnouncementLevel>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub roles: Option<Vec<RoleType>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub starts_at: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub ends_at: Option<Option<DateTime<Utc>>>,
+}
+fn validate_announcement_update(body: &AnnouncementUpdateRequest) -> Result<(), ValidationError> {
+    require_any_field(
+        body.title.is_some() || body.message.is_some() || body.level.is_some() || body.roles.is_some()
+            || body.starts_at.is_some() || body.ends_at.is_some()
+    )
+}
+
+pub struct NewAnnouncement {
+    pub title: String,
+    pub message: String,
+    pub level: AnnouncementLevel,
+    pub roles: Vec<RoleType>,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub created_by: Uuid,
+}
+
+/// An announcement as clients show it, without who it is for and who wrote it.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ActiveAnnouncement {
+    pub id: Uuid,
+    pub title: String,
+    pub message: String,
+    pub level: AnnouncementLevel,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: Option<DateTime<Utc>>,
+}
+
+impl From<Announcement> for ActiveAnnouncement {
+    fn from(announcement: Announcement) -> Self {
+        Self {
+            id: announcement.id,
+            title: announcement.title,
+            message: announcement.message,
+            level: announcement.level,
+            starts_at: announcement.starts_at,
+            ends_at: announcement.ends_at,
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,93 @@
+use std::sync::Arc;
+use axum::{middleware, Router, routing::{get, post}, Extension, response::IntoResponse, http::{HeaderMap, StatusCode}};
+use uuid::Uuid;
+use crate::{
+    AppState,
+    dto::{HttpResult, SuccessResponse},
+    error::{PathParser, ValidatedJson},
+    middleware::{AuthenticatedUser, auth::optional_user, permission::{check_permission, Permission}},
+    modules::announcement::dto::{ActiveAnnouncement, AnnouncementRequest, AnnouncementUpdateRequest},
+};
+
+/// Open to signed out visitors, a valid token only adds the announcements for the user's role.
+pub fn announcement_router() -> Router {
+    Router::new()
+        .route("/active", get(announcement_active))
+}
+
+pub fn announcement_admin_router() -> Router {
+    Router::new()
+        .route("/announcements", post(announcement_create).get(announcement_list).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminAnnouncementManage.to_string())
+        })))
+        .route("/announcements/{id}", get(announcement_detail).put(announcement_update).delete(announcement_delete).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminAnnouncementManage.to_string())
+        })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/announcements/active",
+    tag = "announcement",
+    responses(
+        (status = 200, description = "Announcements to show now, the most urgent first. Without a valid token only those for everyone", body = SuccessResponse<Vec<ActiveAnnouncement>>),
+    ),
+    security((), ("bearer_auth" = [])),
+)]
+async fn announcement_active(
+    Extension(app_state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+) -> HttpResult<impl IntoResponse> {
+    let viewer = optional_user(&app_state, &headers).await;
+    let announcements = app_state.announcement_service().active(viewer.as_ref()).await?;
+    Ok(
+        SuccessResponse::new("Getting active announcement data", Some(announcements))
+    )
+}
+async fn announcement_create(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    ValidatedJson(body): ValidatedJson<AnnouncementRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let announcement = app_state.announcement_service().create(&user_auth.user, body).await?;
+    Ok((
+        StatusCode::CREATED,
+        SuccessResponse::new("Successfully created the announcement.", Some(announcement))
+    ))
+}
+async fn announcement_list(
+    Extension(app_state): Extension<Arc<AppState>>,
+) -> HttpResult<impl IntoResponse> {
+    let announcements = app_state.announcement_service().all().await?;
+    Ok(
+        SuccessResponse::new("Getting announcement list data", Some(announcements))
+    )
+}
+async fn announcement_detail(
+    Extension(app_state): Extension<Arc<AppState>>,
+    PathParser(id): PathParser<Uuid>,
+) -> HttpResult<impl IntoResponse> {
+    let announcement = app_state.announcement_service().get(id).await?;
+    Ok(
+        SuccessResponse::new("Getting announcement data", Some(announcement))
+    )
+}
+async fn announcement_update(
+    Extension(app_state): Extension<Arc<AppState>>,
+    PathParser(id): PathParser<Uuid>,
+    ValidatedJson(body): ValidatedJson<AnnouncementUpdateRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let announcement = app_state.announcement_service().update(id, body).await?;
+    Ok(
+        SuccessResponse::new("Successfully updated the announcement.", Some(announcement))
+    )
+}
+async fn announcement_delete(
+    Extension(app_state): Extension<Arc<AppState>>,
+    PathParser(id): PathParser<Uuid>,
+) -> HttpResult<impl IntoResponse> {
+    app_state.announcement_service().delete(id).await?;
+    Ok(
+        SuccessResponse::<()>::new("Successfully deleted the announcement.", None)
+    )
+}
\ No newline at end of file
@@ -0,0 +1,165 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Type, query, query_as};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use crate::{
+    db::DBClient,
+    error::RepositoryError,
+    modules::{
+        announcement::dto::{AnnouncementUpdateRequest, NewAnnouncement},
+        role::model::RoleType,
+    },
+};
+
+/// How prominently clients show an announcement, ordered from the least to the most urgent.
+#[derive(Serialize, Deserialize, Type, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, ToSchema)]
+#[sqlx(type_name = "announcement_level", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum AnnouncementLevel {
+    #[default]
+    Info,
+    Warning,
+    Critical,
+}
+
+#[derive(Serialize, Deserialize, FromRow, Clone)]
+pub struct Announcement {
+    pub id: Uuid,
+    pub title: String,
+    pub message: String,
+    pub level: AnnouncementLevel,
+    /// Role names the announcement is shown to, empty for everyone, signed out visitors included.
+    pub roles: Vec<String>,
+    pub starts_at: DateTime<Utc>,
+    /// Shown until deleted when `None`.
+    pub ends_at: Option<DateTime<Utc>>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Announcement {
+    /// Whether the announcement is shown at `now` to a visitor with `role`, `None` when signed out.
+    pub fn is_shown(&self, now: DateTime<Utc>, role: Option<RoleType>) -> bool {
+        let scheduled = self.starts_at <= now && self.ends_at.is_none_or(|ends_at| ends_at > now);
+        let targeted = self.roles.is_empty()
+            || role.is_some_and(|role| self.roles.iter().any(|name| name == role.get_value()));
+        scheduled && targeted
+    }
+}
+
+#[async_trait]
+pub trait AnnouncementRepository: Send + Sync {
+    /// Every announcement, the latest to start first.
+    async fn get_announcements(&self) -> Result<Vec<Announcement>, RepositoryError>;
+    /// The announcements that haven't ended at `now`, started or not, the most urgent first.
+    async fn get_current_announcements(&self, now: DateTime<Utc>) -> Result<Vec<Announcement>, RepositoryError>;
+    async fn get_announcement(&self, id: Uuid) -> Result<Option<Announcement>, RepositoryError>;
+    async fn save_announcement(&self, data: NewAnnouncement) -> Result<Announcement, RepositoryError>;
+    /// Changes the fields set in `data`, the others keep their value.
+    async fn update_announcement(&self, id: Uuid, data: AnnouncementUpdateRequest) -> Result<Announcement, RepositoryError>;
+    async fn delete_announcement(&self, id: Uuid) -> Result<(), RepositoryError>;
+}
+
+#[async_trait]
+impl AnnouncementRepository for DBClient {
+    async fn get_announcements(&self) -> Result<Vec<Announcement>, RepositoryError> {
+        let _timer = self.time_query("announcement.get_announcements");
+        let announcements = query_as!(
+            Announcement,
+            r#"
+                SELECT id, title, message, level AS "level: AnnouncementLevel", roles, starts_at, ends_at, created_by, created_at, updated_at
+                FROM announcements
+                ORDER BY starts_at DESC, id;
+            "#
+        ).fetch_all(&self.pool).await?;
+        Ok(announcements)
+    }
+    async fn get_current_announcements(&self, now: DateTime<Utc>) -> Result<Vec<Announcement>, RepositoryError> {
+        let _timer = self.time_query("announcement.get_current_announcements");
+        let announcements = query_as!(
+            Announcement,
+            r#"
+                SELECT id, title, message, level AS "level: AnnouncementLevel", roles, starts_at, ends_at, created_by, created_at, updated_at
+                FROM announcements
+                WHERE ends_at IS NULL OR ends_at > $1
+                ORDER BY level DESC, starts_at DESC, id;
+            "#,
+            now,
+        ).fetch_all(&self.pool).await?;
+        Ok(announcements)
+    }
+    async fn get_announcement(&self, id: Uuid) -> Result<Option<Announcement>, RepositoryError> {
+        let _timer = self.time_query("announcement.get_announcement");
+        let announcement = query_as!(
+            Announcement,
+            r#"
+                SELECT id, title, message, level AS "level: AnnouncementLevel", roles, starts_at, ends_at, created_by, created_at, updated_at
+                FROM announcements
+                WHERE id = $1;
+            "#,
+            id,
+        ).fetch_optional(&self.pool).await?;
+        Ok(announcement)
+    }
+    async fn save_announcement(&self, data: NewAnnouncement) -> Result<Announcement, RepositoryError> {
+        let _timer = self.time_query("announcement.save_announcement");
+        let roles: Vec<String> = data.roles.iter().map(|role| role.get_value().to_string()).collect();
+        let announcement = query_as!(
+            Announcement,
+            r#"
+                INSERT INTO announcements (title, message, level, roles, starts_at, ends_at, created_by)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                RETURNING id, title, message, level AS "level: AnnouncementLevel", roles, starts_at, ends_at, created_by, created_at, updated_at;
+            "#,
+            data.title,
+            data.message,
+            data.level as AnnouncementLevel,
+            &roles,
+            data.starts_at,
+            data.ends_at,
+            data.created_by,
+        ).fetch_one(&self.pool).await?;
+        Ok(announcement)
+    }
+    async fn update_announcement(&self, id: Uuid, data: AnnouncementUpdateRequest) -> Result<Announcement, RepositoryError> {
+        let _timer = self.time_query("announcement.update_announcement");
+        let roles: Option<Vec<String>> = data.roles
+            .map(|roles| roles.iter().map(|role| role.get_value().to_string()).collect());
+        let announcement = query_as!(
+            Announcement,
+            r#"
+                UPDATE announcements
+                SET title = COALESCE($2, title), message = COALESCE($3, message), level = COALESCE($4, level),
+                    roles = COALESCE($5, roles), starts_at = COALESCE($6, starts_at),
+                    ends_at = CASE WHEN $7 THEN $8 ELSE ends_at END, updated_at = Now()
+                WHERE id = $1
+                RETURNING id, title, message, level AS "level: AnnouncementLevel", roles, starts_at, ends_at, created_by, created_at, updated_at;
+            "#,
+            id,
+            data.title,
+            data.message,
+            data.level as Option<AnnouncementLevel>,
+            roles.as_deref(),
+            data.starts_at,
+            data.ends_at.is_some(),
+            data.ends_at.flatten(),
+        ).fetch_optional(&self.pool).await?.ok_or(RepositoryError::NotFound)?;
+        Ok(announcement)
+    }
+    async fn delete_announcement(&self, id: Uuid) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("announcement.delete_announcement");
+        let result = query!(
+            r#"
+                DELETE FROM announcements WHERE id = $1;
+            "#,
+            id
+        ).execute(&self.pool).await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,99 @@
+use std::sync::Arc;
+use chrono::{DateTime, Utc};
+use log::warn;
+use uuid::Uuid;
+use crate::{
+    AppState,
+    error::{AppError, ErrorMessage},
+    modules::{
+        announcement::{
+            dto::{ActiveAnnouncement, AnnouncementRequest, AnnouncementUpdateRequest, NewAnnouncement},
+            model::Announcement,
+        },
+        user::model::User,
+    },
+};
+
+/// Announcement banners. Clients read the active ones through a Redis copy of the announcements
+/// that haven't ended, so a page load doesn't reach Postgres; every admin change drops the copy.
+pub struct AnnouncementService {
+    app_state: Arc<AppState>,
+}
+
+impl AnnouncementService {
+    pub fn new(app_state: Arc<AppState>) -> Self {
+        Self { app_state }
+    }
+    /// The announcements shown now to `viewer`, `None` for a signed out visitor, the most urgent
+    /// first.
+    pub async fn active(&self, viewer: Option<&User>) -> Result<Vec<ActiveAnnouncement>, AppError> {
+        let now = Utc::now();
+        let announcements = match self.app_state.redis_client.get_announcements().await {
+            Ok(Some(announcements)) => announcements,
+            _ => {
+                let announcements = self.app_state.db.announcements.get_current_announcements(now).await?;
+                let _ = self.app_state.redis_client.set_announcements(&announcements, self.app_state.env.announcement_cache_ttl).await;
+                announcements
+            }
+        };
+        let role = match viewer {
+            Some(user) => match self.app_state.db.roles.get_role_name_by_id(user.role_id).await {
+                Ok(role) => role,
+                Err(e) => {
+                    warn!("Failed to load the role of user {}, showing announcements for everyone: {}", user.id, e);
+                    None
+                }
+            },
+            None => None,
+        };
+        Ok(announcements.into_iter()
+            .filter(|announcement| announcement.is_shown(now, role))
+            .map(ActiveAnnouncement::from)
+            .collect())
+    }
+
+    pub async fn all(&self) -> Result<Vec<Announcement>, AppError> {
+        Ok(self.app_state.db.announcements.get_announcements().await?)
+    }
+    pub async fn get(&self, id: Uuid) -> Result<Announcement, AppError> {
+        self.app_state.db.announcements.get_announcement(id).await?
+            .ok_or(AppError::not_found(ErrorMessage::DataNotFound))
+    }
+    pub async fn create(&self, actor: &User, body: AnnouncementRequest) -> Result<Announcement, AppError> {
+        let starts_at = body.starts_at.unwrap_or_else(Utc::now);
+        check_schedule(starts_at, body.ends_at)?;
+        let new_announcement = NewAnnouncement {
+            title: body.title,
+            message: body.message,
+            level: body.level,
+            roles: body.roles,
+            starts_at,
+            ends_at: body.ends_at,
+            created_by: actor.id,
+        };
+        let announcement = self.app_state.db.announcements.save_announcement(new_announcement).await?;
+        let _ = self.app_state.redis_client.delete_announcements().await;
+        Ok(announcement)
+    }
+    pub async fn update(&self, id: Uuid, body: AnnouncementUpdateRequest) -> Result<Announcement, AppError> {
+        if body.starts_at.is_some() || body.ends_at.is_some() {
+            let current = self.get(id).await?;
+            check_schedule(body.starts_at.unwrap_or(current.starts_at), body.ends_at.unwrap_or(current.ends_at))?;
+        }
+        let announcement = self.app_state.db.announcements.update_announcement(id, body).await?;
+        let _ = self.app_state.redis_client.delete_announcements().await;
+        Ok(announcement)
+    }
+    pub async fn delete(&self, id: Uuid) -> Result<(), AppError> {
+        self.app_state.db.announcements.delete_announcement(id).await?;
+        let _ = self.app_state.redis_client.delete_announcements().await;
+        Ok(())
+    }
+}
+
+fn check_schedule(starts_at: DateTime<Utc>, ends_at: Option<DateTime<Utc>>) -> Result<(), AppError> {
+    if ends_at.is_some_and(|ends_at| ends_at <= starts_at) {
+        return Err(AppError::bad_request(ErrorMessage::AnnouncementScheduleInvalid));
+    }
+    Ok(())
+}
\ No newline at end of file
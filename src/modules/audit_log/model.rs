@@ -0,0 +1,266 @@
+use async_trait::async_trait;
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{FromRow, Type, Postgres, QueryBuilder, query, query_as};
+use uuid::Uuid;
+use crate::{
+    db::DBClient,
+    dto::{PaginatedData, PaginationMeta},
+    error::RepositoryError,
+    modules::admin::dto::UserHistoryParams,
+};
+
+#[derive(Serialize, Deserialize, Type, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "audit_category", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum AuditCategory {
+    Audit,
+    Login,
+    Email,
+    Moderation,
+}
+
+pub enum AuditAction {
+    UserRegistered,
+    AccountVerified,
+    UserUpdated,
+    PasswordChanged,
+    PasswordReset,
+    UserDeleted,
+    SignIn,
+    SignOut,
+    TokenRefreshed,
+    VerificationEmailSent,
+    WelcomeEmailSent,
+    ResetPasswordEmailSent,
+    ReportDismissed,
+    ContentHidden,
+    UserBanned,
+    WaitlistApproved,
+    WaitlistEmailSent,
+    ImpersonationStarted,
+    ImpersonatedRequest,
+    UserImported,
+    InviteEmailSent,
+    InvitationAccepted,
+    NewSignInEmailSent,
+    SessionRevoked,
+    OrganizationExported,
+    OrganizationDeleted,
+}
+
+impl AuditAction {
+    pub fn get_value(&self) -> &str {
+        match self {
+            AuditAction::UserRegistered => "user.registered",
+            AuditAction::AccountVerified => "user.account-verified",
+            AuditAction::UserUpdated => "user.updated",
+            AuditAction::PasswordChanged => "user.password-changed",
+            AuditAction::PasswordReset => "user.password-reset",
+            AuditAction::UserDeleted => "user.deleted",
+            AuditAction::SignIn => "auth.sign-in",
+            AuditAction::SignOut => "auth.sign-out",
+            AuditAction::TokenRefreshed => "auth.token-refreshed",
+            AuditAction::VerificationEmailSent => "email.verification",
+            AuditAction::WelcomeEmailSent => "email.welcome",
+            AuditAction::ResetPasswordEmailSent => "email.reset-password",
+            AuditAction::ReportDismissed => "moderation.report-dismissed",
+            AuditAction::ContentHidden => "moderation.content-hidden",
+            AuditAction::UserBanned => "moderation.user-banned",
+            AuditAction::WaitlistApproved => "user.waitlist-approved",
+            AuditAction::WaitlistEmailSent => "email.waitlist",
+            AuditAction::ImpersonationStarted => "admin.impersonation-started",
+            AuditAction::ImpersonatedRequest => "admin.impersonated-request",
+            AuditAction::UserImported => "user.imported",
+            AuditAction::InviteEmailSent => "email.invite",
+            AuditAction::InvitationAccepted => "user.invitation-accepted",
+            AuditAction::NewSignInEmailSent => "email.new-sign-in",
+            AuditAction::SessionRevoked => "auth.session-revoked",
+            AuditAction::OrganizationExported => "admin.organization-exported",
+            AuditAction::OrganizationDeleted => "admin.organization-deleted",
+        }
+    }
+    pub fn get_category(&self) -> AuditCategory {
+        match self {
+            AuditAction::SignIn | AuditAction::SignOut | AuditAction::TokenRefreshed | AuditAction::SessionRevoked => AuditCategory::Login,
+            AuditAction::VerificationEmailSent | AuditAction::WelcomeEmailSent | AuditAction::ResetPasswordEmailSent
+            | AuditAction::WaitlistEmailSent | AuditAction::InviteEmailSent | AuditAction::NewSignInEmailSent => AuditCategory::Email,
+            AuditAction::ReportDismissed | AuditAction::ContentHidden | AuditAction::UserBanned => AuditCategory::Moderation,
+            _ => AuditCategory::Audit,
+        }
+    }
+}
+
+#[derive(Serialize, FromRow)]
+pub struct AuditLog {
+    pub id: Uuid,
+    pub actor_id: Option<Uuid>,
+    pub target_user_id: Uuid,
+    pub category: AuditCategory,
+    pub action: String,
+    pub metadata: Value,
+    pub created_at: DateTime<Utc>,
+}
+
+pub struct NewAuditLog {
+    pub actor_id: Option<Uuid>,
+    pub target_user_id: Uuid,
+    pub action: AuditAction,
+    pub metadata: Value,
+}
+
+impl NewAuditLog {
+    pub fn new(actor_id: Option<Uuid>, target_user_id: Uuid, action: AuditAction) -> Self {
+        Self {
+            actor_id,
+            target_user_id,
+            action,
+            metadata: Value::Object(Default::default()),
+        }
+    }
+    pub fn with_metadata(mut self, metadata: Value) -> Self {
+        self.metadata = metadata;
+        self
+    }
+}
+
+#[async_trait]
+pub trait AuditLogRepository: Send + Sync {
+    async fn save_audit_log(&self, data: NewAuditLog) -> Result<(), RepositoryError>;
+    async fn get_user_history(&self, user_id: Uuid, params: UserHistoryParams) -> Result<PaginatedData<AuditLog>, RepositoryError>;
+    async fn get_unexported_audit_logs(&self, limit: i64) -> Result<Vec<AuditLog>, RepositoryError>;
+    async fn mark_audit_logs_exported(&self, ids: &[Uuid]) -> Result<(), RepositoryError>;
+    async fn mark_audit_logs_failed(&self, ids: &[Uuid]) -> Result<(), RepositoryError>;
+}
+
+#[async_trait]
+impl AuditLogRepository for DBClient {
+    async fn save_audit_log(&self, data: NewAuditLog) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("audit_log.save_audit_log");
+        query!(
+            r#"
+                INSERT INTO audit_logs (actor_id, target_user_id, category, action, metadata)
+                VALUES ($1, $2, $3, $4, $5);
+            "#,
+            data.actor_id,
+            data.target_user_id,
+            data.action.get_category() as AuditCategory,
+            data.action.get_value(),
+            data.metadata,
+        ).execute(&self.pool).await?;
+        Ok(())
+    }
+    async fn get_user_history(&self, user_id: Uuid, params: UserHistoryParams) -> Result<PaginatedData<AuditLog>, RepositoryError> {
+        let _timer = self.time_query("audit_log.get_user_history");
+        let limit = params.pagination.limit as i32;
+        let page = params.pagination.page as i32;
+        let offset = params.pagination.offset() as i32;
+        let order_by = params.order_by.unwrap_or("DESC".to_string());
+        let mut transaction = self.pool.begin().await?;
+        let mut query_builder_items: QueryBuilder<Postgres> = QueryBuilder::new(
+            "\
+            SELECT id, actor_id, target_user_id, category, action, metadata, created_at \
+            FROM audit_logs \
+            WHERE (target_user_id = \
+            "
+        );
+        let mut query_builder_count: QueryBuilder<Postgres> = QueryBuilder::new(
+            "\
+            SELECT COUNT(*) \
+            FROM audit_logs \
+            WHERE (target_user_id = \
+            "
+        );
+        for query_builder in [&mut query_builder_items, &mut query_builder_count] {
+            query_builder
+                .push_bind(user_id)
+                .push(" OR actor_id = ")
+                .push_bind(user_id)
+                .push(")");
+            if let Some(category) = params.category {
+                query_builder
+                    .push(" AND category = ")
+                    .push_bind(category);
+            }
+            if let Some(search) = &params.search {
+                query_builder
+                    .push(" AND (action ILIKE ")
+                    .push_bind(format!("%{}%", search))
+                    .push(" OR metadata::text ILIKE ")
+                    .push_bind(format!("%{}%", search))
+                    .push(")");
+            }
+            if let (Some(since_str), Some(until_str)) = (&params.since, &params.until)
+                && let (Ok(since_naive), Ok(until_naive)) = (
+                    NaiveDate::parse_from_str(since_str, "%Y-%m-%d"),
+                    NaiveDate::parse_from_str(until_str, "%Y-%m-%d"),
+            ) {
+                let since_utc: DateTime<Utc> = Utc.from_utc_datetime(&since_naive.and_hms_opt(0, 0, 0).unwrap());
+                let until_utc: DateTime<Utc> = Utc.from_utc_datetime(&until_naive.and_hms_opt(23, 59, 59).unwrap());
+                query_builder
+                    .push(" AND (created_at BETWEEN ")
+                    .push_bind(since_utc)
+                    .push(" AND ")
+                    .push_bind(until_utc)
+                    .push(")");
+            }
+        }
+        query_builder_items
+            .push(" ORDER BY created_at ")
+            .push(order_by)
+            .push(" LIMIT ")
+            .push_bind(limit)
+            .push(" OFFSET ")
+            .push_bind(offset);
+        let query_items = query_builder_items.build_query_as::<AuditLog>();
+        let query_count = query_builder_count.build_query_scalar::<i64>();
+        let items = query_items.fetch_all(&mut *transaction).await?;
+        let total_items = query_count.fetch_one(&mut *transaction).await?;
+        transaction.commit().await?;
+        let pagination = PaginationMeta::new(page, limit, total_items);
+        Ok(PaginatedData {
+            items,
+            pagination,
+        })
+    }
+    async fn get_unexported_audit_logs(&self, limit: i64) -> Result<Vec<AuditLog>, RepositoryError> {
+        let _timer = self.time_query("audit_log.get_unexported_audit_logs");
+        let logs = query_as!(
+            AuditLog,
+            r#"
+                SELECT id, actor_id, target_user_id, category AS "category: AuditCategory", action, metadata, created_at
+                FROM audit_logs
+                WHERE exported_at IS NULL
+                ORDER BY created_at ASC
+                LIMIT $1;
+            "#,
+            limit
+        ).fetch_all(&self.pool).await?;
+        Ok(logs)
+    }
+    async fn mark_audit_logs_exported(&self, ids: &[Uuid]) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("audit_log.mark_audit_logs_exported");
+        query!(
+            r#"
+                UPDATE audit_logs
+                SET exported_at = Now(), export_attempts = export_attempts + 1
+                WHERE id = ANY($1);
+            "#,
+            ids
+        ).execute(&self.pool).await?;
+        Ok(())
+    }
+    async fn mark_audit_logs_failed(&self, ids: &[Uuid]) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("audit_log.mark_audit_logs_failed");
+        query!(
+            r#"
+                UPDATE audit_logs
+                SET export_attempts = export_attempts + 1
+                WHERE id = ANY($1);
+            "#,
+            ids
+        ).execute(&self.pool).await?;
+        Ok(())
+    }
+}
\ No newline at end of file
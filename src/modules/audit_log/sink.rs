@@ -0,0 +1,99 @@
+use std::{error::Error, time::Duration};
+use chrono::SecondsFormat;
+use reqwest::Url;
+use tokio::{io::AsyncWriteExt, net::TcpStream, time::timeout};
+use crate::{config::Config, modules::{audit_log::model::AuditLog, data_export::storage::S3Bucket}};
+
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub enum AuditSink {
+    /// RFC 5424 messages over TCP to `host:port`, octet-counting framed (RFC 6587).
+    Syslog(String),
+    Http(String),
+    /// One NDJSON object per batch in an S3 compatible bucket.
+    S3(S3Bucket),
+}
+
+impl AuditSink {
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let url = config.audit_sink_url.clone()?;
+        match config.audit_sink.as_str() {
+            "syslog" => Some(AuditSink::Syslog(url)),
+            "http" => Some(AuditSink::Http(url)),
+            "s3" => {
+                let endpoint = Url::parse(&url).ok()
+                    .filter(|url| matches!(url.scheme(), "http" | "https") && url.host().is_some())?;
+                Some(AuditSink::S3(S3Bucket::new(
+                    endpoint,
+                    config.audit_s3_bucket.clone()?,
+                    config.audit_s3_region.clone(),
+                    config.audit_s3_access_key.clone()?,
+                    config.audit_s3_secret_key.clone()?,
+                )))
+            }
+            _ => None,
+        }
+    }
+    pub fn get_value(&self) -> &str {
+        match self {
+            AuditSink::Syslog(_) => "syslog",
+            AuditSink::Http(_) => "http",
+            AuditSink::S3(_) => "s3",
+        }
+    }
+    /// Succeeds once the sink took the whole batch: the http sink answered 2xx, the bucket stored the
+    /// object, or every syslog frame was written and the connection closed without an error.
+    pub async fn send(&self, logs: &[AuditLog]) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match self {
+            AuditSink::Syslog(address) => {
+                let mut frames = Vec::new();
+                for log in logs {
+                    let message = syslog_message(log)?;
+                    frames.extend_from_slice(format!("{} {}", message.len(), message).as_bytes());
+                }
+                let mut stream = timeout(SEND_TIMEOUT, TcpStream::connect(address)).await??;
+                timeout(SEND_TIMEOUT, async {
+                    stream.write_all(&frames).await?;
+                    stream.shutdown().await
+                }).await??;
+                Ok(())
+            }
+            AuditSink::Http(url) => {
+                let response = reqwest::Client::new()
+                    .post(url)
+                    .timeout(SEND_TIMEOUT)
+                    .json(logs)
+                    .send()
+                    .await?;
+                response.error_for_status()?;
+                Ok(())
+            }
+            AuditSink::S3(bucket) => {
+                let Some(first) = logs.first() else {
+                    return Ok(());
+                };
+                let mut data = Vec::new();
+                for log in logs {
+                    serde_json::to_writer(&mut data, log)?;
+                    data.push(b'\n');
+                }
+                bucket.put_object(&batch_key(first), "application/x-ndjson", data).await
+            }
+        }
+    }
+}
+
+// RFC 5424 message, facility "security/authorization" (10) and severity "notice" (5).
+fn syslog_message(log: &AuditLog) -> Result<String, serde_json::Error> {
+    Ok(format!(
+        "<85>1 {} - axum-restful-api - {} - {}",
+        log.created_at.to_rfc3339_opts(SecondsFormat::Millis, true),
+        log.action,
+        serde_json::to_string(log)?,
+    ))
+}
+
+/// Named after the batch's first log, so a batch sent again after a failure replaces its own object.
+fn batch_key(first: &AuditLog) -> String {
+    format!("audit-logs/{}-{}.ndjson", first.created_at.format("%Y/%m/%d/%H%M%S%.3f"), first.id)
+}
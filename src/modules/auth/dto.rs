@@ -1,8 +1,10 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
-use crate::modules::user::dto::UserResponse;
+use crate::modules::user::dto::{UserResponse, validate_username};
 
-#[derive(Deserialize, Validate)]
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
 pub struct SignUpRequest {
     #[validate(length(
         min = 4,
@@ -24,6 +26,12 @@ pub struct SignUpRequest {
         must_match(other = "password", message="Password Confirm is not match")
     )]
     pub password_confirm: String,
+    /// Required while `REGISTRATION_MODE` is `invite_only`, and then only valid for the invited email.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invite_token: Option<String>,
+    /// The hCaptcha or reCAPTCHA response token, required when `CAPTCHA_ENDPOINTS` lists `sign-up`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub captcha_token: Option<String>,
 }
 
 #[derive(Deserialize, Validate)]
@@ -32,12 +40,18 @@ pub struct VerifyAccountQuery {
     pub token: String,
 }
 #[derive(Deserialize, Validate)]
+pub struct RevokeSessionQuery {
+    #[validate(length(min = 1, message = "Token key is required."))]
+    pub token: String,
+}
+#[derive(Deserialize, Validate)]
 pub struct ResendActivationRequest {
     #[validate(
         length(min = 1, message = "Email is required"),
         email(message = "Email is invalid")
     )]
     pub email: String,
+    pub captcha_token: Option<String>,
 }
 #[derive(Deserialize, Validate)]
 pub struct ForgotPasswordRequest {
@@ -46,6 +60,7 @@ pub struct ForgotPasswordRequest {
         email(message = "Email is invalid")
     )]
     pub email: String,
+    pub captcha_token: Option<String>,
 }
 #[derive(Deserialize, Validate)]
 pub struct ResetPasswordQuery {
@@ -64,7 +79,7 @@ pub struct ResetPasswordRequest {
     )]
     pub new_password_confirm: String,
 }
-#[derive(Deserialize, Validate)]
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
 pub struct SignInRequest {
     #[validate(
         length(min = 1, message = "Email is required"),
@@ -77,13 +92,49 @@ pub struct SignInRequest {
     pub password: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AvailabilityQuery {
+    #[validate(email(message = "Email is invalid"))]
+    pub email: Option<String>,
+    #[validate(length(min = 1, message = "Username is required"))]
+    #[validate(custom(function = "validate_username"))]
+    pub username: Option<String>,
+}
+/// `true` when the identifier is free, missing when it wasn't asked about.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AvailabilityResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct TokenResponse {
     pub access_token: String,
     pub token_type: String,
     pub expires_in: String,
+    pub access_token_expires_at: DateTime<Utc>,
+    /// When the refresh token cookie expires and the user has to sign in again.
+    pub refresh_token_expires_at: DateTime<Utc>,
+}
+/// Where the verification email stands when the sign-up response goes out.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum VerificationEmailStatus {
+    /// Waiting in the email outbox, it is sent right after the response.
+    Queued,
+    /// Could not be queued, the account exists and `POST /api/auth/resend-activation` sends it.
+    Failed,
+}
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct SignUpResponse {
+    #[serde(flatten)]
+    pub user: UserResponse,
+    pub verification_email: VerificationEmailStatus,
 }
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct SignInResponse {
     pub user: UserResponse,
     pub token: TokenResponse,
@@ -1,40 +1,26 @@
-use std::sync::Arc;
-use axum::{middleware, Extension, Router, http::{StatusCode, header, HeaderMap}, response::IntoResponse, routing::{post, get}};
+use std::{net::{IpAddr, SocketAddr}, sync::Arc};
+use axum::{
+    middleware, Extension, Json, Router,
+    extract::{ConnectInfo, Query, rejection::QueryRejection},
+    http::{StatusCode, header, HeaderMap},
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{post, get},
+};
 use axum_extra::extract::cookie::{Cookie, SameSite, CookieJar};
-use sqlx::{Error as SqlxError};
-use chrono::{Duration, Utc};
-use uuid::Uuid;
 use validator::Validate;
 use crate::{
     AppState,
     dto::{HttpResult, SuccessResponse},
-    error::{map_sqlx_error, ErrorMessage, ErrorPayload, FieldError, HttpError, BodyParser, QueryParser},
+    error::{AppError, ErrorMessage, ValidatedJson, ValidatedQuery},
     modules::{
-        auth::dto::{TokenResponse, SignUpRequest, SignInRequest, VerifyAccountQuery, ResendActivationRequest, ForgotPasswordRequest, ResetPasswordQuery, ResetPasswordRequest, SignInResponse},
-        role::model::{RoleRepository, RoleType},
-        email::{
-            mail_verification::send_verification_email,
-            mail_welcome::send_welcome_email,
-            mail_reset_password::send_forgot_password_email,
-        },
-        user::{
-            dto::UserResponse,
-            model::{NewUser, UserRepository}
+        auth::{
+            dto::{AvailabilityQuery, AvailabilityResponse, TokenResponse, SignUpRequest, SignInRequest, VerifyAccountQuery, ResendActivationRequest, ForgotPasswordRequest, ResetPasswordQuery, ResetPasswordRequest, RevokeSessionQuery, SignInResponse, SignUpResponse, VerificationEmailStatus},
+            service::IssuedTokens,
         },
-        user_action_token::model::{
-            ActionType, 
-            NewUserActionToken, 
-            UserActionToken, 
-            UserActionTokenRepository
-        },
-        refresh_token::model::{RefreshTokenRepository}
-    },
-    utils::{
-        password,
-        rand::generate_random_string,
-        jwt
+        session::model::ClientDevice,
     },
-    middleware::{AuthenticatedUser, auth::{auth_basic, auth_token}}
+    middleware::{AuthenticatedUser, auth::{auth_basic, auth_token, bearer_token}, rate_limiter::availability_rate_limit},
+    utils::{captcha::verify_captcha, html},
 };
 
 pub fn auth_router() -> Router {
@@ -47,50 +33,20 @@ pub fn auth_router() -> Router {
                 }))
         )
         .route("/sign-up", post(sign_up))
-        .route("/verify", post(verify_account))
+        .route("/availability", get(availability).layer(middleware::from_fn(availability_rate_limit)))
+        .route("/verify", post(verify_account).get(verify_account_link))
         .route("/resend-activation", post(resend_activation))
         .route("/sign-in", post(sign_in))
         .route("/forgot-password", post(forgot_password))
         .route("/reset-password", post(reset_password))
         .route("/refresh", post(refresh_token))
         .route("/sign-out", post(sign_out).layer(middleware::from_fn(auth_token)))
+        .route("/sessions/revoke", get(revoke_session_link))
 }
-async fn user_by_email(email: &str, app_state: Arc<AppState>) -> Result<Option<UserResponse>, HttpError<ErrorPayload>> {
-    let user = app_state.db_client
-        .get_user_by_email(email).await
-        .map_err(map_sqlx_error)?;
-    Ok(user)
-}
-async fn user_action_by_token(token: &str, app_state: Arc<AppState>) -> Result<Option<UserActionToken>, HttpError<ErrorPayload>> {
-    let user = app_state.db_client
-        .get_by_token(token).await
-        .map_err(map_sqlx_error)?;
-    Ok(user)
-}
-async fn send_email_verification(email: &str, name: &str, verification_token: &str) -> Result<(), HttpError<ErrorPayload>> {
-    send_verification_email(email, name, verification_token).await
-        .map_err(|e| {
-            HttpError::server_error(ErrorMessage::FailedSendEmail(e.to_string()).to_string(), None)
-        })?;
-    Ok(())
-}
-async fn token_handling(
-    user_id: Uuid,
-    app_state: Arc<AppState>
-) -> Result<(String, HeaderMap), HttpError<ErrorPayload>> {
-    let access_token = jwt::create_token(
-        &user_id.to_string(),
-        app_state.env.jwt_secret.as_bytes(),
-        app_state.env.jwt_max_age
-    ).map_err(|e| HttpError::server_error(e.to_string(), None))?;
-    let refresh_token = generate_random_string(64);
-    let cookie_duration = time::Duration::days(app_state.env.refresh_token_age);
-    let expires_at = Utc::now() + Duration::days(app_state.env.refresh_token_age);
-    app_state.db_client.refresh_token(user_id, &refresh_token, expires_at).await
-        .map_err(map_sqlx_error)?;
+fn refresh_cookie(refresh_token: String, max_age: time::Duration) -> HeaderMap {
     let cookie = Cookie::build(("refresh_token", refresh_token))
         .path("/api/auth/refresh")
-        .max_age(cookie_duration)
+        .max_age(max_age)
         .http_only(true)
         .secure(true)
         .same_site(SameSite::Strict)
@@ -100,7 +56,25 @@ async fn token_handling(
         header::SET_COOKIE,
         cookie.to_string().parse().expect("couldn't parse cookie"),
     );
-    Ok((access_token, headers))
+    headers
+}
+fn client_ip(connect_info: Option<Extension<ConnectInfo<SocketAddr>>>) -> Option<IpAddr> {
+    connect_info.map(|Extension(ConnectInfo(addr))| addr.ip())
+}
+
+fn token_response(app_state: &AppState, tokens: IssuedTokens) -> (TokenResponse, HeaderMap) {
+    let headers = match tokens.refresh_token {
+        Some(refresh_token) => refresh_cookie(refresh_token, time::Duration::days(app_state.env.refresh_token_age)),
+        None => HeaderMap::new(),
+    };
+    let token = TokenResponse {
+        access_token: tokens.access_token,
+        token_type: String::from("Bearer"),
+        expires_in: format!("{} Minutes", app_state.env.jwt_max_age / 60),
+        access_token_expires_at: tokens.access_token_expires_at,
+        refresh_token_expires_at: tokens.refresh_token_expires_at,
+    };
+    (token, headers)
 }
 
 async fn basic_auth() -> HttpResult<impl IntoResponse> {
@@ -108,115 +82,178 @@ async fn basic_auth() -> HttpResult<impl IntoResponse> {
         SuccessResponse::<()>::new("Authenticated as Basic Authentication.", None)
     )
 }
+#[utoipa::path(
+    get,
+    path = "/api/auth/availability",
+    tag = "auth",
+    params(AvailabilityQuery),
+    responses(
+        (status = 200, description = "Which of the given identifiers are free", body = SuccessResponse<AvailabilityResponse>),
+        (status = 429, description = "Too many lookups from this client"),
+    ),
+)]
+async fn availability(
+    Extension(app_state): Extension<Arc<AppState>>,
+    ValidatedQuery(query_params): ValidatedQuery<AvailabilityQuery>,
+) -> HttpResult<impl IntoResponse> {
+    let result = app_state.auth_service().availability(query_params).await?;
+    Ok(
+        SuccessResponse::new("Checked identifier availability", Some(result))
+    )
+}
+#[utoipa::path(
+    post,
+    path = "/api/auth/sign-up",
+    tag = "auth",
+    request_body = SignUpRequest,
+    responses(
+        (status = 201, description = "Account registered, verification email queued", body = SuccessResponse<SignUpResponse>),
+        (status = 202, description = "ACCOUNT_DISCLOSURE is conceal: the same answer whether or not the email was registered before"),
+        (status = 400, description = "Validation failed, or the captcha is missing or invalid"),
+        (status = 409, description = "Email already registered"),
+    ),
+)]
 async fn sign_up(
     Extension(app_state): Extension<Arc<AppState>>, 
-    BodyParser(body): BodyParser<SignUpRequest>
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+    ValidatedJson(body): ValidatedJson<SignUpRequest>
 ) -> HttpResult<impl IntoResponse> {
-    body.validate().map_err(FieldError::populate_errors)?;
-    let user = user_by_email(&body.email, app_state.clone()).await?;
-    if user.is_some() {
-        return Err(HttpError::unique_constraint_violation(
-            ErrorMessage::EmailExist.to_string(), None
-        ));
-    }
-    let verification_token = generate_random_string(32);
-    let expires_at = Utc::now() + Duration::hours(24);
-    let hash_password = password::hash(&body.password)
-        .map_err(|_| HttpError::server_error(ErrorMessage::ServerError.to_string(), None))?;
-    let role_id = app_state.db_client.get_role_id_by_name(RoleType::User).await
-        .map_err(map_sqlx_error)?
-        .ok_or(HttpError::bad_request(ErrorMessage::DataNotFound.to_string(), None))?;
-    let user_data = NewUser {
-        role_id,
-        name: &body.name,
-        email: &body.email,
-        password: hash_password,
+    verify_captcha(&app_state, "sign-up", body.captcha_token.as_deref(), client_ip(connect_info)).await?;
+    let result = app_state.auth_service().sign_up(body).await?;
+    let result = match result {
+        Some(result) if !app_state.env.conceal_accounts => result,
+        _ => return Ok((
+            StatusCode::ACCEPTED,
+            SuccessResponse::new("Thanks! If this email can be registered, a verification email is on its way.", None),
+        )),
     };
-    let user_action_token_data = NewUserActionToken {
-        token: &verification_token,
-        action_type: ActionType::VerifyAccount,
-        expires_at,
+    let message = match (result.verification_email, result.waitlisted) {
+        (VerificationEmailStatus::Failed, _) => "Registration is successfully! The verification email could not be sent, please request a new one.",
+        (_, true) => "Registration is successfully! Please check your email to verify your account, it will be opened once your spot on the waitlist comes up.",
+        (_, false) => "Registration is successfully! Please check your email to verify your account.",
     };
-    let result = app_state.db_client.save_user(user_data, user_action_token_data).await;
-    match result {
-        Err(SqlxError::Database(db_err)) => Err(HttpError::server_error(db_err.to_string(), None)),
-        Err(_) => Err(HttpError::server_error(ErrorMessage::ServerError.to_string(), None)),
-        Ok(data) => {
-            send_email_verification(&body.email, &body.name, &verification_token).await?;
-            let (user, role_type) = data;
-            let user_response = UserResponse::get_user_response(&user, role_type);
-            Ok((
-                StatusCode::CREATED,
-                SuccessResponse::new("Registration is successfully! Please check your email to verify your account.", Some(user_response))
-            ))
-        }
-    }
+    let response = SignUpResponse {
+        user: result.user,
+        verification_email: result.verification_email,
+    };
+    Ok((
+        StatusCode::CREATED,
+        SuccessResponse::new(message, Some(response))
+    ))
 }
 
 async fn verify_account(
     Extension(app_state): Extension<Arc<AppState>>,
-    QueryParser(query_params): QueryParser<VerifyAccountQuery>
+    ValidatedQuery(query_params): ValidatedQuery<VerifyAccountQuery>
 ) -> HttpResult<impl IntoResponse> {
-    query_params.validate().map_err(FieldError::populate_errors)?;
-    let user_action = user_action_by_token(&query_params.token, app_state.clone()).await?
-        .ok_or(HttpError::bad_request(ErrorMessage::TokenKeyInvalid.to_string(), None))?;
-    let expires_at = user_action.expires_at.ok_or(HttpError::bad_request(ErrorMessage::TokenKeyExpired.to_string(), None))?;
-    if Utc::now() > expires_at {
-        return Err(HttpError::bad_request(ErrorMessage::TokenKeyExpired.to_string(), None));
-    }
-    let user = app_state.db_client.verify_account(user_action.user_id, user_action.id).await
-        .map_err(map_sqlx_error)?;
-    send_welcome_email(&user.email, &user.name).await
-        .map_err(|e| {
-            HttpError::server_error(ErrorMessage::FailedSendEmail(e.to_string()).to_string(), None)
-        })?;
+    app_state.auth_service().verify_account(&query_params.token).await?;
     Ok(SuccessResponse::<()>::new("Congratulations! Your account is activated, please login.", None))
 }
 
+/// The link in the verification email. Mail clients open it with a GET, so the outcome is a page
+/// for a person to read, or a redirect to the frontend when `VERIFY_LINK_MODE` is `redirect`.
+async fn verify_account_link(
+    Extension(app_state): Extension<Arc<AppState>>,
+    query_params: Result<Query<VerifyAccountQuery>, QueryRejection>,
+) -> Response {
+    let result = match query_params {
+        Ok(Query(query_params)) if query_params.validate().is_ok() => {
+            app_state.auth_service().verify_account(&query_params.token).await
+        }
+        _ => Err(AppError::bad_request(ErrorMessage::TokenKeyInvalid)),
+    };
+    let error = result.err().map(AppError::into_parts);
+    if app_state.env.verify_link_redirect {
+        let status = match &error {
+            Some((_, message, _)) => format!("status=error&code={}", message.get_code()),
+            None => "status=success".to_string(),
+        };
+        let frontend_url = app_state.env.frontend_url.trim_end_matches('/');
+        return Redirect::to(&format!("{}/verify?{}", frontend_url, status)).into_response();
+    }
+    let (status, title, message) = match error {
+        Some((status, message, _)) => (status, "Verification failed", format!("{} You can request a new verification email from the app.", message)),
+        None => (StatusCode::OK, "Account verified", "Congratulations! Your account is activated, please login.".to_string()),
+    };
+    link_page(status, title, &message)
+}
+
+/// The "This wasn't me" link in the new sign-in email, opened from a mail client like the
+/// verification link.
+async fn revoke_session_link(
+    Extension(app_state): Extension<Arc<AppState>>,
+    query_params: Result<Query<RevokeSessionQuery>, QueryRejection>,
+) -> Response {
+    let result = match query_params {
+        Ok(Query(query_params)) if query_params.validate().is_ok() => {
+            app_state.auth_service().revoke_session(&query_params.token).await
+        }
+        _ => Err(AppError::bad_request(ErrorMessage::TokenKeyInvalid)),
+    };
+    match result.err().map(AppError::into_parts) {
+        Some((status, message, _)) => link_page(status, "Sign-out failed", &format!("{} The link may have been used already.", message)),
+        None => link_page(StatusCode::OK, "Device signed out", "The device was signed out of your account. Change your password now if you don't recognise the sign-in."),
+    }
+}
+
+/// A minimal page for links followed from an email.
+fn link_page(status: StatusCode, title: &str, message: &str) -> Response {
+    (
+        status,
+        Html(format!(
+            "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><meta name=\"viewport\" content=\"width=device-width, initial-scale=1.0\">\
+            <title>{title}</title></head><body style=\"font-family: Arial, sans-serif; background-color: #f4f4f4; padding: 20px;\">\
+            <div style=\"max-width: 600px; margin: 0 auto; background-color: #ffffff; padding: 20px; border-radius: 8px;\">\
+            <h2 style=\"color: #333333;\">{title}</h2><p style=\"color: #555555;\">{message}</p></div></body></html>",
+            title = title,
+            message = html::escape(message),
+        )),
+    ).into_response()
+}
+
 pub async fn resend_activation(
     Extension(app_state): Extension<Arc<AppState>>,
-    BodyParser(body): BodyParser<ResendActivationRequest>
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+    ValidatedJson(body): ValidatedJson<ResendActivationRequest>
 ) -> HttpResult<impl IntoResponse> {
-    body.validate().map_err(FieldError::populate_errors)?;
-    let user = user_by_email(&body.email, app_state.clone()).await?
-        .ok_or(HttpError::not_found(ErrorMessage::DataNotFound.to_string(), None))?;
-    if user.is_verified {
-       return Err(HttpError::bad_request(ErrorMessage::AccountActive.to_string(), None)); 
+    verify_captcha(&app_state, "resend-activation", body.captcha_token.as_deref(), client_ip(connect_info)).await?;
+    let updated_user_action_token = app_state.auth_service().resend_activation(&body.email).await?;
+    if app_state.env.conceal_accounts {
+        return Ok(SuccessResponse::new("If this email has an account waiting for verification, a new verification email is on its way.", None));
     }
-    let verification_token = generate_random_string(32);
-    let expires_at = Utc::now() + Duration::hours(24);
-    let updated_user_action_token = app_state.db_client.resend_activation(user.id, &verification_token, expires_at).await
-        .map_err(map_sqlx_error)?;
-    send_email_verification(&user.email, &user.name, &verification_token).await?;
     Ok(SuccessResponse::new(
-        "Regenerate a new token key is successfully! Please check your email to verify your account.", 
-        Some(updated_user_action_token)
+        "Verification email is sent again! Please check your email to verify your account.",
+        updated_user_action_token
     ))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/auth/sign-in",
+    tag = "auth",
+    request_body = SignInRequest,
+    responses(
+        (status = 200, description = "Signed in, refresh token set as a cookie", body = SuccessResponse<SignInResponse>),
+        (status = 400, description = "Wrong credentials or inactive account"),
+        (status = 403, description = "Account is banned or still on the waitlist"),
+        (status = 429, description = "Too many failed sign-ins for this email from this address"),
+    ),
+)]
 async fn sign_in(
     Extension(app_state): Extension<Arc<AppState>>,
-    BodyParser(body): BodyParser<SignInRequest>
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+    headers: HeaderMap,
+    ValidatedJson(body): ValidatedJson<SignInRequest>
 ) -> HttpResult<impl IntoResponse> {
-    body.validate().map_err(FieldError::populate_errors)?;
-    let user = user_by_email(&body.email, app_state.clone()).await?
-        .ok_or(HttpError::bad_request(ErrorMessage::WrongCredentials.to_string(), None))?;
-    if !user.is_verified {
-        return Err(HttpError::bad_request(ErrorMessage::AccountNotActive.to_string(), None));
-    }
-    let password_matched = password::compare(&body.password, &user.password)
-        .map_err(|_| HttpError::bad_request(ErrorMessage::WrongCredentials.to_string(), None))?;
-    if !password_matched {
-        return Err(HttpError::bad_request(ErrorMessage::WrongCredentials.to_string(), None));
-    }
-    let (access_token, headers) = token_handling(user.id, app_state).await?;
+    let device = ClientDevice::new(
+        client_ip(connect_info),
+        headers.get(header::USER_AGENT).and_then(|value| value.to_str().ok()),
+    );
+    let (user, tokens) = app_state.auth_service().sign_in(&body.email, &body.password, &device).await?;
+    let (token, headers) = token_response(&app_state, tokens);
     let sign_in_response = SignInResponse {
         user,
-        token: TokenResponse {
-            access_token,
-            token_type: String::from("Bearer"),
-            expires_in: "60 Minutes".to_string(),
-        },
+        token,
     };
     let mut response = SuccessResponse::new(
         "Login is successfully.",
@@ -228,77 +265,53 @@ async fn sign_in(
 
 async fn forgot_password(
     Extension(app_state): Extension<Arc<AppState>>,
-    BodyParser(body): BodyParser<ForgotPasswordRequest>
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+    ValidatedJson(body): ValidatedJson<ForgotPasswordRequest>
 ) -> HttpResult<impl IntoResponse> {
-    body.validate().map_err(FieldError::populate_errors)?;
-    let user = user_by_email(&body.email, app_state.clone()).await?
-        .ok_or(HttpError::bad_request(ErrorMessage::DataNotFound.to_string(), None))?;
-    if !user.is_verified {
-        return Err(HttpError::bad_request(ErrorMessage::AccountNotActive.to_string(), None));
+    verify_captcha(&app_state, "forgot-password", body.captcha_token.as_deref(), client_ip(connect_info)).await?;
+    let user_action_data = app_state.auth_service().forgot_password(&body.email).await?;
+    if app_state.env.conceal_accounts {
+        return Ok(SuccessResponse::new("If this email has an active account, a password reset link is on its way.", None));
     }
-    let verification_token = generate_random_string(32);
-    let expires_at = Utc::now() + Duration::hours(2);
-    let new_user_action = NewUserActionToken {
-        token: &verification_token,
-        action_type: ActionType::ResetPassword,
-        expires_at,
-    };
-    let user_action_data = app_state.db_client.forgot_password(user.id, new_user_action).await
-        .map_err(map_sqlx_error)?;
-    send_forgot_password_email(&user.email, &user.name, &verification_token).await
-        .map_err(|e| {
-            HttpError::server_error(ErrorMessage::FailedSendEmail(e.to_string()).to_string(), None)
-        })?;
-    Ok(SuccessResponse::new("Password reset link has been sent to your email.", Some(user_action_data)))
+    Ok(SuccessResponse::new("Password reset link has been sent to your email.", user_action_data))
 }
 
 async fn reset_password(
     Extension(app_state): Extension<Arc<AppState>>,
-    QueryParser(query_params): QueryParser<ResetPasswordQuery>,
-    BodyParser(body): BodyParser<ResetPasswordRequest>,
+    ValidatedQuery(query_params): ValidatedQuery<ResetPasswordQuery>,
+    ValidatedJson(body): ValidatedJson<ResetPasswordRequest>,
 ) -> HttpResult<impl IntoResponse> {
-    query_params.validate().map_err(FieldError::populate_errors)?;
-    body.validate().map_err(FieldError::populate_errors)?;
-    let user_action = user_action_by_token(&query_params.token, app_state.clone()).await?
-        .ok_or(HttpError::bad_request(ErrorMessage::TokenKeyInvalid.to_string(), None))?;
-    let expires_at = user_action.expires_at.ok_or(HttpError::bad_request(ErrorMessage::TokenKeyExpired.to_string(), None))?;
-    if Utc::now() > expires_at {
-        return Err(HttpError::bad_request(ErrorMessage::TokenKeyExpired.to_string(), None));
-    }
-    let hash_password = password::hash(&body.new_password)
-        .map_err(|e| HttpError::server_error(e.to_string(), None))?;
-    let user = app_state.db_client.reset_password(user_action.user_id, user_action.id, hash_password).await
-        .map_err(map_sqlx_error)?;
-    let role_type = app_state.db_client.get_role_name_by_id(user.role_id).await
-        .map_err(map_sqlx_error)?
-        .ok_or(HttpError::server_error(ErrorMessage::ServerError.to_string(), None))?;
-    let user_response = UserResponse::get_user_response(&user, role_type);
+    let user_response = app_state.auth_service().reset_password(&query_params.token, &body.new_password).await?;
     Ok(SuccessResponse::new("Password has been successfully changed. Please Login.", Some(user_response)))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/auth/refresh",
+    tag = "auth",
+    responses(
+        (status = 200, description = "New access token issued from the refresh token cookie, or from a Bearer access token within JWT_REFRESH_GRACE seconds of its expiry", body = SuccessResponse<TokenResponse>),
+        (status = 400, description = "The Bearer access token is not close enough to its expiry"),
+        (status = 401, description = "Refresh token missing, invalid or expired"),
+    ),
+)]
 async fn refresh_token(
     cookie_jar: CookieJar,
+    headers: HeaderMap,
     Extension(app_state): Extension<Arc<AppState>>,
 ) -> HttpResult<impl IntoResponse> {
     let cookie_value = cookie_jar
         .get("refresh_token")
         .map(|cookie| cookie.value().to_string())
-        .ok_or(HttpError::unauthorized(ErrorMessage::TokenNotProvided.to_string(), None))?;
-    if cookie_value.trim().is_empty() {
-        return Err(HttpError::unauthorized(ErrorMessage::TokenNotProvided.to_string(), None))
-    }
-    let refresh_token_data = app_state.db_client.get_refresh_token(&cookie_value).await
-        .map_err(map_sqlx_error)?
-        .ok_or(HttpError::unauthorized(ErrorMessage::TokenInvalid.to_string(), None))?;
-    if Utc::now() > refresh_token_data.expires_at || refresh_token_data.revoked {
-        return Err(HttpError::unauthorized(ErrorMessage::TokenExpired.to_string(), None));
-    }
-    let (access_token, headers) = token_handling(refresh_token_data.user_id, app_state).await?;
-    let refresh_token_response = TokenResponse {
-        access_token,
-        token_type: String::from("Bearer"),
-        expires_in: "60 Minutes".to_string(),
+        .filter(|value| !value.trim().is_empty());
+    let tokens = match cookie_value {
+        Some(cookie_value) => app_state.auth_service().refresh(&cookie_value).await?,
+        None => {
+            let access_token = bearer_token(&headers)?;
+            app_state.auth_service().refresh_access_token(&access_token).await?
+        }
     };
+    let (refresh_token_response, headers) = token_response(&app_state, tokens);
     let mut response = SuccessResponse::new(
         "Refresh Token is successfully.",
         Some(refresh_token_response)
@@ -307,29 +320,32 @@ async fn refresh_token(
     Ok(response)
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/auth/sign-out",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Refresh tokens revoked"),
+        (status = 401, description = "Not authenticated"),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn sign_out(
     Extension(app_state): Extension<Arc<AppState>>,
     Extension(user_auth): Extension<AuthenticatedUser>
 ) -> HttpResult<impl IntoResponse> {
-    app_state.db_client.revoke_token(user_auth.user.id).await
-        .map_err(map_sqlx_error)?;
-    let expired_cookie = Cookie::build(("refresh_token", ""))
-        .path("/api/auth/refresh")
-        .max_age(time::Duration::seconds(0))
-        .http_only(true)
-        .secure(true)
-        .same_site(SameSite::Strict)
-        .build();
-    let mut headers = HeaderMap::new();
-    headers.append(
-        header::SET_COOKIE,
-        expired_cookie.to_string().parse().expect("couldn't parse cookie"),
-    );
-    let _ = app_state.redis_client.delete_user(&user_auth.user.id).await;
+    app_state.auth_service().sign_out(user_auth.user.id).await?;
+    let headers = refresh_cookie(String::new(), time::Duration::seconds(0));
     let mut response = SuccessResponse::<()>::new(
         "Logout is successfully.",
         None
     ).into_response();
     response.headers_mut().extend(headers);
     Ok(response)
+}/// Served at `/.well-known/jwks.json` as a bare JWK Set, the format token verifiers expect.
+pub async fn jwks(Extension(app_state): Extension<Arc<AppState>>) -> impl IntoResponse {
+    (
+        [(header::CACHE_CONTROL, "public, max-age=300")],
+        Json(app_state.env.jwt_keys.jwks()),
+    )
 }
\ No newline at end of file
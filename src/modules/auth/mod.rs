@@ -1,2 +1,3 @@
 pub mod dto;
-pub mod handler;
\ No newline at end of file
+pub mod handler;
+pub mod service;
\ No newline at end of file
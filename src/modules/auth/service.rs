@@ -0,0 +1,545 @@
+use std::{sync::Arc, time::Instant};
+use chrono::{DateTime, Duration, Utc};
+use log::{error, warn};
+use serde_json::json;
+use uuid::Uuid;
+use validator::Validate;
+use crate::{
+    AppState,
+    error::{AppError, ErrorMessage, RepositoryError},
+    middleware::rate_limiter::{email_rate_limit, sign_in_failed, sign_in_succeeded, sign_in_throttle},
+    modules::{
+        admin::dto::{ImpersonationResponse, UserImportRecord},
+        auth::dto::{AvailabilityQuery, AvailabilityResponse, SignUpRequest, VerificationEmailStatus},
+        role::model::RoleType,
+        email::{
+            mailer::OutboxMailer,
+            mail_invite::{send_invitation_email, send_invite_email},
+            mail_verification::send_verification_email,
+            mail_welcome::send_welcome_email,
+            mail_reset_password::send_forgot_password_email,
+            mail_new_sign_in::send_new_sign_in_email,
+        },
+        user::{
+            dto::{EmailKind, UserResponse},
+            model::{NewUser, User},
+        },
+        user_action_token::model::{ActionType, NewUserActionToken, UserActionToken},
+        audit_log::model::{AuditAction, NewAuditLog},
+        event::domain_event::DomainEvent,
+        invitation::model::{Invitation, NewInvitation},
+        session::model::{ClientDevice, NewUserSession},
+    },
+    utils::{action_token, rand::generate_random_string, jwt},
+};
+
+/// A token that passed `valid_action_token` but was used or expired before it could be, by a
+/// request racing this one.
+fn used_token_error(err: RepositoryError) -> AppError {
+    match err {
+        RepositoryError::NotFound => AppError::bad_request(ErrorMessage::TokenKeyInvalid),
+        err => err.into(),
+    }
+}
+
+/// Registration, account activation, sign in and token lifecycle. Handlers only turn the results
+/// into responses and cookies, so jobs and CLI commands get the same rules.
+pub struct AuthService {
+    app_state: Arc<AppState>,
+}
+
+pub struct IssuedTokens {
+    pub access_token: String,
+    pub access_token_expires_at: DateTime<Utc>,
+    /// `None` when the refresh token the client holds stays in use.
+    pub refresh_token: Option<String>,
+    pub refresh_token_expires_at: DateTime<Utc>,
+}
+
+pub struct SignUpResult {
+    pub user: UserResponse,
+    pub waitlisted: bool,
+    pub verification_email: VerificationEmailStatus,
+}
+
+impl AuthService {
+    pub fn new(app_state: Arc<AppState>) -> Self {
+        Self { app_state }
+    }
+    async fn user_by_email(&self, email: &str) -> Result<Option<UserResponse>, AppError> {
+        Ok(self.app_state.db.users.get_user_by_email(email).await?)
+    }
+    /// Replaces a hash made with weaker parameters than the configured ones, while the plain
+    /// password is at hand. Failing to is logged, the old hash keeps working.
+    async fn rehash_password(&self, user_id: Uuid, password: &str, hashed_password: &str) {
+        let hasher = &self.app_state.hasher;
+        if !hasher.needs_rehash(hashed_password) {
+            return;
+        }
+        let result = match hasher.hash(password) {
+            Ok(new_hash) => self.app_state.db.users.update_user_password(&user_id, new_hash).await.map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        match result {
+            Ok(_) => {
+                let _ = self.app_state.redis_client.delete_user(&user_id).await;
+            }
+            Err(e) => error!("Failed to rehash the password of user {}: {}", user_id, e),
+        }
+    }
+    fn token_hash(&self, token: &str) -> String {
+        action_token::hash(&self.app_state.env.action_token_pepper, token)
+    }
+    /// Looks up an unused verification or reset token and rejects it once it has expired.
+    async fn valid_action_token(&self, token: &str, action_type: ActionType) -> Result<UserActionToken, AppError> {
+        let pepper = &self.app_state.env.action_token_pepper;
+        let user_action = self.app_state.db.action_tokens.get_by_token(&self.token_hash(token), action_type).await?
+            .filter(|user_action| user_action.token_hash.as_deref().is_some_and(|token_hash| action_token::matches(pepper, token, token_hash)))
+            .ok_or(AppError::bad_request(ErrorMessage::TokenKeyInvalid))?;
+        let expires_at = user_action.expires_at.ok_or(AppError::bad_request(ErrorMessage::TokenKeyExpired))?;
+        if Utc::now() > expires_at {
+            return Err(AppError::bad_request(ErrorMessage::TokenKeyExpired));
+        }
+        Ok(user_action)
+    }
+    /// The invitation `token` grants to `email`, required while registration is invite only.
+    async fn sign_up_invitation(&self, email: &str, token: Option<&str>) -> Result<Option<Invitation>, AppError> {
+        if !self.app_state.env.invite_only {
+            return Ok(None);
+        }
+        let token = token.ok_or(AppError::forbidden(ErrorMessage::InvitationRequired))?;
+        match self.app_state.db.invitations.get_invitation_by_token(token).await? {
+            Some(invitation) if invitation.accepts(email) => Ok(Some(invitation)),
+            _ => Err(AppError::bad_request(ErrorMessage::InvitationInvalid)),
+        }
+    }
+    /// Queues the verification email in the outbox, the request doesn't wait for the SMTP server.
+    async fn send_email_verification(&self, email: &str, name: &str, verification_token: &str) -> Result<(), AppError> {
+        let mailer = OutboxMailer::new(self.app_state.db.emails.clone());
+        send_verification_email(&mailer, email, name, verification_token).await
+            .map_err(|e| AppError::server_error(ErrorMessage::FailedSendEmail(e.to_string())))
+    }
+    fn access_token(&self, user_id: Uuid) -> Result<(String, DateTime<Utc>), AppError> {
+        let env = &self.app_state.env;
+        let expires_at = Utc::now() + Duration::seconds(env.jwt_max_age);
+        let access_token = jwt::create_token(&user_id.to_string(), &env.jwt_keys, env.jwt_max_age)
+            .map_err(|e| {
+                error!("Failed to create access token: {}", e);
+                AppError::server_error(ErrorMessage::ServerError)
+            })?;
+        Ok((access_token, expires_at))
+    }
+    async fn issue_tokens(&self, user_id: Uuid) -> Result<IssuedTokens, AppError> {
+        let (access_token, access_token_expires_at) = self.access_token(user_id)?;
+        let refresh_token = generate_random_string(64);
+        let refresh_token_expires_at = Utc::now() + Duration::days(self.app_state.env.refresh_token_age);
+        self.app_state.db.refresh_tokens.refresh_token(user_id, &refresh_token, refresh_token_expires_at).await?;
+        Ok(IssuedTokens {
+            access_token,
+            access_token_expires_at,
+            refresh_token: Some(refresh_token),
+            refresh_token_expires_at,
+        })
+    }
+
+    /// Checks the identifiers a sign-up form is about to submit. The lookup is one query either way
+    /// and the answer is held back until `AVAILABILITY_MIN_RESPONSE_MS`, so the response time says
+    /// nothing about which identifier is taken.
+    pub async fn availability(&self, query: AvailabilityQuery) -> Result<AvailabilityResponse, AppError> {
+        if query.email.is_none() && query.username.is_none() {
+            return Err(AppError::bad_request(ErrorMessage::RequestInvalid));
+        }
+        let started = Instant::now();
+        let result = self.app_state.db.users.get_identifier_availability(query.email.as_deref(), query.username.as_deref()).await;
+        let min_duration = std::time::Duration::from_millis(self.app_state.env.availability_min_response_ms);
+        tokio::time::sleep(min_duration.saturating_sub(started.elapsed())).await;
+        let (email_available, username_available) = result?;
+        Ok(AvailabilityResponse {
+            email: query.email.map(|_| email_available),
+            username: query.username.map(|_| username_available),
+        })
+    }
+    /// With `ACCOUNT_DISCLOSURE=conceal`, holds the response of an endpoint that would otherwise tell
+    /// whether an email has an account back until `ACCOUNT_DISCLOSURE_MIN_RESPONSE_MS`.
+    async fn hold_back(&self, started: Instant) {
+        let env = &self.app_state.env;
+        if env.conceal_accounts {
+            let min_duration = std::time::Duration::from_millis(env.account_disclosure_min_response_ms);
+            tokio::time::sleep(min_duration.saturating_sub(started.elapsed())).await;
+        }
+    }
+    /// Registers a new account. `None` when the email is taken and `ACCOUNT_DISCLOSURE=conceal`
+    /// keeps that from the caller.
+    pub async fn sign_up(&self, body: SignUpRequest) -> Result<Option<SignUpResult>, AppError> {
+        let started = Instant::now();
+        let result = self.register(body).await;
+        self.hold_back(started).await;
+        result
+    }
+    async fn register(&self, body: SignUpRequest) -> Result<Option<SignUpResult>, AppError> {
+        let app_state = &self.app_state;
+        let conceal = app_state.env.conceal_accounts;
+        let email_taken = self.user_by_email(&body.email).await?.is_some();
+        if email_taken && !conceal {
+            return Err(AppError::unique_constraint_violation(ErrorMessage::EmailExist));
+        }
+        // Checked before a taken email is concealed, a missing invitation must be refused either way.
+        let invitation = self.sign_up_invitation(&body.email, body.invite_token.as_deref()).await?;
+        if email_taken {
+            return Ok(None);
+        }
+        let verification_token = generate_random_string(32);
+        let expires_at = Utc::now() + Duration::hours(24);
+        let hash_password = self.app_state.hasher.hash(&body.password)
+            .map_err(|_| AppError::server_error(ErrorMessage::ServerError))?;
+        let role_id = app_state.db.roles.get_role_id_by_name(RoleType::User).await?
+            .ok_or(AppError::bad_request(ErrorMessage::DataNotFound))?;
+        let user_data = NewUser {
+            role_id,
+            name: &body.name,
+            email: &body.email,
+            password: hash_password,
+            waitlisted: app_state.env.waitlist_enabled,
+            invitation_id: invitation.as_ref().map(|invitation| invitation.id),
+        };
+        let verification_token_hash = self.token_hash(&verification_token);
+        let user_action_token_data = NewUserActionToken {
+            token_hash: &verification_token_hash,
+            action_type: ActionType::VerifyAccount,
+            expires_at,
+        };
+        let (user, role_type) = match app_state.db.users.save_user(user_data, user_action_token_data).await {
+            // Another sign-up consumed the invitation after it was checked.
+            Err(RepositoryError::NotFound) if invitation.is_some() => return Err(AppError::bad_request(ErrorMessage::InvitationInvalid)),
+            Err(RepositoryError::Conflict { constraint }) if conceal && constraint == "users_email_key" => return Ok(None),
+            result => result?,
+        };
+        let _ = app_state.db.audit_logs.save_audit_log(NewAuditLog::new(None, user.id, AuditAction::UserRegistered)).await;
+        if let Some(invitation) = &invitation {
+            let audit_log = NewAuditLog::new(invitation.invited_by, user.id, AuditAction::InvitationAccepted)
+                .with_metadata(json!({ "invitation_id": invitation.id }));
+            let _ = app_state.db.audit_logs.save_audit_log(audit_log).await;
+        }
+        // The account is committed by now, a missing email must not turn into an error response.
+        let verification_email = match self.send_email_verification(&body.email, &body.name, &verification_token).await {
+            Ok(()) => {
+                let _ = app_state.db.audit_logs.save_audit_log(NewAuditLog::new(None, user.id, AuditAction::VerificationEmailSent)).await;
+                VerificationEmailStatus::Queued
+            }
+            Err(e) => {
+                warn!("Verification email for user {} was not queued: {}", user.id, e);
+                VerificationEmailStatus::Failed
+            }
+        };
+        let user_response = UserResponse::get_user_response(&user, role_type);
+        let waitlist_position = if app_state.env.waitlist_enabled {
+            app_state.db.waitlist.get_waitlist_position(user.id).await?
+        } else {
+            None
+        };
+        app_state.publish(DomainEvent::UserRegistered { user: user_response.clone(), waitlist_position }).await;
+        Ok(Some(SignUpResult {
+            user: user_response,
+            waitlisted: waitlist_position.is_some(),
+            verification_email,
+        }))
+    }
+    pub async fn verify_account(&self, token: &str) -> Result<(), AppError> {
+        let app_state = &self.app_state;
+        let user_action = self.valid_action_token(token, ActionType::VerifyAccount).await?;
+        let user = app_state.db.action_tokens.verify_account(user_action.user_id, user_action.id).await
+            .map_err(used_token_error)?;
+        let _ = app_state.redis_client.delete_user(&user.id).await;
+        let _ = app_state.db.audit_logs.save_audit_log(NewAuditLog::new(None, user.id, AuditAction::AccountVerified)).await;
+        if !app_state.user_service().email_allowed(user.id, EmailKind::Welcome).await {
+            return Ok(());
+        }
+        send_welcome_email(&*app_state.mailer, &user.email, &user.name).await
+            .map_err(|e| AppError::server_error(ErrorMessage::FailedSendEmail(e.to_string())))?;
+        let _ = app_state.db.audit_logs.save_audit_log(NewAuditLog::new(None, user.id, AuditAction::WelcomeEmailSent)).await;
+        Ok(())
+    }
+    /// Sends the verification email again with a new token, the links in earlier emails stop
+    /// working. Limited per address by `EMAIL_RATE_LIMIT_MAX`, like `forgot_password`. `None` when
+    /// there is nothing to send and `ACCOUNT_DISCLOSURE=conceal` keeps that from the caller.
+    pub async fn resend_activation(&self, email: &str) -> Result<Option<UserActionToken>, AppError> {
+        let started = Instant::now();
+        let result = self.send_activation_again(email).await;
+        self.hold_back(started).await;
+        result
+    }
+    async fn send_activation_again(&self, email: &str) -> Result<Option<UserActionToken>, AppError> {
+        email_rate_limit(&self.app_state, email, "resend-activation").await?;
+        let conceal = self.app_state.env.conceal_accounts;
+        let user = match self.user_by_email(email).await? {
+            Some(user) => user,
+            None if conceal => return Ok(None),
+            None => return Err(AppError::not_found(ErrorMessage::DataNotFound)),
+        };
+        if user.is_verified {
+            return if conceal { Ok(None) } else { Err(AppError::bad_request(ErrorMessage::AccountActive)) };
+        }
+        let verification_token = generate_random_string(32);
+        let expires_at = Utc::now() + Duration::hours(24);
+        let user_action_token = self.app_state.db.action_tokens.resend_activation(user.id, &self.token_hash(&verification_token), expires_at).await?;
+        self.send_email_verification(&user.email, &user.name, &verification_token).await?;
+        let _ = self.app_state.db.audit_logs.save_audit_log(NewAuditLog::new(None, user.id, AuditAction::VerificationEmailSent)).await;
+        Ok(Some(user_action_token))
+    }
+    pub async fn sign_in(&self, email: &str, password: &str, device: &ClientDevice) -> Result<(UserResponse, IssuedTokens), AppError> {
+        sign_in_throttle(&self.app_state, email, &device.ip_address).await?;
+        let Some(user) = self.user_by_email(email).await? else {
+            sign_in_failed(&self.app_state, email, &device.ip_address).await;
+            return Err(AppError::bad_request(ErrorMessage::WrongCredentials));
+        };
+        if !user.is_verified {
+            return Err(AppError::bad_request(ErrorMessage::AccountNotActive));
+        }
+        let password_matched = self.app_state.hasher.verify(password, &user.password).unwrap_or(false);
+        if !password_matched {
+            sign_in_failed(&self.app_state, email, &device.ip_address).await;
+            return Err(AppError::bad_request(ErrorMessage::WrongCredentials));
+        }
+        sign_in_succeeded(&self.app_state, email, &device.ip_address).await;
+        if user.is_banned {
+            return Err(AppError::forbidden(ErrorMessage::AccountBanned));
+        }
+        if let Some(position) = self.app_state.db.waitlist.get_waitlist_position(user.id).await? {
+            return Err(AppError::forbidden(ErrorMessage::AccountWaitlisted(position)));
+        }
+        self.rehash_password(user.id, password, &user.password).await;
+        let tokens = self.issue_tokens(user.id).await?;
+        let _ = self.app_state.db.audit_logs.save_audit_log(NewAuditLog::new(Some(user.id), user.id, AuditAction::SignIn)).await;
+        if let Err(e) = self.track_session(&user, device).await {
+            warn!("Sign-in device of user {} was not tracked: {:?}", user.id, e);
+        }
+        Ok((user, tokens))
+    }
+    /// Records the device and, when the user has signed in from others but never from this one,
+    /// queues the new sign-in email unless their settings turn it off. The first device tracked
+    /// for an account is taken as known, so accounts don't get an alert for their first sign-in.
+    async fn track_session(&self, user: &UserResponse, device: &ClientDevice) -> Result<(), AppError> {
+        let app_state = &self.app_state;
+        let revoke_token = generate_random_string(32);
+        let sighting = app_state.db.sessions.record_session(NewUserSession {
+            user_id: user.id,
+            device,
+            revoke_token_hash: &self.token_hash(&revoke_token),
+        }).await?;
+        if !sighting.is_new || sighting.known_devices == 0 {
+            return Ok(());
+        }
+        let settings = app_state.db.users.get_user_settings(user.id).await?;
+        if !settings.allows_email(EmailKind::SignInAlert) {
+            return Ok(());
+        }
+        let mailer = OutboxMailer::new(app_state.db.emails.clone());
+        let signed_in_at = Utc::now().with_timezone(&settings.time_zone());
+        send_new_sign_in_email(&mailer, &user.email, &user.name, device, &revoke_token, signed_in_at).await
+            .map_err(|e| AppError::server_error(ErrorMessage::FailedSendEmail(e.to_string())))?;
+        let _ = app_state.db.audit_logs.save_audit_log(
+            NewAuditLog::new(None, user.id, AuditAction::NewSignInEmailSent)
+                .with_metadata(json!({ "ip_address": device.ip_address, "user_agent": device.user_agent }))
+        ).await;
+        Ok(())
+    }
+    /// Follows the revoke link of a new sign-in email: forgets the device and revokes the refresh
+    /// token, which signs the account out everywhere once the access tokens expire.
+    pub async fn revoke_session(&self, token: &str) -> Result<(), AppError> {
+        let session = self.app_state.db.sessions.revoke_session(&self.token_hash(token)).await?
+            .ok_or(AppError::bad_request(ErrorMessage::TokenKeyInvalid))?;
+        self.app_state.db.refresh_tokens.revoke_token(session.user_id).await?;
+        let _ = self.app_state.redis_client.delete_user(&session.user_id).await;
+        let _ = self.app_state.db.audit_logs.save_audit_log(
+            NewAuditLog::new(None, session.user_id, AuditAction::SessionRevoked)
+                .with_metadata(json!({ "ip_address": session.ip_address, "user_agent": session.user_agent }))
+        ).await;
+        Ok(())
+    }
+    /// Emails a password reset link. `None` when there is no active account to send it to and
+    /// `ACCOUNT_DISCLOSURE=conceal` keeps that from the caller.
+    pub async fn forgot_password(&self, email: &str) -> Result<Option<UserActionToken>, AppError> {
+        let started = Instant::now();
+        let result = self.send_password_reset(email).await;
+        self.hold_back(started).await;
+        result
+    }
+    async fn send_password_reset(&self, email: &str) -> Result<Option<UserActionToken>, AppError> {
+        email_rate_limit(&self.app_state, email, "forgot-password").await?;
+        let conceal = self.app_state.env.conceal_accounts;
+        let user = match self.user_by_email(email).await? {
+            Some(user) => user,
+            None if conceal => return Ok(None),
+            None => return Err(AppError::bad_request(ErrorMessage::DataNotFound)),
+        };
+        if !user.is_verified {
+            return if conceal { Ok(None) } else { Err(AppError::bad_request(ErrorMessage::AccountNotActive)) };
+        }
+        let verification_token = generate_random_string(32);
+        let expires_at = Utc::now() + Duration::hours(2);
+        let verification_token_hash = self.token_hash(&verification_token);
+        let new_user_action = NewUserActionToken {
+            token_hash: &verification_token_hash,
+            action_type: ActionType::ResetPassword,
+            expires_at,
+        };
+        let user_action_data = self.app_state.db.action_tokens.forgot_password(user.id, new_user_action).await?;
+        send_forgot_password_email(&*self.app_state.mailer, &user.email, &user.name, &verification_token).await
+            .map_err(|e| AppError::server_error(ErrorMessage::FailedSendEmail(e.to_string())))?;
+        let _ = self.app_state.db.audit_logs.save_audit_log(NewAuditLog::new(None, user.id, AuditAction::ResetPasswordEmailSent)).await;
+        Ok(Some(user_action_data))
+    }
+    pub async fn reset_password(&self, token: &str, new_password: &str) -> Result<UserResponse, AppError> {
+        let app_state = &self.app_state;
+        let user_action = self.valid_action_token(token, ActionType::ResetPassword).await?;
+        let hash_password = app_state.hasher.hash(new_password)
+            .map_err(AppError::server_error)?;
+        let user = app_state.db.action_tokens.reset_password(user_action.user_id, user_action.id, hash_password).await
+            .map_err(used_token_error)?;
+        let _ = app_state.redis_client.delete_user(&user.id).await;
+        let role_type = app_state.db.roles.get_role_name_by_id(user.role_id).await?
+            .ok_or(AppError::server_error(ErrorMessage::ServerError))?;
+        let _ = app_state.db.audit_logs.save_audit_log(NewAuditLog::new(None, user.id, AuditAction::PasswordReset)).await;
+        Ok(UserResponse::get_user_response(&user, role_type))
+    }
+    /// Issues `admin` a token acting as `user_id` for `IMPERSONATION_TOKEN_AGE` seconds. Other admins
+    /// can't be impersonated, so an impersonation token never carries admin permissions.
+    pub async fn impersonate(&self, admin: &User, user_id: Uuid) -> Result<ImpersonationResponse, AppError> {
+        let app_state = &self.app_state;
+        let user = app_state.db.users.get_user_by_id(&user_id).await?
+            .ok_or(AppError::not_found(ErrorMessage::DataNotFound))?;
+        let role_type = app_state.db.roles.get_role_name_by_id(user.role_id).await?
+            .ok_or(AppError::server_error(ErrorMessage::ServerError))?;
+        if role_type == RoleType::Admin {
+            return Err(AppError::forbidden(ErrorMessage::PermissionDenied));
+        }
+        if user.is_banned {
+            return Err(AppError::forbidden(ErrorMessage::AccountBanned));
+        }
+        let token_age = app_state.env.impersonation_token_age;
+        let expires_at = Utc::now() + Duration::seconds(token_age);
+        let access_token = jwt::create_impersonation_token(&admin.id.to_string(), &user.id.to_string(), &app_state.env.jwt_keys, token_age)
+            .map_err(|e| {
+                error!("Failed to create impersonation token: {}", e);
+                AppError::server_error(ErrorMessage::ServerError)
+            })?;
+        let audit_log = NewAuditLog::new(Some(admin.id), user.id, AuditAction::ImpersonationStarted)
+            .with_metadata(json!({ "expires_at": expires_at }));
+        let _ = app_state.db.audit_logs.save_audit_log(audit_log).await;
+        Ok(ImpersonationResponse {
+            access_token,
+            expires_at,
+            user: UserResponse::get_user_response(&user, role_type),
+        })
+    }
+    /// Creates a verified account with a random temporary password and queues the invite email
+    /// carrying it. `None` when the email already belongs to an account, which is left untouched.
+    pub async fn import_user(&self, admin: &User, record: UserImportRecord) -> Result<Option<UserResponse>, AppError> {
+        record.validate()?;
+        let app_state = &self.app_state;
+        let temporary_password = generate_random_string(16);
+        let hash_password = app_state.hasher.hash(&temporary_password)
+            .map_err(|_| AppError::server_error(ErrorMessage::ServerError))?;
+        let role_id = app_state.db.roles.get_role_id_by_name(RoleType::User).await?
+            .ok_or(AppError::bad_request(ErrorMessage::DataNotFound))?;
+        let user_data = NewUser {
+            role_id,
+            name: &record.name,
+            email: &record.email,
+            password: hash_password,
+            waitlisted: false,
+            invitation_id: None,
+        };
+        let Some(user) = app_state.db.users.import_user(user_data).await? else {
+            return Ok(None);
+        };
+        let _ = app_state.db.audit_logs.save_audit_log(NewAuditLog::new(Some(admin.id), user.id, AuditAction::UserImported)).await;
+        let mailer = OutboxMailer::new(app_state.db.emails.clone());
+        match send_invite_email(&mailer, &user.email, &user.name, &temporary_password).await {
+            Ok(()) => {
+                let _ = app_state.db.audit_logs.save_audit_log(NewAuditLog::new(Some(admin.id), user.id, AuditAction::InviteEmailSent)).await;
+            }
+            Err(e) => warn!("Invite email for imported user {} was not queued: {}", user.id, e),
+        }
+        let user_response = UserResponse::get_user_response(&user, RoleType::User);
+        app_state.publish(DomainEvent::UserRegistered { user: user_response.clone(), waitlist_position: None }).await;
+        Ok(Some(user_response))
+    }
+    /// Emails `email` a single-use sign-up link, valid for `INVITATION_TOKEN_AGE` seconds.
+    pub async fn invite(&self, admin: &User, email: &str) -> Result<Invitation, AppError> {
+        let app_state = &self.app_state;
+        if self.user_by_email(email).await?.is_some() {
+            return Err(AppError::unique_constraint_violation(ErrorMessage::EmailExist));
+        }
+        let token = generate_random_string(32);
+        let expires_at = Utc::now() + Duration::seconds(app_state.env.invitation_token_age);
+        let invitation = app_state.db.invitations.save_invitation(NewInvitation {
+            email,
+            token: &token,
+            invited_by: admin.id,
+            expires_at,
+        }).await?;
+        let mailer = OutboxMailer::new(app_state.db.emails.clone());
+        send_invitation_email(&mailer, email, &app_state.env.frontend_url, &token, expires_at).await
+            .map_err(|e| AppError::server_error(ErrorMessage::FailedSendEmail(e.to_string())))?;
+        Ok(invitation)
+    }
+    /// Trades a valid refresh token for a new access token and a new refresh token.
+    pub async fn refresh(&self, refresh_token: &str) -> Result<IssuedTokens, AppError> {
+        let refresh_token_data = self.app_state.db.refresh_tokens.get_refresh_token(refresh_token).await?
+            .ok_or(AppError::unauthorized(ErrorMessage::TokenInvalid))?;
+        if Utc::now() > refresh_token_data.expires_at || refresh_token_data.revoked {
+            return Err(AppError::unauthorized(ErrorMessage::TokenExpired));
+        }
+        let user_id = refresh_token_data.user_id;
+        let tokens = self.issue_tokens(user_id).await?;
+        let _ = self.app_state.db.audit_logs.save_audit_log(
+            NewAuditLog::new(Some(user_id), user_id, AuditAction::TokenRefreshed)
+        ).await;
+        Ok(tokens)
+    }
+    /// Trades an access token that expires within `JWT_REFRESH_GRACE` seconds, or expired at most
+    /// that long ago, for a new one, for clients that can't keep the refresh token cookie. Only a
+    /// live session qualifies: once its refresh token expires or the user signs out, this stops
+    /// working too.
+    pub async fn refresh_access_token(&self, access_token: &str) -> Result<IssuedTokens, AppError> {
+        let env = &self.app_state.env;
+        if env.jwt_refresh_grace == 0 {
+            return Err(AppError::unauthorized(ErrorMessage::TokenNotProvided));
+        }
+        let claims = jwt::parse_token(access_token, &env.jwt_keys, env.jwt_refresh_grace)?;
+        if claims.act_as.is_some() {
+            return Err(AppError::unauthorized(ErrorMessage::TokenInvalid));
+        }
+        if claims.exp as i64 > Utc::now().timestamp() + env.jwt_refresh_grace as i64 {
+            return Err(AppError::bad_request(ErrorMessage::TokenRefreshTooEarly(env.jwt_refresh_grace)));
+        }
+        let user_id = Uuid::parse_str(&claims.sub)
+            .map_err(|_| AppError::unauthorized(ErrorMessage::TokenInvalid))?;
+        let user = self.app_state.db.users.get_user_by_id(&user_id).await?
+            .ok_or(AppError::unauthorized(ErrorMessage::UserNoLongerExist))?;
+        if user.is_banned {
+            return Err(AppError::forbidden(ErrorMessage::AccountBanned));
+        }
+        let refresh_token_expires_at = self.app_state.db.refresh_tokens.get_session_expiry(user_id).await?
+            .ok_or(AppError::unauthorized(ErrorMessage::TokenExpired))?;
+        let (access_token, access_token_expires_at) = self.access_token(user_id)?;
+        let _ = self.app_state.db.audit_logs.save_audit_log(
+            NewAuditLog::new(Some(user_id), user_id, AuditAction::TokenRefreshed)
+        ).await;
+        Ok(IssuedTokens {
+            access_token,
+            access_token_expires_at,
+            refresh_token: None,
+            refresh_token_expires_at,
+        })
+    }
+    pub async fn sign_out(&self, user_id: Uuid) -> Result<(), AppError> {
+        self.app_state.db.refresh_tokens.revoke_token(user_id).await?;
+        let _ = self.app_state.redis_client.delete_user(&user_id).await;
+        let _ = self.app_state.db.audit_logs.save_audit_log(
+            NewAuditLog::new(Some(user_id), user_id, AuditAction::SignOut)
+        ).await;
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -1,15 +1,20 @@
 use std::sync::Arc;
-use axum::{response::IntoResponse, middleware, Router, routing::{delete, get, post, put}, Extension};
+use axum::{response::IntoResponse, middleware, Router, routing::{delete, get, post, put}, Extension, http::header::ETAG};
 use uuid::Uuid;
-use validator::Validate;
 use crate::{
     dto::{HttpResult, SuccessResponse},
     middleware::{AuthenticatedUser, permission::{check_permission, Permission}},
-    error::{PathParser, map_sqlx_error, BodyParser, FieldError, ErrorMessage, HttpError},
-    modules::comment::{
-        dto::{CommentRequest, NewComment},
-        model::CommentRepository,
+    error::{IfMatch, PathParser, ValidatedJson, ErrorMessage, AppError},
+    modules::{
+        comment::{
+            dto::{CommentRequest, NewComment},
+            model::{Comment, CommentDetail, CommentsByPost},
+        },
+        report::{dto::ReportTarget, handler::report_comment, model::ReportReason},
+        velocity::{model::VelocityKind, service::check_velocity},
+        event::domain_event::DomainEvent,
     },
+    utils::moderation,
     AppState
 };
 
@@ -30,67 +35,142 @@ pub fn comment_router() -> Router {
         .route("/{comment_id}/delete", delete(comment_delete).layer(middleware::from_fn(|state, req, next| {
             check_permission(state, req, next, Permission::CommentDelete.to_string())
         })))
+        .route("/{comment_id}/report", post(report_comment).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::ReportCreate.to_string())
+        })))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/comment/{post_id}",
+    tag = "comment",
+    params(("post_id" = Uuid, Path)),
+    request_body = CommentRequest,
+    responses(
+        (status = 200, description = "Created comment", body = SuccessResponse<Comment>),
+        (status = 404, description = "Post not found"),
+        (status = 422, description = "Rejected by the content filter, the reasons are attached per field"),
+        (status = 429, description = "Over the comment velocity limit, retry after `Retry-After` seconds"),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn comment_create(
     Extension(app_state): Extension<Arc<AppState>>,
     Extension(user_auth): Extension<AuthenticatedUser>,
     PathParser(post_id): PathParser<Uuid>,
-    BodyParser(body): BodyParser<CommentRequest>,
+    ValidatedJson(body): ValidatedJson<CommentRequest>,
 ) -> HttpResult<impl IntoResponse> {
-    body.validate().map_err(FieldError::populate_errors)?;
+    let flagged = moderation::moderate(&app_state, &[("content", &body.content)]).await?;
+    let too_fast = check_velocity(&app_state, &user_auth.user, VelocityKind::Comment).await?;
     let new_comment = NewComment {
         user_id: user_auth.user.id,
         post_id,
         content: body.content,
     };
-    let result = app_state.db_client.save_comment(post_id, new_comment).await.map_err(map_sqlx_error)?;
+    let result = app_state.db.comments.save_comment(post_id, new_comment).await?;
+    moderation::report_flagged(&app_state, ReportTarget::Comment(result.id), ReportReason::Other, flagged).await;
+    moderation::report_flagged(&app_state, ReportTarget::Comment(result.id), ReportReason::Spam, too_fast).await;
+    let _ = app_state.redis_client.delete_post_detail(&post_id).await;
+    app_state.publish(DomainEvent::CommentCreated { comment: result.clone() }).await;
     Ok(
         SuccessResponse::new("Successfully created a new comment.", Some(result))
     )
 }
+#[utoipa::path(
+    get,
+    path = "/api/comment/{post_id}/{comment_id}",
+    tag = "comment",
+    params(("post_id" = Uuid, Path), ("comment_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Comment with its post", body = SuccessResponse<CommentDetail>),
+        (status = 404, description = "Comment not found"),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn comment_detail(
     Extension(app_state): Extension<Arc<AppState>>,
     PathParser((post_id, comment_id)): PathParser<(Uuid, Uuid)>,
 ) -> HttpResult<impl IntoResponse> {
-    let comment_detail = app_state.db_client.get_comment_detail(post_id, comment_id).await
-        .map_err(map_sqlx_error)?
-        .ok_or(HttpError::not_found(ErrorMessage::DataNotFound.to_string(), None))?;
+    let comment_detail = app_state.db.comments.get_comment_detail(post_id, comment_id).await?
+        .ok_or(AppError::not_found(ErrorMessage::DataNotFound))?;
     Ok(
         SuccessResponse::new("Getting comment detail data", Some(comment_detail))
     )
 }
+#[utoipa::path(
+    get,
+    path = "/api/comment/{post_id}",
+    tag = "comment",
+    params(("post_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Post with its comments", body = SuccessResponse<CommentsByPost>),
+        (status = 404, description = "Post not found"),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn comment_list_by_post(
     Extension(app_state): Extension<Arc<AppState>>,
     PathParser(post_id): PathParser<Uuid>,
 ) -> HttpResult<impl IntoResponse> {
-    let comments_by_post = app_state.db_client.get_comments_by_post(post_id).await.map_err(map_sqlx_error)?;
+    let comments_by_post = app_state.db.comments.get_comments_by_post(post_id).await?;
     Ok(
         SuccessResponse::new("Getting comments data by a post", Some(comments_by_post))
     )
 }
+#[utoipa::path(
+    put,
+    path = "/api/comment/{comment_id}/update",
+    tag = "comment",
+    params(("comment_id" = Uuid, Path), ("If-Match" = Option<String>, Header, description = "`updated_at` of the edited version")),
+    request_body = CommentRequest,
+    responses(
+        (status = 200, description = "Updated comment", body = SuccessResponse<Comment>),
+        (status = 403, description = "Not the author of the comment"),
+        (status = 409, description = "The comment changed since the If-Match version, the current one is attached"),
+        (status = 422, description = "Rejected by the content filter, the reasons are attached per field"),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn comment_update(
     Extension(app_state): Extension<Arc<AppState>>,
     Extension(user_auth): Extension<AuthenticatedUser>,
     PathParser(comment_id): PathParser<Uuid>,
-    BodyParser(body): BodyParser<CommentRequest>,
+    IfMatch(expected_version): IfMatch,
+    ValidatedJson(body): ValidatedJson<CommentRequest>,
 ) -> HttpResult<impl IntoResponse> {
-    body.validate().map_err(FieldError::populate_errors)?;
-    let updated_comment = app_state.db_client.update_comment(
-        comment_id, user_auth.user.id, user_auth.user.role_id, body.content
-    ).await.map_err(map_sqlx_error)?;
-    Ok(
-        SuccessResponse::new("Successfully updated comment data.", Some(updated_comment))
-    )
+    let flagged = moderation::moderate(&app_state, &[("content", &body.content)]).await?;
+    let updated_comment = app_state.db.comments.update_comment(
+        comment_id, user_auth.user.id, user_auth.user.role_id, body.content, expected_version
+    ).await?;
+    moderation::report_flagged(&app_state, ReportTarget::Comment(comment_id), ReportReason::Other, flagged).await;
+    let _ = app_state.redis_client.delete_post_detail(&updated_comment.post_id).await;
+    Ok((
+        [(ETAG, IfMatch::etag(updated_comment.updated_at))],
+        SuccessResponse::new("Successfully updated comment data.", Some(updated_comment)),
+    ))
 }
+#[utoipa::path(
+    delete,
+    path = "/api/comment/{comment_id}/delete",
+    tag = "comment",
+    params(("comment_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Comment deleted"),
+        (status = 403, description = "Not the author of the comment"),
+        (status = 404, description = "Comment not found or already deleted"),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn comment_delete(
     Extension(app_state): Extension<Arc<AppState>>,
     Extension(user_auth): Extension<AuthenticatedUser>,
     PathParser(comment_id): PathParser<Uuid>,
 ) -> HttpResult<impl IntoResponse> {
-    app_state.db_client.delete_comment(
-        comment_id, user_auth.user.id, user_auth.user.role_id
-    ).await.map_err(map_sqlx_error)?;
+    let post_author_id = app_state.db.comments.delete_comment(
+        comment_id, user_auth.user.id, user_auth.user.role_id, app_state.env.comment_soft_delete
+    ).await?;
+    // Cached feed pages carry the post's comment count.
+    app_state.post_service().invalidate_feeds(post_author_id).await;
     Ok(
         SuccessResponse::<()>::new("Successfully deleted a comment.", None)
     )
@@ -1,18 +1,19 @@
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use crate::{
     db::DBClient,
     modules::{
-        comment::dto::NewComment, post::model::Post,
+        comment::dto::NewComment, post::model::{Post, PostLicense},
         role::model::{RoleRepository, RoleType},
     },
-    error::ErrorMessage,
+    error::RepositoryError,
 };
-use sqlx::{Error as SqlxError, query_as, query, FromRow, query_scalar};
+use sqlx::{query_as, query, FromRow, query_scalar};
 use uuid::Uuid;
 
-#[derive(Serialize, FromRow)]
+#[derive(Serialize, Deserialize, FromRow, ToSchema, Clone)]
 pub struct Comment {
     pub id: Uuid,
     pub user_id: Uuid,
@@ -21,7 +22,7 @@ pub struct Comment {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct CommentDetail {
     pub id: Uuid,
     pub user_id: Uuid,
@@ -32,31 +33,36 @@ pub struct CommentDetail {
     pub post: Post,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct CommentsByPost {
     pub post: Post,
     pub comments: Vec<Comment>,
 }
 
 #[async_trait]
-pub trait CommentRepository {
-    async fn save_comment(&self, post_id: Uuid, data: NewComment) -> Result<Comment, SqlxError>;
-    async fn get_comment_detail(&self, post_id: Uuid, comment_id: Uuid) -> Result<Option<CommentDetail>, SqlxError>;
-    async fn get_comments_by_post(&self, post_id: Uuid) -> Result<CommentsByPost, SqlxError>;
-    async fn update_comment(&self, comment_id: Uuid, user_id: Uuid, user_role_id: Uuid, content: String) -> Result<Comment, SqlxError>;
-    async fn delete_comment(&self, comment_id: Uuid, user_id: Uuid, user_role_id: Uuid) -> Result<(), SqlxError>;
+pub trait CommentRepository: Send + Sync {
+    async fn save_comment(&self, post_id: Uuid, data: NewComment) -> Result<Comment, RepositoryError>;
+    async fn get_comment_detail(&self, post_id: Uuid, comment_id: Uuid) -> Result<Option<CommentDetail>, RepositoryError>;
+    async fn get_comments_by_post(&self, post_id: Uuid) -> Result<CommentsByPost, RepositoryError>;
+    /// `expected_version` is the `updated_at` the caller edited, a different stored one fails with `VersionMismatch`.
+    async fn update_comment(&self, comment_id: Uuid, user_id: Uuid, user_role_id: Uuid, content: String, expected_version: Option<DateTime<Utc>>) -> Result<Comment, RepositoryError>;
+    /// Deletes the comment for its author or an admin and returns the id of the post's author.
+    /// `soft` keeps the row hidden with `deleted_at` set, comments with open reports are always
+    /// kept that way so the reports stay resolvable. A hard delete cascades to the comment's reports.
+    async fn delete_comment(&self, comment_id: Uuid, user_id: Uuid, user_role_id: Uuid, soft: bool) -> Result<Uuid, RepositoryError>;
 }
 
 #[async_trait]
 impl CommentRepository for DBClient {
-    async fn save_comment(&self, post_id: Uuid, data: NewComment) -> Result<Comment, SqlxError> {
+    async fn save_comment(&self, post_id: Uuid, data: NewComment) -> Result<Comment, RepositoryError> {
+        let _timer = self.time_query("comment.save_comment");
         let mut transaction = self.pool.begin().await?;
         query_scalar!(
             r#"
-                SELECT id FROM posts WHERE id = $1 FOR UPDATE;
+                SELECT id FROM posts WHERE id = $1 AND is_hidden = false FOR UPDATE;
             "#,
             post_id,
-        ).fetch_optional(&mut *transaction).await?.ok_or(SqlxError::RowNotFound)?;
+        ).fetch_optional(&mut *transaction).await?.ok_or(RepositoryError::NotFound)?;
         let new_comment = query_as!(
             Comment,
             r#"
@@ -71,14 +77,15 @@ impl CommentRepository for DBClient {
         transaction.commit().await?;
         Ok(new_comment)
     }
-    async fn get_comment_detail(&self, post_id: Uuid, comment_id: Uuid) -> Result<Option<CommentDetail>, SqlxError> {
+    async fn get_comment_detail(&self, post_id: Uuid, comment_id: Uuid) -> Result<Option<CommentDetail>, RepositoryError> {
+        let _timer = self.time_query("comment.get_comment_detail");
         let data = query!(
             r#"
                 SELECT c.id AS c_id, c.user_id AS c_user_id, c.post_id AS c_post_id, c.content AS c_content, c.created_at AS c_created_at, c.updated_at AS c_updated_at,
-                       p.id AS p_id, p.user_id AS p_user_id, p.title AS p_title, p.content AS p_content, p.tags AS p_tags, p.created_at AS p_created_at, p.updated_at AS p_updated_at
+                       p.id AS p_id, p.user_id AS p_user_id, p.organization_id AS p_organization_id, p.title AS p_title, p.content AS p_content, p.tags AS p_tags, p.license AS "p_license: PostLicense", p.views_count AS p_views_count, p.created_at AS p_created_at, p.updated_at AS p_updated_at
                 FROM comments AS c
                 JOIN posts AS p ON p.id = c.post_id
-                WHERE c.id = $1 AND c.post_id = $2
+                WHERE c.id = $1 AND c.post_id = $2 AND c.is_hidden = false AND p.is_hidden = false
             "#,
             comment_id,
             post_id,
@@ -96,28 +103,34 @@ impl CommentRepository for DBClient {
             post: Post {
                 id: data.p_id,
                 user_id: data.p_user_id,
+                organization_id: data.p_organization_id,
                 title: data.p_title,
                 content: data.p_content,
                 tags: data.p_tags,
+                license: data.p_license,
+                views_count: data.p_views_count,
                 created_at: data.p_created_at,
                 updated_at: data.p_updated_at,
             }
         };
         Ok(Some(comment_detail))
     }
-    async fn get_comments_by_post(&self, post_id: Uuid) -> Result<CommentsByPost, SqlxError> {
+    async fn get_comments_by_post(&self, post_id: Uuid) -> Result<CommentsByPost, RepositoryError> {
+        let _timer = self.time_query("comment.get_comments_by_post");
         let mut transaction = self.pool.begin().await?;
         let post = query_as!(
             Post,
             r#"
-                SELECT * FROM posts WHERE id = $1;
+                SELECT id, user_id, organization_id, title, content, tags, license AS "license: PostLicense", views_count, created_at, updated_at FROM posts
+                WHERE id = $1 AND is_hidden = false;
             "#,
             post_id,
-        ).fetch_optional(&mut *transaction).await?.ok_or(SqlxError::RowNotFound)?;
+        ).fetch_optional(&mut *transaction).await?.ok_or(RepositoryError::NotFound)?;
         let comments = query_as!(
             Comment,
             r#"
-                SELECT * FROM comments WHERE post_id = $1;
+                SELECT id, user_id, post_id, content, created_at, updated_at FROM comments
+                WHERE post_id = $1 AND is_hidden = false;
             "#,
             post_id,
         ).fetch_all(&mut *transaction).await?;
@@ -128,17 +141,23 @@ impl CommentRepository for DBClient {
         transaction.commit().await?;
         Ok(result)
     }
-    async fn update_comment(&self, comment_id: Uuid, user_id: Uuid, user_role_id: Uuid, content: String) -> Result<Comment, SqlxError> {
+    async fn update_comment(&self, comment_id: Uuid, user_id: Uuid, user_role_id: Uuid, content: String, expected_version: Option<DateTime<Utc>>) -> Result<Comment, RepositoryError> {
+        let _timer = self.time_query("comment.update_comment");
         let mut transaction = self.pool.begin().await?;
-        let comment_user_id = query_scalar!(
+        let current = query_as!(
+            Comment,
             r#"
-                SELECT user_id FROM comments WHERE id = $1 FOR UPDATE;
+                SELECT id, user_id, post_id, content, created_at, updated_at
+                FROM comments WHERE id = $1 AND deleted_at IS NULL FOR UPDATE;
             "#,
             comment_id,
-        ).fetch_optional(&mut *transaction).await?.ok_or(SqlxError::RowNotFound)?;
-        let role = self.get_role_name_by_id(user_role_id).await?.ok_or(SqlxError::RowNotFound)?;
-        if comment_user_id != user_id && role.get_value() != RoleType::Admin.get_value() {
-            return Err(SqlxError::InvalidArgument(ErrorMessage::PermissionDenied.to_string()));
+        ).fetch_optional(&mut *transaction).await?.ok_or(RepositoryError::NotFound)?;
+        let role = self.get_role_name_by_id(user_role_id).await?.ok_or(RepositoryError::NotFound)?;
+        if current.user_id != user_id && role.get_value() != RoleType::Admin.get_value() {
+            return Err(RepositoryError::Forbidden);
+        }
+        if expected_version.is_some_and(|version| version != current.updated_at) {
+            return Err(RepositoryError::version_mismatch(&current));
         }
         let comment = query_as!(
             Comment,
@@ -154,25 +173,40 @@ impl CommentRepository for DBClient {
         transaction.commit().await?;
         Ok(comment)
     }
-    async fn delete_comment(&self, comment_id: Uuid, user_id: Uuid, user_role_id: Uuid) -> Result<(), SqlxError> {
+    async fn delete_comment(&self, comment_id: Uuid, user_id: Uuid, user_role_id: Uuid, soft: bool) -> Result<Uuid, RepositoryError> {
+        let _timer = self.time_query("comment.delete_comment");
         let mut transaction = self.pool.begin().await?;
-        let comment_user_id = query_scalar!(
+        let comment = query!(
             r#"
-                SELECT user_id FROM comments WHERE id = $1 FOR UPDATE;
+                SELECT c.user_id, p.user_id AS post_user_id,
+                       EXISTS (SELECT 1 FROM reports AS r WHERE r.comment_id = c.id AND r.status = 'open') AS "has_open_reports!"
+                FROM comments AS c
+                JOIN posts AS p ON p.id = c.post_id
+                WHERE c.id = $1 AND c.deleted_at IS NULL
+                FOR UPDATE OF c;
             "#,
             comment_id,
-        ).fetch_optional(&mut *transaction).await?.ok_or(SqlxError::RowNotFound)?;
-        let role = self.get_role_name_by_id(user_role_id).await?.ok_or(SqlxError::RowNotFound)?;
-        if comment_user_id != user_id && role.get_value() != RoleType::Admin.get_value() {
-            return Err(SqlxError::InvalidArgument(ErrorMessage::PermissionDenied.to_string()));
+        ).fetch_optional(&mut *transaction).await?.ok_or(RepositoryError::NotFound)?;
+        let role = self.get_role_name_by_id(user_role_id).await?.ok_or(RepositoryError::NotFound)?;
+        if comment.user_id != user_id && role.get_value() != RoleType::Admin.get_value() {
+            return Err(RepositoryError::Forbidden);
+        }
+        if soft || comment.has_open_reports {
+            query!(
+                r#"
+                    UPDATE comments SET is_hidden = true, deleted_at = NOW(), updated_at = NOW() WHERE id = $1;
+                "#,
+                comment_id,
+            ).execute(&mut *transaction).await?;
+        } else {
+            query!(
+                r#"
+                    DELETE FROM comments WHERE id = $1;
+                "#,
+                comment_id,
+            ).execute(&mut *transaction).await?;
         }
-        query!(
-            r#"
-                DELETE FROM comments WHERE id = $1;
-            "#,
-            comment_id,
-        ).execute(&mut *transaction).await?;
         transaction.commit().await?;
-        Ok(())
+        Ok(comment.post_user_id)
     }
 }
\ No newline at end of file
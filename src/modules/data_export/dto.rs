@@ -0,0 +1,75 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
+use crate::modules::data_export::model::{DataExportStatus, ExportFormat, ExportTable};
+
+fn validate_export_tables(value: &[String]) -> Result<(), ValidationError> {
+    if value.iter().all(|table| ExportTable::from_value(table).is_some()) {
+        return Ok(());
+    }
+    let mut error = ValidationError::new("invalid_table");
+    error.message = Some("Tables must be ones the export supports".into());
+    Err(error)
+}
+
+/// Body of `POST /api/admin/exports`. No tables, or none given, exports every table there is.
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
+pub struct DataExportRequest {
+    #[validate(custom(function = "validate_export_tables"))]
+    #[serde(default)]
+    pub tables: Vec<String>,
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+pub struct NewDataExport {
+    /// In `ExportTable` order, without duplicates.
+    pub tables: Vec<ExportTable>,
+    pub format: ExportFormat,
+    /// Only the rows of this organization, see `ExportTable::organization_filter`.
+    pub organization_id: Option<Uuid>,
+    pub requested_by: Uuid,
+}
+
+/// A written table and the link it downloads from.
+#[derive(Serialize, ToSchema)]
+pub struct DataExportFileResponse {
+    pub table: ExportTable,
+    pub rows: i64,
+    pub bytes: i64,
+    /// Presigned by the bucket, or signed for the API with local storage. Only once the export
+    /// has completed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DataExportResponse {
+    pub id: Uuid,
+    pub tables: Vec<String>,
+    pub format: ExportFormat,
+    /// Set for the export of a single organization.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub organization_id: Option<Uuid>,
+    pub status: DataExportStatus,
+    /// Percent of the rows written, 100 once completed.
+    pub progress: u8,
+    pub rows_total: Option<i64>,
+    pub rows_exported: i64,
+    pub bytes_exported: i64,
+    pub files: Vec<DataExportFileResponse>,
+    /// `manifest.json`, listing every file with its row count and size.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manifest_url: Option<String>,
+    /// When the links stop working, ask again for new ones.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub links_expire_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub requested_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
\ No newline at end of file
@@ -0,0 +1,99 @@
+use std::{io, sync::Arc};
+use axum::{
+    middleware, Router, routing::get, Extension, response::IntoResponse,
+    body::{Body, Bytes},
+    http::{StatusCode, header::{CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_TYPE}},
+};
+use futures_util::stream;
+use tokio::{fs::File, io::AsyncReadExt};
+use uuid::Uuid;
+use crate::{
+    AppState,
+    dto::{HttpResult, SuccessResponse},
+    error::{AppError, ErrorMessage, PathParser, ValidatedJson},
+    middleware::{AuthenticatedUser, permission::{check_permission, Permission}},
+    modules::data_export::dto::DataExportRequest,
+    utils::signing::{DataExportDownload, SignedUrl},
+};
+
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+pub fn data_export_admin_router() -> Router {
+    Router::new()
+        .route("/exports", get(data_export_list).post(data_export_request).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminDataExport.to_string())
+        })))
+        .route("/exports/{id}", get(data_export_get).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminDataExport.to_string())
+        })))
+}
+
+/// The files of an export in local storage, behind the links `GET /api/admin/exports/{id}` signs.
+pub fn data_export_public_router() -> Router {
+    Router::new()
+        .route("/exports/{id}/files/{file}", get(data_export_download))
+}
+
+/// Accepted right away, the worker writes the export in the background.
+async fn data_export_request(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    ValidatedJson(body): ValidatedJson<DataExportRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let export = app_state.data_export_service().request(&user_auth.user, body).await?;
+    Ok((
+        StatusCode::ACCEPTED,
+        SuccessResponse::new("Data export is queued.", Some(export))
+    ))
+}
+async fn data_export_list(
+    Extension(app_state): Extension<Arc<AppState>>,
+) -> HttpResult<impl IntoResponse> {
+    let exports = app_state.data_export_service().all().await?;
+    Ok(
+        SuccessResponse::new("Getting data export list data", Some(exports))
+    )
+}
+/// Progress while it runs, download links valid for `SIGNED_URL_AGE` seconds once completed.
+async fn data_export_get(
+    Extension(app_state): Extension<Arc<AppState>>,
+    PathParser(id): PathParser<Uuid>,
+) -> HttpResult<impl IntoResponse> {
+    let export = app_state.data_export_service().get(id).await?;
+    Ok(
+        SuccessResponse::new("Getting data export data", Some(export))
+    )
+}
+async fn data_export_download(
+    _: SignedUrl<DataExportDownload>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    PathParser((id, file)): PathParser<(Uuid, String)>,
+) -> HttpResult<impl IntoResponse> {
+    let (path, content_type) = app_state.data_export_service().local_file(id, &file).await?;
+    let reader = File::open(&path).await
+        .map_err(|_| AppError::not_found(ErrorMessage::DataNotFound))?;
+    let length = reader.metadata().await
+        .map_err(|_| AppError::not_found(ErrorMessage::DataNotFound))?
+        .len();
+    // The file is read a chunk at a time, a read error ends the body early.
+    let body = Body::from_stream(stream::unfold(Some(reader), |reader| async move {
+        let mut reader = reader?;
+        let mut buffer = vec![0; READ_CHUNK_SIZE];
+        match reader.read(&mut buffer).await {
+            Ok(0) => None,
+            Ok(read) => {
+                buffer.truncate(read);
+                Some((Ok(Bytes::from(buffer)), Some(reader)))
+            }
+            Err(e) => Some((Err::<Bytes, io::Error>(e), None)),
+        }
+    }));
+    Ok((
+        [
+            (CONTENT_TYPE, content_type.to_string()),
+            (CONTENT_LENGTH, length.to_string()),
+            (CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", file)),
+        ],
+        body,
+    ))
+}
\ No newline at end of file
@@ -0,0 +1,5 @@
+pub mod dto;
+pub mod model;
+pub mod service;
+pub mod handler;
+pub mod storage;
\ No newline at end of file
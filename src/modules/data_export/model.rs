@@ -0,0 +1,392 @@
+use async_stream::try_stream;
+use async_trait::async_trait;
+use axum::body::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Type, query, query_as, query_scalar, types::Json};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use crate::{db::{DBClient, RowStream}, error::RepositoryError, modules::data_export::dto::NewDataExport};
+
+#[derive(Serialize, Deserialize, Type, Clone, Copy, PartialEq, Debug, ToSchema)]
+#[sqlx(type_name = "data_export_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum DataExportStatus {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// One `row_to_json` object per line.
+    #[default]
+    Ndjson,
+    /// `COPY ... WITH (FORMAT csv, HEADER)`, loadable again with `COPY ... FROM`.
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn get_value(&self) -> &'static str {
+        match self {
+            ExportFormat::Ndjson => "ndjson",
+            ExportFormat::Csv => "csv",
+        }
+    }
+    pub fn from_value(value: &str) -> Self {
+        match value {
+            "csv" => ExportFormat::Csv,
+            _ => ExportFormat::Ndjson,
+        }
+    }
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Ndjson => "application/x-ndjson",
+            ExportFormat::Csv => "text/csv",
+        }
+    }
+}
+
+/// The tables an export may include, in an order that loads back without breaking a foreign key.
+/// Tokens, sessions, the email outbox and webhook secrets are left out; `users` keeps the password
+/// hashes, so the files need the same care as the database. `invitations` are tokens too: each row
+/// holds the token as it was emailed, which signs someone up until it expires.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportTable {
+    Roles,
+    Permissions,
+    RolePermissions,
+    Users,
+    UserSettings,
+    UserFollowers,
+    FollowRequests,
+    Organizations,
+    OrganizationMembers,
+    OrganizationFollowers,
+    OrganizationInvitations,
+    OrganizationUsage,
+    Posts,
+    PostCollaborators,
+    PostViews,
+    Comments,
+    Reports,
+    AuditLogs,
+    AnalyticsEvents,
+    Announcements,
+    FeatureFlags,
+    VelocityLimits,
+    TosVersions,
+    UserTosAcceptance,
+    EmailDigests,
+    FeedSources,
+    FeedDrafts,
+}
+
+impl ExportTable {
+    pub const ALL: [ExportTable; 27] = [
+        ExportTable::Roles,
+        ExportTable::Permissions,
+        ExportTable::RolePermissions,
+        ExportTable::Users,
+        ExportTable::UserSettings,
+        ExportTable::UserFollowers,
+        ExportTable::FollowRequests,
+        ExportTable::Organizations,
+        ExportTable::OrganizationMembers,
+        ExportTable::OrganizationFollowers,
+        ExportTable::OrganizationInvitations,
+        ExportTable::OrganizationUsage,
+        ExportTable::Posts,
+        ExportTable::PostCollaborators,
+        ExportTable::PostViews,
+        ExportTable::Comments,
+        ExportTable::Reports,
+        ExportTable::AuditLogs,
+        ExportTable::AnalyticsEvents,
+        ExportTable::Announcements,
+        ExportTable::FeatureFlags,
+        ExportTable::VelocityLimits,
+        ExportTable::TosVersions,
+        ExportTable::UserTosAcceptance,
+        ExportTable::EmailDigests,
+        ExportTable::FeedSources,
+        ExportTable::FeedDrafts,
+    ];
+
+    /// The table's name, also its name in requests.
+    pub fn get_value(&self) -> &'static str {
+        match self {
+            ExportTable::Roles => "roles",
+            ExportTable::Permissions => "permissions",
+            ExportTable::RolePermissions => "role_permissions",
+            ExportTable::Users => "users",
+            ExportTable::UserSettings => "user_settings",
+            ExportTable::UserFollowers => "user_followers",
+            ExportTable::FollowRequests => "follow_requests",
+            ExportTable::Organizations => "organizations",
+            ExportTable::OrganizationMembers => "organization_members",
+            ExportTable::OrganizationFollowers => "organization_followers",
+            ExportTable::OrganizationInvitations => "organization_invitations",
+            ExportTable::OrganizationUsage => "organization_usage",
+            ExportTable::Posts => "posts",
+            ExportTable::PostCollaborators => "post_collaborators",
+            ExportTable::PostViews => "post_views",
+            ExportTable::Comments => "comments",
+            ExportTable::Reports => "reports",
+            ExportTable::AuditLogs => "audit_logs",
+            ExportTable::AnalyticsEvents => "analytics_events",
+            ExportTable::Announcements => "announcements",
+            ExportTable::FeatureFlags => "feature_flags",
+            ExportTable::VelocityLimits => "velocity_limits",
+            ExportTable::TosVersions => "tos_versions",
+            ExportTable::UserTosAcceptance => "user_tos_acceptance",
+            ExportTable::EmailDigests => "email_digests",
+            ExportTable::FeedSources => "feed_sources",
+            ExportTable::FeedDrafts => "feed_drafts",
+        }
+    }
+    pub fn from_value(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|table| table.get_value() == value)
+    }
+    /// The rows of the table belonging to the organization, as a `WHERE` condition. `None` for the
+    /// tables without any, which an organization's export leaves out.
+    pub fn organization_filter(&self, organization_id: Uuid) -> Option<String> {
+        match self {
+            ExportTable::Organizations => Some(format!("id = '{}'", organization_id)),
+            ExportTable::OrganizationMembers
+            | ExportTable::OrganizationFollowers
+            | ExportTable::OrganizationInvitations
+            | ExportTable::OrganizationUsage
+            | ExportTable::Posts => Some(format!("organization_id = '{}'", organization_id)),
+            ExportTable::PostCollaborators
+            | ExportTable::PostViews
+            | ExportTable::Comments => Some(format!("post_id IN (SELECT id FROM posts WHERE organization_id = '{}')", organization_id)),
+            _ => None,
+        }
+    }
+}
+
+/// A table written to the storage.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct DataExportFile {
+    pub table: ExportTable,
+    /// Where it is in the storage, `exports/{id}/{table}.{format}`.
+    pub key: String,
+    pub rows: i64,
+    pub bytes: i64,
+}
+
+#[derive(Serialize, Deserialize, FromRow, Clone)]
+pub struct DataExport {
+    pub id: Uuid,
+    /// Table names, in the order they are written.
+    pub tables: Vec<String>,
+    pub format: String,
+    pub organization_id: Option<Uuid>,
+    pub status: DataExportStatus,
+    pub files: Json<Vec<DataExportFile>>,
+    /// Rows in all the tables, counted once the export started.
+    pub rows_total: Option<i64>,
+    pub rows_exported: i64,
+    pub bytes_exported: i64,
+    pub error: Option<String>,
+    pub requested_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl DataExport {
+    pub fn format(&self) -> ExportFormat {
+        ExportFormat::from_value(&self.format)
+    }
+    pub fn tables(&self) -> Vec<ExportTable> {
+        self.tables.iter().filter_map(|table| ExportTable::from_value(table)).collect()
+    }
+}
+
+/// The progress an export reports while it runs, written with its heartbeat.
+pub struct DataExportProgress {
+    pub files: Vec<DataExportFile>,
+    pub rows_total: i64,
+    pub rows_exported: i64,
+    pub bytes_exported: i64,
+}
+
+/// What `dump_tables` streams: the rows in all the tables first, then each table with its row count
+/// followed by its data in chunks.
+pub enum DumpChunk {
+    Total(i64),
+    Table(ExportTable, i64),
+    Data(Bytes),
+}
+
+#[async_trait]
+pub trait DataExportRepository: Send + Sync {
+    async fn save_data_export(&self, data: NewDataExport) -> Result<DataExport, RepositoryError>;
+    async fn get_data_export(&self, id: Uuid) -> Result<Option<DataExport>, RepositoryError>;
+    /// The latest exports first, at most `limit`.
+    async fn get_data_exports(&self, limit: i64) -> Result<Vec<DataExport>, RepositoryError>;
+    /// Marks the oldest pending export as running and returns it. A running export whose heartbeat
+    /// is older than `stale_secs` is taken over too, its worker is gone, and starts over.
+    async fn claim_data_export(&self, stale_secs: i64) -> Result<Option<DataExport>, RepositoryError>;
+    async fn update_data_export_progress(&self, id: Uuid, progress: &DataExportProgress) -> Result<(), RepositoryError>;
+    /// `error` is `None` for a completed export.
+    async fn finish_data_export(&self, id: Uuid, error: Option<String>) -> Result<(), RepositoryError>;
+    /// Every table in one read-only snapshot, so the files agree with each other, only the rows of
+    /// `organization_id` when set. The stream holds a pool connection until it ends.
+    fn dump_tables(&self, tables: Vec<ExportTable>, format: ExportFormat, organization_id: Option<Uuid>) -> RowStream<DumpChunk>;
+}
+
+#[async_trait]
+impl DataExportRepository for DBClient {
+    async fn save_data_export(&self, data: NewDataExport) -> Result<DataExport, RepositoryError> {
+        let _timer = self.time_query("data_export.save_data_export");
+        let tables: Vec<String> = data.tables.iter().map(|table| table.get_value().to_string()).collect();
+        let export = query_as!(
+            DataExport,
+            r#"
+                INSERT INTO data_exports (tables, format, organization_id, requested_by)
+                VALUES ($1, $2, $3, $4)
+                RETURNING id, tables, format, organization_id, status AS "status: DataExportStatus", files AS "files: Json<Vec<DataExportFile>>",
+                    rows_total, rows_exported, bytes_exported, error, requested_by, created_at, started_at, finished_at, updated_at;
+            "#,
+            &tables,
+            data.format.get_value(),
+            data.organization_id,
+            data.requested_by,
+        ).fetch_one(&self.pool).await?;
+        Ok(export)
+    }
+    async fn get_data_export(&self, id: Uuid) -> Result<Option<DataExport>, RepositoryError> {
+        let _timer = self.time_query("data_export.get_data_export");
+        let export = query_as!(
+            DataExport,
+            r#"
+                SELECT id, tables, format, organization_id, status AS "status: DataExportStatus", files AS "files: Json<Vec<DataExportFile>>",
+                    rows_total, rows_exported, bytes_exported, error, requested_by, created_at, started_at, finished_at, updated_at
+                FROM data_exports
+                WHERE id = $1;
+            "#,
+            id,
+        ).fetch_optional(&self.pool).await?;
+        Ok(export)
+    }
+    async fn get_data_exports(&self, limit: i64) -> Result<Vec<DataExport>, RepositoryError> {
+        let _timer = self.time_query("data_export.get_data_exports");
+        let exports = query_as!(
+            DataExport,
+            r#"
+                SELECT id, tables, format, organization_id, status AS "status: DataExportStatus", files AS "files: Json<Vec<DataExportFile>>",
+                    rows_total, rows_exported, bytes_exported, error, requested_by, created_at, started_at, finished_at, updated_at
+                FROM data_exports
+                ORDER BY created_at DESC
+                LIMIT $1;
+            "#,
+            limit,
+        ).fetch_all(&self.pool).await?;
+        Ok(exports)
+    }
+    async fn claim_data_export(&self, stale_secs: i64) -> Result<Option<DataExport>, RepositoryError> {
+        let _timer = self.time_query("data_export.claim_data_export");
+        let export = query_as!(
+            DataExport,
+            r#"
+                UPDATE data_exports
+                SET status = 'running', files = '[]'::jsonb, rows_total = NULL, rows_exported = 0, bytes_exported = 0,
+                    started_at = NOW(), updated_at = NOW()
+                WHERE id = (
+                    SELECT id FROM data_exports
+                    WHERE status = 'pending' OR (status = 'running' AND updated_at < NOW() - make_interval(secs => $1))
+                    ORDER BY created_at
+                    LIMIT 1
+                    FOR UPDATE SKIP LOCKED
+                )
+                RETURNING id, tables, format, organization_id, status AS "status: DataExportStatus", files AS "files: Json<Vec<DataExportFile>>",
+                    rows_total, rows_exported, bytes_exported, error, requested_by, created_at, started_at, finished_at, updated_at;
+            "#,
+            stale_secs as f64,
+        ).fetch_optional(&self.pool).await?;
+        Ok(export)
+    }
+    async fn update_data_export_progress(&self, id: Uuid, progress: &DataExportProgress) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("data_export.update_data_export_progress");
+        query!(
+            r#"
+                UPDATE data_exports
+                SET files = $2, rows_total = $3, rows_exported = $4, bytes_exported = $5, updated_at = NOW()
+                WHERE id = $1;
+            "#,
+            id,
+            Json(&progress.files) as _,
+            progress.rows_total,
+            progress.rows_exported,
+            progress.bytes_exported,
+        ).execute(&self.pool).await?;
+        Ok(())
+    }
+    async fn finish_data_export(&self, id: Uuid, error: Option<String>) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("data_export.finish_data_export");
+        let status = if error.is_some() { DataExportStatus::Failed } else { DataExportStatus::Completed };
+        query!(
+            r#"
+                UPDATE data_exports
+                SET status = $2, error = $3, finished_at = NOW(), updated_at = NOW()
+                WHERE id = $1;
+            "#,
+            id,
+            status as DataExportStatus,
+            error,
+        ).execute(&self.pool).await?;
+        Ok(())
+    }
+    fn dump_tables(&self, tables: Vec<ExportTable>, format: ExportFormat, organization_id: Option<Uuid>) -> RowStream<DumpChunk> {
+        let (pool, timer) = (self.pool.clone(), self.time_query("data_export.dump_tables"));
+        Box::pin(try_stream! {
+            let _timer = timer;
+            let mut transaction = pool.begin().await?;
+            query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY").execute(&mut *transaction).await?;
+            // Table names only ever come from `ExportTable` and filters from a `Uuid`, never from
+            // the request.
+            let mut sources = Vec::with_capacity(tables.len());
+            for table in tables {
+                let source = match organization_id {
+                    None => table.get_value().to_string(),
+                    Some(organization_id) => match table.organization_filter(organization_id) {
+                        Some(filter) => format!("(SELECT * FROM {} WHERE {})", table.get_value(), filter),
+                        None => continue,
+                    },
+                };
+                let rows: i64 = query_scalar(&format!("SELECT COUNT(*) FROM {} AS t", source)).fetch_one(&mut *transaction).await?;
+                sources.push((table, source, rows));
+            }
+            yield DumpChunk::Total(sources.iter().map(|(_, _, rows)| rows).sum());
+            for (table, source, rows) in sources {
+                yield DumpChunk::Table(table, rows);
+                match format {
+                    ExportFormat::Csv => {
+                        let mut chunks = transaction.copy_out_raw(&format!("COPY {} TO STDOUT WITH (FORMAT csv, HEADER)", source)).await?;
+                        while let Some(chunk) = chunks.try_next().await? {
+                            yield DumpChunk::Data(chunk);
+                        }
+                    }
+                    ExportFormat::Ndjson => {
+                        let sql = format!("SELECT row_to_json(t)::text FROM {} AS t", source);
+                        let mut lines = query_scalar::<_, String>(&sql).fetch(&mut *transaction);
+                        while let Some(line) = lines.try_next().await? {
+                            let mut line = line.into_bytes();
+                            line.push(b'\n');
+                            yield DumpChunk::Data(Bytes::from(line));
+                        }
+                    }
+                }
+            }
+            transaction.commit().await?;
+        })
+    }
+}
\ No newline at end of file
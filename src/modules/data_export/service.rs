@@ -0,0 +1,223 @@
+use std::{path::PathBuf, sync::Arc, time::{Duration, Instant}};
+use chrono::{Duration as ChronoDuration, Utc};
+use futures_util::TryStreamExt;
+use serde_json::json;
+use uuid::Uuid;
+use crate::{
+    AppState,
+    error::{AppError, ErrorMessage},
+    modules::{
+        data_export::{
+            dto::{DataExportFileResponse, DataExportRequest, DataExportResponse, NewDataExport},
+            model::{DataExport, DataExportFile, DataExportProgress, DataExportStatus, DumpChunk, ExportFormat, ExportTable},
+            storage::{ExportStorage, ObjectWriter, StorageError},
+        },
+        user::model::User,
+    },
+    utils::{metrics, signing::{self, DataExportDownload, UrlScope}},
+};
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+/// How often a running export writes its progress, which doubles as its heartbeat.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+/// A running export without a heartbeat for this long lost its worker and is started over.
+pub const STALE_AFTER_SECS: i64 = 600;
+const LIST_LIMIT: i64 = 50;
+
+/// Dumps of whole tables to `EXPORT_STORAGE` for backups, requested by admins and written by
+/// `jobs::data_export`. A table is read from Postgres as a stream and written out part by part, so
+/// neither side holds it in memory.
+pub struct DataExportService {
+    app_state: Arc<AppState>,
+}
+
+impl DataExportService {
+    pub fn new(app_state: Arc<AppState>) -> Self {
+        Self { app_state }
+    }
+    fn storage(&self) -> ExportStorage {
+        ExportStorage::from_config(&self.app_state.env).expect("EXPORT_STORAGE is checked at startup")
+    }
+    /// Queues the export, the worker picks it up within `EXPORT_INTERVAL` seconds.
+    pub async fn request(&self, user: &User, body: DataExportRequest) -> Result<DataExportResponse, AppError> {
+        let mut tables: Vec<ExportTable> = body.tables.iter().filter_map(|table| ExportTable::from_value(table)).collect();
+        if tables.is_empty() {
+            tables = ExportTable::ALL.to_vec();
+        }
+        tables.sort();
+        tables.dedup();
+        let export = self.app_state.db.data_exports.save_data_export(NewDataExport {
+            tables,
+            format: body.format,
+            organization_id: None,
+            requested_by: user.id,
+        }).await?;
+        Ok(self.response(export))
+    }
+    /// Queues an export of every row belonging to the organization, like `request` otherwise.
+    pub async fn request_organization(&self, user: &User, organization_id: Uuid, format: ExportFormat) -> Result<DataExportResponse, AppError> {
+        let tables = ExportTable::ALL.into_iter()
+            .filter(|table| table.organization_filter(organization_id).is_some())
+            .collect();
+        let export = self.app_state.db.data_exports.save_data_export(NewDataExport {
+            tables,
+            format,
+            organization_id: Some(organization_id),
+            requested_by: user.id,
+        }).await?;
+        Ok(self.response(export))
+    }
+    pub async fn get(&self, id: Uuid) -> Result<DataExportResponse, AppError> {
+        let export = self.app_state.db.data_exports.get_data_export(id).await?
+            .ok_or(AppError::not_found(ErrorMessage::DataNotFound))?;
+        Ok(self.response(export))
+    }
+    pub async fn all(&self) -> Result<Vec<DataExportResponse>, AppError> {
+        let exports = self.app_state.db.data_exports.get_data_exports(LIST_LIMIT).await?;
+        Ok(exports.into_iter().map(|export| self.response(export)).collect())
+    }
+    /// Links are only handed out for completed exports, fresh ones with every call.
+    fn response(&self, export: DataExport) -> DataExportResponse {
+        let completed = export.status == DataExportStatus::Completed;
+        let age = self.app_state.env.signed_url_age;
+        let storage = self.storage();
+        let link = |key: &str| -> Option<String> {
+            if !completed {
+                return None;
+            }
+            storage.presigned_url(key, age as u64).or_else(|| {
+                let path = format!("/api/admin/exports/{}/files/{}", export.id, file_name(key));
+                Some(signing::sign_url(self.app_state.env.jwt_secret.as_bytes(), &path, DataExportDownload::SCOPE, age))
+            })
+        };
+        let progress = match (export.status, export.rows_total) {
+            (DataExportStatus::Completed, _) => 100,
+            (_, Some(total)) if total > 0 => (export.rows_exported * 100 / total).min(99) as u8,
+            _ => 0,
+        };
+        DataExportResponse {
+            id: export.id,
+            tables: export.tables.clone(),
+            format: export.format(),
+            organization_id: export.organization_id,
+            status: export.status,
+            progress,
+            rows_total: export.rows_total,
+            rows_exported: export.rows_exported,
+            bytes_exported: export.bytes_exported,
+            files: export.files.iter().map(|file| DataExportFileResponse {
+                table: file.table,
+                rows: file.rows,
+                bytes: file.bytes,
+                url: link(&file.key),
+            }).collect(),
+            manifest_url: link(&file_key(export.id, MANIFEST_FILE)),
+            links_expire_at: completed.then(|| Utc::now() + ChronoDuration::seconds(age)),
+            error: export.error.clone(),
+            requested_by: export.requested_by,
+            created_at: export.created_at,
+            started_at: export.started_at,
+            finished_at: export.finished_at,
+        }
+    }
+    /// Where `file` of a completed export is kept in local storage, with its content type. Not
+    /// found with a bucket, its links go to the bucket.
+    pub async fn local_file(&self, id: Uuid, file: &str) -> Result<(PathBuf, &'static str), AppError> {
+        let export = self.app_state.db.data_exports.get_data_export(id).await?
+            .filter(|export| export.status == DataExportStatus::Completed)
+            .ok_or(AppError::not_found(ErrorMessage::DataNotFound))?;
+        let key = if file == MANIFEST_FILE {
+            file_key(id, MANIFEST_FILE)
+        } else {
+            export.files.iter().find(|f| file_name(&f.key) == file)
+                .ok_or(AppError::not_found(ErrorMessage::DataNotFound))?
+                .key.clone()
+        };
+        let content_type = if file == MANIFEST_FILE { "application/json" } else { export.format().content_type() };
+        let path = self.storage().local_path(&key).ok_or(AppError::not_found(ErrorMessage::DataNotFound))?;
+        Ok((path, content_type))
+    }
+
+    /// Claims the next export and runs it to the end, `false` when there was none to claim.
+    pub async fn run_next(&self) -> Result<bool, AppError> {
+        let Some(export) = self.app_state.db.data_exports.claim_data_export(STALE_AFTER_SECS).await? else {
+            return Ok(false);
+        };
+        let started = Instant::now();
+        let result = self.run(&export).await;
+        let status = if result.is_ok() { "completed" } else { "failed" };
+        metrics::increment_counter("data_exports_total", &[("status", status)]);
+        metrics::observe_duration("data_export_duration_seconds", &[("status", status)], started.elapsed());
+        self.app_state.db.data_exports.finish_data_export(export.id, result.err().map(|e| e.to_string())).await?;
+        Ok(true)
+    }
+    /// Writes every table of `export` and then its manifest. An export that fails part way keeps
+    /// the files it finished, a retry writes over them.
+    async fn run(&self, export: &DataExport) -> Result<(), StorageError> {
+        let storage = self.storage();
+        let format = export.format();
+        let mut chunks = self.app_state.db.data_exports.dump_tables(export.tables(), format, export.organization_id);
+        let mut progress = DataExportProgress { files: Vec::new(), rows_total: 0, rows_exported: 0, bytes_exported: 0 };
+        let mut current: Option<(DataExportFile, ObjectWriter)> = None;
+        let mut heartbeat = Instant::now();
+        let result: Result<(), StorageError> = async {
+            while let Some(chunk) = chunks.try_next().await? {
+                match chunk {
+                    DumpChunk::Total(rows) => progress.rows_total = rows,
+                    DumpChunk::Table(table, rows) => {
+                        if let Some((file, writer)) = current.take() {
+                            self.finish_file(export.id, file, writer, &mut progress).await?;
+                            heartbeat = Instant::now();
+                        }
+                        let key = file_key(export.id, &format!("{}.{}", table.get_value(), format.get_value()));
+                        let writer = storage.create(&key, format.content_type()).await?;
+                        current = Some((DataExportFile { table, key, rows, bytes: 0 }, writer));
+                    }
+                    DumpChunk::Data(data) => {
+                        let Some((_, writer)) = current.as_mut() else { continue };
+                        writer.write(&data).await?;
+                        progress.bytes_exported += data.len() as i64;
+                        if heartbeat.elapsed() >= HEARTBEAT_INTERVAL {
+                            self.app_state.db.data_exports.update_data_export_progress(export.id, &progress).await?;
+                            heartbeat = Instant::now();
+                        }
+                    }
+                }
+            }
+            if let Some((file, writer)) = current.take() {
+                self.finish_file(export.id, file, writer, &mut progress).await?;
+            }
+            Ok(())
+        }.await;
+        if let Err(e) = result {
+            if let Some((_, writer)) = current {
+                writer.abort().await;
+            }
+            return Err(e);
+        }
+        let manifest = json!({
+            "id": export.id,
+            "format": format,
+            "organization_id": export.organization_id,
+            "created_at": export.created_at,
+            "files": &progress.files,
+        });
+        storage.put(&file_key(export.id, MANIFEST_FILE), "application/json", &serde_json::to_vec_pretty(&manifest)?).await?;
+        Ok(())
+    }
+    async fn finish_file(&self, id: Uuid, mut file: DataExportFile, writer: ObjectWriter, progress: &mut DataExportProgress) -> Result<(), StorageError> {
+        file.bytes = writer.finish().await? as i64;
+        metrics::increment_counter_by("data_export_rows_total", &[("table", file.table.get_value())], file.rows as u64);
+        progress.rows_exported += file.rows;
+        progress.files.push(file);
+        self.app_state.db.data_exports.update_data_export_progress(id, progress).await?;
+        Ok(())
+    }
+}
+
+fn file_key(id: Uuid, file: &str) -> String {
+    format!("exports/{}/{}", id, file)
+}
+fn file_name(key: &str) -> &str {
+    key.rsplit('/').next().unwrap_or(key)
+}
\ No newline at end of file
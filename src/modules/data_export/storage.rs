@@ -0,0 +1,264 @@
+use std::{error::Error, path::PathBuf, time::Duration};
+use chrono::Utc;
+use reqwest::{Method, Url, header::{CONTENT_TYPE, ETAG}};
+use tokio::{fs::{self, File}, io::AsyncWriteExt};
+use crate::{config::Config, utils::sigv4::{Credentials, sha256_hex, uri_encode}};
+
+pub type StorageError = Box<dyn Error + Send + Sync>;
+
+/// Bytes held before they are written out. Objects larger than a part go to S3 as a multipart
+/// upload, which wants at least 5 MiB in every part but the last.
+const PART_SIZE: usize = 8 * 1024 * 1024;
+const LOCAL_BUFFER_SIZE: usize = 256 * 1024;
+
+/// Where data exports are written, from `EXPORT_STORAGE`.
+pub enum ExportStorage {
+    /// Files under `EXPORT_STORAGE_PATH`, downloaded through the API with a signed link.
+    Local(PathBuf),
+    /// An S3 compatible bucket, downloaded straight from it with a presigned link.
+    S3(S3Bucket),
+}
+
+impl ExportStorage {
+    pub fn from_config(config: &Config) -> Option<Self> {
+        match config.export_storage.as_str() {
+            "s3" => {
+                let endpoint = Url::parse(config.export_s3_endpoint.as_deref()?).ok()
+                    .filter(|url| matches!(url.scheme(), "http" | "https") && url.host().is_some())?;
+                Some(ExportStorage::S3(S3Bucket::new(
+                    endpoint,
+                    config.export_s3_bucket.clone()?,
+                    config.export_s3_region.clone(),
+                    config.export_s3_access_key.clone()?,
+                    config.export_s3_secret_key.clone()?,
+                )))
+            }
+            _ => Some(ExportStorage::Local(PathBuf::from(&config.export_storage_path))),
+        }
+    }
+    pub fn get_value(&self) -> &str {
+        match self {
+            ExportStorage::Local(_) => "local",
+            ExportStorage::S3(_) => "s3",
+        }
+    }
+    /// Starts writing `key`. Nothing shows up under it until the writer is finished.
+    pub async fn create(&self, key: &str, content_type: &'static str) -> Result<ObjectWriter, StorageError> {
+        let target = match self {
+            ExportStorage::Local(root) => {
+                let path = root.join(key);
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).await?;
+                }
+                let partial = path.with_extension("partial");
+                Target::Local { file: File::create(&partial).await?, partial, path }
+            }
+            ExportStorage::S3(bucket) => Target::S3 {
+                bucket: bucket.clone(),
+                key: key.to_string(),
+                content_type,
+                upload_id: None,
+                etags: Vec::new(),
+            },
+        };
+        Ok(ObjectWriter { target, buffer: Vec::new(), bytes: 0 })
+    }
+    pub async fn put(&self, key: &str, content_type: &'static str, data: &[u8]) -> Result<(), StorageError> {
+        let mut writer = self.create(key, content_type).await?;
+        writer.write(data).await?;
+        writer.finish().await?;
+        Ok(())
+    }
+    /// A link that downloads `key` for `age` seconds. `None` for local storage, its files are
+    /// served by the API.
+    pub fn presigned_url(&self, key: &str, age: u64) -> Option<String> {
+        match self {
+            ExportStorage::Local(_) => None,
+            ExportStorage::S3(bucket) => Some(bucket.credentials().presign("GET", &bucket.object_url(key), age, Utc::now()).to_string()),
+        }
+    }
+    /// Where a local file is kept, `None` for a bucket.
+    pub fn local_path(&self, key: &str) -> Option<PathBuf> {
+        match self {
+            ExportStorage::Local(root) => Some(root.join(key)),
+            ExportStorage::S3(_) => None,
+        }
+    }
+}
+
+/// Writes one object in chunks, holding at most a part in memory.
+pub struct ObjectWriter {
+    target: Target,
+    buffer: Vec<u8>,
+    bytes: u64,
+}
+
+enum Target {
+    /// Written to `partial` and renamed to `path` once complete.
+    Local { file: File, partial: PathBuf, path: PathBuf },
+    /// A multipart upload, started with the first full part. Smaller objects are a single PUT.
+    S3 { bucket: S3Bucket, key: String, content_type: &'static str, upload_id: Option<String>, etags: Vec<String> },
+}
+
+impl ObjectWriter {
+    pub async fn write(&mut self, data: &[u8]) -> Result<(), StorageError> {
+        self.buffer.extend_from_slice(data);
+        self.bytes += data.len() as u64;
+        let limit = match self.target {
+            Target::Local { .. } => LOCAL_BUFFER_SIZE,
+            Target::S3 { .. } => PART_SIZE,
+        };
+        if self.buffer.len() >= limit {
+            let data = std::mem::take(&mut self.buffer);
+            self.target.write(data).await?;
+        }
+        Ok(())
+    }
+    /// Bytes written so far.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+    /// Writes what is left and makes the object visible, returns its size.
+    pub async fn finish(mut self) -> Result<u64, StorageError> {
+        let data = std::mem::take(&mut self.buffer);
+        match &mut self.target {
+            Target::Local { file, partial, path } => {
+                file.write_all(&data).await?;
+                file.sync_all().await?;
+                fs::rename(partial, path).await?;
+            }
+            Target::S3 { bucket, key, content_type, upload_id: None, .. } => bucket.put_object(key, content_type, data).await?,
+            Target::S3 { bucket, key, upload_id: Some(upload_id), etags, .. } => {
+                if !data.is_empty() {
+                    etags.push(bucket.upload_part(key, upload_id, etags.len() + 1, data).await?);
+                }
+                bucket.complete_multipart(key, upload_id, etags).await?;
+            }
+        }
+        Ok(self.bytes)
+    }
+    /// Drops what was written, best effort: a bucket may keep the parts of an upload it fails to
+    /// abort until its lifecycle rules clean them up.
+    pub async fn abort(self) {
+        match self.target {
+            Target::Local { partial, .. } => {
+                let _ = fs::remove_file(partial).await;
+            }
+            Target::S3 { bucket, key, upload_id: Some(upload_id), .. } => {
+                let _ = bucket.abort_multipart(&key, &upload_id).await;
+            }
+            Target::S3 { upload_id: None, .. } => {}
+        }
+    }
+}
+
+impl Target {
+    async fn write(&mut self, data: Vec<u8>) -> Result<(), StorageError> {
+        match self {
+            Target::Local { file, .. } => file.write_all(&data).await?,
+            Target::S3 { bucket, key, content_type, upload_id, etags } => {
+                let upload_id = match upload_id {
+                    Some(upload_id) => upload_id,
+                    None => upload_id.insert(bucket.start_multipart(key, content_type).await?),
+                };
+                etags.push(bucket.upload_part(key, upload_id, etags.len() + 1, data).await?);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A bucket addressed path-style, `{endpoint}/{bucket}/{key}`, which every S3 compatible store
+/// accepts.
+#[derive(Clone)]
+pub struct S3Bucket {
+    endpoint: Url,
+    bucket: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+}
+
+impl S3Bucket {
+    pub fn new(endpoint: Url, bucket: String, region: String, access_key: String, secret_key: String) -> Self {
+        Self { endpoint, bucket, region, access_key, secret_key }
+    }
+    fn credentials(&self) -> Credentials<'_> {
+        Credentials { access_key: &self.access_key, secret_key: &self.secret_key, region: &self.region, service: "s3" }
+    }
+    fn object_url(&self, key: &str) -> Url {
+        let mut url = self.endpoint.clone();
+        let prefix = self.endpoint.path().trim_end_matches('/').to_string();
+        url.set_path(&format!("{}/{}/{}", prefix, uri_encode(&self.bucket, false), uri_encode(key, true)));
+        url
+    }
+    async fn send(&self, method: Method, url: Url, body: Vec<u8>, content_type: Option<&str>) -> Result<reqwest::Response, StorageError> {
+        let headers = self.credentials().sign_headers(method.as_str(), &url, &sha256_hex(&body), Utc::now());
+        let mut request = reqwest::Client::new()
+            .request(method, url)
+            .timeout(Duration::from_secs(120))
+            .body(body);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        if let Some(content_type) = content_type {
+            request = request.header(CONTENT_TYPE, content_type);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!("bucket answered {}: {}", status, body.chars().take(500).collect::<String>()).into());
+        }
+        Ok(response)
+    }
+    pub async fn put_object(&self, key: &str, content_type: &str, data: Vec<u8>) -> Result<(), StorageError> {
+        self.send(Method::PUT, self.object_url(key), data, Some(content_type)).await?;
+        Ok(())
+    }
+    async fn start_multipart(&self, key: &str, content_type: &str) -> Result<String, StorageError> {
+        let mut url = self.object_url(key);
+        url.set_query(Some("uploads"));
+        let body = self.send(Method::POST, url, Vec::new(), Some(content_type)).await?.text().await?;
+        xml_value(&body, "UploadId").ok_or_else(|| "no UploadId in the bucket's answer".into())
+    }
+    async fn upload_part(&self, key: &str, upload_id: &str, part_number: usize, data: Vec<u8>) -> Result<String, StorageError> {
+        let mut url = self.object_url(key);
+        url.query_pairs_mut()
+            .append_pair("partNumber", &part_number.to_string())
+            .append_pair("uploadId", upload_id);
+        let response = self.send(Method::PUT, url, data, None).await?;
+        response.headers().get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(String::from)
+            .ok_or_else(|| "no ETag for an uploaded part".into())
+    }
+    async fn complete_multipart(&self, key: &str, upload_id: &str, etags: &[String]) -> Result<(), StorageError> {
+        let mut url = self.object_url(key);
+        url.query_pairs_mut().append_pair("uploadId", upload_id);
+        let parts: String = etags.iter().enumerate()
+            .map(|(index, etag)| format!("<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>", index + 1, etag))
+            .collect();
+        let body = format!("<CompleteMultipartUpload>{}</CompleteMultipartUpload>", parts);
+        // S3 may answer 200 and still report a failure in the body.
+        let answer = self.send(Method::POST, url, body.into_bytes(), Some("application/xml")).await?.text().await?;
+        if answer.contains("<Error>") {
+            return Err(format!("bucket failed to complete the upload: {}", answer.chars().take(500).collect::<String>()).into());
+        }
+        Ok(())
+    }
+    async fn abort_multipart(&self, key: &str, upload_id: &str) -> Result<(), StorageError> {
+        let mut url = self.object_url(key);
+        url.query_pairs_mut().append_pair("uploadId", upload_id);
+        self.send(Method::DELETE, url, Vec::new(), None).await?;
+        Ok(())
+    }
+}
+
+/// The text of the first `<tag>` element, enough for the few fields read from S3's XML answers.
+fn xml_value(body: &str, tag: &str) -> Option<String> {
+    let open = format!("<{}>", tag);
+    let start = body.find(&open)? + open.len();
+    let end = start + body[start..].find(&format!("</{}>", tag))?;
+    Some(body[start..end].to_string())
+}
\ No newline at end of file
@@ -0,0 +1,130 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, query, query_as, types::Json};
+use uuid::Uuid;
+use crate::{db::DBClient, error::RepositoryError, modules::user::dto::UserSettings};
+
+/// A user whose feed digest is due.
+pub struct DigestRecipient {
+    pub user_id: Uuid,
+    pub name: String,
+    pub email: String,
+    pub settings: Json<UserSettings>,
+    /// When the last digest went out, `None` before the first one.
+    pub sent_at: Option<DateTime<Utc>>,
+}
+#[derive(Serialize, Deserialize, FromRow)]
+pub struct DigestPost {
+    pub id: Uuid,
+    pub title: String,
+    pub author_name: String,
+    pub created_at: DateTime<Utc>,
+}
+/// What a digest reports: the newest posts of followed accounts since the last one, and the follow
+/// requests and organization invitations still waiting for an answer.
+#[derive(Serialize, Deserialize)]
+pub struct DigestContent {
+    pub posts: Vec<DigestPost>,
+    /// Every new post, `posts` only holds the newest of them.
+    pub posts_count: i64,
+    pub follow_requests: i64,
+    pub organization_invitations: i64,
+}
+
+impl DigestContent {
+    pub fn is_empty(&self) -> bool {
+        self.posts_count == 0 && self.follow_requests == 0 && self.organization_invitations == 0
+    }
+}
+
+#[async_trait]
+pub trait DigestRepository: Send + Sync {
+    /// Verified, unbanned and admitted users who opted in to a digest and got none for its period
+    /// at `now`, by id after `after`, so a pass walks every user once whatever happens to their
+    /// digest. A few minutes of slack keep a pass that starts a little early from putting a digest
+    /// off until the next one.
+    async fn get_due_digests(&self, now: DateTime<Utc>, after: Option<Uuid>, limit: i64) -> Result<Vec<DigestRecipient>, RepositoryError>;
+    /// At most `max_posts` of the posts published since `since`, newest first.
+    async fn get_digest_content(&self, user_id: Uuid, since: DateTime<Utc>, max_posts: i64) -> Result<DigestContent, RepositoryError>;
+    async fn mark_digest_sent(&self, user_id: Uuid, sent_at: DateTime<Utc>) -> Result<(), RepositoryError>;
+}
+
+#[async_trait]
+impl DigestRepository for DBClient {
+    async fn get_due_digests(&self, now: DateTime<Utc>, after: Option<Uuid>, limit: i64) -> Result<Vec<DigestRecipient>, RepositoryError> {
+        let _timer = self.time_query("digest.get_due_digests");
+        let recipients = query_as!(
+            DigestRecipient,
+            r#"
+                SELECT u.id AS user_id, u.name, u.email::TEXT AS "email!", s.settings AS "settings: Json<UserSettings>", d.sent_at AS "sent_at?"
+                FROM user_settings AS s
+                JOIN users AS u ON u.id = s.user_id
+                LEFT JOIN email_digests AS d ON d.user_id = u.id
+                WHERE s.settings->>'email_digest' IN ('daily', 'weekly')
+                    AND u.is_verified = true AND u.is_banned = false AND u.waitlisted_at IS NULL
+                    AND ($2::uuid IS NULL OR u.id > $2)
+                    AND (d.sent_at IS NULL OR d.sent_at <= $1::timestamptz + INTERVAL '5 minutes' - CASE s.settings->>'email_digest'
+                        WHEN 'daily' THEN INTERVAL '1 day'
+                        ELSE INTERVAL '7 days'
+                    END)
+                ORDER BY u.id
+                LIMIT $3;
+            "#,
+            now,
+            after,
+            limit,
+        ).fetch_all(&self.pool).await?;
+        Ok(recipients)
+    }
+    async fn get_digest_content(&self, user_id: Uuid, since: DateTime<Utc>, max_posts: i64) -> Result<DigestContent, RepositoryError> {
+        let _timer = self.time_query("digest.get_digest_content");
+        let posts = query_as!(
+            DigestPost,
+            r#"
+                SELECT p.id, p.title, a.name AS author_name, p.created_at
+                FROM posts AS p
+                JOIN user_followers AS f ON f.following_id = p.user_id AND f.follower_id = $1
+                JOIN users AS a ON a.id = p.user_id
+                WHERE p.created_at > $2 AND p.is_hidden = false AND a.is_banned = false
+                ORDER BY p.created_at DESC
+                LIMIT $3;
+            "#,
+            user_id,
+            since,
+            max_posts,
+        ).fetch_all(&self.pool).await?;
+        let counts = query!(
+            r#"
+                SELECT
+                    (SELECT COUNT(*) FROM posts AS p
+                        JOIN user_followers AS f ON f.following_id = p.user_id AND f.follower_id = $1
+                        JOIN users AS a ON a.id = p.user_id
+                        WHERE p.created_at > $2 AND p.is_hidden = false AND a.is_banned = false) AS "posts_count!",
+                    (SELECT COUNT(*) FROM follow_requests WHERE target_id = $1) AS "follow_requests!",
+                    (SELECT COUNT(*) FROM organization_invitations WHERE user_id = $1) AS "organization_invitations!";
+            "#,
+            user_id,
+            since,
+        ).fetch_one(&self.pool).await?;
+        Ok(DigestContent {
+            posts,
+            posts_count: counts.posts_count,
+            follow_requests: counts.follow_requests,
+            organization_invitations: counts.organization_invitations,
+        })
+    }
+    async fn mark_digest_sent(&self, user_id: Uuid, sent_at: DateTime<Utc>) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("digest.mark_digest_sent");
+        query!(
+            r#"
+                INSERT INTO email_digests (user_id, sent_at)
+                VALUES ($1, $2)
+                ON CONFLICT (user_id) DO UPDATE SET sent_at = EXCLUDED.sent_at;
+            "#,
+            user_id,
+            sent_at,
+        ).execute(&self.pool).await?;
+        Ok(())
+    }
+}
\ No newline at end of file
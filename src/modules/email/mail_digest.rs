@@ -0,0 +1,79 @@
+use chrono_tz::Tz;
+use crate::{
+    modules::{
+        digest::model::DigestContent,
+        email::mailer::{MailError, Mailer, send_email},
+        user::dto::DigestFrequency,
+    },
+    utils::html,
+};
+
+/// Posts link to the frontend, times are shown in the user's time zone. Sections without news are
+/// left out.
+pub async fn send_digest_email(mailer: &dyn Mailer, to_email: &str, name: &str, frontend_url: &str, frequency: DigestFrequency, content: &DigestContent, time_zone: Tz) -> Result<(), MailError> {
+    let (subject, period) = match frequency {
+        DigestFrequency::Weekly => ("Your Weekly Digest", "Weekly"),
+        _ => ("Your Daily Digest", "Daily"),
+    };
+    let template_path = "src/modules/email/templates/digest-email.html";
+    let frontend_url = frontend_url.trim_end_matches('/');
+    let placeholders = vec![
+        ("{{name}}".to_string(), html::escape(name)),
+        ("{{period}}".to_string(), period.to_string()),
+        ("{{posts}}".to_string(), posts_section(frontend_url, content, time_zone)),
+        ("{{pending}}".to_string(), pending_section(frontend_url, content)),
+        ("{{settings_link}}".to_string(), format!("{}/settings", frontend_url)),
+    ];
+    send_email(mailer, to_email, subject, template_path, &placeholders).await
+}
+
+fn posts_section(frontend_url: &str, content: &DigestContent, time_zone: Tz) -> String {
+    if content.posts.is_empty() {
+        return String::new();
+    }
+    let items: String = content.posts.iter()
+        .map(|post| format!(
+            "<li><a href=\"{}/post/{}\" style=\"color: #007bff;\">{}</a> by {}, {}</li>",
+            frontend_url,
+            post.id,
+            html::escape(&post.title),
+            html::escape(&post.author_name),
+            post.created_at.with_timezone(&time_zone).format("%B %-d at %H:%M"),
+        ))
+        .collect();
+    let more = content.posts_count - content.posts.len() as i64;
+    let more = if more > 0 {
+        format!("<p style=\"color: #555555;\">And {} more in <a href=\"{}/feed\" style=\"color: #007bff;\">your feed</a>.</p>", more, frontend_url)
+    } else {
+        String::new()
+    };
+    format!(
+        "<h3 style=\"color: #333333;\">New posts from people you follow</h3><ul style=\"color: #555555;\">{}</ul>{}",
+        items,
+        more,
+    )
+}
+
+fn pending_section(frontend_url: &str, content: &DigestContent) -> String {
+    let mut items = String::new();
+    if content.follow_requests > 0 {
+        items.push_str(&format!(
+            "<li><a href=\"{}/follow-requests\" style=\"color: #007bff;\">{} follow request{}</a></li>",
+            frontend_url,
+            content.follow_requests,
+            if content.follow_requests == 1 { "" } else { "s" },
+        ));
+    }
+    if content.organization_invitations > 0 {
+        items.push_str(&format!(
+            "<li><a href=\"{}/organizations/invitations\" style=\"color: #007bff;\">{} organization invitation{}</a></li>",
+            frontend_url,
+            content.organization_invitations,
+            if content.organization_invitations == 1 { "" } else { "s" },
+        ));
+    }
+    if items.is_empty() {
+        return String::new();
+    }
+    format!("<h3 style=\"color: #333333;\">Waiting for your answer</h3><ul style=\"color: #555555;\">{}</ul>", items)
+}
\ No newline at end of file
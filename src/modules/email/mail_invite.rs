@@ -0,0 +1,29 @@
+use chrono::{DateTime, Utc};
+use crate::{
+    modules::email::mailer::{MailError, Mailer, create_link, send_email},
+    utils::html,
+};
+
+pub async fn send_invite_email(mailer: &dyn Mailer, to_email: &str, name: &str, temporary_password: &str) -> Result<(), MailError> {
+    let subject = "You're Invited";
+    let template_path = "src/modules/email/templates/invite-email.html";
+    // Imported names come from a file nobody validated as HTML.
+    let placeholders = vec![
+        ("{{name}}".to_string(), html::escape(name)),
+        ("{{temporary_password}}".to_string(), temporary_password.to_string())
+    ];
+    send_email(mailer, to_email, subject, template_path, &placeholders).await
+}
+
+/// The link opens the frontend's sign-up page, which sends the token back as `invite_token`.
+pub async fn send_invitation_email(mailer: &dyn Mailer, to_email: &str, frontend_url: &str, token: &str, expires_at: DateTime<Utc>) -> Result<(), MailError> {
+    let subject = "Your Invitation to Sign Up";
+    let template_path = "src/modules/email/templates/invitation-email.html";
+    let base_url = format!("{}/sign-up", frontend_url.trim_end_matches('/'));
+    let sign_up_link = create_link(&base_url, token);
+    let placeholders = vec![
+        ("{{sign_up_link}}".to_string(), sign_up_link),
+        ("{{expires_at}}".to_string(), expires_at.format("%B %-d, %Y").to_string())
+    ];
+    send_email(mailer, to_email, subject, template_path, &placeholders).await
+}
\ No newline at end of file
@@ -0,0 +1,24 @@
+use chrono::DateTime;
+use chrono_tz::Tz;
+use crate::{
+    modules::{email::mailer::{MailError, Mailer, create_link, send_email}, session::model::ClientDevice},
+    utils::html,
+};
+
+/// The revoke link signs the device out and forgets it, it works from a browser without signing in.
+/// `signed_in_at` is shown in the user's time zone.
+pub async fn send_new_sign_in_email(mailer: &dyn Mailer, to_email: &str, name: &str, device: &ClientDevice, token: &str, signed_in_at: DateTime<Tz>) -> Result<(), MailError> {
+    let subject = "New Sign-In to Your Account";
+    let template_path = "src/modules/email/templates/new-sign-in-email.html";
+    let base_url = "http://localhost:4000/api/auth/sessions/revoke";
+    let revoke_link = create_link(base_url, token);
+    // The user agent is whatever the client sent.
+    let placeholders = vec![
+        ("{{name}}".to_string(), html::escape(name)),
+        ("{{ip_address}}".to_string(), html::escape(&device.ip_address)),
+        ("{{user_agent}}".to_string(), html::escape(&device.user_agent)),
+        ("{{signed_in_at}}".to_string(), signed_in_at.format("%B %-d, %Y at %H:%M %Z").to_string()),
+        ("{{revoke_link}}".to_string(), revoke_link)
+    ];
+    send_email(mailer, to_email, subject, template_path, &placeholders).await
+}
\ No newline at end of file
@@ -1,7 +1,6 @@
-use std::error::Error;
-use crate::modules::email::mailer::{create_link, send_email};
+use crate::modules::email::mailer::{MailError, Mailer, create_link, send_email};
 
-pub async fn send_forgot_password_email(to_email: &str, name: &str, token: &str) -> Result<(), Box<dyn Error>> {
+pub async fn send_forgot_password_email(mailer: &dyn Mailer, to_email: &str, name: &str, token: &str) -> Result<(), MailError> {
     let subject = "Reset your Password";
     let template_path = "src/modules/email/templates/reset-password-email.html";
     let base_url = "http://localhost:4000/api/auth/reset-password";
@@ -10,5 +9,5 @@ pub async fn send_forgot_password_email(to_email: &str, name: &str, token: &str)
         ("{{name}}".to_string(), name.to_string()),
         ("{{reset_link}}".to_string(), reset_link.to_string())
     ];
-    send_email(to_email, subject, template_path, &placeholders).await
+    send_email(mailer, to_email, subject, template_path, &placeholders).await
 }
\ No newline at end of file
@@ -0,0 +1,20 @@
+use crate::modules::email::mailer::{MailError, Mailer, send_email};
+
+pub async fn send_waitlist_email(mailer: &dyn Mailer, to_email: &str, name: &str, position: i64) -> Result<(), MailError> {
+    let subject = "You're on the Waitlist";
+    let template_path = "src/modules/email/templates/waitlist-email.html";
+    let placeholders = vec![
+        ("{{name}}".to_string(), name.to_string()),
+        ("{{position}}".to_string(), position.to_string())
+    ];
+    send_email(mailer, to_email, subject, template_path, &placeholders).await
+}
+
+pub async fn send_waitlist_approved_email(mailer: &dyn Mailer, to_email: &str, name: &str) -> Result<(), MailError> {
+    let subject = "Your Account is Ready";
+    let template_path = "src/modules/email/templates/waitlist-approved-email.html";
+    let placeholders = vec![
+        ("{{name}}".to_string(), name.to_string())
+    ];
+    send_email(mailer, to_email, subject, template_path, &placeholders).await
+}
\ No newline at end of file
@@ -1,11 +1,10 @@
-use std::error::Error;
-use crate::modules::email::mailer::send_email;
+use crate::modules::email::mailer::{MailError, Mailer, send_email};
 
-pub async fn send_welcome_email(to_email: &str, name: &str) -> Result<(), Box<dyn Error>> {
+pub async fn send_welcome_email(mailer: &dyn Mailer, to_email: &str, name: &str) -> Result<(), MailError> {
     let subject = "Welcome to Application";
     let template_path = "src/modules/email/templates/welcome-email.html";
     let placeholders = vec![
         ("{{name}}".to_string(), name.to_string())
     ];
-    send_email(to_email, subject, template_path, &placeholders).await
+    send_email(mailer, to_email, subject, template_path, &placeholders).await
 }
\ No newline at end of file
@@ -1,47 +1,167 @@
-use std::{env, fs, error::Error};
+use std::{env, fs, error::Error, sync::{Arc, LazyLock}, time::Duration};
+use async_trait::async_trait;
 use lettre::{
     message::{header, SinglePart},
     transport::smtp::authentication::Credentials,
     Message, SmtpTransport, Transport,
 };
+use log::warn;
+use tokio::{sync::Notify, time::{sleep, timeout}};
+use crate::{
+    config::Config,
+    modules::email::model::EmailOutboxRepository,
+    utils::{metrics, rand::with_jitter},
+};
+
+pub type MailError = Box<dyn Error + Send + Sync>;
+
+/// Delivers a rendered email. `AppState.mailer` holds the SMTP one, tests swap in a fake.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, to_email: &str, subject: &str, html: String) -> Result<(), MailError>;
+}
+
+/// Sends through the `SMTP_*` server with STARTTLS. The SMTP client blocks, so it runs on the
+/// blocking thread pool.
+pub struct SmtpMailer;
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, to_email: &str, subject: &str, html: String) -> Result<(), MailError> {
+        let smtp_username = env::var("SMTP_USERNAME")?;
+        let smtp_password = env::var("SMTP_PASSWORD")?;
+        let smtp_server = env::var("SMTP_SERVER")?;
+        let smtp_port: u16 = env::var("SMTP_PORT")?.parse()?;
+        let email = Message::builder()
+            .from(smtp_username.parse()?)
+            .to(to_email.parse()?)
+            .subject(subject)
+            .header(header::ContentType::TEXT_HTML)
+            .singlepart(SinglePart::builder()
+                .header(header::ContentType::TEXT_HTML)
+                .body(html)
+            )?;
+
+        let creds = Credentials::new(smtp_username, smtp_password);
+        let mailer = SmtpTransport::starttls_relay(&smtp_server)?
+            .credentials(creds)
+            .port(smtp_port)
+            .build();
+        let result = tokio::task::spawn_blocking(move || mailer.send(&email)).await?;
+        match result {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+}
+
+/// Woken when `OutboxMailer` queues an email, so the outbox job sends it without waiting for its next poll.
+pub static OUTBOX_QUEUED: LazyLock<Notify> = LazyLock::new(Notify::new);
+
+/// Queues emails in the outbox instead of sending them, for requests that must not wait on the SMTP
+/// server. The outbox job of this instance is woken to send them at once.
+pub struct OutboxMailer {
+    outbox: Arc<dyn EmailOutboxRepository>,
+}
+
+impl OutboxMailer {
+    pub fn new(outbox: Arc<dyn EmailOutboxRepository>) -> Self {
+        Self { outbox }
+    }
+}
+
+#[async_trait]
+impl Mailer for OutboxMailer {
+    async fn send(&self, to_email: &str, subject: &str, html: String) -> Result<(), MailError> {
+        self.outbox.queue_email(to_email, subject, &html).await?;
+        OUTBOX_QUEUED.notify_one();
+        Ok(())
+    }
+}
+
+/// Sends one email with a timeout, the attempt `RetryingMailer` makes and the outbox job retries.
+pub async fn send_with_timeout(mailer: &dyn Mailer, send_timeout: Duration, to_email: &str, subject: &str, html: String) -> Result<(), MailError> {
+    match timeout(send_timeout, mailer.send(to_email, subject, html)).await {
+        Ok(result) => result,
+        Err(_) => Err(format!("timed out after {}s", send_timeout.as_secs()).into()),
+    }
+}
+
+/// Wraps the mailer that talks to the server. Each attempt is bounded by `MAIL_TIMEOUT`, failed ones
+/// are retried `MAIL_MAX_ATTEMPTS` times in all, `MAIL_RETRY_BACKOFF_MS` apart (doubled and jittered).
+/// An email that still isn't sent goes to the outbox for the outbox job, and counts as sent for the
+/// caller, so a slow or down SMTP server doesn't fail the request. Only an email that can't be
+/// queued either is an error.
+pub struct RetryingMailer {
+    inner: Arc<dyn Mailer>,
+    outbox: Arc<dyn EmailOutboxRepository>,
+    send_timeout: Duration,
+    max_attempts: u32,
+    backoff: Duration,
+}
+
+impl RetryingMailer {
+    pub fn new(inner: Arc<dyn Mailer>, outbox: Arc<dyn EmailOutboxRepository>, config: &Config) -> Self {
+        Self {
+            inner,
+            outbox,
+            send_timeout: Duration::from_secs(config.mail_timeout),
+            max_attempts: config.mail_max_attempts,
+            backoff: Duration::from_millis(config.mail_retry_backoff),
+        }
+    }
+}
+
+#[async_trait]
+impl Mailer for RetryingMailer {
+    async fn send(&self, to_email: &str, subject: &str, html: String) -> Result<(), MailError> {
+        let mut attempt = 1;
+        let error = loop {
+            match send_with_timeout(&*self.inner, self.send_timeout, to_email, subject, html.clone()).await {
+                Ok(()) => {
+                    metrics::increment_counter("emails_total", &[("outcome", "sent")]);
+                    return Ok(());
+                }
+                Err(e) if attempt >= self.max_attempts => break e,
+                Err(e) => {
+                    let delay = with_jitter(self.backoff.saturating_mul(1 << (attempt - 1).min(10)));
+                    warn!("Sending \"{}\" email failed (attempt {}), retrying in {}ms: {}", subject, attempt, delay.as_millis(), e);
+                    metrics::increment_counter("emails_total", &[("outcome", "retried")]);
+                    sleep(delay).await;
+                    attempt += 1;
+                }
+            }
+        };
+        match self.outbox.queue_email(to_email, subject, &html).await {
+            Ok(email_id) => {
+                warn!("Sending \"{}\" email failed {} times, queued as {}: {}", subject, attempt, email_id, error);
+                metrics::increment_counter("emails_total", &[("outcome", "queued")]);
+                Ok(())
+            }
+            Err(e) => {
+                warn!("Sending \"{}\" email failed {} times and it could not be queued: {}", subject, attempt, e);
+                metrics::increment_counter("emails_total", &[("outcome", "failed")]);
+                Err(error)
+            }
+        }
+    }
+}
 
 pub fn create_link(base_url: &str, token: &str) -> String {
     format!("{}?token={}", base_url, token)
 }
 
 pub async fn send_email(
+    mailer: &dyn Mailer,
     to_email: &str,
     subject: &str,
     template_path: &str,
     placeholders: &[(String, String)]
-) -> Result<(), Box<dyn Error>> {
-    let smtp_username = env::var("SMTP_USERNAME")?;
-    let smtp_password = env::var("SMTP_PASSWORD")?;
-    let smtp_server = env::var("SMTP_SERVER")?;
-    let smtp_port: u16 = env::var("SMTP_PORT")?.parse()?;
+) -> Result<(), MailError> {
     let mut html_template = fs::read_to_string(template_path)?;
 
     for (key, value) in placeholders {
         html_template = html_template.replace(key, value)
     }
-    let email = Message::builder()
-        .from(smtp_username.parse()?)
-        .to(to_email.parse()?)
-        .subject(subject)
-        .header(header::ContentType::TEXT_HTML)
-        .singlepart(SinglePart::builder()
-            .header(header::ContentType::TEXT_HTML)
-            .body(html_template)
-        )?;
-
-    let creds = Credentials::new(smtp_username, smtp_password);
-    let mailer = SmtpTransport::starttls_relay(&smtp_server)?
-        .credentials(creds)
-        .port(smtp_port)
-        .build();
-    let result = mailer.send(&email);
-    match result {
-        Ok(_) => Ok(()),
-        Err(e) => Err(Box::new(e)),
-    }
+    mailer.send(to_email, subject, html_template).await
 }
\ No newline at end of file
@@ -1,4 +1,9 @@
 pub mod mailer;
+pub mod model;
 pub mod mail_reset_password;
 pub mod mail_verification;
-pub mod mail_welcome;
\ No newline at end of file
+pub mod mail_welcome;
+pub mod mail_waitlist;
+pub mod mail_invite;
+pub mod mail_new_sign_in;
+pub mod mail_digest;
\ No newline at end of file
@@ -0,0 +1,96 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{query, query_as};
+use uuid::Uuid;
+use crate::{db::DBClient, error::RepositoryError};
+
+/// A rendered email the outbox job still has to send.
+pub struct QueuedEmail {
+    pub id: Uuid,
+    pub to_email: String,
+    pub subject: String,
+    pub html: String,
+    pub attempts: i32,
+}
+
+#[async_trait]
+pub trait EmailOutboxRepository: Send + Sync {
+    /// Keeps an email that could not be sent right away, the outbox job sends it on its next tick.
+    async fn queue_email(&self, to_email: &str, subject: &str, html: &str) -> Result<Uuid, RepositoryError>;
+    /// Claims up to `limit` due emails by pushing their next attempt `lease_secs` into the future,
+    /// so another instance polling at the same time skips them.
+    async fn claim_emails(&self, limit: i64, lease_secs: f64) -> Result<Vec<QueuedEmail>, RepositoryError>;
+    async fn mark_email_sent(&self, email_id: Uuid) -> Result<(), RepositoryError>;
+    /// Counts a failed attempt. The email is tried again at `retry_at`, or dead-lettered when it is `None`.
+    async fn mark_email_failed(&self, email_id: Uuid, error: &str, retry_at: Option<DateTime<Utc>>) -> Result<(), RepositoryError>;
+}
+
+#[async_trait]
+impl EmailOutboxRepository for DBClient {
+    async fn queue_email(&self, to_email: &str, subject: &str, html: &str) -> Result<Uuid, RepositoryError> {
+        let _timer = self.time_query("email.queue_email");
+        let email_id = query!(
+            r#"
+                INSERT INTO email_outbox (to_email, subject, html)
+                VALUES ($1, $2, $3)
+                RETURNING id;
+            "#,
+            to_email,
+            subject,
+            html,
+        ).fetch_one(&self.pool).await?.id;
+        Ok(email_id)
+    }
+    async fn claim_emails(&self, limit: i64, lease_secs: f64) -> Result<Vec<QueuedEmail>, RepositoryError> {
+        let _timer = self.time_query("email.claim_emails");
+        let emails = query_as!(
+            QueuedEmail,
+            r#"
+                UPDATE email_outbox
+                SET next_attempt_at = Now() + make_interval(secs => $2), updated_at = Now()
+                WHERE id IN (
+                    SELECT id FROM email_outbox
+                    WHERE status = 'pending' AND next_attempt_at <= Now()
+                    ORDER BY next_attempt_at
+                    LIMIT $1
+                    FOR UPDATE SKIP LOCKED
+                )
+                RETURNING id, to_email, subject, html, attempts;
+            "#,
+            limit,
+            lease_secs,
+        ).fetch_all(&self.pool).await?;
+        Ok(emails)
+    }
+    async fn mark_email_sent(&self, email_id: Uuid) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("email.mark_email_sent");
+        query!(
+            r#"
+                UPDATE email_outbox
+                SET status = 'sent', attempts = attempts + 1, last_error = NULL, sent_at = Now(), updated_at = Now()
+                WHERE id = $1;
+            "#,
+            email_id,
+        ).execute(&self.pool).await?;
+        Ok(())
+    }
+    async fn mark_email_failed(&self, email_id: Uuid, error: &str, retry_at: Option<DateTime<Utc>>) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("email.mark_email_failed");
+        query!(
+            r#"
+                UPDATE email_outbox
+                SET status = CASE WHEN $3::TIMESTAMPTZ IS NULL
+                        THEN 'dead'::email_outbox_status
+                        ELSE 'pending'::email_outbox_status
+                    END,
+                    attempts = attempts + 1, last_error = $2,
+                    next_attempt_at = COALESCE($3, next_attempt_at), updated_at = Now()
+                WHERE id = $1;
+            "#,
+            email_id,
+            error,
+            retry_at,
+        ).execute(&self.pool).await?;
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,37 @@
+use serde::Serialize;
+use uuid::Uuid;
+use crate::modules::{
+    analytics::model::AnalyticsEvent,
+    comment::model::Comment,
+    post::model::Post,
+    user::dto::UserResponse,
+};
+
+/// Something that happened, published after the write it describes has been committed. Serialized
+/// as `{"type": "post_created", "data": {...}}` for the event stream.
+#[derive(Serialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum DomainEvent {
+    /// `waitlist_position` is set when the account was put on the waitlist.
+    UserRegistered { user: UserResponse, waitlist_position: Option<i64> },
+    PostCreated { post: Post },
+    CommentCreated { comment: Comment },
+    /// A direct follow or an accepted follow request, a pending request is not a follow yet.
+    UserFollowed { follower_id: Uuid, following_id: Uuid },
+    UserUnfollowed { follower_id: Uuid, following_id: Uuid },
+    /// Client analytics events, published by the flush job when `ANALYTICS_SINK` is `events`.
+    AnalyticsEventsRecorded { events: Vec<AnalyticsEvent> },
+}
+
+impl DomainEvent {
+    pub fn get_value(&self) -> &str {
+        match self {
+            DomainEvent::UserRegistered { .. } => "user_registered",
+            DomainEvent::PostCreated { .. } => "post_created",
+            DomainEvent::CommentCreated { .. } => "comment_created",
+            DomainEvent::UserFollowed { .. } => "user_followed",
+            DomainEvent::UserUnfollowed { .. } => "user_unfollowed",
+            DomainEvent::AnalyticsEventsRecorded { .. } => "analytics_events_recorded",
+        }
+    }
+}
\ No newline at end of file
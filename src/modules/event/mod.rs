@@ -0,0 +1,4 @@
+pub mod domain_event;
+pub mod publisher;
+pub mod stream;
+pub mod subscribers;
\ No newline at end of file
@@ -0,0 +1,49 @@
+use std::sync::Arc;
+use async_trait::async_trait;
+use crate::{
+    AppState,
+    modules::event::{
+        domain_event::DomainEvent,
+        subscribers::{EventSubscriber, FeedCacheSubscriber, WaitlistEmailSubscriber, WebhookSubscriber},
+    },
+};
+
+/// Hands domain events to whoever consumes them. `AppState.events` holds the in-process bus, use
+/// `AppState::publish` to send one.
+#[async_trait]
+pub trait EventPublisher: Send + Sync {
+    async fn publish(&self, app_state: &Arc<AppState>, event: &DomainEvent);
+}
+
+/// Runs every subscriber in order before `publish` returns, so a cache a subscriber drops is gone
+/// by the time the request that published the event answers. Subscribers log their own failures,
+/// publishing never fails the request.
+pub struct LocalEventBus {
+    subscribers: Vec<Arc<dyn EventSubscriber>>,
+}
+
+impl LocalEventBus {
+    pub fn new(subscribers: Vec<Arc<dyn EventSubscriber>>) -> Self {
+        Self { subscribers }
+    }
+}
+
+impl Default for LocalEventBus {
+    /// The subscribers the API runs with.
+    fn default() -> Self {
+        Self::new(vec![
+            Arc::new(FeedCacheSubscriber),
+            Arc::new(WebhookSubscriber),
+            Arc::new(WaitlistEmailSubscriber),
+        ])
+    }
+}
+
+#[async_trait]
+impl EventPublisher for LocalEventBus {
+    async fn publish(&self, app_state: &Arc<AppState>, event: &DomainEvent) {
+        for subscriber in &self.subscribers {
+            subscriber.handle(app_state, event).await;
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,167 @@
+use std::{collections::BTreeMap, error::Error, sync::Arc, time::Duration};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use rskafka::{
+    BackoffConfig,
+    client::{ClientBuilder, partition::{Compression, PartitionClient, UnknownTopicHandling}},
+    record::Record,
+};
+use serde::Serialize;
+use tokio::sync::mpsc::{self, Receiver, Sender, error::TrySendError};
+use uuid::Uuid;
+use crate::{
+    AppState,
+    config::Config,
+    modules::event::{domain_event::DomainEvent, publisher::{EventPublisher, LocalEventBus}},
+};
+
+pub type StreamError = Box<dyn Error + Send + Sync>;
+
+/// Bumped when a field of the envelope or of an event's `data` changes meaning or goes away.
+pub const EVENT_SCHEMA_VERSION: u32 = 1;
+/// Most records sent to the broker in one request.
+const STREAM_BATCH_SIZE: usize = 100;
+
+/// What goes on the wire, one JSON document per event.
+#[derive(Serialize)]
+struct EventEnvelope<'a> {
+    id: Uuid,
+    schema_version: u32,
+    occurred_at: DateTime<Utc>,
+    #[serde(flatten)]
+    event: &'a DomainEvent,
+}
+
+pub struct StreamRecord {
+    event_type: String,
+    occurred_at: DateTime<Utc>,
+    payload: Vec<u8>,
+}
+
+impl StreamRecord {
+    pub fn new(event: &DomainEvent) -> Result<Self, serde_json::Error> {
+        let envelope = EventEnvelope {
+            id: Uuid::new_v4(),
+            schema_version: EVENT_SCHEMA_VERSION,
+            occurred_at: Utc::now(),
+            event,
+        };
+        Ok(Self {
+            event_type: event.get_value().to_string(),
+            occurred_at: envelope.occurred_at,
+            payload: serde_json::to_vec(&envelope)?,
+        })
+    }
+}
+
+/// A broker that external consumers read the domain events from, picked with `EVENT_STREAM`.
+pub enum EventStream {
+    /// Every event goes to partition 0 of `EVENT_STREAM_TOPIC`, so consumers read them in publish
+    /// order. The event type is in the `event-type` record header.
+    Kafka(PartitionClient),
+    /// Each event type has its own subject, `<EVENT_STREAM_TOPIC>.<event type>`.
+    Nats { client: async_nats::Client, subject_prefix: String },
+}
+
+impl EventStream {
+    /// Connects to the configured broker, `None` when streaming is off.
+    pub async fn from_config(config: &Config) -> Result<Option<Self>, StreamError> {
+        let Some(url) = config.event_stream_url.as_deref() else {
+            return Ok(None);
+        };
+        match config.event_stream.as_str() {
+            "kafka" => {
+                let brokers = url.split(',').map(|broker| broker.trim().to_string()).collect();
+                let backoff = BackoffConfig {
+                    deadline: Some(Duration::from_secs(30)),
+                    ..BackoffConfig::default()
+                };
+                let client = ClientBuilder::new(brokers).backoff_config(backoff).build().await?;
+                let partition = client
+                    .partition_client(config.event_stream_topic.clone(), 0, UnknownTopicHandling::Error)
+                    .await?;
+                Ok(Some(EventStream::Kafka(partition)))
+            }
+            "nats" => {
+                let client = async_nats::connect(url).await?;
+                Ok(Some(EventStream::Nats { client, subject_prefix: config.event_stream_topic.clone() }))
+            }
+            _ => Ok(None),
+        }
+    }
+    pub fn get_value(&self) -> &str {
+        match self {
+            EventStream::Kafka(_) => "kafka",
+            EventStream::Nats { .. } => "nats",
+        }
+    }
+    pub async fn send(&self, records: Vec<StreamRecord>) -> Result<(), StreamError> {
+        match self {
+            EventStream::Kafka(partition) => {
+                let records = records.into_iter().map(|record| Record {
+                    key: None,
+                    value: Some(record.payload),
+                    headers: BTreeMap::from([("event-type".to_string(), record.event_type.into_bytes())]),
+                    timestamp: record.occurred_at,
+                }).collect();
+                partition.produce(records, Compression::NoCompression).await?;
+                Ok(())
+            }
+            EventStream::Nats { client, subject_prefix } => {
+                for record in records {
+                    let subject = format!("{}.{}", subject_prefix, record.event_type);
+                    client.publish(subject, record.payload.into()).await?;
+                }
+                client.flush().await?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The local subscribers plus a copy of every event on the event stream. Requests only wait for
+/// the local subscribers: records are queued for a background task, and dropped with a warning
+/// when `EVENT_STREAM_BUFFER` records are already waiting or the broker refuses them.
+pub struct StreamingEventBus {
+    local: LocalEventBus,
+    sender: Sender<StreamRecord>,
+}
+
+impl StreamingEventBus {
+    pub fn spawn(stream: EventStream, local: LocalEventBus, buffer: usize) -> Self {
+        let (sender, receiver) = mpsc::channel(buffer);
+        info!("Domain events are streamed to {}.", stream.get_value());
+        tokio::spawn(forward(stream, receiver));
+        Self { local, sender }
+    }
+}
+
+async fn forward(stream: EventStream, mut receiver: Receiver<StreamRecord>) {
+    let mut batch = Vec::with_capacity(STREAM_BATCH_SIZE);
+    while receiver.recv_many(&mut batch, STREAM_BATCH_SIZE).await > 0 {
+        let count = batch.len();
+        if let Err(e) = stream.send(std::mem::take(&mut batch)).await {
+            warn!("Failed to stream {} domain events to {}: {}", count, stream.get_value(), e);
+        }
+    }
+}
+
+#[async_trait]
+impl EventPublisher for StreamingEventBus {
+    async fn publish(&self, app_state: &Arc<AppState>, event: &DomainEvent) {
+        self.local.publish(app_state, event).await;
+        let record = match StreamRecord::new(event) {
+            Ok(record) => record,
+            Err(e) => {
+                warn!("Failed to serialize {} event: {}", event.get_value(), e);
+                return;
+            }
+        };
+        match self.sender.try_send(record) {
+            Ok(()) => {}
+            Err(TrySendError::Full(record)) => warn!("Event stream buffer is full, dropped a {} event", record.event_type),
+            Err(TrySendError::Closed(record)) => warn!("Event stream is closed, dropped a {} event", record.event_type),
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,67 @@
+use std::sync::Arc;
+use async_trait::async_trait;
+use serde_json::json;
+use crate::{
+    AppState,
+    modules::{
+        event::domain_event::DomainEvent,
+        waitlist::{handler::queue_position_email, model::WaitlistEntry},
+        webhook::{handler::queue_webhook_event, model::WebhookEvent},
+    },
+};
+
+#[async_trait]
+pub trait EventSubscriber: Send + Sync {
+    async fn handle(&self, app_state: &Arc<AppState>, event: &DomainEvent);
+}
+
+/// Drops the cached feeds an event changes: a new post shows up for the author and their
+/// followers (and its organization's audience), a follow or unfollow changes what the follower sees.
+pub struct FeedCacheSubscriber;
+
+#[async_trait]
+impl EventSubscriber for FeedCacheSubscriber {
+    async fn handle(&self, app_state: &Arc<AppState>, event: &DomainEvent) {
+        match event {
+            DomainEvent::PostCreated { post } => app_state.post_service().invalidate_post_feeds(post).await,
+            DomainEvent::UserFollowed { follower_id, .. } | DomainEvent::UserUnfollowed { follower_id, .. } => {
+                let _ = app_state.redis_client.delete_feeds(&[*follower_id]).await;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Queues the outgoing webhook deliveries for the events endpoints can subscribe to.
+pub struct WebhookSubscriber;
+
+#[async_trait]
+impl EventSubscriber for WebhookSubscriber {
+    async fn handle(&self, app_state: &Arc<AppState>, event: &DomainEvent) {
+        let (webhook_event, data) = match event {
+            DomainEvent::UserRegistered { user, .. } => (WebhookEvent::UserCreated, json!(user)),
+            DomainEvent::PostCreated { post } => (WebhookEvent::PostCreated, json!(post)),
+            DomainEvent::CommentCreated { comment } => (WebhookEvent::CommentCreated, json!(comment)),
+            DomainEvent::UserFollowed { follower_id, following_id } => (
+                WebhookEvent::UserFollowed,
+                json!({ "follower_id": follower_id, "following_id": following_id }),
+            ),
+            DomainEvent::UserUnfollowed { .. } | DomainEvent::AnalyticsEventsRecorded { .. } => return,
+        };
+        queue_webhook_event(app_state.clone(), webhook_event, data);
+    }
+}
+
+/// Tells a waitlisted sign-up their position. The verification email is not sent from here, it
+/// carries a token that has no business in an event.
+pub struct WaitlistEmailSubscriber;
+
+#[async_trait]
+impl EventSubscriber for WaitlistEmailSubscriber {
+    async fn handle(&self, app_state: &Arc<AppState>, event: &DomainEvent) {
+        if let DomainEvent::UserRegistered { user, waitlist_position: Some(position) } = event {
+            let entry = WaitlistEntry { id: user.id, name: user.name.clone(), email: user.email.clone() };
+            queue_position_email(app_state.clone(), entry, *position);
+        }
+    }
+}
\ No newline at end of file
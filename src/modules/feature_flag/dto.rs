@@ -0,0 +1,57 @@
+use serde::Deserialize;
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
+use crate::modules::role::model::RoleType;
+
+/// Keys are 2 to 100 lowercase letters, digits, dots, dashes or underscores, e.g. `post.reactions`.
+fn validate_flag_key(value: &str) -> Result<(), ValidationError> {
+    let is_key = (2..=100).contains(&value.len())
+        && value.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '.' | '-' | '_'));
+    if is_key {
+        return Ok(());
+    }
+    let mut error = ValidationError::new("invalid_key");
+    error.message = Some("Key must be 2 to 100 lowercase letters, digits, dots, dashes or underscores".into());
+    Err(error)
+}
+
+#[derive(Deserialize, Validate)]
+pub struct FeatureFlagRequest {
+    #[validate(custom(function = "validate_flag_key"))]
+    pub key: String,
+    #[validate(length(max = 255, message = "Description must be at most 255 characters"))]
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub enabled: bool,
+    /// Every role when empty.
+    #[serde(default)]
+    pub roles: Vec<RoleType>,
+    #[validate(range(min = 0, max = 100, message = "Percentage must be between 0 and 100"))]
+    #[serde(default = "default_percentage")]
+    pub percentage: i32,
+}
+
+fn default_percentage() -> i32 {
+    100
+}
+
+/// Missing fields keep their value.
+#[derive(Deserialize, Validate)]
+pub struct FeatureFlagUpdateRequest {
+    #[validate(length(max = 255, message = "Description must be at most 255 characters"))]
+    pub description: Option<String>,
+    pub enabled: Option<bool>,
+    pub roles: Option<Vec<RoleType>>,
+    #[validate(range(min = 0, max = 100, message = "Percentage must be between 0 and 100"))]
+    pub percentage: Option<i32>,
+}
+
+pub struct NewFeatureFlag {
+    pub key: String,
+    pub description: String,
+    pub enabled: bool,
+    pub roles: Vec<RoleType>,
+    pub percentage: i32,
+    pub created_by: Uuid,
+}
\ No newline at end of file
@@ -0,0 +1,64 @@
+use std::sync::Arc;
+use axum::{middleware, Router, routing::{delete, get, post, put}, Extension, response::IntoResponse, http::StatusCode};
+use crate::{
+    AppState,
+    dto::{HttpResult, SuccessResponse},
+    error::{PathParser, ValidatedJson},
+    middleware::{AuthenticatedUser, permission::{check_permission, Permission}},
+    modules::feature_flag::dto::{FeatureFlagRequest, FeatureFlagUpdateRequest},
+};
+
+pub fn feature_flag_admin_router() -> Router {
+    Router::new()
+        .route("/feature-flags", post(feature_flag_create).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminFeatureFlagManage.to_string())
+        })))
+        .route("/feature-flags", get(feature_flag_list).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminFeatureFlagManage.to_string())
+        })))
+        .route("/feature-flags/{key}", put(feature_flag_update).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminFeatureFlagManage.to_string())
+        })))
+        .route("/feature-flags/{key}", delete(feature_flag_delete).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminFeatureFlagManage.to_string())
+        })))
+}
+
+async fn feature_flag_create(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    ValidatedJson(body): ValidatedJson<FeatureFlagRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let flag = app_state.flags().create(&user_auth.user, body).await?;
+    Ok((
+        StatusCode::CREATED,
+        SuccessResponse::new("Successfully created the feature flag.", Some(flag))
+    ))
+}
+async fn feature_flag_list(
+    Extension(app_state): Extension<Arc<AppState>>,
+) -> HttpResult<impl IntoResponse> {
+    let flags = app_state.flags().all().await?;
+    Ok(
+        SuccessResponse::new("Getting feature flag list data", Some(flags))
+    )
+}
+async fn feature_flag_update(
+    Extension(app_state): Extension<Arc<AppState>>,
+    PathParser(key): PathParser<String>,
+    ValidatedJson(body): ValidatedJson<FeatureFlagUpdateRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let flag = app_state.flags().update(&key, body).await?;
+    Ok(
+        SuccessResponse::new("Successfully updated the feature flag.", Some(flag))
+    )
+}
+async fn feature_flag_delete(
+    Extension(app_state): Extension<Arc<AppState>>,
+    PathParser(key): PathParser<String>,
+) -> HttpResult<impl IntoResponse> {
+    app_state.flags().delete(&key).await?;
+    Ok(
+        SuccessResponse::<()>::new("Successfully removed the feature flag.", None)
+    )
+}
\ No newline at end of file
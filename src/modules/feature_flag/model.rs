@@ -0,0 +1,103 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, query, query_as};
+use uuid::Uuid;
+use crate::{
+    db::DBClient,
+    error::RepositoryError,
+    modules::feature_flag::dto::{FeatureFlagUpdateRequest, NewFeatureFlag},
+};
+
+#[derive(Serialize, Deserialize, FromRow, Clone)]
+pub struct FeatureFlag {
+    pub key: String,
+    pub description: String,
+    /// Off turns the feature off for everyone, whatever the other fields say.
+    pub enabled: bool,
+    /// Role names the feature is rolled out to, empty for every role.
+    pub roles: Vec<String>,
+    /// Share of the users in `roles` that get the feature, from 0 to 100.
+    pub percentage: i32,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait FeatureFlagRepository: Send + Sync {
+    async fn get_feature_flags(&self) -> Result<Vec<FeatureFlag>, RepositoryError>;
+    async fn save_feature_flag(&self, data: NewFeatureFlag) -> Result<FeatureFlag, RepositoryError>;
+    /// Changes the fields set in `data`, the others keep their value.
+    async fn update_feature_flag(&self, key: &str, data: FeatureFlagUpdateRequest) -> Result<FeatureFlag, RepositoryError>;
+    async fn delete_feature_flag(&self, key: &str) -> Result<(), RepositoryError>;
+}
+
+#[async_trait]
+impl FeatureFlagRepository for DBClient {
+    async fn get_feature_flags(&self) -> Result<Vec<FeatureFlag>, RepositoryError> {
+        let _timer = self.time_query("feature_flag.get_feature_flags");
+        let flags = query_as!(
+            FeatureFlag,
+            r#"
+                SELECT key, description, enabled, roles, percentage, created_by, created_at, updated_at
+                FROM feature_flags
+                ORDER BY key;
+            "#
+        ).fetch_all(&self.pool).await?;
+        Ok(flags)
+    }
+    async fn save_feature_flag(&self, data: NewFeatureFlag) -> Result<FeatureFlag, RepositoryError> {
+        let _timer = self.time_query("feature_flag.save_feature_flag");
+        let roles: Vec<String> = data.roles.iter().map(|role| role.get_value().to_string()).collect();
+        let flag = query_as!(
+            FeatureFlag,
+            r#"
+                INSERT INTO feature_flags (key, description, enabled, roles, percentage, created_by)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                RETURNING key, description, enabled, roles, percentage, created_by, created_at, updated_at;
+            "#,
+            data.key,
+            data.description,
+            data.enabled,
+            &roles,
+            data.percentage,
+            data.created_by,
+        ).fetch_one(&self.pool).await?;
+        Ok(flag)
+    }
+    async fn update_feature_flag(&self, key: &str, data: FeatureFlagUpdateRequest) -> Result<FeatureFlag, RepositoryError> {
+        let _timer = self.time_query("feature_flag.update_feature_flag");
+        let roles: Option<Vec<String>> = data.roles
+            .map(|roles| roles.iter().map(|role| role.get_value().to_string()).collect());
+        let flag = query_as!(
+            FeatureFlag,
+            r#"
+                UPDATE feature_flags
+                SET description = COALESCE($2, description), enabled = COALESCE($3, enabled),
+                    roles = COALESCE($4, roles), percentage = COALESCE($5, percentage), updated_at = Now()
+                WHERE key = $1
+                RETURNING key, description, enabled, roles, percentage, created_by, created_at, updated_at;
+            "#,
+            key,
+            data.description,
+            data.enabled,
+            roles.as_deref(),
+            data.percentage,
+        ).fetch_optional(&self.pool).await?.ok_or(RepositoryError::NotFound)?;
+        Ok(flag)
+    }
+    async fn delete_feature_flag(&self, key: &str) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("feature_flag.delete_feature_flag");
+        let result = query!(
+            r#"
+                DELETE FROM feature_flags WHERE key = $1;
+            "#,
+            key
+        ).execute(&self.pool).await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,104 @@
+use std::sync::Arc;
+use log::warn;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+use crate::{
+    AppState,
+    error::AppError,
+    modules::{
+        feature_flag::{
+            dto::{FeatureFlagRequest, FeatureFlagUpdateRequest, NewFeatureFlag},
+            model::FeatureFlag,
+        },
+        user::model::User,
+    },
+};
+
+/// Feature flags read through a Redis copy of the `feature_flags` table. Handlers ask
+/// `app_state.flags().is_enabled("post.reactions", &user)` before serving a feature still being
+/// rolled out; every admin change drops the cached copy, so it applies on the next request.
+pub struct FeatureFlags {
+    app_state: Arc<AppState>,
+}
+
+impl FeatureFlags {
+    pub fn new(app_state: Arc<AppState>) -> Self {
+        Self { app_state }
+    }
+    /// Every flag, from the cache when it is there.
+    pub async fn all(&self) -> Result<Vec<FeatureFlag>, AppError> {
+        if let Ok(Some(flags)) = self.app_state.redis_client.get_feature_flags().await {
+            return Ok(flags);
+        }
+        let flags = self.app_state.db.feature_flags.get_feature_flags().await?;
+        let _ = self.app_state.redis_client.set_feature_flags(&flags, self.app_state.env.feature_flag_cache_ttl).await;
+        Ok(flags)
+    }
+    /// Whether `user` gets the feature behind `key`. Unknown flags, and flags that can't be loaded,
+    /// are off, so a feature never shows up by accident.
+    pub async fn is_enabled(&self, key: &str, user: &User) -> bool {
+        let flags = match self.all().await {
+            Ok(flags) => flags,
+            Err(e) => {
+                warn!("Failed to load feature flags, treating {} as off: {}", key, e);
+                return false;
+            }
+        };
+        let Some(flag) = flags.into_iter().find(|flag| flag.key == key) else {
+            return false;
+        };
+        if !flag.enabled || !in_rollout(&flag.key, &user.id, flag.percentage) {
+            return false;
+        }
+        if flag.roles.is_empty() {
+            return true;
+        }
+        match self.app_state.db.roles.get_role_name_by_id(user.role_id).await {
+            Ok(Some(role)) => flag.roles.iter().any(|name| name == role.get_value()),
+            Ok(None) => false,
+            Err(e) => {
+                warn!("Failed to load the role of user {}, treating {} as off: {}", user.id, key, e);
+                false
+            }
+        }
+    }
+
+    pub async fn create(&self, actor: &User, body: FeatureFlagRequest) -> Result<FeatureFlag, AppError> {
+        let new_flag = NewFeatureFlag {
+            key: body.key,
+            description: body.description,
+            enabled: body.enabled,
+            roles: body.roles,
+            percentage: body.percentage,
+            created_by: actor.id,
+        };
+        let flag = self.app_state.db.feature_flags.save_feature_flag(new_flag).await?;
+        let _ = self.app_state.redis_client.delete_feature_flags().await;
+        Ok(flag)
+    }
+    pub async fn update(&self, key: &str, body: FeatureFlagUpdateRequest) -> Result<FeatureFlag, AppError> {
+        let flag = self.app_state.db.feature_flags.update_feature_flag(key, body).await?;
+        let _ = self.app_state.redis_client.delete_feature_flags().await;
+        Ok(flag)
+    }
+    pub async fn delete(&self, key: &str) -> Result<(), AppError> {
+        self.app_state.db.feature_flags.delete_feature_flag(key).await?;
+        let _ = self.app_state.redis_client.delete_feature_flags().await;
+        Ok(())
+    }
+}
+
+/// Puts every user in one of 100 buckets per flag. The bucket only depends on the flag and the
+/// user, so raising the percentage keeps the users that already have the feature, and each flag
+/// rolls out to a different set of users.
+fn in_rollout(key: &str, user_id: &Uuid, percentage: i32) -> bool {
+    if percentage >= 100 {
+        return true;
+    }
+    let digest = Sha256::new()
+        .chain_update(key.as_bytes())
+        .chain_update(user_id.as_bytes())
+        .finalize();
+    let bucket = u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]) % 100;
+    (bucket as i32) < percentage
+}
\ No newline at end of file
@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+use crate::modules::post::model::PostLicense;
+
+fn validate_feed_url(value: &str) -> Result<(), ValidationError> {
+    match reqwest::Url::parse(value) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.host().is_some() => Ok(()),
+        _ => {
+            let mut error = ValidationError::new("invalid_url");
+            error.message = Some("Url must be an absolute http or https url".into());
+            Err(error)
+        }
+    }
+}
+
+/// Body of `POST /api/feed-import/sources`. The feed is fetched for the first time on the next
+/// pass of the fetcher.
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
+pub struct FeedSourceRequest {
+    #[validate(length(max = 2048, message = "Url must be at most 2048 characters"))]
+    #[validate(custom(function = "validate_feed_url"))]
+    pub url: String,
+}
+
+/// Body of `POST /api/feed-import/drafts/{id}/publish`, `{}` to publish the draft as it is. What is
+/// left out is taken from the draft, and the result has to pass the same checks as any new post.
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
+pub struct FeedDraftPublishRequest {
+    pub title: Option<String>,
+    pub content: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub license: Option<PostLicense>,
+}
\ No newline at end of file
@@ -0,0 +1,91 @@
+use std::sync::Arc;
+use axum::{middleware, Router, routing::{delete, get, post}, Extension, response::IntoResponse, http::StatusCode};
+use uuid::Uuid;
+use crate::{
+    AppState,
+    dto::{HttpResult, SuccessResponse},
+    error::{PathParser, ValidatedJson},
+    middleware::{AuthenticatedUser, permission::{check_permission, Permission}},
+    modules::feed_import::dto::{FeedDraftPublishRequest, FeedSourceRequest},
+};
+
+pub fn feed_import_router() -> Router {
+    Router::new()
+        .route("/sources", post(feed_source_add).get(feed_source_list).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::FeedImport.to_string())
+        })))
+        .route("/sources/{id}", delete(feed_source_remove).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::FeedImport.to_string())
+        })))
+        .route("/drafts", get(feed_draft_list).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::FeedImport.to_string())
+        })))
+        .route("/drafts/{id}", delete(feed_draft_dismiss).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::FeedImport.to_string())
+        })))
+        .route("/drafts/{id}/publish", post(feed_draft_publish).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::FeedImport.to_string())
+        })))
+}
+
+async fn feed_source_add(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    ValidatedJson(body): ValidatedJson<FeedSourceRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let source = app_state.feed_import_service().add_source(&user_auth.user, body).await?;
+    Ok((
+        StatusCode::CREATED,
+        SuccessResponse::new("Successfully added the feed.", Some(source))
+    ))
+}
+async fn feed_source_list(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+) -> HttpResult<impl IntoResponse> {
+    let sources = app_state.feed_import_service().sources(&user_auth.user).await?;
+    Ok(
+        SuccessResponse::new("Getting feed list data", Some(sources))
+    )
+}
+async fn feed_source_remove(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(source_id): PathParser<Uuid>,
+) -> HttpResult<impl IntoResponse> {
+    app_state.feed_import_service().remove_source(&user_auth.user, source_id).await?;
+    Ok(
+        SuccessResponse::<()>::new("Successfully removed the feed.", None)
+    )
+}
+async fn feed_draft_list(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+) -> HttpResult<impl IntoResponse> {
+    let drafts = app_state.feed_import_service().drafts(&user_auth.user).await?;
+    Ok(
+        SuccessResponse::new("Getting imported draft list data", Some(drafts))
+    )
+}
+async fn feed_draft_publish(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(draft_id): PathParser<Uuid>,
+    ValidatedJson(body): ValidatedJson<FeedDraftPublishRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let post = app_state.feed_import_service().publish(&user_auth.user, draft_id, body).await?;
+    Ok((
+        StatusCode::CREATED,
+        SuccessResponse::new("Successfully published the imported draft.", Some(post))
+    ))
+}
+async fn feed_draft_dismiss(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(draft_id): PathParser<Uuid>,
+) -> HttpResult<impl IntoResponse> {
+    app_state.feed_import_service().dismiss(&user_auth.user, draft_id).await?;
+    Ok(
+        SuccessResponse::<()>::new("Successfully dismissed the imported draft.", None)
+    )
+}
\ No newline at end of file
@@ -0,0 +1,5 @@
+pub mod dto;
+pub mod model;
+pub mod parser;
+pub mod service;
+pub mod handler;
\ No newline at end of file
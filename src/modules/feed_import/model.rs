@@ -0,0 +1,290 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Type, query, query_as, query_scalar};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use crate::{db::DBClient, error::RepositoryError};
+
+#[derive(Serialize, Deserialize, FromRow, Clone, ToSchema)]
+pub struct FeedSource {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub url: String,
+    /// The feed's own title, once it was fetched.
+    pub title: Option<String>,
+    /// Validators for the next conditional fetch.
+    #[serde(skip_serializing)]
+    pub etag: Option<String>,
+    #[serde(skip_serializing)]
+    pub last_modified: Option<String>,
+    pub last_fetched_at: Option<DateTime<Utc>>,
+    /// Why the last fetch failed, `None` once one succeeds.
+    pub last_error: Option<String>,
+    /// Failed fetches in a row, each one doubles the wait before the next.
+    pub failure_count: i32,
+    pub next_fetch_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Copy, PartialEq, Debug, ToSchema)]
+#[sqlx(type_name = "feed_draft_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum FeedDraftStatus {
+    Draft,
+    Published,
+    Dismissed,
+}
+
+/// A feed entry waiting to become a post, fitted to what a post allows.
+#[derive(Serialize, Deserialize, FromRow, Clone, ToSchema)]
+pub struct FeedDraft {
+    pub id: Uuid,
+    pub source_id: Uuid,
+    pub user_id: Uuid,
+    pub guid: String,
+    /// The entry on the original site.
+    pub link: Option<String>,
+    pub title: String,
+    pub content: String,
+    pub tags: Vec<String>,
+    pub status: FeedDraftStatus,
+    /// The post it was published as.
+    pub post_id: Option<Uuid>,
+    pub entry_published_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+pub struct NewFeedDraft {
+    pub guid: String,
+    pub link: Option<String>,
+    pub title: String,
+    pub content: String,
+    pub tags: Vec<String>,
+    pub entry_published_at: Option<DateTime<Utc>>,
+}
+
+/// What a successful fetch leaves on the source for the next one.
+pub struct FeedFetch {
+    pub title: Option<String>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub next_fetch_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait FeedImportRepository: Send + Sync {
+    async fn save_feed_source(&self, user_id: Uuid, url: &str) -> Result<FeedSource, RepositoryError>;
+    async fn count_feed_sources(&self, user_id: Uuid) -> Result<i64, RepositoryError>;
+    async fn get_feed_sources(&self, user_id: Uuid) -> Result<Vec<FeedSource>, RepositoryError>;
+    /// Removes the source with the drafts it left, `false` when `user_id` has no such source.
+    async fn delete_feed_source(&self, id: Uuid, user_id: Uuid) -> Result<bool, RepositoryError>;
+    /// Up to `limit` sources whose next fetch is due, of users that aren't banned, moved `lease_secs`
+    /// ahead so another pass leaves them alone while they are fetched.
+    async fn claim_feed_sources(&self, limit: i64, lease_secs: i64) -> Result<Vec<FeedSource>, RepositoryError>;
+    async fn record_feed_success(&self, id: Uuid, fetch: FeedFetch) -> Result<(), RepositoryError>;
+    async fn record_feed_failure(&self, id: Uuid, error: &str, next_fetch_at: DateTime<Utc>) -> Result<(), RepositoryError>;
+    /// Adds the entries not seen on this source before, by GUID, and returns how many that was.
+    async fn save_feed_drafts(&self, source_id: Uuid, user_id: Uuid, drafts: Vec<NewFeedDraft>) -> Result<u64, RepositoryError>;
+    /// The drafts still waiting, newest first, at most `limit`.
+    async fn get_feed_drafts(&self, user_id: Uuid, limit: i64) -> Result<Vec<FeedDraft>, RepositoryError>;
+    async fn get_feed_draft(&self, id: Uuid, user_id: Uuid) -> Result<Option<FeedDraft>, RepositoryError>;
+    /// Moves a waiting draft to `status`, `None` when there is no such draft or it was already
+    /// published or dismissed.
+    async fn take_feed_draft(&self, id: Uuid, user_id: Uuid, status: FeedDraftStatus) -> Result<Option<FeedDraft>, RepositoryError>;
+    /// Puts a taken draft back, or records the post it became.
+    async fn release_feed_draft(&self, id: Uuid, post_id: Option<Uuid>) -> Result<(), RepositoryError>;
+}
+
+#[async_trait]
+impl FeedImportRepository for DBClient {
+    async fn save_feed_source(&self, user_id: Uuid, url: &str) -> Result<FeedSource, RepositoryError> {
+        let _timer = self.time_query("feed_import.save_feed_source");
+        let source = query_as!(
+            FeedSource,
+            r#"
+                INSERT INTO feed_sources (user_id, url)
+                VALUES ($1, $2)
+                RETURNING id, user_id, url, title, etag, last_modified, last_fetched_at, last_error, failure_count, next_fetch_at, created_at, updated_at;
+            "#,
+            user_id,
+            url,
+        ).fetch_one(&self.pool).await?;
+        Ok(source)
+    }
+    async fn count_feed_sources(&self, user_id: Uuid) -> Result<i64, RepositoryError> {
+        let _timer = self.time_query("feed_import.count_feed_sources");
+        let count = query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM feed_sources WHERE user_id = $1;"#,
+            user_id,
+        ).fetch_one(&self.pool).await?;
+        Ok(count)
+    }
+    async fn get_feed_sources(&self, user_id: Uuid) -> Result<Vec<FeedSource>, RepositoryError> {
+        let _timer = self.time_query("feed_import.get_feed_sources");
+        let sources = query_as!(
+            FeedSource,
+            r#"
+                SELECT id, user_id, url, title, etag, last_modified, last_fetched_at, last_error, failure_count, next_fetch_at, created_at, updated_at
+                FROM feed_sources
+                WHERE user_id = $1
+                ORDER BY created_at;
+            "#,
+            user_id,
+        ).fetch_all(&self.pool).await?;
+        Ok(sources)
+    }
+    async fn delete_feed_source(&self, id: Uuid, user_id: Uuid) -> Result<bool, RepositoryError> {
+        let _timer = self.time_query("feed_import.delete_feed_source");
+        let result = query!(
+            r#"DELETE FROM feed_sources WHERE id = $1 AND user_id = $2;"#,
+            id,
+            user_id,
+        ).execute(&self.pool).await?;
+        Ok(result.rows_affected() > 0)
+    }
+    async fn claim_feed_sources(&self, limit: i64, lease_secs: i64) -> Result<Vec<FeedSource>, RepositoryError> {
+        let _timer = self.time_query("feed_import.claim_feed_sources");
+        let sources = query_as!(
+            FeedSource,
+            r#"
+                UPDATE feed_sources
+                SET next_fetch_at = NOW() + make_interval(secs => $2)
+                WHERE id IN (
+                    SELECT f.id FROM feed_sources f
+                    JOIN users u ON u.id = f.user_id
+                    WHERE f.next_fetch_at <= NOW() AND NOT u.is_banned
+                    ORDER BY f.next_fetch_at
+                    LIMIT $1
+                    FOR UPDATE OF f SKIP LOCKED
+                )
+                RETURNING id, user_id, url, title, etag, last_modified, last_fetched_at, last_error, failure_count, next_fetch_at, created_at, updated_at;
+            "#,
+            limit,
+            lease_secs as f64,
+        ).fetch_all(&self.pool).await?;
+        Ok(sources)
+    }
+    async fn record_feed_success(&self, id: Uuid, fetch: FeedFetch) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("feed_import.record_feed_success");
+        query!(
+            r#"
+                UPDATE feed_sources
+                SET title = COALESCE($2, title), etag = $3, last_modified = $4, next_fetch_at = $5,
+                    last_fetched_at = NOW(), last_error = NULL, failure_count = 0, updated_at = NOW()
+                WHERE id = $1;
+            "#,
+            id,
+            fetch.title,
+            fetch.etag,
+            fetch.last_modified,
+            fetch.next_fetch_at,
+        ).execute(&self.pool).await?;
+        Ok(())
+    }
+    async fn record_feed_failure(&self, id: Uuid, error: &str, next_fetch_at: DateTime<Utc>) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("feed_import.record_feed_failure");
+        query!(
+            r#"
+                UPDATE feed_sources
+                SET last_error = $2, next_fetch_at = $3, last_fetched_at = NOW(),
+                    failure_count = failure_count + 1, updated_at = NOW()
+                WHERE id = $1;
+            "#,
+            id,
+            error,
+            next_fetch_at,
+        ).execute(&self.pool).await?;
+        Ok(())
+    }
+    async fn save_feed_drafts(&self, source_id: Uuid, user_id: Uuid, drafts: Vec<NewFeedDraft>) -> Result<u64, RepositoryError> {
+        let _timer = self.time_query("feed_import.save_feed_drafts");
+        let mut transaction = self.pool.begin().await?;
+        let mut saved = 0;
+        for draft in drafts {
+            let result = query!(
+                r#"
+                    INSERT INTO feed_drafts (source_id, user_id, guid, link, title, content, tags, entry_published_at)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                    ON CONFLICT (source_id, guid) DO NOTHING;
+                "#,
+                source_id,
+                user_id,
+                draft.guid,
+                draft.link,
+                draft.title,
+                draft.content,
+                &draft.tags,
+                draft.entry_published_at,
+            ).execute(&mut *transaction).await?;
+            saved += result.rows_affected();
+        }
+        transaction.commit().await?;
+        Ok(saved)
+    }
+    async fn get_feed_drafts(&self, user_id: Uuid, limit: i64) -> Result<Vec<FeedDraft>, RepositoryError> {
+        let _timer = self.time_query("feed_import.get_feed_drafts");
+        let drafts = query_as!(
+            FeedDraft,
+            r#"
+                SELECT id, source_id, user_id, guid, link, title, content, tags, status AS "status: FeedDraftStatus",
+                    post_id, entry_published_at, created_at, updated_at
+                FROM feed_drafts
+                WHERE user_id = $1 AND status = 'draft'
+                ORDER BY created_at DESC, entry_published_at DESC NULLS LAST
+                LIMIT $2;
+            "#,
+            user_id,
+            limit,
+        ).fetch_all(&self.pool).await?;
+        Ok(drafts)
+    }
+    async fn get_feed_draft(&self, id: Uuid, user_id: Uuid) -> Result<Option<FeedDraft>, RepositoryError> {
+        let _timer = self.time_query("feed_import.get_feed_draft");
+        let draft = query_as!(
+            FeedDraft,
+            r#"
+                SELECT id, source_id, user_id, guid, link, title, content, tags, status AS "status: FeedDraftStatus",
+                    post_id, entry_published_at, created_at, updated_at
+                FROM feed_drafts
+                WHERE id = $1 AND user_id = $2;
+            "#,
+            id,
+            user_id,
+        ).fetch_optional(&self.pool).await?;
+        Ok(draft)
+    }
+    async fn take_feed_draft(&self, id: Uuid, user_id: Uuid, status: FeedDraftStatus) -> Result<Option<FeedDraft>, RepositoryError> {
+        let _timer = self.time_query("feed_import.take_feed_draft");
+        let draft = query_as!(
+            FeedDraft,
+            r#"
+                UPDATE feed_drafts
+                SET status = $3, updated_at = NOW()
+                WHERE id = $1 AND user_id = $2 AND status = 'draft'
+                RETURNING id, source_id, user_id, guid, link, title, content, tags, status AS "status: FeedDraftStatus",
+                    post_id, entry_published_at, created_at, updated_at;
+            "#,
+            id,
+            user_id,
+            status as FeedDraftStatus,
+        ).fetch_optional(&self.pool).await?;
+        Ok(draft)
+    }
+    async fn release_feed_draft(&self, id: Uuid, post_id: Option<Uuid>) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("feed_import.release_feed_draft");
+        query!(
+            r#"
+                UPDATE feed_drafts
+                SET post_id = $2, status = CASE WHEN $2::uuid IS NULL THEN 'draft'::feed_draft_status ELSE status END,
+                    updated_at = NOW()
+                WHERE id = $1;
+            "#,
+            id,
+            post_id,
+        ).execute(&self.pool).await?;
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,244 @@
+use chrono::{DateTime, Utc};
+use quick_xml::{Reader, XmlVersion, escape::resolve_predefined_entity, events::{BytesStart, Event}};
+
+/// What the importer reads from an RSS 0.9x to 2.0, RSS 1.0 (RDF) or Atom document.
+pub struct ParsedFeed {
+    pub title: Option<String>,
+    /// In document order, newest first in most feeds.
+    pub entries: Vec<FeedEntry>,
+}
+
+pub struct FeedEntry {
+    /// The RSS `guid` or Atom `id`, else the link, else the title.
+    pub guid: String,
+    pub link: Option<String>,
+    /// HTML in most feeds, like the content.
+    pub title: String,
+    /// The full content when the feed has it, the summary otherwise.
+    pub content: String,
+    pub categories: Vec<String>,
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Default)]
+struct EntryFields {
+    guid: Option<String>,
+    link: Option<String>,
+    title: Option<String>,
+    content: Option<String>,
+    summary: Option<String>,
+    categories: Vec<String>,
+    published: Option<DateTime<Utc>>,
+    updated: Option<DateTime<Utc>>,
+}
+
+impl EntryFields {
+    fn into_entry(self) -> Option<FeedEntry> {
+        let title = self.title.unwrap_or_default();
+        let guid = self.guid.or_else(|| self.link.clone())
+            .or_else(|| (!title.is_empty()).then(|| title.clone()))?;
+        Some(FeedEntry {
+            guid,
+            link: self.link,
+            title,
+            content: self.content.or(self.summary).unwrap_or_default(),
+            categories: self.categories,
+            published_at: self.published.or(self.updated),
+        })
+    }
+}
+
+/// Reads the entries of a feed, `Err` when the document isn't well formed XML or not a feed at all.
+/// Elements are matched by their qualified name, with the prefixes feeds use in practice
+/// (`content:encoded`, `dc:date`); Atom and RSS 1.0 are usually written without one.
+pub fn parse(document: &str) -> Result<ParsedFeed, String> {
+    let mut reader = Reader::from_str(document);
+    let mut path: Vec<String> = Vec::new();
+    let mut is_feed = false;
+    let mut feed_title = None;
+    let mut entry: Option<(usize, EntryFields)> = None;
+    let mut entries = Vec::new();
+    // The element whose text is being collected, with its depth. Markup nested in it, like an Atom
+    // `xhtml` content, adds its text.
+    let mut field: Option<(usize, String)> = None;
+    let mut text = String::new();
+    loop {
+        let event = reader.read_event().map_err(|e| format!("invalid XML at byte {}: {}", reader.error_position(), e))?;
+        match event {
+            Event::Start(element) => {
+                let name = element.name().as_ref().to_string();
+                if path.is_empty() {
+                    is_feed = matches!(name.as_str(), "rss" | "feed" | "rdf:RDF" | "RDF");
+                }
+                path.push(name.clone());
+                let depth = path.len();
+                if field.is_some() {
+                    continue;
+                }
+                if matches!(name.as_str(), "item" | "entry") && entry.is_none() {
+                    entry = Some((depth, EntryFields::default()));
+                    continue;
+                }
+                if let Some((entry_depth, fields)) = entry.as_mut() {
+                    if depth == *entry_depth + 1 {
+                        attribute_fields(&element, fields);
+                        field = Some((depth, name));
+                        text.clear();
+                    }
+                } else if name == "title" && feed_title.is_none() && is_feed_level(&path) {
+                    field = Some((depth, name));
+                    text.clear();
+                }
+            }
+            Event::Empty(element) => {
+                if let Some((entry_depth, fields)) = entry.as_mut()
+                    && field.is_none()
+                    && path.len() == *entry_depth
+                {
+                    attribute_fields(&element, fields);
+                }
+            }
+            Event::Text(content) if field.is_some() => text.push_str(&content.xml10_content()),
+            Event::CData(content) if field.is_some() => text.push_str(&content.xml10_content()),
+            Event::GeneralRef(reference) if field.is_some() => {
+                match reference.resolve_char_ref() {
+                    Ok(Some(character)) => text.push(character),
+                    _ => text.push_str(resolve_predefined_entity(&reference.xml10_content()).unwrap_or_default()),
+                }
+            }
+            Event::End(_) => {
+                let depth = path.len();
+                path.pop();
+                if field.as_ref().is_some_and(|(field_depth, _)| *field_depth == depth) {
+                    let (_, name) = field.take().expect("field checked above");
+                    let value = text.trim().to_string();
+                    match entry.as_mut() {
+                        Some((_, fields)) => text_field(&name, value, fields),
+                        None => feed_title = Some(value).filter(|title| !title.is_empty()),
+                    }
+                } else if entry.as_ref().is_some_and(|(entry_depth, _)| *entry_depth == depth) {
+                    let (_, fields) = entry.take().expect("entry checked above");
+                    entries.extend(fields.into_entry());
+                }
+            }
+            Event::Eof if path.is_empty() => break,
+            Event::Eof => return Err("the document ends before its elements are closed".to_string()),
+            _ => {}
+        }
+    }
+    if !is_feed {
+        return Err("not an RSS or Atom feed".to_string());
+    }
+    Ok(ParsedFeed { title: feed_title, entries })
+}
+
+/// The `title` of the channel or feed itself, not of an image or an entry.
+fn is_feed_level(path: &[String]) -> bool {
+    match &path[..path.len() - 1] {
+        [root] => root == "feed",
+        [_, channel] => channel == "channel",
+        _ => false,
+    }
+}
+
+fn text_field(name: &str, value: String, fields: &mut EntryFields) {
+    if value.is_empty() {
+        return;
+    }
+    match name {
+        "title" => fields.title = Some(value),
+        "guid" | "id" => fields.guid = Some(value),
+        "link" if fields.link.is_none() => fields.link = Some(value),
+        "content:encoded" | "content" => fields.content = Some(value),
+        "description" | "summary" => fields.summary = Some(value),
+        "category" | "dc:subject" => fields.categories.push(value),
+        "pubDate" => fields.published = DateTime::parse_from_rfc2822(&value).ok().map(|date| date.to_utc()),
+        "published" | "dc:date" => fields.published = DateTime::parse_from_rfc3339(&value).ok().map(|date| date.to_utc()),
+        "updated" => fields.updated = DateTime::parse_from_rfc3339(&value).ok().map(|date| date.to_utc()),
+        _ => {}
+    }
+}
+
+/// Atom keeps the link and the categories in attributes: `<link rel="alternate" href="..."/>` and
+/// `<category term="..."/>`.
+fn attribute_fields(element: &BytesStart, fields: &mut EntryFields) {
+    let attribute = |name: &str| element.try_get_attribute(name).ok().flatten()
+        .and_then(|attribute| attribute.normalized_value(XmlVersion::Implicit1_0).ok().map(|value| value.trim().to_string()))
+        .filter(|value| !value.is_empty());
+    match element.name().as_ref() {
+        "link" => {
+            let rel = attribute("rel");
+            if fields.link.is_none() && rel.as_deref().is_none_or(|rel| rel == "alternate") {
+                fields.link = attribute("href");
+            }
+        }
+        "category" => fields.categories.extend(attribute("term")),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_rss_items() {
+        let feed = parse(r#"<?xml version="1.0"?>
+            <rss version="2.0" xmlns:content="http://purl.org/rss/1.0/modules/content/">
+              <channel>
+                <title>Clark &amp; Lois</title>
+                <image><title>Logo</title></image>
+                <item>
+                  <title>Up, up &#38; away</title>
+                  <link>https://example.com/up</link>
+                  <guid isPermaLink="false">post-1</guid>
+                  <description>Short</description>
+                  <content:encoded><![CDATA[<p>The <b>full</b> story</p>]]></content:encoded>
+                  <category>Flight</category>
+                  <pubDate>Tue, 02 Sep 2025 10:00:00 +0200</pubDate>
+                </item>
+                <item><title>No guid</title><link>https://example.com/2</link></item>
+                <item><description>Nothing to tell it apart</description></item>
+              </channel>
+            </rss>"#).unwrap();
+        assert_eq!(feed.title.as_deref(), Some("Clark & Lois"));
+        assert_eq!(feed.entries.len(), 2);
+        let first = &feed.entries[0];
+        assert_eq!((first.guid.as_str(), first.title.as_str()), ("post-1", "Up, up & away"));
+        assert_eq!(first.content, "<p>The <b>full</b> story</p>");
+        assert_eq!(first.categories, ["Flight"]);
+        assert_eq!(first.published_at.unwrap().to_rfc3339(), "2025-09-02T08:00:00+00:00");
+        assert_eq!(feed.entries[1].guid, "https://example.com/2");
+    }
+
+    #[test]
+    fn reads_atom_entries() {
+        let feed = parse(r#"<feed xmlns="http://www.w3.org/2005/Atom">
+              <title>Daily Planet</title>
+              <entry>
+                <id>urn:uuid:1</id>
+                <title type="html">Extra &lt;b&gt;edition&lt;/b&gt;</title>
+                <link rel="edit" href="https://example.com/edit/1"/>
+                <link href="https://example.com/1"/>
+                <category term="news"/>
+                <updated>2025-09-02T10:00:00Z</updated>
+                <summary>Summary</summary>
+                <content type="xhtml"><div xmlns="http://www.w3.org/1999/xhtml">Full <em>text</em></div></content>
+              </entry>
+            </feed>"#).unwrap();
+        assert_eq!(feed.title.as_deref(), Some("Daily Planet"));
+        let entry = &feed.entries[0];
+        assert_eq!(entry.guid, "urn:uuid:1");
+        assert_eq!(entry.title, "Extra <b>edition</b>");
+        assert_eq!(entry.link.as_deref(), Some("https://example.com/1"));
+        assert_eq!(entry.categories, ["news"]);
+        assert_eq!(entry.content, "Full text");
+        assert!(entry.published_at.is_some());
+    }
+
+    #[test]
+    fn refuses_other_documents() {
+        assert!(parse("<html><body>Not a feed</body></html>").is_err());
+        assert!(parse("<rss><channel><item>").is_err());
+    }
+}
\ No newline at end of file
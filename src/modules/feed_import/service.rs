@@ -0,0 +1,217 @@
+use std::{sync::Arc, time::Duration};
+use chrono::{Duration as ChronoDuration, Utc};
+use reqwest::{StatusCode, Url, header::{ACCEPT, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, LOCATION}};
+use uuid::Uuid;
+use validator::Validate;
+use crate::{
+    AppState,
+    error::{AppError, ErrorMessage},
+    modules::{
+        feed_import::{
+            dto::{FeedDraftPublishRequest, FeedSourceRequest},
+            model::{FeedDraft, FeedDraftStatus, FeedFetch, FeedSource, NewFeedDraft},
+            parser::{self, FeedEntry, ParsedFeed},
+        },
+        post::{dto::PostRequest, model::Post},
+        user::model::User,
+    },
+    utils::{html, metrics, outbound},
+};
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(15);
+/// How long a claimed source is left alone by other passes while it is fetched.
+const FETCH_LEASE_SECS: i64 = 600;
+/// Failures back off up to this, unless `FEED_IMPORT_FETCH_INTERVAL` is longer already.
+const MAX_BACKOFF_SECS: u64 = 86400;
+const DRAFT_LIST_LIMIT: i64 = 100;
+/// Limits of a post, see `PostRequest`.
+const TITLE_MAX_CHARS: usize = 20;
+const CONTENT_MAX_CHARS: usize = 200;
+const TAG_LENGTH: std::ops::RangeInclusive<usize> = 4..=20;
+const MAX_TAGS: usize = 5;
+const DEFAULT_TAG: &str = "imported";
+
+/// Mirrors RSS and Atom feeds into draft posts. Users register feed urls, `jobs::feed_import`
+/// fetches the ones that are due and turns entries it hasn't seen before into drafts, and the user
+/// publishes or dismisses each draft. Entries are told apart by their GUID, so one is only ever
+/// imported once per feed.
+pub struct FeedImportService {
+    app_state: Arc<AppState>,
+}
+
+impl FeedImportService {
+    pub fn new(app_state: Arc<AppState>) -> Self {
+        Self { app_state }
+    }
+    /// At most `FEED_IMPORT_MAX_SOURCES` per user, a url registered twice is a conflict.
+    pub async fn add_source(&self, user: &User, body: FeedSourceRequest) -> Result<FeedSource, AppError> {
+        let max_sources = self.app_state.env.feed_import_max_sources;
+        if self.app_state.db.feed_imports.count_feed_sources(user.id).await? >= max_sources {
+            return Err(AppError::unique_constraint_violation(ErrorMessage::FeedSourceLimit(max_sources)));
+        }
+        Ok(self.app_state.db.feed_imports.save_feed_source(user.id, body.url.trim()).await?)
+    }
+    pub async fn sources(&self, user: &User) -> Result<Vec<FeedSource>, AppError> {
+        Ok(self.app_state.db.feed_imports.get_feed_sources(user.id).await?)
+    }
+    /// Drafts not published yet go with it, posts already published from it stay.
+    pub async fn remove_source(&self, user: &User, source_id: Uuid) -> Result<(), AppError> {
+        if !self.app_state.db.feed_imports.delete_feed_source(source_id, user.id).await? {
+            return Err(AppError::not_found(ErrorMessage::DataNotFound));
+        }
+        Ok(())
+    }
+    pub async fn drafts(&self, user: &User) -> Result<Vec<FeedDraft>, AppError> {
+        Ok(self.app_state.db.feed_imports.get_feed_drafts(user.id, DRAFT_LIST_LIMIT).await?)
+    }
+    /// Creates the post like `POST /api/post` would, with the draft filling in what `body` leaves
+    /// out. The draft is taken first, so publishing it twice at once makes a single post.
+    pub async fn publish(&self, user: &User, draft_id: Uuid, body: FeedDraftPublishRequest) -> Result<Post, AppError> {
+        let draft = self.app_state.db.feed_imports.get_feed_draft(draft_id, user.id).await?
+            .filter(|draft| draft.status == FeedDraftStatus::Draft)
+            .ok_or(AppError::not_found(ErrorMessage::DataNotFound))?;
+        let request = PostRequest {
+            title: body.title.unwrap_or(draft.title),
+            content: body.content.unwrap_or(draft.content),
+            tags: body.tags.unwrap_or(draft.tags),
+            license: body.license,
+            organization_id: None,
+        };
+        request.validate()?;
+        self.app_state.db.feed_imports.take_feed_draft(draft_id, user.id, FeedDraftStatus::Published).await?
+            .ok_or(AppError::not_found(ErrorMessage::DataNotFound))?;
+        match self.app_state.post_service().create(user, request).await {
+            Ok(post) => {
+                self.app_state.db.feed_imports.release_feed_draft(draft_id, Some(post.id)).await?;
+                Ok(post)
+            }
+            Err(e) => {
+                self.app_state.db.feed_imports.release_feed_draft(draft_id, None).await?;
+                Err(e)
+            }
+        }
+    }
+    /// The entry stays known, a later fetch won't bring it back.
+    pub async fn dismiss(&self, user: &User, draft_id: Uuid) -> Result<(), AppError> {
+        self.app_state.db.feed_imports.take_feed_draft(draft_id, user.id, FeedDraftStatus::Dismissed).await?
+            .ok_or(AppError::not_found(ErrorMessage::DataNotFound))?;
+        Ok(())
+    }
+
+    /// Fetches up to `FEED_IMPORT_BATCH_SIZE` sources that are due and returns how many it fetched.
+    /// A failing feed is retried later and doesn't hold up the others.
+    pub async fn fetch_due(&self) -> Result<usize, AppError> {
+        let env = &self.app_state.env;
+        let sources = self.app_state.db.feed_imports.claim_feed_sources(env.feed_import_batch_size, FETCH_LEASE_SECS).await?;
+        for source in &sources {
+            match self.fetch(source).await {
+                Ok(imported) => {
+                    metrics::increment_counter("feed_import_fetches_total", &[("result", "success")]);
+                    metrics::increment_counter_by("feed_import_drafts_total", &[], imported);
+                }
+                Err(e) => {
+                    metrics::increment_counter("feed_import_fetches_total", &[("result", "failure")]);
+                    let failures = (source.failure_count.max(0) as u32 + 1).min(6);
+                    let backoff = env.feed_import_fetch_interval.saturating_mul(1 << failures)
+                        .min(MAX_BACKOFF_SECS.max(env.feed_import_fetch_interval));
+                    let next_fetch_at = Utc::now() + ChronoDuration::seconds(backoff as i64);
+                    let error: String = e.chars().take(1000).collect();
+                    self.app_state.db.feed_imports.record_feed_failure(source.id, &error, next_fetch_at).await?;
+                }
+            }
+        }
+        Ok(sources.len())
+    }
+    /// One conditional fetch of `source`, returns how many drafts it added.
+    async fn fetch(&self, source: &FeedSource) -> Result<u64, String> {
+        let env = &self.app_state.env;
+        let url = Url::parse(&source.url).map_err(|e| format!("invalid url: {}", e))?;
+        let client = outbound::guarded_client(&url, FETCH_TIMEOUT, env.feed_import_private_hosts).await?;
+        let mut request = client.get(url)
+            .header(ACCEPT, "application/rss+xml, application/atom+xml, application/xml;q=0.9, text/xml;q=0.8, */*;q=0.1");
+        if let Some(etag) = &source.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &source.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        let status = response.status();
+        let next_fetch_at = Utc::now() + ChronoDuration::seconds(env.feed_import_fetch_interval as i64);
+        if status == StatusCode::NOT_MODIFIED {
+            self.record_success(source, None, source.etag.clone(), source.last_modified.clone(), next_fetch_at).await?;
+            return Ok(0);
+        }
+        if status.is_redirection() {
+            let location = response.headers().get(LOCATION).and_then(|value| value.to_str().ok()).unwrap_or_default();
+            return Err(format!("the feed answered {} and moved to {}, register that url instead", status, location));
+        }
+        if !status.is_success() {
+            return Err(format!("the feed answered {}", status));
+        }
+        let header = |name| response.headers().get(name).and_then(|value| value.to_str().ok()).map(String::from);
+        let (etag, last_modified) = (header(ETAG), header(LAST_MODIFIED));
+        let body = outbound::read_limited(response, env.feed_import_max_bytes).await?;
+        let ParsedFeed { title, entries } = parser::parse(&String::from_utf8_lossy(&body))?;
+        let drafts: Vec<NewFeedDraft> = entries.into_iter()
+            .take(env.feed_import_max_entries)
+            .map(new_draft)
+            .collect();
+        let imported = self.app_state.db.feed_imports.save_feed_drafts(source.id, source.user_id, drafts).await
+            .map_err(|e| e.to_string())?;
+        let title = title.map(|title| truncate(&html::to_text(&title), 200));
+        self.record_success(source, title, etag, last_modified, next_fetch_at).await?;
+        Ok(imported)
+    }
+    async fn record_success(
+        &self,
+        source: &FeedSource,
+        title: Option<String>,
+        etag: Option<String>,
+        last_modified: Option<String>,
+        next_fetch_at: chrono::DateTime<Utc>,
+    ) -> Result<(), String> {
+        // Validators too long for their columns are dropped, the next fetch is unconditional.
+        let fetch = FeedFetch {
+            title,
+            etag: etag.filter(|etag| etag.len() <= 500),
+            last_modified: last_modified.filter(|last_modified| last_modified.len() <= 100),
+            next_fetch_at,
+        };
+        self.app_state.db.feed_imports.record_feed_success(source.id, fetch).await.map_err(|e| e.to_string())
+    }
+}
+
+/// The entry as plain text cut to what a post allows, with its categories as tags. Titles under
+/// four characters are kept, the user has to give the post a title of their own to publish it.
+fn new_draft(entry: FeedEntry) -> NewFeedDraft {
+    let mut tags: Vec<String> = Vec::new();
+    for category in &entry.categories {
+        let tag: String = html::to_text(category).to_lowercase().split_whitespace().collect::<Vec<_>>().join("-")
+            .chars().filter(|c| c.is_alphanumeric() || *c == '-').collect();
+        if TAG_LENGTH.contains(&tag.len()) && !tags.contains(&tag) && tags.len() < MAX_TAGS {
+            tags.push(tag);
+        }
+    }
+    if tags.is_empty() {
+        tags.push(DEFAULT_TAG.to_string());
+    }
+    NewFeedDraft {
+        guid: truncate(&entry.guid, 1000),
+        link: entry.link.filter(|link| link.len() <= 2048),
+        title: truncate(&html::to_text(&entry.title), TITLE_MAX_CHARS),
+        content: truncate(&html::to_text(&entry.content), CONTENT_MAX_CHARS),
+        tags,
+        entry_published_at: entry.published_at,
+    }
+}
+
+/// At most `max` characters, the last one an ellipsis when something was cut.
+fn truncate(value: &str, max: usize) -> String {
+    if value.chars().count() <= max {
+        return value.to_string();
+    }
+    let mut cut: String = value.chars().take(max - 1).collect::<String>().trim_end().to_string();
+    cut.push('…');
+    cut
+}
\ No newline at end of file
@@ -0,0 +1,28 @@
+use std::sync::Arc;
+use axum::{middleware, Router, routing::post, Extension, response::IntoResponse, http::StatusCode};
+use crate::{
+    AppState,
+    dto::{HttpResult, SuccessResponse},
+    error::ValidatedJson,
+    middleware::{AuthenticatedUser, permission::{check_permission, Permission}},
+    modules::invitation::dto::InvitationRequest,
+};
+
+pub fn invitation_admin_router() -> Router {
+    Router::new()
+        .route("/invitations", post(invitation_create).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminInvitationCreate.to_string())
+        })))
+}
+
+async fn invitation_create(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    ValidatedJson(body): ValidatedJson<InvitationRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let invitation = app_state.auth_service().invite(&user_auth.user, &body.email).await?;
+    Ok((
+        StatusCode::CREATED,
+        SuccessResponse::new("Successfully sent the invitation.", Some(invitation))
+    ))
+}
\ No newline at end of file
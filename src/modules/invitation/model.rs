@@ -0,0 +1,73 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, query_as};
+use uuid::Uuid;
+use crate::{db::DBClient, error::RepositoryError};
+
+#[derive(Serialize, FromRow, Clone)]
+pub struct Invitation {
+    pub id: Uuid,
+    pub email: String,
+    /// Only ever sent to `email`, never returned by the API.
+    #[serde(skip_serializing)]
+    pub token: String,
+    pub invited_by: Option<Uuid>,
+    pub expires_at: DateTime<Utc>,
+    pub consumed_at: Option<DateTime<Utc>>,
+    pub consumed_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Invitation {
+    /// Whether a sign-up with `email` may use this invitation right now.
+    pub fn accepts(&self, email: &str) -> bool {
+        self.consumed_at.is_none() && self.expires_at > Utc::now() && self.email.eq_ignore_ascii_case(email)
+    }
+}
+
+pub struct NewInvitation<'a> {
+    pub email: &'a str,
+    pub token: &'a str,
+    pub invited_by: Uuid,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Invitations are consumed by `UserRepository::save_user`, in the transaction creating the account.
+#[async_trait]
+pub trait InvitationRepository: Send + Sync {
+    async fn save_invitation<'a>(&self, data: NewInvitation<'a>) -> Result<Invitation, RepositoryError>;
+    async fn get_invitation_by_token(&self, token: &str) -> Result<Option<Invitation>, RepositoryError>;
+}
+
+#[async_trait]
+impl InvitationRepository for DBClient {
+    async fn save_invitation<'a>(&self, data: NewInvitation<'a>) -> Result<Invitation, RepositoryError> {
+        let _timer = self.time_query("invitation.save_invitation");
+        let invitation = query_as!(
+            Invitation,
+            r#"
+                INSERT INTO invitations (email, token, invited_by, expires_at)
+                VALUES ($1, $2, $3, $4)
+                RETURNING id, email, token, invited_by, expires_at, consumed_at, consumed_by, created_at;
+            "#,
+            data.email,
+            data.token,
+            data.invited_by,
+            data.expires_at,
+        ).fetch_one(&self.pool).await?;
+        Ok(invitation)
+    }
+    async fn get_invitation_by_token(&self, token: &str) -> Result<Option<Invitation>, RepositoryError> {
+        let _timer = self.time_query("invitation.get_invitation_by_token");
+        let invitation = query_as!(
+            Invitation,
+            r#"
+                SELECT id, email, token, invited_by, expires_at, consumed_at, consumed_by, created_at
+                FROM invitations WHERE token = $1;
+            "#,
+            token,
+        ).fetch_optional(&self.pool).await?;
+        Ok(invitation)
+    }
+}
\ No newline at end of file
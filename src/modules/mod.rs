@@ -7,4 +7,25 @@ pub mod user_action_token;
 pub mod post;
 pub mod comment;
 pub mod refresh_token;
-pub mod redis;
\ No newline at end of file
+pub mod redis;
+pub mod audit_log;
+pub mod admin;
+pub mod report;
+pub mod aggregate;
+pub mod waitlist;
+pub mod webhook;
+pub mod event;
+pub mod feature_flag;
+pub mod invitation;
+pub mod session;
+pub mod activity;
+pub mod velocity;
+pub mod search;
+pub mod organization;
+pub mod digest;
+pub mod analytics;
+pub mod announcement;
+pub mod tos;
+pub mod retention;
+pub mod data_export;
+pub mod feed_import;
\ No newline at end of file
@@ -0,0 +1,76 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
+use crate::{dto::require_any_field, modules::{data_export::model::ExportFormat, organization::model::{OrganizationRole, OrganizationUsageMonth}}};
+
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
+pub struct OrganizationRequest {
+    #[validate(length(min = 2, max = 100, message = "Name must be between 2 and 100 characters"))]
+    pub name: String,
+    #[validate(length(max = 255, message = "Description must be at most 255 characters"))]
+    #[serde(default)]
+    pub description: String,
+    /// One of `DATA_REGIONS`, the first of them when missing. It can't be changed later.
+    #[validate(length(min = 1, max = 50, message = "Region must be between 1 and 50 characters"))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+}
+
+/// Body of `PUT /api/organization/{id}`, missing fields keep their value.
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
+#[validate(schema(function = "validate_organization_update", skip_on_field_errors = true))]
+pub struct OrganizationUpdateRequest {
+    #[validate(length(min = 2, max = 100, message = "Name must be between 2 and 100 characters"))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[validate(length(max = 255, message = "Description must be at most 255 characters"))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+fn validate_organization_update(body: &OrganizationUpdateRequest) -> Result<(), ValidationError> {
+    require_any_field(body.name.is_some() || body.description.is_some())
+}
+
+/// Body of `POST /api/organization/{id}/invitations`.
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
+pub struct OrganizationInvitationRequest {
+    pub user_id: Uuid,
+    pub role: OrganizationRole,
+}
+
+/// Body of `PUT /api/organization/{id}/members/{user_id}`.
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
+pub struct OrganizationMemberRequest {
+    pub role: OrganizationRole,
+}
+
+/// Response of `GET /api/organization/{id}/usage`.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct OrganizationUsageResponse {
+    /// The first day of the current month.
+    pub month: NaiveDate,
+    pub requests: i64,
+    /// `None` when unlimited.
+    pub quota: Option<i64>,
+    pub remaining: Option<i64>,
+    pub resets_at: DateTime<Utc>,
+    /// Months as written to the database, newest first. The current one lags behind `requests`
+    /// until the next flush.
+    pub history: Vec<OrganizationUsageMonth>,
+}
+
+/// Body of `PUT /api/admin/organizations/{id}/quota`.
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
+pub struct OrganizationQuotaRequest {
+    /// `null` follows `ORGANIZATION_MONTHLY_REQUEST_QUOTA` again, 0 is unlimited.
+    #[validate(range(min = 0, message = "Quota must not be negative"))]
+    pub monthly_request_quota: Option<i64>,
+}
+/// Body of `POST /api/admin/organizations/{id}/export`.
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
+pub struct OrganizationExportRequest {
+    #[serde(default)]
+    pub format: ExportFormat,
+}
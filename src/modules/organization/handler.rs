@@ -0,0 +1,464 @@
+use std::sync::Arc;
+use axum::{middleware, Router, routing::{delete, get, post, put}, Extension, response::IntoResponse, http::StatusCode};
+use uuid::Uuid;
+use crate::{
+    AppState,
+    dto::{HttpResult, SuccessResponse},
+    error::{PathParser, ValidatedJson},
+    middleware::{AuthenticatedUser, permission::{check_permission, Permission}},
+    modules::organization::{
+        dto::{OrganizationExportRequest, OrganizationInvitationRequest, OrganizationMemberRequest, OrganizationQuotaRequest, OrganizationRequest, OrganizationUpdateRequest, OrganizationUsageResponse},
+        model::{Organization, OrganizationDetail, OrganizationInvitation, OrganizationMember},
+    },
+};
+
+pub fn organization_router() -> Router {
+    Router::new()
+        .route("/", post(organization_create).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::OrganizationCreate.to_string())
+        })))
+        .route("/invitations", get(organization_invitations).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::OrganizationJoin.to_string())
+        })))
+        .route("/invitations/{id}/accept", post(organization_invitation_accept).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::OrganizationJoin.to_string())
+        })))
+        .route("/invitations/{id}/decline", post(organization_invitation_decline).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::OrganizationJoin.to_string())
+        })))
+        .route("/{id}", get(organization_detail).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::OrganizationDetail.to_string())
+        })))
+        .route("/{id}", put(organization_update).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::OrganizationManage.to_string())
+        })))
+        .route("/{id}", delete(organization_delete).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::OrganizationManage.to_string())
+        })))
+        .route("/{id}/members", get(organization_members).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::OrganizationDetail.to_string())
+        })))
+        .route("/{id}/members/{user_id}", put(organization_member_update).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::OrganizationManage.to_string())
+        })))
+        .route("/{id}/members/{user_id}", delete(organization_member_remove).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::OrganizationManage.to_string())
+        })))
+        .route("/{id}/leave", post(organization_leave).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::OrganizationJoin.to_string())
+        })))
+        .route("/{id}/invitations", post(organization_invite).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::OrganizationManage.to_string())
+        })))
+        .route("/{id}/invitations/{user_id}", delete(organization_invitation_revoke).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::OrganizationManage.to_string())
+        })))
+        .route("/{id}/follow", post(organization_follow).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::OrganizationJoin.to_string())
+        })))
+        .route("/{id}/follow", delete(organization_unfollow).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::OrganizationJoin.to_string())
+        })))
+        .route("/{id}/usage", get(organization_usage).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::OrganizationDetail.to_string())
+        })))
+}
+
+pub fn organization_admin_router() -> Router {
+    Router::new()
+        .route("/organizations/{id}", delete(organization_purge).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminOrganizationData.to_string())
+        })))
+        .route("/organizations/{id}/quota", put(organization_quota_update).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminOrganizationQuota.to_string())
+        })))
+        .route("/organizations/{id}/export", post(organization_export).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminOrganizationData.to_string())
+        })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/organization",
+    tag = "organization",
+    request_body = OrganizationRequest,
+    responses(
+        (status = 201, description = "Created organization, owned by the caller", body = SuccessResponse<Organization>),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn organization_create(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    ValidatedJson(body): ValidatedJson<OrganizationRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let organization = app_state.organization_service().create(&user_auth.user, body).await?;
+    Ok((
+        StatusCode::CREATED,
+        SuccessResponse::new("Successfully created an organization.", Some(organization)),
+    ))
+}
+#[utoipa::path(
+    get,
+    path = "/api/organization/{id}",
+    tag = "organization",
+    params(("id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Organization with its counts and the caller's role", body = SuccessResponse<OrganizationDetail>),
+        (status = 404, description = "Organization not found"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn organization_detail(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(organization_id): PathParser<Uuid>,
+) -> HttpResult<impl IntoResponse> {
+    let organization = app_state.organization_service().detail(organization_id, &user_auth.user).await?;
+    Ok(
+        SuccessResponse::new("Getting organization data", Some(organization))
+    )
+}
+#[utoipa::path(
+    put,
+    path = "/api/organization/{id}",
+    tag = "organization",
+    params(("id" = Uuid, Path)),
+    request_body = OrganizationUpdateRequest,
+    responses(
+        (status = 200, description = "Updated organization", body = SuccessResponse<Organization>),
+        (status = 403, description = "Neither an owner nor an admin"),
+        (status = 404, description = "Organization not found"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn organization_update(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(organization_id): PathParser<Uuid>,
+    ValidatedJson(body): ValidatedJson<OrganizationUpdateRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let organization = app_state.organization_service().update(organization_id, &user_auth.user, body).await?;
+    Ok(
+        SuccessResponse::new("Successfully updated the organization.", Some(organization))
+    )
+}
+#[utoipa::path(
+    delete,
+    path = "/api/organization/{id}",
+    tag = "organization",
+    params(("id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Organization deleted, its posts are kept as their authors' own"),
+        (status = 403, description = "Neither an owner nor an admin"),
+        (status = 404, description = "Organization not found"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn organization_delete(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(organization_id): PathParser<Uuid>,
+) -> HttpResult<impl IntoResponse> {
+    app_state.organization_service().delete(organization_id, &user_auth.user).await?;
+    Ok(
+        SuccessResponse::<()>::new("Successfully deleted the organization.", None)
+    )
+}
+#[utoipa::path(
+    get,
+    path = "/api/organization/{id}/members",
+    tag = "organization",
+    params(("id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Owners, editors and viewers, earliest joined first within each", body = SuccessResponse<Vec<OrganizationMember>>),
+        (status = 404, description = "Organization not found"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn organization_members(
+    Extension(app_state): Extension<Arc<AppState>>,
+    PathParser(organization_id): PathParser<Uuid>,
+) -> HttpResult<impl IntoResponse> {
+    let members = app_state.organization_service().members(organization_id).await?;
+    Ok(
+        SuccessResponse::new("Getting organization members", Some(members))
+    )
+}
+#[utoipa::path(
+    put,
+    path = "/api/organization/{id}/members/{user_id}",
+    tag = "organization",
+    params(("id" = Uuid, Path), ("user_id" = Uuid, Path)),
+    request_body = OrganizationMemberRequest,
+    responses(
+        (status = 200, description = "The member with the new role", body = SuccessResponse<OrganizationMember>),
+        (status = 400, description = "Would leave the organization without an owner"),
+        (status = 403, description = "Neither an owner nor an admin"),
+        (status = 404, description = "Organization or member not found"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn organization_member_update(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser((organization_id, user_id)): PathParser<(Uuid, Uuid)>,
+    ValidatedJson(body): ValidatedJson<OrganizationMemberRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let member = app_state.organization_service().set_member_role(organization_id, user_id, body.role, &user_auth.user).await?;
+    Ok(
+        SuccessResponse::new("Successfully changed the member's role.", Some(member))
+    )
+}
+#[utoipa::path(
+    delete,
+    path = "/api/organization/{id}/members/{user_id}",
+    tag = "organization",
+    params(("id" = Uuid, Path), ("user_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Member removed"),
+        (status = 400, description = "Would leave the organization without an owner"),
+        (status = 403, description = "Neither an owner nor an admin"),
+        (status = 404, description = "Organization or member not found"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn organization_member_remove(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser((organization_id, user_id)): PathParser<(Uuid, Uuid)>,
+) -> HttpResult<impl IntoResponse> {
+    app_state.organization_service().remove_member(organization_id, user_id, &user_auth.user).await?;
+    Ok(
+        SuccessResponse::<()>::new("Successfully removed the member.", None)
+    )
+}
+#[utoipa::path(
+    post,
+    path = "/api/organization/{id}/leave",
+    tag = "organization",
+    params(("id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Left the organization"),
+        (status = 400, description = "The caller is the last owner"),
+        (status = 404, description = "Organization not found or not a member"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn organization_leave(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(organization_id): PathParser<Uuid>,
+) -> HttpResult<impl IntoResponse> {
+    app_state.organization_service().leave(organization_id, &user_auth.user).await?;
+    Ok(
+        SuccessResponse::<()>::new("Successfully left the organization.", None)
+    )
+}
+#[utoipa::path(
+    post,
+    path = "/api/organization/{id}/invitations",
+    tag = "organization",
+    params(("id" = Uuid, Path)),
+    request_body = OrganizationInvitationRequest,
+    responses(
+        (status = 201, description = "Invitation waiting for the user to accept or decline", body = SuccessResponse<OrganizationInvitation>),
+        (status = 403, description = "Neither an owner nor an admin"),
+        (status = 404, description = "No such organization, or no such user that isn't banned"),
+        (status = 409, description = "Already a member or already invited"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn organization_invite(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(organization_id): PathParser<Uuid>,
+    ValidatedJson(body): ValidatedJson<OrganizationInvitationRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let invitation = app_state.organization_service().invite(organization_id, &user_auth.user, body).await?;
+    Ok((
+        StatusCode::CREATED,
+        SuccessResponse::new("Successfully sent the invitation.", Some(invitation)),
+    ))
+}
+#[utoipa::path(
+    delete,
+    path = "/api/organization/{id}/invitations/{user_id}",
+    tag = "organization",
+    params(("id" = Uuid, Path), ("user_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Invitation withdrawn"),
+        (status = 403, description = "Neither an owner nor an admin"),
+        (status = 404, description = "Organization or invitation not found"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn organization_invitation_revoke(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser((organization_id, user_id)): PathParser<(Uuid, Uuid)>,
+) -> HttpResult<impl IntoResponse> {
+    app_state.organization_service().revoke_invitation(organization_id, user_id, &user_auth.user).await?;
+    Ok(
+        SuccessResponse::<()>::new("Successfully withdrew the invitation.", None)
+    )
+}
+#[utoipa::path(
+    get,
+    path = "/api/organization/invitations",
+    tag = "organization",
+    responses(
+        (status = 200, description = "The caller's pending invitations, newest first", body = SuccessResponse<Vec<OrganizationInvitation>>),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn organization_invitations(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+) -> HttpResult<impl IntoResponse> {
+    let invitations = app_state.organization_service().invitations(&user_auth.user).await?;
+    Ok(
+        SuccessResponse::new("Getting organization invitations", Some(invitations))
+    )
+}
+#[utoipa::path(
+    post,
+    path = "/api/organization/invitations/{id}/accept",
+    tag = "organization",
+    params(("id" = Uuid, Path, description = "The organization's id")),
+    responses(
+        (status = 200, description = "Joined the organization with the invited role", body = SuccessResponse<OrganizationMember>),
+        (status = 404, description = "No pending invitation to the organization"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn organization_invitation_accept(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(organization_id): PathParser<Uuid>,
+) -> HttpResult<impl IntoResponse> {
+    let member = app_state.organization_service().accept_invitation(organization_id, &user_auth.user).await?;
+    Ok(
+        SuccessResponse::new("Successfully joined the organization.", Some(member))
+    )
+}
+#[utoipa::path(
+    post,
+    path = "/api/organization/invitations/{id}/decline",
+    tag = "organization",
+    params(("id" = Uuid, Path, description = "The organization's id")),
+    responses(
+        (status = 200, description = "Invitation declined"),
+        (status = 404, description = "No pending invitation to the organization"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn organization_invitation_decline(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(organization_id): PathParser<Uuid>,
+) -> HttpResult<impl IntoResponse> {
+    app_state.organization_service().decline_invitation(organization_id, &user_auth.user).await?;
+    Ok(
+        SuccessResponse::<()>::new("Successfully declined the invitation.", None)
+    )
+}
+#[utoipa::path(
+    post,
+    path = "/api/organization/{id}/follow",
+    tag = "organization",
+    params(("id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Following the organization, its posts show up in the feed with `organizations=true`"),
+        (status = 404, description = "Organization not found"),
+        (status = 409, description = "Already following"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn organization_follow(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(organization_id): PathParser<Uuid>,
+) -> HttpResult<impl IntoResponse> {
+    app_state.organization_service().follow(organization_id, &user_auth.user).await?;
+    Ok(
+        SuccessResponse::<()>::new("Successfully followed the organization.", None)
+    )
+}
+#[utoipa::path(
+    delete,
+    path = "/api/organization/{id}/follow",
+    tag = "organization",
+    params(("id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "No longer following the organization"),
+        (status = 404, description = "Organization not found or not followed"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn organization_unfollow(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(organization_id): PathParser<Uuid>,
+) -> HttpResult<impl IntoResponse> {
+    app_state.organization_service().unfollow(organization_id, &user_auth.user).await?;
+    Ok(
+        SuccessResponse::<()>::new("Successfully unfollowed the organization.", None)
+    )
+}
+#[utoipa::path(
+    get,
+    path = "/api/organization/{id}/usage",
+    tag = "organization",
+    params(("id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Requests members made for the organization this month against the quota, and the past months", body = SuccessResponse<OrganizationUsageResponse>),
+        (status = 403, description = "Neither an owner nor an admin"),
+        (status = 404, description = "Organization not found"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn organization_usage(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(organization_id): PathParser<Uuid>,
+) -> HttpResult<impl IntoResponse> {
+    let usage = app_state.organization_service().usage(organization_id, &user_auth.user).await?;
+    Ok(
+        SuccessResponse::new("Getting organization usage data", Some(usage))
+    )
+}
+async fn organization_quota_update(
+    Extension(app_state): Extension<Arc<AppState>>,
+    PathParser(organization_id): PathParser<Uuid>,
+    ValidatedJson(body): ValidatedJson<OrganizationQuotaRequest>,
+) -> HttpResult<impl IntoResponse> {
+    app_state.organization_service().set_quota(organization_id, body).await?;
+    Ok(
+        SuccessResponse::<()>::new("Successfully updated the organization quota.", None)
+    )
+}
+/// Accepted right away, the export worker writes the files and `GET /api/admin/exports/{id}`
+/// follows it.
+async fn organization_export(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(organization_id): PathParser<Uuid>,
+    ValidatedJson(body): ValidatedJson<OrganizationExportRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let export = app_state.organization_service().export(organization_id, &user_auth.user, body).await?;
+    Ok((
+        StatusCode::ACCEPTED,
+        SuccessResponse::new("Organization data export is queued.", Some(export))
+    ))
+}
+async fn organization_purge(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(organization_id): PathParser<Uuid>,
+) -> HttpResult<impl IntoResponse> {
+    app_state.organization_service().purge(organization_id, &user_auth.user).await?;
+    Ok(
+        SuccessResponse::<()>::new("Successfully deleted the organization and all of its data.", None)
+    )
+}
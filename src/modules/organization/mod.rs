@@ -0,0 +1,4 @@
+pub mod dto;
+pub mod model;
+pub mod service;
+pub mod handler;
\ No newline at end of file
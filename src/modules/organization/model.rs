@@ -0,0 +1,548 @@
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, Type, query, query_as, query_scalar};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use crate::{db::DBClient, error::RepositoryError};
+
+/// What a member may do in an organization. Owners manage it, editors publish and edit its posts,
+/// viewers get its posts in their feed.
+#[derive(Serialize, Deserialize, Type, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+#[sqlx(type_name = "organization_role", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum OrganizationRole {
+    Owner,
+    Editor,
+    Viewer,
+}
+
+impl OrganizationRole {
+    pub fn get_value(&self) -> &str {
+        match self {
+            OrganizationRole::Owner => "owner",
+            OrganizationRole::Editor => "editor",
+            OrganizationRole::Viewer => "viewer",
+        }
+    }
+    /// Whether the role may publish posts under the organization and edit them.
+    pub fn can_publish(&self) -> bool {
+        matches!(self, OrganizationRole::Owner | OrganizationRole::Editor)
+    }
+}
+
+#[derive(Serialize, Deserialize, FromRow, ToSchema, Clone)]
+pub struct Organization {
+    pub id: Uuid,
+    pub name: String,
+    pub description: String,
+    /// Where the organization's data is kept, one of `DATA_REGIONS`.
+    pub region: String,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+#[derive(Serialize, Deserialize, FromRow, ToSchema)]
+pub struct OrganizationDetail {
+    pub id: Uuid,
+    pub name: String,
+    pub description: String,
+    pub region: String,
+    pub created_by: Option<Uuid>,
+    pub members_count: i64,
+    pub followers_count: i64,
+    /// The viewer's role, `None` when they aren't a member.
+    pub role: Option<OrganizationRole>,
+    pub is_following: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+#[derive(Serialize, Deserialize, FromRow, ToSchema)]
+pub struct OrganizationMember {
+    pub user_id: Uuid,
+    pub name: String,
+    pub username: Option<String>,
+    pub avatar_url: Option<String>,
+    pub role: OrganizationRole,
+    pub created_at: DateTime<Utc>,
+}
+/// A pending invitation, accepted or declined by the invited user.
+#[derive(Serialize, Deserialize, FromRow, ToSchema)]
+pub struct OrganizationInvitation {
+    pub organization_id: Uuid,
+    pub organization_name: String,
+    pub user_id: Uuid,
+    pub role: OrganizationRole,
+    pub invited_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// What `charge` needs to let a request count against an organization.
+#[derive(FromRow)]
+pub struct OrganizationUsageAccess {
+    /// The user's role, `None` when they aren't a member.
+    pub role: Option<OrganizationRole>,
+    /// `None` follows `ORGANIZATION_MONTHLY_REQUEST_QUOTA`, 0 is unlimited.
+    pub monthly_request_quota: Option<i64>,
+    /// Requests of the month already written to the database.
+    pub requests: i64,
+}
+
+impl OrganizationUsageAccess {
+    /// The monthly limit in force, `None` when unlimited.
+    pub fn quota(&self, default: i64) -> Option<i64> {
+        match self.monthly_request_quota.unwrap_or(default) {
+            0 => None,
+            quota => Some(quota),
+        }
+    }
+}
+#[derive(Serialize, Deserialize, FromRow, ToSchema)]
+pub struct OrganizationUsageMonth {
+    /// The first day of the month.
+    pub month: NaiveDate,
+    pub requests: i64,
+}
+
+/// Who may do what is decided by `OrganizationService`, these only keep every organization with
+/// at least one owner.
+#[async_trait]
+pub trait OrganizationRepository: Send + Sync {
+    /// Creates the organization in `region` with `owner_id` as its first owner.
+    async fn save_organization(&self, name: &str, description: &str, region: &str, owner_id: Uuid) -> Result<Organization, RepositoryError>;
+    async fn get_organization(&self, organization_id: Uuid) -> Result<Option<Organization>, RepositoryError>;
+    async fn get_organization_detail(&self, organization_id: Uuid, viewer_id: Uuid) -> Result<Option<OrganizationDetail>, RepositoryError>;
+    async fn update_organization(&self, organization_id: Uuid, name: Option<&str>, description: Option<&str>) -> Result<Organization, RepositoryError>;
+    /// The organization's posts stay with their authors as personal posts.
+    async fn delete_organization(&self, organization_id: Uuid) -> Result<(), RepositoryError>;
+    /// Deletes the organization together with its posts, returning the authors of the posts.
+    async fn purge_organization(&self, organization_id: Uuid) -> Result<Vec<Uuid>, RepositoryError>;
+    /// The role of `user_id`, `None` for non-members and `NotFound` when there is no such organization.
+    async fn get_member_role(&self, organization_id: Uuid, user_id: Uuid) -> Result<Option<OrganizationRole>, RepositoryError>;
+    /// Owners first, then editors and viewers, earliest joined first within each.
+    async fn get_members(&self, organization_id: Uuid) -> Result<Vec<OrganizationMember>, RepositoryError>;
+    /// Demoting the last owner is `InvalidInput`.
+    async fn set_member_role(&self, organization_id: Uuid, user_id: Uuid, role: OrganizationRole) -> Result<OrganizationMember, RepositoryError>;
+    /// Removing the last owner is `InvalidInput`.
+    async fn remove_member(&self, organization_id: Uuid, user_id: Uuid) -> Result<(), RepositoryError>;
+    /// Banned or missing users are `NotFound`, a pending invitation for the same user is `Conflict`.
+    async fn save_invitation(&self, organization_id: Uuid, user_id: Uuid, role: OrganizationRole, invited_by: Uuid) -> Result<OrganizationInvitation, RepositoryError>;
+    /// The pending invitations of `user_id`, newest first.
+    async fn get_invitations_for_user(&self, user_id: Uuid) -> Result<Vec<OrganizationInvitation>, RepositoryError>;
+    /// Turns the invitation into a membership with the invited role.
+    async fn accept_invitation(&self, organization_id: Uuid, user_id: Uuid) -> Result<OrganizationMember, RepositoryError>;
+    async fn delete_invitation(&self, organization_id: Uuid, user_id: Uuid) -> Result<(), RepositoryError>;
+    /// Following twice is `Conflict`.
+    async fn follow_organization(&self, organization_id: Uuid, user_id: Uuid) -> Result<(), RepositoryError>;
+    async fn unfollow_organization(&self, organization_id: Uuid, user_id: Uuid) -> Result<(), RepositoryError>;
+    /// Members and followers, the users whose feeds can show the organization's posts.
+    async fn get_audience_ids(&self, organization_id: Uuid) -> Result<Vec<Uuid>, RepositoryError>;
+    /// `None` when there is no such organization.
+    async fn get_usage_access(&self, organization_id: Uuid, user_id: Uuid, month: NaiveDate) -> Result<Option<OrganizationUsageAccess>, RepositoryError>;
+    /// The last `months` months with requests, newest first.
+    async fn get_usage_history(&self, organization_id: Uuid, months: i64) -> Result<Vec<OrganizationUsageMonth>, RepositoryError>;
+    async fn set_request_quota(&self, organization_id: Uuid, quota: Option<i64>) -> Result<(), RepositoryError>;
+    /// Adds request counts per organization and month, skipping organizations deleted meanwhile.
+    async fn add_usage(&self, usage: &[(Uuid, NaiveDate, i64)]) -> Result<(), RepositoryError>;
+}
+
+impl DBClient {
+    /// Fails with `InvalidInput` when `user_id` is the only owner left, under a lock on the
+    /// organization so two owners can't step down at the same time.
+    async fn ensure_other_owner(&self, conn: &mut PgConnection, organization_id: Uuid, user_id: Uuid) -> Result<(), RepositoryError> {
+        query_scalar!(
+            r#"
+                SELECT id FROM organizations WHERE id = $1 FOR UPDATE;
+            "#,
+            organization_id,
+        ).fetch_optional(&mut *conn).await?.ok_or(RepositoryError::NotFound)?;
+        let other_owners = query_scalar!(
+            r#"
+                SELECT COUNT(*) AS "count!" FROM organization_members
+                WHERE organization_id = $1 AND role = 'owner' AND user_id <> $2;
+            "#,
+            organization_id,
+            user_id,
+        ).fetch_one(&mut *conn).await?;
+        if other_owners == 0 {
+            return Err(RepositoryError::InvalidInput("An organization needs at least one owner".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl OrganizationRepository for DBClient {
+    async fn save_organization(&self, name: &str, description: &str, region: &str, owner_id: Uuid) -> Result<Organization, RepositoryError> {
+        let _timer = self.time_query("organization.save_organization");
+        let mut transaction = self.pool.begin().await?;
+        let organization = query_as!(
+            Organization,
+            r#"
+                INSERT INTO organizations (name, description, region, created_by)
+                VALUES ($1, $2, $3, $4)
+                RETURNING id, name, description, region, created_by, created_at, updated_at;
+            "#,
+            name,
+            description,
+            region,
+            owner_id,
+        ).fetch_one(&mut *transaction).await?;
+        query!(
+            r#"
+                INSERT INTO organization_members (organization_id, user_id, role)
+                VALUES ($1, $2, 'owner');
+            "#,
+            organization.id,
+            owner_id,
+        ).execute(&mut *transaction).await?;
+        transaction.commit().await?;
+        Ok(organization)
+    }
+    async fn get_organization(&self, organization_id: Uuid) -> Result<Option<Organization>, RepositoryError> {
+        let _timer = self.time_query("organization.get_organization");
+        let organization = query_as!(
+            Organization,
+            r#"
+                SELECT id, name, description, region, created_by, created_at, updated_at
+                FROM organizations
+                WHERE id = $1;
+            "#,
+            organization_id,
+        ).fetch_optional(&self.pool).await?;
+        Ok(organization)
+    }
+    async fn get_organization_detail(&self, organization_id: Uuid, viewer_id: Uuid) -> Result<Option<OrganizationDetail>, RepositoryError> {
+        let _timer = self.time_query("organization.get_organization_detail");
+        let organization = query_as!(
+            OrganizationDetail,
+            r#"
+                SELECT o.id, o.name, o.description, o.region, o.created_by,
+                    (SELECT COUNT(*) FROM organization_members WHERE organization_id = o.id) AS "members_count!",
+                    (SELECT COUNT(*) FROM organization_followers WHERE organization_id = o.id) AS "followers_count!",
+                    (SELECT role FROM organization_members WHERE organization_id = o.id AND user_id = $2) AS "role: OrganizationRole",
+                    EXISTS (SELECT 1 FROM organization_followers WHERE organization_id = o.id AND user_id = $2) AS "is_following!",
+                    o.created_at, o.updated_at
+                FROM organizations AS o
+                WHERE o.id = $1;
+            "#,
+            organization_id,
+            viewer_id,
+        ).fetch_optional(&self.pool).await?;
+        Ok(organization)
+    }
+    async fn update_organization(&self, organization_id: Uuid, name: Option<&str>, description: Option<&str>) -> Result<Organization, RepositoryError> {
+        let _timer = self.time_query("organization.update_organization");
+        let organization = query_as!(
+            Organization,
+            r#"
+                UPDATE organizations
+                SET name = COALESCE($2, name), description = COALESCE($3, description), updated_at = NOW()
+                WHERE id = $1
+                RETURNING id, name, description, region, created_by, created_at, updated_at;
+            "#,
+            organization_id,
+            name,
+            description,
+        ).fetch_optional(&self.pool).await?.ok_or(RepositoryError::NotFound)?;
+        Ok(organization)
+    }
+    async fn delete_organization(&self, organization_id: Uuid) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("organization.delete_organization");
+        let result = query!(
+            r#"
+                DELETE FROM organizations WHERE id = $1;
+            "#,
+            organization_id,
+        ).execute(&self.pool).await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(())
+    }
+    async fn purge_organization(&self, organization_id: Uuid) -> Result<Vec<Uuid>, RepositoryError> {
+        let _timer = self.time_query("organization.purge_organization");
+        let mut transaction = self.pool.begin().await?;
+        query_scalar!(
+            r#"
+                SELECT id FROM organizations WHERE id = $1 FOR UPDATE;
+            "#,
+            organization_id,
+        ).fetch_optional(&mut *transaction).await?.ok_or(RepositoryError::NotFound)?;
+        let author_ids = query_scalar!(
+            r#"
+                DELETE FROM posts WHERE organization_id = $1
+                RETURNING user_id;
+            "#,
+            organization_id,
+        ).fetch_all(&mut *transaction).await?;
+        query!(
+            r#"
+                DELETE FROM organizations WHERE id = $1;
+            "#,
+            organization_id,
+        ).execute(&mut *transaction).await?;
+        transaction.commit().await?;
+        Ok(author_ids)
+    }
+    async fn get_member_role(&self, organization_id: Uuid, user_id: Uuid) -> Result<Option<OrganizationRole>, RepositoryError> {
+        let _timer = self.time_query("organization.get_member_role");
+        let role = query_scalar!(
+            r#"
+                SELECT m.role AS "role?: OrganizationRole"
+                FROM organizations AS o
+                LEFT JOIN organization_members AS m ON m.organization_id = o.id AND m.user_id = $2
+                WHERE o.id = $1;
+            "#,
+            organization_id,
+            user_id,
+        ).fetch_optional(&self.pool).await?.ok_or(RepositoryError::NotFound)?;
+        Ok(role)
+    }
+    async fn get_members(&self, organization_id: Uuid) -> Result<Vec<OrganizationMember>, RepositoryError> {
+        let _timer = self.time_query("organization.get_members");
+        let members = query_as!(
+            OrganizationMember,
+            r#"
+                SELECT m.user_id, u.name, u.username, u.avatar_url, m.role AS "role: OrganizationRole", m.created_at
+                FROM organization_members AS m
+                JOIN users AS u ON u.id = m.user_id
+                WHERE m.organization_id = $1
+                ORDER BY m.role, m.created_at, m.user_id;
+            "#,
+            organization_id,
+        ).fetch_all(&self.pool).await?;
+        Ok(members)
+    }
+    async fn set_member_role(&self, organization_id: Uuid, user_id: Uuid, role: OrganizationRole) -> Result<OrganizationMember, RepositoryError> {
+        let _timer = self.time_query("organization.set_member_role");
+        let mut transaction = self.pool.begin().await?;
+        if role != OrganizationRole::Owner {
+            self.ensure_other_owner(&mut transaction, organization_id, user_id).await?;
+        }
+        let member = query_as!(
+            OrganizationMember,
+            r#"
+                WITH updated AS (
+                    UPDATE organization_members SET role = $3
+                    WHERE organization_id = $1 AND user_id = $2
+                    RETURNING user_id, role, created_at
+                )
+                SELECT m.user_id AS "user_id!", u.name AS "name!", u.username, u.avatar_url,
+                    m.role AS "role!: OrganizationRole", m.created_at AS "created_at!"
+                FROM updated AS m
+                JOIN users AS u ON u.id = m.user_id;
+            "#,
+            organization_id,
+            user_id,
+            role as OrganizationRole,
+        ).fetch_optional(&mut *transaction).await?.ok_or(RepositoryError::NotFound)?;
+        transaction.commit().await?;
+        Ok(member)
+    }
+    async fn remove_member(&self, organization_id: Uuid, user_id: Uuid) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("organization.remove_member");
+        let mut transaction = self.pool.begin().await?;
+        self.ensure_other_owner(&mut transaction, organization_id, user_id).await?;
+        let result = query!(
+            r#"
+                DELETE FROM organization_members WHERE organization_id = $1 AND user_id = $2;
+            "#,
+            organization_id,
+            user_id,
+        ).execute(&mut *transaction).await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+    async fn save_invitation(&self, organization_id: Uuid, user_id: Uuid, role: OrganizationRole, invited_by: Uuid) -> Result<OrganizationInvitation, RepositoryError> {
+        let _timer = self.time_query("organization.save_invitation");
+        let invitation = query_as!(
+            OrganizationInvitation,
+            r#"
+                WITH invited AS (
+                    INSERT INTO organization_invitations (organization_id, user_id, role, invited_by)
+                    SELECT $1, id, $3, $4 FROM users WHERE id = $2 AND is_banned = false
+                    RETURNING organization_id, user_id, role, invited_by, created_at
+                )
+                SELECT i.organization_id AS "organization_id!", o.name AS "organization_name!", i.user_id AS "user_id!",
+                    i.role AS "role!: OrganizationRole", i.invited_by, i.created_at AS "created_at!"
+                FROM invited AS i
+                JOIN organizations AS o ON o.id = i.organization_id;
+            "#,
+            organization_id,
+            user_id,
+            role as OrganizationRole,
+            invited_by,
+        ).fetch_optional(&self.pool).await?.ok_or(RepositoryError::NotFound)?;
+        Ok(invitation)
+    }
+    async fn get_invitations_for_user(&self, user_id: Uuid) -> Result<Vec<OrganizationInvitation>, RepositoryError> {
+        let _timer = self.time_query("organization.get_invitations_for_user");
+        let invitations = query_as!(
+            OrganizationInvitation,
+            r#"
+                SELECT i.organization_id, o.name AS organization_name, i.user_id, i.role AS "role: OrganizationRole", i.invited_by, i.created_at
+                FROM organization_invitations AS i
+                JOIN organizations AS o ON o.id = i.organization_id
+                WHERE i.user_id = $1
+                ORDER BY i.created_at DESC, i.organization_id;
+            "#,
+            user_id,
+        ).fetch_all(&self.pool).await?;
+        Ok(invitations)
+    }
+    async fn accept_invitation(&self, organization_id: Uuid, user_id: Uuid) -> Result<OrganizationMember, RepositoryError> {
+        let _timer = self.time_query("organization.accept_invitation");
+        let mut transaction = self.pool.begin().await?;
+        let role = query_scalar!(
+            r#"
+                DELETE FROM organization_invitations WHERE organization_id = $1 AND user_id = $2
+                RETURNING role AS "role: OrganizationRole";
+            "#,
+            organization_id,
+            user_id,
+        ).fetch_optional(&mut *transaction).await?.ok_or(RepositoryError::NotFound)?;
+        let member = query_as!(
+            OrganizationMember,
+            r#"
+                WITH joined AS (
+                    INSERT INTO organization_members (organization_id, user_id, role)
+                    VALUES ($1, $2, $3)
+                    RETURNING user_id, role, created_at
+                )
+                SELECT m.user_id AS "user_id!", u.name AS "name!", u.username, u.avatar_url,
+                    m.role AS "role!: OrganizationRole", m.created_at AS "created_at!"
+                FROM joined AS m
+                JOIN users AS u ON u.id = m.user_id;
+            "#,
+            organization_id,
+            user_id,
+            role as OrganizationRole,
+        ).fetch_one(&mut *transaction).await?;
+        transaction.commit().await?;
+        Ok(member)
+    }
+    async fn delete_invitation(&self, organization_id: Uuid, user_id: Uuid) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("organization.delete_invitation");
+        let result = query!(
+            r#"
+                DELETE FROM organization_invitations WHERE organization_id = $1 AND user_id = $2;
+            "#,
+            organization_id,
+            user_id,
+        ).execute(&self.pool).await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(())
+    }
+    async fn follow_organization(&self, organization_id: Uuid, user_id: Uuid) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("organization.follow_organization");
+        let result = query!(
+            r#"
+                INSERT INTO organization_followers (organization_id, user_id)
+                SELECT id, $2 FROM organizations WHERE id = $1;
+            "#,
+            organization_id,
+            user_id,
+        ).execute(&self.pool).await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(())
+    }
+    async fn unfollow_organization(&self, organization_id: Uuid, user_id: Uuid) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("organization.unfollow_organization");
+        let result = query!(
+            r#"
+                DELETE FROM organization_followers WHERE organization_id = $1 AND user_id = $2;
+            "#,
+            organization_id,
+            user_id,
+        ).execute(&self.pool).await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(())
+    }
+    async fn get_audience_ids(&self, organization_id: Uuid) -> Result<Vec<Uuid>, RepositoryError> {
+        let _timer = self.time_query("organization.get_audience_ids");
+        let user_ids = query_scalar!(
+            r#"
+                SELECT user_id AS "user_id!" FROM organization_members WHERE organization_id = $1
+                UNION
+                SELECT user_id FROM organization_followers WHERE organization_id = $1;
+            "#,
+            organization_id,
+        ).fetch_all(&self.pool).await?;
+        Ok(user_ids)
+    }    async fn get_usage_access(&self, organization_id: Uuid, user_id: Uuid, month: NaiveDate) -> Result<Option<OrganizationUsageAccess>, RepositoryError> {
+        let _timer = self.time_query("organization.get_usage_access");
+        let access = query_as!(
+            OrganizationUsageAccess,
+            r#"
+                SELECT
+                    (SELECT role FROM organization_members WHERE organization_id = o.id AND user_id = $2) AS "role: OrganizationRole",
+                    o.monthly_request_quota,
+                    COALESCE((SELECT requests FROM organization_usage WHERE organization_id = o.id AND month = $3), 0) AS "requests!"
+                FROM organizations AS o
+                WHERE o.id = $1;
+            "#,
+            organization_id,
+            user_id,
+            month,
+        ).fetch_optional(&self.pool).await?;
+        Ok(access)
+    }
+    async fn get_usage_history(&self, organization_id: Uuid, months: i64) -> Result<Vec<OrganizationUsageMonth>, RepositoryError> {
+        let _timer = self.time_query("organization.get_usage_history");
+        let history = query_as!(
+            OrganizationUsageMonth,
+            r#"
+                SELECT month, requests FROM organization_usage
+                WHERE organization_id = $1
+                ORDER BY month DESC
+                LIMIT $2;
+            "#,
+            organization_id,
+            months,
+        ).fetch_all(&self.pool).await?;
+        Ok(history)
+    }
+    async fn set_request_quota(&self, organization_id: Uuid, quota: Option<i64>) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("organization.set_request_quota");
+        let result = query!(
+            r#"
+                UPDATE organizations SET monthly_request_quota = $2, updated_at = NOW() WHERE id = $1;
+            "#,
+            organization_id,
+            quota,
+        ).execute(&self.pool).await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(())
+    }
+    async fn add_usage(&self, usage: &[(Uuid, NaiveDate, i64)]) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("organization.add_usage");
+        let organization_ids: Vec<Uuid> = usage.iter().map(|(organization_id, _, _)| *organization_id).collect();
+        let months: Vec<NaiveDate> = usage.iter().map(|(_, month, _)| *month).collect();
+        let requests: Vec<i64> = usage.iter().map(|(_, _, requests)| *requests).collect();
+        query!(
+            r#"
+                INSERT INTO organization_usage (organization_id, month, requests)
+                SELECT u.organization_id, u.month, u.requests
+                FROM UNNEST($1::uuid[], $2::date[], $3::bigint[]) AS u(organization_id, month, requests)
+                JOIN organizations AS o ON o.id = u.organization_id
+                ON CONFLICT (organization_id, month) DO UPDATE SET requests = organization_usage.requests + EXCLUDED.requests;
+            "#,
+            &organization_ids,
+            &months,
+            &requests,
+        ).execute(&self.pool).await?;
+        Ok(())
+    }
+}
\ No newline at end of file
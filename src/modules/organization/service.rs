@@ -0,0 +1,188 @@
+use std::sync::Arc;
+use chrono::Utc;
+use log::warn;
+use serde_json::json;
+use uuid::Uuid;
+use crate::{
+    AppState,
+    error::{AppError, ErrorMessage},
+    middleware::org_quota::usage_period,
+    modules::{
+        audit_log::model::{AuditAction, NewAuditLog},
+        data_export::dto::DataExportResponse,
+        organization::{
+            dto::{OrganizationExportRequest, OrganizationInvitationRequest, OrganizationQuotaRequest, OrganizationRequest, OrganizationUpdateRequest, OrganizationUsageResponse},
+            model::{Organization, OrganizationDetail, OrganizationInvitation, OrganizationMember, OrganizationRole},
+        },
+        role::model::RoleType,
+        user::model::User,
+    },
+};
+
+/// Months of history `usage` returns, the current one included.
+const USAGE_HISTORY_MONTHS: i64 = 12;
+
+/// Organizations, their members and invitations. Owners manage the organization, admins may do
+/// whatever an owner can. Every change to who sees an organization's posts drops the cached feeds
+/// of the users it concerns.
+pub struct OrganizationService {
+    app_state: Arc<AppState>,
+}
+
+impl OrganizationService {
+    pub fn new(app_state: Arc<AppState>) -> Self {
+        Self { app_state }
+    }
+    /// `Forbidden` unless `actor` is an owner of the organization or an admin, `NotFound` when
+    /// there is no such organization.
+    async fn ensure_owner(&self, organization_id: Uuid, actor: &User) -> Result<(), AppError> {
+        let role = self.app_state.db.organizations.get_member_role(organization_id, actor.id).await?;
+        if role == Some(OrganizationRole::Owner) {
+            return Ok(());
+        }
+        if self.app_state.db.roles.get_role_name_by_id(actor.role_id).await? == Some(RoleType::Admin) {
+            return Ok(());
+        }
+        Err(AppError::forbidden(ErrorMessage::PermissionDenied))
+    }
+    async fn invalidate_feeds(&self, user_ids: &[Uuid]) {
+        let _ = self.app_state.redis_client.delete_feeds(user_ids).await;
+    }
+
+    pub async fn create(&self, actor: &User, body: OrganizationRequest) -> Result<Organization, AppError> {
+        let regions = &self.app_state.env.data_regions;
+        let region = match body.region {
+            Some(region) if !regions.contains(&region) => {
+                return Err(AppError::bad_request(ErrorMessage::RegionUnavailable(regions.join(", "))));
+            }
+            Some(region) => region,
+            None => regions[0].clone(),
+        };
+        Ok(self.app_state.db.organizations.save_organization(&body.name, &body.description, &region, actor.id).await?)
+    }
+    pub async fn detail(&self, organization_id: Uuid, actor: &User) -> Result<OrganizationDetail, AppError> {
+        self.app_state.db.organizations.get_organization_detail(organization_id, actor.id).await?
+            .ok_or(AppError::not_found(ErrorMessage::DataNotFound))
+    }
+    pub async fn update(&self, organization_id: Uuid, actor: &User, body: OrganizationUpdateRequest) -> Result<Organization, AppError> {
+        self.ensure_owner(organization_id, actor).await?;
+        Ok(self.app_state.db.organizations.update_organization(organization_id, body.name.as_deref(), body.description.as_deref()).await?)
+    }
+    pub async fn delete(&self, organization_id: Uuid, actor: &User) -> Result<(), AppError> {
+        self.ensure_owner(organization_id, actor).await?;
+        let audience = self.app_state.db.organizations.get_audience_ids(organization_id).await?;
+        self.app_state.db.organizations.delete_organization(organization_id).await?;
+        self.invalidate_feeds(&audience).await;
+        Ok(())
+    }
+    pub async fn members(&self, organization_id: Uuid) -> Result<Vec<OrganizationMember>, AppError> {
+        let members = self.app_state.db.organizations.get_members(organization_id).await?;
+        // Every organization has an owner, no members means no organization.
+        if members.is_empty() {
+            return Err(AppError::not_found(ErrorMessage::DataNotFound));
+        }
+        Ok(members)
+    }
+    pub async fn set_member_role(&self, organization_id: Uuid, user_id: Uuid, role: OrganizationRole, actor: &User) -> Result<OrganizationMember, AppError> {
+        self.ensure_owner(organization_id, actor).await?;
+        Ok(self.app_state.db.organizations.set_member_role(organization_id, user_id, role).await?)
+    }
+    pub async fn remove_member(&self, organization_id: Uuid, user_id: Uuid, actor: &User) -> Result<(), AppError> {
+        self.ensure_owner(organization_id, actor).await?;
+        self.app_state.db.organizations.remove_member(organization_id, user_id).await?;
+        self.invalidate_feeds(&[user_id]).await;
+        Ok(())
+    }
+    /// The last owner can't leave, they hand the organization over or delete it first.
+    pub async fn leave(&self, organization_id: Uuid, actor: &User) -> Result<(), AppError> {
+        self.app_state.db.organizations.remove_member(organization_id, actor.id).await?;
+        self.invalidate_feeds(&[actor.id]).await;
+        Ok(())
+    }
+    pub async fn invite(&self, organization_id: Uuid, actor: &User, body: OrganizationInvitationRequest) -> Result<OrganizationInvitation, AppError> {
+        self.ensure_owner(organization_id, actor).await?;
+        if self.app_state.db.organizations.get_member_role(organization_id, body.user_id).await?.is_some() {
+            return Err(AppError::unique_constraint_violation(ErrorMessage::AlreadyMember));
+        }
+        Ok(self.app_state.db.organizations.save_invitation(organization_id, body.user_id, body.role, actor.id).await?)
+    }
+    pub async fn revoke_invitation(&self, organization_id: Uuid, user_id: Uuid, actor: &User) -> Result<(), AppError> {
+        self.ensure_owner(organization_id, actor).await?;
+        Ok(self.app_state.db.organizations.delete_invitation(organization_id, user_id).await?)
+    }
+    pub async fn invitations(&self, actor: &User) -> Result<Vec<OrganizationInvitation>, AppError> {
+        Ok(self.app_state.db.organizations.get_invitations_for_user(actor.id).await?)
+    }
+    pub async fn accept_invitation(&self, organization_id: Uuid, actor: &User) -> Result<OrganizationMember, AppError> {
+        let member = self.app_state.db.organizations.accept_invitation(organization_id, actor.id).await?;
+        self.invalidate_feeds(&[actor.id]).await;
+        Ok(member)
+    }
+    pub async fn decline_invitation(&self, organization_id: Uuid, actor: &User) -> Result<(), AppError> {
+        Ok(self.app_state.db.organizations.delete_invitation(organization_id, actor.id).await?)
+    }
+    pub async fn follow(&self, organization_id: Uuid, actor: &User) -> Result<(), AppError> {
+        self.app_state.db.organizations.follow_organization(organization_id, actor.id).await?;
+        self.invalidate_feeds(&[actor.id]).await;
+        Ok(())
+    }
+    pub async fn unfollow(&self, organization_id: Uuid, actor: &User) -> Result<(), AppError> {
+        self.app_state.db.organizations.unfollow_organization(organization_id, actor.id).await?;
+        self.invalidate_feeds(&[actor.id]).await;
+        Ok(())
+    }    /// The requests made for the organization this month against its quota. Redis counts them as
+    /// they come, the database only holds what was flushed, so the larger of the two wins.
+    pub async fn usage(&self, organization_id: Uuid, actor: &User) -> Result<OrganizationUsageResponse, AppError> {
+        self.ensure_owner(organization_id, actor).await?;
+        let (month, resets_at) = usage_period(Utc::now());
+        let access = self.app_state.db.organizations.get_usage_access(organization_id, actor.id, month).await?
+            .ok_or(AppError::not_found(ErrorMessage::DataNotFound))?;
+        let counted = self.app_state.redis_client.get_org_usage(&organization_id, month).await
+            .unwrap_or_else(|e| {
+                warn!("Failed to read the usage of organization {}: {}", organization_id, e);
+                None
+            });
+        let requests = counted.unwrap_or(0).max(access.requests);
+        let quota = access.quota(self.app_state.env.organization_monthly_request_quota);
+        let history = self.app_state.db.organizations.get_usage_history(organization_id, USAGE_HISTORY_MONTHS).await?;
+        Ok(OrganizationUsageResponse {
+            month,
+            requests,
+            quota,
+            remaining: quota.map(|quota| (quota - requests).max(0)),
+            resets_at,
+            history,
+        })
+    }
+    pub async fn set_quota(&self, organization_id: Uuid, body: OrganizationQuotaRequest) -> Result<(), AppError> {
+        Ok(self.app_state.db.organizations.set_request_quota(organization_id, body.monthly_request_quota).await?)
+    }
+    /// Queues an export of everything the organization has, for data residency requests. The
+    /// worker writes it like any other export.
+    pub async fn export(&self, organization_id: Uuid, actor: &User, body: OrganizationExportRequest) -> Result<DataExportResponse, AppError> {
+        let organization = self.app_state.db.organizations.get_organization(organization_id).await?
+            .ok_or(AppError::not_found(ErrorMessage::DataNotFound))?;
+        let export = self.app_state.data_export_service().request_organization(actor, organization.id, body.format).await?;
+        let _ = self.app_state.db.audit_logs.save_audit_log(
+            NewAuditLog::new(Some(actor.id), actor.id, AuditAction::OrganizationExported)
+                .with_metadata(json!({ "organization_id": organization.id, "region": organization.region, "export_id": export.id }))
+        ).await;
+        Ok(export)
+    }
+    /// Deletes the organization and its posts for good, where `delete` hands the posts back to
+    /// their authors. The audit log keeps only the organization's id and region.
+    pub async fn purge(&self, organization_id: Uuid, actor: &User) -> Result<(), AppError> {
+        let organization = self.app_state.db.organizations.get_organization(organization_id).await?
+            .ok_or(AppError::not_found(ErrorMessage::DataNotFound))?;
+        let mut affected = self.app_state.db.organizations.get_audience_ids(organization_id).await?;
+        affected.extend(self.app_state.db.organizations.purge_organization(organization_id).await?);
+        affected.sort();
+        affected.dedup();
+        self.invalidate_feeds(&affected).await;
+        let _ = self.app_state.db.audit_logs.save_audit_log(
+            NewAuditLog::new(Some(actor.id), actor.id, AuditAction::OrganizationDeleted)
+                .with_metadata(json!({ "organization_id": organization.id, "region": organization.region }))
+        ).await;
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -1,10 +1,11 @@
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use serde::{Serialize};
-use sqlx::{FromRow, Error as SqlxError, query_scalar};
+use sqlx::{FromRow, query, query_scalar};
 use uuid::Uuid;
-use crate::db::DBClient;
+use crate::{db::DBClient, error::RepositoryError, modules::role::model::RoleType};
 
+#[allow(dead_code)]
 #[derive(Serialize, FromRow)]
 pub struct Permission {
     pub id: Uuid,
@@ -15,21 +16,62 @@ pub struct Permission {
 }
 
 #[async_trait]
-pub trait PermissionRepository {
-    async fn get_permission_by_role(&self, role_id: &Uuid) -> Result<Vec<String>, SqlxError>;
+pub trait PermissionRepository: Send + Sync {
+    /// The permissions granted to the role and to its ancestors along `parent_role_id`. A chain that
+    /// loops back stops at the first role seen twice.
+    async fn get_permission_by_role(&self, role_id: &Uuid) -> Result<Vec<String>, RepositoryError>;
+    async fn ensure_permission(&self, name: &str, description: &str) -> Result<bool, RepositoryError>;
+    async fn grant_permission(&self, role: RoleType, permission: &str) -> Result<bool, RepositoryError>;
 }
 
 #[async_trait]
 impl PermissionRepository for DBClient {
-    async fn get_permission_by_role(&self, role_id: &Uuid) -> Result<Vec<String>, SqlxError> {
+    async fn get_permission_by_role(&self, role_id: &Uuid) -> Result<Vec<String>, RepositoryError> {
+        let _timer = self.time_query("permission.get_permission_by_role");
         let permissions = query_scalar!(
                 r#"
-                    SELECT p.name FROM permissions AS p
-                    JOIN role_permissions AS rp ON rp.permission_id = p.id
-                    WHERE rp.role_id = $1;
+                    WITH RECURSIVE role_chain (id, path) AS (
+                        SELECT id, ARRAY[id] FROM roles WHERE id = $1
+                        UNION ALL
+                        SELECT r.parent_role_id, c.path || r.parent_role_id
+                        FROM role_chain AS c
+                        JOIN roles AS r ON r.id = c.id
+                        WHERE r.parent_role_id IS NOT NULL AND r.parent_role_id <> ALL (c.path)
+                    )
+                    SELECT DISTINCT p.name AS "name!" FROM role_chain AS c
+                    JOIN role_permissions AS rp ON rp.role_id = c.id
+                    JOIN permissions AS p ON p.id = rp.permission_id;
                 "#,
                 role_id
             ).fetch_all(&self.pool).await?;
         Ok(permissions)
+    }    /// Inserts the permission unless it exists. Returns whether it was inserted.
+    async fn ensure_permission(&self, name: &str, description: &str) -> Result<bool, RepositoryError> {
+        let _timer = self.time_query("permission.ensure_permission");
+        let result = query!(
+                r#"
+                    INSERT INTO permissions (name, description)
+                    VALUES ($1, $2)
+                    ON CONFLICT (name) DO NOTHING;
+                "#,
+                name,
+                description
+            ).execute(&self.pool).await?;
+        Ok(result.rows_affected() > 0)
+    }
+    /// Grants the permission to the role unless it already has it. Returns whether it was granted.
+    async fn grant_permission(&self, role: RoleType, permission: &str) -> Result<bool, RepositoryError> {
+        let _timer = self.time_query("permission.grant_permission");
+        let result = query!(
+                r#"
+                    INSERT INTO role_permissions (role_id, permission_id)
+                    SELECT r.id, p.id FROM roles AS r, permissions AS p
+                    WHERE r.name = $1 AND p.name = $2
+                    ON CONFLICT DO NOTHING;
+                "#,
+                role as RoleType,
+                permission
+            ).execute(&self.pool).await?;
+        Ok(result.rows_affected() > 0)
     }
 }
\ No newline at end of file
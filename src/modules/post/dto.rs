@@ -1,8 +1,14 @@
-use serde::Deserialize;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 use validator::{Validate, ValidationError};
+use crate::{dto::{require_any_field, validate_fields}, modules::post::model::PostLicense, utils::hashtag};
 
 fn validate_tags(tags: &Vec<String>) -> Result<(), ValidationError> {
+    if tags.len() > hashtag::MAX_TAGS {
+        return Err(ValidationError::new("A post can have at most 10 tags."));
+    }
     for tag in tags {
         let length = tag.len();
         if tag.trim().is_empty() {
@@ -18,7 +24,7 @@ fn validate_tags(tags: &Vec<String>) -> Result<(), ValidationError> {
     Ok(())
 }
 
-#[derive(Deserialize, Validate)]
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
 pub struct PostRequest {
     #[validate(length(
         min = 4,
@@ -35,11 +41,127 @@ pub struct PostRequest {
     #[validate(length(min = 1, message = "At least one tag is required"))]
     #[validate(custom(function = "validate_tags"))]
     pub tags: Vec<String>,
+    pub license: Option<PostLicense>,
+    /// Publishes the post under an organization the author owns or edits for. Only read on create,
+    /// updates keep the post where it is.
+    pub organization_id: Option<Uuid>,
+}
+
+/// Body of `POST /api/post/{id}/collaborators`.
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
+pub struct PostCollaboratorRequest {
+    pub user_id: Uuid,
+}
+
+/// Body of `PATCH /api/post/{id}`: only the given fields change. `PUT` bodies are turned into one
+/// of these as well.
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
+#[validate(schema(function = "validate_post_patch", skip_on_field_errors = true))]
+pub struct PostPatchRequest {
+    #[validate(length(
+        min = 4,
+        max = 20,
+        message = "Title must be between 4 and 20 characters"
+    ))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[validate(length(
+        min = 8,
+        max = 200,
+        message = "Content must be between 8 and 200 characters"
+    ))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[validate(length(min = 1, message = "At least one tag is required"))]
+    #[validate(custom(function = "validate_tags"))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<PostLicense>,
+}
+fn validate_post_patch(patch: &PostPatchRequest) -> Result<(), ValidationError> {
+    require_any_field(patch.title.is_some() || patch.content.is_some() || patch.tags.is_some() || patch.license.is_some())
+}
+impl From<PostRequest> for PostPatchRequest {
+    fn from(body: PostRequest) -> Self {
+        Self {
+            title: Some(body.title),
+            content: Some(body.content),
+            tags: Some(body.tags),
+            license: body.license,
+        }
+    }
+}
+
+/// What `fields=` may pick from a post list item.
+pub const POST_LIST_FIELDS: &[&str] = &["id", "title", "content", "tags", "license", "created_at", "updated_at"];
+fn validate_post_list_fields(value: &str) -> Result<(), ValidationError> {
+    validate_fields(value, POST_LIST_FIELDS)
+}
+
+#[derive(Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PostListParams {
+    /// Comma separated post fields to return, e.g. `id,title,tags`. `id` is always included.
+    #[validate(custom(function = "validate_post_list_fields"))]
+    pub fields: Option<String>,
+}
+
+fn validate_embed_format(value: &str) -> Result<(), ValidationError> {
+    match value {
+        "html" | "json" => Ok(()),
+        _ => {
+            let mut error = ValidationError::new("invalid_format");
+            error.message = Some("Format must be either 'html' or 'json'".into());
+            Err(error)
+        }
+    }
+}
+
+#[derive(Deserialize, Validate)]
+pub struct EmbedParams {
+    #[validate(length(min = 1, message = "Token is required."))]
+    pub token: String,
+    #[validate(custom(function = "validate_embed_format"))]
+    pub format: Option<String>,
+}
+fn validate_syndication_format(value: &str) -> Result<(), ValidationError> {
+    match value {
+        "rss" | "atom" => Ok(()),
+        _ => {
+            let mut error = ValidationError::new("invalid_format");
+            error.message = Some("Format must be either 'rss' or 'atom'".into());
+            Err(error)
+        }
+    }
+}
+#[derive(Deserialize, Validate)]
+pub struct SyndicationParams {
+    #[validate(custom(function = "validate_syndication_format"))]
+    pub format: Option<String>,
+}
+#[derive(Serialize)]
+pub struct EmbedTokenResponse {
+    pub token: String,
+    pub embed_url: String,
+    pub expires_in: i64,
+}
+#[derive(Serialize)]
+pub struct EmbedPost {
+    pub id: Uuid,
+    pub title: String,
+    pub content: String,
+    pub tags: Vec<String>,
+    pub license: PostLicense,
+    pub author: String,
+    pub created_at: DateTime<Utc>,
 }
 
 pub struct NewPost {
     pub user_id: Uuid,
+    pub organization_id: Option<Uuid>,
     pub title: String,
     pub content: String,
     pub tags: Vec<String>,
+    pub license: Option<PostLicense>,
 }
\ No newline at end of file
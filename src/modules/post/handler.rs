@@ -1,13 +1,29 @@
-use std::sync::Arc;
-use axum::{middleware, Router, routing::{delete, get, post, put}, Extension, response::IntoResponse};
+use std::{net::SocketAddr, sync::Arc};
+use axum::{
+    middleware, Router, routing::{delete, get, patch, post, put}, Extension,
+    extract::ConnectInfo,
+    http::{HeaderMap, HeaderValue, StatusCode, header::{CACHE_CONTROL, CONTENT_SECURITY_POLICY, CONTENT_TYPE, ETAG, IF_NONE_MATCH, LAST_MODIFIED}},
+    response::{Html, IntoResponse, Response},
+};
+use reqwest::Url;
+use serde_json::json;
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
-use validator::Validate;
 use crate::{
     AppState,
-    dto::{HttpResult, SuccessResponse},
-    error::{BodyParser, PathParser, FieldError, HttpError, ErrorMessage, map_sqlx_error},
+    dto::{select_fields, BatchData, BatchRequest, HttpResult, SuccessResponse},
+    error::{IfMatch, ValidatedJson, PathParser, ValidatedQuery},
     middleware::{AuthenticatedUser, permission::{check_permission, Permission}},
-    modules::post::dto::{PostRequest, NewPost}
+    modules::{
+        post::{
+            dto::{PostRequest, PostPatchRequest, PostListParams, PostCollaboratorRequest, EmbedParams, EmbedPost, SyndicationParams},
+            model::{Post, PostCollaborator, PostDetail, PostListByUser, SyndicatedPost},
+            syndication::{self, FeedChannel, FeedFormat},
+        },
+        report::handler::report_post,
+        aggregate::handler::post_trending,
+    },
+    utils::{html, ndjson},
 };
 
 pub fn post_router() -> Router {
@@ -15,83 +31,490 @@ pub fn post_router() -> Router {
         .route("/", post(post_create).layer(middleware::from_fn(|state, req, next| {
             check_permission(state, req, next, Permission::PostCreate.to_string())
         })))
+        .route("/trending", get(post_trending).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::PostTrending.to_string())
+        })))
         .route("/{id}", get(post_detail).layer(middleware::from_fn(|state, req, next| {
             check_permission(state, req, next, Permission::PostDetail.to_string())
         })))
+        .route("/stream", get(post_stream).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminPostExport.to_string())
+        })))
+        .route("/batch", post(post_batch).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::PostDetail.to_string())
+        })))
         .route("/user/{id}", get(post_list_by_user).layer(middleware::from_fn(|state, req, next| {
             check_permission(state, req, next, Permission::PostListByUser.to_string())
         })))
         .route("/{id}", put(post_update).layer(middleware::from_fn(|state, req, next| {
             check_permission(state, req, next, Permission::PostUpdate.to_string())
         })))
+        .route("/{id}", patch(post_patch).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::PostUpdate.to_string())
+        })))
         .route("/{id}", delete(post_delete).layer(middleware::from_fn(|state, req, next| {
             check_permission(state, req, next, Permission::PostDelete.to_string())
         })))
+        .route("/{id}/view", post(post_view).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::PostDetail.to_string())
+        })))
+        .route("/{id}/collaborators", get(post_collaborators).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::PostDetail.to_string())
+        })))
+        .route("/{id}/collaborators", post(post_collaborator_add).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::PostCollaboratorManage.to_string())
+        })))
+        .route("/{id}/collaborators/{user_id}", delete(post_collaborator_remove).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::PostCollaboratorManage.to_string())
+        })))
+        .route("/{id}/embed-token", post(post_embed_token).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::PostEmbed.to_string())
+        })))
+        .route("/{id}/report", post(report_post).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::ReportCreate.to_string())
+        })))
+}
+
+/// Routes that are reachable without an access token, an iframe can't send one.
+pub fn post_public_router() -> Router {
+    Router::new()
+        .route("/{id}/embed", get(post_embed))
+}
+/// `GET /api/user/{id}/posts.rss`, public like the embeds.
+pub fn user_syndication_router() -> Router {
+    Router::new()
+        .route("/{id}/posts.rss", get(post_user_syndication))
+}
+/// `GET /api/tag/{name}/posts.rss`, public like the embeds.
+pub fn tag_syndication_router() -> Router {
+    Router::new()
+        .route("/{name}/posts.rss", get(post_tag_syndication))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/post",
+    tag = "post",
+    request_body = PostRequest,
+    responses(
+        (status = 200, description = "Created post", body = SuccessResponse<Post>),
+        (status = 403, description = "Not an owner or editor of the organization in `organization_id`"),
+        (status = 404, description = "No organization with the id in `organization_id`"),
+        (status = 422, description = "Rejected by the content filter, the reasons are attached per field"),
+        (status = 429, description = "Over the post velocity limit, retry after `Retry-After` seconds"),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn post_create(
     Extension(app_state): Extension<Arc<AppState>>,
     Extension(user_auth): Extension<AuthenticatedUser>,
-    BodyParser(body): BodyParser<PostRequest>
+    ValidatedJson(body): ValidatedJson<PostRequest>
 ) -> HttpResult<impl IntoResponse> {
-    body.validate().map_err(FieldError::populate_errors)?;
-    let new_post = NewPost {
-        user_id: user_auth.user.id,
-        title: body.title,
-        content: body.content,
-        tags: body.tags,
-    };
-    let data = app_state.db_client.save_post(new_post).await
-        .map_err(map_sqlx_error)?;
+    let data = app_state.post_service().create(&user_auth.user, body).await?;
     Ok(
         SuccessResponse::new("Successfully created a new post.", Some(data))
     )
 }
+#[utoipa::path(
+    get,
+    path = "/api/post/{id}",
+    tag = "post",
+    params(("id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Post with its author and comments", body = SuccessResponse<PostDetail>),
+        (status = 403, description = "Author account is private"),
+        (status = 404, description = "Post not found"),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn post_detail(
     Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
     PathParser(post_id): PathParser<Uuid>,
 ) -> HttpResult<impl IntoResponse> {
-    let post_detail = app_state.db_client.get_post_detail(post_id).await
-        .map_err(map_sqlx_error)?
-        .ok_or(HttpError::not_found(ErrorMessage::DataNotFound.to_string(), None))?;
+    let post_detail = app_state.post_service().detail(post_id, &user_auth.user).await?;
     Ok(
         SuccessResponse::new("Getting posts detail data", Some(post_detail))
     )
 }
+#[utoipa::path(
+    post,
+    path = "/api/post/batch",
+    tag = "post",
+    request_body = BatchRequest,
+    responses(
+        (status = 200, description = "Posts in the requested order, hidden or unknown ids listed as missing", body = SuccessResponse<BatchData<Post>>),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn post_batch(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    ValidatedJson(body): ValidatedJson<BatchRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let result = app_state.post_service().batch(&body, &user_auth.user).await?;
+    Ok(
+        SuccessResponse::new("Getting post data by ids", Some(result))
+    )
+}
+#[utoipa::path(
+    get,
+    path = "/api/post/user/{id}",
+    tag = "post",
+    params(("id" = Uuid, Path), PostListParams),
+    responses(
+        (status = 200, description = "Posts written by the user", body = SuccessResponse<PostListByUser>),
+        (status = 403, description = "Account is private"),
+        (status = 404, description = "User not found"),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn post_list_by_user(
     Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
     PathParser(user_id): PathParser<Uuid>,
+    ValidatedQuery(params): ValidatedQuery<PostListParams>,
 ) -> HttpResult<impl IntoResponse> {
-    let post_by_user = app_state.db_client.get_post_list_by_user(user_id).await
-        .map_err(map_sqlx_error)?
-        .ok_or(HttpError::not_found(ErrorMessage::DataNotFound.to_string(), None))?;
+    let post_by_user = app_state.post_service().list_by_user(user_id, &user_auth.user).await?;
+    let post_by_user = json!({
+        "user": post_by_user.user,
+        "posts": select_fields(post_by_user.posts, params.fields.as_deref()),
+    });
     Ok(
         SuccessResponse::new("Getting list of posts by user", Some(post_by_user))
     )
 }
+#[utoipa::path(
+    get,
+    path = "/api/post/stream",
+    tag = "post",
+    responses(
+        (status = 200, description = "Every post, oldest first, one JSON object per line", body = Post, content_type = "application/x-ndjson"),
+        (status = 403, description = "Missing the admin:post-export permission"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn post_stream(
+    Extension(app_state): Extension<Arc<AppState>>,
+) -> impl IntoResponse {
+    (
+        [(CONTENT_TYPE, "application/x-ndjson")],
+        ndjson::body(app_state.db.posts.stream_posts()),
+    )
+}
+#[utoipa::path(
+    put,
+    path = "/api/post/{id}",
+    tag = "post",
+    params(("id" = Uuid, Path), ("If-Match" = Option<String>, Header, description = "`updated_at` of the edited version")),
+    request_body = PostRequest,
+    responses(
+        (status = 200, description = "Updated post", body = SuccessResponse<Post>),
+        (status = 403, description = "Not allowed to edit the post: neither the author, a collaborator, an owner or editor of its organization nor an admin"),
+        (status = 409, description = "The post changed since the If-Match version, the current one is attached"),
+        (status = 422, description = "Rejected by the content filter, the reasons are attached per field"),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn post_update(
     Extension(app_state): Extension<Arc<AppState>>,
     Extension(user_auth): Extension<AuthenticatedUser>,
     PathParser(post_id): PathParser<Uuid>,
-    BodyParser(body): BodyParser<PostRequest>,
+    IfMatch(expected_version): IfMatch,
+    ValidatedJson(body): ValidatedJson<PostRequest>,
 ) -> HttpResult<impl IntoResponse> {
-    body.validate().map_err(FieldError::populate_errors)?;
-    let updated_post = app_state.db_client.update_post(
-            post_id, user_auth.user.id, user_auth.user.role_id, body
-        ).await.map_err(map_sqlx_error)?;
-    Ok(
-        SuccessResponse::new("Successfully updating post data.", Some(updated_post))
-    )
+    let updated_post = app_state.post_service().update(post_id, &user_auth.user, body.into(), expected_version).await?;
+    Ok((
+        [(ETAG, IfMatch::etag(updated_post.updated_at))],
+        SuccessResponse::new("Successfully updating post data.", Some(updated_post)),
+    ))
 }
+#[utoipa::path(
+    patch,
+    path = "/api/post/{id}",
+    tag = "post",
+    params(("id" = Uuid, Path), ("If-Match" = Option<String>, Header, description = "`updated_at` of the edited version")),
+    request_body = PostPatchRequest,
+    responses(
+        (status = 200, description = "Updated post, fields missing from the body are unchanged", body = SuccessResponse<Post>),
+        (status = 400, description = "Invalid field or empty patch"),
+        (status = 403, description = "Not allowed to edit the post: neither the author, a collaborator, an owner or editor of its organization nor an admin"),
+        (status = 409, description = "The post changed since the If-Match version, the current one is attached"),
+        (status = 422, description = "Rejected by the content filter, the reasons are attached per field"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn post_patch(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(post_id): PathParser<Uuid>,
+    IfMatch(expected_version): IfMatch,
+    ValidatedJson(body): ValidatedJson<PostPatchRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let updated_post = app_state.post_service().update(post_id, &user_auth.user, body, expected_version).await?;
+    Ok((
+        [(ETAG, IfMatch::etag(updated_post.updated_at))],
+        SuccessResponse::new("Successfully updating post data.", Some(updated_post)),
+    ))
+}
+#[utoipa::path(
+    delete,
+    path = "/api/post/{id}",
+    tag = "post",
+    params(("id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Post deleted"),
+        (status = 403, description = "Neither the author, an owner of the post's organization nor an admin"),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn post_delete(
     Extension(app_state): Extension<Arc<AppState>>,
     Extension(user_auth): Extension<AuthenticatedUser>,
     PathParser(post_id): PathParser<Uuid>,
 ) -> HttpResult<impl IntoResponse> {
-    app_state.db_client.delete_post(
-            post_id, user_auth.user.id, user_auth.user.role_id
-        ).await.map_err(map_sqlx_error)?;
+    app_state.post_service().delete(post_id, &user_auth.user).await?;
     Ok(
         SuccessResponse::<()>::new("Successfully deleted a post.", None)
     )
+}
+#[utoipa::path(
+    post,
+    path = "/api/post/{id}/view",
+    tag = "post",
+    params(("id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "View counted, at most once per user and hour"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn post_view(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(post_id): PathParser<Uuid>,
+) -> HttpResult<impl IntoResponse> {
+    app_state.post_service().record_view(post_id, &format!("user:{}", user_auth.user.id)).await;
+    Ok(
+        SuccessResponse::<()>::new("Post view recorded.", None)
+    )
+}
+#[utoipa::path(
+    get,
+    path = "/api/post/{id}/collaborators",
+    tag = "post",
+    params(("id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Users allowed to edit the post, earliest added first", body = SuccessResponse<Vec<PostCollaborator>>),
+        (status = 403, description = "Neither the author, a collaborator nor an admin"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn post_collaborators(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(post_id): PathParser<Uuid>,
+) -> HttpResult<impl IntoResponse> {
+    let collaborators = app_state.post_service().collaborators(post_id, &user_auth.user).await?;
+    Ok(
+        SuccessResponse::new("Getting post collaborators", Some(collaborators))
+    )
+}
+#[utoipa::path(
+    post,
+    path = "/api/post/{id}/collaborators",
+    tag = "post",
+    params(("id" = Uuid, Path)),
+    request_body = PostCollaboratorRequest,
+    responses(
+        (status = 201, description = "The user may now edit the post", body = SuccessResponse<PostCollaborator>),
+        (status = 400, description = "The user is the author of the post"),
+        (status = 403, description = "Neither the author nor an admin"),
+        (status = 404, description = "No such post, or no such user that isn't banned"),
+        (status = 409, description = "Already a collaborator"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn post_collaborator_add(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(post_id): PathParser<Uuid>,
+    ValidatedJson(body): ValidatedJson<PostCollaboratorRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let collaborator = app_state.post_service().add_collaborator(post_id, body.user_id, &user_auth.user).await?;
+    Ok((
+        StatusCode::CREATED,
+        SuccessResponse::new("Successfully added a collaborator.", Some(collaborator)),
+    ))
+}
+#[utoipa::path(
+    delete,
+    path = "/api/post/{id}/collaborators/{user_id}",
+    tag = "post",
+    params(("id" = Uuid, Path), ("user_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "The user can no longer edit the post"),
+        (status = 403, description = "Neither the author, an admin nor the collaborator themselves"),
+        (status = 404, description = "No such post or not a collaborator"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn post_collaborator_remove(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser((post_id, collaborator_id)): PathParser<(Uuid, Uuid)>,
+) -> HttpResult<impl IntoResponse> {
+    app_state.post_service().remove_collaborator(post_id, collaborator_id, &user_auth.user).await?;
+    Ok(
+        SuccessResponse::<()>::new("Successfully removed a collaborator.", None)
+    )
+}
+async fn post_embed_token(
+    Extension(app_state): Extension<Arc<AppState>>,
+    PathParser(post_id): PathParser<Uuid>,
+) -> HttpResult<impl IntoResponse> {
+    let response = app_state.post_service().embed_token(post_id).await?;
+    Ok(
+        SuccessResponse::new("Successfully created an embed token.", Some(response))
+    )
+}
+async fn post_embed(
+    Extension(app_state): Extension<Arc<AppState>>,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+    PathParser(post_id): PathParser<Uuid>,
+    ValidatedQuery(query_params): ValidatedQuery<EmbedParams>,
+) -> HttpResult<Response> {
+    let post_detail = app_state.post_service().embedded(post_id, &query_params.token).await?;
+    if let Some(Extension(ConnectInfo(address))) = connect_info {
+        app_state.post_service().record_view(post_id, &format!("ip:{}", address.ip())).await;
+    }
+    let embed = EmbedPost {
+        id: post_detail.id,
+        title: post_detail.title,
+        content: post_detail.content,
+        tags: post_detail.tags,
+        license: post_detail.license,
+        author: post_detail.user.name,
+        created_at: post_detail.created_at,
+    };
+    let mut response = match query_params.format.as_deref() {
+        Some("json") => SuccessResponse::new("Getting embedded post", Some(embed)).into_response(),
+        _ => {
+            let tags = embed.tags.iter()
+                .map(|tag| format!("<span class=\"tag\">#{}</span>", html::escape(tag)))
+                .collect::<Vec<String>>()
+                .join(" ");
+            Html(format!(
+                "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{title}</title></head>\
+                <body><article><h1>{title}</h1><p>{content}</p><footer>{tags}<br>\
+                <small>by {author} &middot; {created_at} &middot; {license}</small></footer></article></body></html>",
+                title = html::escape(&embed.title),
+                content = html::escape(&embed.content),
+                tags = tags,
+                author = html::escape(&embed.author),
+                created_at = embed.created_at.format("%Y-%m-%d"),
+                license = embed.license.get_value(),
+            )).into_response()
+        }
+    };
+    let frame_ancestors = format!("frame-ancestors {}", app_state.env.embed_frame_ancestors);
+    if let Ok(value) = HeaderValue::from_str(&frame_ancestors) {
+        response.headers_mut().insert(CONTENT_SECURITY_POLICY, value);
+    }
+    Ok(response)
+}
+#[utoipa::path(
+    get,
+    path = "/api/user/{id}/posts.rss",
+    tag = "post",
+    params(
+        ("id" = Uuid, Path),
+        ("format" = Option<String>, Query, description = "`rss` (default) or `atom`"),
+    ),
+    responses(
+        (status = 200, description = "The author's latest posts as RSS 2.0 or Atom", content_type = "application/rss+xml"),
+        (status = 304, description = "The `If-None-Match` version is still current"),
+        (status = 404, description = "No such user, or a private or banned one"),
+    ),
+)]
+async fn post_user_syndication(
+    Extension(app_state): Extension<Arc<AppState>>,
+    PathParser(user_id): PathParser<Uuid>,
+    ValidatedQuery(query_params): ValidatedQuery<SyndicationParams>,
+    headers: HeaderMap,
+) -> HttpResult<Response> {
+    let (user, posts) = app_state.post_service().syndicated_by_user(user_id).await?;
+    let channel = FeedChannel {
+        title: format!("Posts by {}", user.name),
+        description: format!("The latest posts by {}", user.name),
+        link: frontend_link(&app_state.env.frontend_url, &["user", &user_id.to_string()]),
+    };
+    Ok(syndication_response(&app_state, &headers, &query_params, &channel, &posts))
+}
+#[utoipa::path(
+    get,
+    path = "/api/tag/{name}/posts.rss",
+    tag = "post",
+    params(
+        ("name" = String, Path, description = "The tag, matched exactly"),
+        ("format" = Option<String>, Query, description = "`rss` (default) or `atom`"),
+    ),
+    responses(
+        (status = 200, description = "The latest public posts with the tag as RSS 2.0 or Atom", content_type = "application/rss+xml"),
+        (status = 304, description = "The `If-None-Match` version is still current"),
+    ),
+)]
+async fn post_tag_syndication(
+    Extension(app_state): Extension<Arc<AppState>>,
+    PathParser(tag): PathParser<String>,
+    ValidatedQuery(query_params): ValidatedQuery<SyndicationParams>,
+    headers: HeaderMap,
+) -> HttpResult<Response> {
+    let posts = app_state.post_service().syndicated_by_tag(&tag).await?;
+    let channel = FeedChannel {
+        title: format!("Posts tagged #{}", tag),
+        description: format!("The latest posts tagged #{}", tag),
+        link: frontend_link(&app_state.env.frontend_url, &["tag", &tag]),
+    };
+    Ok(syndication_response(&app_state, &headers, &query_params, &channel, &posts))
+}
+/// The rendered feed with an ETag of its bytes, so a reader polling with `If-None-Match` gets a 304
+/// until a post is added, edited, hidden or deleted. `If-Modified-Since` alone isn't answered with a
+/// 304: deleting the newest post moves `Last-Modified` back in time.
+fn syndication_response(
+    app_state: &AppState,
+    headers: &HeaderMap,
+    query_params: &SyndicationParams,
+    channel: &FeedChannel,
+    posts: &[SyndicatedPost],
+) -> Response {
+    let format = FeedFormat::from_param(query_params.format.as_deref());
+    let xml = syndication::render(format, channel, posts, &app_state.env.frontend_url);
+    let etag = format!("\"{}\"", hex::encode(&Sha256::digest(xml.as_bytes())[..16]));
+    let cache_control = format!("public, max-age={}", app_state.env.syndication_cache_ttl);
+    let fresh = headers.get_all(IF_NONE_MATCH).iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|tag| tag.trim() == etag || tag.trim() == "*");
+    let mut response = if fresh {
+        (StatusCode::NOT_MODIFIED, [(ETAG, etag), (CACHE_CONTROL, cache_control)]).into_response()
+    } else {
+        ([(CONTENT_TYPE, format.content_type().to_string()), (ETAG, etag), (CACHE_CONTROL, cache_control)], xml).into_response()
+    };
+    let last_modified = syndication::last_modified(posts).map(|date| date.format("%a, %d %b %Y %H:%M:%S GMT").to_string());
+    if let Some(Ok(value)) = last_modified.as_deref().map(HeaderValue::from_str) {
+        response.headers_mut().insert(LAST_MODIFIED, value);
+    }
+    response
+}
+/// `frontend_url` with `segments` appended, each percent-encoded.
+fn frontend_link(frontend_url: &str, segments: &[&str]) -> String {
+    match Url::parse(frontend_url) {
+        Ok(mut url) if !url.cannot_be_a_base() => {
+            if let Ok(mut path) = url.path_segments_mut() {
+                path.pop_if_empty().extend(segments);
+            }
+            url.to_string()
+        }
+        _ => format!("{}/{}", frontend_url.trim_end_matches('/'), segments.join("/")),
+    }
 }
\ No newline at end of file
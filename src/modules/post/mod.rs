@@ -1,3 +1,5 @@
 pub mod model;
 pub mod handler;
-pub mod dto;
\ No newline at end of file
+pub mod dto;
+pub mod service;
+pub mod syndication;
\ No newline at end of file
@@ -1,28 +1,67 @@
+use async_stream::try_stream;
+use async_trait::async_trait;
 use chrono::{DateTime, Utc};
-use serde::Serialize;
-use sqlx::{FromRow, Error as SqlxError, query_as, query, query_scalar};
+use futures_util::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use sqlx::{FromRow, PgConnection, Type, query_as, query, query_scalar};
 use uuid::Uuid;
 use crate::{
-    db::DBClient,
+    db::{DBClient, RowStream},
     modules::{
-        post::dto::{NewPost, PostRequest},
+        post::dto::{NewPost, PostPatchRequest},
         user::dto::UserResponse,
         role::model::{RoleType, RoleRepository},
     },
-    error::ErrorMessage
+    error::RepositoryError,
+    utils::hashtag,
 };
 
-#[derive(Serialize, FromRow)]
+#[derive(Serialize, Deserialize, Type, Clone, Copy, Debug, ToSchema)]
+#[sqlx(type_name = "post_license", rename_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum PostLicense {
+    AllRightsReserved,
+    Cc0,
+    CcBy,
+    CcBySa,
+    CcByNd,
+    CcByNc,
+    CcByNcSa,
+    CcByNcNd,
+}
+
+impl PostLicense {
+    pub fn get_value(&self) -> &str {
+        match self {
+            PostLicense::AllRightsReserved => "all-rights-reserved",
+            PostLicense::Cc0 => "cc0",
+            PostLicense::CcBy => "cc-by",
+            PostLicense::CcBySa => "cc-by-sa",
+            PostLicense::CcByNd => "cc-by-nd",
+            PostLicense::CcByNc => "cc-by-nc",
+            PostLicense::CcByNcSa => "cc-by-nc-sa",
+            PostLicense::CcByNcNd => "cc-by-nc-nd",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, FromRow, ToSchema, Clone)]
 pub struct Post {
     pub id: Uuid,
     pub user_id: Uuid,
+    /// The organization the post was published under, `None` for personal posts.
+    pub organization_id: Option<Uuid>,
     pub title: String,
     pub content: String,
     pub tags: Vec<String>,
+    pub license: PostLicense,
+    /// Views flushed from Redis so far, at most one per viewer and hour.
+    pub views_count: i64,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct PostComment {
     pub id: Uuid,
     pub user_id: Uuid,
@@ -30,18 +69,20 @@ pub struct PostComment {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct PostDetail {
     pub id: Uuid,
     pub title: String,
     pub content: String,
     pub tags: Vec<String>,
+    pub license: PostLicense,
+    pub views_count: i64,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub user: UserResponse,
     pub comments: Vec<PostComment>,
 }
-#[derive(Serialize, FromRow)]
+#[derive(Serialize, Deserialize, FromRow, ToSchema)]
 pub struct UserPost {
     pub id: Uuid,
     pub name: String,
@@ -49,46 +90,142 @@ pub struct UserPost {
     pub role: RoleType,
     pub is_verified: bool,
 }
-#[derive(Serialize, FromRow)]
+#[derive(Serialize, Deserialize, FromRow, ToSchema)]
 pub struct PostUser {
     pub id: Uuid,
     pub title: String,
     pub content: String,
     pub tags: Vec<String>,
+    pub license: PostLicense,
+    pub views_count: i64,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct PostListByUser {
     pub user: UserPost,
     pub posts: Vec<PostUser>,
 }
+/// A user the author lets edit a post.
+#[derive(Serialize, Deserialize, FromRow, ToSchema)]
+pub struct PostCollaborator {
+    pub user_id: Uuid,
+    pub name: String,
+    pub username: Option<String>,
+    pub avatar_url: Option<String>,
+    pub added_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+/// A post as it goes into an RSS or Atom feed.
+#[derive(FromRow)]
+pub struct SyndicatedPost {
+    pub id: Uuid,
+    pub title: String,
+    pub content: String,
+    pub tags: Vec<String>,
+    pub license: PostLicense,
+    pub author_name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait PostRepository: Send + Sync {
+    /// The `#hashtags` of the content are stored as tags along with the given ones.
+    async fn save_post(&self, data: NewPost) -> Result<Post, RepositoryError>;
+    async fn get_post_detail(&self, post_id: Uuid) -> Result<Option<PostDetail>, RepositoryError>;
+    async fn get_post_list_by_user(&self, user_id: Uuid) -> Result<Option<PostListByUser>, RepositoryError>;
+    /// The posts among `post_ids` that `viewer_id` may see, in the order of `post_ids`. Hidden posts
+    /// and posts of private authors the viewer doesn't follow are left out.
+    async fn get_posts_by_ids(&self, post_ids: &[Uuid], viewer_id: Uuid) -> Result<Vec<Post>, RepositoryError>;
+    /// The organization the post is published under, `None` for personal and missing posts.
+    async fn get_post_organization_id(&self, post_id: Uuid) -> Result<Option<Uuid>, RepositoryError>;
+    /// `expected_version` is the `updated_at` the caller edited, a different stored one fails with `VersionMismatch`.
+    /// The hashtags of the resulting content are added to the resulting tags, read and written under
+    /// the same row lock. The author, the post's collaborators, the owners and editors of its
+    /// organization and admins may update it.
+    async fn update_post(&self, post_id: Uuid, user_id: Uuid, user_role_id: Uuid, patch: PostPatchRequest, expected_version: Option<DateTime<Utc>>) -> Result<Post, RepositoryError>;
+    /// Only the author, the owners of its organization and admins may delete a post, collaborators
+    /// and editors can't. Returns the deleted post.
+    async fn delete_post(&self, post_id: Uuid, user_id: Uuid, user_role_id: Uuid) -> Result<Post, RepositoryError>;
+    /// The collaborators of the post, earliest added first, for whoever may update it.
+    async fn get_post_collaborators(&self, post_id: Uuid, user_id: Uuid, user_role_id: Uuid) -> Result<Vec<PostCollaborator>, RepositoryError>;
+    /// Lets `collaborator_id` update the post. Only the author and admins add collaborators, the
+    /// author can't be one (`InvalidInput`) and banned or missing users are `NotFound`.
+    async fn add_post_collaborator(&self, post_id: Uuid, collaborator_id: Uuid, user_id: Uuid, user_role_id: Uuid) -> Result<PostCollaborator, RepositoryError>;
+    /// The author and admins remove any collaborator, a collaborator only themselves.
+    async fn remove_post_collaborator(&self, post_id: Uuid, collaborator_id: Uuid, user_id: Uuid, user_role_id: Uuid) -> Result<(), RepositoryError>;
+    /// Every post, hidden ones included, oldest first, from a single query streamed row by row.
+    fn stream_posts(&self) -> RowStream<Post>;
+    /// Adds `(post_id, views)` to the posts' `views_count` and to their `post_views` row of the current
+    /// hour. Posts deleted in the meantime are skipped.
+    async fn add_post_views(&self, views: &[(Uuid, i64)]) -> Result<(), RepositoryError>;
+    /// The author's latest `limit` posts that aren't hidden, newest first. Whether the author may be
+    /// syndicated at all is up to the caller.
+    async fn get_syndicated_posts_by_user(&self, user_id: Uuid, limit: i64) -> Result<Vec<SyndicatedPost>, RepositoryError>;
+    /// The latest `limit` posts tagged exactly `tag`, newest first, leaving out hidden posts and
+    /// those of private or banned authors.
+    async fn get_syndicated_posts_by_tag(&self, tag: &str, limit: i64) -> Result<Vec<SyndicatedPost>, RepositoryError>;
+}
 
 impl DBClient {
-    pub async fn save_post(&self, data: NewPost) -> Result<Post, SqlxError> {
+    /// Whether `user_id` is the author of the post, one of its collaborators, an owner or editor of
+    /// its organization or an admin.
+    async fn can_edit_post(&self, conn: &mut PgConnection, post_id: Uuid, author_id: Uuid, user_id: Uuid, user_role_id: Uuid) -> Result<bool, RepositoryError> {
+        if author_id == user_id || self.is_admin(user_role_id).await? {
+            return Ok(true);
+        }
+        let can_edit = query_scalar!(
+            r#"
+                SELECT EXISTS (SELECT 1 FROM post_collaborators WHERE post_id = $1 AND user_id = $2)
+                    OR EXISTS (
+                        SELECT 1 FROM posts AS p
+                        JOIN organization_members AS m ON m.organization_id = p.organization_id
+                        WHERE p.id = $1 AND m.user_id = $2 AND m.role IN ('owner', 'editor')
+                    ) AS "exists!";
+            "#,
+            post_id,
+            user_id,
+        ).fetch_one(conn).await?;
+        Ok(can_edit)
+    }
+    async fn is_admin(&self, role_id: Uuid) -> Result<bool, RepositoryError> {
+        let role = self.get_role_name_by_id(role_id).await?.ok_or(RepositoryError::NotFound)?;
+        Ok(role == RoleType::Admin)
+    }
+}
+
+#[async_trait]
+impl PostRepository for DBClient {
+    async fn save_post(&self, data: NewPost) -> Result<Post, RepositoryError> {
+        let _timer = self.time_query("post.save_post");
         let new_post = query_as!(
             Post,
             r#"
-                INSERT INTO posts (user_id, title, content, tags)
-                VALUES ($1, $2, $3, $4)
-                RETURNING id, user_id, title, content, tags, created_at, updated_at
+                INSERT INTO posts (user_id, title, content, tags, license, organization_id)
+                VALUES ($1, $2, $3, $4, COALESCE($5, (SELECT default_license FROM users WHERE id = $1)), $6)
+                RETURNING id, user_id, organization_id, title, content, tags, license AS "license: PostLicense", views_count, created_at, updated_at
             "#,
             data.user_id,
             data.title,
             data.content,
-            &data.tags,
+            &hashtag::merge(data.tags, &data.content),
+            data.license as Option<PostLicense>,
+            data.organization_id,
         ).fetch_one(&self.pool).await?;
         Ok(new_post)
     }
-    pub async fn get_post_detail(&self, post_id: Uuid) -> Result<Option<PostDetail>, SqlxError> {
+    async fn get_post_detail(&self, post_id: Uuid) -> Result<Option<PostDetail>, RepositoryError> {
+        let _timer = self.time_query("post.get_post_detail");
         let mut transaction = self.pool.begin().await?;
         let record = query!(
             r#"
-                SELECT p.id, p.title, p.content, p.tags, p.created_at, p.updated_at,
-                       u.id AS u_id, u.name AS u_name, u.email AS u_email, r.name AS "role: RoleType", u.password AS u_pass, u.is_verified AS u_is_verified, u.created_at AS u_created_at, u.updated_at AS u_updated_at FROM posts AS p
+                SELECT p.id, p.title, p.content, p.tags, p.license AS "license: PostLicense", p.views_count, p.created_at, p.updated_at,
+                       u.id AS u_id, u.name AS u_name, u.email AS u_email, r.name AS "role: RoleType", u.password AS u_pass, u.is_verified AS u_is_verified, u.is_banned AS u_is_banned, u.is_private AS u_is_private,
+                       u.username AS u_username, u.bio AS u_bio, u.avatar_url AS u_avatar_url, u.website AS u_website, u.created_at AS u_created_at, u.updated_at AS u_updated_at FROM posts AS p
                 JOIN users AS u ON u.id = p.user_id
                 JOIN roles AS r ON r.id = u.role_id
-                WHERE p.id = $1
+                WHERE p.id = $1 AND p.is_hidden = false
             "#,
             post_id,
         ).fetch_optional(&mut *transaction).await?;
@@ -99,7 +236,7 @@ impl DBClient {
             PostComment,
             r#"
                 SELECT id, user_id, content, created_at, updated_at FROM comments
-                WHERE post_id = $1;
+                WHERE post_id = $1 AND is_hidden = false;
             "#,
             data.id,
         ).fetch_all(&mut *transaction).await?;
@@ -108,6 +245,8 @@ impl DBClient {
             title: data.title,
             content: data.content,
             tags: data.tags,
+            license: data.license,
+            views_count: data.views_count,
             created_at: data.created_at,
             updated_at: data.updated_at,
             user: UserResponse {
@@ -117,6 +256,12 @@ impl DBClient {
                 role: data.role,
                 password: data.u_pass,
                 is_verified: data.u_is_verified,
+                is_banned: data.u_is_banned,
+                is_private: data.u_is_private,
+                username: data.u_username,
+                bio: data.u_bio,
+                avatar_url: data.u_avatar_url,
+                website: data.u_website,
                 created_at: data.u_created_at,
                 updated_at: data.u_updated_at,
             },
@@ -125,7 +270,8 @@ impl DBClient {
         transaction.commit().await?;
         Ok(Some(post_detail))
     }
-    pub async fn get_post_list_by_user(&self, user_id: Uuid) -> Result<Option<PostListByUser>, SqlxError> {
+    async fn get_post_list_by_user(&self, user_id: Uuid) -> Result<Option<PostListByUser>, RepositoryError> {
+        let _timer = self.time_query("post.get_post_list_by_user");
         let mut transaction = self.pool.begin().await?;
         let user = query_as!(
             UserPost,
@@ -142,8 +288,8 @@ impl DBClient {
         let posts = query_as!(
             PostUser,
             r#"
-                SELECT id, title, content, tags, created_at, updated_at FROM posts
-                WHERE user_id = $1;
+                SELECT id, title, content, tags, license AS "license: PostLicense", views_count, created_at, updated_at FROM posts
+                WHERE user_id = $1 AND is_hidden = false;
             "#,
             user_id,
         ).fetch_all(&mut *transaction).await?;
@@ -153,53 +299,263 @@ impl DBClient {
             posts,
         }))
     }
-    pub async fn update_post(&self, post_id: Uuid, user_id: Uuid, user_role_id: Uuid, data: PostRequest) -> Result<Post, SqlxError> {
+    async fn get_post_organization_id(&self, post_id: Uuid) -> Result<Option<Uuid>, RepositoryError> {
+        let _timer = self.time_query("post.get_post_organization_id");
+        let organization_id = query_scalar!(
+            r#"
+                SELECT organization_id FROM posts WHERE id = $1;
+            "#,
+            post_id,
+        ).fetch_optional(&self.pool).await?;
+        Ok(organization_id.flatten())
+    }
+    async fn update_post(&self, post_id: Uuid, user_id: Uuid, user_role_id: Uuid, patch: PostPatchRequest, expected_version: Option<DateTime<Utc>>) -> Result<Post, RepositoryError> {
+        let _timer = self.time_query("post.update_post");
         let mut transaction = self.pool.begin().await?;
-        let post_user_id = query_scalar!(
+        let current = query_as!(
+            Post,
             r#"
-                SELECT user_id FROM posts WHERE id = $1 FOR UPDATE;
+                SELECT id, user_id, organization_id, title, content, tags, license AS "license: PostLicense", views_count, created_at, updated_at
+                FROM posts WHERE id = $1 FOR UPDATE;
             "#,
             post_id,
-        ).fetch_optional(&mut *transaction).await?.ok_or(SqlxError::RowNotFound)?;
-        let role = self.get_role_name_by_id(user_role_id).await?.ok_or(SqlxError::RowNotFound)?;
-        if post_user_id != user_id && role.get_value() != RoleType::Admin.get_value() {
-            return Err(SqlxError::InvalidArgument(ErrorMessage::PermissionDenied.to_string()));
+        ).fetch_optional(&mut *transaction).await?.ok_or(RepositoryError::NotFound)?;
+        if !self.can_edit_post(&mut transaction, post_id, current.user_id, user_id, user_role_id).await? {
+            return Err(RepositoryError::Forbidden);
+        }
+        if expected_version.is_some_and(|version| version != current.updated_at) {
+            return Err(RepositoryError::version_mismatch(&current));
         }
+        let tags = hashtag::merge(patch.tags.unwrap_or(current.tags), patch.content.as_deref().unwrap_or(&current.content));
         let post = query_as!(
             Post,
             r#"
                 UPDATE posts
-                SET title = $1, content = $2, tags = $3, updated_at = Now()
-                WHERE id = $4
-                RETURNING id, user_id, title, content, tags, created_at, updated_at;
+                SET title = COALESCE($1, title), content = COALESCE($2, content), tags = $3,
+                    license = COALESCE($4, license), updated_at = Now()
+                WHERE id = $5
+                RETURNING id, user_id, organization_id, title, content, tags, license AS "license: PostLicense", views_count, created_at, updated_at;
             "#,
-            data.title,
-            data.content,
-            &data.tags,
+            patch.title,
+            patch.content,
+            &tags,
+            patch.license as Option<PostLicense>,
             post_id,
         ).fetch_one(&mut *transaction).await?;
         transaction.commit().await?;
         Ok(post)
     }
-    pub async fn delete_post(&self, post_id: Uuid, user_id: Uuid, user_role_id: Uuid) -> Result<(), SqlxError> {
+    async fn get_posts_by_ids(&self, post_ids: &[Uuid], viewer_id: Uuid) -> Result<Vec<Post>, RepositoryError> {
+        let _timer = self.time_query("post.get_posts_by_ids");
+        let posts = query_as!(
+            Post,
+            r#"
+                SELECT p.id, p.user_id, p.organization_id, p.title, p.content, p.tags, p.license AS "license: PostLicense", p.views_count, p.created_at, p.updated_at
+                FROM posts AS p
+                JOIN users AS u ON u.id = p.user_id
+                WHERE p.id = ANY($1) AND p.is_hidden = false
+                  AND (
+                    NOT u.is_private
+                    OR u.id = $2
+                    OR EXISTS (SELECT 1 FROM user_followers WHERE following_id = u.id AND follower_id = $2)
+                    OR EXISTS (
+                        SELECT 1 FROM users AS v JOIN roles AS r ON r.id = v.role_id
+                        WHERE v.id = $2 AND r.name = 'admin'
+                    )
+                  )
+                ORDER BY array_position($1, p.id);
+            "#,
+            post_ids,
+            viewer_id,
+        ).fetch_all(&self.pool).await?;
+        Ok(posts)
+    }
+    async fn delete_post(&self, post_id: Uuid, user_id: Uuid, user_role_id: Uuid) -> Result<Post, RepositoryError> {
+        let _timer = self.time_query("post.delete_post");
         let mut transaction = self.pool.begin().await?;
-        let post_user_id = query_scalar!(
+        let current = query!(
             r#"
-                SELECT user_id FROM posts WHERE id = $1 FOR UPDATE;
+                SELECT p.user_id, m.role IS NOT DISTINCT FROM 'owner' AS "is_organization_owner!"
+                FROM posts AS p
+                LEFT JOIN organization_members AS m ON m.organization_id = p.organization_id AND m.user_id = $2
+                WHERE p.id = $1
+                FOR UPDATE OF p;
             "#,
             post_id,
-        ).fetch_optional(&mut *transaction).await?.ok_or(SqlxError::RowNotFound)?;
-        let role = self.get_role_name_by_id(user_role_id).await?.ok_or(SqlxError::RowNotFound)?;
-        if post_user_id != user_id && role.get_value() != RoleType::Admin.get_value() {
-            return Err(SqlxError::InvalidArgument(ErrorMessage::PermissionDenied.to_string()));
+            user_id,
+        ).fetch_optional(&mut *transaction).await?.ok_or(RepositoryError::NotFound)?;
+        if current.user_id != user_id && !current.is_organization_owner && !self.is_admin(user_role_id).await? {
+            return Err(RepositoryError::Forbidden);
         }
-        query!(
+        let deleted_post = query_as!(
+            Post,
             r#"
-                DELETE FROM posts WHERE id = $1;
+                DELETE FROM posts WHERE id = $1
+                RETURNING id, user_id, organization_id, title, content, tags, license AS "license: PostLicense", views_count, created_at, updated_at;
             "#,
             post_id,
+        ).fetch_one(&mut *transaction).await?;
+        transaction.commit().await?;
+        Ok(deleted_post)
+    }
+    async fn get_post_collaborators(&self, post_id: Uuid, user_id: Uuid, user_role_id: Uuid) -> Result<Vec<PostCollaborator>, RepositoryError> {
+        let _timer = self.time_query("post.get_post_collaborators");
+        let mut conn = self.pool.acquire().await?;
+        let author_id = query_scalar!(
+            r#"
+                SELECT user_id FROM posts WHERE id = $1;
+            "#,
+            post_id,
+        ).fetch_optional(&mut *conn).await?.ok_or(RepositoryError::NotFound)?;
+        if !self.can_edit_post(&mut conn, post_id, author_id, user_id, user_role_id).await? {
+            return Err(RepositoryError::Forbidden);
+        }
+        let collaborators = query_as!(
+            PostCollaborator,
+            r#"
+                SELECT c.user_id, u.name, u.username, u.avatar_url, c.added_by, c.created_at
+                FROM post_collaborators AS c
+                JOIN users AS u ON u.id = c.user_id
+                WHERE c.post_id = $1
+                ORDER BY c.created_at, c.user_id;
+            "#,
+            post_id,
+        ).fetch_all(&mut *conn).await?;
+        Ok(collaborators)
+    }
+    async fn add_post_collaborator(&self, post_id: Uuid, collaborator_id: Uuid, user_id: Uuid, user_role_id: Uuid) -> Result<PostCollaborator, RepositoryError> {
+        let _timer = self.time_query("post.add_post_collaborator");
+        let mut transaction = self.pool.begin().await?;
+        let author_id = query_scalar!(
+            r#"
+                SELECT user_id FROM posts WHERE id = $1 FOR SHARE;
+            "#,
+            post_id,
+        ).fetch_optional(&mut *transaction).await?.ok_or(RepositoryError::NotFound)?;
+        if author_id != user_id && !self.is_admin(user_role_id).await? {
+            return Err(RepositoryError::Forbidden);
+        }
+        if collaborator_id == author_id {
+            return Err(RepositoryError::InvalidInput("The author of a post can't be its collaborator".to_string()));
+        }
+        let collaborator = query_as!(
+            PostCollaborator,
+            r#"
+                WITH added AS (
+                    INSERT INTO post_collaborators (post_id, user_id, added_by)
+                    SELECT $1, id, $3 FROM users WHERE id = $2 AND is_banned = false
+                    RETURNING user_id, added_by, created_at
+                )
+                SELECT a.user_id AS "user_id!", u.name AS "name!", u.username, u.avatar_url, a.added_by, a.created_at AS "created_at!"
+                FROM added AS a
+                JOIN users AS u ON u.id = a.user_id;
+            "#,
+            post_id,
+            collaborator_id,
+            user_id,
+        ).fetch_optional(&mut *transaction).await?.ok_or(RepositoryError::NotFound)?;
+        transaction.commit().await?;
+        Ok(collaborator)
+    }
+    async fn remove_post_collaborator(&self, post_id: Uuid, collaborator_id: Uuid, user_id: Uuid, user_role_id: Uuid) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("post.remove_post_collaborator");
+        let mut transaction = self.pool.begin().await?;
+        let author_id = query_scalar!(
+            r#"
+                SELECT user_id FROM posts WHERE id = $1 FOR SHARE;
+            "#,
+            post_id,
+        ).fetch_optional(&mut *transaction).await?.ok_or(RepositoryError::NotFound)?;
+        if author_id != user_id && collaborator_id != user_id && !self.is_admin(user_role_id).await? {
+            return Err(RepositoryError::Forbidden);
+        }
+        let result = query!(
+            r#"
+                DELETE FROM post_collaborators WHERE post_id = $1 AND user_id = $2;
+            "#,
+            post_id,
+            collaborator_id,
         ).execute(&mut *transaction).await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
         transaction.commit().await?;
         Ok(())
     }
+    fn stream_posts(&self) -> RowStream<Post> {
+        let (pool, timer) = (self.pool.clone(), self.time_query("post.stream_posts"));
+        Box::pin(try_stream! {
+            let _timer = timer;
+            let mut posts = query_as!(
+                Post,
+                r#"
+                    SELECT id, user_id, organization_id, title, content, tags, license AS "license: PostLicense", views_count, created_at, updated_at
+                    FROM posts
+                    ORDER BY created_at, id;
+                "#,
+            ).fetch(&pool);
+            while let Some(post) = posts.try_next().await? {
+                yield post;
+            }
+        })
+    }
+    async fn add_post_views(&self, views: &[(Uuid, i64)]) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("post.add_post_views");
+        let (post_ids, counts): (Vec<Uuid>, Vec<i64>) = views.iter().copied().unzip();
+        let mut transaction = self.pool.begin().await?;
+        query!(
+            r#"
+                INSERT INTO post_views (post_id, hour, views)
+                SELECT v.post_id, date_trunc('hour', Now()), v.views
+                FROM UNNEST($1::uuid[], $2::bigint[]) AS v(post_id, views)
+                JOIN posts AS p ON p.id = v.post_id
+                ON CONFLICT (post_id, hour) DO UPDATE SET views = post_views.views + EXCLUDED.views;
+            "#,
+            &post_ids,
+            &counts,
+        ).execute(&mut *transaction).await?;
+        query!(
+            r#"
+                UPDATE posts AS p SET views_count = p.views_count + v.views
+                FROM UNNEST($1::uuid[], $2::bigint[]) AS v(post_id, views)
+                WHERE p.id = v.post_id;
+            "#,
+            &post_ids,
+            &counts,
+        ).execute(&mut *transaction).await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+    async fn get_syndicated_posts_by_user(&self, user_id: Uuid, limit: i64) -> Result<Vec<SyndicatedPost>, RepositoryError> {
+        let _timer = self.time_query("post.get_syndicated_posts_by_user");
+        let posts = query_as!(
+            SyndicatedPost,
+            r#"
+                SELECT p.id, p.title, p.content, p.tags, p.license AS "license: PostLicense", u.name AS author_name, p.created_at, p.updated_at
+                FROM posts AS p JOIN users AS u ON u.id = p.user_id
+                WHERE p.user_id = $1 AND p.is_hidden = false
+                ORDER BY p.created_at DESC, p.id DESC
+                LIMIT $2;
+            "#,
+            user_id,
+            limit,
+        ).fetch_all(&self.pool).await?;
+        Ok(posts)
+    }
+    async fn get_syndicated_posts_by_tag(&self, tag: &str, limit: i64) -> Result<Vec<SyndicatedPost>, RepositoryError> {
+        let _timer = self.time_query("post.get_syndicated_posts_by_tag");
+        let posts = query_as!(
+            SyndicatedPost,
+            r#"
+                SELECT p.id, p.title, p.content, p.tags, p.license AS "license: PostLicense", u.name AS author_name, p.created_at, p.updated_at
+                FROM posts AS p JOIN users AS u ON u.id = p.user_id
+                WHERE p.tags @> ARRAY[$1]::VARCHAR[] AND p.is_hidden = false AND u.is_private = false AND u.is_banned = false
+                ORDER BY p.created_at DESC, p.id DESC
+                LIMIT $2;
+            "#,
+            tag,
+            limit,
+        ).fetch_all(&self.pool).await?;
+        Ok(posts)
+    }
 }
\ No newline at end of file
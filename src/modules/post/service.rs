@@ -0,0 +1,198 @@
+use std::sync::Arc;
+use chrono::{DateTime, Utc};
+use log::warn;
+use uuid::Uuid;
+use crate::{
+    AppState,
+    dto::{BatchData, BatchRequest},
+    error::{AppError, ErrorMessage},
+    middleware::org_quota::charge_write,
+    modules::{
+        post::{
+            dto::{EmbedTokenResponse, NewPost, PostPatchRequest, PostRequest},
+            model::{Post, PostCollaborator, PostDetail, PostListByUser, SyndicatedPost},
+        },
+        event::domain_event::DomainEvent,
+        report::{dto::ReportTarget, model::ReportReason},
+        user::model::User,
+        velocity::{model::VelocityKind, service::check_velocity},
+    },
+    utils::{jwt, metrics, moderation},
+};
+
+/// Repeated views of a post by the same viewer within this many seconds count once.
+const VIEW_DEDUPE_SECS: u64 = 3600;
+
+/// Post writes, visibility checks and embed tokens. Every write drops the cached post and the cached
+/// feeds it shows up in.
+pub struct PostService {
+    app_state: Arc<AppState>,
+}
+
+impl PostService {
+    pub fn new(app_state: Arc<AppState>) -> Self {
+        Self { app_state }
+    }
+    /// A post shows up in the author's own feed and in the feed of everyone following them.
+    pub async fn invalidate_feeds(&self, author_id: Uuid) {
+        let mut user_ids = self.app_state.db.users.get_follower_ids(author_id).await.unwrap_or_default();
+        user_ids.push(author_id);
+        let _ = self.app_state.redis_client.delete_feeds(&user_ids).await;
+    }
+    /// The feeds of `post`'s author and followers, and of its organization's members and followers.
+    pub async fn invalidate_post_feeds(&self, post: &Post) {
+        self.invalidate_feeds(post.user_id).await;
+        if let Some(organization_id) = post.organization_id {
+            let user_ids = self.app_state.db.organizations.get_audience_ids(organization_id).await.unwrap_or_default();
+            let _ = self.app_state.redis_client.delete_feeds(&user_ids).await;
+        }
+    }
+    async fn ensure_visible(&self, author_id: &Uuid, actor: &User) -> Result<(), AppError> {
+        if !self.app_state.db.users.is_user_visible_to(author_id, &actor.id).await? {
+            return Err(AppError::forbidden(ErrorMessage::PrivateAccount));
+        }
+        Ok(())
+    }
+    /// Counts a write to an organization's post against its quota, members' writes only.
+    async fn charge_organization(&self, post_id: Uuid, actor: &User) -> Result<(), AppError> {
+        match self.app_state.db.posts.get_post_organization_id(post_id).await? {
+            Some(organization_id) => charge_write(&self.app_state, organization_id, actor.id).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Title and content go through the content moderator, flagged posts are refused or reported
+    /// depending on `MODERATION_ACTION`, and the post counts against the author's velocity limit.
+    /// Posts under an organization need the author to be one of its owners or editors, and count
+    /// against its monthly request quota.
+    pub async fn create(&self, actor: &User, body: PostRequest) -> Result<Post, AppError> {
+        if let Some(organization_id) = body.organization_id {
+            let role = self.app_state.db.organizations.get_member_role(organization_id, actor.id).await?;
+            if !role.is_some_and(|role| role.can_publish()) {
+                return Err(AppError::forbidden(ErrorMessage::PermissionDenied));
+            }
+            charge_write(&self.app_state, organization_id, actor.id).await?;
+        }
+        let flagged = moderation::moderate(&self.app_state, &[("title", &body.title), ("content", &body.content)]).await?;
+        let too_fast = check_velocity(&self.app_state, actor, VelocityKind::Post).await?;
+        let new_post = NewPost {
+            user_id: actor.id,
+            organization_id: body.organization_id,
+            title: body.title,
+            content: body.content,
+            tags: body.tags,
+            license: body.license,
+        };
+        let post = self.app_state.db.posts.save_post(new_post).await?;
+        moderation::report_flagged(&self.app_state, ReportTarget::Post(post.id), ReportReason::Other, flagged).await;
+        moderation::report_flagged(&self.app_state, ReportTarget::Post(post.id), ReportReason::Spam, too_fast).await;
+        self.app_state.publish(DomainEvent::PostCreated { post: post.clone() }).await;
+        Ok(post)
+    }
+    /// The post with its author and comments, cached for `POST_CACHE_TTL` seconds. Who may see it
+    /// is decided by the callers on every read.
+    async fn find_detail(&self, post_id: Uuid) -> Result<PostDetail, AppError> {
+        if let Ok(Some(post_detail)) = self.app_state.redis_client.get_post_detail(&post_id).await {
+            metrics::increment_counter("post_cache_requests_total", &[("result", "hit")]);
+            return Ok(post_detail);
+        }
+        metrics::increment_counter("post_cache_requests_total", &[("result", "miss")]);
+        let post_detail = self.app_state.db.posts.get_post_detail(post_id).await?
+            .ok_or(AppError::not_found(ErrorMessage::DataNotFound))?;
+        let _ = self.app_state.redis_client.set_post_detail(&post_detail, self.app_state.env.post_cache_ttl).await;
+        Ok(post_detail)
+    }
+    pub async fn detail(&self, post_id: Uuid, actor: &User) -> Result<PostDetail, AppError> {
+        let post_detail = self.find_detail(post_id).await?;
+        self.ensure_visible(&post_detail.user.id, actor).await?;
+        self.record_view(post_id, &format!("user:{}", actor.id)).await;
+        Ok(post_detail)
+    }
+    pub async fn list_by_user(&self, user_id: Uuid, actor: &User) -> Result<PostListByUser, AppError> {
+        let post_by_user = self.app_state.db.posts.get_post_list_by_user(user_id).await?
+            .ok_or(AppError::not_found(ErrorMessage::DataNotFound))?;
+        self.ensure_visible(&user_id, actor).await?;
+        Ok(post_by_user)
+    }
+    pub async fn batch(&self, body: &BatchRequest, actor: &User) -> Result<BatchData<Post>, AppError> {
+        let ids = body.unique_ids();
+        let posts = self.app_state.db.posts.get_posts_by_ids(&ids, actor.id).await?;
+        Ok(BatchData::new(&ids, posts, |post| post.id))
+    }
+    pub async fn update(&self, post_id: Uuid, actor: &User, patch: PostPatchRequest, expected_version: Option<DateTime<Utc>>) -> Result<Post, AppError> {
+        let fields = [("title", patch.title.as_deref()), ("content", patch.content.as_deref())];
+        let fields: Vec<_> = fields.into_iter().filter_map(|(field, text)| Some((field, text?))).collect();
+        let flagged = moderation::moderate(&self.app_state, &fields).await?;
+        self.charge_organization(post_id, actor).await?;
+        let updated_post = self.app_state.db.posts.update_post(post_id, actor.id, actor.role_id, patch, expected_version).await?;
+        moderation::report_flagged(&self.app_state, ReportTarget::Post(updated_post.id), ReportReason::Other, flagged).await;
+        self.invalidate_post_feeds(&updated_post).await;
+        let _ = self.app_state.redis_client.delete_post_detail(&post_id).await;
+        Ok(updated_post)
+    }
+    pub async fn delete(&self, post_id: Uuid, actor: &User) -> Result<(), AppError> {
+        self.charge_organization(post_id, actor).await?;
+        let deleted_post = self.app_state.db.posts.delete_post(post_id, actor.id, actor.role_id).await?;
+        self.invalidate_post_feeds(&deleted_post).await;
+        let _ = self.app_state.redis_client.delete_post_detail(&post_id).await;
+        Ok(())
+    }
+    pub async fn collaborators(&self, post_id: Uuid, actor: &User) -> Result<Vec<PostCollaborator>, AppError> {
+        Ok(self.app_state.db.posts.get_post_collaborators(post_id, actor.id, actor.role_id).await?)
+    }
+    pub async fn add_collaborator(&self, post_id: Uuid, collaborator_id: Uuid, actor: &User) -> Result<PostCollaborator, AppError> {
+        Ok(self.app_state.db.posts.add_post_collaborator(post_id, collaborator_id, actor.id, actor.role_id).await?)
+    }
+    pub async fn remove_collaborator(&self, post_id: Uuid, collaborator_id: Uuid, actor: &User) -> Result<(), AppError> {
+        Ok(self.app_state.db.posts.remove_post_collaborator(post_id, collaborator_id, actor.id, actor.role_id).await?)
+    }
+    /// Counts a view of `post_id`, once per `viewer` and hour. The count reaches `views_count` when
+    /// `jobs::flush_post_views` next runs, views of posts that don't exist are dropped there.
+    pub async fn record_view(&self, post_id: Uuid, viewer: &str) {
+        if let Err(e) = self.app_state.redis_client.record_post_view(&post_id, viewer, VIEW_DEDUPE_SECS).await {
+            warn!("Failed to count a view of post {}: {}", post_id, e);
+        }
+    }
+    /// Private and banned authors' posts are reported as missing rather than forbidden to embedders.
+    pub async fn embeddable(&self, post_id: Uuid) -> Result<PostDetail, AppError> {
+        let post_detail = self.find_detail(post_id).await?;
+        if post_detail.user.is_private || post_detail.user.is_banned {
+            return Err(AppError::not_found(ErrorMessage::DataNotFound));
+        }
+        Ok(post_detail)
+    }
+    pub async fn embed_token(&self, post_id: Uuid) -> Result<EmbedTokenResponse, AppError> {
+        self.embeddable(post_id).await?;
+        let env = &self.app_state.env;
+        let token = jwt::create_embed_token(&post_id.to_string(), env.jwt_secret.as_bytes(), env.embed_token_age)
+            .map_err(|_| AppError::server_error(ErrorMessage::ServerError))?;
+        Ok(EmbedTokenResponse {
+            embed_url: format!("/api/post/{}/embed?token={}", post_id, token),
+            token,
+            expires_in: env.embed_token_age,
+        })
+    }
+    /// Resolves an embed token to its post, the token must have been issued for `post_id`.
+    pub async fn embedded(&self, post_id: Uuid, token: &str) -> Result<PostDetail, AppError> {
+        let token_post_id = jwt::parse_embed_token(token, self.app_state.env.jwt_secret.as_bytes())
+            .map_err(|_| AppError::unauthorized(ErrorMessage::TokenInvalid))?;
+        if token_post_id != post_id.to_string() {
+            return Err(AppError::unauthorized(ErrorMessage::TokenInvalid));
+        }
+        self.embeddable(post_id).await
+    }
+    /// The author and their latest posts for `GET /api/user/{id}/posts.rss`. Private and banned
+    /// authors are missing, like their embeds.
+    pub async fn syndicated_by_user(&self, user_id: Uuid) -> Result<(User, Vec<SyndicatedPost>), AppError> {
+        let user = self.app_state.db.users.get_user_by_id(&user_id).await?
+            .filter(|user| !user.is_private && !user.is_banned)
+            .ok_or(AppError::not_found(ErrorMessage::DataNotFound))?;
+        let posts = self.app_state.db.posts.get_syndicated_posts_by_user(user_id, self.app_state.env.syndication_limit).await?;
+        Ok((user, posts))
+    }
+    /// The latest public posts tagged `tag` for `GET /api/tag/{name}/posts.rss`, empty rather than
+    /// missing for a tag nobody used yet so readers can subscribe ahead.
+    pub async fn syndicated_by_tag(&self, tag: &str) -> Result<Vec<SyndicatedPost>, AppError> {
+        Ok(self.app_state.db.posts.get_syndicated_posts_by_tag(tag, self.app_state.env.syndication_limit).await?)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,178 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use crate::{modules::post::model::SyndicatedPost, utils::html};
+
+/// The XML flavour of a public feed, `?format=` of the feed endpoints.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FeedFormat {
+    Rss,
+    Atom,
+}
+
+impl FeedFormat {
+    pub fn from_param(format: Option<&str>) -> Self {
+        match format {
+            Some("atom") => FeedFormat::Atom,
+            _ => FeedFormat::Rss,
+        }
+    }
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            FeedFormat::Rss => "application/rss+xml; charset=utf-8",
+            FeedFormat::Atom => "application/atom+xml; charset=utf-8",
+        }
+    }
+}
+
+/// What a feed is about, `link` is where a reader finds the same posts on the frontend.
+pub struct FeedChannel {
+    pub title: String,
+    pub description: String,
+    pub link: String,
+}
+
+/// The posts as an RSS 2.0 or Atom document, newest first as given. Posts link to
+/// `{frontend_url}/post/{id}` and are identified by their id, so an edited post replaces the entry
+/// in readers instead of showing up twice.
+pub fn render(format: FeedFormat, channel: &FeedChannel, posts: &[SyndicatedPost], frontend_url: &str) -> String {
+    let frontend_url = frontend_url.trim_end_matches('/');
+    match format {
+        FeedFormat::Rss => render_rss(channel, posts, frontend_url),
+        FeedFormat::Atom => render_atom(channel, posts, frontend_url),
+    }
+}
+
+/// When the feed last changed as far as its posts tell, `None` for an empty one.
+pub fn last_modified(posts: &[SyndicatedPost]) -> Option<DateTime<Utc>> {
+    posts.iter().map(|post| post.updated_at).max()
+}
+
+fn render_rss(channel: &FeedChannel, posts: &[SyndicatedPost], frontend_url: &str) -> String {
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+        <rss version=\"2.0\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\"><channel>\
+        <title>{}</title><link>{}</link><description>{}</description>",
+        text(&channel.title),
+        text(&channel.link),
+        text(&channel.description),
+    );
+    if let Some(updated) = last_modified(posts) {
+        xml.push_str(&format!("<lastBuildDate>{}</lastBuildDate>", updated.to_rfc2822()));
+    }
+    for post in posts {
+        let categories: String = post.tags.iter().map(|tag| format!("<category>{}</category>", text(tag))).collect();
+        xml.push_str(&format!(
+            "<item><guid isPermaLink=\"false\">urn:uuid:{id}</guid><title>{title}</title>\
+            <link>{frontend_url}/post/{id}</link><description>{content}</description>\
+            <dc:creator>{author}</dc:creator><dc:rights>{license}</dc:rights><pubDate>{published}</pubDate>{categories}</item>",
+            id = post.id,
+            title = text(&post.title),
+            frontend_url = text(frontend_url),
+            content = text(&post.content),
+            author = text(&post.author_name),
+            license = post.license.get_value(),
+            published = post.created_at.to_rfc2822(),
+            categories = categories,
+        ));
+    }
+    xml.push_str("</channel></rss>");
+    xml
+}
+
+fn render_atom(channel: &FeedChannel, posts: &[SyndicatedPost], frontend_url: &str) -> String {
+    let updated = last_modified(posts).unwrap_or(DateTime::UNIX_EPOCH);
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+        <feed xmlns=\"http://www.w3.org/2005/Atom\"><id>{link}</id><title>{title}</title>\
+        <subtitle>{description}</subtitle><link rel=\"alternate\" href=\"{link}\"/><updated>{updated}</updated>",
+        link = text(&channel.link),
+        title = text(&channel.title),
+        description = text(&channel.description),
+        updated = atom_date(updated),
+    );
+    for post in posts {
+        let categories: String = post.tags.iter().map(|tag| format!("<category term=\"{}\"/>", text(tag))).collect();
+        xml.push_str(&format!(
+            "<entry><id>urn:uuid:{id}</id><title type=\"text\">{title}</title>\
+            <link rel=\"alternate\" href=\"{frontend_url}/post/{id}\"/><content type=\"text\">{content}</content>\
+            <author><name>{author}</name></author><rights>{license}</rights><published>{published}</published><updated>{updated}</updated>{categories}</entry>",
+            id = post.id,
+            title = text(&post.title),
+            frontend_url = text(frontend_url),
+            content = text(&post.content),
+            author = text(&post.author_name),
+            license = post.license.get_value(),
+            published = atom_date(post.created_at),
+            updated = atom_date(post.updated_at),
+            categories = categories,
+        ));
+    }
+    xml.push_str("</feed>");
+    xml
+}
+
+fn atom_date(date: DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Escaped for XML, with the control characters XML 1.0 has no place for dropped.
+fn text(value: &str) -> String {
+    let allowed: String = value.chars().filter(|c| !c.is_control() || matches!(c, '\t' | '\n' | '\r')).collect();
+    html::escape(&allowed)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use uuid::Uuid;
+    use crate::modules::{feed_import::parser, post::model::PostLicense};
+    use super::*;
+
+    fn posts() -> Vec<SyndicatedPost> {
+        vec![SyndicatedPost {
+            id: Uuid::new_v4(),
+            title: "Fish & <chips>".to_string(),
+            content: "Best \"served\" hot\u{7}.".to_string(),
+            tags: vec!["food".to_string(), "uk-eats".to_string()],
+            license: PostLicense::CcBySa,
+            author_name: "Clark Kent".to_string(),
+            created_at: Utc.with_ymd_and_hms(2025, 9, 1, 8, 0, 0).unwrap(),
+            updated_at: Utc.with_ymd_and_hms(2025, 9, 2, 8, 0, 0).unwrap(),
+        }]
+    }
+
+    #[test]
+    fn both_formats_read_back_as_the_posts() {
+        let posts = posts();
+        let channel = FeedChannel {
+            title: "Posts tagged #food".to_string(),
+            description: "The latest posts tagged #food".to_string(),
+            link: "https://example.com/tag/food".to_string(),
+        };
+        for format in [FeedFormat::Rss, FeedFormat::Atom] {
+            let xml = render(format, &channel, &posts, "https://example.com/");
+            let feed = parser::parse(&xml).unwrap_or_else(|e| panic!("{:?}: {}\n{}", format, e, xml));
+            assert_eq!(feed.title.as_deref(), Some("Posts tagged #food"));
+            let entry = &feed.entries[0];
+            assert_eq!(entry.guid, format!("urn:uuid:{}", posts[0].id));
+            assert_eq!(entry.link.as_deref(), Some(format!("https://example.com/post/{}", posts[0].id).as_str()));
+            assert_eq!(entry.title, "Fish & <chips>");
+            assert_eq!(entry.content, "Best \"served\" hot.");
+            assert_eq!(entry.categories, ["food", "uk-eats"]);
+            assert_eq!(entry.published_at, Some(posts[0].created_at));
+            let rights = match format {
+                FeedFormat::Rss => "<dc:rights>cc-by-sa</dc:rights>",
+                FeedFormat::Atom => "<rights>cc-by-sa</rights>",
+            };
+            assert!(xml.contains(rights), "{:?}: {}", format, xml);
+        }
+    }
+
+    #[test]
+    fn an_empty_feed_is_still_a_valid_document() {
+        let channel = FeedChannel { title: "Nobody".to_string(), description: String::new(), link: "https://example.com".to_string() };
+        for format in [FeedFormat::Rss, FeedFormat::Atom] {
+            let feed = parser::parse(&render(format, &channel, &[], "https://example.com")).unwrap();
+            assert!(feed.entries.is_empty());
+        }
+    }
+}
\ No newline at end of file
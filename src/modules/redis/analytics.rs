@@ -0,0 +1,42 @@
+use std::collections::HashMap;
+use redis::{AsyncTypedCommands, ErrorKind, RedisError, RedisResult, cmd};
+use uuid::Uuid;
+use crate::modules::{analytics::model::AnalyticsEvent, redis::redis::RedisClient};
+
+/// Analytics events not yet handed to the sink, a hash of event id to the event as JSON.
+const PENDING_EVENTS_KEY: &str = "analytics:pending";
+
+impl RedisClient {
+    /// Holds `events` until the flush job takes them.
+    pub async fn buffer_analytics_events(&self, events: &[AnalyticsEvent]) -> RedisResult<()> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            let fields = events.iter()
+                .map(|event| Ok((event.id.to_string(), serde_json::to_string(event)?)))
+                .collect::<Result<Vec<_>, serde_json::Error>>()
+                .map_err(|e| RedisError::from((ErrorKind::TypeError, "Serialization Error", e.to_string())))?;
+            cmd("HSET").arg(PENDING_EVENTS_KEY).arg(&fields).exec_async(&mut conn).await
+        }).await
+    }
+    /// Moves the buffered events out of Redis, for the flush job, the same way `take_org_usage`
+    /// does. Events that no longer parse are dropped.
+    pub async fn take_analytics_events(&self) -> RedisResult<Vec<AnalyticsEvent>> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            if !conn.exists(PENDING_EVENTS_KEY).await? {
+                return Ok(Vec::new());
+            }
+            let batch_key = format!("analytics:flushing:{}", Uuid::new_v4());
+            conn.rename(PENDING_EVENTS_KEY, &batch_key).await?;
+            let batch: HashMap<String, String> = conn.hgetall(&batch_key).await?;
+            conn.del(&batch_key).await?;
+            Ok(batch.values()
+                .filter_map(|event| serde_json::from_str(event).ok())
+                .collect())
+        }).await
+    }
+}
\ No newline at end of file
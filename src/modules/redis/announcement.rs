@@ -0,0 +1,55 @@
+use log::warn;
+use redis::{AsyncTypedCommands, ErrorKind, RedisError, RedisResult};
+use crate::modules::{announcement::model::Announcement, redis::redis::RedisClient};
+
+/// The announcements that haven't ended, one entry for every visitor, who is shown what is decided
+/// on each read.
+const ANNOUNCEMENTS_KEY: &str = "announcements:current";
+
+impl RedisClient {
+    pub async fn get_announcements(&self) -> RedisResult<Option<Vec<Announcement>>> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            let value = conn.get(ANNOUNCEMENTS_KEY).await?;
+            match value {
+                None => Ok(None),
+                Some(value) => {
+                    match serde_json::from_str::<Vec<Announcement>>(&value) {
+                        Ok(announcements) => Ok(Some(announcements)),
+                        Err(e) => {
+                            warn!("Invalid announcement cache at key {}: {:?}", ANNOUNCEMENTS_KEY, e);
+                            Ok(None)
+                        }
+                    }
+                }
+            }
+        }).await
+    }
+    pub async fn set_announcements(&self, announcements: &[Announcement], ttl: u64) -> RedisResult<()> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            match serde_json::to_string(announcements) {
+                Ok(value) => {
+                    conn.set_ex(ANNOUNCEMENTS_KEY, value, ttl).await
+                }
+                Err(e) => {
+                    warn!("Failed to serialize announcements for cache {}: {:?}", ANNOUNCEMENTS_KEY, e);
+                    Err(RedisError::from((ErrorKind::TypeError, "Serialization error")))
+                }
+            }
+        }).await
+    }
+    pub async fn delete_announcements(&self) -> RedisResult<()> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            conn.del(ANNOUNCEMENTS_KEY).await?;
+            Ok(())
+        }).await
+    }
+}
\ No newline at end of file
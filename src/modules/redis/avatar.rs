@@ -0,0 +1,25 @@
+use redis::{cmd, AsyncTypedCommands, ErrorKind, RedisError, RedisResult};
+use uuid::Uuid;
+use crate::modules::redis::redis::RedisClient;
+
+impl RedisClient {
+    /// A rendered avatar variant, `version` from `utils::avatar::version`.
+    pub async fn get_avatar(&self, user_id: &Uuid, version: &str) -> RedisResult<Option<Vec<u8>>> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            let cache_key = format!("avatar:{}:{}", user_id, version);
+            cmd("GET").arg(&cache_key).query_async(&mut conn).await
+        }).await
+    }
+    pub async fn set_avatar(&self, user_id: &Uuid, version: &str, image: &[u8], ttl: u64) -> RedisResult<()> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            let cache_key = format!("avatar:{}:{}", user_id, version);
+            conn.set_ex(&cache_key, image, ttl).await
+        }).await
+    }
+}
\ No newline at end of file
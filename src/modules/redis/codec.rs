@@ -0,0 +1,52 @@
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Compressed values start with this prefix. JSON never starts with a NUL byte, so values cached
+/// before compression was enabled (or below the size threshold) are still read as plain JSON.
+const COMPRESSED_PREFIX: &[u8] = b"\0zs";
+const COMPRESSION_LEVEL: i32 = 3;
+/// Guards against a corrupt or hostile entry inflating into something huge.
+const MAX_DECOMPRESSED_SIZE: usize = 16 * 1024 * 1024;
+
+/// Serializes to JSON and zstd-compresses the result once it reaches `min_size` bytes (0 disables compression).
+pub fn encode<T: Serialize>(value: &T, min_size: usize) -> serde_json::Result<Vec<u8>> {
+    let json = serde_json::to_vec(value)?;
+    if min_size == 0 || json.len() < min_size {
+        return Ok(json);
+    }
+    match zstd::bulk::compress(&json, COMPRESSION_LEVEL) {
+        Ok(compressed) => {
+            let mut encoded = COMPRESSED_PREFIX.to_vec();
+            encoded.extend(compressed);
+            Ok(encoded)
+        }
+        Err(_) => Ok(json),
+    }
+}
+
+pub fn decode<T: DeserializeOwned>(value: &[u8]) -> Result<T, String> {
+    match value.strip_prefix(COMPRESSED_PREFIX) {
+        Some(compressed) => {
+            let json = zstd::bulk::decompress(compressed, MAX_DECOMPRESSED_SIZE).map_err(|e| e.to_string())?;
+            serde_json::from_slice(&json).map_err(|e| e.to_string())
+        }
+        None => serde_json::from_slice(value).map_err(|e| e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode, COMPRESSED_PREFIX};
+
+    #[test]
+    fn large_values_are_compressed_and_small_ones_stay_json() {
+        let large = vec!["the same words over and over"; 100];
+        let encoded = encode(&large, 1024).unwrap();
+        assert!(encoded.starts_with(COMPRESSED_PREFIX));
+        assert!(encoded.len() < serde_json::to_vec(&large).unwrap().len() / 10);
+        assert_eq!(decode::<Vec<String>>(&encoded).unwrap(), large);
+
+        let small = encode(&["short"], 1024).unwrap();
+        assert_eq!(small, br#"["short"]"#);
+        assert_eq!(decode::<Vec<String>>(&small).unwrap(), ["short"]);
+    }
+}
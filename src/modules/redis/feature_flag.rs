@@ -0,0 +1,54 @@
+use log::warn;
+use redis::{AsyncTypedCommands, ErrorKind, RedisError, RedisResult};
+use crate::modules::{feature_flag::model::FeatureFlag, redis::redis::RedisClient};
+
+/// The whole flag table is one entry, there are few flags and every check needs most of them.
+const FEATURE_FLAGS_KEY: &str = "feature_flags";
+
+impl RedisClient {
+    pub async fn get_feature_flags(&self) -> RedisResult<Option<Vec<FeatureFlag>>> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            let value = conn.get(FEATURE_FLAGS_KEY).await?;
+            match value {
+                None => Ok(None),
+                Some(value) => {
+                    match serde_json::from_str::<Vec<FeatureFlag>>(&value) {
+                        Ok(flags) => Ok(Some(flags)),
+                        Err(e) => {
+                            warn!("Invalid feature flag cache at key {}: {:?}", FEATURE_FLAGS_KEY, e);
+                            Ok(None)
+                        }
+                    }
+                }
+            }
+        }).await
+    }
+    pub async fn set_feature_flags(&self, flags: &[FeatureFlag], ttl: u64) -> RedisResult<()> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            match serde_json::to_string(flags) {
+                Ok(value) => {
+                    conn.set_ex(FEATURE_FLAGS_KEY, value, ttl).await
+                }
+                Err(e) => {
+                    warn!("Failed to serialize feature flags for cache {}: {:?}", FEATURE_FLAGS_KEY, e);
+                    Err(RedisError::from((ErrorKind::TypeError, "Serialization error")))
+                }
+            }
+        }).await
+    }
+    pub async fn delete_feature_flags(&self) -> RedisResult<()> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            conn.del(FEATURE_FLAGS_KEY).await?;
+            Ok(())
+        }).await
+    }
+}
\ No newline at end of file
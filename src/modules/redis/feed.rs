@@ -0,0 +1,64 @@
+use log::warn;
+use redis::{cmd, AsyncTypedCommands, ErrorKind, RedisError, RedisResult};
+use uuid::Uuid;
+use crate::{
+    dto::PaginatedData,
+    modules::{redis::{codec, redis::RedisClient}, user::dto::UserFeeds},
+};
+
+impl RedisClient {
+    pub async fn get_feed(&self, user_id: &Uuid, variant: &str) -> RedisResult<Option<PaginatedData<UserFeeds>>> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            let cache_key = format!("feed:{}", user_id);
+            let value: Option<Vec<u8>> = cmd("HGET").arg(&cache_key).arg(variant).query_async(&mut conn).await?;
+            match value {
+                None => Ok(None),
+                Some(value) => {
+                    match codec::decode::<PaginatedData<UserFeeds>>(&value) {
+                        Ok(feed) => Ok(Some(feed)),
+                        Err(e) => {
+                            warn!("Invalid feed cache at key {}: {:?}", cache_key, e);
+                            Ok(None)
+                        }
+                    }
+                }
+            }
+        }).await
+    }
+    pub async fn set_feed(&self, user_id: &Uuid, variant: &str, feed: &PaginatedData<UserFeeds>, ttl: u64) -> RedisResult<()> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            let cache_key = format!("feed:{}", user_id);
+            match codec::encode(feed, self.compression_min_size) {
+                Ok(value) => {
+                    conn.hset(&cache_key, variant, value).await?;
+                    conn.expire(&cache_key, ttl as i64).await?;
+                    Ok(())
+                }
+                Err(e) => {
+                    warn!("Failed to serialize feed for cache {}: {:?}", cache_key, e);
+                    Err(RedisError::from((ErrorKind::TypeError, "Serialization error")))
+                }
+            }
+        }).await
+    }
+    /// Drops every cached feed page (all limit/order variants) of the given users.
+    pub async fn delete_feeds(&self, user_ids: &[Uuid]) -> RedisResult<()> {
+        self.timed(async {
+            if user_ids.is_empty() {
+                return Ok(());
+            }
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            let cache_keys: Vec<String> = user_ids.iter().map(|user_id| format!("feed:{}", user_id)).collect();
+            conn.del(cache_keys).await?;
+            Ok(())
+        }).await
+    }
+}
\ No newline at end of file
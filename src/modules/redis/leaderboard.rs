@@ -0,0 +1,44 @@
+use log::warn;
+use redis::{AsyncTypedCommands, ErrorKind, RedisError, RedisResult};
+use crate::modules::{redis::redis::RedisClient, aggregate::model::Leaderboard};
+
+impl RedisClient {
+    pub async fn get_leaderboard(&self, limit: i64) -> RedisResult<Option<Leaderboard>> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            let cache_key = format!("leaderboard:{}", limit);
+            let value = conn.get(&cache_key).await?;
+            match value {
+                None => Ok(None),
+                Some(value) => {
+                    match serde_json::from_str::<Leaderboard>(&value) {
+                        Ok(leaderboard) => Ok(Some(leaderboard)),
+                        Err(e) => {
+                            warn!("Invalid leaderboard cache at key {}: {:?}", cache_key, e);
+                            Ok(None)
+                        }
+                    }
+                }
+            }
+        }).await
+    }
+    pub async fn set_leaderboard(&self, limit: i64, leaderboard: &Leaderboard, ttl: u64) -> RedisResult<()> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            let cache_key = format!("leaderboard:{}", limit);
+            match serde_json::to_string(leaderboard) {
+                Ok(value) => {
+                    conn.set_ex(&cache_key, value, ttl).await
+                }
+                Err(e) => {
+                    warn!("Failed to serialize leaderboard for cache {}: {:?}", cache_key, e);
+                    Err(RedisError::from((ErrorKind::TypeError, "Serialization error")))
+                }
+            }
+        }).await
+    }
+}
\ No newline at end of file
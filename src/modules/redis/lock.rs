@@ -0,0 +1,43 @@
+use std::{future::Future, time::Duration};
+use log::warn;
+use redis::{AsyncTypedCommands, ErrorKind, ExistenceCheck, RedisError, RedisResult, Script, SetExpiry, SetOptions};
+use uuid::Uuid;
+use crate::modules::redis::redis::RedisClient;
+
+/// Deletes `KEYS[1]` only while it still holds the token `ARGV[1]`, so a holder whose lock expired
+/// and went to someone else can't release the new holder's lock.
+pub const RELEASE_LOCK_SCRIPT: &str = r#"if redis.call("GET", KEYS[1]) == ARGV[1] then return redis.call("DEL", KEYS[1]) else return 0 end"#;
+
+impl RedisClient {
+    /// Runs `task` while holding the lock `lock:{key}`, so replicas running the same job don't do
+    /// the same work at once. Answers `None` without running `task` when another holder has the
+    /// lock. The lock expires after `ttl` even if its holder dies, and a `task` running longer than
+    /// `ttl` loses it. An error means the lock couldn't be taken and `task` didn't run.
+    pub async fn with_lock<T>(&self, key: &str, ttl: Duration, task: impl Future<Output = T>) -> RedisResult<Option<T>> {
+        let lock_key = format!("lock:{}", key);
+        let token = Uuid::new_v4().to_string();
+        let acquired = self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            let options = SetOptions::default()
+                .conditional_set(ExistenceCheck::NX)
+                .with_expiration(SetExpiry::PX(ttl.as_millis().max(1) as u64));
+            Ok(conn.set_options(&lock_key, &token, options).await?.is_some())
+        }).await?;
+        if !acquired {
+            return Ok(None);
+        }
+        let output = task.await;
+        let released = self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            Script::new(RELEASE_LOCK_SCRIPT).key(&lock_key).arg(&token).invoke_async::<i64>(&mut conn).await
+        }).await;
+        if let Err(e) = released {
+            warn!("Failed to release the lock {}, it expires in {}ms: {}", lock_key, ttl.as_millis(), e);
+        }
+        Ok(Some(output))
+    }
+}
\ No newline at end of file
@@ -1,2 +1,18 @@
+#[allow(clippy::module_inception)]
 pub mod redis;
-pub mod user;
\ No newline at end of file
+pub mod user;
+pub mod leaderboard;
+pub mod feed;
+pub mod post;
+pub mod permission;
+pub mod webhook;
+pub mod rate_limit;
+pub mod codec;
+pub mod feature_flag;
+pub mod avatar;
+pub mod post_view;
+pub mod lock;
+pub mod org_usage;
+pub mod analytics;
+pub mod announcement;
+pub mod tos;
\ No newline at end of file
@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+use chrono::NaiveDate;
+use redis::{AsyncTypedCommands, ErrorKind, ExistenceCheck, RedisError, RedisResult, SetExpiry, SetOptions};
+use uuid::Uuid;
+use crate::modules::redis::redis::RedisClient;
+
+/// Requests per organization and month not yet written to the database, a hash of
+/// `{organization_id}:{month}` to count.
+const PENDING_USAGE_KEY: &str = "org_usage:pending";
+/// Outlives the month it counts, so a request on its last day still finds the counter.
+const USAGE_TTL_SECS: u64 = 40 * 24 * 60 * 60;
+
+impl RedisClient {
+    /// Counts a request of `organization_id` in `month` unless `limit` requests were counted already.
+    /// The counter starts from `persisted`, the count the database holds, when Redis has none, so a
+    /// flushed or restarted Redis doesn't hand out the month again. Returns whether the request was
+    /// counted and the count after it.
+    pub async fn hit_org_usage(&self, organization_id: &Uuid, month: NaiveDate, persisted: i64, limit: Option<i64>) -> RedisResult<(bool, i64)> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            let key = format!("org_usage:{}:{}", organization_id, month.format("%Y-%m"));
+            let options = SetOptions::default()
+                .conditional_set(ExistenceCheck::NX)
+                .with_expiration(SetExpiry::EX(USAGE_TTL_SECS));
+            conn.set_options(&key, persisted, options).await?;
+            let count: i64 = conn.get(&key).await?.and_then(|count| count.parse().ok()).unwrap_or(persisted);
+            if limit.is_some_and(|limit| count >= limit) {
+                return Ok((false, count));
+            }
+            let count = conn.incr(&key, 1).await? as i64;
+            conn.hincr(PENDING_USAGE_KEY, format!("{}:{}", organization_id, month), 1).await?;
+            Ok((true, count))
+        }).await
+    }
+    /// The requests of `organization_id` counted in `month`, `None` when Redis has no counter.
+    pub async fn get_org_usage(&self, organization_id: &Uuid, month: NaiveDate) -> RedisResult<Option<i64>> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            let key = format!("org_usage:{}:{}", organization_id, month.format("%Y-%m"));
+            Ok(conn.get(&key).await?.and_then(|count| count.parse().ok()))
+        }).await
+    }
+    /// Moves the pending request counts out of Redis, for the flush job, the same way
+    /// `take_post_views` does.
+    pub async fn take_org_usage(&self) -> RedisResult<Vec<(Uuid, NaiveDate, i64)>> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            if !conn.exists(PENDING_USAGE_KEY).await? {
+                return Ok(Vec::new());
+            }
+            let batch_key = format!("org_usage:flushing:{}", Uuid::new_v4());
+            conn.rename(PENDING_USAGE_KEY, &batch_key).await?;
+            let batch: HashMap<String, String> = conn.hgetall(&batch_key).await?;
+            conn.del(&batch_key).await?;
+            Ok(batch.into_iter()
+                .filter_map(|(field, requests)| {
+                    let (organization_id, month) = field.split_once(':')?;
+                    Some((organization_id.parse().ok()?, month.parse().ok()?, requests.parse().ok()?))
+                })
+                .collect())
+        }).await
+    }
+    /// Puts back counts `take_org_usage` returned but the database didn't take.
+    pub async fn restore_org_usage(&self, usage: &[(Uuid, NaiveDate, i64)]) -> RedisResult<()> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            for (organization_id, month, requests) in usage {
+                conn.hincr(PENDING_USAGE_KEY, format!("{}:{}", organization_id, month), *requests).await?;
+            }
+            Ok(())
+        }).await
+    }
+}
\ No newline at end of file
@@ -0,0 +1,67 @@
+use log::warn;
+use redis::{AsyncTypedCommands, ErrorKind, ExistenceCheck, RedisError, RedisResult, SetExpiry, SetOptions};
+use uuid::Uuid;
+use crate::modules::redis::redis::RedisClient;
+
+pub struct CachedPermissions {
+    pub permissions: Vec<String>,
+    pub ttl: i64,
+}
+
+impl RedisClient {
+    pub async fn get_permissions(&self, role_id: &Uuid) -> RedisResult<Option<CachedPermissions>> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            let cache_key = format!("permissions:{}", role_id);
+            let value = conn.get(&cache_key).await?;
+            match value {
+                None => Ok(None),
+                Some(value) => {
+                    match serde_json::from_str::<Vec<String>>(&value) {
+                        Ok(permissions) => {
+                            let ttl = conn.ttl(&cache_key).await?.raw() as i64;
+                            Ok(Some(CachedPermissions { permissions, ttl }))
+                        }
+                        Err(e) => {
+                            warn!("Invalid permission cache at key {}: {:?}", cache_key, e);
+                            Ok(None)
+                        }
+                    }
+                }
+            }
+        }).await
+    }
+    pub async fn set_permissions(&self, role_id: &Uuid, permissions: &[String], ttl: u64) -> RedisResult<()> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            let cache_key = format!("permissions:{}", role_id);
+            match serde_json::to_string(permissions) {
+                Ok(value) => {
+                    conn.set_ex(&cache_key, value, ttl).await
+                }
+                Err(e) => {
+                    warn!("Failed to serialize permissions for cache {}: {:?}", cache_key, e);
+                    Err(RedisError::from((ErrorKind::TypeError, "Serialization error")))
+                }
+            }
+        }).await
+    }
+    /// Only the caller that gets the lock refreshes a role, so a busy role triggers one reload instead of one per request.
+    pub async fn lock_permission_refresh(&self, role_id: &Uuid, ttl: u64) -> RedisResult<bool> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            let lock_key = format!("permissions:{}:refresh", role_id);
+            let options = SetOptions::default()
+                .conditional_set(ExistenceCheck::NX)
+                .with_expiration(SetExpiry::EX(ttl));
+            let acquired = conn.set_options(&lock_key, 1, options).await?;
+            Ok(acquired.is_some())
+        }).await
+    }
+}
\ No newline at end of file
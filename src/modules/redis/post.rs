@@ -0,0 +1,54 @@
+use log::warn;
+use redis::{cmd, AsyncTypedCommands, ErrorKind, RedisError, RedisResult};
+use uuid::Uuid;
+use crate::modules::{post::model::PostDetail, redis::{codec, redis::RedisClient}};
+
+impl RedisClient {
+    pub async fn get_post_detail(&self, post_id: &Uuid) -> RedisResult<Option<PostDetail>> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            let cache_key = format!("post:{}", post_id);
+            let value: Option<Vec<u8>> = cmd("GET").arg(&cache_key).query_async(&mut conn).await?;
+            match value {
+                None => Ok(None),
+                Some(value) => {
+                    match codec::decode::<PostDetail>(&value) {
+                        Ok(post_detail) => Ok(Some(post_detail)),
+                        Err(e) => {
+                            warn!("Invalid post cache at key {}: {:?}", cache_key, e);
+                            Ok(None)
+                        }
+                    }
+                }
+            }
+        }).await
+    }
+    pub async fn set_post_detail(&self, post_detail: &PostDetail, ttl: u64) -> RedisResult<()> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            let cache_key = format!("post:{}", post_detail.id);
+            match codec::encode(post_detail, self.compression_min_size) {
+                Ok(value) => {
+                    conn.set_ex(&cache_key, value, ttl).await
+                }
+                Err(e) => {
+                    warn!("Failed to serialize post for cache {}: {:?}", cache_key, e);
+                    Err(RedisError::from((ErrorKind::TypeError, "Serialization error")))
+                }
+            }
+        }).await
+    }
+    pub async fn delete_post_detail(&self, post_id: &Uuid) -> RedisResult<()> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            conn.del(format!("post:{}", post_id)).await?;
+            Ok(())
+        }).await
+    }
+}
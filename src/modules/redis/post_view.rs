@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+use redis::{AsyncTypedCommands, ErrorKind, ExistenceCheck, RedisError, RedisResult, SetExpiry, SetOptions};
+use uuid::Uuid;
+use crate::modules::redis::redis::RedisClient;
+
+/// Views per post not yet written to the database, a hash of post id to count.
+const PENDING_VIEWS_KEY: &str = "post_views:pending";
+
+impl RedisClient {
+    /// Counts a view of `post_id` by `viewer` unless the same viewer was counted in the last
+    /// `dedupe_secs`. Returns whether it was counted.
+    pub async fn record_post_view(&self, post_id: &Uuid, viewer: &str, dedupe_secs: u64) -> RedisResult<bool> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            let seen_key = format!("post_view:{}:{}", post_id, viewer);
+            let options = SetOptions::default()
+                .conditional_set(ExistenceCheck::NX)
+                .with_expiration(SetExpiry::EX(dedupe_secs));
+            if conn.set_options(&seen_key, 1, options).await?.is_none() {
+                return Ok(false);
+            }
+            conn.hincr(PENDING_VIEWS_KEY, post_id.to_string(), 1).await?;
+            Ok(true)
+        }).await
+    }
+    /// Moves the pending views out of Redis, for the flush job. The batch is renamed away first, so
+    /// views counted meanwhile and other instances flushing at the same time start a new one.
+    pub async fn take_post_views(&self) -> RedisResult<Vec<(Uuid, i64)>> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            if !conn.exists(PENDING_VIEWS_KEY).await? {
+                return Ok(Vec::new());
+            }
+            let batch_key = format!("post_views:flushing:{}", Uuid::new_v4());
+            conn.rename(PENDING_VIEWS_KEY, &batch_key).await?;
+            let batch: HashMap<String, String> = conn.hgetall(&batch_key).await?;
+            conn.del(&batch_key).await?;
+            Ok(batch.into_iter()
+                .filter_map(|(post_id, views)| Some((post_id.parse().ok()?, views.parse().ok()?)))
+                .collect())
+        }).await
+    }
+    /// Puts back views `take_post_views` returned but the database didn't take.
+    pub async fn restore_post_views(&self, views: &[(Uuid, i64)]) -> RedisResult<()> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            for (post_id, count) in views {
+                conn.hincr(PENDING_VIEWS_KEY, post_id.to_string(), *count).await?;
+            }
+            Ok(())
+        }).await
+    }
+}
\ No newline at end of file
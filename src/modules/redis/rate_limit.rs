@@ -0,0 +1,88 @@
+use chrono::Utc;
+use redis::{AsyncTypedCommands, ErrorKind, ExistenceCheck, IntegerReplyOrNoOp, RedisError, RedisResult, SetExpiry, SetOptions};
+use crate::modules::redis::redis::RedisClient;
+
+impl RedisClient {
+    /// Counts one hit in the current window and returns the total so far.
+    pub async fn hit_rate_limit(&self, key: &str, window_secs: i64) -> RedisResult<u32> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            let count = conn.incr(key, 1).await? as u32;
+            if count == 1 {
+                conn.expire(key, window_secs).await?;
+            }
+            Ok(count)
+        }).await
+    }    /// Seconds until the window of `key` ends, `None` when it has already ended.
+    pub async fn rate_limit_ttl(&self, key: &str) -> RedisResult<Option<u64>> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            match conn.ttl(key).await? {
+                IntegerReplyOrNoOp::IntegerReply(ttl) if ttl > 0 => Ok(Some(ttl as u64)),
+                _ => Ok(None),
+            }
+        }).await
+    }
+    /// Failed sign-ins under `key` in a sliding window: the current fixed window plus the previous
+    /// one, weighted by how much of it the sliding window still covers.
+    pub async fn sign_in_failures(&self, key: &str, window_secs: i64) -> RedisResult<f64> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            let now = Utc::now().timestamp();
+            let window = now / window_secs;
+            let current = conn.get(format!("{}:{}", key, window)).await?.and_then(|count| count.parse::<f64>().ok()).unwrap_or(0.0);
+            let previous = conn.get(format!("{}:{}", key, window - 1)).await?.and_then(|count| count.parse::<f64>().ok()).unwrap_or(0.0);
+            let overlap = 1.0 - (now % window_secs) as f64 / window_secs as f64;
+            Ok(current + previous * overlap)
+        }).await
+    }
+    pub async fn record_sign_in_failure(&self, key: &str, window_secs: i64) -> RedisResult<()> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            let window_key = format!("{}:{}", key, Utc::now().timestamp() / window_secs);
+            // Kept through the next window, which still counts part of it.
+            if conn.incr(&window_key, 1).await? == 1 {
+                conn.expire(&window_key, window_secs * 2).await?;
+            }
+            Ok(())
+        }).await
+    }
+    /// Claims the one attempt allowed under `key` in the next `delay_secs`. When an earlier attempt
+    /// holds it, the error is the seconds until the next claim can succeed.
+    pub async fn claim_sign_in_attempt(&self, key: &str, delay_secs: u64) -> RedisResult<Result<(), u64>> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            let delay_key = format!("{}:delay", key);
+            let options = SetOptions::default()
+                .conditional_set(ExistenceCheck::NX)
+                .with_expiration(SetExpiry::EX(delay_secs));
+            if conn.set_options(&delay_key, 1, options).await?.is_some() {
+                return Ok(Ok(()));
+            }
+            match conn.ttl(&delay_key).await? {
+                IntegerReplyOrNoOp::IntegerReply(ttl) if ttl > 0 => Ok(Err(ttl as u64)),
+                _ => Ok(Err(1)),
+            }
+        }).await
+    }
+    pub async fn clear_sign_in_failures(&self, key: &str, window_secs: i64) -> RedisResult<()> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            let window = Utc::now().timestamp() / window_secs;
+            conn.del(&[format!("{}:{}", key, window), format!("{}:{}", key, window - 1), format!("{}:delay", key)]).await?;
+            Ok(())
+        }).await
+    }
+}
\ No newline at end of file
@@ -1,11 +1,86 @@
 use deadpool_redis::{Pool, Config as RedisConfig, Runtime, PoolError, CreatePoolError};
-use redis::{RedisError};
+use log::{info, warn};
+use redis::{ErrorKind, RedisError, RedisResult};
 use thiserror::Error;
-use std::io::Error as IoError;
+use tokio::time::timeout;
+use std::{
+    future::Future,
+    io::Error as IoError,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use crate::utils::metrics;
 
 #[derive(Clone)]
 pub struct RedisClient {
     pub pool: Pool,
+    pub command_timeout: Duration,
+    pub compression_min_size: usize,
+    breaker: Option<Arc<CircuitBreaker>>,
+}
+
+/// Stops sending commands after `threshold` connection errors or timeouts in a row, so during an
+/// outage every cache lookup fails at once instead of waiting on a connect or a timeout. Once the
+/// cooldown is over a single command goes through as a probe: an answer closes the breaker, another
+/// failure keeps it open for one more cooldown. Error replies from Redis don't count, it is up.
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self { threshold, cooldown, state: Mutex::new(BreakerState::default()) }
+    }
+    pub fn is_open(&self) -> bool {
+        self.state.lock().unwrap().open_until.is_some()
+    }
+    /// Whether a command may be sent now. Letting the probe through pushes `open_until` back, so
+    /// the other requests keep short-circuiting while it runs.
+    fn allow(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        match state.open_until {
+            Some(until) if Instant::now() < until => false,
+            Some(_) => {
+                state.open_until = Some(Instant::now() + self.cooldown);
+                true
+            }
+            None => true,
+        }
+    }
+    fn record<T>(&self, result: &RedisResult<T>) {
+        let mut state = self.state.lock().unwrap();
+        match result {
+            Err(e) if e.is_io_error() || e.is_connection_dropped() => {
+                state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+                if state.consecutive_failures < self.threshold {
+                    return;
+                }
+                if state.open_until.is_none() {
+                    warn!(
+                        "Redis circuit breaker opened after {} failures, skipping Redis for {}ms",
+                        state.consecutive_failures, self.cooldown.as_millis()
+                    );
+                    metrics::increment_counter("redis_breaker_transitions_total", &[("state", "open")]);
+                }
+                state.open_until = Some(Instant::now() + self.cooldown);
+            }
+            _ => {
+                state.consecutive_failures = 0;
+                if state.open_until.take().is_some() {
+                    info!("Redis circuit breaker closed, Redis is answering again");
+                    metrics::increment_counter("redis_breaker_transitions_total", &[("state", "closed")]);
+                }
+            }
+        }
+    }
 }
 #[derive(Debug, Error)]
 pub enum CustomRedisError {
@@ -26,12 +101,41 @@ pub enum CustomRedisError {
 }
 
 impl RedisClient {
-    pub async fn new(redis_url: &str) -> Result<Self, CustomRedisError> {
+    pub async fn new(redis_url: &str, command_timeout: Duration, compression_min_size: usize) -> Result<Self, CustomRedisError> {
         let config = RedisConfig::from_url(redis_url);
         let pool = config
             .create_pool(Some(Runtime::Tokio1))
-            .map_err(|e| CustomRedisError::CreatePoolError(e))?;
-        Ok(Self { pool })
+            .map_err(CustomRedisError::CreatePoolError)?;
+        Ok(Self { pool, command_timeout, compression_min_size, breaker: None })
+    }
+    /// Puts a `CircuitBreaker` in front of every command, a `threshold` of 0 leaves it out.
+    pub fn with_circuit_breaker(mut self, threshold: u32, cooldown: Duration) -> Self {
+        self.breaker = (threshold > 0).then(|| Arc::new(CircuitBreaker::new(threshold, cooldown)));
+        self
+    }
+    pub fn circuit_breaker(&self) -> Option<&CircuitBreaker> {
+        self.breaker.as_deref()
+    }
+    /// Bounds one cache command, pool checkout included, by `REDIS_COMMAND_TIMEOUT_MS`. A timeout, like
+    /// a command refused by an open circuit breaker, is an ordinary `RedisError`, so callers take the
+    /// same fallback they take when Redis is down.
+    pub async fn timed<T>(&self, command: impl Future<Output = RedisResult<T>>) -> RedisResult<T> {
+        if self.breaker.as_ref().is_some_and(|breaker| !breaker.allow()) {
+            metrics::increment_counter("redis_breaker_short_circuits_total", &[]);
+            return Err(RedisError::from((ErrorKind::IoError, "Redis circuit breaker is open")));
+        }
+        let result = match timeout(self.command_timeout, command).await {
+            Ok(result) => result,
+            Err(_) => {
+                warn!("Redis command timed out after {}ms", self.command_timeout.as_millis());
+                metrics::increment_counter("redis_command_timeouts_total", &[]);
+                Err(RedisError::from((ErrorKind::IoError, "Redis command timed out")))
+            }
+        };
+        if let Some(breaker) = &self.breaker {
+            breaker.record(&result);
+        }
+        result
     }
     pub async fn get_conn(&self) -> Result<deadpool_redis::Connection, CustomRedisError> {
         self.pool.get().await.map_err(|e| {
@@ -0,0 +1,77 @@
+use log::warn;
+use redis::{AsyncTypedCommands, ErrorKind, RedisError, RedisResult};
+use uuid::Uuid;
+use crate::modules::{redis::redis::RedisClient, tos::model::TosVersion};
+
+/// The current version, `null` while none is published so that isn't looked up on every request
+/// either.
+const CURRENT_TOS_KEY: &str = "tos:current";
+/// A user's acceptance is only looked up in the database once a day while it holds.
+const TOS_ACCEPTED_TTL_SECS: u64 = 24 * 60 * 60;
+
+impl RedisClient {
+    /// `None` when nothing is cached, `Some(None)` when no version is published.
+    pub async fn get_current_tos(&self) -> RedisResult<Option<Option<TosVersion>>> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            let value = conn.get(CURRENT_TOS_KEY).await?;
+            match value {
+                None => Ok(None),
+                Some(value) => {
+                    match serde_json::from_str::<Option<TosVersion>>(&value) {
+                        Ok(version) => Ok(Some(version)),
+                        Err(e) => {
+                            warn!("Invalid terms of service cache at key {}: {:?}", CURRENT_TOS_KEY, e);
+                            Ok(None)
+                        }
+                    }
+                }
+            }
+        }).await
+    }
+    pub async fn set_current_tos(&self, version: Option<&TosVersion>, ttl: u64) -> RedisResult<()> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            match serde_json::to_string(&version) {
+                Ok(value) => {
+                    conn.set_ex(CURRENT_TOS_KEY, value, ttl).await
+                }
+                Err(e) => {
+                    warn!("Failed to serialize the terms of service for cache {}: {:?}", CURRENT_TOS_KEY, e);
+                    Err(RedisError::from((ErrorKind::TypeError, "Serialization error")))
+                }
+            }
+        }).await
+    }
+    pub async fn delete_current_tos(&self) -> RedisResult<()> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            conn.del(CURRENT_TOS_KEY).await?;
+            Ok(())
+        }).await
+    }
+    /// The version `user_id` was last seen to have accepted.
+    pub async fn get_tos_accepted(&self, user_id: &Uuid) -> RedisResult<Option<Uuid>> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            let value = conn.get(format!("tos:accepted:{}", user_id)).await?;
+            Ok(value.and_then(|value| Uuid::parse_str(&value).ok()))
+        }).await
+    }
+    pub async fn set_tos_accepted(&self, user_id: &Uuid, tos_version_id: &Uuid) -> RedisResult<()> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            conn.set_ex(format!("tos:accepted:{}", user_id), tos_version_id.to_string(), TOS_ACCEPTED_TTL_SECS).await
+        }).await
+    }
+}
\ No newline at end of file
@@ -0,0 +1,19 @@
+use redis::{AsyncTypedCommands, ErrorKind, ExistenceCheck, RedisError, RedisResult, SetExpiry, SetOptions};
+use crate::modules::redis::redis::RedisClient;
+
+impl RedisClient {
+    /// Returns false when the nonce was already claimed, i.e. the delivery is a replay.
+    pub async fn claim_webhook_nonce(&self, provider: &str, nonce: &str, ttl: u64) -> RedisResult<bool> {
+        self.timed(async {
+            let mut conn = self.pool.get().await.map_err(|e| {
+                RedisError::from((ErrorKind::IoError, "Pool Error", format!("{:?}", e)))
+            })?;
+            let nonce_key = format!("webhook:{}:{}", provider, nonce);
+            let options = SetOptions::default()
+                .conditional_set(ExistenceCheck::NX)
+                .with_expiration(SetExpiry::EX(ttl));
+            let claimed = conn.set_options(&nonce_key, 1, options).await?;
+            Ok(claimed.is_some())
+        }).await
+    }
+}
\ No newline at end of file
@@ -1,11 +1,11 @@
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use serde::Serialize;
-use sqlx::{query, query_as, Error as SqlxError, FromRow};
+use sqlx::{query, query_as, query_scalar, FromRow};
 use uuid::Uuid;
-use crate::db::DBClient;
+use crate::{db::DBClient, error::RepositoryError};
 
-#[derive(Serialize, FromRow)]
+#[derive(Serialize, FromRow, Clone)]
 pub struct RefreshToken {
     pub user_id: Uuid,
     pub token: String,
@@ -15,15 +15,18 @@ pub struct RefreshToken {
     pub updated_at: DateTime<Utc>,
 }
 #[async_trait]
-pub trait RefreshTokenRepository {
-    async fn refresh_token(&self, user_id: Uuid, token: &str, expires_at: DateTime<Utc>) -> Result<(), SqlxError>;
-    async fn revoke_token(&self, user_id: Uuid) -> Result<(), SqlxError>;
-    async fn get_refresh_token(&self, token: &str) -> Result<Option<RefreshToken>, SqlxError>;
+pub trait RefreshTokenRepository: Send + Sync {
+    async fn refresh_token(&self, user_id: Uuid, token: &str, expires_at: DateTime<Utc>) -> Result<(), RepositoryError>;
+    async fn revoke_token(&self, user_id: Uuid) -> Result<(), RepositoryError>;
+    async fn get_refresh_token(&self, token: &str) -> Result<Option<RefreshToken>, RepositoryError>;
+    /// When the user's refresh token expires, `None` once it has expired or was revoked.
+    async fn get_session_expiry(&self, user_id: Uuid) -> Result<Option<DateTime<Utc>>, RepositoryError>;
 }
 
 #[async_trait]
 impl RefreshTokenRepository for DBClient {
-    async fn refresh_token(&self, user_id: Uuid, token: &str, expires_at: DateTime<Utc>) -> Result<(), SqlxError> {
+    async fn refresh_token(&self, user_id: Uuid, token: &str, expires_at: DateTime<Utc>) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("refresh_token.refresh_token");
         query!(
             r#"
                 INSERT INTO refresh_tokens (user_id, token, expires_at)
@@ -37,7 +40,8 @@ impl RefreshTokenRepository for DBClient {
         ).execute(&self.pool).await?;
         Ok(())
     }
-    async fn revoke_token(&self, user_id: Uuid) -> Result<(), SqlxError> {
+    async fn revoke_token(&self, user_id: Uuid) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("refresh_token.revoke_token");
         query!(
             r#"
                 UPDATE refresh_tokens SET revoked = true, updated_at = NOW()
@@ -47,7 +51,8 @@ impl RefreshTokenRepository for DBClient {
         ).execute(&self.pool).await?;
         Ok(())
     }
-    async fn get_refresh_token(&self, token: &str) -> Result<Option<RefreshToken>, SqlxError> {
+    async fn get_refresh_token(&self, token: &str) -> Result<Option<RefreshToken>, RepositoryError> {
+        let _timer = self.time_query("refresh_token.get_refresh_token");
         let data = query_as!(
             RefreshToken,
             r#"
@@ -58,4 +63,15 @@ impl RefreshTokenRepository for DBClient {
         ).fetch_optional(&self.pool).await?;
         Ok(data)
     }
+    async fn get_session_expiry(&self, user_id: Uuid) -> Result<Option<DateTime<Utc>>, RepositoryError> {
+        let _timer = self.time_query("refresh_token.get_session_expiry");
+        let expires_at = query_scalar!(
+            r#"
+                SELECT expires_at FROM refresh_tokens
+                WHERE user_id = $1 AND NOT revoked AND expires_at > Now();
+            "#,
+            user_id
+        ).fetch_optional(&self.pool).await?;
+        Ok(expires_at)
+    }
 }
\ No newline at end of file
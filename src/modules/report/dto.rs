@@ -0,0 +1,71 @@
+use serde::Deserialize;
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
+use crate::{
+    config::Config,
+    dto::{default_order_by, Paginated, Pagination},
+    modules::{
+        report::model::{ReportReason, ReportStatus},
+        user::dto::validate_order_by,
+    },
+};
+
+#[derive(Deserialize, Validate)]
+pub struct ReportRequest {
+    pub reason: ReportReason,
+    #[validate(length(
+        min = 1,
+        max = 500,
+        message = "Details must be between 1 and 500 characters"
+    ))]
+    pub details: Option<String>,
+}
+
+fn validate_report_action(body: &ReportActionRequest) -> Result<(), ValidationError> {
+    if !body.hide_content && !body.ban_author {
+        let mut error = ValidationError::new("empty_action");
+        error.message = Some("At least one of hide_content or ban_author must be true".into());
+        return Err(error);
+    }
+    Ok(())
+}
+
+#[derive(Deserialize, Validate)]
+#[validate(schema(function = "validate_report_action", skip_on_field_errors = false))]
+pub struct ReportActionRequest {
+    #[serde(default)]
+    pub hide_content: bool,
+    #[serde(default)]
+    pub ban_author: bool,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct ReportListParams {
+    pub limit: Option<usize>,
+    #[validate(range(min = 1, max = 100000, message = "Page must be between 1 and 100000."))]
+    pub page: Option<usize>,
+    #[serde(default = "default_order_by")]
+    #[validate(custom(function = "validate_order_by"))]
+    pub order_by: Option<String>,
+    pub status: Option<ReportStatus>,
+    pub reason: Option<ReportReason>,
+    #[serde(skip)]
+    pub pagination: Pagination,
+}
+impl Paginated for ReportListParams {
+    fn paginate(&mut self, config: &Config) {
+        self.pagination = Pagination::sanitize(self.page, self.limit, config);
+    }
+}
+
+pub enum ReportTarget {
+    Post(Uuid),
+    Comment(Uuid),
+}
+
+pub struct NewReport {
+    pub reporter_id: Option<Uuid>,
+    pub target: ReportTarget,
+    pub reason: ReportReason,
+    pub details: Option<String>,
+}
\ No newline at end of file
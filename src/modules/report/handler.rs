@@ -0,0 +1,122 @@
+use std::sync::Arc;
+use axum::{middleware, Router, routing::{get, post}, Extension, response::IntoResponse, http::StatusCode};
+use serde_json::json;
+use uuid::Uuid;
+use crate::{
+    AppState,
+    dto::{HttpResult, SuccessResponse},
+    error::{ValidatedJson, PathParser, PaginatedQuery},
+    middleware::{AuthenticatedUser, permission::{check_permission, Permission}},
+    modules::{
+        report::{
+            dto::{NewReport, ReportActionRequest, ReportListParams, ReportRequest, ReportTarget},
+        },
+        audit_log::model::{AuditAction, NewAuditLog},
+    },
+};
+
+pub fn report_admin_router() -> Router {
+    Router::new()
+        .route("/reports", get(report_list).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminReportList.to_string())
+        })))
+        .route("/reports/{id}/dismiss", post(report_dismiss).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminReportResolve.to_string())
+        })))
+        .route("/reports/{id}/action", post(report_action).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminReportResolve.to_string())
+        })))
+}
+
+async fn create_report(
+    app_state: Arc<AppState>,
+    reporter_id: Uuid,
+    target: ReportTarget,
+    body: ReportRequest,
+) -> HttpResult<impl IntoResponse> {
+    let new_report = NewReport {
+        reporter_id: Some(reporter_id),
+        target,
+        reason: body.reason,
+        details: body.details,
+    };
+    let report = app_state.db.reports.save_report(new_report).await?;
+    Ok((
+        StatusCode::CREATED,
+        SuccessResponse::new("Thank you, the report has been submitted for review.", Some(report))
+    ))
+}
+pub async fn report_post(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(post_id): PathParser<Uuid>,
+    ValidatedJson(body): ValidatedJson<ReportRequest>,
+) -> HttpResult<impl IntoResponse> {
+    create_report(app_state, user_auth.user.id, ReportTarget::Post(post_id), body).await
+}
+pub async fn report_comment(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(comment_id): PathParser<Uuid>,
+    ValidatedJson(body): ValidatedJson<ReportRequest>,
+) -> HttpResult<impl IntoResponse> {
+    create_report(app_state, user_auth.user.id, ReportTarget::Comment(comment_id), body).await
+}
+async fn report_list(
+    Extension(app_state): Extension<Arc<AppState>>,
+    PaginatedQuery(query_params): PaginatedQuery<ReportListParams>,
+) -> HttpResult<impl IntoResponse> {
+    let result = app_state.db.reports.get_reports(query_params).await?;
+    Ok(
+        SuccessResponse::new("Getting report list data", Some(result))
+    )
+}
+async fn report_dismiss(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(report_id): PathParser<Uuid>,
+) -> HttpResult<impl IntoResponse> {
+    let resolution = app_state.db.reports.dismiss_report(report_id, user_auth.user.id).await?;
+    let _ = app_state.db.audit_logs.save_audit_log(
+        NewAuditLog::new(Some(user_auth.user.id), resolution.author_id, AuditAction::ReportDismissed)
+            .with_metadata(json!({ "report_id": report_id }))
+    ).await;
+    Ok(
+        SuccessResponse::new("Successfully dismissed the report.", Some(resolution.report))
+    )
+}
+async fn report_action(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(report_id): PathParser<Uuid>,
+    ValidatedJson(body): ValidatedJson<ReportActionRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let resolution = app_state.db.reports.action_report(
+        report_id, user_auth.user.id, body.hide_content, body.ban_author
+    ).await?;
+    let metadata = json!({
+        "report_id": report_id,
+        "post_id": resolution.report.post_id,
+        "comment_id": resolution.report.comment_id,
+    });
+    if body.hide_content {
+        if let Some(post_id) = resolution.report.post_id {
+            let _ = app_state.redis_client.delete_post_detail(&post_id).await;
+        }
+        let _ = app_state.db.audit_logs.save_audit_log(
+            NewAuditLog::new(Some(user_auth.user.id), resolution.author_id, AuditAction::ContentHidden)
+                .with_metadata(metadata.clone())
+        ).await;
+    }
+    if body.ban_author {
+        let _ = app_state.redis_client.delete_user(&resolution.author_id).await;
+        let _ = app_state.db.refresh_tokens.revoke_token(resolution.author_id).await;
+        let _ = app_state.db.audit_logs.save_audit_log(
+            NewAuditLog::new(Some(user_auth.user.id), resolution.author_id, AuditAction::UserBanned)
+                .with_metadata(metadata)
+        ).await;
+    }
+    Ok(
+        SuccessResponse::new("Successfully took action on the report.", Some(resolution.report))
+    )
+}
\ No newline at end of file
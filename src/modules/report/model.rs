@@ -0,0 +1,247 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Type, Postgres, QueryBuilder, query, query_as, query_scalar};
+use uuid::Uuid;
+use crate::{
+    db::DBClient,
+    dto::{PaginatedData, PaginationMeta},
+    error::RepositoryError,
+    modules::report::dto::{NewReport, ReportListParams, ReportTarget},
+};
+
+#[derive(Serialize, Deserialize, Type, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "report_reason", rename_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum ReportReason {
+    Spam,
+    Harassment,
+    HateSpeech,
+    Misinformation,
+    Nsfw,
+    Other,
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "report_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ReportStatus {
+    Open,
+    Dismissed,
+    Actioned,
+}
+
+#[derive(Serialize, FromRow, Clone)]
+pub struct Report {
+    pub id: Uuid,
+    /// None for reports the content moderator filed, see `MODERATION_ACTION`.
+    pub reporter_id: Option<Uuid>,
+    pub post_id: Option<Uuid>,
+    pub comment_id: Option<Uuid>,
+    pub reason: ReportReason,
+    pub details: Option<String>,
+    pub status: ReportStatus,
+    pub resolved_by: Option<Uuid>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+pub struct ReportResolution {
+    pub report: Report,
+    pub author_id: Uuid,
+}
+
+#[async_trait]
+pub trait ReportRepository: Send + Sync {
+    async fn save_report(&self, data: NewReport) -> Result<Report, RepositoryError>;
+    async fn get_reports(&self, params: ReportListParams) -> Result<PaginatedData<Report>, RepositoryError>;
+    async fn dismiss_report(&self, report_id: Uuid, admin_id: Uuid) -> Result<ReportResolution, RepositoryError>;
+    async fn action_report(&self, report_id: Uuid, admin_id: Uuid, hide_content: bool, ban_author: bool) -> Result<ReportResolution, RepositoryError>;
+}
+
+#[async_trait]
+impl ReportRepository for DBClient {
+    async fn save_report(&self, data: NewReport) -> Result<Report, RepositoryError> {
+        let _timer = self.time_query("report.save_report");
+        let (post_id, comment_id) = match data.target {
+            ReportTarget::Post(post_id) => {
+                query_scalar!(
+                    r#"
+                        SELECT id FROM posts WHERE id = $1 AND is_hidden = false;
+                    "#,
+                    post_id,
+                ).fetch_optional(&self.pool).await?.ok_or(RepositoryError::NotFound)?;
+                (Some(post_id), None)
+            }
+            ReportTarget::Comment(comment_id) => {
+                query_scalar!(
+                    r#"
+                        SELECT id FROM comments WHERE id = $1 AND is_hidden = false;
+                    "#,
+                    comment_id,
+                ).fetch_optional(&self.pool).await?.ok_or(RepositoryError::NotFound)?;
+                (None, Some(comment_id))
+            }
+        };
+        let report = query_as!(
+            Report,
+            r#"
+                INSERT INTO reports (reporter_id, post_id, comment_id, reason, details)
+                VALUES ($1, $2, $3, $4, $5)
+                RETURNING id, reporter_id, post_id, comment_id, reason AS "reason: ReportReason", details,
+                          status AS "status: ReportStatus", resolved_by, resolved_at, created_at, updated_at;
+            "#,
+            data.reporter_id,
+            post_id,
+            comment_id,
+            data.reason as ReportReason,
+            data.details,
+        ).fetch_one(&self.pool).await?;
+        Ok(report)
+    }
+    async fn get_reports(&self, params: ReportListParams) -> Result<PaginatedData<Report>, RepositoryError> {
+        let _timer = self.time_query("report.get_reports");
+        let limit = params.pagination.limit as i32;
+        let page = params.pagination.page as i32;
+        let offset = params.pagination.offset() as i32;
+        let order_by = params.order_by.unwrap_or("DESC".to_string());
+        let mut transaction = self.pool.begin().await?;
+        let mut query_builder_items: QueryBuilder<Postgres> = QueryBuilder::new(
+            "\
+            SELECT id, reporter_id, post_id, comment_id, reason, details, status, resolved_by, resolved_at, created_at, updated_at \
+            FROM reports WHERE 1 = 1\
+            "
+        );
+        let mut query_builder_count: QueryBuilder<Postgres> = QueryBuilder::new(
+            "SELECT COUNT(*) FROM reports WHERE 1 = 1"
+        );
+        for query_builder in [&mut query_builder_items, &mut query_builder_count] {
+            if let Some(status) = params.status {
+                query_builder
+                    .push(" AND status = ")
+                    .push_bind(status);
+            }
+            if let Some(reason) = params.reason {
+                query_builder
+                    .push(" AND reason = ")
+                    .push_bind(reason);
+            }
+        }
+        query_builder_items
+            .push(" ORDER BY created_at ")
+            .push(order_by)
+            .push(" LIMIT ")
+            .push_bind(limit)
+            .push(" OFFSET ")
+            .push_bind(offset);
+        let items = query_builder_items.build_query_as::<Report>().fetch_all(&mut *transaction).await?;
+        let total_items = query_builder_count.build_query_scalar::<i64>().fetch_one(&mut *transaction).await?;
+        transaction.commit().await?;
+        let pagination = PaginationMeta::new(page, limit, total_items);
+        Ok(PaginatedData {
+            items,
+            pagination,
+        })
+    }
+    async fn dismiss_report(&self, report_id: Uuid, admin_id: Uuid) -> Result<ReportResolution, RepositoryError> {
+        let _timer = self.time_query("report.dismiss_report");
+        let mut transaction = self.pool.begin().await?;
+        let report = query_as!(
+            Report,
+            r#"
+                UPDATE reports
+                SET status = 'dismissed', resolved_by = $1, resolved_at = Now(), updated_at = Now()
+                WHERE id = $2 AND status = 'open'
+                RETURNING id, reporter_id, post_id, comment_id, reason AS "reason: ReportReason", details,
+                          status AS "status: ReportStatus", resolved_by, resolved_at, created_at, updated_at;
+            "#,
+            admin_id,
+            report_id,
+        ).fetch_optional(&mut *transaction).await?.ok_or(RepositoryError::NotFound)?;
+        let author_id = query_scalar!(
+            r#"
+                SELECT COALESCE(
+                    (SELECT user_id FROM posts WHERE id = $1),
+                    (SELECT user_id FROM comments WHERE id = $2)
+                ) AS "user_id!";
+            "#,
+            report.post_id,
+            report.comment_id,
+        ).fetch_one(&mut *transaction).await?;
+        transaction.commit().await?;
+        Ok(ReportResolution {
+            report,
+            author_id,
+        })
+    }
+    async fn action_report(&self, report_id: Uuid, admin_id: Uuid, hide_content: bool, ban_author: bool) -> Result<ReportResolution, RepositoryError> {
+        let _timer = self.time_query("report.action_report");
+        let mut transaction = self.pool.begin().await?;
+        let target = query!(
+            r#"
+                SELECT post_id, comment_id FROM reports WHERE id = $1 AND status = 'open' FOR UPDATE;
+            "#,
+            report_id,
+        ).fetch_optional(&mut *transaction).await?.ok_or(RepositoryError::NotFound)?;
+        let author_id = match (target.post_id, target.comment_id) {
+            (Some(post_id), _) => {
+                query_scalar!(
+                    r#"
+                        UPDATE posts SET is_hidden = is_hidden OR $1 WHERE id = $2 RETURNING user_id;
+                    "#,
+                    hide_content,
+                    post_id,
+                ).fetch_one(&mut *transaction).await?
+            }
+            (None, Some(comment_id)) => {
+                query_scalar!(
+                    r#"
+                        UPDATE comments SET is_hidden = is_hidden OR $1 WHERE id = $2 RETURNING user_id;
+                    "#,
+                    hide_content,
+                    comment_id,
+                ).fetch_one(&mut *transaction).await?
+            }
+            (None, None) => return Err(RepositoryError::NotFound),
+        };
+        if ban_author {
+            query!(
+                r#"
+                    UPDATE users SET is_banned = true, updated_at = Now() WHERE id = $1;
+                "#,
+                author_id,
+            ).execute(&mut *transaction).await?;
+        }
+        // Every open report on the same content is settled by the same decision.
+        query!(
+            r#"
+                UPDATE reports
+                SET status = 'actioned', resolved_by = $1, resolved_at = Now(), updated_at = Now()
+                WHERE status = 'open' AND id <> $2
+                  AND (post_id = $3 OR comment_id = $4);
+            "#,
+            admin_id,
+            report_id,
+            target.post_id,
+            target.comment_id,
+        ).execute(&mut *transaction).await?;
+        let report = query_as!(
+            Report,
+            r#"
+                UPDATE reports
+                SET status = 'actioned', resolved_by = $1, resolved_at = Now(), updated_at = Now()
+                WHERE id = $2
+                RETURNING id, reporter_id, post_id, comment_id, reason AS "reason: ReportReason", details,
+                          status AS "status: ReportStatus", resolved_by, resolved_at, created_at, updated_at;
+            "#,
+            admin_id,
+            report_id,
+        ).fetch_one(&mut *transaction).await?;
+        transaction.commit().await?;
+        Ok(ReportResolution {
+            report,
+            author_id,
+        })
+    }
+}
\ No newline at end of file
@@ -0,0 +1,16 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+use crate::modules::retention::model::{RetentionAction, RetentionRule};
+
+/// What a retention rule would do if the worker ran now.
+#[derive(Serialize, ToSchema)]
+pub struct RetentionPreview {
+    pub rule: RetentionRule,
+    pub action: RetentionAction,
+    pub retention_days: u32,
+    /// Rows from before this are affected.
+    pub cutoff: DateTime<Utc>,
+    pub count: i64,
+    pub oldest: Option<DateTime<Utc>>,
+}
\ No newline at end of file
@@ -0,0 +1,24 @@
+use std::sync::Arc;
+use axum::{middleware, Router, routing::get, Extension, response::IntoResponse};
+use crate::{
+    AppState,
+    dto::{HttpResult, SuccessResponse},
+    middleware::permission::{check_permission, Permission},
+};
+
+pub fn retention_admin_router() -> Router {
+    Router::new()
+        .route("/retention/dry-run", get(retention_dry_run).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminRetentionView.to_string())
+        })))
+}
+
+/// Counts what each retention rule that is on would purge or anonymize now, without touching it.
+async fn retention_dry_run(
+    Extension(app_state): Extension<Arc<AppState>>,
+) -> HttpResult<impl IntoResponse> {
+    let previews = app_state.retention_service().dry_run().await?;
+    Ok(
+        SuccessResponse::new("Getting data retention preview data", Some(previews))
+    )
+}
\ No newline at end of file
@@ -0,0 +1,153 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as};
+use utoipa::ToSchema;
+use crate::{config::Config, db::DBClient, error::RepositoryError};
+
+/// What the retention worker cleans up, each rule with its own `RETENTION_*_DAYS` setting.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionRule {
+    /// Comments soft-deleted by their author or a moderator, kept while they have open reports.
+    DeletedComments,
+    /// Audit logs lose the actor, the user they are about and their metadata.
+    AuditLogs,
+    AnalyticsEvents,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RetentionAction {
+    Purge,
+    Anonymize,
+}
+
+impl RetentionRule {
+    pub const ALL: [RetentionRule; 3] = [RetentionRule::DeletedComments, RetentionRule::AuditLogs, RetentionRule::AnalyticsEvents];
+
+    pub fn get_value(&self) -> &'static str {
+        match self {
+            RetentionRule::DeletedComments => "deleted_comments",
+            RetentionRule::AuditLogs => "audit_logs",
+            RetentionRule::AnalyticsEvents => "analytics_events",
+        }
+    }
+    pub fn action(&self) -> RetentionAction {
+        match self {
+            RetentionRule::AuditLogs => RetentionAction::Anonymize,
+            RetentionRule::DeletedComments | RetentionRule::AnalyticsEvents => RetentionAction::Purge,
+        }
+    }
+    /// Days the rows are kept before the rule applies, 0 when it is off.
+    pub fn days(&self, env: &Config) -> u32 {
+        match self {
+            RetentionRule::DeletedComments => env.retention_deleted_comment_days,
+            RetentionRule::AuditLogs => env.retention_audit_log_days,
+            RetentionRule::AnalyticsEvents => env.retention_analytics_event_days,
+        }
+    }
+}
+
+pub struct RetentionCandidates {
+    pub count: i64,
+    pub oldest: Option<DateTime<Utc>>,
+}
+
+#[async_trait]
+pub trait RetentionRepository: Send + Sync {
+    /// The rows `rule` applies to, those from before `cutoff` it hasn't handled yet.
+    async fn get_retention_candidates(&self, rule: RetentionRule, cutoff: DateTime<Utc>) -> Result<RetentionCandidates, RepositoryError>;
+    /// Applies `rule` to at most `limit` of its rows, the oldest first, and returns how many.
+    async fn apply_retention(&self, rule: RetentionRule, cutoff: DateTime<Utc>, limit: i64) -> Result<u64, RepositoryError>;
+}
+
+#[async_trait]
+impl RetentionRepository for DBClient {
+    async fn get_retention_candidates(&self, rule: RetentionRule, cutoff: DateTime<Utc>) -> Result<RetentionCandidates, RepositoryError> {
+        let _timer = self.time_query("retention.get_retention_candidates");
+        let candidates = match rule {
+            RetentionRule::DeletedComments => query_as!(
+                RetentionCandidates,
+                r#"
+                    SELECT COUNT(*) AS "count!", MIN(c.deleted_at) AS oldest
+                    FROM comments AS c
+                    WHERE c.deleted_at < $1
+                        AND NOT EXISTS (SELECT 1 FROM reports AS r WHERE r.comment_id = c.id AND r.status = 'open');
+                "#,
+                cutoff,
+            ).fetch_one(&self.pool).await?,
+            RetentionRule::AuditLogs => query_as!(
+                RetentionCandidates,
+                r#"
+                    SELECT COUNT(*) AS "count!", MIN(created_at) AS oldest
+                    FROM audit_logs
+                    WHERE created_at < $1 AND anonymized_at IS NULL;
+                "#,
+                cutoff,
+            ).fetch_one(&self.pool).await?,
+            RetentionRule::AnalyticsEvents => query_as!(
+                RetentionCandidates,
+                r#"
+                    SELECT COUNT(*) AS "count!", MIN(received_at) AS oldest
+                    FROM analytics_events
+                    WHERE received_at < $1;
+                "#,
+                cutoff,
+            ).fetch_one(&self.pool).await?,
+        };
+        Ok(candidates)
+    }
+    async fn apply_retention(&self, rule: RetentionRule, cutoff: DateTime<Utc>, limit: i64) -> Result<u64, RepositoryError> {
+        let _timer = self.time_query("retention.apply_retention");
+        let result = match rule {
+            RetentionRule::DeletedComments => query!(
+                r#"
+                    DELETE FROM comments
+                    WHERE id IN (
+                        SELECT c.id
+                        FROM comments AS c
+                        WHERE c.deleted_at < $1
+                            AND NOT EXISTS (SELECT 1 FROM reports AS r WHERE r.comment_id = c.id AND r.status = 'open')
+                        ORDER BY c.deleted_at
+                        LIMIT $2
+                    );
+                "#,
+                cutoff,
+                limit,
+            ).execute(&self.pool).await?,
+            // The nil uuid stands in for the user, `target_user_id` can't be null.
+            RetentionRule::AuditLogs => query!(
+                r#"
+                    UPDATE audit_logs
+                    SET actor_id = NULL,
+                        target_user_id = '00000000-0000-0000-0000-000000000000',
+                        metadata = '{}'::jsonb,
+                        anonymized_at = NOW()
+                    WHERE id IN (
+                        SELECT id FROM audit_logs
+                        WHERE created_at < $1 AND anonymized_at IS NULL
+                        ORDER BY created_at
+                        LIMIT $2
+                    );
+                "#,
+                cutoff,
+                limit,
+            ).execute(&self.pool).await?,
+            RetentionRule::AnalyticsEvents => query!(
+                r#"
+                    DELETE FROM analytics_events
+                    WHERE id IN (
+                        SELECT id FROM analytics_events
+                        WHERE received_at < $1
+                        ORDER BY received_at
+                        LIMIT $2
+                    );
+                "#,
+                cutoff,
+                limit,
+            ).execute(&self.pool).await?,
+        };
+        Ok(result.rows_affected())
+    }
+}
\ No newline at end of file
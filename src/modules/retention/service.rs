@@ -0,0 +1,57 @@
+use std::sync::Arc;
+use chrono::{DateTime, Duration, Utc};
+use crate::{
+    AppState,
+    error::AppError,
+    modules::retention::{dto::RetentionPreview, model::RetentionRule},
+    utils::metrics,
+};
+
+/// The data retention rules from `RETENTION_*_DAYS`, applied by `jobs::retention` and previewed by
+/// the admin dry run.
+pub struct RetentionService {
+    app_state: Arc<AppState>,
+}
+
+impl RetentionService {
+    pub fn new(app_state: Arc<AppState>) -> Self {
+        Self { app_state }
+    }
+    /// The rules that are on, with how many days they keep and the cutoff that gives at `now`.
+    pub fn rules(&self, now: DateTime<Utc>) -> Vec<(RetentionRule, u32, DateTime<Utc>)> {
+        RetentionRule::ALL.into_iter()
+            .map(|rule| (rule, rule.days(&self.app_state.env)))
+            .filter(|(_, days)| *days > 0)
+            .map(|(rule, days)| (rule, days, now - Duration::days(days.into())))
+            .collect()
+    }
+    pub async fn dry_run(&self) -> Result<Vec<RetentionPreview>, AppError> {
+        let mut previews = Vec::new();
+        for (rule, days, cutoff) in self.rules(Utc::now()) {
+            let candidates = self.app_state.db.retention.get_retention_candidates(rule, cutoff).await?;
+            previews.push(RetentionPreview {
+                rule,
+                action: rule.action(),
+                retention_days: days,
+                cutoff,
+                count: candidates.count,
+                oldest: candidates.oldest,
+            });
+        }
+        Ok(previews)
+    }
+    /// Applies `rule` batch by batch until none of its rows from before `cutoff` are left, and
+    /// returns how many it handled. A failing batch stops it, the next run picks up from there.
+    pub async fn apply(&self, rule: RetentionRule, cutoff: DateTime<Utc>) -> Result<u64, AppError> {
+        let batch_size = self.app_state.env.retention_batch_size;
+        let mut total = 0;
+        loop {
+            let count = self.app_state.db.retention.apply_retention(rule, cutoff, batch_size).await?;
+            metrics::increment_counter_by("retention_rows_total", &[("rule", rule.get_value())], count);
+            total += count;
+            if (count as i64) < batch_size {
+                return Ok(total);
+            }
+        }
+    }
+}
\ No newline at end of file
@@ -1,11 +1,13 @@
+use std::{collections::HashMap, sync::{Arc, RwLock}};
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, Type, Error as SqlxError, query_scalar};
+use utoipa::ToSchema;
+use sqlx::{FromRow, Type, query};
 use uuid::Uuid;
-use crate::db::DBClient;
+use crate::{db::DBClient, error::RepositoryError};
 
-#[derive(Serialize, Type, Deserialize, Debug)]
+#[derive(Serialize, Type, Deserialize, Debug, Clone, Copy, PartialEq, ToSchema)]
 #[sqlx(type_name = "role_type", rename_all = "lowercase")]
 pub enum RoleType {
     Admin,
@@ -21,39 +23,90 @@ impl RoleType {
     }
 }
 
+#[allow(dead_code)]
 #[derive(Serialize, FromRow, Type)]
 pub struct Role {
     pub id: Uuid,
     pub name: RoleType,
+    /// The role whose permissions this one inherits.
+    pub parent_role_id: Option<Uuid>,
     pub description: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+/// The roles table, read once on the first lookup and kept in memory. It holds a handful of rows
+/// that only the seed command adds to, so role lookups after a user query cost no round trip. A
+/// lookup that misses reloads the table in case a role was added since.
+#[derive(Clone, Default)]
+pub struct RoleCache {
+    roles: Arc<RwLock<HashMap<Uuid, RoleType>>>,
+}
+
+impl RoleCache {
+    fn name(&self, role_id: Uuid) -> Option<RoleType> {
+        self.roles.read().ok()?.get(&role_id).copied()
+    }
+    fn id(&self, name: RoleType) -> Option<Uuid> {
+        self.roles.read().ok()?.iter().find(|(_, role)| **role == name).map(|(id, _)| *id)
+    }
+    fn replace(&self, roles: impl IntoIterator<Item = (Uuid, RoleType)>) {
+        if let Ok(mut cached) = self.roles.write() {
+            *cached = roles.into_iter().collect();
+        }
+    }
+}
+
+impl DBClient {
+    async fn load_roles(&self) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("role.load_roles");
+        let roles = query!(
+            r#"
+                SELECT id, name as "name: RoleType" FROM roles;
+            "#,
+        ).fetch_all(&self.pool).await?;
+        self.roles.replace(roles.into_iter().map(|role| (role.id, role.name)));
+        Ok(())
+    }
+}
+
 #[async_trait]
-pub trait RoleRepository {
-    async fn get_role_id_by_name(&self, name: RoleType) -> Result<Option<Uuid>, SqlxError>;
-    async fn get_role_name_by_id(&self, role_id: Uuid) -> Result<Option<RoleType>, SqlxError>;
+pub trait RoleRepository: Send + Sync {
+    async fn get_role_id_by_name(&self, name: RoleType) -> Result<Option<Uuid>, RepositoryError>;
+    async fn get_role_name_by_id(&self, role_id: Uuid) -> Result<Option<RoleType>, RepositoryError>;
+    async fn ensure_role(&self, name: RoleType, parent: Option<RoleType>, description: &str) -> Result<bool, RepositoryError>;
 }
 
 #[async_trait]
 impl RoleRepository for DBClient {
-    async fn get_role_id_by_name(&self, name: RoleType) -> Result<Option<Uuid>, SqlxError> {
-        let role_id = query_scalar!(
-            r#"
-                SELECT id FROM roles WHERE name = $1;
-            "#,
-            name as RoleType,
-        ).fetch_optional(&self.pool).await?;
-        Ok(role_id)
+    async fn get_role_id_by_name(&self, name: RoleType) -> Result<Option<Uuid>, RepositoryError> {
+        if let Some(role_id) = self.roles.id(name) {
+            return Ok(Some(role_id));
+        }
+        self.load_roles().await?;
+        Ok(self.roles.id(name))
     }
-    async fn get_role_name_by_id(&self, role_id: Uuid) -> Result<Option<RoleType>, SqlxError> {
-        let role_name = query_scalar!(
+    async fn get_role_name_by_id(&self, role_id: Uuid) -> Result<Option<RoleType>, RepositoryError> {
+        if let Some(role_name) = self.roles.name(role_id) {
+            return Ok(Some(role_name));
+        }
+        self.load_roles().await?;
+        Ok(self.roles.name(role_id))
+    }
+    /// Inserts the role, inheriting from `parent` when that role exists, unless one with the same
+    /// name exists. Returns whether it was inserted.
+    async fn ensure_role(&self, name: RoleType, parent: Option<RoleType>, description: &str) -> Result<bool, RepositoryError> {
+        let _timer = self.time_query("role.ensure_role");
+        let result = query!(
             r#"
-               SELECT name as "name: RoleType" FROM roles WHERE id = $1;
+                INSERT INTO roles (name, parent_role_id, description)
+                SELECT $1, (SELECT id FROM roles WHERE name = $2), $3
+                WHERE NOT EXISTS (SELECT 1 FROM roles WHERE name = $1);
             "#,
-            role_id,
-        ).fetch_optional(&self.pool).await?;
-        Ok(role_name)
+            name as RoleType,
+            parent as Option<RoleType>,
+            description,
+        ).execute(&self.pool).await?;
+        Ok(result.rows_affected() > 0)
     }
 }
@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+use utoipa::IntoParams;
+use validator::{Validate, ValidationError};
+use crate::{config::Config, dto::{Paginated, Pagination}};
+
+/// How many matches of each group `GET /api/search` returns without a `type`.
+pub const SEARCH_GROUP_LIMIT: usize = 5;
+
+fn validate_search_query(value: &str) -> Result<(), ValidationError> {
+    if !value.trim().trim_start_matches(['@', '#']).trim().is_empty() {
+        return Ok(());
+    }
+    let mut error = ValidationError::new("invalid_query");
+    error.message = Some("Query must contain more than spaces, @ and #".into());
+    Err(error)
+}
+fn validate_search_type(value: &str) -> Result<(), ValidationError> {
+    match value {
+        "users" | "posts" | "tags" => Ok(()),
+        _ => {
+            let mut error = ValidationError::new("invalid_type");
+            error.message = Some("Type must be one of 'users', 'posts' or 'tags'".into());
+            Err(error)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchParams {
+    /// Words to look for. A leading `@` is dropped when matching users and a leading `#` when
+    /// matching tags.
+    #[validate(length(min = 1, max = 100, message = "Query must be between 1 and 100 characters"))]
+    #[validate(custom(function = "validate_search_query"))]
+    pub q: String,
+    /// One of `users`, `posts` or `tags` to page through that group alone.
+    #[serde(rename = "type")]
+    #[validate(custom(function = "validate_search_type"))]
+    pub kind: Option<String>,
+    pub limit: Option<usize>,
+    #[validate(range(min = 1, max = 100000, message = "Page must be between 1 and 100000."))]
+    pub page: Option<usize>,
+    #[serde(skip)]
+    pub pagination: Pagination,
+}
+impl Paginated for SearchParams {
+    fn paginate(&mut self, config: &Config) {
+        self.pagination = Pagination::sanitize(self.page, self.limit, config);
+    }
+}
+impl SearchParams {
+    /// `q` as a full text query for posts.
+    pub fn text(&self) -> &str {
+        self.q.trim()
+    }
+    /// `q` as a username or name prefix.
+    pub fn user_query(&self) -> String {
+        self.q.trim().trim_start_matches('@').to_lowercase()
+    }
+    /// `q` as a tag, or a tag prefix.
+    pub fn tag_query(&self) -> String {
+        self.q.trim().trim_start_matches('#').to_lowercase()
+    }
+}
\ No newline at end of file
@@ -0,0 +1,73 @@
+use std::sync::Arc;
+use axum::{middleware, Router, routing::get, Extension, response::{IntoResponse, Response}};
+use crate::{
+    AppState,
+    dto::{HttpResult, Pagination, PaginatedData, SuccessResponse},
+    error::PaginatedQuery,
+    middleware::{AuthenticatedUser, permission::{check_permission, Permission}},
+    modules::{
+        search::{
+            dto::{SearchParams, SEARCH_GROUP_LIMIT},
+            model::{PostSearchResult, SearchResults, TagSearchResult},
+        },
+        user::model::UserSearchResult,
+    },
+};
+
+pub fn search_router() -> Router {
+    Router::new()
+        .route("/", get(search).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::SearchQuery.to_string())
+        })))
+}
+#[utoipa::path(
+    get,
+    path = "/api/search",
+    tag = "search",
+    params(SearchParams),
+    responses(
+        (status = 200, description = "The top users, posts and tags matching the query, or a page of one group when `type` is set", body = SuccessResponse<SearchResults>),
+        (status = 200, description = "A page of users, with `type=users`", body = SuccessResponse<PaginatedData<UserSearchResult>>),
+        (status = 200, description = "A page of posts, with `type=posts`", body = SuccessResponse<PaginatedData<PostSearchResult>>),
+        (status = 200, description = "A page of tags, with `type=tags`", body = SuccessResponse<PaginatedData<TagSearchResult>>),
+        (status = 400, description = "Missing or empty query, or an unknown type"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn search(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PaginatedQuery(query_params): PaginatedQuery<SearchParams>,
+) -> HttpResult<Response> {
+    let search = &app_state.db.search;
+    let viewer_id = user_auth.user.id;
+    let pagination = &query_params.pagination;
+    let (user_query, tag_query) = (query_params.user_query(), query_params.tag_query());
+    let response = match query_params.kind.as_deref() {
+        Some("users") => SuccessResponse::new(
+            "Getting user search results",
+            Some(search.search_users(&user_query, pagination).await?),
+        ).into_response(),
+        Some("posts") => SuccessResponse::new(
+            "Getting post search results",
+            Some(search.search_posts(query_params.text(), &tag_query, viewer_id, pagination).await?),
+        ).into_response(),
+        Some("tags") => SuccessResponse::new(
+            "Getting tag search results",
+            Some(search.search_tags(&tag_query, viewer_id, pagination).await?),
+        ).into_response(),
+        _ => {
+            let top = Pagination { page: 1, limit: SEARCH_GROUP_LIMIT };
+            let (users, posts, tags) = tokio::try_join!(
+                search.search_users(&user_query, &top),
+                search.search_posts(query_params.text(), &tag_query, viewer_id, &top),
+                search.search_tags(&tag_query, viewer_id, &top),
+            )?;
+            SuccessResponse::new(
+                "Getting search results",
+                Some(SearchResults { users: users.items, posts: posts.items, tags: tags.items }),
+            ).into_response()
+        }
+    };
+    Ok(response)
+}
\ No newline at end of file
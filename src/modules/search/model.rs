@@ -0,0 +1,183 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Postgres, QueryBuilder};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use crate::{
+    db::{like_prefix, Counted, DBClient},
+    dto::{PaginatedData, Pagination, PaginationMeta},
+    error::RepositoryError,
+    modules::user::model::UserSearchResult,
+};
+
+#[derive(Serialize, Deserialize, FromRow, ToSchema)]
+pub struct PostSearchResult {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub author: String,
+    pub title: String,
+    pub content: String,
+    pub tags: Vec<String>,
+    pub views_count: i64,
+    pub created_at: DateTime<Utc>,
+}
+#[derive(Serialize, Deserialize, FromRow, ToSchema)]
+pub struct TagSearchResult {
+    pub name: String,
+    pub posts_count: i64,
+}
+/// The best matches of every group, `GET /api/search` without `type`.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct SearchResults {
+    pub users: Vec<UserSearchResult>,
+    pub posts: Vec<PostSearchResult>,
+    pub tags: Vec<TagSearchResult>,
+}
+
+#[async_trait]
+pub trait SearchRepository: Send + Sync {
+    /// Matched and ordered like `UserRepository::search_users`, a page at a time.
+    async fn search_users(&self, query: &str, pagination: &Pagination) -> Result<PaginatedData<UserSearchResult>, RepositoryError>;
+    /// Posts `viewer_id` may see whose title or content match `text` as a web search query (quoted
+    /// phrases, `or`, `-word`) or that are tagged `tag`. Best full text rank first, a tag match
+    /// counting more than any rank.
+    async fn search_posts(&self, text: &str, tag: &str, viewer_id: Uuid, pagination: &Pagination) -> Result<PaginatedData<PostSearchResult>, RepositoryError>;
+    /// Tags starting with `query` (lowercase) on posts `viewer_id` may see, case folded. The exact
+    /// tag comes first, then the most used.
+    async fn search_tags(&self, query: &str, viewer_id: Uuid, pagination: &Pagination) -> Result<PaginatedData<TagSearchResult>, RepositoryError>;
+}
+
+fn push_user_filters(query_builder: &mut QueryBuilder<'_, Postgres>, query: &str) {
+    let prefix = like_prefix(query);
+    query_builder
+        .push(" FROM users WHERE is_banned = false AND (username LIKE ")
+        .push_bind(prefix.clone())
+        .push(" OR lower(name) LIKE ")
+        .push_bind(prefix.clone())
+        .push(" OR lower(name) LIKE '% ' || ")
+        .push_bind(prefix)
+        .push(")");
+}
+/// The conditions `PostRepository::get_posts_by_ids` applies, on `p` joined with its author `u`.
+fn push_post_visibility(query_builder: &mut QueryBuilder<'_, Postgres>, viewer_id: Uuid) {
+    query_builder
+        .push(" AND p.is_hidden = false AND u.is_banned = false AND (NOT u.is_private OR u.id = ")
+        .push_bind(viewer_id)
+        .push(" OR EXISTS (SELECT 1 FROM user_followers WHERE following_id = u.id AND follower_id = ")
+        .push_bind(viewer_id)
+        .push(") OR EXISTS (SELECT 1 FROM users AS v JOIN roles AS r ON r.id = v.role_id WHERE v.id = ")
+        .push_bind(viewer_id)
+        .push(" AND r.name = 'admin'))");
+}
+fn push_post_filters(query_builder: &mut QueryBuilder<'_, Postgres>, text: &str, tag: &str, viewer_id: Uuid) {
+    query_builder
+        .push(" FROM posts AS p JOIN users AS u ON u.id = p.user_id WHERE (p.search_vector @@ websearch_to_tsquery('english', ")
+        .push_bind(text.to_string())
+        .push(") OR p.tags @> ARRAY[")
+        .push_bind(tag.to_string())
+        .push("]::VARCHAR[])");
+    push_post_visibility(query_builder, viewer_id);
+}
+fn push_tag_filters(query_builder: &mut QueryBuilder<'_, Postgres>, query: &str, viewer_id: Uuid) {
+    query_builder
+        .push(" FROM posts AS p CROSS JOIN LATERAL unnest(p.tags) AS t(tag) JOIN users AS u ON u.id = p.user_id WHERE lower(t.tag) LIKE ")
+        .push_bind(like_prefix(query));
+    push_post_visibility(query_builder, viewer_id);
+}
+
+impl DBClient {
+    /// The total of a page query split with `Counted::split`, counted with `count` when the page
+    /// came back empty past the first one.
+    async fn page_total(&self, total_items: Option<i64>, pagination: &Pagination, mut count: QueryBuilder<'_, Postgres>) -> Result<i64, RepositoryError> {
+        match total_items {
+            Some(total_items) => Ok(total_items),
+            None if pagination.offset() == 0 => Ok(0),
+            None => Ok(count.build_query_scalar::<i64>().fetch_one(&self.pool).await?),
+        }
+    }
+}
+
+#[async_trait]
+impl SearchRepository for DBClient {
+    async fn search_users(&self, query: &str, pagination: &Pagination) -> Result<PaginatedData<UserSearchResult>, RepositoryError> {
+        let _timer = self.time_query("search.search_users");
+        let mut query_builder_items: QueryBuilder<Postgres> = QueryBuilder::new(
+            "SELECT id, name, username, avatar_url, is_verified, is_private, COUNT(*) OVER () AS total_items"
+        );
+        push_user_filters(&mut query_builder_items, query);
+        query_builder_items
+            .push(" ORDER BY CASE WHEN username = ")
+            .push_bind(query.to_string())
+            .push(" THEN 0 WHEN username LIKE ")
+            .push_bind(like_prefix(query))
+            .push(" THEN 1 WHEN lower(name) LIKE ")
+            .push_bind(like_prefix(query))
+            .push(" THEN 2 ELSE 3 END, similarity(lower(name), ")
+            .push_bind(query.to_string())
+            .push(") DESC, name, id LIMIT ")
+            .push_bind(pagination.limit as i64)
+            .push(" OFFSET ")
+            .push_bind(pagination.offset() as i64);
+        let (users, total_items) = Counted::split(
+            query_builder_items.build_query_as::<Counted<UserSearchResult>>().fetch_all(&self.pool).await?
+        );
+        let mut query_builder_count: QueryBuilder<Postgres> = QueryBuilder::new("SELECT COUNT(*)");
+        push_user_filters(&mut query_builder_count, query);
+        let total_items = self.page_total(total_items, pagination, query_builder_count).await?;
+        Ok(PaginatedData {
+            items: users,
+            pagination: PaginationMeta::new(pagination.page as i32, pagination.limit as i32, total_items),
+        })
+    }
+    async fn search_posts(&self, text: &str, tag: &str, viewer_id: Uuid, pagination: &Pagination) -> Result<PaginatedData<PostSearchResult>, RepositoryError> {
+        let _timer = self.time_query("search.search_posts");
+        let mut query_builder_items: QueryBuilder<Postgres> = QueryBuilder::new(
+            "SELECT p.id, p.user_id, u.name AS author, p.title, p.content, p.tags, p.views_count, p.created_at, COUNT(*) OVER () AS total_items"
+        );
+        push_post_filters(&mut query_builder_items, text, tag, viewer_id);
+        query_builder_items
+            .push(" ORDER BY ts_rank(p.search_vector, websearch_to_tsquery('english', ")
+            .push_bind(text.to_string())
+            .push(")) + CASE WHEN p.tags @> ARRAY[")
+            .push_bind(tag.to_string())
+            .push("]::VARCHAR[] THEN 1 ELSE 0 END DESC, p.created_at DESC, p.id LIMIT ")
+            .push_bind(pagination.limit as i64)
+            .push(" OFFSET ")
+            .push_bind(pagination.offset() as i64);
+        let (posts, total_items) = Counted::split(
+            query_builder_items.build_query_as::<Counted<PostSearchResult>>().fetch_all(&self.pool).await?
+        );
+        let mut query_builder_count: QueryBuilder<Postgres> = QueryBuilder::new("SELECT COUNT(*)");
+        push_post_filters(&mut query_builder_count, text, tag, viewer_id);
+        let total_items = self.page_total(total_items, pagination, query_builder_count).await?;
+        Ok(PaginatedData {
+            items: posts,
+            pagination: PaginationMeta::new(pagination.page as i32, pagination.limit as i32, total_items),
+        })
+    }
+    async fn search_tags(&self, query: &str, viewer_id: Uuid, pagination: &Pagination) -> Result<PaginatedData<TagSearchResult>, RepositoryError> {
+        let _timer = self.time_query("search.search_tags");
+        let mut query_builder_items: QueryBuilder<Postgres> = QueryBuilder::new(
+            "SELECT lower(t.tag) AS name, COUNT(*) AS posts_count, COUNT(*) OVER () AS total_items"
+        );
+        push_tag_filters(&mut query_builder_items, query, viewer_id);
+        query_builder_items
+            .push(" GROUP BY lower(t.tag) ORDER BY lower(t.tag) = ")
+            .push_bind(query.to_string())
+            .push(" DESC, posts_count DESC, name LIMIT ")
+            .push_bind(pagination.limit as i64)
+            .push(" OFFSET ")
+            .push_bind(pagination.offset() as i64);
+        let (tags, total_items) = Counted::split(
+            query_builder_items.build_query_as::<Counted<TagSearchResult>>().fetch_all(&self.pool).await?
+        );
+        let mut query_builder_count: QueryBuilder<Postgres> = QueryBuilder::new("SELECT COUNT(DISTINCT lower(t.tag))");
+        push_tag_filters(&mut query_builder_count, query, viewer_id);
+        let total_items = self.page_total(total_items, pagination, query_builder_count).await?;
+        Ok(PaginatedData {
+            items: tags,
+            pagination: PaginationMeta::new(pagination.page as i32, pagination.limit as i32, total_items),
+        })
+    }
+}
\ No newline at end of file
@@ -0,0 +1,101 @@
+use std::net::IpAddr;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, query_as};
+use uuid::Uuid;
+use crate::{db::DBClient, error::RepositoryError};
+
+/// Where a sign-in came from. Both values are what the client reported, good enough to tell
+/// devices apart but not to identify one.
+#[derive(Clone)]
+pub struct ClientDevice {
+    pub ip_address: String,
+    pub user_agent: String,
+}
+
+impl ClientDevice {
+    pub fn new(ip_address: Option<IpAddr>, user_agent: Option<&str>) -> Self {
+        let user_agent = user_agent.map(str::trim).filter(|user_agent| !user_agent.is_empty()).unwrap_or("unknown");
+        Self {
+            ip_address: ip_address.map(|ip| ip.to_string()).unwrap_or_else(|| "unknown".to_string()),
+            user_agent: user_agent.chars().take(255).collect(),
+        }
+    }
+}
+
+/// A device the user has signed in from, one row per IP address and user agent.
+#[derive(Serialize, FromRow, Clone)]
+pub struct UserSession {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub ip_address: String,
+    pub user_agent: String,
+    /// Hash of the token in the revoke link of the new sign-in email.
+    #[serde(skip_serializing)]
+    pub revoke_token_hash: String,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+}
+
+pub struct NewUserSession<'a> {
+    pub user_id: Uuid,
+    pub device: &'a ClientDevice,
+    pub revoke_token_hash: &'a str,
+}
+
+/// What `record_session` found: whether the device is new, and how many devices the user had
+/// signed in from before.
+pub struct SessionSighting {
+    pub is_new: bool,
+    pub known_devices: i64,
+}
+
+#[async_trait]
+pub trait SessionRepository: Send + Sync {
+    /// Adds the device, or bumps `last_seen_at` when the user signed in from it before, in which
+    /// case `revoke_token_hash` is dropped and the stored one kept.
+    async fn record_session<'a>(&self, data: NewUserSession<'a>) -> Result<SessionSighting, RepositoryError>;
+    /// Forgets the device the revoke link was sent for, so it counts as new again.
+    async fn revoke_session(&self, revoke_token_hash: &str) -> Result<Option<UserSession>, RepositoryError>;
+}
+
+#[async_trait]
+impl SessionRepository for DBClient {
+    async fn record_session<'a>(&self, data: NewUserSession<'a>) -> Result<SessionSighting, RepositoryError> {
+        let _timer = self.time_query("session.record_session");
+        // `known` reads the table as it was before the insert, xmax is 0 only for a freshly inserted row.
+        let sighting = query_as!(
+            SessionSighting,
+            r#"
+                WITH known AS (
+                    SELECT COUNT(*) AS count FROM user_sessions WHERE user_id = $1
+                ), sighting AS (
+                    INSERT INTO user_sessions (user_id, ip_address, user_agent, revoke_token_hash)
+                    VALUES ($1, $2, $3, $4)
+                    ON CONFLICT (user_id, ip_address, user_agent) DO UPDATE SET last_seen_at = Now()
+                    RETURNING (xmax = 0) AS is_new
+                )
+                SELECT sighting.is_new AS "is_new!", known.count AS "known_devices!"
+                FROM sighting, known;
+            "#,
+            data.user_id,
+            data.device.ip_address,
+            data.device.user_agent,
+            data.revoke_token_hash,
+        ).fetch_one(&self.pool).await?;
+        Ok(sighting)
+    }
+    async fn revoke_session(&self, revoke_token_hash: &str) -> Result<Option<UserSession>, RepositoryError> {
+        let _timer = self.time_query("session.revoke_session");
+        let session = query_as!(
+            UserSession,
+            r#"
+                DELETE FROM user_sessions WHERE revoke_token_hash = $1
+                RETURNING id, user_id, ip_address, user_agent, revoke_token_hash, created_at, last_seen_at;
+            "#,
+            revoke_token_hash,
+        ).fetch_optional(&self.pool).await?;
+        Ok(session)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
+
+/// Versions go out in the `X-Tos-Required` header, so they are kept to 1 to 50 letters, digits,
+/// dots, dashes or underscores, e.g. `2025-09`.
+fn validate_tos_version(value: &str) -> Result<(), ValidationError> {
+    let is_version = (1..=50).contains(&value.len())
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'));
+    if is_version {
+        return Ok(());
+    }
+    let mut error = ValidationError::new("invalid_version");
+    error.message = Some("Version must be 1 to 50 letters, digits, dots, dashes or underscores".into());
+    Err(error)
+}
+fn validate_tos_url(value: &str) -> Result<(), ValidationError> {
+    match reqwest::Url::parse(value) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.host().is_some() => Ok(()),
+        _ => {
+            let mut error = ValidationError::new("invalid_url");
+            error.message = Some("Url must be an absolute http or https url".into());
+            Err(error)
+        }
+    }
+}
+
+/// Body of `POST /api/admin/tos`, the version becomes the current one right away.
+#[derive(Serialize, Deserialize, Validate)]
+pub struct TosVersionRequest {
+    #[validate(custom(function = "validate_tos_version"))]
+    pub version: String,
+    /// Where the full text is published.
+    #[validate(length(max = 500, message = "Url must be at most 500 characters"))]
+    #[validate(custom(function = "validate_tos_url"))]
+    pub url: String,
+    /// What changed, for the prompt asking users to accept.
+    #[validate(length(max = 2000, message = "Summary must be at most 2000 characters"))]
+    #[serde(default)]
+    pub summary: String,
+}
+
+pub struct NewTosVersion {
+    pub version: String,
+    pub url: String,
+    pub summary: String,
+    pub published_by: Uuid,
+}
+
+/// Body of `POST /api/user/accept-tos`, naming the version the user was shown, so a version
+/// published in the meantime isn't accepted unseen.
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
+pub struct TosAcceptRequest {
+    #[validate(length(min = 1, max = 50, message = "Version must be between 1 and 50 characters"))]
+    pub version: String,
+}
\ No newline at end of file
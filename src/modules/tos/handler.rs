@@ -0,0 +1,87 @@
+use std::sync::Arc;
+use axum::{middleware, Router, routing::{get, post}, Extension, response::IntoResponse, http::StatusCode};
+use crate::{
+    AppState,
+    dto::{HttpResult, SuccessResponse},
+    error::{AppError, ErrorMessage, ValidatedJson},
+    middleware::{AuthenticatedUser, permission::{check_permission, Permission}},
+    modules::tos::{dto::{TosAcceptRequest, TosVersionRequest}, model::{TosAcceptance, TosVersion}},
+};
+
+/// Open to signed out visitors, so sign-up forms can link the current version.
+pub fn tos_router() -> Router {
+    Router::new()
+        .route("/current", get(tos_current))
+}
+
+pub fn tos_admin_router() -> Router {
+    Router::new()
+        .route("/tos", post(tos_publish).get(tos_list).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminTosPublish.to_string())
+        })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/tos/current",
+    tag = "tos",
+    responses(
+        (status = 200, description = "The current terms of service version", body = SuccessResponse<TosVersion>),
+        (status = 404, description = "No version has been published"),
+    ),
+    security(()),
+)]
+async fn tos_current(
+    Extension(app_state): Extension<Arc<AppState>>,
+) -> HttpResult<impl IntoResponse> {
+    let version = app_state.tos_service().current().await?
+        .ok_or(AppError::not_found(ErrorMessage::DataNotFound))?;
+    Ok(
+        SuccessResponse::new("Getting current terms of service data", Some(version))
+    )
+}
+#[utoipa::path(
+    post,
+    path = "/api/user/accept-tos",
+    tag = "user",
+    request_body = TosAcceptRequest,
+    responses(
+        (status = 200, description = "The current version is accepted, writes are open again", body = SuccessResponse<TosAcceptance>),
+        (status = 403, description = "Impersonation tokens can't accept on the user's behalf"),
+        (status = 404, description = "No version has been published"),
+        (status = 409, description = "The version isn't the current one"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+pub async fn user_accept_tos(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    ValidatedJson(body): ValidatedJson<TosAcceptRequest>,
+) -> HttpResult<impl IntoResponse> {
+    if user_auth.impersonator.is_some() {
+        return Err(AppError::forbidden(ErrorMessage::PermissionDenied));
+    }
+    let acceptance = app_state.tos_service().accept(user_auth.user.id, body).await?;
+    Ok(
+        SuccessResponse::new("Successfully accepted the terms of service.", Some(acceptance))
+    )
+}
+async fn tos_publish(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    ValidatedJson(body): ValidatedJson<TosVersionRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let version = app_state.tos_service().publish(&user_auth.user, body).await?;
+    Ok((
+        StatusCode::CREATED,
+        SuccessResponse::new("Successfully published the terms of service.", Some(version))
+    ))
+}
+async fn tos_list(
+    Extension(app_state): Extension<Arc<AppState>>,
+) -> HttpResult<impl IntoResponse> {
+    let versions = app_state.tos_service().all().await?;
+    Ok(
+        SuccessResponse::new("Getting terms of service list data", Some(versions))
+    )
+}
\ No newline at end of file
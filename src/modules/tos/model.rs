@@ -0,0 +1,106 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, query_as, query_scalar};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use crate::{db::DBClient, error::RepositoryError, modules::tos::dto::NewTosVersion};
+
+#[derive(Serialize, Deserialize, FromRow, Clone, ToSchema)]
+pub struct TosVersion {
+    pub id: Uuid,
+    pub version: String,
+    pub url: String,
+    pub summary: String,
+    pub published_by: Option<Uuid>,
+    pub published_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct TosAcceptance {
+    pub version: String,
+    pub accepted_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait TosRepository: Send + Sync {
+    /// The latest published version, `None` before the first one.
+    async fn get_current_tos_version(&self) -> Result<Option<TosVersion>, RepositoryError>;
+    /// Every version, the latest first.
+    async fn get_tos_versions(&self) -> Result<Vec<TosVersion>, RepositoryError>;
+    async fn save_tos_version(&self, data: NewTosVersion) -> Result<TosVersion, RepositoryError>;
+    async fn has_accepted_tos(&self, user_id: Uuid, tos_version_id: Uuid) -> Result<bool, RepositoryError>;
+    /// Records the acceptance and returns when it happened, the first time for a version accepted
+    /// before.
+    async fn accept_tos(&self, user_id: Uuid, tos_version_id: Uuid) -> Result<DateTime<Utc>, RepositoryError>;
+}
+
+#[async_trait]
+impl TosRepository for DBClient {
+    async fn get_current_tos_version(&self) -> Result<Option<TosVersion>, RepositoryError> {
+        let _timer = self.time_query("tos.get_current_tos_version");
+        let version = query_as!(
+            TosVersion,
+            r#"
+                SELECT id, version, url, summary, published_by, published_at
+                FROM tos_versions
+                ORDER BY published_at DESC
+                LIMIT 1;
+            "#
+        ).fetch_optional(&self.pool).await?;
+        Ok(version)
+    }
+    async fn get_tos_versions(&self) -> Result<Vec<TosVersion>, RepositoryError> {
+        let _timer = self.time_query("tos.get_tos_versions");
+        let versions = query_as!(
+            TosVersion,
+            r#"
+                SELECT id, version, url, summary, published_by, published_at
+                FROM tos_versions
+                ORDER BY published_at DESC;
+            "#
+        ).fetch_all(&self.pool).await?;
+        Ok(versions)
+    }
+    async fn save_tos_version(&self, data: NewTosVersion) -> Result<TosVersion, RepositoryError> {
+        let _timer = self.time_query("tos.save_tos_version");
+        let version = query_as!(
+            TosVersion,
+            r#"
+                INSERT INTO tos_versions (version, url, summary, published_by)
+                VALUES ($1, $2, $3, $4)
+                RETURNING id, version, url, summary, published_by, published_at;
+            "#,
+            data.version,
+            data.url,
+            data.summary,
+            data.published_by,
+        ).fetch_one(&self.pool).await?;
+        Ok(version)
+    }
+    async fn has_accepted_tos(&self, user_id: Uuid, tos_version_id: Uuid) -> Result<bool, RepositoryError> {
+        let _timer = self.time_query("tos.has_accepted_tos");
+        let accepted = query_scalar!(
+            r#"
+                SELECT EXISTS (SELECT 1 FROM user_tos_acceptance WHERE user_id = $1 AND tos_version_id = $2) AS "accepted!";
+            "#,
+            user_id,
+            tos_version_id,
+        ).fetch_one(&self.pool).await?;
+        Ok(accepted)
+    }
+    async fn accept_tos(&self, user_id: Uuid, tos_version_id: Uuid) -> Result<DateTime<Utc>, RepositoryError> {
+        let _timer = self.time_query("tos.accept_tos");
+        let accepted_at = query_scalar!(
+            r#"
+                INSERT INTO user_tos_acceptance (user_id, tos_version_id)
+                VALUES ($1, $2)
+                ON CONFLICT (user_id, tos_version_id) DO UPDATE SET accepted_at = user_tos_acceptance.accepted_at
+                RETURNING accepted_at;
+            "#,
+            user_id,
+            tos_version_id,
+        ).fetch_one(&self.pool).await?;
+        Ok(accepted_at)
+    }
+}
\ No newline at end of file
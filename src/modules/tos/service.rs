@@ -0,0 +1,83 @@
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::{
+    AppState,
+    error::{AppError, ErrorMessage},
+    modules::{
+        tos::{
+            dto::{NewTosVersion, TosAcceptRequest, TosVersionRequest},
+            model::{TosAcceptance, TosVersion},
+        },
+        user::model::User,
+    },
+};
+
+/// Terms of service versions and who accepted them. The latest published version is the current
+/// one; users who signed up before it was published have to accept it before they change anything,
+/// see `middleware::tos`. Accounts created after it count as having accepted it at sign-up.
+pub struct TosService {
+    app_state: Arc<AppState>,
+}
+
+impl TosService {
+    pub fn new(app_state: Arc<AppState>) -> Self {
+        Self { app_state }
+    }
+    /// The current version, from the cache when it is there.
+    pub async fn current(&self) -> Result<Option<TosVersion>, AppError> {
+        if let Ok(Some(version)) = self.app_state.redis_client.get_current_tos().await {
+            return Ok(version);
+        }
+        let version = self.app_state.db.tos.get_current_tos_version().await?;
+        let _ = self.app_state.redis_client.set_current_tos(version.as_ref(), self.app_state.env.tos_cache_ttl).await;
+        Ok(version)
+    }
+    /// The version `user` still has to accept, `None` when there is nothing to accept.
+    pub async fn pending_for(&self, user: &User) -> Result<Option<TosVersion>, AppError> {
+        let Some(current) = self.current().await? else {
+            return Ok(None);
+        };
+        if user.created_at >= current.published_at {
+            return Ok(None);
+        }
+        if let Ok(Some(accepted)) = self.app_state.redis_client.get_tos_accepted(&user.id).await
+            && accepted == current.id
+        {
+            return Ok(None);
+        }
+        if self.app_state.db.tos.has_accepted_tos(user.id, current.id).await? {
+            let _ = self.app_state.redis_client.set_tos_accepted(&user.id, &current.id).await;
+            return Ok(None);
+        }
+        Ok(Some(current))
+    }
+    /// Only the current version can be accepted, `TosVersionNotCurrent` names it otherwise.
+    pub async fn accept(&self, user_id: Uuid, body: TosAcceptRequest) -> Result<TosAcceptance, AppError> {
+        let current = self.current().await?
+            .ok_or(AppError::not_found(ErrorMessage::DataNotFound))?;
+        if body.version != current.version {
+            return Err(AppError::unique_constraint_violation(ErrorMessage::TosVersionNotCurrent(current.version)));
+        }
+        let accepted_at = self.app_state.db.tos.accept_tos(user_id, current.id).await?;
+        let _ = self.app_state.redis_client.set_tos_accepted(&user_id, &current.id).await;
+        Ok(TosAcceptance { version: current.version, accepted_at })
+    }
+
+    pub async fn all(&self) -> Result<Vec<TosVersion>, AppError> {
+        Ok(self.app_state.db.tos.get_tos_versions().await?)
+    }
+    /// The version is current from now on, for every replica once the cached copy is dropped. The
+    /// admin publishing it accepts it with that.
+    pub async fn publish(&self, actor: &User, body: TosVersionRequest) -> Result<TosVersion, AppError> {
+        let new_version = NewTosVersion {
+            version: body.version,
+            url: body.url,
+            summary: body.summary,
+            published_by: actor.id,
+        };
+        let version = self.app_state.db.tos.save_tos_version(new_version).await?;
+        self.app_state.db.tos.accept_tos(actor.id, version.id).await?;
+        let _ = self.app_state.redis_client.delete_current_tos().await;
+        Ok(version)
+    }
+}
\ No newline at end of file
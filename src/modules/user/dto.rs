@@ -1,6 +1,8 @@
 use core::str;
-use chrono::{DateTime, NaiveDate, Utc};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 use sqlx::FromRow;
 use uuid::Uuid;
 use validator::{Validate, ValidationError};
@@ -9,26 +11,36 @@ use crate::{
         user::model::{User},
         role::model::RoleType,
         comment::model::Comment,
+        post::model::PostLicense,
     },
-    dto::{default_limit, default_page, default_order_by},
+    config::Config,
+    utils::avatar::AVATAR_SIZES,
+    dto::{default_limit, default_order_by, nullable, require_any_field, validate_fields, Paginated, Pagination},
 };
 
-#[derive(Serialize, FromRow)]
+#[derive(Serialize, Deserialize, FromRow, ToSchema, Clone)]
 pub struct UserResponse {
     pub id: Uuid,
     pub name: String,
     pub email: String,
     pub role: RoleType,
-    #[serde(skip_serializing)]
+    #[serde(skip)]
     pub password: String,
     pub is_verified: bool,
+    pub is_banned: bool,
+    pub is_private: bool,
+    pub username: Option<String>,
+    pub bio: Option<String>,
+    pub avatar_url: Option<String>,
+    pub website: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
-#[derive(Serialize, FromRow)]
+#[derive(Serialize, Deserialize, FromRow, ToSchema)]
 pub struct UserFeeds {
     pub id: Uuid,
     pub user_id: Uuid,
+    pub organization_id: Option<Uuid>,
     pub title: String,
     pub content: String,
     pub tags: Vec<String>,
@@ -42,6 +54,7 @@ pub struct UserFeeds {
 pub struct UserFeedRow {
     pub id: Uuid,
     pub user_id: Uuid,
+    pub organization_id: Option<Uuid>,
     pub title: String,
     pub content: String,
     pub tags: Vec<String>,
@@ -60,6 +73,12 @@ impl UserResponse {
             role,
             password: user.password.to_owned(),
             is_verified: user.is_verified,
+            is_banned: user.is_banned,
+            is_private: user.is_private,
+            username: user.username.to_owned(),
+            bio: user.bio.to_owned(),
+            avatar_url: user.avatar_url.to_owned(),
+            website: user.website.to_owned(),
             created_at: user.created_at,
             updated_at: user.updated_at,
         }
@@ -69,7 +88,7 @@ impl UserResponse {
     // }
 }
 
-#[derive(Deserialize, Validate)]
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
 pub struct UserUpdateRequest {
     #[validate(length(
         min = 4,
@@ -77,14 +96,70 @@ pub struct UserUpdateRequest {
         message = "Name must be between 4 and 20 characters"
     ))]
     pub name: String,
+    pub is_private: Option<bool>,
+    pub default_license: Option<PostLicense>,
+    /// The profile fields below are left alone when missing and cleared with an empty string.
+    #[validate(custom(function = "validate_username"))]
+    pub username: Option<String>,
+    #[validate(length(max = 160, message = "Bio must be at most 160 characters"))]
+    pub bio: Option<String>,
+    #[validate(length(max = 500, message = "Avatar url must be at most 500 characters"))]
+    #[validate(custom(function = "validate_profile_url"))]
+    pub avatar_url: Option<String>,
+    #[validate(length(max = 500, message = "Website must be at most 500 characters"))]
+    #[validate(custom(function = "validate_profile_url"))]
+    pub website: Option<String>,
 }
 
-#[derive(Deserialize, Validate)]
-pub struct UserListRequest {
-    #[validate(range(min = 1))]
-    pub page: Option<usize>,
-    #[validate(range(min = 1, max = 50))]
-    pub limit: Option<usize>,
+/// Body of `PATCH /api/user/{id}`, a JSON merge patch: missing fields are left alone and `null` (or
+/// an empty string) clears a profile field. `PUT` bodies are turned into one of these as well.
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
+#[validate(schema(function = "validate_user_patch", skip_on_field_errors = true))]
+pub struct UserPatchRequest {
+    #[validate(length(
+        min = 4,
+        max = 20,
+        message = "Name must be between 4 and 20 characters"
+    ))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_private: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_license: Option<PostLicense>,
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    #[validate(custom(function = "validate_username"))]
+    pub username: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    #[validate(length(max = 160, message = "Bio must be at most 160 characters"))]
+    pub bio: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    #[validate(length(max = 500, message = "Avatar url must be at most 500 characters"))]
+    #[validate(custom(function = "validate_profile_url"))]
+    pub avatar_url: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    #[validate(length(max = 500, message = "Website must be at most 500 characters"))]
+    #[validate(custom(function = "validate_profile_url"))]
+    pub website: Option<Option<String>>,
+}
+fn validate_user_patch(patch: &UserPatchRequest) -> Result<(), ValidationError> {
+    require_any_field(
+        patch.name.is_some() || patch.is_private.is_some() || patch.default_license.is_some()
+            || patch.username.is_some() || patch.bio.is_some() || patch.avatar_url.is_some() || patch.website.is_some()
+    )
+}
+impl From<UserUpdateRequest> for UserPatchRequest {
+    fn from(body: UserUpdateRequest) -> Self {
+        Self {
+            name: Some(body.name),
+            is_private: body.is_private,
+            default_license: body.default_license,
+            username: body.username.map(Some),
+            bio: body.bio.map(Some),
+            avatar_url: body.avatar_url.map(Some),
+            website: body.website.map(Some),
+        }
+    }
 }
 
 #[derive(Deserialize, Validate)]
@@ -104,7 +179,7 @@ pub struct UserPasswordUpdateRequest {
     pub new_password_confirm: String,
 }
 
-fn validate_order_by(value: &str) -> Result<(), ValidationError> {
+pub fn validate_order_by(value: &str) -> Result<(), ValidationError> {
     match value {
         "ASC" | "DESC" => Ok(()),
         _ => {
@@ -114,6 +189,59 @@ fn validate_order_by(value: &str) -> Result<(), ValidationError> {
         }
     }
 }
+pub fn validate_avatar_size(value: u32) -> Result<(), ValidationError> {
+    if AVATAR_SIZES.contains(&value) {
+        return Ok(());
+    }
+    let mut error = ValidationError::new("invalid_avatar_size");
+    error.message = Some(format!("Size must be one of {:?}.", AVATAR_SIZES).into());
+    Err(error)
+}
+pub fn validate_timezone(value: &str) -> Result<(), ValidationError> {
+    match value.parse::<Tz>() {
+        Ok(_) => Ok(()),
+        Err(_) => {
+            let mut error = ValidationError::new("invalid_timezone");
+            error.message = Some("Timezone must be an IANA time zone such as 'Europe/Berlin'.".into());
+            Err(error)
+        }
+    }
+}
+/// Handles are 3 to 30 lowercase letters, digits or underscores, so they are unique without
+/// case folding and safe to put in a url.
+pub fn validate_username(value: &str) -> Result<(), ValidationError> {
+    let is_handle = (3..=30).contains(&value.len())
+        && value.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if value.is_empty() || is_handle {
+        return Ok(());
+    }
+    let mut error = ValidationError::new("invalid_username");
+    error.message = Some("Username must be 3 to 30 lowercase letters, digits or underscores".into());
+    Err(error)
+}
+fn validate_profile_url(value: &str) -> Result<(), ValidationError> {
+    if value.is_empty() {
+        return Ok(());
+    }
+    match reqwest::Url::parse(value) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.host().is_some() => Ok(()),
+        _ => {
+            let mut error = ValidationError::new("invalid_url");
+            error.message = Some("Url must be an absolute http or https url".into());
+            Err(error)
+        }
+    }
+}
+fn validate_feed_sort(value: &str) -> Result<(), ValidationError> {
+    match value {
+        "recent" | "top" | "trending" => Ok(()),
+        _ => {
+            let mut error = ValidationError::new("invalid_sort");
+            error.message = Some("Sort must be either 'recent', 'top' or 'trending'".into());
+            Err(error)
+        }
+    }
+}
 pub fn validate_optional_date(value: &str) -> Result<(), ValidationError> {
     if NaiveDate::parse_from_str(value, "%Y-%m-%d").is_err() {
         let mut err = ValidationError::new("invalid_date_format");
@@ -123,14 +251,16 @@ pub fn validate_optional_date(value: &str) -> Result<(), ValidationError> {
     Ok(())
 }
 
+/// What `fields=` may pick from a user list item.
+pub const USER_LIST_FIELDS: &[&str] = &["id", "name", "email", "role", "is_verified", "is_banned", "is_private", "username", "bio", "avatar_url", "website", "created_at", "updated_at"];
+fn validate_user_list_fields(value: &str) -> Result<(), ValidationError> {
+    validate_fields(value, USER_LIST_FIELDS)
+}
 
 #[derive(Deserialize, Validate)]
 pub struct UserListParams {
-    #[serde(default = "default_limit")]
-    #[validate(range(min = 1, message = "Limit is minimum 1."))]
     pub limit: Option<usize>,
-    #[serde(default = "default_page")]
-    #[validate(range(min = 1, message = "Page is minimum 1."))]
+    #[validate(range(min = 1, max = 100000, message = "Page must be between 1 and 100000."))]
     pub page: Option<usize>,
     #[serde(default = "default_order_by")]
     #[validate(custom(function = "validate_order_by"))]
@@ -138,14 +268,21 @@ pub struct UserListParams {
     #[validate(length(min = 1, message = "Search must be at least 1 character."))]
     pub search: Option<String>,
     pub is_verified: Option<bool>,
+    #[validate(custom(function = "validate_user_list_fields"))]
+    pub fields: Option<String>,
+    #[serde(skip)]
+    pub pagination: Pagination,
 }
-#[derive(Deserialize, Validate)]
+impl Paginated for UserListParams {
+    fn paginate(&mut self, config: &Config) {
+        self.pagination = Pagination::sanitize(self.page, self.limit, config);
+    }
+}
+#[derive(Serialize, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct UserFeedParams {
-    #[serde(default = "default_limit")]
-    #[validate(range(min = 1, message = "Limit is minimum 1."))]
     pub limit: Option<usize>,
-    #[serde(default = "default_page")]
-    #[validate(range(min = 1, message = "Page is minimum 1."))]
+    #[validate(range(min = 1, max = 100000, message = "Page must be between 1 and 100000."))]
     pub page: Option<usize>,
     #[serde(default = "default_order_by")]
     #[validate(custom(function = "validate_order_by"))]
@@ -156,9 +293,100 @@ pub struct UserFeedParams {
     pub since: Option<String>,
     #[validate(custom(function = "validate_optional_date"))]
     pub until: Option<String>,
+    /// `recent` orders by creation time, `top` by comment count, `trending` by comment count
+    /// decayed by the post's age. `order_by` picks the direction of the chosen key.
+    #[validate(custom(function = "validate_feed_sort"))]
+    pub sort: Option<String>,
+    /// Also include the posts of organizations the user belongs to or follows.
+    pub organizations: Option<bool>,
+    #[serde(skip)]
+    pub pagination: Pagination,
+}
+impl Paginated for UserFeedParams {
+    fn paginate(&mut self, config: &Config) {
+        self.pagination = Pagination::sanitize(self.page, self.limit, config);
+    }
+}
+
+#[derive(Serialize, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UserConnectionParams {
+    pub limit: Option<usize>,
+    #[validate(range(min = 1, max = 100000, message = "Page must be between 1 and 100000."))]
+    pub page: Option<usize>,
+    /// Direction by the time the follow started.
+    #[serde(default = "default_order_by")]
+    #[validate(custom(function = "validate_order_by"))]
+    pub order_by: Option<String>,
+    #[serde(skip)]
+    pub pagination: Pagination,
+}
+impl Paginated for UserConnectionParams {
+    fn paginate(&mut self, config: &Config) {
+        self.pagination = Pagination::sanitize(self.page, self.limit, config);
+    }
+}
+
+#[derive(Serialize, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UserAvatarParams {
+    /// Width and height in pixels, defaults to 128.
+    #[serde(default = "default_avatar_size")]
+    #[validate(custom(function = "validate_avatar_size"))]
+    pub size: u32,
+}
+fn default_avatar_size() -> u32 {
+    128
+}
+/// Matches `GET /api/user/search` returns without a `limit`, and the most it returns with one.
+const DEFAULT_SEARCH_LIMIT: i64 = 10;
+const MAX_SEARCH_LIMIT: i64 = 20;
+
+fn validate_search_query(value: &str) -> Result<(), ValidationError> {
+    if !value.trim().trim_start_matches('@').is_empty() {
+        return Ok(());
+    }
+    let mut error = ValidationError::new("invalid_query");
+    error.message = Some("Query must contain more than spaces and @".into());
+    Err(error)
+}
+
+#[derive(Serialize, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UserSearchParams {
+    /// Start of a username, with or without `@`, or of a word of the name.
+    #[validate(length(min = 1, max = 50, message = "Query must be between 1 and 50 characters"))]
+    #[validate(custom(function = "validate_search_query"))]
+    pub q: String,
+    /// At most 20, larger values are clamped.
+    pub limit: Option<i64>,
+}
+impl UserSearchParams {
+    /// `q` as the repository matches it.
+    pub fn query(&self) -> String {
+        self.q.trim().trim_start_matches('@').to_lowercase()
+    }
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_SEARCH_LIMIT).clamp(1, MAX_SEARCH_LIMIT)
+    }
+}
+#[derive(Serialize, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UserSuggestionParams {
+    #[validate(range(min = 1, max = 50, message = "Limit must be between 1 and 50."))]
+    pub limit: Option<usize>,
+    #[validate(range(min = 1, max = 100, message = "Page must be between 1 and 100."))]
+    pub page: Option<usize>,
+    #[serde(skip)]
+    pub pagination: Pagination,
+}
+impl Paginated for UserSuggestionParams {
+    fn paginate(&mut self, config: &Config) {
+        self.pagination = Pagination::sanitize(self.page, self.limit, config);
+    }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct FollowUnfollowResponse {
     pub user_target: Uuid,
     pub user_sender: Uuid,
@@ -170,6 +398,7 @@ pub enum FollowKind {
     Followers,
 }
 impl FollowKind {
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(str: &str) -> Option<Self> {
         match str {
             "following" => Some(FollowKind::Following),
@@ -177,4 +406,110 @@ impl FollowKind {
             _ => None,
         }
     }
+}
+#[derive(Serialize, Deserialize, Clone, Copy, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    Light,
+    Dark,
+    System,
+}
+
+/// How often the feed digest is emailed.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestFrequency {
+    Off,
+    Daily,
+    Weekly,
+}
+
+impl DigestFrequency {
+    /// The time a digest covers, `None` when it is off.
+    pub fn period(&self) -> Option<Duration> {
+        match self {
+            DigestFrequency::Off => None,
+            DigestFrequency::Daily => Some(Duration::days(1)),
+            DigestFrequency::Weekly => Some(Duration::weeks(1)),
+        }
+    }
+}
+
+/// Preferences a frontend may persist server-side, stored as JSONB in `user_settings`.
+/// Unknown keys are rejected, so every new preference is added here first; retiring one needs a
+/// migration that strips the key from the stored documents. Only the keys a user set are stored,
+/// `with_defaults` fills in the rest when they are read.
+#[derive(Serialize, Deserialize, Validate, Default, Clone, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct UserSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub theme: Option<Theme>,
+    /// Also accepted as `locale`.
+    #[serde(skip_serializing_if = "Option::is_none", alias = "locale")]
+    #[validate(length(min = 2, max = 10, message = "Language must be between 2 and 10 characters"))]
+    pub language: Option<String>,
+    /// An IANA time zone such as `Europe/Berlin`, used for times in emails.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(custom(function = "validate_timezone"))]
+    pub timezone: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = 1, max = 50, message = "Feed limit must be between 1 and 50."))]
+    pub feed_limit: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(custom(function = "validate_order_by"))]
+    pub feed_order_by: Option<String>,
+    /// Every email that isn't needed to use the account, sent unless this is `false`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_notifications: Option<bool>,
+    /// Emails about sign-ins from a new device, sent unless this or `email_notifications` is `false`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub login_alerts: Option<bool>,
+    /// A digest of new posts by followed accounts and pending requests, only sent when this is
+    /// `daily` or `weekly` and `email_notifications` isn't `false`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_digest: Option<DigestFrequency>,
+    /// Anonymized usage events from the client, kept unless this is `false`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub analytics: Option<bool>,
+}
+
+/// Emails the settings can turn off. Verification, password reset and invitation emails aren't
+/// listed, the account can't be used without them.
+#[derive(Clone, Copy)]
+pub enum EmailKind {
+    Welcome,
+    Waitlist,
+    SignInAlert,
+    Digest,
+}
+
+impl UserSettings {
+    /// The settings as they apply, with every key the user left out set to its default.
+    pub fn with_defaults(self) -> Self {
+        Self {
+            theme: self.theme.or(Some(Theme::System)),
+            language: self.language.or(Some("en".to_string())),
+            timezone: self.timezone.or(Some("UTC".to_string())),
+            feed_limit: self.feed_limit.or(default_limit()),
+            feed_order_by: self.feed_order_by.or(default_order_by()),
+            email_notifications: self.email_notifications.or(Some(true)),
+            login_alerts: self.login_alerts.or(Some(true)),
+            email_digest: self.email_digest.or(Some(DigestFrequency::Off)),
+            analytics: self.analytics.or(Some(true)),
+        }
+    }
+    pub fn allows_email(&self, kind: EmailKind) -> bool {
+        if self.email_notifications == Some(false) {
+            return false;
+        }
+        match kind {
+            EmailKind::Welcome | EmailKind::Waitlist => true,
+            EmailKind::SignInAlert => self.login_alerts != Some(false),
+            EmailKind::Digest => self.email_digest.is_some_and(|frequency| frequency != DigestFrequency::Off),
+        }
+    }
+    /// The time zone to show times in, UTC when none was set.
+    pub fn time_zone(&self) -> Tz {
+        self.timezone.as_deref().and_then(|timezone| timezone.parse().ok()).unwrap_or(Tz::UTC)
+    }
 }
\ No newline at end of file
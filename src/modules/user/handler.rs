@@ -1,23 +1,24 @@
 use std::sync::Arc;
 use axum::{
-    routing::{get, post, put, delete},
-    extract::Request, Router, response::{IntoResponse}, Extension, middleware
+    routing::{get, patch, post, put, delete},
+    extract::Request, Router, response::{IntoResponse, Response}, Extension, middleware,
+    http::{HeaderMap, StatusCode, header::{CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_NONE_MATCH}},
 };
 use uuid::Uuid;
-use validator::Validate;
 use crate::{
     AppState,
-    dto::{HttpResult, SuccessResponse},
+    dto::{select_fields, BatchData, BatchRequest, HttpResult, SuccessResponse, PaginatedData},
     middleware::{
         AuthenticatedUser,
         permission::{check_permission, Permission}
     },
     modules::{
-        user::{dto::{UserListParams, UserFeedParams, FollowUnfollowResponse, UserResponse, UserUpdateRequest, UserPasswordUpdateRequest, FollowKind}, model::{UserRepository, User}},
-        role::model::RoleRepository,
+        user::{dto::{UserListParams, UserConnectionParams, UserFeedParams, UserSuggestionParams, UserSearchParams, UserAvatarParams, UserFeeds, FollowUnfollowResponse, UserResponse, UserUpdateRequest, UserPatchRequest, UserPasswordUpdateRequest, FollowKind, UserSettings}, model::{UserDetail, UserSearchResult, UserSuggestion, UserSummary}},
+        activity::{dto::UserActivityParams, model::ActivityItem},
+        aggregate::handler::user_leaderboard,
+        tos::handler::user_accept_tos,
     },
-    error::{map_sqlx_error, FieldError, ErrorPayload, QueryParser, HttpError, ErrorMessage, PathParser, BodyParser},
-    utils::password
+    error::{IfMatch, PaginatedQuery, PathParser, ValidatedJson, ValidatedQuery},
 };
 
 pub fn user_router() -> Router {
@@ -31,15 +32,30 @@ pub fn user_router() -> Router {
         .route("/{id}", get(user_detail).layer(middleware::from_fn(|state, req, next| {
             check_permission(state, req, next, Permission::UserDetail.to_string())
         })))
+        .route("/by-username/{username}", get(user_by_username).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::UserDetail.to_string())
+        })))
+        .route("/batch", post(user_batch).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::UserDetail.to_string())
+        })))
         .route("/{id}", put(user_update).layer(middleware::from_fn(|state, req, next| {
             check_permission(state, req, next, Permission::UserUpdate.to_string())
         })))
+        .route("/{id}", patch(user_patch).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::UserUpdate.to_string())
+        })))
         .route("/change-password", put(user_change_password).layer(middleware::from_fn(|state, req, next| {
             check_permission(state, req, next, Permission::UserChangePassword.to_string())
         })))
         .route("/{id}/follow", post(user_follow_unfollow).layer(middleware::from_fn(|state, req, next| {
             check_permission(state, req, next, Permission::UserFollow.to_string())
         })))
+        .route("/{id}/avatar", get(user_avatar).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::UserDetail.to_string())
+        })))
+        .route("/{id}/activity", get(user_activity).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::UserDetail.to_string())
+        })))
         .route("/{id}/followers", get(user_connections).layer(middleware::from_fn(|state, req, next| {
             check_permission(state, req, next, Permission::UserFollowers.to_string())
         })))
@@ -49,145 +65,417 @@ pub fn user_router() -> Router {
         .route("/{id}", delete(user_delete).layer(middleware::from_fn(|state, req, next| {
             check_permission(state, req, next, Permission::UserDelete.to_string())
         })))
+        .route("/leaderboard", get(user_leaderboard).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::UserLeaderboard.to_string())
+        })))
+        .route("/search", get(user_search).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::UserDetail.to_string())
+        })))
+        .route("/suggestions", get(user_suggestions).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::UserSuggestions.to_string())
+        })))
+        .route("/follow-requests", get(user_follow_requests).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::UserFollowRequestList.to_string())
+        })))
+        .route("/follow-requests/{id}/accept", post(user_follow_request_accept).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::UserFollowRequestRespond.to_string())
+        })))
+        .route("/follow-requests/{id}/reject", post(user_follow_request_reject).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::UserFollowRequestRespond.to_string())
+        })))
+        .route("/settings", get(user_settings).put(user_settings_update).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::UserSettings.to_string())
+        })))
         .route("/feed", get(user_feeds).layer(middleware::from_fn(|state, req, next| {
             check_permission(state, req, next, Permission::UserFeed.to_string())
         })))
+        .route("/accept-tos", post(user_accept_tos).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::UserAcceptTos.to_string())
+        })))
 }
 
-async fn user_by_id(user_id: &Uuid, app_state: Arc<AppState>) -> Result<Option<User>, HttpError<ErrorPayload>> {
-    let user = app_state.db_client
-        .get_user_by_id(user_id).await
-        .map_err(map_sqlx_error)?;
-    Ok(user)
-}
+#[utoipa::path(
+    get,
+    path = "/api/user/self",
+    tag = "user",
+    responses(
+        (status = 200, description = "Profile of the signed in user", body = SuccessResponse<UserResponse>),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn user_self(
     Extension(app_state): Extension<Arc<AppState>>,
     Extension(user_auth): Extension<AuthenticatedUser>
 ) -> HttpResult<impl IntoResponse> {
-    let role_type = app_state.db_client.get_role_name_by_id(user_auth.user.role_id).await
-        .map_err(map_sqlx_error)?
-        .ok_or(HttpError::server_error(ErrorMessage::ServerError.to_string(), None))?;
-    let user_response = UserResponse::get_user_response(&user_auth.user, role_type);
+    let user_response = app_state.user_service().profile(&user_auth.user).await?;
     Ok(
         SuccessResponse::new("Getting logged in user profile data.", Some(user_response))
     )
 }
+#[utoipa::path(
+    get,
+    path = "/api/user/settings",
+    tag = "user",
+    responses(
+        (status = 200, description = "Preferences of the signed in user, with defaults for the keys never saved", body = SuccessResponse<UserSettings>),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn user_settings(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>
+) -> HttpResult<impl IntoResponse> {
+    let settings = app_state.db.users.get_user_settings(user_auth.user.id).await?.with_defaults();
+    Ok(
+        SuccessResponse::new("Getting user settings.", Some(settings))
+    )
+}
+#[utoipa::path(
+    put,
+    path = "/api/user/settings",
+    tag = "user",
+    request_body = UserSettings,
+    responses(
+        (status = 200, description = "Preferences replaced with the request body, keys left out fall back to their defaults", body = SuccessResponse<UserSettings>),
+        (status = 400, description = "Unknown key or invalid value"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn user_settings_update(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    ValidatedJson(body): ValidatedJson<UserSettings>,
+) -> HttpResult<impl IntoResponse> {
+    let settings = app_state.db.users.save_user_settings(user_auth.user.id, body).await?.with_defaults();
+    Ok(
+        SuccessResponse::new("Successfully updating user settings.", Some(settings))
+    )
+}
 async fn user_list(
     Extension(app_state): Extension<Arc<AppState>>,
-    QueryParser(query_params): QueryParser<UserListParams>
+    PaginatedQuery(query_params): PaginatedQuery<UserListParams>
 ) -> HttpResult<impl IntoResponse> {
-    query_params.validate().map_err(FieldError::populate_errors)?;
-    let result = app_state.db_client.get_users(query_params).await
-        .map_err(map_sqlx_error)?;
+    let fields = query_params.fields.clone();
+    let result = app_state.db.users.get_users(query_params).await?;
+    let result = PaginatedData {
+        items: select_fields(result.items, fields.as_deref()),
+        pagination: result.pagination,
+    };
     let response = SuccessResponse::new("Getting user list data", Some(result));
     Ok(response)
 }
+#[utoipa::path(
+    get,
+    path = "/api/user/{id}",
+    tag = "user",
+    params(("id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "User with follower and following counts", body = SuccessResponse<UserDetail>),
+        (status = 404, description = "User not found"),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn user_detail(
     Extension(app_state): Extension<Arc<AppState>>,
     PathParser(user_id): PathParser<Uuid>,
 ) -> HttpResult<impl IntoResponse> {
-    let user_detail = app_state.db_client.get_user_detail(&user_id).await
-        .map_err(map_sqlx_error)?
-        .ok_or(HttpError::not_found(ErrorMessage::DataNotFound.to_string(), None))?;
+    let user_detail = app_state.user_service().detail(user_id).await?;
+    Ok(
+        SuccessResponse::new("Getting user detail data", Some(user_detail))
+    )
+}
+#[utoipa::path(
+    get,
+    path = "/api/user/by-username/{username}",
+    tag = "user",
+    params(("username" = String, Path)),
+    responses(
+        (status = 200, description = "User with follower and following counts", body = SuccessResponse<UserDetail>),
+        (status = 404, description = "No user has this username"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn user_by_username(
+    Extension(app_state): Extension<Arc<AppState>>,
+    PathParser(username): PathParser<String>,
+) -> HttpResult<impl IntoResponse> {
+    let user_detail = app_state.user_service().detail_by_username(&username).await?;
     Ok(
         SuccessResponse::new("Getting user detail data", Some(user_detail))
     )
 }
+#[utoipa::path(
+    post,
+    path = "/api/user/batch",
+    tag = "user",
+    request_body = BatchRequest,
+    responses(
+        (status = 200, description = "Users in the requested order, unknown ids listed as missing", body = SuccessResponse<BatchData<UserSummary>>),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn user_batch(
+    Extension(app_state): Extension<Arc<AppState>>,
+    ValidatedJson(body): ValidatedJson<BatchRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let result = app_state.user_service().batch(&body).await?;
+    Ok(
+        SuccessResponse::new("Getting user data by ids", Some(result))
+    )
+}
+#[utoipa::path(
+    put,
+    path = "/api/user/{id}",
+    tag = "user",
+    params(("id" = Uuid, Path), ("If-Match" = Option<String>, Header, description = "`updated_at` of the edited version")),
+    request_body = UserUpdateRequest,
+    responses(
+        (status = 200, description = "Updated user", body = SuccessResponse<UserResponse>),
+        (status = 403, description = "Not the owner of the account"),
+        (status = 409, description = "The user changed since the If-Match version, the current one is attached"),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn user_update(
     Extension(app_state): Extension<Arc<AppState>>,
     Extension(user_auth): Extension<AuthenticatedUser>,
     PathParser(user_id): PathParser<Uuid>,
-    BodyParser(body): BodyParser<UserUpdateRequest>,
+    IfMatch(expected_version): IfMatch,
+    ValidatedJson(body): ValidatedJson<UserUpdateRequest>,
 ) -> HttpResult<impl IntoResponse> {
-    body.validate().map_err(FieldError::populate_errors)?;
-    let updated_user = app_state.db_client.update_user(&user_id, &user_auth.user.id, body).await
-        .map_err(map_sqlx_error)?;
-    let _ = app_state.redis_client.set_user(&updated_user, app_state.env.jwt_max_age as u64).await;
-    Ok(
-        SuccessResponse::new("Successfully updating user data.", Some(updated_user))
-    )
+    let updated_user = app_state.user_service().update(user_id, &user_auth.user, body.into(), expected_version).await?;
+    Ok((
+        [(ETAG, IfMatch::etag(updated_user.updated_at))],
+        SuccessResponse::new("Successfully updating user data.", Some(updated_user)),
+    ))
+}
+#[utoipa::path(
+    patch,
+    path = "/api/user/{id}",
+    tag = "user",
+    params(("id" = Uuid, Path), ("If-Match" = Option<String>, Header, description = "`updated_at` of the edited version")),
+    request_body = UserPatchRequest,
+    responses(
+        (status = 200, description = "Updated user, fields missing from the body are unchanged", body = SuccessResponse<UserResponse>),
+        (status = 400, description = "Invalid field or empty patch"),
+        (status = 403, description = "Not the owner of the account"),
+        (status = 409, description = "The user changed since the If-Match version, the current one is attached"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn user_patch(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(user_id): PathParser<Uuid>,
+    IfMatch(expected_version): IfMatch,
+    ValidatedJson(body): ValidatedJson<UserPatchRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let updated_user = app_state.user_service().update(user_id, &user_auth.user, body, expected_version).await?;
+    Ok((
+        [(ETAG, IfMatch::etag(updated_user.updated_at))],
+        SuccessResponse::new("Successfully updating user data.", Some(updated_user)),
+    ))
 }
 async fn user_change_password(
     Extension(app_state): Extension<Arc<AppState>>,
     Extension(user_auth): Extension<AuthenticatedUser>,
-    BodyParser(body): BodyParser<UserPasswordUpdateRequest>,
+    ValidatedJson(body): ValidatedJson<UserPasswordUpdateRequest>,
 ) -> HttpResult<impl IntoResponse> {
-    body.validate().map_err(FieldError::populate_errors)?;
-    let password_match = password::compare(&body.old_password, &user_auth.user.password)
-        .map_err(|_| HttpError::server_error(ErrorMessage::ServerError.to_string(), None))?;
-    if !password_match {
-        return Err(HttpError::bad_request(ErrorMessage::WrongCredentials.to_string(), None));
-    }
-    let hash_password = password::hash(&body.new_password)
-        .map_err(|_| HttpError::server_error(ErrorMessage::ServerError.to_string(), None))?;
-    let updated_user_password = app_state.db_client.update_user_password(&user_auth.user.id, hash_password).await
-        .map_err(map_sqlx_error)?;
-    let _ = app_state.redis_client.set_user(&updated_user_password, app_state.env.jwt_max_age as u64).await;
+    app_state.user_service().change_password(&user_auth.user, body).await?;
     Ok(
         SuccessResponse::<()>::new("Password updated successfully.", None)
     )
 }
+#[utoipa::path(
+    post,
+    path = "/api/user/{id}/follow",
+    tag = "user",
+    params(("id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Followed, unfollowed or follow request toggled", body = SuccessResponse<FollowUnfollowResponse>),
+        (status = 404, description = "User not found"),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn user_follow_unfollow(
     Extension(app_state): Extension<Arc<AppState>>,
     Extension(user_auth): Extension<AuthenticatedUser>,
     PathParser(user_id): PathParser<Uuid>,
 ) -> HttpResult<impl IntoResponse> {
-    let sender_id = user_auth.user.id;
-    if user_id == sender_id {
-        return Err(HttpError::bad_request(ErrorMessage::RequestInvalid.to_string(), None));
-    }
-    user_by_id(&user_id, app_state.clone()).await?
-        .ok_or(HttpError::not_found(ErrorMessage::DataNotFound.to_string(), None))?;
-    let message = app_state.db_client.follow_unfollow_user(user_id, sender_id).await
-        .map_err(map_sqlx_error)?;
-    let response = FollowUnfollowResponse {
-        user_target: user_id,
-        user_sender: sender_id,
-        message,
-    };
+    let response = app_state.user_service().follow_unfollow(user_id, &user_auth.user).await?;
     Ok(
         SuccessResponse::new("Successfully follow / unfollow a new user.", Some(response))
     )
 }
 async fn user_connections(
     Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
     PathParser(user_id): PathParser<Uuid>,
+    PaginatedQuery(query_params): PaginatedQuery<UserConnectionParams>,
     req: Request,
 ) -> HttpResult<impl IntoResponse> {
     let path = req.uri().path().rsplit('/').next().unwrap_or("");
     let kind = FollowKind::from_str(path).unwrap_or(FollowKind::Following);
-    user_by_id(&user_id, app_state.clone()).await?
-        .ok_or(HttpError::not_found(ErrorMessage::DataNotFound.to_string(), None))?;
-    let result = app_state.db_client.get_user_connections(user_id, &kind).await
-        .map_err(map_sqlx_error)?;
+    let result = app_state.user_service().connections(user_id, &user_auth.user, &kind, query_params).await?;
     match kind {
         FollowKind::Following => Ok(SuccessResponse::new("List of user's following.", Some(result))),
         FollowKind::Followers => Ok(SuccessResponse::new("List of user's followers.", Some(result)))
     }
 }
-async fn user_delete(
+#[utoipa::path(
+    get,
+    path = "/api/user/{id}/activity",
+    tag = "user",
+    params(("id" = Uuid, Path), UserActivityParams),
+    responses(
+        (status = 200, description = "The user's posts, comments and follows, newest first", body = SuccessResponse<PaginatedData<ActivityItem>>),
+        (status = 403, description = "Account is private"),
+        (status = 404, description = "User not found"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn user_activity(
     Extension(app_state): Extension<Arc<AppState>>,
     Extension(user_auth): Extension<AuthenticatedUser>,
     PathParser(user_id): PathParser<Uuid>,
+    PaginatedQuery(query_params): PaginatedQuery<UserActivityParams>,
 ) -> HttpResult<impl IntoResponse> {
-    let sender_id = user_auth.user.id;
-    if user_id == sender_id {
-        return Err(HttpError::bad_request(ErrorMessage::RequestInvalid.to_string(), None));
+    let result = app_state.user_service().activity(user_id, &user_auth.user, query_params).await?;
+    Ok(
+        SuccessResponse::new("Getting the user's activity", Some(result))
+    )
+}
+#[utoipa::path(
+    get,
+    path = "/api/user/{id}/avatar",
+    tag = "user",
+    params(("id" = Uuid, Path), UserAvatarParams),
+    responses(
+        (status = 200, description = "The avatar as a square PNG", content_type = "image/png"),
+        (status = 304, description = "The `If-None-Match` version is still current"),
+        (status = 404, description = "User not found or has no avatar"),
+        (status = 502, description = "The avatar could not be fetched or decoded"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn user_avatar(
+    Extension(app_state): Extension<Arc<AppState>>,
+    PathParser(user_id): PathParser<Uuid>,
+    ValidatedQuery(query_params): ValidatedQuery<UserAvatarParams>,
+    headers: HeaderMap,
+) -> HttpResult<Response> {
+    let user_service = app_state.user_service();
+    let (avatar_url, version) = user_service.avatar_version(user_id, query_params.size).await?;
+    let etag = format!("\"{}\"", version);
+    let cache_control = format!("private, max-age={}", app_state.env.avatar_cache_ttl);
+    let fresh = headers.get_all(IF_NONE_MATCH).iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|tag| tag.trim() == etag || tag.trim() == "*");
+    if fresh {
+        return Ok((StatusCode::NOT_MODIFIED, [(ETAG, etag), (CACHE_CONTROL, cache_control)]).into_response());
     }
-    app_state.db_client.delete_user(user_id).await
-        .map_err(map_sqlx_error)?;
+    let image = user_service.render_avatar(user_id, &avatar_url, &version, query_params.size).await?;
+    Ok((
+        [(CONTENT_TYPE, "image/png".to_string()), (ETAG, etag), (CACHE_CONTROL, cache_control)],
+        image,
+    ).into_response())
+}
+#[utoipa::path(
+    get,
+    path = "/api/user/search",
+    tag = "user",
+    params(UserSearchParams),
+    responses(
+        (status = 200, description = "Users whose username or name starts with the query, best match first", body = SuccessResponse<Vec<UserSearchResult>>),
+        (status = 400, description = "Missing or empty query"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn user_search(
+    Extension(app_state): Extension<Arc<AppState>>,
+    ValidatedQuery(query_params): ValidatedQuery<UserSearchParams>,
+) -> HttpResult<impl IntoResponse> {
+    let users = app_state.user_service().search(&query_params).await?;
+    Ok(
+        SuccessResponse::new("Getting user search results", Some(users))
+    )
+}
+#[utoipa::path(
+    get,
+    path = "/api/user/suggestions",
+    tag = "user",
+    params(UserSuggestionParams),
+    responses(
+        (status = 200, description = "Accounts to follow, ranked by mutual connections and shared tags", body = SuccessResponse<PaginatedData<UserSuggestion>>),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn user_suggestions(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PaginatedQuery(query_params): PaginatedQuery<UserSuggestionParams>,
+) -> HttpResult<impl IntoResponse> {
+    let result = app_state.user_service().suggestions(&user_auth.user, query_params).await?;
+    Ok(
+        SuccessResponse::new("Getting follow suggestions", Some(result))
+    )
+}
+async fn user_follow_requests(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+) -> HttpResult<impl IntoResponse> {
+    let result = app_state.db.users.get_follow_requests(user_auth.user.id).await?;
+    Ok(
+        SuccessResponse::new("List of pending follow requests.", Some(result))
+    )
+}
+async fn user_follow_request_accept(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(requester_id): PathParser<Uuid>,
+) -> HttpResult<impl IntoResponse> {
+    app_state.user_service().accept_follow_request(requester_id, &user_auth.user).await?;
+    Ok(
+        SuccessResponse::<()>::new("Successfully accepted a follow request.", None)
+    )
+}
+async fn user_follow_request_reject(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(requester_id): PathParser<Uuid>,
+) -> HttpResult<impl IntoResponse> {
+    app_state.db.users.reject_follow_request(user_auth.user.id, requester_id).await?;
+    Ok(
+        SuccessResponse::<()>::new("Successfully rejected a follow request.", None)
+    )
+}
+async fn user_delete(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(user_id): PathParser<Uuid>,
+) -> HttpResult<impl IntoResponse> {
+    app_state.user_service().delete(user_id, &user_auth.user).await?;
     Ok(
         SuccessResponse::<()>::new("Successfully deleted a user.", None)
     )
 }
+#[utoipa::path(
+    get,
+    path = "/api/user/feed",
+    tag = "user",
+    params(UserFeedParams),
+    responses(
+        (status = 200, description = "Posts from the user and the accounts they follow", body = SuccessResponse<PaginatedData<UserFeeds>>),
+    ),
+    security(("bearer_auth" = [])),
+)]
 async fn user_feeds(
     Extension(app_state): Extension<Arc<AppState>>,
     Extension(user_auth): Extension<AuthenticatedUser>,
-    QueryParser(query_params): QueryParser<UserFeedParams>
+    PaginatedQuery(query_params): PaginatedQuery<UserFeedParams>
 ) -> HttpResult<impl IntoResponse> {
-    query_params.validate().map_err(FieldError::populate_errors)?;
-    let result = app_state.db_client.get_user_feeds(user_auth.user.id, query_params).await
-        .map_err(map_sqlx_error)?;
+    let result = app_state.user_service().feed(&user_auth.user, query_params).await?;
     let response = SuccessResponse::new("Getting user feeds data", Some(result));
     Ok(response)
 }
\ No newline at end of file
@@ -1,3 +1,6 @@
 pub mod dto;
 pub mod model;
-pub mod handler;
\ No newline at end of file
+pub mod handler;
+pub mod service;
+#[cfg(test)]
+mod tests;
\ No newline at end of file
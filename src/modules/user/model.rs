@@ -1,22 +1,26 @@
 use std::collections::HashMap;
+use async_stream::try_stream;
 use async_trait::async_trait;
 use chrono::prelude::*;
+use futures_util::TryStreamExt;
 use serde::{Deserialize, Serialize};
-use sqlx::{query, query_as, query_scalar, Error as SqlxError, FromRow, Postgres, QueryBuilder};
+use utoipa::ToSchema;
+use sqlx::{query, query_as, query_scalar, types::Json, FromRow, PgConnection, Postgres, QueryBuilder};
 use uuid::Uuid;
 use crate::{
-    db::DBClient, 
+    db::{like_prefix, Counted, DBClient, RowStream},
     modules::{
         role::model::{RoleType, RoleRepository},
         user_action_token::model::NewUserActionToken,
-        user::dto::{UserResponse, UserListParams, UserUpdateRequest, FollowKind, UserFeedParams, UserFeeds, UserFeedRow},
-        comment::model::Comment
+        user::dto::{UserResponse, UserListParams, UserPatchRequest, FollowKind, UserConnectionParams, UserFeedParams, UserSuggestionParams, UserFeeds, UserFeedRow, UserSettings},
+        comment::model::Comment,
+        post::model::PostLicense,
     },
     dto::{PaginatedData, PaginationMeta},
-    error::{ErrorMessage}
+    error::RepositoryError
 };
 
-#[derive(Serialize, Deserialize, FromRow, Clone)]
+#[derive(Serialize, Deserialize, FromRow, Clone, ToSchema)]
 pub struct User {
     pub id: Uuid,
     pub role_id: Uuid,
@@ -24,23 +28,72 @@ pub struct User {
     pub email: String,
     pub password: String,
     pub is_verified: bool,
+    pub is_banned: bool,
+    pub is_private: bool,
+    pub username: Option<String>,
+    pub bio: Option<String>,
+    pub avatar_url: Option<String>,
+    pub website: Option<String>,
+    pub default_license: PostLicense,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct UserDetail {
     pub id: Uuid,
     pub name: String,
     pub email: String,
     pub role: RoleType,
     pub is_verified: bool,
+    pub is_private: bool,
+    pub username: Option<String>,
+    pub bio: Option<String>,
+    pub avatar_url: Option<String>,
+    pub website: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
-    pub following: Vec<Connections>,
-    pub followers: Vec<Connections>,
+    /// Kept on the `users` row by every follow change, the lists themselves are paginated
+    /// under `/api/user/{id}/followers` and `/api/user/{id}/following`.
+    pub followers_count: i64,
+    pub following_count: i64,
 }
+/// A profile without its connections, as returned by the batch endpoint.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct UserSummary {
+    pub id: Uuid,
+    pub name: String,
+    pub email: String,
+    pub role: RoleType,
+    pub is_verified: bool,
+    pub is_private: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+/// A match of `GET /api/user/search`, just enough to render a typeahead entry.
+#[derive(Serialize, Deserialize, FromRow, ToSchema)]
+pub struct UserSearchResult {
+    pub id: Uuid,
+    pub name: String,
+    pub username: Option<String>,
+    pub avatar_url: Option<String>,
+    pub is_verified: bool,
+    pub is_private: bool,
+}
+/// One line of the admin CSV export and the NDJSON stream, the password hash is left out.
 #[derive(Serialize)]
+pub struct UserExportRow {
+    pub id: Uuid,
+    pub name: String,
+    pub email: String,
+    pub username: Option<String>,
+    pub role: RoleType,
+    pub is_verified: bool,
+    pub is_banned: bool,
+    pub is_private: bool,
+    pub created_at: DateTime<Utc>,
+}
+#[derive(Serialize, Deserialize, FromRow, ToSchema)]
 pub struct Connections {
     pub id: Uuid,
     pub name: String,
@@ -48,46 +101,165 @@ pub struct Connections {
     pub role: RoleType,
     pub is_verified: bool,
 }
+/// An account the viewer doesn't follow yet. `mutual_connections` counts the accounts the viewer
+/// follows that follow it, `shared_tags` the distinct tags both have posted with.
+#[derive(Serialize, Deserialize, FromRow, ToSchema)]
+pub struct UserSuggestion {
+    pub id: Uuid,
+    pub name: String,
+    pub is_verified: bool,
+    pub is_private: bool,
+    pub followers_count: i64,
+    pub mutual_connections: i64,
+    pub shared_tags: i64,
+}
+#[derive(Serialize)]
+pub struct FollowRequest {
+    pub requester_id: Uuid,
+    pub name: String,
+    pub email: String,
+    pub created_at: DateTime<Utc>,
+}
 
 pub struct NewUser<'a> {
     pub role_id: Uuid,
     pub name: &'a str,
     pub email: &'a str,
     pub password: String,
+    pub waitlisted: bool,
+    /// Consumed together with the account, `save_user` fails with `NotFound` once it is used or expired.
+    pub invitation_id: Option<Uuid>,
 }
 
 #[async_trait]
-pub trait UserRepository {
-    async fn get_user_by_id(&self, user_id: &Uuid) -> Result<Option<User>, SqlxError>;
-    async fn get_user_by_email(&self, email: &str) -> Result<Option<UserResponse>, SqlxError>;
-    async fn save_user<'a, 'b>(&self, user_data: NewUser<'a>, user_action_data: NewUserActionToken<'b>) -> Result<(User, RoleType), SqlxError>;
-    async fn get_user_feeds(&self, user_id: Uuid, user_feed_params: UserFeedParams) -> Result<PaginatedData<UserFeeds>, SqlxError>;
-    async fn get_users(&self, user_params: UserListParams) -> Result<PaginatedData<UserResponse>, SqlxError>;
-    async fn get_user_detail(&self, user_id: &Uuid) -> Result<Option<UserDetail>, SqlxError>;
-    async fn update_user(&self, user_id: &Uuid, auth_user_id: &Uuid, user: UserUpdateRequest) -> Result<User, SqlxError>;
-    async fn update_user_password(&self, user_id: &Uuid, new_password: String) -> Result<User, SqlxError>;
-    async fn follow_unfollow_user(&self, user_target: Uuid, user_sender: Uuid) -> Result<String, SqlxError>;
-    async fn get_user_connections(&self, user_id: Uuid, kind: &FollowKind) -> Result<Vec<Connections>, SqlxError>;
-    async fn delete_user(&self, user_id: Uuid) -> Result<(), SqlxError>;
+pub trait UserRepository: Send + Sync {
+    async fn get_user_by_id(&self, user_id: &Uuid) -> Result<Option<User>, RepositoryError>;
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<UserResponse>, RepositoryError>;
+    /// Whether `email` and `username` are still free, checked together so the query does the same
+    /// work whichever of them is missing or taken.
+    async fn get_identifier_availability(&self, email: Option<&str>, username: Option<&str>) -> Result<(bool, bool), RepositoryError>;
+    async fn save_user<'a, 'b>(&self, user_data: NewUser<'a>, user_action_data: NewUserActionToken<'b>) -> Result<(User, RoleType), RepositoryError>;
+    async fn get_user_feeds(&self, user_id: Uuid, user_feed_params: UserFeedParams) -> Result<PaginatedData<UserFeeds>, RepositoryError>;
+    async fn get_users(&self, user_params: UserListParams) -> Result<PaginatedData<UserResponse>, RepositoryError>;
+    async fn get_user_detail(&self, user_id: &Uuid) -> Result<Option<UserDetail>, RepositoryError>;
+    async fn get_user_id_by_username(&self, username: &str) -> Result<Option<Uuid>, RepositoryError>;
+    async fn is_user_visible_to(&self, user_id: &Uuid, viewer_id: &Uuid) -> Result<bool, RepositoryError>;
+    /// The users among `user_ids` that exist, in the order of `user_ids`.
+    async fn get_users_by_ids(&self, user_ids: &[Uuid]) -> Result<Vec<UserSummary>, RepositoryError>;
+    /// Users whose username or a word of whose name starts with `query` (lowercase), banned ones
+    /// left out. An exact username comes first, then username prefixes, then name prefixes, then
+    /// other name words, ties broken by trigram similarity and name.
+    async fn search_users(&self, query: &str, limit: i64) -> Result<Vec<UserSearchResult>, RepositoryError>;
+    /// `expected_version` is the `updated_at` the caller edited, a different stored one fails with `VersionMismatch`.
+    async fn update_user(&self, user_id: &Uuid, auth_user_id: &Uuid, patch: UserPatchRequest, expected_version: Option<DateTime<Utc>>) -> Result<User, RepositoryError>;
+    async fn update_user_password(&self, user_id: &Uuid, new_password: String) -> Result<User, RepositoryError>;
+    async fn follow_unfollow_user(&self, user_target: Uuid, user_sender: Uuid) -> Result<String, RepositoryError>;
+    async fn get_user_connections(&self, user_id: Uuid, kind: &FollowKind, params: UserConnectionParams) -> Result<PaginatedData<Connections>, RepositoryError>;
+    async fn delete_user(&self, user_id: Uuid) -> Result<(), RepositoryError>;
+    async fn get_follower_ids(&self, user_id: Uuid) -> Result<Vec<Uuid>, RepositoryError>;
+    /// Accounts two steps away in the follow graph or posting under the same tags, best match first.
+    async fn get_user_suggestions(&self, user_id: Uuid, params: UserSuggestionParams) -> Result<PaginatedData<UserSuggestion>, RepositoryError>;
+    async fn get_follow_requests(&self, user_id: Uuid) -> Result<Vec<FollowRequest>, RepositoryError>;
+    async fn accept_follow_request(&self, user_id: Uuid, requester_id: Uuid) -> Result<(), RepositoryError>;
+    async fn reject_follow_request(&self, user_id: Uuid, requester_id: Uuid) -> Result<(), RepositoryError>;
+    async fn get_user_settings(&self, user_id: Uuid) -> Result<UserSettings, RepositoryError>;
+    async fn save_user_settings(&self, user_id: Uuid, settings: UserSettings) -> Result<UserSettings, RepositoryError>;
+    async fn ensure_admin(&self, name: &str, email: &str, password: String, admin_role_id: Uuid) -> Result<bool, RepositoryError>;
+    /// Creates a verified account outside of the waitlist, `None` when the email is already taken.
+    async fn import_user<'a>(&self, user_data: NewUser<'a>) -> Result<Option<User>, RepositoryError>;
+    /// Up to `limit` users created after the `(created_at, id)` cursor, oldest first.
+    async fn get_users_for_export(&self, after: Option<(DateTime<Utc>, Uuid)>, limit: i64) -> Result<Vec<UserExportRow>, RepositoryError>;
+    /// Every user, oldest first, from a single query streamed row by row.
+    fn stream_users(&self) -> RowStream<UserExportRow>;
+}
+
+/// FROM and WHERE of the feed, shared by the page query and the count query.
+fn push_feed_filters(query_builder: &mut QueryBuilder<'_, Postgres>, user_id: Uuid, params: &UserFeedParams) {
+    query_builder
+        .push("\
+            FROM posts AS p \
+            JOIN users AS u ON u.id = p.user_id \
+            LEFT JOIN comments AS c ON c.post_id = p.id AND c.is_hidden = false \
+            LEFT JOIN user_followers AS uf ON uf.following_id = p.user_id AND uf.follower_id = \
+        ")
+        .push_bind(user_id)
+        .push(" WHERE p.is_hidden = false AND (p.user_id = ")
+        .push_bind(user_id)
+        .push(" OR uf.follower_id = ")
+        .push_bind(user_id);
+    if params.organizations == Some(true) {
+        query_builder
+            .push(" OR p.organization_id IN (SELECT organization_id FROM organization_members WHERE user_id = ")
+            .push_bind(user_id)
+            .push(" UNION SELECT organization_id FROM organization_followers WHERE user_id = ")
+            .push_bind(user_id)
+            .push(")");
+    }
+    query_builder.push(")");
+    if let Some(search) = &params.search {
+        query_builder
+            .push(" AND (p.title ILIKE ")
+            .push_bind(format!("%{}%", search))
+            .push(" OR p.content ILIKE ")
+            .push_bind(format!("%{}%", search))
+            .push(")");
+    }
+    if let (Some(since_str), Some(until_str)) = (&params.since, &params.until)
+        && let (Ok(since_naive), Ok(until_naive)) = (
+            NaiveDate::parse_from_str(since_str, "%Y-%m-%d"),
+            NaiveDate::parse_from_str(until_str, "%Y-%m-%d"),
+    ) {
+        let since_utc: DateTime<Utc> = Utc.from_utc_datetime(&since_naive.and_hms_opt(0, 0, 0).unwrap());
+        let until_utc: DateTime<Utc> = Utc.from_utc_datetime(&until_naive.and_hms_opt(23, 59, 59).unwrap());
+        query_builder
+            .push(" AND (p.created_at BETWEEN ")
+            .push_bind(since_utc)
+            .push(" AND ")
+            .push_bind(until_utc)
+            .push(")");
+    }
+}
+
+/// FROM and WHERE of the admin user list, shared by the page query and the count query.
+fn push_user_list_filters(query_builder: &mut QueryBuilder<'_, Postgres>, params: &UserListParams) {
+    query_builder.push("FROM users AS u JOIN roles AS r ON r.id = u.role_id WHERE true");
+    if let Some(is_verified) = params.is_verified {
+        query_builder
+            .push(" AND is_verified = ")
+            .push_bind(is_verified);
+    }
+    if let Some(search) = &params.search {
+        query_builder
+            .push(" AND (u.name ILIKE ")
+            .push_bind(format!("%{}%", search))
+            .push(" OR u.email ILIKE ")
+            .push_bind(format!("%{}%", search))
+            .push(")");
+    }
 }
 
 #[async_trait]
 impl UserRepository for DBClient {
-    async fn get_user_by_id(&self, user_id: &Uuid) -> Result<Option<User>, SqlxError> {
+    async fn get_user_by_id(&self, user_id: &Uuid) -> Result<Option<User>, RepositoryError> {
+        let _timer = self.time_query("user.get_user_by_id");
         let user = query_as!(
                 User,
                 r#"
-                    SELECT * FROM users WHERE id = $1;
+                    SELECT id, role_id, name, email, password, is_verified, is_banned, is_private, username, bio, avatar_url, website,
+                           default_license AS "default_license: PostLicense", created_at, updated_at
+                    FROM users WHERE id = $1;
                 "#,
                 user_id
             ).fetch_optional(&self.pool).await?;
         Ok(user)
     }
-    async fn get_user_by_email(&self, email: &str) -> Result<Option<UserResponse>, SqlxError> {
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<UserResponse>, RepositoryError> {
+        let _timer = self.time_query("user.get_user_by_email");
         let user = query_as!(
                 UserResponse,
                 r#"
-                    SELECT u.id, u.name AS name, u.email, r.name AS "role: RoleType", u.password, u.is_verified, u.created_at, u.updated_at 
+                    SELECT u.id, u.name AS name, u.email, r.name AS "role: RoleType", u.password, u.is_verified, u.is_banned, u.is_private,
+                           u.username, u.bio, u.avatar_url, u.website, u.created_at, u.updated_at
                     FROM users AS u JOIN roles AS r ON r.id = u.role_id
                     WHERE u.email = $1;
                 "#,
@@ -95,30 +267,59 @@ impl UserRepository for DBClient {
             ).fetch_optional(&self.pool).await?;
         Ok(user)
     }
-    async fn save_user<'a, 'b>(&self, user_data: NewUser<'a>, user_action_data: NewUserActionToken<'b>) -> Result<(User, RoleType), SqlxError> {
+    async fn get_identifier_availability(&self, email: Option<&str>, username: Option<&str>) -> Result<(bool, bool), RepositoryError> {
+        let _timer = self.time_query("user.get_identifier_availability");
+        let availability = query!(
+            r#"
+                SELECT NOT EXISTS (SELECT 1 FROM users WHERE email = $1) AS "email_available!",
+                       NOT EXISTS (SELECT 1 FROM users WHERE username = $2) AS "username_available!";
+            "#,
+            email,
+            username
+        ).fetch_one(&self.pool).await?;
+        Ok((availability.email_available, availability.username_available))
+    }
+    async fn save_user<'a, 'b>(&self, user_data: NewUser<'a>, user_action_data: NewUserActionToken<'b>) -> Result<(User, RoleType), RepositoryError> {
+        let _timer = self.time_query("user.save_user");
         let mut transaction = self.pool.begin().await?;
         let user = query_as!(
             User,
             r#"
-                INSERT INTO users (role_id, name, email, password) 
-                VALUES ($1, $2, $3, $4) 
-                RETURNING id, role_id, name, email, password, is_verified, created_at, updated_at
+                INSERT INTO users (role_id, name, email, password, waitlisted_at) 
+                VALUES ($1, $2, $3, $4, CASE WHEN $5 THEN Now() END) 
+                RETURNING id, role_id, name, email, password, is_verified, is_banned, is_private, username, bio, avatar_url, website, default_license AS "default_license: PostLicense", created_at, updated_at
             "#,
             user_data.role_id,
             user_data.name,
             user_data.email,
             user_data.password,
+            user_data.waitlisted,
         ).fetch_one(&mut *transaction).await?;
         query!(
             r#"
-                INSERT INTO user_action_tokens (user_id, token, action_type, expires_at) 
+                INSERT INTO user_action_tokens (user_id, token_hash, action_type, expires_at) 
                 VALUES ($1, $2, $3::text::action_type, $4)
             "#,
             user.id,
-            user_action_data.token,
+            user_action_data.token_hash,
             user_action_data.action_type.get_value(),
             user_action_data.expires_at,
         ).execute(&mut *transaction).await?;
+        if let Some(invitation_id) = user_data.invitation_id {
+            // Two sign-ups racing for the same invitation: only one of them updates the row.
+            let consumed = query!(
+                r#"
+                    UPDATE invitations SET consumed_at = Now(), consumed_by = $2
+                    WHERE id = $1 AND consumed_at IS NULL AND expires_at > Now();
+                "#,
+                invitation_id,
+                user.id,
+            ).execute(&mut *transaction).await?;
+            if consumed.rows_affected() == 0 {
+                transaction.rollback().await?;
+                return Err(RepositoryError::NotFound);
+            }
+        }
         let role_type = self.get_role_name_by_id(user.role_id).await?;
         match role_type {
             Some(role_type) => {
@@ -127,114 +328,74 @@ impl UserRepository for DBClient {
             }
             None => {
                 transaction.rollback().await?;
-                Err(SqlxError::RowNotFound.into())
+                Err(RepositoryError::NotFound)
             }
         }
     }
-    async fn get_user_feeds(&self, user_id: Uuid, user_feed_params: UserFeedParams) -> Result<PaginatedData<UserFeeds>, SqlxError> {
-        let limit = user_feed_params.limit.unwrap_or(1) as i32;
-        let page = user_feed_params.page.unwrap_or(1) as i32;
-        let offset = (page - 1) * limit;
-        let order_by = user_feed_params.order_by.unwrap_or("DESC".to_string());
+    async fn get_user_feeds(&self, user_id: Uuid, user_feed_params: UserFeedParams) -> Result<PaginatedData<UserFeeds>, RepositoryError> {
+        let _timer = self.time_query("user.get_user_feeds");
+        let limit = user_feed_params.pagination.limit as i32;
+        let page = user_feed_params.pagination.page as i32;
+        let offset = user_feed_params.pagination.offset() as i32;
+        let order_by = user_feed_params.order_by.as_deref().unwrap_or("DESC");
+        // Same decay as the `trending_posts` view, but over every post in the feed rather than the
+        // last week, so older pages still rank.
+        let sort_key = match user_feed_params.sort.as_deref() {
+            Some("top") => "COUNT(c.id)",
+            Some("trending") => "(COUNT(c.id) + 1) / POWER(EXTRACT(EPOCH FROM (Now() - p.created_at)) / 3600 + 2, 1.5)",
+            _ => "p.created_at",
+        };
         let mut transaction = self.pool.begin().await?;
+        // The window runs after GROUP BY, so it counts posts rather than post and comment rows.
         let mut query_builder_items: QueryBuilder<Postgres> = QueryBuilder::new(
             "\
-            SELECT p.id, p.user_id, p.title, p.content, p.tags, u.name AS posted_by, p.created_at, p.updated_at, COUNT(c.id) AS comments_count \
-            FROM posts AS p \
-            JOIN users AS u ON u.id = p.user_id \
-            LEFT JOIN comments AS c ON c.post_id = p.id \
-            LEFT JOIN user_followers AS uf ON uf.following_id = p.user_id AND uf.follower_id =
+            SELECT p.id, p.user_id, p.organization_id, p.title, p.content, p.tags, u.name AS posted_by, p.created_at, p.updated_at, COUNT(c.id) AS comments_count, \
+            COUNT(*) OVER () AS total_items \
             "
         );
-        query_builder_items
-            .push(" ")
-            .push_bind(user_id)
-            .push(" WHERE (p.user_id = ")
-            .push_bind(user_id)
-            .push(" OR uf.follower_id = ")
-            .push_bind(user_id)
-            .push(")");
-        let mut query_builder_count: QueryBuilder<Postgres> = QueryBuilder::new(
-            "\
-            SELECT COUNT(DISTINCT p.id) \
-            FROM posts AS p \
-            JOIN users AS u ON u.id = p.user_id \
-            LEFT JOIN comments AS c ON c.post_id = p.id \
-            LEFT JOIN user_followers AS uf ON uf.following_id = p.user_id AND uf.follower_id =
-            "
-        );
-        query_builder_count
-            .push(" ")
-            .push_bind(user_id)
-            .push(" WHERE (p.user_id = ")
-            .push_bind(user_id)
-            .push(" OR uf.follower_id = ")
-            .push_bind(user_id)
-            .push(")");
-        if let Some(search) = user_feed_params.search {
-            query_builder_items
-                .push(" AND (p.title ILIKE ")
-                .push_bind(format!("%{}%", search))
-                .push(" OR p.content ILIKE ")
-                .push_bind(format!("%{}%", search))
-                .push(")");
-            query_builder_count
-                .push(" AND (p.title ILIKE ")
-                .push_bind(format!("%{}%", search))
-                .push(" OR p.content ILIKE ")
-                .push_bind(format!("%{}%", search))
-                .push(")");
-        }
-        if let (Some(since_str), Some(until_str)) = (&user_feed_params.since, &user_feed_params.until) {
-            if let (Ok(since_naive), Ok(until_naive)) = (
-                NaiveDate::parse_from_str(since_str, "%Y-%m-%d"),
-                NaiveDate::parse_from_str(until_str, "%Y-%m-%d"),
-            ) {
-                let since_utc: DateTime<Utc> = Utc.from_utc_datetime(&since_naive.and_hms_opt(0, 0, 0).unwrap());
-                let until_utc: DateTime<Utc> = Utc.from_utc_datetime(&until_naive.and_hms_opt(23, 59, 59).unwrap());
-                query_builder_items
-                    .push(" AND (p.created_at BETWEEN ")
-                    .push_bind(since_utc)
-                    .push(" AND ")
-                    .push_bind(until_utc)
-                    .push(")");
-                query_builder_count
-                    .push(" AND (p.created_at BETWEEN ")
-                    .push_bind(since_utc)
-                    .push(" AND ")
-                    .push_bind(until_utc)
-                    .push(")");
-            }
-        }
+        push_feed_filters(&mut query_builder_items, user_id, &user_feed_params);
         query_builder_items
             .push(" GROUP BY p.id, u.name")
-            .push(" ORDER BY p.created_at ")
+            .push(" ORDER BY ")
+            .push(sort_key)
+            .push(" ")
             .push(order_by)
+            .push(", p.created_at DESC, p.id")
             .push(" LIMIT ")
             .push_bind(limit)
             .push(" OFFSET ")
             .push_bind(offset);
-        let query_items = query_builder_items.build_query_as::<UserFeedRow>();
-        let query_count = query_builder_count.build_query_scalar::<i64>();
-        let feed_rows = query_items.fetch_all(&mut *transaction).await?;
-        let total_items = query_count.fetch_one(&mut *transaction).await?;
+        let (feed_rows, total_items) = Counted::split(
+            query_builder_items.build_query_as::<Counted<UserFeedRow>>().fetch_all(&mut *transaction).await?
+        );
+        let total_items = match total_items {
+            Some(total_items) => total_items,
+            None if offset == 0 => 0,
+            None => {
+                let mut query_builder_count: QueryBuilder<Postgres> = QueryBuilder::new("SELECT COUNT(DISTINCT p.id) ");
+                push_feed_filters(&mut query_builder_count, user_id, &user_feed_params);
+                query_builder_count.build_query_scalar::<i64>().fetch_one(&mut *transaction).await?
+            }
+        };
         let post_ids: Vec<Uuid> = feed_rows.iter().map(|feed| feed.id).collect();
         let comments = query_as!(
             Comment,
             r#"
-                SELECT * FROM comments WHERE post_id = ANY($1)
+                SELECT id, user_id, post_id, content, created_at, updated_at FROM comments
+                WHERE post_id = ANY($1) AND is_hidden = false
             "#,
             &post_ids
         ).fetch_all(&mut *transaction).await?;
         let mut comment_map: HashMap<Uuid, Vec<Comment>> = HashMap::new();
         for comment in comments {
-            comment_map.entry(comment.post_id).or_insert_with(Vec::new).push(comment);
+            comment_map.entry(comment.post_id).or_default().push(comment);
         }
         let feeds_with_comments: Vec<UserFeeds> = feed_rows
             .into_iter()
             .map(|row| UserFeeds {
                 id: row.id,
                 user_id: row.user_id,
+                organization_id: row.organization_id,
                 title: row.title,
                 content: row.content,
                 tags: row.tags,
@@ -252,63 +413,19 @@ impl UserRepository for DBClient {
         };
         Ok(paginated_data)
     }
-    async fn get_users(&self, user_params: UserListParams) -> Result<PaginatedData<UserResponse>, SqlxError> {
-        let limit = user_params.limit.unwrap_or(1) as i32;
-        let page = user_params.page.unwrap_or(1) as i32;
-        let offset = (page - 1) * limit;
-        let order_by = user_params.order_by.unwrap_or("DESC".to_string());
-        let mut transaction = self.pool.begin().await?;
+    async fn get_users(&self, user_params: UserListParams) -> Result<PaginatedData<UserResponse>, RepositoryError> {
+        let _timer = self.time_query("user.get_users");
+        let limit = user_params.pagination.limit as i32;
+        let page = user_params.pagination.page as i32;
+        let offset = user_params.pagination.offset() as i32;
+        let order_by = user_params.order_by.as_deref().unwrap_or("DESC");
         let mut query_builder_items: QueryBuilder<Postgres> = QueryBuilder::new(
             "\
-            SELECT u.id, u.name AS name, u.email, r.name AS role, u.password, u.is_verified, u.created_at, u.updated_at \
-            FROM users AS u JOIN roles AS r ON r.id = u.role_id\
+            SELECT u.id, u.name AS name, u.email, r.name AS role, u.password, u.is_verified, u.is_banned, u.is_private, \
+            u.username, u.bio, u.avatar_url, u.website, u.created_at, u.updated_at, COUNT(*) OVER () AS total_items \
             "
         );
-        let mut query_builder_count: QueryBuilder<Postgres> = QueryBuilder::new(
-            "\
-            SELECT COUNT(DISTINCT u.id) \
-            FROM users AS u JOIN roles AS r ON r.id = u.role_id\
-            "
-        );
-        let mut has_where = false;
-        if let Some(is_verified) = user_params.is_verified {
-            query_builder_items
-                .push(" WHERE is_verified = ")
-                .push_bind(is_verified);
-            query_builder_count
-                .push(" WHERE is_verified = ")
-                .push_bind(is_verified);
-            has_where = true;
-        }
-        if let Some(search) = user_params.search {
-            if !has_where {
-                query_builder_items
-                    .push(" WHERE (u.name ILIKE ")
-                    .push_bind(format!("%{}%", search))
-                    .push(" OR u.email ILIKE ")
-                    .push_bind(format!("%{}%", search))
-                    .push(")");
-                query_builder_count
-                    .push(" WHERE (u.name ILIKE ")
-                    .push_bind(format!("%{}%", search))
-                    .push(" OR u.email ILIKE ")
-                    .push_bind(format!("%{}%", search))
-                    .push(")");
-            } else {
-                query_builder_items
-                    .push(" AND (u.name ILIKE ")
-                    .push_bind(format!("%{}%", search))
-                    .push(" OR u.email ILIKE ")
-                    .push_bind(format!("%{}%", search))
-                    .push(")");
-                query_builder_count
-                    .push(" AND (u.name ILIKE ")
-                    .push_bind(format!("%{}%", search))
-                    .push(" OR u.email ILIKE ")
-                    .push_bind(format!("%{}%", search))
-                    .push(")");
-            }
-        }
+        push_user_list_filters(&mut query_builder_items, &user_params);
         query_builder_items
             .push(" ORDER BY u.created_at ")
             .push(order_by)
@@ -316,11 +433,18 @@ impl UserRepository for DBClient {
             .push_bind(limit)
             .push(" OFFSET ")
             .push_bind(offset);
-        let query_items = query_builder_items.build_query_as::<UserResponse>();
-        let query_count = query_builder_count.build_query_scalar::<i64>();
-        let users = query_items.fetch_all(&mut *transaction).await?;
-        let total_items = query_count.fetch_one(&mut *transaction).await?;
-        transaction.commit().await?;
+        let (users, total_items) = Counted::split(
+            query_builder_items.build_query_as::<Counted<UserResponse>>().fetch_all(&self.pool).await?
+        );
+        let total_items = match total_items {
+            Some(total_items) => total_items,
+            None if offset == 0 => 0,
+            None => {
+                let mut query_builder_count: QueryBuilder<Postgres> = QueryBuilder::new("SELECT COUNT(*) ");
+                push_user_list_filters(&mut query_builder_count, &user_params);
+                query_builder_count.build_query_scalar::<i64>().fetch_one(&self.pool).await?
+            }
+        };
         let pagination = PaginationMeta::new(page, limit, total_items);
         let paginated_data = PaginatedData {
             items: users,
@@ -328,95 +452,172 @@ impl UserRepository for DBClient {
         };
         Ok(paginated_data)
     }
-    async fn get_user_detail(&self, user_id: &Uuid) -> Result<Option<UserDetail>, SqlxError> {
-        let mut transaction = self.pool.begin().await?;
-        let user_data = query!(
+    async fn get_user_detail(&self, user_id: &Uuid) -> Result<Option<UserDetail>, RepositoryError> {
+        let _timer = self.time_query("user.get_user_detail");
+        let user_detail = query_as!(
+                UserDetail,
                 r#"
-                    SELECT u.id, u.name AS name, u.email, r.name AS "role: RoleType", u.is_verified, u.created_at, u.updated_at 
+                    SELECT u.id, u.name AS name, u.email, r.name AS "role: RoleType", u.is_verified, u.is_private, u.created_at, u.updated_at,
+                           u.username, u.bio, u.avatar_url, u.website, u.followers_count, u.following_count
                     FROM users AS u JOIN roles AS r ON r.id = u.role_id
                     WHERE u.id = $1;
                 "#,
                 user_id
-            ).fetch_optional(&mut *transaction).await?;
-        let Some(user) = user_data else {
-            return Ok(None);
-        };
-        let following = query_as!(
-                Connections,
-                r#"
-                    SELECT u.id, u.name AS name, u.email, r.name AS "role: RoleType", u.is_verified
-                    FROM users AS u
-                        JOIN roles AS r ON r.id = u.role_id
-                        JOIN user_followers AS uf ON uf.following_id = u.id
-                    WHERE uf.follower_id = $1;
-                "#,
-                user_id
-            ).fetch_all(&mut *transaction).await?;
-        let followers = query_as!(
-                Connections,
-                r#"
-                    SELECT u.id, u.name AS name, u.email, r.name AS "role: RoleType", u.is_verified
-                    FROM users AS u
-                        JOIN roles AS r ON r.id = u.role_id
-                        JOIN user_followers AS uf ON uf.follower_id = u.id
-                    WHERE uf.following_id = $1;
-                "#,
-                user_id
-            ).fetch_all(&mut *transaction).await?;
-        let user_detail = UserDetail {
-            id: user.id,
-            name: user.name,
-            email: user.email,
-            role: user.role,
-            is_verified: user.is_verified,
-            created_at: user.created_at,
-            updated_at: user.updated_at,
-            following,
-            followers,
-        };
-        transaction.commit().await?;
-        Ok(Some(user_detail))
+            ).fetch_optional(&self.pool).await?;
+        Ok(user_detail)
+    }
+    async fn get_user_id_by_username(&self, username: &str) -> Result<Option<Uuid>, RepositoryError> {
+        let _timer = self.time_query("user.get_user_id_by_username");
+        let user_id = query_scalar!(
+            r#"
+                SELECT id FROM users WHERE username = $1;
+            "#,
+            username
+        ).fetch_optional(&self.pool).await?;
+        Ok(user_id)
+    }
+    async fn is_user_visible_to(&self, user_id: &Uuid, viewer_id: &Uuid) -> Result<bool, RepositoryError> {
+        let _timer = self.time_query("user.is_user_visible_to");
+        let is_visible = query_scalar!(
+            r#"
+                SELECT (
+                    NOT u.is_private
+                    OR u.id = $2
+                    OR EXISTS (SELECT 1 FROM user_followers WHERE following_id = u.id AND follower_id = $2)
+                    OR EXISTS (
+                        SELECT 1 FROM users AS v JOIN roles AS r ON r.id = v.role_id
+                        WHERE v.id = $2 AND r.name = 'admin'
+                    )
+                ) AS "is_visible!"
+                FROM users AS u
+                WHERE u.id = $1;
+            "#,
+            user_id,
+            viewer_id
+        ).fetch_optional(&self.pool).await?.unwrap_or(false);
+        Ok(is_visible)
+    }
+    async fn get_users_by_ids(&self, user_ids: &[Uuid]) -> Result<Vec<UserSummary>, RepositoryError> {
+        let _timer = self.time_query("user.get_users_by_ids");
+        let users = query_as!(
+            UserSummary,
+            r#"
+                SELECT u.id, u.name, u.email, r.name AS "role: RoleType", u.is_verified, u.is_private, u.created_at, u.updated_at
+                FROM users AS u JOIN roles AS r ON r.id = u.role_id
+                WHERE u.id = ANY($1)
+                ORDER BY array_position($1, u.id);
+            "#,
+            user_ids
+        ).fetch_all(&self.pool).await?;
+        Ok(users)
     }
-    async fn update_user(&self, user_id: &Uuid, auth_user_id: &Uuid, body: UserUpdateRequest) -> Result<User, SqlxError> {
+    async fn search_users(&self, query: &str, limit: i64) -> Result<Vec<UserSearchResult>, RepositoryError> {
+        let _timer = self.time_query("user.search_users");
+        let prefix = like_prefix(query);
+        let users = query_as!(
+            UserSearchResult,
+            r#"
+                SELECT id, name, username, avatar_url, is_verified, is_private
+                FROM users
+                WHERE is_banned = false
+                  AND (username LIKE $2 OR lower(name) LIKE $2 OR lower(name) LIKE '% ' || $2)
+                ORDER BY CASE
+                            WHEN username = $1 THEN 0
+                            WHEN username LIKE $2 THEN 1
+                            WHEN lower(name) LIKE $2 THEN 2
+                            ELSE 3
+                         END,
+                         similarity(lower(name), $1) DESC, name, id
+                LIMIT $3;
+            "#,
+            query,
+            prefix,
+            limit,
+        ).fetch_all(&self.pool).await?;
+        Ok(users)
+    }
+    async fn update_user(&self, user_id: &Uuid, auth_user_id: &Uuid, patch: UserPatchRequest, expected_version: Option<DateTime<Utc>>) -> Result<User, RepositoryError> {
+        let _timer = self.time_query("user.update_user");
         let mut transaction = self.pool.begin().await?;
-        query_scalar!(
+        let current = query_as!(
+            User,
             r#"
-                SELECT id FROM users WHERE id = $1 FOR UPDATE;
+                SELECT id, role_id, name, email, password, is_verified, is_banned, is_private, username, bio, avatar_url, website, default_license AS "default_license: PostLicense", created_at, updated_at
+                FROM users WHERE id = $1 FOR UPDATE;
             "#,
             user_id
-        ).fetch_optional(&mut *transaction).await?.ok_or(SqlxError::RowNotFound)?;
+        ).fetch_optional(&mut *transaction).await?.ok_or(RepositoryError::NotFound)?;
         if auth_user_id != user_id {
-            return Err(SqlxError::InvalidArgument(ErrorMessage::PermissionDenied.to_string()));
+            return Err(RepositoryError::Forbidden);
+        }
+        if expected_version.is_some_and(|version| version != current.updated_at) {
+            return Err(RepositoryError::version_mismatch(&current));
         }
         let user = query_as!(
             User,
             r#"
                 UPDATE users
-                SET name = $1, updated_at = Now()
-                WHERE id = $2
-                RETURNING id, role_id, name, email, password, is_verified, created_at, updated_at
+                SET name = COALESCE($1, name), is_private = COALESCE($2, is_private),
+                    default_license = COALESCE($3, default_license),
+                    username = CASE WHEN $5 THEN NULLIF($6, '') ELSE username END,
+                    bio = CASE WHEN $7 THEN NULLIF($8, '') ELSE bio END,
+                    avatar_url = CASE WHEN $9 THEN NULLIF($10, '') ELSE avatar_url END,
+                    website = CASE WHEN $11 THEN NULLIF($12, '') ELSE website END,
+                    updated_at = Now()
+                WHERE id = $4
+                RETURNING id, role_id, name, email, password, is_verified, is_banned, is_private, username, bio, avatar_url, website, default_license AS "default_license: PostLicense", created_at, updated_at
             "#,
-            body.name,
-            user_id
+            patch.name,
+            patch.is_private,
+            patch.default_license as Option<PostLicense>,
+            user_id,
+            patch.username.is_some(),
+            patch.username.flatten(),
+            patch.bio.is_some(),
+            patch.bio.flatten(),
+            patch.avatar_url.is_some(),
+            patch.avatar_url.flatten(),
+            patch.website.is_some(),
+            patch.website.flatten(),
         ).fetch_one(&mut *transaction).await?;
+        if !user.is_private {
+            // Going public approves whoever was still waiting for an answer.
+            let requester_ids = query_scalar!(
+                r#"
+                    WITH pending AS (
+                        DELETE FROM follow_requests WHERE target_id = $1 RETURNING requester_id
+                    )
+                    INSERT INTO user_followers (follower_id, following_id)
+                    SELECT requester_id, $1 FROM pending
+                    ON CONFLICT DO NOTHING
+                    RETURNING follower_id;
+                "#,
+                user_id
+            ).fetch_all(&mut *transaction).await?;
+            for requester_id in requester_ids {
+                adjust_follow_counts(&mut transaction, requester_id, *user_id, 1).await?;
+            }
+        }
         transaction.commit().await?;
         Ok(user)
     }
-    async fn update_user_password(&self, user_id: &Uuid, new_password: String) -> Result<User, SqlxError> {
+    async fn update_user_password(&self, user_id: &Uuid, new_password: String) -> Result<User, RepositoryError> {
+        let _timer = self.time_query("user.update_user_password");
         let user = query_as!(
             User,
             r#"
                 UPDATE users
                 SET password = $1, updated_at = Now()
                 WHERE id = $2
-                RETURNING id, role_id, name, email, password, is_verified, created_at, updated_at
+                RETURNING id, role_id, name, email, password, is_verified, is_banned, is_private, username, bio, avatar_url, website, default_license AS "default_license: PostLicense", created_at, updated_at
             "#,
             new_password,
             user_id
         ).fetch_one(&self.pool).await?;
         Ok(user)
     }
-    async fn follow_unfollow_user(&self, user_target: Uuid, user_sender: Uuid) -> Result<String, SqlxError> {
+    async fn follow_unfollow_user(&self, user_target: Uuid, user_sender: Uuid) -> Result<String, RepositoryError> {
+        let _timer = self.time_query("user.follow_unfollow_user");
         let mut transaction = self.pool.begin().await?;
         let is_exist = query_scalar!(
             r#"
@@ -424,7 +625,7 @@ impl UserRepository for DBClient {
             "#,
             user_target,
             user_sender
-        ).fetch_one(&mut *transaction).await?.ok_or(SqlxError::WorkerCrashed)?;
+        ).fetch_one(&mut *transaction).await?.unwrap_or(0);
         let message = match is_exist {
             1 => {
                 query!(
@@ -434,63 +635,125 @@ impl UserRepository for DBClient {
                     user_target,
                     user_sender
                 ).execute(&mut *transaction).await?;
+                adjust_follow_counts(&mut transaction, user_sender, user_target, -1).await?;
                 String::from("Successfully Unfollowed")
             }
             0 => {
-                query!(
+                let is_private = query_scalar!(
                     r#"
-                        INSERT INTO user_followers (follower_id, following_id)
-                        VALUES ($1, $2)
+                        SELECT is_private FROM users WHERE id = $1;
                     "#,
-                    user_sender,
-                    user_target,
-                ).execute(&mut *transaction).await?;
-                String::from("Successfully Followed")
+                    user_target
+                ).fetch_optional(&mut *transaction).await?.ok_or(RepositoryError::NotFound)?;
+                if is_private {
+                    let cancelled = query!(
+                        r#"
+                            DELETE FROM follow_requests WHERE requester_id = $1 AND target_id = $2
+                        "#,
+                        user_sender,
+                        user_target,
+                    ).execute(&mut *transaction).await?.rows_affected();
+                    if cancelled > 0 {
+                        String::from("Follow Request Cancelled")
+                    } else {
+                        query!(
+                            r#"
+                                INSERT INTO follow_requests (requester_id, target_id)
+                                VALUES ($1, $2)
+                            "#,
+                            user_sender,
+                            user_target,
+                        ).execute(&mut *transaction).await?;
+                        String::from("Follow Request Sent")
+                    }
+                } else {
+                    query!(
+                        r#"
+                            INSERT INTO user_followers (follower_id, following_id)
+                            VALUES ($1, $2)
+                        "#,
+                        user_sender,
+                        user_target,
+                    ).execute(&mut *transaction).await?;
+                    adjust_follow_counts(&mut transaction, user_sender, user_target, 1).await?;
+                    String::from("Successfully Followed")
+                }
             }
             _ => unreachable!()
         };
         transaction.commit().await?;
         Ok(message)
     }
-    async fn get_user_connections(&self, user_id: Uuid, kind: &FollowKind) -> Result<Vec<Connections>, SqlxError> {
-        let data = match kind {
-            FollowKind::Following => {
-                query_as!(
-                    Connections,
-                    r#"
-                        SELECT u.id, u.name AS name, u.email, r.name AS "role: RoleType", u.is_verified
-                        FROM users AS u
-                            JOIN roles AS r ON r.id = u.role_id
-                            JOIN user_followers AS uf ON uf.following_id = u.id
-                        WHERE uf.follower_id = $1;
-                    "#,
-                    user_id
-                ).fetch_all(&self.pool).await?
-            }
-            FollowKind::Followers => {
-                query_as!(
-                    Connections,
-                    r#"
-                        SELECT u.id, u.name AS name, u.email, r.name AS "role: RoleType", u.is_verified
-                        FROM users AS u
-                            JOIN roles AS r ON r.id = u.role_id
-                            JOIN user_followers AS uf ON uf.follower_id = u.id
-                        WHERE uf.following_id = $1;
-                    "#,
-                    user_id
-                ).fetch_all(&self.pool).await?
-            },
+    async fn get_user_connections(&self, user_id: Uuid, kind: &FollowKind, params: UserConnectionParams) -> Result<PaginatedData<Connections>, RepositoryError> {
+        let _timer = self.time_query("user.get_user_connections");
+        let limit = params.pagination.limit as i32;
+        let page = params.pagination.page as i32;
+        let offset = params.pagination.offset() as i32;
+        let order_by = params.order_by.unwrap_or("DESC".to_string());
+        // The user's counter doubles as the total, so paging never counts the follower rows.
+        let (join, filter, total_column) = match kind {
+            FollowKind::Following => ("uf.following_id", "uf.follower_id", "following_count"),
+            FollowKind::Followers => ("uf.follower_id", "uf.following_id", "followers_count"),
         };
-        Ok(data)
+        let mut transaction = self.pool.begin().await?;
+        let mut query_builder_items: QueryBuilder<Postgres> = QueryBuilder::new(
+            "\
+            SELECT u.id, u.name AS name, u.email, r.name AS role, u.is_verified \
+            FROM users AS u \
+                JOIN roles AS r ON r.id = u.role_id \
+                JOIN user_followers AS uf ON \
+            "
+        );
+        query_builder_items
+            .push(join)
+            .push(" = u.id WHERE ")
+            .push(filter)
+            .push(" = ")
+            .push_bind(user_id)
+            .push(" ORDER BY uf.created_at ")
+            .push(order_by)
+            .push(", u.id LIMIT ")
+            .push_bind(limit)
+            .push(" OFFSET ")
+            .push_bind(offset);
+        let mut query_builder_count: QueryBuilder<Postgres> = QueryBuilder::new("SELECT ");
+        query_builder_count
+            .push(total_column)
+            .push(" FROM users WHERE id = ")
+            .push_bind(user_id);
+        let items = query_builder_items.build_query_as::<Connections>().fetch_all(&mut *transaction).await?;
+        let total_items = query_builder_count.build_query_scalar::<i64>().fetch_optional(&mut *transaction).await?.unwrap_or(0);
+        transaction.commit().await?;
+        let pagination = PaginationMeta::new(page, limit, total_items);
+        Ok(PaginatedData {
+            items,
+            pagination,
+        })
     }
-    async fn delete_user(&self, user_id: Uuid) -> Result<(), SqlxError> {
+    async fn delete_user(&self, user_id: Uuid) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("user.delete_user");
         let mut transaction = self.pool.begin().await?;
         query_scalar!(
             r#"
                 SELECT id FROM users WHERE id = $1 FOR UPDATE;
             "#,
             user_id
-        ).fetch_optional(&mut *transaction).await?.ok_or(SqlxError::RowNotFound)?;
+        ).fetch_optional(&mut *transaction).await?.ok_or(RepositoryError::NotFound)?;
+        // The follow rows go with the user, so the counters on the other side are settled first.
+        query!(
+            r#"
+                UPDATE users SET following_count = following_count - 1
+                WHERE id IN (SELECT follower_id FROM user_followers WHERE following_id = $1);
+            "#,
+            user_id
+        ).execute(&mut *transaction).await?;
+        query!(
+            r#"
+                UPDATE users SET followers_count = followers_count - 1
+                WHERE id IN (SELECT following_id FROM user_followers WHERE follower_id = $1);
+            "#,
+            user_id
+        ).execute(&mut *transaction).await?;
         query!(
             r#"
                 DELETE FROM users WHERE id = $1;
@@ -500,4 +763,239 @@ impl UserRepository for DBClient {
         transaction.commit().await?;
         Ok(())
     }
+    async fn get_follower_ids(&self, user_id: Uuid) -> Result<Vec<Uuid>, RepositoryError> {
+        let _timer = self.time_query("user.get_follower_ids");
+        let follower_ids = query_scalar!(
+            r#"
+                SELECT follower_id FROM user_followers WHERE following_id = $1;
+            "#,
+            user_id
+        ).fetch_all(&self.pool).await?;
+        Ok(follower_ids)
+    }
+    async fn get_user_suggestions(&self, user_id: Uuid, params: UserSuggestionParams) -> Result<PaginatedData<UserSuggestion>, RepositoryError> {
+        let _timer = self.time_query("user.get_user_suggestions");
+        let limit = params.pagination.limit as i32;
+        let page = params.pagination.page as i32;
+        let offset = params.pagination.offset() as i32;
+        let mut transaction = self.pool.begin().await?;
+        let mut query_builder_items: QueryBuilder<Postgres> = QueryBuilder::new("");
+        push_suggestion_candidates(&mut query_builder_items, user_id);
+        query_builder_items
+            .push(" SELECT id, name, is_verified, is_private, followers_count, mutual_connections, shared_tags FROM candidates")
+            .push(" ORDER BY mutual_connections * 2 + shared_tags DESC, followers_count DESC, id")
+            .push(" LIMIT ")
+            .push_bind(limit)
+            .push(" OFFSET ")
+            .push_bind(offset);
+        let mut query_builder_count: QueryBuilder<Postgres> = QueryBuilder::new("");
+        push_suggestion_candidates(&mut query_builder_count, user_id);
+        query_builder_count.push(" SELECT COUNT(*) FROM candidates");
+        let items = query_builder_items.build_query_as::<UserSuggestion>().fetch_all(&mut *transaction).await?;
+        let total_items = query_builder_count.build_query_scalar::<i64>().fetch_one(&mut *transaction).await?;
+        transaction.commit().await?;
+        let pagination = PaginationMeta::new(page, limit, total_items);
+        Ok(PaginatedData {
+            items,
+            pagination,
+        })
+    }
+    async fn get_follow_requests(&self, user_id: Uuid) -> Result<Vec<FollowRequest>, RepositoryError> {
+        let _timer = self.time_query("user.get_follow_requests");
+        let requests = query_as!(
+            FollowRequest,
+            r#"
+                SELECT fr.requester_id, u.name, u.email, fr.created_at
+                FROM follow_requests AS fr
+                    JOIN users AS u ON u.id = fr.requester_id
+                WHERE fr.target_id = $1
+                ORDER BY fr.created_at DESC;
+            "#,
+            user_id
+        ).fetch_all(&self.pool).await?;
+        Ok(requests)
+    }
+    async fn accept_follow_request(&self, user_id: Uuid, requester_id: Uuid) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("user.accept_follow_request");
+        let mut transaction = self.pool.begin().await?;
+        query!(
+            r#"
+                DELETE FROM follow_requests WHERE requester_id = $1 AND target_id = $2 RETURNING requester_id;
+            "#,
+            requester_id,
+            user_id
+        ).fetch_optional(&mut *transaction).await?.ok_or(RepositoryError::NotFound)?;
+        let inserted = query!(
+            r#"
+                INSERT INTO user_followers (follower_id, following_id)
+                VALUES ($1, $2)
+                ON CONFLICT DO NOTHING;
+            "#,
+            requester_id,
+            user_id
+        ).execute(&mut *transaction).await?.rows_affected();
+        if inserted > 0 {
+            adjust_follow_counts(&mut transaction, requester_id, user_id, 1).await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+    async fn reject_follow_request(&self, user_id: Uuid, requester_id: Uuid) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("user.reject_follow_request");
+        query!(
+            r#"
+                DELETE FROM follow_requests WHERE requester_id = $1 AND target_id = $2 RETURNING requester_id;
+            "#,
+            requester_id,
+            user_id
+        ).fetch_optional(&self.pool).await?.ok_or(RepositoryError::NotFound)?;
+        Ok(())
+    }
+    async fn get_user_settings(&self, user_id: Uuid) -> Result<UserSettings, RepositoryError> {
+        let _timer = self.time_query("user.get_user_settings");
+        let settings = query_scalar!(
+            r#"
+                SELECT settings AS "settings: Json<UserSettings>" FROM user_settings WHERE user_id = $1;
+            "#,
+            user_id
+        ).fetch_optional(&self.pool).await?;
+        Ok(settings.map(|settings| settings.0).unwrap_or_default())
+    }
+    async fn save_user_settings(&self, user_id: Uuid, settings: UserSettings) -> Result<UserSettings, RepositoryError> {
+        let _timer = self.time_query("user.save_user_settings");
+        let settings = query_scalar!(
+            r#"
+                INSERT INTO user_settings (user_id, settings)
+                VALUES ($1, $2)
+                ON CONFLICT (user_id) DO UPDATE SET settings = EXCLUDED.settings, updated_at = Now()
+                RETURNING settings AS "settings: Json<UserSettings>";
+            "#,
+            user_id,
+            Json(settings) as _
+        ).fetch_one(&self.pool).await?;
+        Ok(settings.0)
+    }    /// Creates a verified admin account, or promotes and verifies the account already using `email`
+    /// without touching its password. Returns whether the account was created.
+    async fn ensure_admin(&self, name: &str, email: &str, password: String, admin_role_id: Uuid) -> Result<bool, RepositoryError> {
+        let _timer = self.time_query("user.ensure_admin");
+        let created = query_scalar!(
+            r#"
+                INSERT INTO users (role_id, name, email, password, is_verified)
+                VALUES ($1, $2, $3, $4, TRUE)
+                ON CONFLICT (email) DO UPDATE
+                SET role_id = EXCLUDED.role_id, is_verified = TRUE, waitlisted_at = NULL, updated_at = Now()
+                RETURNING (xmax = 0) AS "created!";
+            "#,
+            admin_role_id,
+            name,
+            email,
+            password
+        ).fetch_one(&self.pool).await?;
+        Ok(created)
+    }
+    async fn import_user<'a>(&self, user_data: NewUser<'a>) -> Result<Option<User>, RepositoryError> {
+        let _timer = self.time_query("user.import_user");
+        let user = query_as!(
+            User,
+            r#"
+                INSERT INTO users (role_id, name, email, password, is_verified)
+                VALUES ($1, $2, $3, $4, TRUE)
+                ON CONFLICT (email) DO NOTHING
+                RETURNING id, role_id, name, email, password, is_verified, is_banned, is_private, username, bio, avatar_url, website,
+                          default_license AS "default_license: PostLicense", created_at, updated_at;
+            "#,
+            user_data.role_id,
+            user_data.name,
+            user_data.email,
+            user_data.password,
+        ).fetch_optional(&self.pool).await?;
+        Ok(user)
+    }
+    async fn get_users_for_export(&self, after: Option<(DateTime<Utc>, Uuid)>, limit: i64) -> Result<Vec<UserExportRow>, RepositoryError> {
+        let _timer = self.time_query("user.get_users_for_export");
+        let (after_created_at, after_id) = after.unzip();
+        let users = query_as!(
+            UserExportRow,
+            r#"
+                SELECT u.id, u.name, u.email, u.username, r.name AS "role: RoleType", u.is_verified, u.is_banned, u.is_private, u.created_at
+                FROM users AS u JOIN roles AS r ON r.id = u.role_id
+                WHERE $1::TIMESTAMPTZ IS NULL OR (u.created_at, u.id) > ($1, $2)
+                ORDER BY u.created_at, u.id
+                LIMIT $3;
+            "#,
+            after_created_at,
+            after_id,
+            limit,
+        ).fetch_all(&self.pool).await?;
+        Ok(users)
+    }
+    fn stream_users(&self) -> RowStream<UserExportRow> {
+        let (pool, timer) = (self.pool.clone(), self.time_query("user.stream_users"));
+        Box::pin(try_stream! {
+            let _timer = timer;
+            let mut users = query_as!(
+                UserExportRow,
+                r#"
+                    SELECT u.id, u.name, u.email, u.username, r.name AS "role: RoleType", u.is_verified, u.is_banned, u.is_private, u.created_at
+                    FROM users AS u JOIN roles AS r ON r.id = u.role_id
+                    ORDER BY u.created_at, u.id;
+                "#,
+            ).fetch(&pool);
+            while let Some(user) = users.try_next().await? {
+                yield user;
+            }
+        })
+    }
+}
+
+/// The `candidates` CTE behind the suggestions. Accounts the user already follows, has a pending
+/// request to, banned ones and ones still on the waitlist are left out, and so is anyone with
+/// neither a mutual connection nor a shared tag.
+fn push_suggestion_candidates(query_builder: &mut QueryBuilder<Postgres>, user_id: Uuid) {
+    query_builder
+        .push("WITH my_following AS (SELECT following_id FROM user_followers WHERE follower_id = ")
+        .push_bind(user_id)
+        .push("), mutuals AS (\
+            SELECT uf.following_id AS user_id, COUNT(*) AS mutual_connections \
+            FROM user_followers AS uf \
+            WHERE uf.follower_id IN (SELECT following_id FROM my_following) \
+            GROUP BY uf.following_id\
+        ), my_tags AS (SELECT DISTINCT t.tag FROM posts AS p CROSS JOIN LATERAL unnest(p.tags) AS t(tag) WHERE p.is_hidden = false AND p.user_id = ")
+        .push_bind(user_id)
+        .push("), shared AS (\
+            SELECT p.user_id, COUNT(DISTINCT t.tag) AS shared_tags \
+            FROM posts AS p CROSS JOIN LATERAL unnest(p.tags) AS t(tag) \
+            WHERE p.is_hidden = false AND t.tag IN (SELECT tag FROM my_tags) \
+            GROUP BY p.user_id\
+        ), candidates AS (\
+            SELECT u.id, u.name, u.is_verified, u.is_private, u.followers_count, \
+                COALESCE(m.mutual_connections, 0) AS mutual_connections, COALESCE(s.shared_tags, 0) AS shared_tags \
+            FROM users AS u \
+                LEFT JOIN mutuals AS m ON m.user_id = u.id \
+                LEFT JOIN shared AS s ON s.user_id = u.id \
+            WHERE (m.user_id IS NOT NULL OR s.user_id IS NOT NULL) \
+                AND u.is_banned = false AND u.waitlisted_at IS NULL \
+                AND u.id NOT IN (SELECT following_id FROM my_following) \
+                AND u.id <> ")
+        .push_bind(user_id)
+        .push(" AND NOT EXISTS (SELECT 1 FROM follow_requests WHERE target_id = u.id AND requester_id = ")
+        .push_bind(user_id)
+        .push("))");
+}
+
+/// Moves both counters of one follow relation by `delta`, inside the transaction that added or
+/// removed the `user_followers` row.
+async fn adjust_follow_counts(conn: &mut PgConnection, follower_id: Uuid, following_id: Uuid, delta: i64) -> Result<(), RepositoryError> {
+    query!(
+        r#"
+            UPDATE users
+            SET following_count = following_count + CASE WHEN id = $1 THEN $3::BIGINT ELSE 0 END,
+                followers_count = followers_count + CASE WHEN id = $2 THEN $3::BIGINT ELSE 0 END
+            WHERE id IN ($1, $2);
+        "#,
+        follower_id,
+        following_id,
+        delta
+    ).execute(conn).await?;
+    Ok(())
 }
\ No newline at end of file
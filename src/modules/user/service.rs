@@ -0,0 +1,214 @@
+use std::sync::Arc;
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde_json::json;
+use uuid::Uuid;
+use crate::{
+    AppState,
+    dto::{BatchData, BatchRequest, PaginatedData},
+    error::{AppError, ErrorMessage, RepositoryError},
+    modules::{
+        activity::{dto::UserActivityParams, model::ActivityItem},
+        user::{
+            dto::{EmailKind, FollowKind, FollowUnfollowResponse, UserConnectionParams, UserFeedParams, UserSearchParams, UserSuggestionParams, UserFeeds, UserPasswordUpdateRequest, UserResponse, UserPatchRequest},
+            model::{Connections, User, UserDetail, UserSearchResult, UserSuggestion, UserSummary},
+        },
+        audit_log::model::{AuditAction, NewAuditLog},
+        event::domain_event::DomainEvent,
+    },
+    utils::{avatar, metrics},
+};
+
+/// Profile, follow graph and feed rules. Every method acts on behalf of `actor`, the signed in user.
+pub struct UserService {
+    app_state: Arc<AppState>,
+}
+
+impl UserService {
+    pub fn new(app_state: Arc<AppState>) -> Self {
+        Self { app_state }
+    }
+    async fn ensure_exists(&self, user_id: &Uuid) -> Result<(), AppError> {
+        self.app_state.db.users.get_user_by_id(user_id).await?
+            .ok_or(AppError::not_found(ErrorMessage::DataNotFound))?;
+        Ok(())
+    }
+    async fn ensure_visible(&self, user_id: &Uuid, actor: &User) -> Result<(), AppError> {
+        if !self.app_state.db.users.is_user_visible_to(user_id, &actor.id).await? {
+            return Err(AppError::forbidden(ErrorMessage::PrivateAccount));
+        }
+        Ok(())
+    }
+
+    /// Whether `user_id`'s settings let an email of `kind` go out. When they can't be read the email
+    /// is skipped, an opt-out is never ignored.
+    pub async fn email_allowed(&self, user_id: Uuid, kind: EmailKind) -> bool {
+        match self.app_state.db.users.get_user_settings(user_id).await {
+            Ok(settings) => settings.allows_email(kind),
+            Err(e) => {
+                warn!("Settings of user {} could not be read, email skipped: {}", user_id, e);
+                false
+            }
+        }
+    }
+
+    /// `user` with its role name and without the password hash, as every endpoint sends it back.
+    async fn response(&self, user: &User) -> Result<UserResponse, AppError> {
+        let role_type = self.app_state.db.roles.get_role_name_by_id(user.role_id).await?
+            .ok_or(AppError::server_error(ErrorMessage::ServerError))?;
+        Ok(UserResponse::get_user_response(user, role_type))
+    }
+
+    pub async fn profile(&self, actor: &User) -> Result<UserResponse, AppError> {
+        self.response(actor).await
+    }
+    pub async fn detail(&self, user_id: Uuid) -> Result<UserDetail, AppError> {
+        self.app_state.db.users.get_user_detail(&user_id).await?
+            .ok_or(AppError::not_found(ErrorMessage::DataNotFound))
+    }
+    pub async fn detail_by_username(&self, username: &str) -> Result<UserDetail, AppError> {
+        let user_id = self.app_state.db.users.get_user_id_by_username(&username.to_lowercase()).await?
+            .ok_or(AppError::not_found(ErrorMessage::DataNotFound))?;
+        self.detail(user_id).await
+    }
+    /// The avatar url of `user_id` and the version of its `size` rendering. Users without an avatar
+    /// are not found.
+    pub async fn avatar_version(&self, user_id: Uuid, size: u32) -> Result<(String, String), AppError> {
+        let avatar_url = self.app_state.db.users.get_user_by_id(&user_id).await?
+            .and_then(|user| user.avatar_url)
+            .ok_or(AppError::not_found(ErrorMessage::DataNotFound))?;
+        let version = avatar::version(&avatar_url, size);
+        Ok((avatar_url, version))
+    }
+    /// The avatar at `avatar_url` as a `size` pixels square PNG, rendered once per version and then
+    /// served from Redis for `AVATAR_CACHE_TTL` seconds.
+    pub async fn render_avatar(&self, user_id: Uuid, avatar_url: &str, version: &str, size: u32) -> Result<Vec<u8>, AppError> {
+        let env = &self.app_state.env;
+        if let Ok(Some(image)) = self.app_state.redis_client.get_avatar(&user_id, version).await {
+            return Ok(image);
+        }
+        let unavailable = |e: String| {
+            warn!("Avatar of user {} at {} could not be rendered: {}", user_id, avatar_url, e);
+            AppError::bad_gateway(ErrorMessage::AvatarUnavailable)
+        };
+        let source = avatar::fetch(avatar_url, env.avatar_max_bytes, env.avatar_private_hosts).await.map_err(unavailable)?;
+        let image = tokio::task::spawn_blocking(move || avatar::resize(&source, size)).await
+            .map_err(|e| unavailable(e.to_string()))?
+            .map_err(unavailable)?;
+        let _ = self.app_state.redis_client.set_avatar(&user_id, version, &image, env.avatar_cache_ttl).await;
+        Ok(image)
+    }
+    pub async fn batch(&self, body: &BatchRequest) -> Result<BatchData<UserSummary>, AppError> {
+        let ids = body.unique_ids();
+        let users = self.app_state.db.users.get_users_by_ids(&ids).await?;
+        Ok(BatchData::new(&ids, users, |user| user.id))
+    }
+    pub async fn update(&self, user_id: Uuid, actor: &User, patch: UserPatchRequest, expected_version: Option<DateTime<Utc>>) -> Result<UserResponse, AppError> {
+        let updated_user = match self.app_state.db.users.update_user(&user_id, &actor.id, patch, expected_version).await {
+            // The stored row carries the password hash, the client gets the version it lost against as a `UserResponse`.
+            Err(RepositoryError::VersionMismatch { current }) => {
+                let current: User = serde_json::from_value(current)
+                    .map_err(|_| AppError::server_error(ErrorMessage::ServerError))?;
+                return Err(RepositoryError::version_mismatch(&self.response(&current).await?).into());
+            }
+            result => result?,
+        };
+        let _ = self.app_state.redis_client.delete_user(&updated_user.id).await;
+        let _ = self.app_state.db.audit_logs.save_audit_log(
+            NewAuditLog::new(Some(actor.id), updated_user.id, AuditAction::UserUpdated)
+                .with_metadata(json!({ "name": updated_user.name }))
+        ).await;
+        self.response(&updated_user).await
+    }
+    pub async fn change_password(&self, actor: &User, body: UserPasswordUpdateRequest) -> Result<(), AppError> {
+        let password_match = self.app_state.hasher.verify(&body.old_password, &actor.password)
+            .map_err(|_| AppError::server_error(ErrorMessage::ServerError))?;
+        if !password_match {
+            return Err(AppError::bad_request(ErrorMessage::WrongCredentials));
+        }
+        let hash_password = self.app_state.hasher.hash(&body.new_password)
+            .map_err(|_| AppError::server_error(ErrorMessage::ServerError))?;
+        let updated_user = self.app_state.db.users.update_user_password(&actor.id, hash_password).await?;
+        let _ = self.app_state.redis_client.delete_user(&updated_user.id).await;
+        let _ = self.app_state.db.audit_logs.save_audit_log(
+            NewAuditLog::new(Some(actor.id), actor.id, AuditAction::PasswordChanged)
+        ).await;
+        Ok(())
+    }
+    pub async fn follow_unfollow(&self, user_id: Uuid, actor: &User) -> Result<FollowUnfollowResponse, AppError> {
+        if user_id == actor.id {
+            return Err(AppError::bad_request(ErrorMessage::RequestInvalid));
+        }
+        self.ensure_exists(&user_id).await?;
+        let message = self.app_state.db.users.follow_unfollow_user(user_id, actor.id).await?;
+        let (follower_id, following_id) = (actor.id, user_id);
+        match message.as_str() {
+            "Successfully Followed" => self.app_state.publish(DomainEvent::UserFollowed { follower_id, following_id }).await,
+            "Successfully Unfollowed" => self.app_state.publish(DomainEvent::UserUnfollowed { follower_id, following_id }).await,
+            _ => {}
+        }
+        Ok(FollowUnfollowResponse {
+            user_target: user_id,
+            user_sender: actor.id,
+            message,
+        })
+    }
+    pub async fn connections(&self, user_id: Uuid, actor: &User, kind: &FollowKind, params: UserConnectionParams) -> Result<PaginatedData<Connections>, AppError> {
+        self.ensure_exists(&user_id).await?;
+        self.ensure_visible(&user_id, actor).await?;
+        Ok(self.app_state.db.users.get_user_connections(user_id, kind, params).await?)
+    }
+    pub async fn activity(&self, user_id: Uuid, actor: &User, params: UserActivityParams) -> Result<PaginatedData<ActivityItem>, AppError> {
+        self.ensure_exists(&user_id).await?;
+        self.ensure_visible(&user_id, actor).await?;
+        Ok(self.app_state.db.activity.get_user_activity(user_id, actor.id, params).await?)
+    }
+    pub async fn suggestions(&self, actor: &User, params: UserSuggestionParams) -> Result<PaginatedData<UserSuggestion>, AppError> {
+        Ok(self.app_state.db.users.get_user_suggestions(actor.id, params).await?)
+    }
+    pub async fn search(&self, params: &UserSearchParams) -> Result<Vec<UserSearchResult>, AppError> {
+        Ok(self.app_state.db.users.search_users(&params.query(), params.limit()).await?)
+    }
+    pub async fn accept_follow_request(&self, requester_id: Uuid, actor: &User) -> Result<(), AppError> {
+        self.app_state.db.users.accept_follow_request(actor.id, requester_id).await?;
+        self.app_state.publish(DomainEvent::UserFollowed { follower_id: requester_id, following_id: actor.id }).await;
+        Ok(())
+    }
+    pub async fn delete(&self, user_id: Uuid, actor: &User) -> Result<(), AppError> {
+        if user_id == actor.id {
+            return Err(AppError::bad_request(ErrorMessage::RequestInvalid));
+        }
+        self.app_state.db.users.delete_user(user_id).await?;
+        let _ = self.app_state.redis_client.delete_user(&user_id).await;
+        let _ = self.app_state.db.audit_logs.save_audit_log(
+            NewAuditLog::new(Some(actor.id), user_id, AuditAction::UserDeleted)
+        ).await;
+        Ok(())
+    }
+    pub async fn feed(&self, actor: &User, params: UserFeedParams) -> Result<PaginatedData<UserFeeds>, AppError> {
+        let app_state = &self.app_state;
+        // Only the plain first page is cached, filtered or deeper pages always go to the database.
+        let cache_variant = match (params.pagination.page, &params.search, &params.since, &params.until) {
+            (1, None, None, None) => Some(format!(
+                "{}:{}:{}:{}",
+                params.pagination.limit,
+                params.order_by.as_deref().unwrap_or("DESC"),
+                params.sort.as_deref().unwrap_or("recent"),
+                params.organizations.unwrap_or(false),
+            )),
+            _ => None,
+        };
+        if let Some(variant) = &cache_variant {
+            if let Ok(Some(feed)) = app_state.redis_client.get_feed(&actor.id, variant).await {
+                metrics::increment_counter("feed_cache_requests_total", &[("result", "hit")]);
+                return Ok(feed);
+            }
+            metrics::increment_counter("feed_cache_requests_total", &[("result", "miss")]);
+        }
+        let result = app_state.db.users.get_user_feeds(actor.id, params).await?;
+        if let Some(variant) = &cache_variant {
+            let _ = app_state.redis_client.set_feed(&actor.id, variant, &result, app_state.env.feed_cache_ttl).await;
+        }
+        Ok(result)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,359 @@
+use std::{sync::Arc, time::Duration};
+use axum::{
+    Router,
+    body::{to_bytes, Body},
+    http::{header, Method, Request, StatusCode},
+};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+use uuid::Uuid;
+use crate::{
+    db::memory::InMemoryDb,
+    error::IfMatch,
+    modules::{event::publisher::LocalEventBus, redis::redis::RedisClient, role::model::RoleType, user::model::User},
+    router::create_router,
+    test_utils::{test_config, FakeMailer, JWT_SECRET},
+    utils::{jwt::{self, JwtKeys}, load::LoadMonitor, moderation, password::Argon2idHasher},
+    AppState,
+};
+
+/// The full router on in-memory repositories. Redis points at a closed port, so every cache read
+/// misses and the rate limiter lets requests through, as they do during a Redis outage.
+async fn app(db: &Arc<InMemoryDb>) -> Router {
+    let env = test_config(&[("RATE_LIMITER_MODE", "dry-run")]);
+    let redis_client = RedisClient::new(&env.redis_url, Duration::from_millis(50), 0).await.unwrap();
+    let hasher = Arc::new(Argon2idHasher::from_config(&env));
+    let moderator = moderation::from_config(&env);
+    create_router(Arc::new(AppState {
+        env,
+        db: db.repositories(),
+        redis_client,
+        mailer: Arc::new(FakeMailer::default()),
+        events: Arc::new(LocalEventBus::default()),
+        hasher,
+        moderator,
+        load: Arc::new(LoadMonitor::default()),
+    }))
+}
+
+async fn send(app: &Router, method: Method, uri: &str, user: Option<&User>, body: Option<Value>) -> (StatusCode, Value) {
+    let mut request = Request::builder().method(method).uri(uri);
+    if let Some(user) = user {
+        let token = jwt::create_token(&user.id.to_string(), &JwtKeys::hs256(JWT_SECRET.as_bytes()), 600).unwrap();
+        request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+    }
+    let body = match body {
+        Some(body) => {
+            request = request.header(header::CONTENT_TYPE, "application/json");
+            Body::from(body.to_string())
+        }
+        None => Body::empty(),
+    };
+    let response = app.clone().oneshot(request.body(body).unwrap()).await.unwrap();
+    let status = response.status();
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn self_profile_is_read_from_the_user_repository() {
+    let db = InMemoryDb::seeded();
+    let clark = db.add_user("Clark Kent", "clark@example.com", "clark123", RoleType::User);
+    let app = app(&db).await;
+    let (status, body) = send(&app, Method::GET, "/api/user/self", Some(&clark), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["email"], "clark@example.com");
+    assert_eq!(body["data"]["role"], "User");
+    assert!(body["data"].get("password").is_none(), "password leaked: {}", body);
+}
+
+#[tokio::test]
+async fn requests_without_a_token_or_for_a_deleted_user_are_unauthorized() {
+    let db = InMemoryDb::seeded();
+    let app = app(&db).await;
+    let (status, _) = send(&app, Method::GET, "/api/user/self", None, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let ghost = db.add_user("Ghost User", "ghost@example.com", "ghost123", RoleType::User);
+    let admin = db.add_user("Diana Prince", "diana@example.com", "diana123", RoleType::Admin);
+    let (status, _) = send(&app, Method::DELETE, &format!("/api/user/{}", ghost.id), Some(&admin), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = send(&app, Method::GET, "/api/user/self", Some(&ghost), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["code"], "USER_NO_LONGER_EXISTS");
+}
+
+#[tokio::test]
+async fn missing_or_bad_credentials_are_challenged_and_missing_permissions_are_forbidden() {
+    let db = InMemoryDb::seeded();
+    let clark = db.add_user("Clark Kent", "clark@example.com", "clark123", RoleType::User);
+    let lois = db.add_user("Lois Lane", "lois@example.com", "lois123", RoleType::User);
+    let app = app(&db).await;
+    let challenge = |authorization: Option<&str>, uri: &str| {
+        let mut request = Request::builder().uri(uri);
+        if let Some(authorization) = authorization {
+            request = request.header(header::AUTHORIZATION, authorization);
+        }
+        let app = app.clone();
+        async move {
+            let response = app.oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+            let challenge = response.headers().get(header::WWW_AUTHENTICATE).map(|value| value.to_str().unwrap().to_string());
+            (response.status(), challenge)
+        }
+    };
+    let (status, challenge_header) = challenge(None, "/api/user/self").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(challenge_header.as_deref(), Some("Bearer realm=\"axum-restful-api\""));
+    let (status, challenge_header) = challenge(Some("Bearer not-a-jwt"), "/api/user/self").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(challenge_header.unwrap().contains("error=\"invalid_token\""));
+    let (status, challenge_header) = challenge(None, "/api/metrics").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(challenge_header.unwrap().starts_with("Basic "));
+    for method in [Method::PUT, Method::PATCH] {
+        let body = json!({ "name": "Clark Kent" });
+        let (status, body) = send(&app, method, &format!("/api/user/{}", lois.id), Some(&clark), Some(body)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{}", body);
+        assert_eq!(body["code"], "PERMISSION_DENIED");
+    }
+    let (status, body) = send(&app, Method::GET, "/api/user/users", Some(&clark), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN, "{}", body);
+    assert_eq!(body["code"], "PERMISSION_DENIED");
+}
+
+#[tokio::test]
+async fn user_list_needs_the_admin_role() {
+    let db = InMemoryDb::seeded();
+    let clark = db.add_user("Clark Kent", "clark@example.com", "clark123", RoleType::User);
+    let admin = db.add_user("Diana Prince", "diana@example.com", "diana123", RoleType::Admin);
+    let app = app(&db).await;
+    let (status, body) = send(&app, Method::GET, "/api/user/users", Some(&clark), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN, "{}", body);
+    let (status, body) = send(&app, Method::GET, "/api/user/users?search=KENT", Some(&admin), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["items"].as_array().unwrap().len(), 1);
+    assert_eq!(body["data"]["items"][0]["id"], clark.id.to_string());
+}
+
+#[tokio::test]
+async fn user_list_returns_only_the_requested_fields() {
+    let db = InMemoryDb::seeded();
+    db.add_user("Clark Kent", "clark@example.com", "clark123", RoleType::User);
+    let admin = db.add_user("Diana Prince", "diana@example.com", "diana123", RoleType::Admin);
+    let app = app(&db).await;
+    let (status, body) = send(&app, Method::GET, "/api/user/users?fields=name,%20email", Some(&admin), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let first = body["data"]["items"][0].as_object().unwrap();
+    let mut keys: Vec<&str> = first.keys().map(String::as_str).collect();
+    keys.sort();
+    assert_eq!(keys, vec!["email", "id", "name"]);
+    assert!(body["data"]["pagination"].is_object());
+    let (status, body) = send(&app, Method::GET, "/api/user/users?fields=name,password", Some(&admin), None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+}
+
+#[tokio::test]
+async fn list_page_sizes_default_and_are_clamped() {
+    let db = InMemoryDb::seeded();
+    let admin = db.add_user("Diana Prince", "diana@example.com", "diana123", RoleType::Admin);
+    let app = app(&db).await;
+    for (query, page, limit) in [("", 1, 5), ("?limit=1000", 1, 100), ("?limit=0", 1, 1), ("?limit=20&page=2", 2, 20)] {
+        let (status, body) = send(&app, Method::GET, &format!("/api/user/users{}", query), Some(&admin), None).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["data"]["pagination"]["page"], page, "{}", query);
+        assert_eq!(body["data"]["pagination"]["limit"], limit, "{}", query);
+    }
+}
+
+#[tokio::test]
+async fn users_can_only_update_their_own_profile() {
+    let db = InMemoryDb::seeded();
+    let clark = db.add_user("Clark Kent", "clark@example.com", "clark123", RoleType::User);
+    let bruce = db.add_user("Bruce Wayne", "bruce@example.com", "bruce123", RoleType::User);
+    let app = app(&db).await;
+    let update = json!({ "name": "Kal El", "is_private": true });
+    let (status, _) = send(&app, Method::PUT, &format!("/api/user/{}", bruce.id), Some(&clark), Some(update.clone())).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(db.user(bruce.id).unwrap().name, "Bruce Wayne");
+    let (status, body) = send(&app, Method::PUT, &format!("/api/user/{}", clark.id), Some(&clark), Some(update)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let stored = db.user(clark.id).unwrap();
+    assert_eq!(stored.name, "Kal El");
+    assert!(stored.is_private);
+    assert_eq!(db.audit_actions(clark.id), vec!["user.updated"]);
+}
+
+#[tokio::test]
+async fn updates_against_a_stale_version_are_refused_with_the_current_one() {
+    let db = InMemoryDb::seeded();
+    let clark = db.add_user("Clark Kent", "clark@example.com", "clark123", RoleType::User);
+    let app = app(&db).await;
+    let token = jwt::create_token(&clark.id.to_string(), &JwtKeys::hs256(JWT_SECRET.as_bytes()), 600).unwrap();
+    let update = |name: &str, version: &str| Request::builder()
+        .method(Method::PUT)
+        .uri(format!("/api/user/{}", clark.id))
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::IF_MATCH, version)
+        .body(Body::from(json!({ "name": name }).to_string()))
+        .unwrap();
+    let stale = IfMatch::etag(clark.updated_at);
+    let response = app.clone().oneshot(update("Kal El", &stale)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let current = response.headers()[header::ETAG].to_str().unwrap().to_string();
+    assert_ne!(current, stale);
+    let response = app.clone().oneshot(update("Superman", &stale)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let body: Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+    assert_eq!(body["code"], "VERSION_CONFLICT");
+    assert_eq!(body["error"]["name"], "Kal El");
+    assert_eq!(body["error"]["role"], "User");
+    assert!(body["error"].get("password").is_none(), "{}", body);
+    assert_eq!(db.user(clark.id).unwrap().name, "Kal El");
+    let response = app.clone().oneshot(update("Superman", &current)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(db.user(clark.id).unwrap().name, "Superman");
+    let response = app.clone().oneshot(update("Clark Kent", "yesterday")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn patch_changes_only_the_given_fields_and_null_clears_them() {
+    let db = InMemoryDb::seeded();
+    let clark = db.add_user("Clark Kent", "clark@example.com", "clark123", RoleType::User);
+    let app = app(&db).await;
+    let uri = format!("/api/user/{}", clark.id);
+    let (status, body) = send(&app, Method::PATCH, &uri, Some(&clark), Some(json!({ "bio": "Daily Planet", "website": "https://dailyplanet.com" }))).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["bio"], "Daily Planet");
+    assert!(body["data"].get("password").is_none(), "{}", body);
+    let (status, body) = send(&app, Method::PATCH, &uri, Some(&clark), Some(json!({ "is_private": true, "website": null }))).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let stored = db.user(clark.id).unwrap();
+    assert_eq!(stored.name, "Clark Kent");
+    assert_eq!(stored.bio.as_deref(), Some("Daily Planet"));
+    assert_eq!(stored.website, None);
+    assert!(stored.is_private);
+    let (status, body) = send(&app, Method::PATCH, &uri, Some(&clark), Some(json!({}))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    assert_eq!(body["code"], "VALIDATION_FAILED");
+    let (status, _) = send(&app, Method::PATCH, &uri, Some(&clark), Some(json!({ "name": "Kal" }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn usernames_are_unique_handles_that_resolve_to_the_profile() {
+    let db = InMemoryDb::seeded();
+    let clark = db.add_user("Clark Kent", "clark@example.com", "clark123", RoleType::User);
+    let bruce = db.add_user("Bruce Wayne", "bruce@example.com", "bruce123", RoleType::User);
+    let app = app(&db).await;
+    let update = json!({ "name": "Clark Kent", "username": "Kal-El" });
+    let (status, body) = send(&app, Method::PUT, &format!("/api/user/{}", clark.id), Some(&clark), Some(update)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    let update = json!({ "name": "Clark Kent", "username": "kal_el", "bio": "Last son of Krypton" });
+    let (status, body) = send(&app, Method::PUT, &format!("/api/user/{}", clark.id), Some(&clark), Some(update)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let update = json!({ "name": "Bruce Wayne", "username": "kal_el" });
+    let (status, body) = send(&app, Method::PUT, &format!("/api/user/{}", bruce.id), Some(&bruce), Some(update)).await;
+    assert_eq!(status, StatusCode::CONFLICT, "{}", body);
+    assert_eq!(body["code"], "USERNAME_EXISTS");
+    let (status, body) = send(&app, Method::GET, "/api/user/by-username/Kal_El", Some(&bruce), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["id"], clark.id.to_string());
+    assert_eq!(body["data"]["bio"], "Last son of Krypton");
+    let (status, _) = send(&app, Method::GET, "/api/user/by-username/nobody", Some(&bruce), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn following_a_private_account_waits_for_approval() {
+    let db = InMemoryDb::seeded();
+    let clark = db.add_user("Clark Kent", "clark@example.com", "clark123", RoleType::User);
+    let bruce = db.add_user("Bruce Wayne", "bruce@example.com", "bruce123", RoleType::User);
+    db.set_private(bruce.id, true);
+    let app = app(&db).await;
+    let followers = format!("/api/user/{}/followers", bruce.id);
+    let (status, body) = send(&app, Method::POST, &format!("/api/user/{}/follow", bruce.id), Some(&clark), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["message"], "Follow Request Sent");
+    let (status, _) = send(&app, Method::GET, &followers, Some(&clark), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let accept = format!("/api/user/follow-requests/{}/accept", clark.id);
+    let (status, body) = send(&app, Method::POST, &accept, Some(&bruce), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (status, body) = send(&app, Method::GET, &followers, Some(&clark), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["items"][0]["id"], clark.id.to_string());
+    let (status, body) = send(&app, Method::GET, &format!("/api/user/{}", bruce.id), Some(&clark), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["followers_count"], 1);
+}
+
+#[tokio::test]
+async fn following_yourself_or_a_missing_user_is_rejected() {
+    let db = InMemoryDb::seeded();
+    let clark = db.add_user("Clark Kent", "clark@example.com", "clark123", RoleType::User);
+    let app = app(&db).await;
+    let (status, _) = send(&app, Method::POST, &format!("/api/user/{}/follow", clark.id), Some(&clark), None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = send(&app, Method::POST, &format!("/api/user/{}/follow", Uuid::new_v4()), Some(&clark), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn activity_of_a_private_or_missing_account_is_not_shown() {
+    let db = InMemoryDb::seeded();
+    let clark = db.add_user("Clark Kent", "clark@example.com", "clark123", RoleType::User);
+    let bruce = db.add_user("Bruce Wayne", "bruce@example.com", "bruce123", RoleType::User);
+    db.set_private(bruce.id, true);
+    let app = app(&db).await;
+    let (status, body) = send(&app, Method::GET, &format!("/api/user/{}/activity", bruce.id), Some(&clark), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["code"], "PRIVATE_ACCOUNT");
+    let (status, _) = send(&app, Method::GET, &format!("/api/user/{}/activity", Uuid::new_v4()), Some(&clark), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn settings_are_stored_per_user() {
+    let db = InMemoryDb::seeded();
+    let clark = db.add_user("Clark Kent", "clark@example.com", "clark123", RoleType::User);
+    let bruce = db.add_user("Bruce Wayne", "bruce@example.com", "bruce123", RoleType::User);
+    let app = app(&db).await;
+    let settings = json!({ "theme": "dark", "feed_limit": 20, "locale": "id", "timezone": "Asia/Jakarta" });
+    let (status, body) = send(&app, Method::PUT, "/api/user/settings", Some(&clark), Some(settings)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (_, body) = send(&app, Method::GET, "/api/user/settings", Some(&clark), None).await;
+    assert_eq!(body["data"], json!({
+        "theme": "dark",
+        "language": "id",
+        "timezone": "Asia/Jakarta",
+        "feed_limit": 20,
+        "feed_order_by": "DESC",
+        "email_notifications": true,
+        "login_alerts": true,
+        "email_digest": "off",
+        "analytics": true,
+    }));
+    let (_, body) = send(&app, Method::GET, "/api/user/settings", Some(&bruce), None).await;
+    assert_eq!(body["data"]["theme"], "system");
+    assert_eq!(body["data"]["timezone"], "UTC");
+    let (status, _) = send(&app, Method::PUT, "/api/user/settings", Some(&bruce), Some(json!({ "timezone": "Mars/Olympus" }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn batch_returns_users_in_request_order_and_lists_unknown_ids() {
+    let db = InMemoryDb::seeded();
+    let clark = db.add_user("Clark Kent", "clark@example.com", "clark123", RoleType::User);
+    let bruce = db.add_user("Bruce Wayne", "bruce@example.com", "bruce123", RoleType::User);
+    let app = app(&db).await;
+    let unknown = Uuid::new_v4();
+    let ids = json!({ "ids": [bruce.id, unknown, clark.id, bruce.id] });
+    let (status, body) = send(&app, Method::POST, "/api/user/batch", Some(&clark), Some(ids)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let items: Vec<&str> = body["data"]["items"].as_array().unwrap().iter().map(|user| user["id"].as_str().unwrap()).collect();
+    assert_eq!(items, vec![bruce.id.to_string(), clark.id.to_string()]);
+    assert_eq!(body["data"]["missing"], json!([unknown]));
+    let (status, _) = send(&app, Method::POST, "/api/user/batch", Some(&clark), Some(json!({ "ids": [] }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
\ No newline at end of file
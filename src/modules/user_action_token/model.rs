@@ -1,11 +1,11 @@
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use serde::{Serialize};
-use sqlx::{FromRow, Type, Error as SqlxError, query_as, query};
+use sqlx::{FromRow, Type, query_as, query};
 use uuid::Uuid;
-use crate::{db::DBClient, modules::user::model::User};
+use crate::{db::DBClient, error::RepositoryError, modules::{user::model::User, post::model::PostLicense}};
 
-#[derive(Serialize, Type)]
+#[derive(Clone, Copy, PartialEq, Serialize, Type)]
 #[sqlx(type_name = "action_type")]
 #[serde(rename_all = "kebab-case")]
 pub enum ActionType {
@@ -26,11 +26,13 @@ impl ActionType {
     }
 }
 
-#[derive(Serialize, FromRow, Type)]
+#[derive(Clone, Serialize, FromRow, Type)]
 pub struct UserActionToken {
     pub id: Uuid,
     pub user_id: Uuid,
-    pub token: Option<String>,
+    /// See `utils::action_token`, the plain token only ever goes out by email.
+    #[serde(skip_serializing)]
+    pub token_hash: Option<String>,
     pub action_type: ActionType,
     pub used_at: Option<DateTime<Utc>>,
     pub expires_at: Option<DateTime<Utc>>,
@@ -39,107 +41,128 @@ pub struct UserActionToken {
 }
 
 pub struct NewUserActionToken<'a> {
-    pub token: &'a str,
+    pub token_hash: &'a str,
     pub action_type: ActionType,
     pub expires_at: DateTime<Utc>,
 }
 
 #[async_trait]
-pub trait UserActionTokenRepository {
-    async fn get_by_token(&self, token: &str) -> Result<Option<UserActionToken>, SqlxError>;
-    async fn verify_account(&self, user_id: Uuid, user_action_id: Uuid) -> Result<User, SqlxError>;
-    async fn resend_activation(&self, user_id: Uuid, token: &str, expires_at: DateTime<Utc>) -> Result<UserActionToken, SqlxError>;
-    async fn forgot_password<'a>(&self, user_id: Uuid, user_action_data: NewUserActionToken<'a>) -> Result<UserActionToken, SqlxError>;
-    async fn reset_password(&self, user_id: Uuid, user_action_id: Uuid, new_password: String) -> Result<User, SqlxError>;
+pub trait UserActionTokenRepository: Send + Sync {
+    /// The unused token of `action_type` stored under `token_hash`.
+    async fn get_by_token(&self, token_hash: &str, action_type: ActionType) -> Result<Option<UserActionToken>, RepositoryError>;
+    /// Uses the token and verifies its account. `NotFound` when the token was used or expired in
+    /// the meantime, a token is only ever used once.
+    async fn verify_account(&self, user_id: Uuid, user_action_id: Uuid) -> Result<User, RepositoryError>;
+    /// Replaces the verification token with `token_hash` until `expires_at`, so only the newest link works.
+    async fn resend_activation(&self, user_id: Uuid, token_hash: &str, expires_at: DateTime<Utc>) -> Result<UserActionToken, RepositoryError>;
+    async fn forgot_password<'a>(&self, user_id: Uuid, user_action_data: NewUserActionToken<'a>) -> Result<UserActionToken, RepositoryError>;
+    /// Uses the token and sets the new password, `NotFound` like `verify_account`.
+    async fn reset_password(&self, user_id: Uuid, user_action_id: Uuid, new_password: String) -> Result<User, RepositoryError>;
 }
 
 #[async_trait]
 impl UserActionTokenRepository for DBClient {
-    async fn get_by_token(&self, token: &str) -> Result<Option<UserActionToken>, SqlxError> {
+    async fn get_by_token(&self, token_hash: &str, action_type: ActionType) -> Result<Option<UserActionToken>, RepositoryError> {
+        let _timer = self.time_query("user_action_token.get_by_token");
         let user_action_token = query_as!(
             UserActionToken,
             r#"
-                SELECT id, user_id, token, action_type as "action_type: ActionType", used_at, expires_at, created_at, updated_at 
-                FROM user_action_tokens WHERE token = $1 AND used_at IS NULL;
+                SELECT id, user_id, token_hash, action_type as "action_type: ActionType", used_at, expires_at, created_at, updated_at 
+                FROM user_action_tokens WHERE token_hash = $1 AND action_type = $2::text::action_type AND used_at IS NULL;
             "#,
-            token
+            token_hash,
+            action_type.get_value(),
         ).fetch_optional(&self.pool).await?;
         Ok(user_action_token)
     }
-    async fn verify_account(&self, user_id: Uuid, user_action_id: Uuid) -> Result<User, SqlxError> {
+    async fn verify_account(&self, user_id: Uuid, user_action_id: Uuid) -> Result<User, RepositoryError> {
+        let _timer = self.time_query("user_action_token.verify_account");
         let mut transaction = self.pool.begin().await?;
-        query!(
+        // Two requests with the same token: only the first one finds it unused.
+        let used = query!(
             r#"
                 UPDATE user_action_tokens 
-                SET used_at = Now(), token = NULL, expires_at = NULL, updated_at = Now()
-                WHERE id = $1
+                SET used_at = Now(), token_hash = NULL, expires_at = NULL, updated_at = Now()
+                WHERE id = $1 AND used_at IS NULL AND expires_at > Now()
             "#,
             user_action_id
         ).execute(&mut *transaction).await?;
+        if used.rows_affected() == 0 {
+            transaction.rollback().await?;
+            return Err(RepositoryError::NotFound);
+        }
         let user = query_as!(
             User,
             r#"
                 UPDATE users 
                 SET is_verified = true, updated_at = Now() WHERE id = $1
-                RETURNING id, role_id, name, email, password, is_verified, created_at, updated_at;
+                RETURNING id, role_id, name, email, password, is_verified, is_banned, is_private, username, bio, avatar_url, website, default_license AS "default_license: PostLicense", created_at, updated_at;
             "#,
             user_id
         ).fetch_one(&mut *transaction).await?;
         transaction.commit().await?;
         Ok(user)
     }
-    async fn resend_activation(&self, user_id: Uuid, token: &str, expires_at: DateTime<Utc>) -> Result<UserActionToken, SqlxError> {
+    async fn resend_activation(&self, user_id: Uuid, token_hash: &str, expires_at: DateTime<Utc>) -> Result<UserActionToken, RepositoryError> {
+        let _timer = self.time_query("user_action_token.resend_activation");
         let user_action_token = query_as!(
             UserActionToken,
             r#"
                 UPDATE user_action_tokens
-                SET token = $1, expires_at = $2, updated_at = Now()
+                SET token_hash = $1, expires_at = $2, used_at = NULL, updated_at = Now()
                 WHERE user_id = $3 AND action_type = 'verify-account'
-                RETURNING id, user_id, token, action_type as "action_type: ActionType", used_at, expires_at, created_at, updated_at;
+                RETURNING id, user_id, token_hash, action_type as "action_type: ActionType", used_at, expires_at, created_at, updated_at;
             "#,
-            token,
+            token_hash,
             expires_at,
             user_id,
         ).fetch_one(&self.pool).await?;
         Ok(user_action_token)
     }
-    async fn forgot_password<'a>(&self, user_id: Uuid, user_action_data: NewUserActionToken<'a>) -> Result<UserActionToken, SqlxError> {
+    async fn forgot_password<'a>(&self, user_id: Uuid, user_action_data: NewUserActionToken<'a>) -> Result<UserActionToken, RepositoryError> {
+        let _timer = self.time_query("user_action_token.forgot_password");
         let user_action_token = query_as!(
             UserActionToken,
             r#"
-                INSERT INTO user_action_tokens (user_id, token, action_type, expires_at)
+                INSERT INTO user_action_tokens (user_id, token_hash, action_type, expires_at)
                 VALUES ($1, $2, $3::text::action_type, $4)
                 ON CONFLICT (user_id, action_type)
                 DO UPDATE SET 
-                    token = excluded.token, 
+                    token_hash = excluded.token_hash, 
                     used_at = NULL,
                     expires_at = excluded.expires_at, 
                     updated_at = Now()
-                RETURNING id, user_id, token, action_type as "action_type: ActionType", used_at, expires_at, created_at, updated_at;
+                RETURNING id, user_id, token_hash, action_type as "action_type: ActionType", used_at, expires_at, created_at, updated_at;
             "#,
             user_id,
-            user_action_data.token,
+            user_action_data.token_hash,
             user_action_data.action_type.get_value(),
             user_action_data.expires_at
         ).fetch_one(&self.pool).await?;
         Ok(user_action_token)
     }
-    async fn reset_password(&self, user_id: Uuid, user_action_id: Uuid, new_password: String) -> Result<User, SqlxError> {
+    async fn reset_password(&self, user_id: Uuid, user_action_id: Uuid, new_password: String) -> Result<User, RepositoryError> {
+        let _timer = self.time_query("user_action_token.reset_password");
         let mut transaction = self.pool.begin().await?;
-        query!(
+        // Two requests with the same token: only the first one finds it unused.
+        let used = query!(
             r#"
                 UPDATE user_action_tokens 
-                SET token = NULL, used_at = Now(), expires_at = NULL, updated_at = Now()
-                WHERE id = $1
+                SET used_at = Now(), token_hash = NULL, expires_at = NULL, updated_at = Now()
+                WHERE id = $1 AND used_at IS NULL AND expires_at > Now()
             "#,
             user_action_id
         ).execute(&mut *transaction).await?;
+        if used.rows_affected() == 0 {
+            transaction.rollback().await?;
+            return Err(RepositoryError::NotFound);
+        }
         let user = query_as!(
             User,
             r#"
                 UPDATE users 
                 SET password = $1, updated_at = Now() WHERE id = $2
-                RETURNING id, role_id, name, email, password, is_verified, created_at, updated_at;
+                RETURNING id, role_id, name, email, password, is_verified, is_banned, is_private, username, bio, avatar_url, website, default_license AS "default_license: PostLicense", created_at, updated_at;
             "#,
             new_password,
             user_id
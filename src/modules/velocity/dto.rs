@@ -0,0 +1,13 @@
+use serde::Deserialize;
+use validator::Validate;
+use crate::modules::velocity::model::VelocityAction;
+
+/// Missing fields keep their value.
+#[derive(Deserialize, Validate)]
+pub struct VelocityLimitUpdateRequest {
+    #[validate(range(min = 1, max = 100000, message = "Max count must be between 1 and 100000"))]
+    pub max_count: Option<i32>,
+    #[validate(range(min = 1, max = 86400, message = "Window must be between 1 and 86400 seconds"))]
+    pub window_secs: Option<i32>,
+    pub action: Option<VelocityAction>,
+}
\ No newline at end of file
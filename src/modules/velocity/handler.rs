@@ -0,0 +1,39 @@
+use std::sync::Arc;
+use axum::{middleware, Router, routing::{get, put}, Extension, response::IntoResponse};
+use crate::{
+    AppState,
+    dto::{HttpResult, SuccessResponse},
+    error::{PathParser, ValidatedJson},
+    middleware::{AuthenticatedUser, permission::{check_permission, Permission}},
+    modules::velocity::{dto::VelocityLimitUpdateRequest, model::VelocityKind},
+};
+
+pub fn velocity_admin_router() -> Router {
+    Router::new()
+        .route("/velocity-limits", get(velocity_limit_list).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminVelocityLimitManage.to_string())
+        })))
+        .route("/velocity-limits/{kind}", put(velocity_limit_update).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminVelocityLimitManage.to_string())
+        })))
+}
+
+async fn velocity_limit_list(
+    Extension(app_state): Extension<Arc<AppState>>,
+) -> HttpResult<impl IntoResponse> {
+    let limits = app_state.db.velocity_limits.get_velocity_limits().await?;
+    Ok(
+        SuccessResponse::new("Getting velocity limit list data", Some(limits))
+    )
+}
+async fn velocity_limit_update(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    PathParser(kind): PathParser<VelocityKind>,
+    ValidatedJson(body): ValidatedJson<VelocityLimitUpdateRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let limit = app_state.db.velocity_limits.update_velocity_limit(kind, body, user_auth.user.id).await?;
+    Ok(
+        SuccessResponse::new("Successfully updated the velocity limit.", Some(limit))
+    )
+}
\ No newline at end of file
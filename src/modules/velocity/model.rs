@@ -0,0 +1,93 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Type, query_as};
+use uuid::Uuid;
+use crate::{
+    db::DBClient,
+    error::RepositoryError,
+    modules::velocity::dto::VelocityLimitUpdateRequest,
+};
+
+#[derive(Serialize, Deserialize, Type, Clone, Copy, PartialEq, Debug)]
+#[sqlx(type_name = "velocity_kind", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum VelocityKind {
+    Post,
+    Comment,
+}
+
+impl VelocityKind {
+    pub fn get_value(&self) -> &'static str {
+        match self {
+            VelocityKind::Post => "post",
+            VelocityKind::Comment => "comment",
+        }
+    }
+}
+
+/// What happens to the writes past `max_count`.
+#[derive(Serialize, Deserialize, Type, Clone, Copy, PartialEq, Debug)]
+#[sqlx(type_name = "velocity_action", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum VelocityAction {
+    /// Refused with 429 until the window ends.
+    Throttle,
+    /// Published without telling the author, and reported to the admins as spam.
+    Flag,
+}
+
+#[derive(Serialize, Deserialize, FromRow, Clone)]
+pub struct VelocityLimit {
+    pub kind: VelocityKind,
+    /// Posts or comments one user may write per window.
+    pub max_count: i32,
+    pub window_secs: i32,
+    pub action: VelocityAction,
+    pub updated_by: Option<Uuid>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait VelocityLimitRepository: Send + Sync {
+    async fn get_velocity_limits(&self) -> Result<Vec<VelocityLimit>, RepositoryError>;
+    /// Changes the fields set in `data`, the others keep their value.
+    async fn update_velocity_limit(&self, kind: VelocityKind, data: VelocityLimitUpdateRequest, admin_id: Uuid) -> Result<VelocityLimit, RepositoryError>;
+}
+
+#[async_trait]
+impl VelocityLimitRepository for DBClient {
+    async fn get_velocity_limits(&self) -> Result<Vec<VelocityLimit>, RepositoryError> {
+        let _timer = self.time_query("velocity.get_velocity_limits");
+        let limits = query_as!(
+            VelocityLimit,
+            r#"
+                SELECT kind AS "kind: VelocityKind", max_count, window_secs, action AS "action: VelocityAction",
+                       updated_by, updated_at
+                FROM velocity_limits
+                ORDER BY kind;
+            "#
+        ).fetch_all(&self.pool).await?;
+        Ok(limits)
+    }
+    async fn update_velocity_limit(&self, kind: VelocityKind, data: VelocityLimitUpdateRequest, admin_id: Uuid) -> Result<VelocityLimit, RepositoryError> {
+        let _timer = self.time_query("velocity.update_velocity_limit");
+        let limit = query_as!(
+            VelocityLimit,
+            r#"
+                UPDATE velocity_limits
+                SET max_count = COALESCE($2, max_count), window_secs = COALESCE($3, window_secs),
+                    action = COALESCE($4, action), updated_by = $5, updated_at = Now()
+                WHERE kind = $1
+                RETURNING kind AS "kind: VelocityKind", max_count, window_secs, action AS "action: VelocityAction",
+                          updated_by, updated_at;
+            "#,
+            kind as VelocityKind,
+            data.max_count,
+            data.window_secs,
+            data.action as Option<VelocityAction>,
+            admin_id,
+        ).fetch_optional(&self.pool).await?.ok_or(RepositoryError::NotFound)?;
+        Ok(limit)
+    }
+}
\ No newline at end of file
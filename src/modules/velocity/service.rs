@@ -0,0 +1,55 @@
+use std::sync::Arc;
+use log::warn;
+use crate::{
+    AppState,
+    error::{AppError, ErrorMessage},
+    middleware::permission::{has_permission, Permission},
+    modules::{
+        user::model::User,
+        velocity::model::{VelocityAction, VelocityKind},
+    },
+};
+
+/// Counts a new post or comment of `actor` against the `velocity_limits` row of `kind`, in fixed
+/// windows kept in Redis. Past a `throttle` limit the write is refused with 429 `POSTING_TOO_FAST`
+/// until the window ends. Past a `flag` limit it goes through, and the first write over the limit
+/// in each window returns the details to file with `moderation::report_flagged`.
+///
+/// Roles with `post:velocity-exempt` are not counted. When the limits or the counter can't be
+/// read the write goes through, spam detection never takes posting down with it.
+pub async fn check_velocity(app_state: &Arc<AppState>, actor: &User, kind: VelocityKind) -> Result<Option<String>, AppError> {
+    if has_permission(app_state, actor.role_id, Permission::PostVelocityExempt).await.unwrap_or(false) {
+        return Ok(None);
+    }
+    let limit = match app_state.db.velocity_limits.get_velocity_limits().await {
+        Ok(limits) => limits.into_iter().find(|limit| limit.kind == kind),
+        Err(e) => {
+            warn!("Failed to load the velocity limits, not counting the {}: {}", kind.get_value(), e);
+            None
+        }
+    };
+    let Some(limit) = limit else {
+        return Ok(None);
+    };
+    let key = format!("velocity:{}:{}", kind.get_value(), actor.id);
+    let count = match app_state.redis_client.hit_rate_limit(&key, limit.window_secs as i64).await {
+        Ok(count) => count as i64,
+        Err(e) => {
+            warn!("Failed to count the {} velocity of user {}: {}", kind.get_value(), actor.id, e);
+            return Ok(None);
+        }
+    };
+    if count <= limit.max_count as i64 {
+        return Ok(None);
+    }
+    match limit.action {
+        VelocityAction::Throttle => {
+            let retry_after = app_state.redis_client.rate_limit_ttl(&key).await.ok().flatten().unwrap_or(1);
+            Err(AppError::too_many_request(ErrorMessage::PostingTooFast(retry_after)))
+        }
+        VelocityAction::Flag if count == limit.max_count as i64 + 1 => Ok(Some(format!(
+            "Posting velocity: more than {} {}s in {} seconds.", limit.max_count, kind.get_value(), limit.window_secs
+        ))),
+        VelocityAction::Flag => Ok(None),
+    }
+}
\ No newline at end of file
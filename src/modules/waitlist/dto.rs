@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use crate::modules::waitlist::model::WaitlistEntry;
+
+#[derive(Deserialize, Validate)]
+pub struct WaitlistApproveRequest {
+    #[validate(range(min = 1, max = 1000, message = "Count must be between 1 and 1000."))]
+    pub count: i64,
+}
+
+#[derive(Serialize)]
+pub struct WaitlistApproval {
+    pub approved: Vec<WaitlistEntry>,
+    pub remaining: i64,
+}
\ No newline at end of file
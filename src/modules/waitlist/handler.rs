@@ -0,0 +1,67 @@
+use std::sync::Arc;
+use axum::{middleware, Router, routing::post, Extension, response::IntoResponse};
+use log::warn;
+use crate::{
+    AppState,
+    dto::{HttpResult, SuccessResponse},
+    error::ValidatedJson,
+    middleware::{AuthenticatedUser, permission::{check_permission, Permission}},
+    modules::{
+        audit_log::model::{AuditAction, NewAuditLog},
+        email::mail_waitlist::{send_waitlist_approved_email, send_waitlist_email},
+        user::dto::EmailKind,
+        waitlist::{dto::WaitlistApproveRequest, model::WaitlistEntry},
+    },
+};
+
+pub fn waitlist_admin_router() -> Router {
+    Router::new()
+        .route("/waitlist/approve", post(waitlist_approve).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminWaitlistApprove.to_string())
+        })))
+}
+
+/// Sends the sign-up position email in the background, sign-up does not wait on SMTP for it.
+pub fn queue_position_email(app_state: Arc<AppState>, user: WaitlistEntry, position: i64) {
+    tokio::spawn(async move {
+        if !app_state.user_service().email_allowed(user.id, EmailKind::Waitlist).await {
+            return;
+        }
+        if let Err(e) = send_waitlist_email(&*app_state.mailer, &user.email, &user.name, position).await {
+            warn!("Failed to send waitlist email to {}: {}", user.id, e);
+            return;
+        }
+        let _ = app_state.db.audit_logs.save_audit_log(NewAuditLog::new(None, user.id, AuditAction::WaitlistEmailSent)).await;
+    });
+}
+
+/// A batch can be large, so approval emails go out one by one after the response is sent.
+fn queue_approved_emails(app_state: Arc<AppState>, users: Vec<WaitlistEntry>) {
+    tokio::spawn(async move {
+        for user in users {
+            if !app_state.user_service().email_allowed(user.id, EmailKind::Waitlist).await {
+                continue;
+            }
+            if let Err(e) = send_waitlist_approved_email(&*app_state.mailer, &user.email, &user.name).await {
+                warn!("Failed to send waitlist approval email to {}: {}", user.id, e);
+            }
+        }
+    });
+}
+
+async fn waitlist_approve(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    ValidatedJson(body): ValidatedJson<WaitlistApproveRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let result = app_state.db.waitlist.approve_waitlist(body.count).await?;
+    for user in &result.approved {
+        let _ = app_state.db.audit_logs.save_audit_log(
+            NewAuditLog::new(Some(user_auth.user.id), user.id, AuditAction::WaitlistApproved)
+        ).await;
+    }
+    queue_approved_emails(app_state.clone(), result.approved.clone());
+    Ok(
+        SuccessResponse::new("Successfully approved the next waitlist batch.", Some(result))
+    )
+}
\ No newline at end of file
@@ -0,0 +1,71 @@
+use async_trait::async_trait;
+use serde::Serialize;
+use sqlx::{query_as, query_scalar};
+use uuid::Uuid;
+use crate::{
+    db::DBClient,
+    error::RepositoryError,
+    modules::waitlist::dto::WaitlistApproval,
+};
+
+#[derive(Serialize, Clone)]
+pub struct WaitlistEntry {
+    pub id: Uuid,
+    pub name: String,
+    pub email: String,
+}
+
+#[async_trait]
+pub trait WaitlistRepository: Send + Sync {
+    async fn get_waitlist_position(&self, user_id: Uuid) -> Result<Option<i64>, RepositoryError>;
+    async fn approve_waitlist(&self, count: i64) -> Result<WaitlistApproval, RepositoryError>;
+}
+
+#[async_trait]
+impl WaitlistRepository for DBClient {
+    async fn get_waitlist_position(&self, user_id: Uuid) -> Result<Option<i64>, RepositoryError> {
+        let _timer = self.time_query("waitlist.get_waitlist_position");
+        let position = query_scalar!(
+            r#"
+                SELECT (
+                    SELECT COUNT(*) FROM users AS w
+                    WHERE w.waitlisted_at IS NOT NULL AND (w.waitlisted_at, w.id) <= (u.waitlisted_at, u.id)
+                ) AS "position!"
+                FROM users AS u
+                WHERE u.id = $1 AND u.waitlisted_at IS NOT NULL;
+            "#,
+            user_id
+        ).fetch_optional(&self.pool).await?;
+        Ok(position)
+    }
+    async fn approve_waitlist(&self, count: i64) -> Result<WaitlistApproval, RepositoryError> {
+        let _timer = self.time_query("waitlist.approve_waitlist");
+        let mut transaction = self.pool.begin().await?;
+        // SKIP LOCKED lets two admins approve at the same time without handing out the same accounts.
+        let approved = query_as!(
+            WaitlistEntry,
+            r#"
+                UPDATE users SET waitlisted_at = NULL, updated_at = Now()
+                WHERE id IN (
+                    SELECT id FROM users
+                    WHERE waitlisted_at IS NOT NULL
+                    ORDER BY waitlisted_at, id
+                    LIMIT $1
+                    FOR UPDATE SKIP LOCKED
+                )
+                RETURNING id, name, email;
+            "#,
+            count
+        ).fetch_all(&mut *transaction).await?;
+        let remaining = query_scalar!(
+            r#"
+                SELECT COUNT(*) AS "count!" FROM users WHERE waitlisted_at IS NOT NULL;
+            "#
+        ).fetch_one(&mut *transaction).await?;
+        transaction.commit().await?;
+        Ok(WaitlistApproval {
+            approved,
+            remaining,
+        })
+    }
+}
\ No newline at end of file
@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
+use crate::{
+    config::Config,
+    dto::{default_order_by, Paginated, Pagination},
+    modules::{
+        user::dto::validate_order_by,
+        webhook::model::{WebhookDeliveryStatus, WebhookEndpoint, WebhookEvent},
+    },
+};
+
+fn validate_webhook_url(value: &str) -> Result<(), ValidationError> {
+    match reqwest::Url::parse(value) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.host().is_some() => Ok(()),
+        _ => {
+            let mut error = ValidationError::new("invalid_url");
+            error.message = Some("Url must be an absolute http or https url".into());
+            Err(error)
+        }
+    }
+}
+
+#[derive(Deserialize, Validate)]
+pub struct WebhookEndpointRequest {
+    #[validate(length(max = 500, message = "Url must be at most 500 characters"))]
+    #[validate(custom(function = "validate_webhook_url"))]
+    pub url: String,
+    #[validate(length(min = 1, message = "At least one event is required"))]
+    pub events: Vec<WebhookEvent>,
+    /// Generated when missing. Use the `whsec_<base64>` format to share it with Standard Webhooks libraries.
+    #[validate(length(min = 16, max = 255, message = "Secret must be between 16 and 255 characters"))]
+    pub secret: Option<String>,
+}
+
+/// The only response that includes the secret, the endpoint list never shows it again.
+#[derive(Serialize)]
+pub struct WebhookEndpointCreated {
+    #[serde(flatten)]
+    pub endpoint: WebhookEndpoint,
+    pub secret: String,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct WebhookDeliveryListParams {
+    pub limit: Option<usize>,
+    #[validate(range(min = 1, max = 100000, message = "Page must be between 1 and 100000."))]
+    pub page: Option<usize>,
+    #[serde(default = "default_order_by")]
+    #[validate(custom(function = "validate_order_by"))]
+    pub order_by: Option<String>,
+    pub endpoint_id: Option<Uuid>,
+    pub status: Option<WebhookDeliveryStatus>,
+    pub event: Option<WebhookEvent>,
+    #[serde(skip)]
+    pub pagination: Pagination,
+}
+impl Paginated for WebhookDeliveryListParams {
+    fn paginate(&mut self, config: &Config) {
+        self.pagination = Pagination::sanitize(self.page, self.limit, config);
+    }
+}
+
+pub struct NewWebhookEndpoint {
+    pub url: String,
+    pub secret: String,
+    pub events: Vec<WebhookEvent>,
+    pub created_by: Uuid,
+}
\ No newline at end of file
@@ -0,0 +1,102 @@
+use std::sync::Arc;
+use axum::{middleware, Router, routing::{delete, get, post}, Extension, response::IntoResponse, http::StatusCode};
+use base64::{Engine as _, engine::general_purpose};
+use log::warn;
+use serde_json::Value;
+use uuid::Uuid;
+use crate::{
+    AppState,
+    dto::{HttpResult, SuccessResponse},
+    error::{ValidatedJson, PathParser, PaginatedQuery},
+    middleware::{AuthenticatedUser, permission::{check_permission, Permission}},
+    modules::webhook::{
+        dto::{NewWebhookEndpoint, WebhookDeliveryListParams, WebhookEndpointCreated, WebhookEndpointRequest},
+        model::WebhookEvent,
+    },
+    utils::rand::generate_random_string,
+};
+
+pub fn webhook_admin_router() -> Router {
+    Router::new()
+        .route("/webhooks", post(webhook_create).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminWebhookManage.to_string())
+        })))
+        .route("/webhooks", get(webhook_list).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminWebhookManage.to_string())
+        })))
+        .route("/webhooks/{id}", delete(webhook_delete).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminWebhookManage.to_string())
+        })))
+        .route("/webhooks/deliveries", get(webhook_delivery_list).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminWebhookDeliveries.to_string())
+        })))
+        .route("/webhooks/deliveries/{id}/retry", post(webhook_delivery_retry).layer(middleware::from_fn(|state, req, next| {
+            check_permission(state, req, next, Permission::AdminWebhookDeliveries.to_string())
+        })))
+}
+
+/// Queues `data` for the endpoints subscribed to `event` without holding up the request, the
+/// delivery job sends it on its next tick.
+pub fn queue_webhook_event(app_state: Arc<AppState>, event: WebhookEvent, data: Value) {
+    tokio::spawn(async move {
+        if let Err(e) = app_state.db.webhooks.queue_webhook_event(event, data).await {
+            warn!("Failed to queue {} webhook deliveries: {}", event.get_value(), e);
+        }
+    });
+}
+
+async fn webhook_create(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user_auth): Extension<AuthenticatedUser>,
+    ValidatedJson(body): ValidatedJson<WebhookEndpointRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let secret = body.secret.unwrap_or_else(|| {
+        format!("whsec_{}", general_purpose::STANDARD.encode(generate_random_string(32)))
+    });
+    let new_endpoint = NewWebhookEndpoint {
+        url: body.url,
+        secret: secret.clone(),
+        events: body.events,
+        created_by: user_auth.user.id,
+    };
+    let endpoint = app_state.db.webhooks.save_webhook_endpoint(new_endpoint).await?;
+    Ok((
+        StatusCode::CREATED,
+        SuccessResponse::new("Successfully registered the webhook endpoint.", Some(WebhookEndpointCreated { endpoint, secret }))
+    ))
+}
+async fn webhook_list(
+    Extension(app_state): Extension<Arc<AppState>>,
+) -> HttpResult<impl IntoResponse> {
+    let endpoints = app_state.db.webhooks.get_webhook_endpoints().await?;
+    Ok(
+        SuccessResponse::new("Getting webhook endpoint list data", Some(endpoints))
+    )
+}
+async fn webhook_delete(
+    Extension(app_state): Extension<Arc<AppState>>,
+    PathParser(endpoint_id): PathParser<Uuid>,
+) -> HttpResult<impl IntoResponse> {
+    app_state.db.webhooks.delete_webhook_endpoint(endpoint_id).await?;
+    Ok(
+        SuccessResponse::<()>::new("Successfully removed the webhook endpoint.", None)
+    )
+}
+async fn webhook_delivery_list(
+    Extension(app_state): Extension<Arc<AppState>>,
+    PaginatedQuery(query_params): PaginatedQuery<WebhookDeliveryListParams>,
+) -> HttpResult<impl IntoResponse> {
+    let result = app_state.db.webhooks.get_webhook_deliveries(query_params).await?;
+    Ok(
+        SuccessResponse::new("Getting webhook delivery log", Some(result))
+    )
+}
+async fn webhook_delivery_retry(
+    Extension(app_state): Extension<Arc<AppState>>,
+    PathParser(delivery_id): PathParser<Uuid>,
+) -> HttpResult<impl IntoResponse> {
+    let delivery = app_state.db.webhooks.retry_webhook_delivery(delivery_id).await?;
+    Ok(
+        SuccessResponse::new("Successfully queued the webhook delivery again.", Some(delivery))
+    )
+}
\ No newline at end of file
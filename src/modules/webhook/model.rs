@@ -0,0 +1,279 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{FromRow, Type, Postgres, QueryBuilder, query, query_as};
+use uuid::Uuid;
+use crate::{
+    db::DBClient,
+    dto::{PaginatedData, PaginationMeta},
+    error::RepositoryError,
+    modules::webhook::dto::{NewWebhookEndpoint, WebhookDeliveryListParams},
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum WebhookEvent {
+    #[serde(rename = "user.created")]
+    UserCreated,
+    #[serde(rename = "post.created")]
+    PostCreated,
+    #[serde(rename = "comment.created")]
+    CommentCreated,
+    #[serde(rename = "user.followed")]
+    UserFollowed,
+}
+
+impl WebhookEvent {
+    pub fn get_value(&self) -> &'static str {
+        match self {
+            WebhookEvent::UserCreated => "user.created",
+            WebhookEvent::PostCreated => "post.created",
+            WebhookEvent::CommentCreated => "comment.created",
+            WebhookEvent::UserFollowed => "user.followed",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "webhook_delivery_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookDeliveryStatus {
+    Pending,
+    Delivered,
+    /// Out of attempts, kept for the delivery log until an admin retries it.
+    Dead,
+}
+
+#[derive(Serialize, FromRow, Clone)]
+pub struct WebhookEndpoint {
+    pub id: Uuid,
+    pub url: String,
+    pub events: Vec<String>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, FromRow, Clone)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub endpoint_id: Uuid,
+    pub event: String,
+    pub payload: Value,
+    pub status: WebhookDeliveryStatus,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A pending delivery claimed by the delivery job, with what it needs to send and sign it.
+pub struct DueWebhookDelivery {
+    pub id: Uuid,
+    pub event: String,
+    pub payload: Value,
+    pub attempts: i32,
+    pub created_at: DateTime<Utc>,
+    pub url: String,
+    pub secret: String,
+}
+
+#[async_trait]
+pub trait WebhookRepository: Send + Sync {
+    async fn save_webhook_endpoint(&self, data: NewWebhookEndpoint) -> Result<WebhookEndpoint, RepositoryError>;
+    async fn get_webhook_endpoints(&self) -> Result<Vec<WebhookEndpoint>, RepositoryError>;
+    async fn delete_webhook_endpoint(&self, endpoint_id: Uuid) -> Result<(), RepositoryError>;
+    /// Queues one delivery of `data` for every endpoint subscribed to `event`.
+    async fn queue_webhook_event(&self, event: WebhookEvent, data: Value) -> Result<u64, RepositoryError>;
+    /// Claims up to `limit` due deliveries by pushing their next attempt `lease_secs` into the future,
+    /// so another instance polling at the same time skips them.
+    async fn claim_webhook_deliveries(&self, limit: i64, lease_secs: f64) -> Result<Vec<DueWebhookDelivery>, RepositoryError>;
+    async fn mark_webhook_delivered(&self, delivery_id: Uuid, status_code: i32) -> Result<(), RepositoryError>;
+    /// Counts a failed attempt. The delivery is tried again at `retry_at`, or dead-lettered when it is `None`.
+    async fn mark_webhook_failed(&self, delivery_id: Uuid, status_code: Option<i32>, error: &str, retry_at: Option<DateTime<Utc>>) -> Result<(), RepositoryError>;
+    async fn get_webhook_deliveries(&self, params: WebhookDeliveryListParams) -> Result<PaginatedData<WebhookDelivery>, RepositoryError>;
+    /// Puts a dead delivery back in the queue with a fresh set of attempts. Other deliveries are not found.
+    async fn retry_webhook_delivery(&self, delivery_id: Uuid) -> Result<WebhookDelivery, RepositoryError>;
+}
+
+#[async_trait]
+impl WebhookRepository for DBClient {
+    async fn save_webhook_endpoint(&self, data: NewWebhookEndpoint) -> Result<WebhookEndpoint, RepositoryError> {
+        let _timer = self.time_query("webhook.save_webhook_endpoint");
+        let events: Vec<String> = data.events.iter().map(|event| event.get_value().to_string()).collect();
+        let endpoint = query_as!(
+            WebhookEndpoint,
+            r#"
+                INSERT INTO webhook_endpoints (url, secret, events, created_by)
+                VALUES ($1, $2, $3, $4)
+                RETURNING id, url, events, created_by, created_at, updated_at;
+            "#,
+            data.url,
+            data.secret,
+            &events,
+            data.created_by,
+        ).fetch_one(&self.pool).await?;
+        Ok(endpoint)
+    }
+    async fn get_webhook_endpoints(&self) -> Result<Vec<WebhookEndpoint>, RepositoryError> {
+        let _timer = self.time_query("webhook.get_webhook_endpoints");
+        let endpoints = query_as!(
+            WebhookEndpoint,
+            r#"
+                SELECT id, url, events, created_by, created_at, updated_at
+                FROM webhook_endpoints
+                ORDER BY created_at;
+            "#
+        ).fetch_all(&self.pool).await?;
+        Ok(endpoints)
+    }
+    async fn delete_webhook_endpoint(&self, endpoint_id: Uuid) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("webhook.delete_webhook_endpoint");
+        let result = query!(
+            r#"
+                DELETE FROM webhook_endpoints WHERE id = $1;
+            "#,
+            endpoint_id
+        ).execute(&self.pool).await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(())
+    }
+    async fn queue_webhook_event(&self, event: WebhookEvent, data: Value) -> Result<u64, RepositoryError> {
+        let _timer = self.time_query("webhook.queue_webhook_event");
+        let result = query!(
+            r#"
+                INSERT INTO webhook_deliveries (endpoint_id, event, payload)
+                SELECT id, $1::TEXT, $2 FROM webhook_endpoints WHERE $1::TEXT = ANY(events);
+            "#,
+            event.get_value(),
+            data,
+        ).execute(&self.pool).await?;
+        Ok(result.rows_affected())
+    }
+    async fn claim_webhook_deliveries(&self, limit: i64, lease_secs: f64) -> Result<Vec<DueWebhookDelivery>, RepositoryError> {
+        let _timer = self.time_query("webhook.claim_webhook_deliveries");
+        let deliveries = query_as!(
+            DueWebhookDelivery,
+            r#"
+                UPDATE webhook_deliveries AS d
+                SET next_attempt_at = Now() + make_interval(secs => $2), updated_at = Now()
+                FROM webhook_endpoints AS e
+                WHERE e.id = d.endpoint_id AND d.id IN (
+                    SELECT id FROM webhook_deliveries
+                    WHERE status = 'pending' AND next_attempt_at <= Now()
+                    ORDER BY next_attempt_at
+                    LIMIT $1
+                    FOR UPDATE SKIP LOCKED
+                )
+                RETURNING d.id, d.event, d.payload, d.attempts, d.created_at, e.url, e.secret;
+            "#,
+            limit,
+            lease_secs,
+        ).fetch_all(&self.pool).await?;
+        Ok(deliveries)
+    }
+    async fn mark_webhook_delivered(&self, delivery_id: Uuid, status_code: i32) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("webhook.mark_webhook_delivered");
+        query!(
+            r#"
+                UPDATE webhook_deliveries
+                SET status = 'delivered', attempts = attempts + 1, last_status_code = $2, last_error = NULL,
+                    delivered_at = Now(), updated_at = Now()
+                WHERE id = $1;
+            "#,
+            delivery_id,
+            status_code,
+        ).execute(&self.pool).await?;
+        Ok(())
+    }
+    async fn mark_webhook_failed(&self, delivery_id: Uuid, status_code: Option<i32>, error: &str, retry_at: Option<DateTime<Utc>>) -> Result<(), RepositoryError> {
+        let _timer = self.time_query("webhook.mark_webhook_failed");
+        query!(
+            r#"
+                UPDATE webhook_deliveries
+                SET status = CASE WHEN $4::TIMESTAMPTZ IS NULL
+                        THEN 'dead'::webhook_delivery_status
+                        ELSE 'pending'::webhook_delivery_status
+                    END,
+                    attempts = attempts + 1, last_status_code = $2, last_error = $3,
+                    next_attempt_at = COALESCE($4, next_attempt_at), updated_at = Now()
+                WHERE id = $1;
+            "#,
+            delivery_id,
+            status_code,
+            error,
+            retry_at,
+        ).execute(&self.pool).await?;
+        Ok(())
+    }
+    async fn get_webhook_deliveries(&self, params: WebhookDeliveryListParams) -> Result<PaginatedData<WebhookDelivery>, RepositoryError> {
+        let _timer = self.time_query("webhook.get_webhook_deliveries");
+        let limit = params.pagination.limit as i32;
+        let page = params.pagination.page as i32;
+        let offset = params.pagination.offset() as i32;
+        let order_by = params.order_by.unwrap_or("DESC".to_string());
+        let mut transaction = self.pool.begin().await?;
+        let mut query_builder_items: QueryBuilder<Postgres> = QueryBuilder::new(
+            "\
+            SELECT id, endpoint_id, event, payload, status, attempts, next_attempt_at, last_status_code, last_error, \
+            delivered_at, created_at, updated_at \
+            FROM webhook_deliveries WHERE 1 = 1\
+            "
+        );
+        let mut query_builder_count: QueryBuilder<Postgres> = QueryBuilder::new(
+            "SELECT COUNT(*) FROM webhook_deliveries WHERE 1 = 1"
+        );
+        for query_builder in [&mut query_builder_items, &mut query_builder_count] {
+            if let Some(endpoint_id) = params.endpoint_id {
+                query_builder
+                    .push(" AND endpoint_id = ")
+                    .push_bind(endpoint_id);
+            }
+            if let Some(status) = params.status {
+                query_builder
+                    .push(" AND status = ")
+                    .push_bind(status);
+            }
+            if let Some(event) = params.event {
+                query_builder
+                    .push(" AND event = ")
+                    .push_bind(event.get_value());
+            }
+        }
+        query_builder_items
+            .push(" ORDER BY created_at ")
+            .push(order_by)
+            .push(" LIMIT ")
+            .push_bind(limit)
+            .push(" OFFSET ")
+            .push_bind(offset);
+        let items = query_builder_items.build_query_as::<WebhookDelivery>().fetch_all(&mut *transaction).await?;
+        let total_items = query_builder_count.build_query_scalar::<i64>().fetch_one(&mut *transaction).await?;
+        transaction.commit().await?;
+        let pagination = PaginationMeta::new(page, limit, total_items);
+        Ok(PaginatedData {
+            items,
+            pagination,
+        })
+    }
+    async fn retry_webhook_delivery(&self, delivery_id: Uuid) -> Result<WebhookDelivery, RepositoryError> {
+        let _timer = self.time_query("webhook.retry_webhook_delivery");
+        let delivery = query_as!(
+            WebhookDelivery,
+            r#"
+                UPDATE webhook_deliveries
+                SET status = 'pending', attempts = 0, next_attempt_at = Now(), updated_at = Now()
+                WHERE id = $1 AND status = 'dead'
+                RETURNING id, endpoint_id, event, payload, status AS "status: WebhookDeliveryStatus", attempts,
+                          next_attempt_at, last_status_code, last_error, delivered_at, created_at, updated_at;
+            "#,
+            delivery_id,
+        ).fetch_optional(&self.pool).await?.ok_or(RepositoryError::NotFound)?;
+        Ok(delivery)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,99 @@
+use utoipa::{
+    Modify,
+    OpenApi,
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+};
+use crate::modules::{analytics, announcement, auth, comment, organization, post, search, tos, user};
+
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer_auth",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).bearer_format("JWT").build()),
+        );
+    }
+}
+
+/// The spec is derived from the handler annotations and the DTOs themselves, so the
+/// `client` feature and any generated SDK stay in step with what the server accepts.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Axum RESTful API"),
+    paths(
+        auth::handler::availability,
+        auth::handler::sign_up,
+        auth::handler::sign_in,
+        auth::handler::refresh_token,
+        auth::handler::sign_out,
+        user::handler::user_self,
+        user::handler::user_settings,
+        user::handler::user_settings_update,
+        user::handler::user_detail,
+        user::handler::user_by_username,
+        user::handler::user_batch,
+        user::handler::user_update,
+        user::handler::user_patch,
+        user::handler::user_follow_unfollow,
+        user::handler::user_feeds,
+        user::handler::user_avatar,
+        user::handler::user_activity,
+        user::handler::user_search,
+        user::handler::user_suggestions,
+        post::handler::post_create,
+        post::handler::post_detail,
+        post::handler::post_batch,
+        post::handler::post_list_by_user,
+        post::handler::post_stream,
+        post::handler::post_update,
+        post::handler::post_patch,
+        post::handler::post_delete,
+        post::handler::post_view,
+        post::handler::post_collaborators,
+        post::handler::post_collaborator_add,
+        post::handler::post_collaborator_remove,
+        post::handler::post_user_syndication,
+        post::handler::post_tag_syndication,
+        comment::handler::comment_create,
+        comment::handler::comment_detail,
+        comment::handler::comment_list_by_post,
+        comment::handler::comment_update,
+        comment::handler::comment_delete,
+        search::handler::search,
+        organization::handler::organization_create,
+        organization::handler::organization_detail,
+        organization::handler::organization_update,
+        organization::handler::organization_delete,
+        organization::handler::organization_members,
+        organization::handler::organization_member_update,
+        organization::handler::organization_member_remove,
+        organization::handler::organization_leave,
+        organization::handler::organization_invite,
+        organization::handler::organization_invitation_revoke,
+        organization::handler::organization_invitations,
+        organization::handler::organization_invitation_accept,
+        organization::handler::organization_invitation_decline,
+        organization::handler::organization_follow,
+        organization::handler::organization_unfollow,
+        organization::handler::organization_usage,
+        analytics::handler::analytics_events,
+        announcement::handler::announcement_active,
+        tos::handler::tos_current,
+        tos::handler::user_accept_tos,
+    ),
+    modifiers(&BearerAuth),
+    tags(
+        (name = "auth", description = "Registration, sign in and token refresh"),
+        (name = "user", description = "Profiles, follows and feeds"),
+        (name = "post", description = "Posts"),
+        (name = "comment", description = "Comments on posts"),
+        (name = "search", description = "Search across users, posts and tags"),
+        (name = "organization", description = "Organizations, their members, invitations and followers"),
+        (name = "analytics", description = "Anonymized usage events from the clients"),
+        (name = "announcement", description = "Announcement banners for the clients"),
+        (name = "tos", description = "Terms of service versions"),
+    )
+)]
+pub struct ApiDoc;
\ No newline at end of file
@@ -1,44 +1,78 @@
 use std::sync::Arc;
-use axum::{Extension, Json, Router, extract::Request, http::StatusCode, response::{IntoResponse}, middleware, routing::get};
-use tower_http::trace::TraceLayer;
+use axum::{Extension, Json, Router, extract::Request, middleware, routing::get};
+use tower_http::{catch_panic::CatchPanicLayer, request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer}, trace::TraceLayer};
+use utoipa::OpenApi;
 use crate::{
     AppState,
-    dto::ErrorRouting,
+    db::record_pool_metrics,
+    error::{AppError, ErrorMessage},
     modules::{
-        auth::handler::auth_router,
+        analytics::handler::analytics_router,
+        announcement::handler::announcement_router,
+        auth::handler::{auth_router, jwks},
         user::handler::user_router,
-        post::handler::post_router,
+        post::handler::{post_router, post_public_router, tag_syndication_router, user_syndication_router},
         comment::handler::comment_router,
+        admin::handler::{admin_public_router, admin_router},
+        search::handler::search_router,
+        organization::handler::organization_router,
+        tos::handler::tos_router,
+        feed_import::handler::feed_import_router,
     },
-    middleware::{auth::{auth_token}, rate_limiter::{rate_limit}}
+    middleware::{auth::{auth_basic, auth_token}, org_quota::org_quota, tos::tos_acceptance, rate_limiter::{rate_limit}, frame_options::frame_options, content_negotiation::negotiate_format, db_admission::db_admission, http_log::http_log, error_reporting::report_errors, catch_panic::handle_panic},
+    openapi::ApiDoc,
+    utils::metrics,
 };
 
-async fn not_found(request: Request) -> impl IntoResponse {
-    let response = Json(ErrorRouting{
-        status: "error".to_string(),
-        message: format!("Route {} {} is not exists", request.method(), request.uri().path()),
-    });
-    (StatusCode::NOT_FOUND, response)
+async fn not_found(request: Request) -> AppError {
+    AppError::not_found(ErrorMessage::RouteNotFound(format!("{} {}", request.method(), request.uri().path())))
 }
-async fn not_allowed(request: Request) -> impl IntoResponse {
-    let response = Json(ErrorRouting{
-        status: "error".to_string(),
-        message: format!("{} {} is not valid", request.method(), request.uri().path()),
-    });
-    (StatusCode::METHOD_NOT_ALLOWED, response)
+async fn not_allowed(request: Request) -> AppError {
+    AppError::method_not_allowed(ErrorMessage::MethodNotAllowed(format!("{} {}", request.method(), request.uri().path())))
+}
+async fn render_metrics(Extension(app_state): Extension<Arc<AppState>>) -> String {
+    record_pool_metrics(&app_state.db.pool);
+    metrics::render()
+}
+/// Routes behind an access token.
+/// Users who haven't accepted the current terms of service are turned away before using any.
+fn authenticated(router: Router) -> Router {
+    router
+        .layer(middleware::from_fn(tos_acceptance))
+        .layer(middleware::from_fn(auth_token))
 }
 pub fn create_router(app_state: Arc<AppState>) -> Router {
     let api_route = Router::new()
         .route("/ping", get(|| async { "PONG" }))
-        .nest("/auth", auth_router())
-        .nest("/user", user_router().layer(middleware::from_fn(auth_token)))
-        .nest("/post", post_router().layer(middleware::from_fn(auth_token)))
-        .nest("/comment", comment_router().layer(middleware::from_fn(auth_token)));
+        .route("/openapi.json", get(|| async { Json(ApiDoc::openapi()) }))
+        .route("/metrics", get(render_metrics).layer(middleware::from_fn(auth_basic)))
+        .merge(Router::new()
+            .nest("/auth", auth_router())
+            .nest("/user", authenticated(user_router()).merge(user_syndication_router()))
+            .nest("/post", authenticated(post_router()).merge(post_public_router()))
+            .nest("/tag", tag_syndication_router())
+            .nest("/comment", authenticated(comment_router()))
+            .nest("/search", authenticated(search_router()))
+            .nest("/organization", authenticated(organization_router().layer(middleware::from_fn(org_quota))))
+            .nest("/analytics", authenticated(analytics_router()))
+            .nest("/feed-import", authenticated(feed_import_router()))
+            .nest("/announcements", announcement_router())
+            .nest("/tos", tos_router())
+            .nest("/admin", authenticated(admin_router()).merge(admin_public_router()))
+            .layer(middleware::from_fn(db_admission)));
     Router::new()
         .nest("/api", api_route)
+        .route("/.well-known/jwks.json", get(jwks))
+        .fallback(not_found)
+        .method_not_allowed_fallback(not_allowed)
         .layer(middleware::from_fn(rate_limit))
+        .layer(middleware::from_fn(negotiate_format))
+        .layer(middleware::from_fn(frame_options))
+        .layer(CatchPanicLayer::custom(handle_panic))
         .layer(TraceLayer::new_for_http())
+        .layer(middleware::from_fn(report_errors))
+        .layer(middleware::from_fn(http_log))
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
         .layer(Extension(app_state))
-        .fallback(not_found)
-        .method_not_allowed_fallback(not_allowed)
 }
\ No newline at end of file
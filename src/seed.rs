@@ -0,0 +1,66 @@
+use crate::{
+    config::Config,
+    db::Repositories,
+    error::{AppError, ErrorMessage},
+    middleware::permission::Permission,
+    modules::{
+        role::model::RoleType,
+    },
+    utils::password::{Argon2idHasher, PasswordHasher},
+};
+
+/// Each role with the role it inherits from, parents first.
+const ROLES: [(RoleType, Option<RoleType>, &str); 2] = [
+    (RoleType::User, None, "Can use basic features for their own account."),
+    (RoleType::Admin, Some(RoleType::User), "Has full access to manage users, content, and system settings."),
+];
+
+pub enum AdminAccount {
+    Created(String),
+    Promoted(String),
+}
+
+/// What a seed run inserted, rows that already existed are not counted.
+#[derive(Default)]
+pub struct SeedReport {
+    pub roles: usize,
+    pub permissions: usize,
+    pub grants: usize,
+    pub admin: Option<AdminAccount>,
+}
+
+/// Inserts the roles, the permissions in `Permission::ALL` and their role grants, then the
+/// `SEED_ADMIN_*` account when configured. Every step skips rows that already exist, so running it
+/// again on a seeded database changes nothing. Cached role permissions pick up new grants once they
+/// expire (`PERMISSION_CACHE_TTL`).
+pub async fn run(db: &Repositories, config: &Config) -> Result<SeedReport, AppError> {
+    let mut report = SeedReport::default();
+    for (role, parent, description) in ROLES {
+        if db.roles.ensure_role(role, parent, description).await? {
+            report.roles += 1;
+        }
+    }
+    for permission in Permission::ALL {
+        let name = permission.to_string();
+        if db.permissions.ensure_permission(&name, permission.description()).await? {
+            report.permissions += 1;
+        }
+        for role in permission.roles() {
+            if db.permissions.grant_permission(*role, &name).await? {
+                report.grants += 1;
+            }
+        }
+    }
+    if let (Some(email), Some(admin_password)) = (&config.seed_admin_email, &config.seed_admin_password) {
+        let admin_role_id = db.roles.get_role_id_by_name(RoleType::Admin).await?
+            .ok_or(AppError::server_error(ErrorMessage::DataNotFound))?;
+        let hash_password = Argon2idHasher::from_config(config).hash(admin_password).map_err(AppError::server_error)?;
+        let created = db.users.ensure_admin(&config.seed_admin_name, email, hash_password, admin_role_id).await?;
+        report.admin = Some(if created {
+            AdminAccount::Created(email.clone())
+        } else {
+            AdminAccount::Promoted(email.clone())
+        });
+    }
+    Ok(report)
+}
\ No newline at end of file
@@ -0,0 +1,44 @@
+use std::sync::{Mutex, atomic::{AtomicBool, Ordering}};
+use async_trait::async_trait;
+use crate::modules::email::mailer::{MailError, Mailer};
+
+#[derive(Clone, Debug)]
+pub struct SentEmail {
+    pub to: String,
+    pub subject: String,
+    pub html: String,
+}
+
+/// Keeps every email instead of delivering it, or refuses them all like a down SMTP server.
+#[derive(Default)]
+pub struct FakeMailer {
+    sent: Mutex<Vec<SentEmail>>,
+    failing: AtomicBool,
+}
+
+impl FakeMailer {
+    pub fn set_failing(&self, failing: bool) {
+        self.failing.store(failing, Ordering::SeqCst);
+    }
+    pub fn sent(&self) -> Vec<SentEmail> {
+        self.sent.lock().unwrap().clone()
+    }
+    pub fn sent_to(&self, to: &str) -> Vec<SentEmail> {
+        self.sent().into_iter().filter(|email| email.to == to).collect()
+    }
+}
+
+#[async_trait]
+impl Mailer for FakeMailer {
+    async fn send(&self, to_email: &str, subject: &str, html: String) -> Result<(), MailError> {
+        if self.failing.load(Ordering::SeqCst) {
+            return Err("SMTP server is unavailable".into());
+        }
+        self.sent.lock().unwrap().push(SentEmail {
+            to: to_email.to_string(),
+            subject: subject.to_string(),
+            html,
+        });
+        Ok(())
+    }
+}
\ No newline at end of file
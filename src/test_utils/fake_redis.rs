@@ -0,0 +1,351 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use redis::Script;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+use crate::modules::redis::lock::RELEASE_LOCK_SCRIPT;
+
+enum Value {
+    String(Vec<u8>),
+    Hash(HashMap<Vec<u8>, Vec<u8>>),
+}
+
+struct Entry {
+    value: Value,
+    expires_at: Option<Instant>,
+}
+
+enum Reply {
+    Status(&'static str),
+    Error(String),
+    Integer(i64),
+    Bulk(Option<Vec<u8>>),
+    Array(Vec<Reply>),
+}
+
+impl Reply {
+    fn encode(self) -> Vec<u8> {
+        match self {
+            Reply::Status(status) => format!("+{}\r\n", status).into_bytes(),
+            Reply::Error(message) => format!("-{}\r\n", message).into_bytes(),
+            Reply::Integer(n) => format!(":{}\r\n", n).into_bytes(),
+            Reply::Bulk(None) => b"$-1\r\n".to_vec(),
+            Reply::Bulk(Some(bytes)) => {
+                let mut encoded = format!("${}\r\n", bytes.len()).into_bytes();
+                encoded.extend(bytes);
+                encoded.extend(b"\r\n");
+                encoded
+            }
+            Reply::Array(items) => {
+                let mut encoded = format!("*{}\r\n", items.len()).into_bytes();
+                for item in items {
+                    encoded.extend(item.encode());
+                }
+                encoded
+            }
+        }
+    }
+    fn wrong_arity(command: &str) -> Self {
+        Reply::Error(format!("ERR wrong number of arguments for '{}' command", command.to_lowercase()))
+    }
+    fn not_an_integer() -> Self {
+        Reply::Error("ERR value is not an integer or out of range".to_string())
+    }
+    fn wrong_type() -> Self {
+        Reply::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())
+    }
+}
+
+#[derive(Default)]
+struct Store {
+    entries: HashMap<Vec<u8>, Entry>,
+    /// Loaded scripts by SHA1, only the ones `RedisClient` sends can run.
+    scripts: HashMap<String, Vec<u8>>,
+}
+
+impl Store {
+    /// The entry under `key`, dropping it first when it has expired.
+    fn live(&mut self, key: &[u8]) -> Option<&mut Entry> {
+        if self.entries.get(key).is_some_and(|entry| entry.expires_at.is_some_and(|at| at <= Instant::now())) {
+            self.entries.remove(key);
+        }
+        self.entries.get_mut(key)
+    }
+    fn execute(&mut self, args: &[Vec<u8>]) -> Reply {
+        let Some((name, args)) = args.split_first() else {
+            return Reply::Error("ERR empty command".to_string());
+        };
+        let name = String::from_utf8_lossy(name).to_uppercase();
+        match (name.as_str(), args) {
+            ("PING", _) => Reply::Status("PONG"),
+            ("CLIENT" | "SELECT", _) => Reply::Status("OK"),
+            ("FLUSHDB" | "FLUSHALL", _) => {
+                self.entries.clear();
+                Reply::Status("OK")
+            }
+            ("GET", [key]) => match self.live(key) {
+                None => Reply::Bulk(None),
+                Some(Entry { value: Value::String(bytes), .. }) => Reply::Bulk(Some(bytes.clone())),
+                Some(_) => Reply::wrong_type(),
+            },
+            ("SET", [key, value, options @ ..]) => self.set(key, value, options),
+            ("SETEX", [key, seconds, value]) => match parse_int(seconds) {
+                Some(seconds) if seconds > 0 => {
+                    self.insert(key, Value::String(value.clone()), Some(Duration::from_secs(seconds as u64)));
+                    Reply::Status("OK")
+                }
+                _ => Reply::not_an_integer(),
+            },
+            ("DEL", keys) if !keys.is_empty() => {
+                Reply::Integer(keys.iter().filter(|key| self.live(key).is_some() && self.entries.remove(*key).is_some()).count() as i64)
+            }
+            ("EXISTS", keys) if !keys.is_empty() => {
+                Reply::Integer(keys.iter().filter(|key| self.live(key).is_some()).count() as i64)
+            }
+            ("INCR", [key]) => self.incr(key, 1),
+            ("INCRBY", [key, by]) => match parse_int(by) {
+                Some(by) => self.incr(key, by),
+                None => Reply::not_an_integer(),
+            },
+            ("EXPIRE", [key, seconds]) => match (parse_int(seconds), self.live(key)) {
+                (None, _) => Reply::not_an_integer(),
+                (Some(_), None) => Reply::Integer(0),
+                (Some(seconds), Some(entry)) => {
+                    entry.expires_at = Some(Instant::now() + Duration::from_secs(seconds.max(0) as u64));
+                    Reply::Integer(1)
+                }
+            },
+            ("TTL", [key]) => match self.live(key) {
+                None => Reply::Integer(-2),
+                Some(Entry { expires_at: None, .. }) => Reply::Integer(-1),
+                Some(Entry { expires_at: Some(at), .. }) => {
+                    let remaining = at.saturating_duration_since(Instant::now());
+                    Reply::Integer(((remaining.as_millis() + 500) / 1000) as i64)
+                }
+            },
+            ("HSET", [key, pairs @ ..]) if !pairs.is_empty() && pairs.len() % 2 == 0 => {
+                if self.live(key).is_none() {
+                    self.insert(key, Value::Hash(HashMap::new()), None);
+                }
+                let Some(Entry { value: Value::Hash(fields), .. }) = self.live(key) else {
+                    return Reply::wrong_type();
+                };
+                let added = pairs.chunks(2)
+                    .filter(|pair| fields.insert(pair[0].clone(), pair[1].clone()).is_none())
+                    .count();
+                Reply::Integer(added as i64)
+            }
+            ("HGET", [key, field]) => match self.live(key) {
+                None => Reply::Bulk(None),
+                Some(Entry { value: Value::Hash(fields), .. }) => Reply::Bulk(fields.get(field).cloned()),
+                Some(_) => Reply::wrong_type(),
+            },
+            ("HINCRBY", [key, field, by]) => {
+                let Some(by) = parse_int(by) else {
+                    return Reply::not_an_integer();
+                };
+                if self.live(key).is_none() {
+                    self.insert(key, Value::Hash(HashMap::new()), None);
+                }
+                let Some(Entry { value: Value::Hash(fields), .. }) = self.live(key) else {
+                    return Reply::wrong_type();
+                };
+                let current = fields.get(field).map_or(Some(0), |value| parse_int(value));
+                match current.and_then(|n| n.checked_add(by)) {
+                    Some(n) => {
+                        fields.insert(field.clone(), n.to_string().into_bytes());
+                        Reply::Integer(n)
+                    }
+                    None => Reply::Error("ERR hash value is not an integer".to_string()),
+                }
+            }
+            ("HGETALL", [key]) => match self.live(key) {
+                None => Reply::Array(Vec::new()),
+                Some(Entry { value: Value::Hash(fields), .. }) => Reply::Array(fields.iter()
+                    .flat_map(|(field, value)| [Reply::Bulk(Some(field.clone())), Reply::Bulk(Some(value.clone()))])
+                    .collect()),
+                Some(_) => Reply::wrong_type(),
+            },
+            ("RENAME", [key, new_key]) => match self.live(key).is_some() {
+                false => Reply::Error("ERR no such key".to_string()),
+                true => {
+                    let entry = self.entries.remove(key).expect("live entry");
+                    self.entries.insert(new_key.clone(), entry);
+                    Reply::Status("OK")
+                }
+            },
+            ("SCRIPT", [subcommand, body]) if subcommand.eq_ignore_ascii_case(b"LOAD") => {
+                let hash = Script::new(&String::from_utf8_lossy(body)).get_hash().to_string();
+                self.scripts.insert(hash.clone(), body.clone());
+                Reply::Bulk(Some(hash.into_bytes()))
+            }
+            ("EVALSHA", [hash, num_keys, rest @ ..]) => self.eval(hash, num_keys, rest),
+            ("GET" | "SET" | "SETEX" | "DEL" | "EXISTS" | "INCR" | "INCRBY" | "EXPIRE" | "TTL" | "HSET" | "HGET" | "HINCRBY" | "HGETALL" | "RENAME" | "EVALSHA", _) => {
+                Reply::wrong_arity(&name)
+            }
+            _ => Reply::Error(format!("ERR unknown command '{}'", name.to_lowercase())),
+        }
+    }
+    fn insert(&mut self, key: &[u8], value: Value, ttl: Option<Duration>) {
+        let expires_at = ttl.map(|ttl| Instant::now() + ttl);
+        self.entries.insert(key.to_vec(), Entry { value, expires_at });
+    }
+    /// SET with the EX, PX, NX and XX options the cache and lock helpers use.
+    fn set(&mut self, key: &[u8], value: &[u8], options: &[Vec<u8>]) -> Reply {
+        let mut ttl = None;
+        let (mut only_missing, mut only_existing) = (false, false);
+        let mut options = options.iter();
+        while let Some(option) = options.next() {
+            match String::from_utf8_lossy(option).to_uppercase().as_str() {
+                "NX" => only_missing = true,
+                "XX" => only_existing = true,
+                unit @ ("EX" | "PX") => match options.next().and_then(|n| parse_int(n)) {
+                    Some(n) if n > 0 && unit == "EX" => ttl = Some(Duration::from_secs(n as u64)),
+                    Some(n) if n > 0 => ttl = Some(Duration::from_millis(n as u64)),
+                    _ => return Reply::not_an_integer(),
+                },
+                _ => return Reply::Error("ERR syntax error".to_string()),
+            }
+        }
+        let exists = self.live(key).is_some();
+        if (only_missing && exists) || (only_existing && !exists) {
+            return Reply::Bulk(None);
+        }
+        self.insert(key, Value::String(value.to_vec()), ttl);
+        Reply::Status("OK")
+    }
+    /// Runs a loaded script by replaying what it does, the fake has no Lua.
+    fn eval(&mut self, hash: &[u8], num_keys: &[u8], rest: &[Vec<u8>]) -> Reply {
+        let Some(body) = self.scripts.get(String::from_utf8_lossy(hash).as_ref()) else {
+            return Reply::Error("NOSCRIPT No matching script. Please use EVAL.".to_string());
+        };
+        if body.as_slice() != RELEASE_LOCK_SCRIPT.as_bytes() {
+            return Reply::Error("ERR script not supported by the fake".to_string());
+        }
+        let [key, token] = rest else {
+            return Reply::wrong_arity("EVALSHA");
+        };
+        if parse_int(num_keys) != Some(1) {
+            return Reply::Error("ERR wrong number of keys".to_string());
+        }
+        match self.live(key) {
+            Some(Entry { value: Value::String(bytes), .. }) if bytes == token => {
+                self.entries.remove(key);
+                Reply::Integer(1)
+            }
+            _ => Reply::Integer(0),
+        }
+    }
+    /// INCRBY keeps the key's expiry, a missing key counts from 0.
+    fn incr(&mut self, key: &[u8], by: i64) -> Reply {
+        let Some(entry) = self.live(key) else {
+            self.insert(key, Value::String(by.to_string().into_bytes()), None);
+            return Reply::Integer(by);
+        };
+        let Value::String(bytes) = &mut entry.value else {
+            return Reply::wrong_type();
+        };
+        match parse_int(bytes).and_then(|n| n.checked_add(by)) {
+            Some(n) => {
+                *bytes = n.to_string().into_bytes();
+                Reply::Integer(n)
+            }
+            None => Reply::not_an_integer(),
+        }
+    }
+}
+
+fn parse_int(bytes: &[u8]) -> Option<i64> {
+    std::str::from_utf8(bytes).ok()?.parse().ok()
+}
+
+/// A Redis server speaking just enough RESP2 for `RedisClient`: strings with expiry, counters,
+/// hashes and the lock release script. Unknown commands get an error reply, which the caches treat like Redis being down.
+#[derive(Clone)]
+pub struct FakeRedis {
+    address: SocketAddr,
+    store: Arc<Mutex<Store>>,
+}
+
+impl FakeRedis {
+    /// Listens on a free local port until the runtime shuts down.
+    pub async fn start() -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let store = Arc::new(Mutex::new(Store::default()));
+        let accept_store = store.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, accept_store.clone()));
+            }
+        });
+        Ok(Self { address, store })
+    }
+    pub fn url(&self) -> String {
+        format!("redis://{}/", self.address)
+    }
+    /// The string stored under `key`, for asserting on what the API cached.
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        match self.store.lock().unwrap().live(key.as_bytes()) {
+            Some(Entry { value: Value::String(bytes), .. }) => Some(bytes.clone()),
+            _ => None,
+        }
+    }
+    /// A field of the hash stored under `key`.
+    pub fn hget(&self, key: &str, field: &str) -> Option<Vec<u8>> {
+        match self.store.lock().unwrap().live(key.as_bytes()) {
+            Some(Entry { value: Value::Hash(fields), .. }) => fields.get(field.as_bytes()).cloned(),
+            _ => None,
+        }
+    }
+    pub fn flush(&self) {
+        self.store.lock().unwrap().entries.clear();
+    }
+}
+
+async fn serve(stream: TcpStream, store: Arc<Mutex<Store>>) -> io::Result<()> {
+    // Pipelined replies go out one write each, Nagle would hold every one after the first until the
+    // client's delayed ACK.
+    stream.set_nodelay(true)?;
+    let (read, mut write) = stream.into_split();
+    let mut reader = BufReader::new(read);
+    while let Some(args) = read_command(&mut reader).await? {
+        let reply = store.lock().unwrap().execute(&args);
+        write.write_all(&reply.encode()).await?;
+    }
+    Ok(())
+}
+
+/// One command as the client sends it, an array of bulk strings. `None` once the client hangs up.
+async fn read_command(reader: &mut (impl AsyncBufReadExt + Unpin)) -> io::Result<Option<Vec<Vec<u8>>>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).await? == 0 {
+        return Ok(None);
+    }
+    let count = header(&line, '*')?;
+    let mut args = Vec::with_capacity(count);
+    for _ in 0..count {
+        line.clear();
+        reader.read_line(&mut line).await?;
+        let len = header(&line, '$')?;
+        let mut arg = vec![0; len + 2];
+        reader.read_exact(&mut arg).await?;
+        arg.truncate(len);
+        args.push(arg);
+    }
+    Ok(Some(args))
+}
+
+fn header(line: &str, prefix: char) -> io::Result<usize> {
+    line.trim_end()
+        .strip_prefix(prefix)
+        .and_then(|n| n.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("unexpected RESP line {:?}", line)))
+}
\ No newline at end of file
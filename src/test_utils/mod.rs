@@ -0,0 +1,127 @@
+pub mod fake_mailer;
+pub mod fake_redis;
+
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+use axum::Router;
+use crate::{
+    AppState,
+    config::Config,
+    db::memory::InMemoryDb,
+    jobs::email_outbox,
+    modules::{email::mailer::RetryingMailer, event::publisher::LocalEventBus, redis::redis::RedisClient, role::model::RoleType, user::model::User},
+    router::create_router,
+    utils::{jwt, load::LoadMonitor, moderation, password::Argon2idHasher},
+};
+pub use fake_mailer::{FakeMailer, SentEmail};
+pub use fake_redis::FakeRedis;
+
+pub const JWT_SECRET: &str = "test-utils-secret";
+/// The password of the accounts added by `TestApp::user_with_token`.
+pub const TEST_PASSWORD: &str = "password123";
+
+/// The values behind `test_config`: every required variable, and short timeouts so nothing waits on
+/// the services that are not running. For tests of `Config::from_values` itself.
+pub fn base_config_values() -> Vec<(&'static str, &'static str)> {
+    vec![
+        ("DATABASE_URL", "postgres://localhost:1/unused"),
+        ("FRONTEND_URL", "http://localhost:3000"),
+        ("JWT_SECRET_KEY", JWT_SECRET),
+        ("AUTH_BASIC_USERNAME", "tests"),
+        ("AUTH_BASIC_PASSWORD", "tests"),
+        ("REDIS_URL", "redis://127.0.0.1:1/"),
+        ("ACTION_TOKEN_PEPPER", "tests"),
+        ("REDIS_COMMAND_TIMEOUT_MS", "50"),
+        ("MAIL_RETRY_BACKOFF_MS", "1"),
+    ]
+}
+
+/// A valid configuration that needs nothing running, `overrides` win over the defaults. Redis points
+/// at a closed port, so caches miss and the rate limiter lets everything through.
+pub fn test_config(overrides: &[(&str, &str)]) -> Config {
+    let mut values = base_config_values();
+    values.extend_from_slice(overrides);
+    Config::from_values(&values).expect("valid test configuration")
+}
+
+/// The app and the fakes behind it, so tests can seed data and inspect side effects. `address` is
+/// where `spawn_app` serves it, `build_app` leaves it to `router`.
+pub struct TestApp {
+    pub address: Option<SocketAddr>,
+    pub app_state: Arc<AppState>,
+    pub db: Arc<InMemoryDb>,
+    pub mailer: Arc<FakeMailer>,
+    pub redis: FakeRedis,
+}
+
+impl TestApp {
+    pub fn url(&self, path: &str) -> String {
+        let address = self.address.expect("an app started with spawn_app");
+        format!("http://{}{}", address, path)
+    }
+    /// The full router over the app's state, for requests sent in-process with `tower::ServiceExt`.
+    pub fn router(&self) -> Router {
+        create_router(self.app_state.clone())
+    }
+    /// A 10 minute access token for `user`, signed with the app's keys.
+    pub fn token_for(&self, user: &User) -> String {
+        jwt::create_token(&user.id.to_string(), &self.app_state.env.jwt_keys, 600).expect("signable token")
+    }
+    /// Adds a verified account with the password `TEST_PASSWORD` and signs in as it, see `token_for`.
+    pub fn user_with_token(&self, name: &str, email: &str, role: RoleType) -> (User, String) {
+        let user = self.db.add_user(name, email, TEST_PASSWORD, role);
+        let token = self.token_for(&user);
+        (user, token)
+    }
+    /// What the email outbox job does on a tick: sends the queued emails through the `FakeMailer`.
+    pub async fn send_queued_emails(&self) {
+        email_outbox::send_due(&self.app_state, &*self.mailer).await;
+    }
+}
+
+/// Serves the app of `build_app` on a free local port. The server stops with the test's runtime.
+pub async fn spawn_app(config_overrides: &[(&str, &str)]) -> TestApp {
+    let mut app = build_app(config_overrides).await;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.expect("Failed to bind address");
+    app.address = Some(listener.local_addr().expect("Failed to read bound address"));
+    let router = app.router();
+    tokio::spawn(async move {
+        axum::serve(listener, router.into_make_service_with_connect_info::<SocketAddr>()).await.expect("Failed to run test server");
+    });
+    app
+}
+
+/// The app with seeded in-memory repositories, a `FakeMailer` behind the `RetryingMailer` and a
+/// `FakeRedis`, without a server. `config_overrides` are applied on top of `test_config`.
+/// Background jobs are not started.
+pub async fn build_app(config_overrides: &[(&str, &str)]) -> TestApp {
+    let redis = FakeRedis::start().await.expect("Failed to start fake Redis");
+    let redis_url = redis.url();
+    // Tests send requests back to back, far beyond the default of 5 a second. The ones about the
+    // limiter set their own budget.
+    let mut values = vec![("REDIS_URL", redis_url.as_str()), ("RATE_LIMITER_MAX", "1000")];
+    values.extend_from_slice(config_overrides);
+    let env = test_config(&values);
+    let redis_client = RedisClient::new(
+        &env.redis_url,
+        Duration::from_millis(env.redis_command_timeout),
+        env.cache_compression_min_size,
+    ).await.expect("Failed to create Redis client")
+        .with_circuit_breaker(env.redis_breaker_threshold, Duration::from_millis(env.redis_breaker_cooldown));
+    let db = InMemoryDb::seeded();
+    let mailer = Arc::new(FakeMailer::default());
+    let hasher = Arc::new(Argon2idHasher::from_config(&env));
+    let moderator = moderation::from_config(&env);
+    let repositories = db.repositories();
+    let retrying_mailer = Arc::new(RetryingMailer::new(mailer.clone(), repositories.emails.clone(), &env));
+    let app_state = Arc::new(AppState {
+        env,
+        db: repositories,
+        redis_client,
+        mailer: retrying_mailer,
+        events: Arc::new(LocalEventBus::default()),
+        hasher,
+        moderator,
+        load: Arc::new(LoadMonitor::default()),
+    });
+    TestApp { address: None, app_state, db, mailer, redis }
+}
\ No newline at end of file
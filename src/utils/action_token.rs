@@ -0,0 +1,40 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn mac(pepper: &str, token: &str) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(pepper.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(token.as_bytes());
+    mac
+}
+
+/// What is stored for an emailed token: its HMAC-SHA256 keyed with `ACTION_TOKEN_PEPPER`, as hex.
+/// A leaked table is useless without the pepper, and a token sent by a client is found by its hash.
+pub fn hash(pepper: &str, token: &str) -> String {
+    hex::encode(mac(pepper, token).finalize().into_bytes())
+}
+
+/// Whether `token` hashes to `token_hash`, compared in constant time.
+pub fn matches(pepper: &str, token: &str, token_hash: &str) -> bool {
+    match hex::decode(token_hash) {
+        Ok(expected) => mac(pepper, token).verify_slice(&expected).is_ok(),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_same_pepper_matches_the_stored_hash() {
+        let token_hash = hash("pepper", "token");
+        assert_ne!(token_hash, "token");
+        assert_eq!(token_hash.len(), 64);
+        assert!(matches("pepper", "token", &token_hash));
+        assert!(!matches("other pepper", "token", &token_hash));
+        assert!(!matches("pepper", "other token", &token_hash));
+        assert!(!matches("pepper", "token", "not hex"));
+    }
+}
\ No newline at end of file
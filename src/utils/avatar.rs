@@ -0,0 +1,43 @@
+use std::{io::Cursor, time::Duration};
+use image::{ImageFormat, ImageReader, Limits, imageops::FilterType};
+use reqwest::Url;
+use sha2::{Digest, Sha256};
+use crate::utils::outbound;
+
+/// Sizes the avatar endpoint renders, in pixels. A fixed set keeps the number of cached variants small.
+pub const AVATAR_SIZES: &[u32] = &[32, 48, 64, 96, 128, 256, 512];
+/// Larger source images are refused before they are decoded.
+const MAX_SOURCE_DIMENSION: u32 = 8192;
+
+/// Identifies one rendering of one avatar url, for the cache key and the ETag. A new `avatar_url`
+/// gets a new version, so caches never serve the old picture.
+pub fn version(avatar_url: &str, size: u32) -> String {
+    let digest = Sha256::digest(avatar_url.as_bytes());
+    format!("{}-{}", &hex::encode(digest)[..16], size)
+}
+
+/// Downloads the image at `url`, at most `max_bytes` of it, through `outbound::guarded_client`.
+pub async fn fetch(url: &str, max_bytes: usize, allow_private_hosts: bool) -> Result<Vec<u8>, String> {
+    let parsed = Url::parse(url).map_err(|e| format!("invalid url: {}", e))?;
+    let client = outbound::guarded_client(&parsed, Duration::from_secs(10), allow_private_hosts).await?;
+    let response = client.get(parsed).send().await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?;
+    outbound::read_limited(response, max_bytes).await
+}
+
+/// `source` cropped to a square around its center and scaled to `size` pixels, as PNG. CPU bound,
+/// run it on a blocking thread.
+pub fn resize(source: &[u8], size: u32) -> Result<Vec<u8>, String> {
+    let mut reader = ImageReader::new(Cursor::new(source)).with_guessed_format().map_err(|e| e.to_string())?;
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_SOURCE_DIMENSION);
+    limits.max_image_height = Some(MAX_SOURCE_DIMENSION);
+    reader.limits(limits);
+    let image = reader.decode().map_err(|e| e.to_string())?;
+    let mut png = Vec::new();
+    image.resize_to_fill(size, size, FilterType::Lanczos3)
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| e.to_string())?;
+    Ok(png)
+}
\ No newline at end of file
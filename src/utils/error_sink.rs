@@ -0,0 +1,101 @@
+use std::{error::Error, time::Duration};
+use chrono::{DateTime, SecondsFormat, Utc};
+use reqwest::Url;
+use serde::Serialize;
+use serde_json::json;
+use uuid::Uuid;
+use crate::config::Config;
+
+/// Where server errors are reported, from `ERROR_SINK` and `ERROR_SINK_DSN`. A Sentry DSN looks like
+/// `https://<public key>@<host>/<project id>`; the http sink posts every `ErrorEvent` as JSON to its url.
+pub enum ErrorSink {
+    Sentry { envelope_url: String, public_key: String, dsn: String },
+    Http(String),
+}
+
+/// One 5xx response or panic, with the request it happened in.
+#[derive(Serialize)]
+pub struct ErrorEvent {
+    pub event_id: Uuid,
+    pub timestamp: DateTime<Utc>,
+    /// `error` for 5xx responses, `fatal` for panics.
+    pub level: &'static str,
+    pub message: String,
+    pub status: u16,
+    pub request_id: Option<String>,
+    pub method: String,
+    pub route: String,
+    pub user_id: Option<Uuid>,
+}
+
+impl ErrorSink {
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let dsn = config.error_sink_dsn.clone()?;
+        match config.error_sink.as_str() {
+            "sentry" => Self::sentry(&dsn),
+            "http" => Some(ErrorSink::Http(dsn)),
+            _ => None,
+        }
+    }
+    fn sentry(dsn: &str) -> Option<Self> {
+        let url = Url::parse(dsn).ok()?;
+        let public_key = url.username();
+        let (prefix, project_id) = url.path().rsplit_once('/')?;
+        if public_key.is_empty() || project_id.is_empty() {
+            return None;
+        }
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str()?, port),
+            None => url.host_str()?.to_string(),
+        };
+        Some(ErrorSink::Sentry {
+            envelope_url: format!("{}://{}{}/api/{}/envelope/", url.scheme(), host, prefix, project_id),
+            public_key: public_key.to_string(),
+            dsn: dsn.to_string(),
+        })
+    }
+    pub async fn send(&self, event: &ErrorEvent) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let client = reqwest::Client::new();
+        let request = match self {
+            ErrorSink::Sentry { envelope_url, public_key, dsn } => client
+                .post(envelope_url)
+                .header("X-Sentry-Auth", format!(
+                    "Sentry sentry_version=7, sentry_key={}, sentry_client=axum-restful-api/{}",
+                    public_key, env!("CARGO_PKG_VERSION"),
+                ))
+                .header("Content-Type", "application/x-sentry-envelope")
+                .body(sentry_envelope(event, dsn)?),
+            ErrorSink::Http(url) => client.post(url).json(event),
+        };
+        request.timeout(Duration::from_secs(10)).send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+// Envelope with a single event item, see https://develop.sentry.dev/sdk/envelopes/.
+fn sentry_envelope(event: &ErrorEvent, dsn: &str) -> Result<String, serde_json::Error> {
+    let event_id = event.event_id.simple().to_string();
+    let header = json!({ "event_id": event_id, "dsn": dsn });
+    let payload = json!({
+        "event_id": event_id,
+        "timestamp": event.timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
+        "platform": "rust",
+        "level": event.level,
+        "logger": "axum-restful-api",
+        "transaction": format!("{} {}", event.method, event.route),
+        "message": { "formatted": event.message },
+        "tags": {
+            "request_id": event.request_id,
+            "route": event.route,
+            "status": event.status.to_string(),
+        },
+        "user": event.user_id.map(|id| json!({ "id": id })),
+        "request": { "method": event.method, "url": event.route },
+    });
+    Ok(format!(
+        "{}\n{}\n{}\n",
+        serde_json::to_string(&header)?,
+        json!({ "type": "event" }),
+        serde_json::to_string(&payload)?,
+    ))
+}
\ No newline at end of file
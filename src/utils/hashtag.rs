@@ -0,0 +1,77 @@
+/// The `#hashtags` in `text`, lowercased, in order of first use. A hashtag starts at a `#` that
+/// doesn't follow a letter or digit (so "C#" and url fragments are left alone) and runs over letters,
+/// digits, `-` and `_`. Ones that wouldn't pass as a post tag, shorter than 4 or longer than 20
+/// bytes, are skipped.
+pub fn extract(text: &str) -> Vec<String> {
+    let mut hashtags: Vec<String> = Vec::new();
+    let mut previous = None;
+    for (start, c) in text.char_indices() {
+        let starts_word = !previous.is_some_and(|previous: char| previous.is_alphanumeric() || previous == '#');
+        previous = Some(c);
+        if c != '#' || !starts_word {
+            continue;
+        }
+        let rest = &text[start + 1..];
+        let end = rest.find(|c: char| !(c.is_alphanumeric() || c == '-' || c == '_')).unwrap_or(rest.len());
+        let hashtag = rest[..end].trim_end_matches(['-', '_']).to_lowercase();
+        if (4..=20).contains(&hashtag.len()) && !hashtags.contains(&hashtag) {
+            hashtags.push(hashtag);
+        }
+    }
+    hashtags
+}
+
+/// The most tags a post carries. Explicit tags beyond it fail validation, hashtags beyond it are
+/// left out.
+pub const MAX_TAGS: usize = 10;
+
+/// `tags` followed by the hashtags of `text` that aren't among them yet, compared case-insensitively,
+/// until there are `MAX_TAGS`.
+pub fn merge(mut tags: Vec<String>, text: &str) -> Vec<String> {
+    for hashtag in extract(text) {
+        if tags.len() >= MAX_TAGS {
+            break;
+        }
+        if !tags.iter().any(|tag| tag.to_lowercase() == hashtag) {
+            tags.push(hashtag);
+        }
+    }
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashtags_start_a_word() {
+        let text = "#Rust beats C# and C++, see https://example.com/page#section or #web-dev_ and mail#spam";
+        assert_eq!(extract(text), ["rust", "web-dev"]);
+        assert_eq!(extract("##double #axum#chained"), ["axum"]);
+    }
+
+    #[test]
+    fn hashtags_are_4_to_20_bytes_and_listed_once() {
+        assert_eq!(extract("#abc #abcd #RUST #rust #Rust."), ["abcd", "rust"]);
+        assert_eq!(extract(&format!("#{} #{}", "a".repeat(20), "b".repeat(21))), ["a".repeat(20)]);
+        // Bytes, like the tag validation: five two-byte letters are 10 bytes, eleven are 22.
+        assert_eq!(extract(&format!("#{} #{}", "é".repeat(5), "é".repeat(11))), ["é".repeat(5)]);
+    }
+
+    #[test]
+    fn merge_keeps_explicit_tags_first_and_skips_their_hashtags() {
+        let tags = vec!["Rust".to_string(), "web".to_string()];
+        assert_eq!(merge(tags, "#rust and #axum for the #web"), ["Rust", "web", "axum"]);
+        assert_eq!(merge(Vec::new(), "no hashtags here"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn merge_stops_at_max_tags() {
+        let tags: Vec<String> = (0..MAX_TAGS - 1).map(|n| format!("tag{}", n)).collect();
+        let merged = merge(tags, "#first #second #third");
+        assert_eq!(merged.len(), MAX_TAGS);
+        assert_eq!(merged.last().map(String::as_str), Some("first"));
+        let full: Vec<String> = (0..MAX_TAGS).map(|n| format!("tag{}", n)).collect();
+        assert_eq!(merge(full.clone(), "#another"), full);
+    }
+}
\ No newline at end of file
@@ -1,6 +1,9 @@
+use std::fs;
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
 use chrono::{Duration, Utc};
 use jsonwebtoken::{
     decode,
+    decode_header,
     encode,
     Algorithm,
     DecodingKey,
@@ -8,9 +11,225 @@ use jsonwebtoken::{
     Header,
     Validation,
     errors::{Error as JwtError, ErrorKind as JwtErrorKind},
+    jwk::{
+        AlgorithmParameters,
+        CommonParameters,
+        EllipticCurve,
+        Jwk,
+        JwkSet,
+        KeyAlgorithm,
+        OctetKeyPairParameters,
+        OctetKeyPairType,
+        PublicKeyUse,
+        RSAKeyParameters,
+        RSAKeyType,
+    },
 };
+use ring::{rsa::{KeyPair as RsaKeyPair, PublicKeyComponents}, signature::{Ed25519KeyPair, KeyPair}};
 use serde::{Deserialize, Serialize};
-use crate::error::{ErrorMessage, HttpError};
+use sha2::{Digest, Sha256};
+use simple_asn1::{from_der, ASN1Block};
+use crate::error::AppError;
+
+const RSA_ENCRYPTION_OID: [u64; 7] = [1, 2, 840, 113549, 1, 1, 1];
+const ED25519_OID: [u64; 4] = [1, 3, 101, 112];
+
+/// The keys access tokens are signed and checked with. With HS256 tokens are signed with
+/// `JWT_SECRET_KEY` and carry no `kid`. With RS256 or EdDSA they are signed with the private key
+/// and carry its RFC 7638 thumbprint as `kid`; the public key and the ones in
+/// `JWT_VERIFY_KEY_PATHS` are accepted and published in the JWKS, so a retired key keeps working
+/// until its last token expires. Tokens without a `kid` are checked against the secret, with a key
+/// pair only while `JWT_ACCEPT_HS256` lets the tokens issued before the switch run out; otherwise
+/// whoever knows the secret could keep minting tokens the JWKS can't vouch for.
+#[derive(Clone)]
+pub struct JwtKeys {
+    algorithm: Algorithm,
+    kid: Option<String>,
+    encoding_key: EncodingKey,
+    secret: Option<DecodingKey>,
+    verifying_keys: Vec<VerifyingKey>,
+}
+
+#[derive(Clone)]
+struct VerifyingKey {
+    kid: String,
+    algorithm: Algorithm,
+    key: DecodingKey,
+    jwk: Jwk,
+}
+
+impl VerifyingKey {
+    fn new(algorithm: Algorithm, parameters: AlgorithmParameters) -> Result<Self, String> {
+        let kid = thumbprint(&parameters);
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(match algorithm {
+                    Algorithm::EdDSA => KeyAlgorithm::EdDSA,
+                    _ => KeyAlgorithm::RS256,
+                }),
+                key_id: Some(kid.clone()),
+                ..CommonParameters::default()
+            },
+            algorithm: parameters,
+        };
+        let key = DecodingKey::from_jwk(&jwk).map_err(|e| e.to_string())?;
+        Ok(Self { kid, algorithm, key, jwk })
+    }
+    fn rsa(modulus: &[u8], exponent: &[u8]) -> Result<Self, String> {
+        Self::new(Algorithm::RS256, AlgorithmParameters::RSA(RSAKeyParameters {
+            key_type: RSAKeyType::RSA,
+            n: URL_SAFE_NO_PAD.encode(modulus),
+            e: URL_SAFE_NO_PAD.encode(exponent),
+        }))
+    }
+    fn ed25519(public_key: &[u8]) -> Result<Self, String> {
+        if public_key.len() != 32 {
+            return Err("not an Ed25519 public key".to_string());
+        }
+        Self::new(Algorithm::EdDSA, AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+            key_type: OctetKeyPairType::OctetKeyPair,
+            curve: EllipticCurve::Ed25519,
+            x: URL_SAFE_NO_PAD.encode(public_key),
+        }))
+    }
+}
+
+/// The RFC 7638 thumbprint: SHA-256 of the required members in lexicographic order.
+fn thumbprint(parameters: &AlgorithmParameters) -> String {
+    let canonical = match parameters {
+        AlgorithmParameters::RSA(rsa) => format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, rsa.e, rsa.n),
+        AlgorithmParameters::OctetKeyPair(okp) => format!(r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#, okp.x),
+        _ => unreachable!("only RSA and Ed25519 keys are loaded"),
+    };
+    URL_SAFE_NO_PAD.encode(Sha256::digest(canonical.as_bytes()))
+}
+
+/// RS256 for an RSA key (PKCS#1 or PKCS#8), EdDSA for an Ed25519 key (PKCS#8).
+fn private_key(contents: &[u8]) -> Result<(EncodingKey, VerifyingKey), String> {
+    let parsed = pem::parse(contents).map_err(|e| e.to_string())?;
+    let rsa_key_pair = match parsed.tag() {
+        "RSA PRIVATE KEY" => RsaKeyPair::from_der(parsed.contents()),
+        "PRIVATE KEY" => {
+            if let Ok(key_pair) = Ed25519KeyPair::from_pkcs8_maybe_unchecked(parsed.contents()) {
+                let encoding_key = EncodingKey::from_ed_pem(contents).map_err(|e| e.to_string())?;
+                return Ok((encoding_key, VerifyingKey::ed25519(key_pair.public_key().as_ref())?));
+            }
+            RsaKeyPair::from_pkcs8(parsed.contents())
+        }
+        tag => return Err(format!("expected a PRIVATE KEY or RSA PRIVATE KEY, found {}", tag)),
+    };
+    let public = PublicKeyComponents::<Vec<u8>>::from(rsa_key_pair.map_err(|e| e.to_string())?.public());
+    let encoding_key = EncodingKey::from_rsa_pem(contents).map_err(|e| e.to_string())?;
+    Ok((encoding_key, VerifyingKey::rsa(&public.n, &public.e)?))
+}
+
+/// The `(n, e)` of a PKCS#1 RSAPublicKey.
+fn rsa_public_components(der: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
+    match from_der(der).ok()?.as_slice() {
+        [ASN1Block::Sequence(_, items)] => match items.as_slice() {
+            [ASN1Block::Integer(_, n), ASN1Block::Integer(_, e)] => Some((n.to_bytes_be().1, e.to_bytes_be().1)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// An RSA or Ed25519 public key, as a SubjectPublicKeyInfo (`PUBLIC KEY`) or PKCS#1 (`RSA PUBLIC KEY`).
+fn public_key(contents: &[u8]) -> Result<VerifyingKey, String> {
+    let parsed = pem::parse(contents).map_err(|e| e.to_string())?;
+    let unsupported = || "not an RSA or Ed25519 public key".to_string();
+    match parsed.tag() {
+        "RSA PUBLIC KEY" => {
+            let (n, e) = rsa_public_components(parsed.contents()).ok_or_else(unsupported)?;
+            VerifyingKey::rsa(&n, &e)
+        }
+        "PUBLIC KEY" => {
+            let blocks = from_der(parsed.contents()).map_err(|_| unsupported())?;
+            let [ASN1Block::Sequence(_, info)] = blocks.as_slice() else {
+                return Err(unsupported());
+            };
+            let [ASN1Block::Sequence(_, algorithm), ASN1Block::BitString(_, _, key)] = info.as_slice() else {
+                return Err(unsupported());
+            };
+            let Some(ASN1Block::ObjectIdentifier(_, oid)) = algorithm.first() else {
+                return Err(unsupported());
+            };
+            let oid = oid.as_vec::<u64>().map_err(|_| unsupported())?;
+            if oid == RSA_ENCRYPTION_OID {
+                let (n, e) = rsa_public_components(key).ok_or_else(unsupported)?;
+                VerifyingKey::rsa(&n, &e)
+            } else if oid == ED25519_OID {
+                VerifyingKey::ed25519(key)
+            } else {
+                Err(unsupported())
+            }
+        }
+        tag => Err(format!("expected a PUBLIC KEY or RSA PUBLIC KEY, found {}", tag)),
+    }
+}
+
+/// Reads a PEM file and loads it with `load`, errors name the file.
+fn read_key<T>(path: &str, load: fn(&[u8]) -> Result<T, String>) -> Result<T, String> {
+    let contents = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    load(&contents).map_err(|e| format!("{}: {}", path, e))
+}
+
+impl JwtKeys {
+    pub fn hs256(secret: &[u8]) -> Self {
+        Self {
+            algorithm: Algorithm::HS256,
+            kid: None,
+            encoding_key: EncodingKey::from_secret(secret),
+            secret: Some(DecodingKey::from_secret(secret)),
+            verifying_keys: Vec::new(),
+        }
+    }
+    /// RS256 and EdDSA sign with the key at `private_key_path`, which must be of that type, and
+    /// accept tokens signed with the secret only with `accept_hs256`. Problems are described with
+    /// the offending file.
+    pub fn load(
+        algorithm: Algorithm,
+        secret: &[u8],
+        private_key_path: Option<&str>,
+        verify_key_paths: &[&str],
+        accept_hs256: bool,
+    ) -> Result<Self, String> {
+        let mut keys = Self::hs256(secret);
+        for path in verify_key_paths {
+            keys.verifying_keys.push(read_key(path, public_key)?);
+        }
+        if algorithm == Algorithm::HS256 {
+            return Ok(keys);
+        }
+        let path = private_key_path.ok_or("JWT_PRIVATE_KEY_PATH must be set when JWT_ALGORITHM is not HS256")?;
+        let (encoding_key, verifying_key) = read_key(path, private_key)?;
+        if verifying_key.algorithm != algorithm {
+            return Err(format!("{} holds a {:?} key, JWT_ALGORITHM is {:?}", path, verifying_key.algorithm, algorithm));
+        }
+        keys.algorithm = verifying_key.algorithm;
+        keys.kid = Some(verifying_key.kid.clone());
+        if !accept_hs256 {
+            keys.secret = None;
+        }
+        keys.encoding_key = encoding_key;
+        keys.verifying_keys.retain(|key| key.kid != verifying_key.kid);
+        keys.verifying_keys.insert(0, verifying_key);
+        Ok(keys)
+    }
+    /// The public keys downstream services verify access tokens with, current one first.
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet { keys: self.verifying_keys.iter().map(|key| key.jwk.clone()).collect() }
+    }
+    fn decoding_key(&self, kid: Option<&str>) -> Option<(Algorithm, &DecodingKey)> {
+        match kid {
+            None => self.secret.as_ref().map(|secret| (Algorithm::HS256, secret)),
+            Some(kid) => self.verifying_keys.iter()
+                .find(|key| key.kid == kid)
+                .map(|key| (key.algorithm, &key.key)),
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct TokenClaims{
@@ -18,11 +237,36 @@ pub struct TokenClaims{
     pub iat: usize,
     pub exp: usize,
     pub nbf: usize,
+    /// Set on impersonation tokens: `sub` is the admin, this is the user they act as.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act_as: Option<String>,
 }
 
 pub fn create_token(
     user_id: &str,
-    secret: &[u8],
+    keys: &JwtKeys,
+    expires_in_seconds: i64,
+) -> Result<String, JwtError> {
+    sign_token(user_id, None, keys, expires_in_seconds)
+}
+
+/// A token for `admin_id` acting as `user_id`. It can't be refreshed, so it ends after `expires_in_seconds`.
+pub fn create_impersonation_token(
+    admin_id: &str,
+    user_id: &str,
+    keys: &JwtKeys,
+    expires_in_seconds: i64,
+) -> Result<String, JwtError> {
+    if user_id.is_empty() {
+        return Err(JwtErrorKind::InvalidSubject.into());
+    }
+    sign_token(admin_id, Some(user_id), keys, expires_in_seconds)
+}
+
+fn sign_token(
+    user_id: &str,
+    act_as: Option<&str>,
+    keys: &JwtKeys,
     expires_in_seconds: i64,
 ) -> Result<String, JwtError> {
     if user_id.is_empty() {
@@ -34,6 +278,39 @@ pub fn create_token(
         iat: now.timestamp() as usize,
         exp: (now + Duration::seconds(expires_in_seconds)).timestamp() as usize,
         nbf: now.timestamp() as usize,
+        act_as: act_as.map(str::to_string),
+    };
+    let mut header = Header::new(keys.algorithm);
+    header.kid = keys.kid.clone();
+    encode(
+        &header,
+        &claims,
+        &keys.encoding_key
+    ).map_err(|_| JwtErrorKind::InvalidToken.into())
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EmbedTokenClaims {
+    pub sub: String,
+    pub aud: String,
+    pub iat: usize,
+    pub exp: usize,
+}
+
+const EMBED_AUDIENCE: &str = "post-embed";
+
+/// Embed tokens carry an audience, so they are rejected by `parse_token` and can't be used to sign in.
+pub fn create_embed_token(
+    post_id: &str,
+    secret: &[u8],
+    expires_in_seconds: i64,
+) -> Result<String, JwtError> {
+    let now = Utc::now();
+    let claims = EmbedTokenClaims {
+        sub: post_id.to_string(),
+        aud: EMBED_AUDIENCE.to_string(),
+        iat: now.timestamp() as usize,
+        exp: (now + Duration::seconds(expires_in_seconds)).timestamp() as usize,
     };
     encode(
         &Header::default(),
@@ -42,19 +319,39 @@ pub fn create_token(
     ).map_err(|_| JwtErrorKind::InvalidToken.into())
 }
 
-pub fn parse_token(
+pub fn parse_embed_token(
     token: impl Into<String>,
     secret: &[u8]
-) -> Result<String, HttpError<()>> {
+) -> Result<String, AppError> {
     let mut validation = Validation::new(Algorithm::HS256);
     validation.leeway = 0;
-    let decode = decode::<TokenClaims>(
+    validation.set_audience(&[EMBED_AUDIENCE]);
+    let token = decode::<EmbedTokenClaims>(
         &token.into(),
         &DecodingKey::from_secret(secret),
         &validation,
-    );
-    match decode {
-        Ok(token) => Ok(token.claims.sub),
-        Err(_) => Err(HttpError::unauthorized(ErrorMessage::TokenInvalid.to_string(), None))
-    }
+    )?;
+    Ok(token.claims.sub)
+}
+
+/// Failures carry a distinct `code` so clients can tell whether refreshing the token will help.
+/// A `kid` that is no longer configured makes the token invalid.
+pub fn parse_token(
+    token: impl Into<String>,
+    keys: &JwtKeys,
+    leeway: u64,
+) -> Result<TokenClaims, AppError> {
+    let token = token.into();
+    let header = decode_header(&token)?;
+    let (algorithm, key) = keys.decoding_key(header.kid.as_deref())
+        .ok_or(JwtError::from(JwtErrorKind::InvalidSignature))?;
+    let mut validation = Validation::new(algorithm);
+    validation.leeway = leeway;
+    validation.validate_nbf = true;
+    let token = decode::<TokenClaims>(
+        &token,
+        key,
+        &validation,
+    )?;
+    Ok(token.claims)
 }
\ No newline at end of file
@@ -0,0 +1,107 @@
+use std::{
+    collections::VecDeque,
+    sync::{Mutex, atomic::{AtomicI64, Ordering}},
+    time::{Duration, Instant},
+};
+use log::{info, warn};
+use crate::{config::Config, utils::metrics};
+
+/// Most samples kept per series, older ones are dropped first.
+const MAX_SAMPLES: usize = 2000;
+/// How long an evaluation of the samples is reused, the p99s are not worth sorting per request.
+const EVALUATE_EVERY: Duration = Duration::from_secs(1);
+
+/// Request latencies and database pool waits of the last `RATE_LIMITER_LOAD_WINDOW` seconds on
+/// this instance, telling the rate limiter whether the service is degraded. It is degraded while the
+/// p99 latency reaches `RATE_LIMITER_DEGRADED_LATENCY_MS` or the p99 pool wait reaches
+/// `RATE_LIMITER_DEGRADED_POOL_WAIT_MS`, a threshold of 0 leaves that signal out.
+#[derive(Default)]
+pub struct LoadMonitor {
+    state: Mutex<LoadState>,
+    in_flight: AtomicI64,
+}
+
+#[derive(Default)]
+struct LoadState {
+    latencies: VecDeque<(Instant, Duration)>,
+    pool_waits: VecDeque<(Instant, Duration)>,
+    evaluated_at: Option<Instant>,
+    degraded: bool,
+}
+
+/// Counts a request as in flight until dropped.
+pub struct InFlight<'a>(&'a LoadMonitor);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        let in_flight = self.0.in_flight.fetch_sub(1, Ordering::Relaxed) - 1;
+        metrics::set_gauge("http_requests_in_flight", &[], in_flight as f64);
+    }
+}
+
+impl LoadMonitor {
+    pub fn start_request(&self) -> InFlight<'_> {
+        let in_flight = self.in_flight.fetch_add(1, Ordering::Relaxed) + 1;
+        metrics::set_gauge("http_requests_in_flight", &[], in_flight as f64);
+        InFlight(self)
+    }
+    pub fn record_latency(&self, latency: Duration) {
+        push_sample(&mut self.state.lock().unwrap().latencies, latency);
+    }
+    /// How long a request waited for a pool connection in `db_admission`, zero when one was free.
+    pub fn record_pool_wait(&self, wait: Duration) {
+        push_sample(&mut self.state.lock().unwrap().pool_waits, wait);
+    }
+    pub fn is_degraded(&self, config: &Config) -> bool {
+        if config.rate_limiter_degraded_latency == 0 && config.rate_limiter_degraded_pool_wait == 0 {
+            return false;
+        }
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        if state.evaluated_at.is_some_and(|at| now.duration_since(at) < EVALUATE_EVERY) {
+            return state.degraded;
+        }
+        let window = Duration::from_secs(config.rate_limiter_load_window);
+        let latency = p99(&mut state.latencies, now, window);
+        let pool_wait = p99(&mut state.pool_waits, now, window);
+        metrics::set_gauge("http_request_latency_p99_seconds", &[], latency.as_secs_f64());
+        metrics::set_gauge("db_pool_wait_p99_seconds", &[], pool_wait.as_secs_f64());
+        let over = |value: Duration, threshold_ms: u64| threshold_ms > 0 && value >= Duration::from_millis(threshold_ms);
+        let degraded = over(latency, config.rate_limiter_degraded_latency) || over(pool_wait, config.rate_limiter_degraded_pool_wait);
+        if degraded != state.degraded {
+            if degraded {
+                warn!(
+                    "Service degraded (p99 latency {}ms, p99 pool wait {}ms), tightening anonymous rate limits",
+                    latency.as_millis(), pool_wait.as_millis()
+                );
+            } else {
+                info!("Service recovered, anonymous rate limits are back to normal");
+            }
+            metrics::increment_counter("server_load_transitions_total", &[("state", if degraded { "degraded" } else { "normal" })]);
+        }
+        metrics::set_gauge("server_degraded", &[], if degraded { 1.0 } else { 0.0 });
+        state.degraded = degraded;
+        state.evaluated_at = Some(now);
+        degraded
+    }
+}
+
+fn push_sample(samples: &mut VecDeque<(Instant, Duration)>, value: Duration) {
+    if samples.len() == MAX_SAMPLES {
+        samples.pop_front();
+    }
+    samples.push_back((Instant::now(), value));
+}
+
+/// The 99th percentile of the samples in the window, dropping the older ones. Zero without samples.
+fn p99(samples: &mut VecDeque<(Instant, Duration)>, now: Instant, window: Duration) -> Duration {
+    while samples.front().is_some_and(|(at, _)| now.duration_since(*at) > window) {
+        samples.pop_front();
+    }
+    let mut values: Vec<Duration> = samples.iter().map(|(_, value)| *value).collect();
+    if values.is_empty() {
+        return Duration::ZERO;
+    }
+    values.sort_unstable();
+    values[(values.len() * 99).div_ceil(100) - 1]
+}
\ No newline at end of file
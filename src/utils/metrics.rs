@@ -0,0 +1,71 @@
+use std::{collections::BTreeMap, sync::{LazyLock, Mutex}, time::Duration};
+
+static COUNTERS: LazyLock<Mutex<BTreeMap<String, u64>>> = LazyLock::new(|| Mutex::new(BTreeMap::new()));
+static GAUGES: LazyLock<Mutex<BTreeMap<String, f64>>> = LazyLock::new(|| Mutex::new(BTreeMap::new()));
+static DURATIONS: LazyLock<Mutex<BTreeMap<(String, String), DurationSummary>>> = LazyLock::new(|| Mutex::new(BTreeMap::new()));
+
+#[derive(Default)]
+struct DurationSummary {
+    sum: f64,
+    count: u64,
+}
+
+fn label_set(labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+    let labels: Vec<String> = labels.iter()
+        .map(|(key, value)| format!("{}=\"{}\"", key, value))
+        .collect();
+    format!("{{{}}}", labels.join(","))
+}
+
+pub fn increment_counter(name: &str, labels: &[(&str, &str)]) {
+    increment_counter_by(name, labels, 1);
+}
+
+pub fn increment_counter_by(name: &str, labels: &[(&str, &str)], value: u64) {
+    let key = format!("{}{}", name, label_set(labels));
+    if let Ok(mut counters) = COUNTERS.lock() {
+        *counters.entry(key).or_default() += value;
+    }
+}
+
+pub fn set_gauge(name: &str, labels: &[(&str, &str)], value: f64) {
+    let key = format!("{}{}", name, label_set(labels));
+    if let Ok(mut gauges) = GAUGES.lock() {
+        gauges.insert(key, value);
+    }
+}
+
+/// Adds one observation to a summary, rendered as `<name>_sum` (in seconds) and `<name>_count`.
+pub fn observe_duration(name: &str, labels: &[(&str, &str)], duration: Duration) {
+    let key = (name.to_string(), label_set(labels));
+    if let Ok(mut durations) = DURATIONS.lock() {
+        let summary = durations.entry(key).or_default();
+        summary.sum += duration.as_secs_f64();
+        summary.count += 1;
+    }
+}
+
+/// Renders every counter, gauge and summary in the Prometheus text exposition format.
+pub fn render() -> String {
+    let mut rendered = String::new();
+    if let Ok(counters) = COUNTERS.lock() {
+        for (key, value) in counters.iter() {
+            rendered.push_str(&format!("{} {}\n", key, value));
+        }
+    }
+    if let Ok(gauges) = GAUGES.lock() {
+        for (key, value) in gauges.iter() {
+            rendered.push_str(&format!("{} {}\n", key, value));
+        }
+    }
+    if let Ok(durations) = DURATIONS.lock() {
+        for ((name, labels), summary) in durations.iter() {
+            rendered.push_str(&format!("{}_sum{} {}\n", name, labels, summary.sum));
+            rendered.push_str(&format!("{}_count{} {}\n", name, labels, summary.count));
+        }
+    }
+    rendered
+}
\ No newline at end of file
@@ -1,3 +1,19 @@
 pub mod rand;
 pub mod password;
-pub mod jwt;
\ No newline at end of file
+pub mod jwt;
+pub mod metrics;
+pub mod html;
+pub mod webhook_verify;
+pub mod tls;
+pub mod csv;
+pub mod action_token;
+pub mod ndjson;
+pub mod error_sink;
+pub mod captcha;
+pub mod signing;
+pub mod avatar;
+pub mod moderation;
+pub mod hashtag;
+pub mod load;
+pub mod sigv4;
+pub mod outbound;
\ No newline at end of file
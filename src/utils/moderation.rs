@@ -0,0 +1,156 @@
+use std::{collections::HashSet, sync::Arc, time::Duration};
+use async_trait::async_trait;
+use log::warn;
+use serde::Deserialize;
+use serde_json::json;
+use crate::{
+    AppState,
+    config::Config,
+    error::{AppError, FieldError},
+    modules::report::{dto::{NewReport, ReportTarget}, model::ReportReason},
+};
+
+/// Screens user written text before it is published, see `CONTENT_MODERATION`.
+#[async_trait]
+pub trait ContentModerator: Send + Sync {
+    /// Why `text` should not be published, empty when nothing was found.
+    async fn review(&self, text: &str) -> Vec<String>;
+}
+
+/// `CONTENT_MODERATION=none`, everything goes through.
+pub struct NoModeration;
+
+#[async_trait]
+impl ContentModerator for NoModeration {
+    async fn review(&self, _text: &str) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// Flags text containing any of the `MODERATION_WORDLIST` words, compared case-insensitively and
+/// as whole words, so "class" does not match "ass".
+pub struct WordlistModerator {
+    words: HashSet<String>,
+}
+
+impl WordlistModerator {
+    pub fn new<'a>(words: impl IntoIterator<Item = &'a str>) -> Self {
+        Self { words: words.into_iter().map(str::to_lowercase).collect() }
+    }
+}
+
+#[async_trait]
+impl ContentModerator for WordlistModerator {
+    async fn review(&self, text: &str) -> Vec<String> {
+        let mut reasons = Vec::new();
+        for word in text.split(|c: char| !c.is_alphanumeric()).map(str::to_lowercase) {
+            let reason = format!("Contains the blocked word \"{}\".", word);
+            if self.words.contains(&word) && !reasons.contains(&reason) {
+                reasons.push(reason);
+            }
+        }
+        reasons
+    }
+}
+
+#[derive(Deserialize)]
+struct ModerationVerdict {
+    flagged: bool,
+    #[serde(default)]
+    reasons: Vec<String>,
+}
+
+/// Asks the service at `MODERATION_API_URL`: the text is posted as `{"text": ...}`, with
+/// `MODERATION_API_KEY` as bearer token, and the answer is `{"flagged": bool, "reasons": [...]}`.
+/// An unreachable service lets the text through, so an outage there doesn't stop all posting.
+pub struct ApiModerator {
+    client: reqwest::Client,
+    url: String,
+    api_key: Option<String>,
+}
+
+impl ApiModerator {
+    pub fn new(url: &str, api_key: Option<&str>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.to_string(),
+            api_key: api_key.map(String::from),
+        }
+    }
+}
+
+#[async_trait]
+impl ContentModerator for ApiModerator {
+    async fn review(&self, text: &str) -> Vec<String> {
+        let mut request = self.client.post(&self.url)
+            .json(&json!({ "text": text }))
+            .timeout(Duration::from_secs(5));
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        let verdict = match request.send().await.and_then(|response| response.error_for_status()) {
+            Ok(response) => response.json::<ModerationVerdict>().await,
+            Err(e) => Err(e),
+        };
+        match verdict {
+            Ok(verdict) if verdict.flagged && verdict.reasons.is_empty() => vec!["Flagged by the content filter.".to_string()],
+            Ok(verdict) if verdict.flagged => verdict.reasons,
+            Ok(_) => Vec::new(),
+            Err(e) => {
+                warn!("Content moderation service failed, letting the text through: {}", e);
+                Vec::new()
+            }
+        }
+    }
+}
+
+/// The moderator `CONTENT_MODERATION` selects, for `AppState.moderator`.
+pub fn from_config(config: &Config) -> Arc<dyn ContentModerator> {
+    match (config.content_moderation.as_str(), config.moderation_api_url.as_deref()) {
+        ("wordlist", _) => Arc::new(WordlistModerator::new(config.moderation_wordlist.iter().map(String::as_str))),
+        ("api", Some(url)) => Arc::new(ApiModerator::new(url, config.moderation_api_key.as_deref())),
+        _ => Arc::new(NoModeration),
+    }
+}
+
+/// Reviews the named fields of a post or comment before it is saved. With `MODERATION_ACTION=reject`
+/// flagged content is refused with 422 `CONTENT_REJECTED` and the reasons per field. With `report`
+/// it is let through and the findings are returned, to be filed with `report_flagged` once the
+/// content has an id.
+pub async fn moderate(app_state: &AppState, fields: &[(&str, &str)]) -> Result<Option<String>, AppError> {
+    let mut flagged = Vec::new();
+    for (field, text) in fields {
+        let reasons = app_state.moderator.review(text).await;
+        if !reasons.is_empty() {
+            flagged.push(FieldError { field: field.to_string(), messages: reasons });
+        }
+    }
+    if flagged.is_empty() {
+        return Ok(None);
+    }
+    if !app_state.env.moderation_report {
+        return Err(AppError::ContentRejected(flagged));
+    }
+    let details = flagged.iter()
+        .map(|field| format!("{}: {}", field.field, field.messages.join(" ")))
+        .collect::<Vec<_>>()
+        .join("\n");
+    Ok(Some(details))
+}
+
+/// Queues flagged content for the admins as an open report without a reporter. A failure is only
+/// logged, the content is already published.
+pub async fn report_flagged(app_state: &AppState, target: ReportTarget, reason: ReportReason, details: Option<String>) {
+    let Some(details) = details else {
+        return;
+    };
+    let new_report = NewReport {
+        reporter_id: None,
+        target,
+        reason,
+        details: Some(details.chars().take(500).collect()),
+    };
+    if let Err(e) = app_state.db.reports.save_report(new_report).await {
+        warn!("Failed to file a report for flagged content: {}", e);
+    }
+}
\ No newline at end of file
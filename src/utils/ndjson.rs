@@ -0,0 +1,21 @@
+use std::io;
+use axum::body::{Body, Bytes};
+use futures_util::StreamExt;
+use log::warn;
+use serde::Serialize;
+use crate::db::RowStream;
+
+/// The rows as an `application/x-ndjson` body, one JSON object per line, serialized as they are
+/// read. A database error mid-way aborts the body, so the client sees a failed download instead of
+/// one that looks complete.
+pub fn body<T: Serialize + Send + 'static>(rows: RowStream<T>) -> Body {
+    Body::from_stream(rows.map(|row| {
+        let row = row.map_err(|e| {
+            warn!("Failed to read rows for a stream: {}", e);
+            io::Error::other(e.to_string())
+        })?;
+        let mut line = serde_json::to_vec(&row).map_err(io::Error::other)?;
+        line.push(b'\n');
+        Ok::<_, io::Error>(Bytes::from(line))
+    }))
+}
\ No newline at end of file
@@ -0,0 +1,44 @@
+use std::{net::{IpAddr, SocketAddr}, time::Duration};
+use reqwest::{Client, Url};
+
+/// A client for requests to a url a user gave us, one that won't follow redirects and can only
+/// connect to the address checked here. Unless `allow_private_hosts`, urls whose host resolves to a
+/// loopback, private or link-local address are refused, so a user can't make the server fetch from
+/// the internal network.
+pub async fn guarded_client(url: &Url, timeout: Duration, allow_private_hosts: bool) -> Result<Client, String> {
+    let host = url.host_str().ok_or("url has no host")?.to_string();
+    let port = url.port_or_known_default().ok_or("url has no port")?;
+    let address = tokio::net::lookup_host((host.trim_matches(['[', ']']), port)).await
+        .map_err(|e| format!("{} does not resolve: {}", host, e))?
+        .find(|address| allow_private_hosts || is_public(address.ip()))
+        .ok_or_else(|| format!("{} has no public address", host))?;
+    Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .resolve(&host, SocketAddr::new(address.ip(), port))
+        .timeout(timeout)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// Reads the body of `response`, failing once it grows past `max_bytes`.
+pub async fn read_limited(mut response: reqwest::Response, max_bytes: usize) -> Result<Vec<u8>, String> {
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        if body.len() + chunk.len() > max_bytes {
+            return Err(format!("body is larger than {} bytes", max_bytes));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => !(ip.is_private() || ip.is_loopback() || ip.is_link_local() || ip.is_unspecified()
+            || ip.is_broadcast() || ip.is_documentation() || ip.octets()[0] == 100 && ip.octets()[1] & 0xc0 == 64),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => !(ip.is_loopback() || ip.is_unspecified() || ip.is_unique_local() || ip.is_unicast_link_local()),
+        },
+    }
+}
\ No newline at end of file
@@ -2,43 +2,97 @@ use argon2::{
     password_hash::{
         rand_core::OsRng,
         PasswordHash,
-        PasswordHasher,
+        PasswordHasher as _,
         PasswordVerifier,
         SaltString
     },
+    Algorithm,
     Argon2,
+    Params,
+    Version,
 };
-use crate::error::ErrorMessage;
+use crate::{config::Config, error::ErrorMessage};
 
 const MAX_PASSWORD_LENGTH: usize = 64;
 
-pub fn hash(password: impl Into<String>) -> Result<String, ErrorMessage> {
-    let password = password.into();
-    if password.is_empty() {
-        return Err(ErrorMessage::EmptyPassword);
+/// Hashes and checks account passwords, the hashes are stored in PHC string format.
+pub trait PasswordHasher: Send + Sync {
+    fn hash(&self, password: &str) -> Result<String, ErrorMessage>;
+    fn verify(&self, password: &str, hashed_password: &str) -> Result<bool, ErrorMessage>;
+    /// Whether a stored hash was made with a different algorithm or weaker parameters than the
+    /// configured ones, and should be replaced the next time the plain password is known.
+    fn needs_rehash(&self, hashed_password: &str) -> bool;
+}
+
+/// Argon2id with the costs set by `PASSWORD_HASH_MEMORY_KIB`, `PASSWORD_HASH_ITERATIONS` and
+/// `PASSWORD_HASH_PARALLELISM`. The default is argon2's own: 19 MiB, 2 iterations, 1 lane.
+#[derive(Clone, Default)]
+pub struct Argon2idHasher {
+    params: Params,
+}
+
+impl Argon2idHasher {
+    pub fn new(memory_kib: u32, iterations: u32, parallelism: u32) -> Result<Self, ErrorMessage> {
+        let params = Params::new(memory_kib, iterations, parallelism, None)
+            .map_err(|_| ErrorMessage::HashingError)?;
+        Ok(Self { params })
     }
-    if password.len() > MAX_PASSWORD_LENGTH {
-        return Err(ErrorMessage::ExceededMaxPasswordLength(MAX_PASSWORD_LENGTH));
+    /// The settings are checked when the configuration loads, so this only fails on a `Config`
+    /// built by hand.
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(config.password_hash_memory_kib, config.password_hash_iterations, config.password_hash_parallelism)
+            .expect("Invalid password hashing parameters.")
+    }
+    fn argon2(&self) -> Argon2<'static> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
     }
-    let salt = SaltString::generate(&mut OsRng);
-    let hashed_password = Argon2::default()
-        .hash_password(password.as_bytes(), &salt)
-        .map_err(|_| ErrorMessage::HashingError)?
-        .to_string();
-    Ok(hashed_password)
 }
 
-pub fn compare(password: &str, hashed_password: &str) -> Result<bool, ErrorMessage> {
+fn check_length(password: &str) -> Result<(), ErrorMessage> {
     if password.is_empty() {
         return Err(ErrorMessage::EmptyPassword);
     }
     if password.len() > MAX_PASSWORD_LENGTH {
         return Err(ErrorMessage::ExceededMaxPasswordLength(MAX_PASSWORD_LENGTH));
     }
-    let parsed_hash = PasswordHash::new(hashed_password)
-        .map_err(|_| ErrorMessage::InvalidHashFormat)?;
-    let password_matched = Argon2::default()
-        .verify_password(password.as_bytes(), &parsed_hash)
-        .map_or(false, |_| true);
-    Ok(password_matched)
+    Ok(())
+}
+
+impl PasswordHasher for Argon2idHasher {
+    fn hash(&self, password: &str) -> Result<String, ErrorMessage> {
+        check_length(password)?;
+        let salt = SaltString::generate(&mut OsRng);
+        let hashed_password = self.argon2()
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|_| ErrorMessage::HashingError)?
+            .to_string();
+        Ok(hashed_password)
+    }
+    /// Hashes made with older parameters still verify, the parameters are read from the hash.
+    fn verify(&self, password: &str, hashed_password: &str) -> Result<bool, ErrorMessage> {
+        check_length(password)?;
+        let parsed_hash = PasswordHash::new(hashed_password)
+            .map_err(|_| ErrorMessage::InvalidHashFormat)?;
+        let password_matched = self.argon2()
+            .verify_password(password.as_bytes(), &parsed_hash)
+            .is_ok();
+        Ok(password_matched)
+    }
+    /// Only a strengthening triggers a rehash, lowering a cost leaves the existing hashes alone.
+    fn needs_rehash(&self, hashed_password: &str) -> bool {
+        let Ok(parsed_hash) = PasswordHash::new(hashed_password) else {
+            return false;
+        };
+        if parsed_hash.algorithm != Algorithm::Argon2id.ident() || parsed_hash.version != Some(Version::V0x13.into()) {
+            return true;
+        }
+        match Params::try_from(&parsed_hash) {
+            Ok(stored) => {
+                stored.m_cost() < self.params.m_cost()
+                    || stored.t_cost() < self.params.t_cost()
+                    || stored.p_cost() < self.params.p_cost()
+            }
+            Err(_) => true,
+        }
+    }
 }
\ No newline at end of file
@@ -1,3 +1,4 @@
+use std::time::Duration;
 use rand::prelude::*;
 
 pub fn generate_random_string(n: u8) -> String {
@@ -6,4 +7,10 @@ pub fn generate_random_string(n: u8) -> String {
         .take(n as usize)
         .map(char::from)
         .collect()
+}
+
+/// `delay` cut by a random amount of up to half, so callers that failed together don't all retry
+/// at the same moment.
+pub fn with_jitter(delay: Duration) -> Duration {
+    delay.mul_f64(rand::rng().random_range(0.5..=1.0))
 }
\ No newline at end of file
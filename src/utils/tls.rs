@@ -0,0 +1,110 @@
+use std::{io, net::SocketAddr, sync::Arc, time::Duration};
+use axum::{
+    Router,
+    extract::Request,
+    http::{header, StatusCode, Uri},
+    response::{IntoResponse, Redirect},
+    serve::Listener,
+};
+use log::warn;
+use tokio::{net::{TcpListener, TcpStream}, sync::mpsc, time::timeout};
+use tokio_rustls::{
+    TlsAcceptor,
+    server::TlsStream,
+    rustls::{
+        ServerConfig,
+        crypto::ring::default_provider,
+        pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+    },
+};
+
+/// A client that opens a socket and never finishes the handshake is dropped after this long.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Reads a PEM certificate chain and private key into a rustls server config. Only http/1.1 is
+/// offered over ALPN, axum is built without its http2 feature.
+pub fn load_tls_config(cert_path: &str, key_path: &str) -> Result<Arc<ServerConfig>, String> {
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("Failed to read TLS_CERT_PATH {}: {}", cert_path, e))?;
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .map_err(|e| format!("Failed to read TLS_KEY_PATH {}: {}", key_path, e))?;
+    let mut config = ServerConfig::builder_with_provider(Arc::new(default_provider()))
+        .with_safe_default_protocol_versions()
+        .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+        .map_err(|e| format!("Invalid TLS certificate or key: {}", e))?;
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+/// TLS listener for `axum::serve`. Handshakes run on their own tasks, so one slow client does not hold
+/// up the accept loop, and only connections that finished the handshake are handed to axum.
+pub struct TlsListener {
+    local_addr: SocketAddr,
+    connections: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+}
+
+impl TlsListener {
+    pub fn new(listener: TcpListener, config: Arc<ServerConfig>) -> io::Result<Self> {
+        let local_addr = listener.local_addr()?;
+        let acceptor = TlsAcceptor::from(config);
+        let (sender, connections) = mpsc::channel(64);
+        tokio::spawn(async move {
+            loop {
+                let (stream, addr) = match listener.accept().await {
+                    Ok(connection) => connection,
+                    Err(e) => {
+                        warn!("Failed to accept TCP connection: {}", e);
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+                };
+                let acceptor = acceptor.clone();
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    match timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(stream)) => {
+                            let _ = sender.send((stream, addr)).await;
+                        }
+                        Ok(Err(e)) => warn!("TLS handshake with {} failed: {}", addr, e),
+                        Err(_) => warn!("TLS handshake with {} timed out", addr),
+                    }
+                });
+            }
+        });
+        Ok(Self { local_addr, connections })
+    }
+}
+
+impl Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.connections.recv().await {
+            Some(connection) => connection,
+            // The accept task only stops when the runtime shuts down.
+            None => std::future::pending().await,
+        }
+    }
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
+
+/// Plain HTTP server that answers every request with a permanent redirect to the same path over HTTPS.
+pub async fn serve_https_redirect(listener: TcpListener, https_port: u16) -> io::Result<()> {
+    let app = Router::new().fallback(move |req: Request| async move {
+        let host = req.headers()
+            .get(header::HOST)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<Uri>().ok())
+            .and_then(|uri| uri.host().map(str::to_string));
+        let Some(host) = host else {
+            return StatusCode::BAD_REQUEST.into_response();
+        };
+        let path = req.uri().path_and_query().map(|value| value.as_str()).unwrap_or("/");
+        let authority = if https_port == 443 { host } else { format!("{}:{}", host, https_port) };
+        Redirect::permanent(&format!("https://{}{}", authority, path)).into_response()
+    });
+    axum::serve(listener, app).await
+}
\ No newline at end of file
@@ -0,0 +1,196 @@
+// `ApiClient` against the in-process `spawn_app` server, built with `--features client`. The flows
+// mirror tests/spawn_app.rs through the typed client, and every operation in the OpenAPI document
+// must have a client method, so a documented endpoint can't be left out of the client.
+use std::sync::{Arc, Mutex};
+use axum::{Router, http::{Method, StatusCode as HttpStatus, Uri}};
+use axum_restful_api::{
+    client::{ApiClient, ClientError},
+    modules::{role::model::RoleType, user::dto::UserSettings},
+    openapi::ApiDoc,
+    test_utils::{spawn_app, TEST_PASSWORD},
+};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use utoipa::OpenApi;
+use uuid::Uuid;
+
+/// The documented operations without a typed client method: they answer RSS, images or a stream
+/// rather than the JSON envelope.
+const UNTYPED_OPERATIONS: &[(&str, &str)] = &[
+    ("GET", "/api/post/stream"),
+    ("GET", "/api/tag/{}/posts.rss"),
+    ("GET", "/api/user/{}/avatar"),
+    ("GET", "/api/user/{}/posts.rss"),
+];
+
+fn from_json<T: DeserializeOwned>(value: Value) -> T {
+    serde_json::from_value(value).unwrap()
+}
+fn api_error(result: Result<impl Sized, ClientError>) -> (StatusCode, Option<String>) {
+    match result {
+        Err(ClientError::Api { status, code, .. }) => (status, code),
+        Err(e) => panic!("not an API error: {}", e),
+        Ok(_) => panic!("the request succeeded"),
+    }
+}
+
+#[tokio::test]
+async fn signed_in_clients_refresh_and_sign_out() {
+    let app = spawn_app(&[]).await;
+    app.db.add_user("Clark Kent", "clark@example.com", TEST_PASSWORD, RoleType::User);
+    let mut client = ApiClient::new(app.url(""));
+
+    let wrong = client.sign_in(&from_json(json!({ "email": "clark@example.com", "password": "kryptonite" }))).await;
+    assert_eq!(api_error(wrong).1.as_deref(), Some("WRONG_CREDENTIALS"));
+    let signed_in = client.sign_in(&from_json(json!({ "email": "clark@example.com", "password": TEST_PASSWORD }))).await.unwrap();
+    assert_eq!(client.access_token(), Some(signed_in.token.access_token.as_str()));
+    assert_eq!(client.user_self().await.unwrap().email, "clark@example.com");
+
+    let refreshed = client.refresh_token().await.unwrap();
+    assert_eq!(client.access_token(), Some(refreshed.access_token.as_str()));
+    assert_eq!(client.user_self().await.unwrap().name, "Clark Kent");
+
+    client.sign_out().await.unwrap();
+    assert_eq!(client.access_token(), None);
+    assert_eq!(api_error(client.user_self().await).0, StatusCode::UNAUTHORIZED);
+    assert_eq!(api_error(client.refresh_token().await).0, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn settings_are_read_and_replaced_through_the_client() {
+    let app = spawn_app(&[]).await;
+    let (_, token) = app.user_with_token("Clark Kent", "clark@example.com", RoleType::User);
+    let client = ApiClient::new(app.url("")).with_access_token(token);
+
+    let settings = client.user_settings().await.unwrap();
+    assert_eq!(serde_json::to_value(&settings).unwrap()["email_digest"], "off");
+    let weekly: UserSettings = from_json(json!({ "email_digest": "weekly" }));
+    let settings = client.user_settings_update(&weekly).await.unwrap();
+    assert_eq!(serde_json::to_value(&settings).unwrap()["email_digest"], "weekly");
+    let too_many = UserSettings { feed_limit: Some(500), ..UserSettings::default() };
+    assert_eq!(api_error(client.user_settings_update(&too_many).await), (StatusCode::BAD_REQUEST, Some("VALIDATION_FAILED".to_string())));
+}
+
+#[tokio::test]
+async fn users_are_patched_and_found_through_the_client() {
+    let app = spawn_app(&[]).await;
+    let (clark, token) = app.user_with_token("Clark Kent", "clark@example.com", RoleType::User);
+    let client = ApiClient::new(app.url("")).with_access_token(token);
+
+    let patched = client.user_patch(clark.id, &from_json(json!({ "username": "superman", "bio": "Daily Planet" })), None).await.unwrap();
+    assert_eq!(patched.username.as_deref(), Some("superman"));
+    let stale = client.user_patch(clark.id, &from_json(json!({ "bio": "Smallville" })), Some(clark.updated_at)).await;
+    assert_eq!(api_error(stale), (StatusCode::CONFLICT, Some("VERSION_CONFLICT".to_string())));
+
+    let found = client.user_search(&from_json(json!({ "q": "@super" }))).await.unwrap();
+    assert_eq!(found.iter().map(|user| user.name.as_str()).collect::<Vec<_>>(), ["Clark Kent"]);
+    let detail = client.user_by_username("superman").await.unwrap();
+    assert_eq!(detail.id, clark.id);
+    let available = client.availability(&from_json(json!({ "username": "superman" }))).await.unwrap();
+    assert_eq!(available.username, Some(false));
+}
+
+/// Calls every client method against a server that records the request and answers 404, and
+/// checks the recorded operations against the OpenAPI document.
+#[tokio::test]
+async fn the_client_covers_every_documented_operation() {
+    let recorded = Arc::new(Mutex::new(Vec::new()));
+    let requests = recorded.clone();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let router = Router::new().fallback(move |method: Method, uri: Uri| async move {
+        requests.lock().unwrap().push((method.to_string(), uri.path().to_string()));
+        HttpStatus::NOT_FOUND
+    });
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+    let mut client = ApiClient::new(base_url).with_access_token("token");
+    let id = Uuid::new_v4();
+    let batch = from_json(json!({ "ids": [id] }));
+    let post = from_json(json!({ "title": "Title", "content": "Content", "tags": [] }));
+    let comment = from_json(json!({ "content": "Comment" }));
+    let search = from_json(json!({ "q": "rust" }));
+
+    let _ = client.availability(&from_json(json!({}))).await;
+    let _ = client.sign_up(&from_json(json!({ "name": "Clark", "email": "clark@example.com", "password": TEST_PASSWORD, "password_confirm": TEST_PASSWORD }))).await;
+    let _ = client.sign_in(&from_json(json!({ "email": "clark@example.com", "password": TEST_PASSWORD }))).await;
+    let _ = client.refresh_token().await;
+    let _ = client.sign_out().await;
+    let _ = client.user_self().await;
+    let _ = client.user_settings().await;
+    let _ = client.user_settings_update(&UserSettings::default()).await;
+    let _ = client.user_detail(id).await;
+    let _ = client.user_by_username("clark").await;
+    let _ = client.user_batch(&batch).await;
+    let _ = client.user_update(id, &from_json(json!({ "name": "Clark" })), None).await;
+    let _ = client.user_patch(id, &from_json(json!({})), None).await;
+    let _ = client.user_follow_unfollow(id).await;
+    let _ = client.user_feeds(&from_json(json!({}))).await;
+    let _ = client.user_activity(id, &from_json(json!({}))).await;
+    let _ = client.user_search(&from_json(json!({ "q": "clark" }))).await;
+    let _ = client.user_suggestions(&from_json(json!({}))).await;
+    let _ = client.post_create(&post).await;
+    let _ = client.post_detail(id).await;
+    let _ = client.post_batch(&batch).await;
+    let _ = client.post_list_by_user(id).await;
+    let _ = client.post_update(id, &post, None).await;
+    let _ = client.post_patch(id, &from_json(json!({})), None).await;
+    let _ = client.post_delete(id).await;
+    let _ = client.post_view(id).await;
+    let _ = client.post_collaborators(id).await;
+    let _ = client.post_collaborator_add(id, &from_json(json!({ "user_id": id }))).await;
+    let _ = client.post_collaborator_remove(id, id).await;
+    let _ = client.comment_create(id, &comment).await;
+    let _ = client.comment_detail(id, id).await;
+    let _ = client.comment_list_by_post(id).await;
+    let _ = client.comment_update(id, &comment, None).await;
+    let _ = client.comment_delete(id).await;
+    let _ = client.organization_create(&from_json(json!({ "name": "Daily Planet", "description": "" }))).await;
+    let _ = client.organization_detail(id).await;
+    let _ = client.organization_update(id, &from_json(json!({}))).await;
+    let _ = client.organization_delete(id).await;
+    let _ = client.organization_members(id).await;
+    let _ = client.organization_member_update(id, id, &from_json(json!({ "role": "editor" }))).await;
+    let _ = client.organization_member_remove(id, id).await;
+    let _ = client.organization_leave(id).await;
+    let _ = client.organization_invite(id, &from_json(json!({ "user_id": id, "role": "editor" }))).await;
+    let _ = client.organization_invitation_revoke(id, id).await;
+    let _ = client.organization_invitations().await;
+    let _ = client.organization_invitation_accept(id).await;
+    let _ = client.organization_invitation_decline(id).await;
+    let _ = client.organization_follow(id).await;
+    let _ = client.organization_unfollow(id).await;
+    let _ = client.organization_usage(id).await;
+    let _ = client.analytics_events(&from_json(json!({ "events": [] }))).await;
+    let _ = client.active_announcements().await;
+    let _ = client.current_tos().await;
+    let _ = client.accept_tos(&from_json(json!({ "version": "1" }))).await;
+    let _ = client.search(&search).await;
+
+    let templated = |path: &str| path.split('/')
+        .map(|segment| if Uuid::parse_str(segment).is_ok() || segment == "clark" { "{}" } else { segment })
+        .collect::<Vec<_>>()
+        .join("/");
+    let mut covered: Vec<(String, String)> = recorded.lock().unwrap().iter()
+        .map(|(method, path)| (method.clone(), templated(path)))
+        .collect();
+    covered.extend(UNTYPED_OPERATIONS.iter().map(|(method, path)| (method.to_string(), path.to_string())));
+    covered.sort();
+    covered.dedup();
+    let mut documented = Vec::new();
+    for (path, item) in ApiDoc::openapi().paths.paths {
+        let path = path.split('/')
+            .map(|segment| if segment.starts_with('{') { "{}" } else { segment })
+            .collect::<Vec<_>>()
+            .join("/");
+        for (method, operation) in [("GET", &item.get), ("PUT", &item.put), ("POST", &item.post), ("DELETE", &item.delete), ("PATCH", &item.patch)] {
+            if operation.is_some() {
+                documented.push((method.to_string(), path.clone()));
+            }
+        }
+    }
+    documented.sort();
+    assert_eq!(covered, documented);
+}
@@ -0,0 +1,381 @@
+// Contract tests for the JSON bodies the API sends. Each test pins the exact shape of a response,
+// so renaming, adding or dropping a field fails here before it reaches a client.
+use axum::{body::to_bytes, response::IntoResponse};
+use axum_restful_api::{
+    dto::{PaginatedData, PaginationMeta, SuccessResponse},
+    error::{AppError, ErrorMessage},
+    modules::{
+        activity::model::ActivityItem,
+        auth::dto::{SignInResponse, SignUpRequest, TokenResponse},
+        comment::model::{Comment, CommentsByPost},
+        post::{
+            dto::EmbedTokenResponse,
+            model::{Post, PostComment, PostDetail, PostLicense},
+        },
+        role::model::RoleType,
+        user::dto::{FollowUnfollowResponse, UserFeeds, UserResponse},
+    },
+};
+use chrono::{DateTime, TimeZone, Utc};
+use serde::Serialize;
+use serde_json::{json, Value};
+use uuid::Uuid;
+use validator::Validate;
+
+fn id(n: u128) -> Uuid {
+    Uuid::from_u128(n)
+}
+fn timestamp() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()
+}
+fn to_json<T: Serialize>(value: T) -> Value {
+    serde_json::to_value(value).unwrap()
+}
+async fn error_body(error: AppError) -> (u16, Value) {
+    let response = error.into_response();
+    let status = response.status().as_u16();
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap())
+}
+fn user_response() -> UserResponse {
+    UserResponse {
+        id: id(1),
+        name: "Clark Kent".to_string(),
+        email: "clark_kent@gmail.com".to_string(),
+        role: RoleType::User,
+        password: "$argon2id$hash".to_string(),
+        is_verified: true,
+        is_banned: false,
+        is_private: false,
+        username: Some("clark".to_string()),
+        bio: None,
+        avatar_url: None,
+        website: None,
+        created_at: timestamp(),
+        updated_at: timestamp(),
+    }
+}
+fn post() -> Post {
+    Post {
+        id: id(2),
+        user_id: id(1),
+        organization_id: None,
+        title: "Krypton".to_string(),
+        content: "Last son of Krypton".to_string(),
+        tags: vec!["space".to_string()],
+        license: PostLicense::CcBySa,
+        views_count: 12,
+        created_at: timestamp(),
+        updated_at: timestamp(),
+    }
+}
+fn comment() -> Comment {
+    Comment {
+        id: id(3),
+        user_id: id(1),
+        post_id: id(2),
+        content: "Great post about Krypton".to_string(),
+        created_at: timestamp(),
+        updated_at: timestamp(),
+    }
+}
+
+#[test]
+fn success_response_with_data() {
+    let body = SuccessResponse::new("Getting logged in user profile data.", Some(json!({ "id": 1 })));
+    assert_eq!(to_json(&body.0), json!({
+        "status": "success",
+        "message": "Getting logged in user profile data.",
+        "data": { "id": 1 },
+    }));
+}
+
+#[test]
+fn success_response_without_data() {
+    let body = SuccessResponse::<()>::new("Successfully deleted a post.", None);
+    assert_eq!(to_json(&body.0), json!({
+        "status": "success",
+        "message": "Successfully deleted a post.",
+    }));
+}
+
+#[tokio::test]
+async fn error_response() {
+    let (status, body) = error_body(AppError::not_found(ErrorMessage::DataNotFound)).await;
+    assert_eq!(status, 404);
+    assert_eq!(body, json!({
+        "status": "error",
+        "code": "NOT_FOUND",
+        "message": "Data is not found.",
+    }));
+}
+
+#[tokio::test]
+async fn error_response_with_code() {
+    let (status, body) = error_body(AppError::unauthorized(ErrorMessage::TokenExpired)).await;
+    assert_eq!(status, 401);
+    assert_eq!(body, json!({
+        "status": "error",
+        "code": "TOKEN_EXPIRED",
+        "message": "Token has expired.",
+    }));
+}
+
+#[tokio::test]
+async fn validation_error_response() {
+    let request = SignUpRequest {
+        name: "Bo".to_string(),
+        email: "not-an-email".to_string(),
+        password: "secret".to_string(),
+        password_confirm: "secret".to_string(),
+        invite_token: None,
+        captcha_token: None,
+    };
+    let error = AppError::from(request.validate().unwrap_err());
+    let (status, body) = error_body(error).await;
+    assert_eq!(status, 400);
+    assert_eq!(body, json!({
+        "status": "error",
+        "code": "VALIDATION_FAILED",
+        "message": "Validation Errors",
+        "error": [
+            { "field": "email", "messages": ["Email is invalid"] },
+            { "field": "name", "messages": ["Name must be between 4 and 50 characters"] },
+        ],
+    }));
+}
+
+#[tokio::test]
+async fn routing_error_response() {
+    let (status, body) = error_body(AppError::not_found(ErrorMessage::RouteNotFound("GET /api/nope".to_string()))).await;
+    assert_eq!(status, 404);
+    assert_eq!(body, json!({
+        "status": "error",
+        "code": "ROUTE_NOT_FOUND",
+        "message": "Route GET /api/nope is not exists",
+    }));
+}
+
+#[test]
+fn pagination_meta() {
+    assert_eq!(to_json(PaginationMeta::new(2, 5, 11)), json!({
+        "page": 2,
+        "limit": 5,
+        "total_pages": 3,
+        "total_items": 11,
+        "has_next": true,
+        "has_prev": true,
+    }));
+}
+
+#[test]
+fn user_response_hides_password() {
+    assert_eq!(to_json(user_response()), json!({
+        "id": "00000000-0000-0000-0000-000000000001",
+        "name": "Clark Kent",
+        "email": "clark_kent@gmail.com",
+        "role": "User",
+        "is_verified": true,
+        "is_banned": false,
+        "is_private": false,
+        "username": "clark",
+        "bio": null,
+        "avatar_url": null,
+        "website": null,
+        "created_at": "2025-01-01T00:00:00Z",
+        "updated_at": "2025-01-01T00:00:00Z",
+    }));
+}
+
+#[test]
+fn sign_in_response() {
+    let response = SignInResponse {
+        user: user_response(),
+        token: TokenResponse {
+            access_token: "jwt".to_string(),
+            token_type: "Bearer".to_string(),
+            expires_in: "60 Minutes".to_string(),
+            access_token_expires_at: timestamp(),
+            refresh_token_expires_at: timestamp(),
+        },
+    };
+    let body = to_json(response);
+    assert_eq!(body["token"], json!({
+        "access_token": "jwt",
+        "token_type": "Bearer",
+        "expires_in": "60 Minutes",
+        "access_token_expires_at": "2025-01-01T00:00:00Z",
+        "refresh_token_expires_at": "2025-01-01T00:00:00Z",
+    }));
+    assert_eq!(body["user"], to_json(user_response()));
+}
+
+#[test]
+fn follow_unfollow_response() {
+    let response = FollowUnfollowResponse {
+        user_target: id(1),
+        user_sender: id(4),
+        message: "Successfully Followed".to_string(),
+    };
+    assert_eq!(to_json(response), json!({
+        "user_target": "00000000-0000-0000-0000-000000000001",
+        "user_sender": "00000000-0000-0000-0000-000000000004",
+        "message": "Successfully Followed",
+    }));
+}
+
+#[test]
+fn post_response() {
+    assert_eq!(to_json(post()), json!({
+        "id": "00000000-0000-0000-0000-000000000002",
+        "user_id": "00000000-0000-0000-0000-000000000001",
+        "organization_id": null,
+        "title": "Krypton",
+        "content": "Last son of Krypton",
+        "tags": ["space"],
+        "license": "cc-by-sa",
+        "views_count": 12,
+        "created_at": "2025-01-01T00:00:00Z",
+        "updated_at": "2025-01-01T00:00:00Z",
+    }));
+}
+
+#[test]
+fn post_detail_response() {
+    let detail = PostDetail {
+        id: id(2),
+        title: "Krypton".to_string(),
+        content: "Last son of Krypton".to_string(),
+        tags: vec!["space".to_string()],
+        license: PostLicense::AllRightsReserved,
+        views_count: 0,
+        created_at: timestamp(),
+        updated_at: timestamp(),
+        user: user_response(),
+        comments: vec![PostComment {
+            id: id(3),
+            user_id: id(1),
+            content: "Great post about Krypton".to_string(),
+            created_at: timestamp(),
+            updated_at: timestamp(),
+        }],
+    };
+    assert_eq!(to_json(detail), json!({
+        "id": "00000000-0000-0000-0000-000000000002",
+        "title": "Krypton",
+        "content": "Last son of Krypton",
+        "tags": ["space"],
+        "license": "all-rights-reserved",
+        "views_count": 0,
+        "created_at": "2025-01-01T00:00:00Z",
+        "updated_at": "2025-01-01T00:00:00Z",
+        "user": to_json(user_response()),
+        "comments": [{
+            "id": "00000000-0000-0000-0000-000000000003",
+            "user_id": "00000000-0000-0000-0000-000000000001",
+            "content": "Great post about Krypton",
+            "created_at": "2025-01-01T00:00:00Z",
+            "updated_at": "2025-01-01T00:00:00Z",
+        }],
+    }));
+}
+
+#[test]
+fn comments_by_post_response() {
+    let response = CommentsByPost {
+        post: post(),
+        comments: vec![comment()],
+    };
+    assert_eq!(to_json(response), json!({
+        "post": to_json(post()),
+        "comments": [{
+            "id": "00000000-0000-0000-0000-000000000003",
+            "user_id": "00000000-0000-0000-0000-000000000001",
+            "post_id": "00000000-0000-0000-0000-000000000002",
+            "content": "Great post about Krypton",
+            "created_at": "2025-01-01T00:00:00Z",
+            "updated_at": "2025-01-01T00:00:00Z",
+        }],
+    }));
+}
+
+#[test]
+fn paginated_feed_response() {
+    let feed = PaginatedData {
+        items: vec![UserFeeds {
+            id: id(2),
+            user_id: id(1),
+            organization_id: Some(id(4)),
+            title: "Krypton".to_string(),
+            content: "Last son of Krypton".to_string(),
+            tags: vec!["space".to_string()],
+            posted_by: "Clark Kent".to_string(),
+            comments_count: 1,
+            created_at: timestamp(),
+            updated_at: timestamp(),
+            comments: vec![comment()],
+        }],
+        pagination: PaginationMeta::new(1, 5, 1),
+    };
+    assert_eq!(to_json(feed), json!({
+        "items": [{
+            "id": "00000000-0000-0000-0000-000000000002",
+            "user_id": "00000000-0000-0000-0000-000000000001",
+            "organization_id": "00000000-0000-0000-0000-000000000004",
+            "title": "Krypton",
+            "content": "Last son of Krypton",
+            "tags": ["space"],
+            "posted_by": "Clark Kent",
+            "comments_count": 1,
+            "created_at": "2025-01-01T00:00:00Z",
+            "updated_at": "2025-01-01T00:00:00Z",
+            "comments": [to_json(comment())],
+        }],
+        "pagination": {
+            "page": 1,
+            "limit": 5,
+            "total_pages": 1,
+            "total_items": 1,
+            "has_next": false,
+            "has_prev": false,
+        },
+    }));
+}
+
+#[test]
+fn activity_item_response() {
+    let item = ActivityItem {
+        kind: "comment".to_string(),
+        id: id(3),
+        post_id: Some(id(2)),
+        title: Some("Krypton".to_string()),
+        content: Some("Great post".to_string()),
+        target_user_id: Some(id(1)),
+        target_user_name: Some("Clark Kent".to_string()),
+        created_at: timestamp(),
+    };
+    assert_eq!(to_json(item), json!({
+        "kind": "comment",
+        "id": "00000000-0000-0000-0000-000000000003",
+        "post_id": "00000000-0000-0000-0000-000000000002",
+        "title": "Krypton",
+        "content": "Great post",
+        "target_user_id": "00000000-0000-0000-0000-000000000001",
+        "target_user_name": "Clark Kent",
+        "created_at": "2025-01-01T00:00:00Z",
+    }));
+}
+
+#[test]
+fn embed_token_response() {
+    let response = EmbedTokenResponse {
+        token: "jwt".to_string(),
+        embed_url: "/api/post/00000000-0000-0000-0000-000000000002/embed?token=jwt".to_string(),
+        expires_in: 2592000,
+    };
+    assert_eq!(to_json(response), json!({
+        "token": "jwt",
+        "embed_url": "/api/post/00000000-0000-0000-0000-000000000002/embed?token=jwt",
+        "expires_in": 2592000,
+    }));
+}
\ No newline at end of file
@@ -0,0 +1,320 @@
+// Request fuzzing for every route with proptest. Malformed JSON, absurd pagination values, random
+// UUIDs and random query strings are sent through `tower::ServiceExt::oneshot` to the router of a
+// `build_app` app as an anonymous caller, with a broken token, as a user and as an admin; whatever
+// the input, the API must never answer with a 5xx, and its errors come in the standard envelope.
+// The routes are the OpenAPI document's paths plus the undocumented ones listed below.
+//
+// Every repository is in memory and emails go to a FakeMailer, nothing leaves the process.
+// PROPTEST_CASES sets how many requests are sent per route, PROPTEST_RNG_SEED replays a run and a
+// failing request is shrunk to the smallest one that still fails.
+use std::{env, net::SocketAddr};
+use axum::{
+    body::{Body, Bytes},
+    extract::ConnectInfo,
+    http::{header, Method, Request},
+    Router,
+};
+use axum_restful_api::{
+    modules::role::model::RoleType,
+    openapi::ApiDoc,
+    test_utils::build_app,
+};
+use proptest::{
+    prelude::*,
+    collection::vec,
+    sample::select,
+    test_runner::{Config, TestError, TestRunner},
+};
+use serde_json::{json, Map, Value};
+use tokio::runtime::Runtime;
+use tower::ServiceExt;
+use utoipa::OpenApi;
+use uuid::Uuid;
+
+/// The routes `ApiDoc` leaves out, the documented ones are read from the spec. A route listed here
+/// that gets documented fails the test, so every route is fuzzed once.
+const UNDOCUMENTED_ROUTES: &[(&str, &str)] = &[
+    ("POST", "/api/admin/aggregates/refresh"),
+    ("GET", "/api/admin/announcements"),
+    ("POST", "/api/admin/announcements"),
+    ("GET", "/api/admin/announcements/{id}"),
+    ("PUT", "/api/admin/announcements/{id}"),
+    ("DELETE", "/api/admin/announcements/{id}"),
+    ("GET", "/api/admin/exports"),
+    ("POST", "/api/admin/exports"),
+    ("GET", "/api/admin/exports/{id}"),
+    ("GET", "/api/admin/exports/{id}/files/{file}"),
+    ("GET", "/api/admin/feature-flags"),
+    ("POST", "/api/admin/feature-flags"),
+    ("PUT", "/api/admin/feature-flags/{key}"),
+    ("DELETE", "/api/admin/feature-flags/{key}"),
+    ("POST", "/api/admin/impersonate/{user_id}"),
+    ("POST", "/api/admin/invitations"),
+    ("DELETE", "/api/admin/organizations/{id}"),
+    ("POST", "/api/admin/organizations/{id}/export"),
+    ("PUT", "/api/admin/organizations/{id}/quota"),
+    ("GET", "/api/admin/reports"),
+    ("POST", "/api/admin/reports/{id}/action"),
+    ("POST", "/api/admin/reports/{id}/dismiss"),
+    ("GET", "/api/admin/retention/dry-run"),
+    ("GET", "/api/admin/stats"),
+    ("GET", "/api/admin/stats/overview"),
+    ("GET", "/api/admin/stats/top-posts"),
+    ("GET", "/api/admin/tos"),
+    ("POST", "/api/admin/tos"),
+    ("GET", "/api/admin/users/export"),
+    ("GET", "/api/admin/users/export/download"),
+    ("POST", "/api/admin/users/export/link"),
+    ("POST", "/api/admin/users/import"),
+    ("GET", "/api/admin/users/stream"),
+    ("GET", "/api/admin/users/{id}/history"),
+    ("GET", "/api/admin/velocity-limits"),
+    ("PUT", "/api/admin/velocity-limits/{kind}"),
+    ("POST", "/api/admin/waitlist/approve"),
+    ("GET", "/api/admin/webhooks"),
+    ("POST", "/api/admin/webhooks"),
+    ("GET", "/api/admin/webhooks/deliveries"),
+    ("POST", "/api/admin/webhooks/deliveries/{id}/retry"),
+    ("DELETE", "/api/admin/webhooks/{id}"),
+    ("GET", "/api/auth/basic"),
+    ("POST", "/api/auth/forgot-password"),
+    ("POST", "/api/auth/resend-activation"),
+    ("POST", "/api/auth/reset-password"),
+    ("GET", "/api/auth/sessions/revoke"),
+    ("GET", "/api/auth/verify"),
+    ("POST", "/api/auth/verify"),
+    ("POST", "/api/comment/{comment_id}/report"),
+    ("GET", "/api/feed-import/drafts"),
+    ("DELETE", "/api/feed-import/drafts/{id}"),
+    ("POST", "/api/feed-import/drafts/{id}/publish"),
+    ("GET", "/api/feed-import/sources"),
+    ("POST", "/api/feed-import/sources"),
+    ("DELETE", "/api/feed-import/sources/{id}"),
+    ("GET", "/api/metrics"),
+    ("GET", "/api/openapi.json"),
+    ("GET", "/api/ping"),
+    ("GET", "/api/post/trending"),
+    ("GET", "/api/post/{id}/embed"),
+    ("POST", "/api/post/{id}/embed-token"),
+    ("POST", "/api/post/{id}/report"),
+    ("PUT", "/api/user/change-password"),
+    ("GET", "/api/user/follow-requests"),
+    ("POST", "/api/user/follow-requests/{id}/accept"),
+    ("POST", "/api/user/follow-requests/{id}/reject"),
+    ("GET", "/api/user/leaderboard"),
+    ("GET", "/api/user/users"),
+    ("DELETE", "/api/user/{id}"),
+    ("GET", "/api/user/{id}/followers"),
+    ("GET", "/api/user/{id}/following"),
+    ("GET", "/.well-known/jwks.json"),
+    ("GET", "/api/no-such-route"),
+];
+/// Links opened from an email, their errors are a page for the browser instead of the envelope.
+const LINK_PAGE_ROUTES: &[&str] = &["/api/auth/verify", "/api/auth/sessions/revoke"];
+/// Field names the handlers read, so random objects reach validation and not only deserialization.
+/// `count` is left out on purpose: a valid waitlist batch would approve real accounts, and so are
+/// `url` and `events`, which would register real webhook endpoints.
+const FIELDS: &[&str] = &[
+    "name", "email", "password", "password_confirm", "old_password", "new_password", "new_password_confirm",
+    "title", "content", "tags", "license", "is_private", "default_license", "reason", "details", "theme",
+    "language", "feed_limit", "feed_order_by", "email_notifications", "token", "ids", "username", "bio", "avatar_url",
+    "website", "description", "role", "user_id", "organization_id",
+    "monthly_request_quota", "kind", "screen", "properties", "occurred_at", "analytics",
+    "version", "summary",
+];
+const QUERY_KEYS: &[&str] = &[
+    "page", "limit", "order_by", "search", "since", "until", "is_verified", "token", "format", "status", "sort", "fields", "event",
+    "email", "username", "organizations",
+];
+
+/// `(method, path template)` of every route: the documented ones from the OpenAPI document, then
+/// `UNDOCUMENTED_ROUTES`.
+fn routes() -> Vec<(String, String)> {
+    let mut routes = Vec::new();
+    for (path, item) in ApiDoc::openapi().paths.paths {
+        let operations = [("GET", &item.get), ("POST", &item.post), ("PUT", &item.put), ("PATCH", &item.patch), ("DELETE", &item.delete)];
+        routes.extend(operations.into_iter().filter(|(_, operation)| operation.is_some()).map(|(method, _)| (method.to_string(), path.clone())));
+    }
+    for (method, path) in UNDOCUMENTED_ROUTES {
+        assert!(
+            !routes.iter().any(|(documented_method, documented_path)| documented_method == method && template(documented_path) == template(path)),
+            "{} {} is documented now, remove it from UNDOCUMENTED_ROUTES", method, path
+        );
+    }
+    routes.extend(UNDOCUMENTED_ROUTES.iter().map(|(method, path)| (method.to_string(), path.to_string())));
+    routes
+}
+
+/// `path` with its parameters unnamed, `/api/user/{id}` and `/api/user/{user_id}` are the same route.
+fn template(path: &str) -> String {
+    path.split('/').map(|segment| if segment.starts_with('{') { "{}" } else { segment }).collect::<Vec<_>>().join("/")
+}
+
+/// A generated request for one route, `token` picks one of the credentials the test signs in with.
+#[derive(Clone, Debug)]
+struct FuzzRequest {
+    uri: String,
+    token: usize,
+    json: bool,
+    body: Bytes,
+}
+
+fn number() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        Just(json!(0)),
+        Just(json!(-1)),
+        Just(json!(i64::MAX)),
+        Just(json!(u64::MAX)),
+        Just(json!(1e308)),
+        (-1000i64..1000).prop_map(|number| json!(number)),
+    ]
+}
+
+fn string() -> impl Strategy<Value = String> {
+    prop_oneof![
+        Just(String::new()),
+        Just(" ".to_string()),
+        (1usize..5000).prop_map(|length| "a".repeat(length)),
+        Just("<script>alert(1)</script>".to_string()),
+        Just("'; DROP TABLE users; --".to_string()),
+        Just("\u{0}\u{1f600}\u{202e}".to_string()),
+        Just("9999-99-99".to_string()),
+        Just("2025-01-01".to_string()),
+        Just("not-an-email@".to_string()),
+        any::<u128>().prop_map(|id| Uuid::from_u128(id).to_string()),
+        Just("ASC".to_string()),
+        any::<String>(),
+    ]
+}
+
+/// Any JSON value, objects mostly keyed with `FIELDS`.
+fn value() -> BoxedStrategy<Value> {
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::from),
+        number(),
+        string().prop_map(Value::from),
+    ];
+    leaf.prop_recursive(3, 24, 5, |inner| prop_oneof![
+        vec(inner.clone(), 0..4).prop_map(Value::Array),
+        object(inner),
+    ]).boxed()
+}
+
+fn object(values: impl Strategy<Value = Value>) -> impl Strategy<Value = Value> {
+    let key = prop_oneof![4 => select(FIELDS).prop_map(str::to_string), 1 => string()];
+    vec((key, values), 0..6).prop_map(|fields| Value::Object(fields.into_iter().collect::<Map<_, _>>()))
+}
+
+fn body() -> impl Strategy<Value = Bytes> {
+    prop_oneof![
+        Just(Vec::new()),
+        Just(b"{\"title\": ".to_vec()),
+        vec(any::<u8>(), 1..64),
+        value().prop_map(|value| serde_json::to_vec(&value).unwrap()),
+        object(value()).prop_map(|object| serde_json::to_vec(&object).unwrap()),
+    ].prop_map(Bytes::from)
+}
+
+fn path_segment() -> impl Strategy<Value = String> {
+    prop_oneof![
+        any::<u128>().prop_map(|id| Uuid::from_u128(id).to_string()),
+        Just(Uuid::nil().to_string()),
+        Just("not-a-uuid".to_string()),
+        any::<u32>().prop_map(|number| number.to_string()),
+    ]
+}
+
+fn query() -> impl Strategy<Value = String> {
+    let value = prop_oneof![
+        number().prop_map(|number| number.to_string()),
+        string().prop_map(|string| string.chars().take(64).collect()),
+        Just(String::new()),
+    ];
+    vec((select(QUERY_KEYS), value), 0..4).prop_map(|pairs| {
+        let pairs: Vec<String> = pairs.into_iter()
+            .map(|(key, value)| format!("{}={}", key, value.bytes().map(|byte| format!("%{:02X}", byte)).collect::<String>()))
+            .collect();
+        if pairs.is_empty() { String::new() } else { format!("?{}", pairs.join("&")) }
+    })
+}
+
+/// Requests for the route at `template`, its parameters filled in, with one of `tokens` credentials.
+fn request(template: &str, tokens: usize) -> impl Strategy<Value = FuzzRequest> {
+    let segments: Vec<BoxedStrategy<String>> = template.split('/')
+        .map(|segment| if segment.starts_with('{') { path_segment().boxed() } else { Just(segment.to_string()).boxed() })
+        .collect();
+    (segments, query(), 0..tokens, prop::bool::weighted(0.9), body()).prop_map(|(segments, query, token, json, body)| FuzzRequest {
+        uri: format!("{}{}", segments.join("/"), query),
+        token,
+        json,
+        body,
+    })
+}
+
+/// Sends `request` to `router` the way the server would hand it over, with the caller's address.
+async fn send(router: &Router, method: &Method, request: &FuzzRequest, token: Option<&str>) -> (u16, Bytes) {
+    let mut builder = Request::builder().method(method.clone()).uri(&request.uri);
+    if request.json {
+        builder = builder.header(header::CONTENT_TYPE, "application/json");
+    }
+    if let Some(token) = token {
+        builder = builder.header(header::AUTHORIZATION, format!("Bearer {}", token));
+    }
+    let mut http_request = builder.body(Body::from(request.body.clone())).unwrap();
+    http_request.extensions_mut().insert(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 40000))));
+    let response = router.clone().oneshot(http_request).await.unwrap();
+    let status = response.status().as_u16();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await
+        .unwrap_or_else(|e| panic!("{} {} answered {} and broke off: {}", method, request.uri, status, e));
+    (status, bytes)
+}
+
+#[test]
+fn every_route_answers_malformed_input_with_an_error_envelope() {
+    let runtime = Runtime::new().unwrap();
+    let app = runtime.block_on(build_app(&[
+        // Every request comes from the same address, the limiter would otherwise answer most of them.
+        ("RATE_LIMITER_MODE", "dry-run"),
+        // The padding only hides timing from clients, here it would just slow every availability case down.
+        ("AVAILABILITY_MIN_RESPONSE_MS", "0"),
+    ]));
+    let router = app.router();
+    let mut tokens = vec![None, Some("not-a-jwt".to_string())];
+    for (name, email, role) in [("Princess Diana", "diana@example.com", RoleType::Admin), ("Clark Kent", "clark@example.com", RoleType::User)] {
+        let user = app.db.add_user(name, email, "secret123", role);
+        tokens.push(Some(app.token_for(&user)));
+    }
+    let mut config = Config { failure_persistence: None, ..Config::default() };
+    if env::var_os("PROPTEST_CASES").is_none() {
+        config.cases = 64;
+    }
+
+    for (method, path) in routes() {
+        let method = Method::from_bytes(method.as_bytes()).unwrap();
+        let link_page = method == Method::GET && LINK_PAGE_ROUTES.contains(&path.as_str());
+        let mut runner = TestRunner::new(config.clone());
+        let result = runner.run(&request(&path, tokens.len()), |request| {
+            let (status, bytes) = runtime.block_on(send(&router, &method, &request, tokens[request.token].as_deref()));
+            let answer = String::from_utf8_lossy(&bytes);
+            prop_assert!(status < 500, "answered {}: {}", status, answer);
+            if status < 400 {
+                return Ok(());
+            }
+            if link_page {
+                prop_assert!(bytes.starts_with(b"<!DOCTYPE html>"), "answered {} without a page: {}", status, answer);
+                return Ok(());
+            }
+            let body: Value = serde_json::from_slice(&bytes).map_err(|_| TestCaseError::fail(format!("answered {} without JSON: {}", status, answer)))?;
+            prop_assert_eq!(&body["status"], "error", "answered {}: {}", status, body);
+            prop_assert!(body["code"].is_string(), "answered {}: {}", status, body);
+            prop_assert!(body["message"].is_string(), "answered {}: {}", status, body);
+            Ok(())
+        });
+        match result {
+            Ok(()) => {}
+            Err(TestError::Fail(reason, request)) => panic!("{} {}: {}, smallest failing request: {:?}", method, path, reason, request),
+            Err(e) => panic!("{} {}: {}", method, path, e),
+        }
+    }
+}
@@ -0,0 +1,1952 @@
+// The in-process test server from `test_utils`: nothing has to be running, the repositories are in
+// memory, emails land in a FakeMailer and the caches use a FakeRedis.
+use std::{collections::HashMap, env, fs, sync::{Arc, Mutex}};
+use axum::{Form, Json, Router, body::Bytes, http::{HeaderMap, StatusCode as HttpStatus}, response::IntoResponse, routing::{get, post}};
+use chrono::{Duration, NaiveDate, TimeZone, Utc};
+use axum_restful_api::{
+    db::DBClient,
+    error::AppError,
+    jobs::{audit_export, data_export, feed_import, flush_analytics, retention, webhook_delivery},
+    middleware::permission::Permission,
+    modules::{
+        audit_log::{model::{AuditAction, NewAuditLog}, sink::AuditSink},
+        aggregate::model::AggregateRepository,
+        digest::model::{DigestContent, DigestPost},
+        email::mail_digest::send_digest_email,
+        post::model::PostDetail,
+        role::model::RoleType,
+        user::dto::DigestFrequency,
+        webhook::{dto::NewWebhookEndpoint, model::WebhookEvent},
+    },
+    test_utils::{spawn_app, test_config, FakeMailer},
+    utils::{action_token, jwt, webhook_verify::{sign_standard_webhook, verify_webhook, WebhookProvider}},
+};
+use hmac::{Hmac, Mac};
+use jsonwebtoken::decode_header;
+use reqwest::StatusCode;
+use ring::{rand::SystemRandom, signature::{Ed25519KeyPair, KeyPair}};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use sqlx::postgres::PgPoolOptions;
+use uuid::Uuid;
+
+/// A fresh Ed25519 key pair as PEM files in the temp directory, `(private, public)` paths.
+fn ed25519_key_files() -> (String, String) {
+    let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+    let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+    let mut spki = vec![0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00];
+    spki.extend_from_slice(key_pair.public_key().as_ref());
+    let base = env::temp_dir().join(format!("jwt-{}", Uuid::new_v4()));
+    let (private, public) = (format!("{}.pem", base.display()), format!("{}.pub.pem", base.display()));
+    fs::write(&private, pem::encode(&pem::Pem::new("PRIVATE KEY", pkcs8.as_ref()))).unwrap();
+    fs::write(&public, pem::encode(&pem::Pem::new("PUBLIC KEY", spki))).unwrap();
+    (private, public)
+}
+
+#[tokio::test]
+async fn sign_up_sends_the_verification_email_through_the_fake_mailer() {
+    let app = spawn_app(&[]).await;
+    let response = reqwest::Client::new()
+        .post(app.url("/api/auth/sign-up"))
+        .json(&json!({
+            "name": "Clark Kent",
+            "email": "clark@example.com",
+            "password": "clark123",
+            "password_confirm": "clark123",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["data"]["verification_email"], "queued");
+    assert!(app.mailer.sent().is_empty(), "sign-up only queues the email");
+    app.send_queued_emails().await;
+    let sent = app.mailer.sent_to("clark@example.com");
+    assert_eq!(sent.len(), 1, "{:?}", app.mailer.sent());
+    assert_eq!(sent[0].subject, "Email Verification");
+    assert!(sent[0].html.contains("Clark Kent"));
+    assert!(sent[0].html.contains("/api/auth/verify?token="));
+    let user_id = body["data"]["id"].as_str().unwrap().parse().unwrap();
+    assert_eq!(app.db.audit_actions(user_id), vec!["user.registered", "email.verification"]);
+}
+
+#[tokio::test]
+async fn authenticated_users_are_cached_in_the_fake_redis() {
+    let app = spawn_app(&[]).await;
+    let (clark, token) = app.user_with_token("Clark Kent", "clark@example.com", RoleType::User);
+    assert!(app.redis.get(&format!("user:{}", clark.id)).is_none());
+    let response = reqwest::Client::new()
+        .get(app.url("/api/user/self"))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let expires_in: i64 = response.headers()["x-token-expires-in"].to_str().unwrap().parse().unwrap();
+    assert!((590..=600).contains(&expires_in), "{}", expires_in);
+    let cached = app.redis.get(&format!("user:{}", clark.id)).expect("user cached after the request");
+    let cached: Value = serde_json::from_slice(&cached).unwrap();
+    assert_eq!(cached["email"], "clark@example.com");
+}
+
+#[tokio::test]
+async fn config_overrides_apply_to_the_spawned_app() {
+    let app = spawn_app(&[("RATE_LIMITER_MAX", "2"), ("RATE_LIMITER_MODE", "dry-run")]).await;
+    assert_eq!(app.app_state.env.rate_limiter_max, 2);
+    for _ in 0..4 {
+        let response = reqwest::get(app.url("/api/ping")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
+
+#[tokio::test]
+async fn a_degraded_service_tightens_anonymous_rate_limits_only() {
+    let app = spawn_app(&[
+        ("RATE_LIMITER_MAX", "4"),
+        ("RATE_LIMITER_DURATION", "60"),
+        ("RATE_LIMITER_DEGRADED_LATENCY_MS", "500"),
+        ("RATE_LIMITER_DEGRADED_PERCENT", "25"),
+    ]).await;
+    app.app_state.load.record_latency(std::time::Duration::from_secs(2));
+    let token = jwt::create_token(&Uuid::new_v4().to_string(), &app.app_state.env.jwt_keys, 600).unwrap();
+    let client = reqwest::Client::new();
+
+    let statuses = [
+        client.get(app.url("/api/ping")).send().await.unwrap().status(),
+        client.get(app.url("/api/ping")).send().await.unwrap().status(),
+        client.get(app.url("/api/ping")).bearer_auth("not-a-jwt").send().await.unwrap().status(),
+        client.get(app.url("/api/ping")).bearer_auth(&token).send().await.unwrap().status(),
+    ];
+    assert_eq!(statuses, [StatusCode::OK, StatusCode::TOO_MANY_REQUESTS, StatusCode::TOO_MANY_REQUESTS, StatusCode::OK]);
+    assert!(app.app_state.load.is_degraded(&app.app_state.env));
+
+    // An anonymous flood from the same address doesn't count against the signed-in user.
+    for _ in 0..6 {
+        assert_eq!(client.get(app.url("/api/ping")).send().await.unwrap().status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+    for _ in 0..3 {
+        assert_eq!(client.get(app.url("/api/ping")).bearer_auth(&token).send().await.unwrap().status(), StatusCode::OK);
+    }
+    let response = client.get(app.url("/api/ping")).bearer_auth(&token).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS, "the user's own budget is the full RATE_LIMITER_MAX");
+}
+
+#[tokio::test]
+async fn rate_limits_are_counted_per_route_not_per_path() {
+    let app = spawn_app(&[("RATE_LIMITER_MAX", "2"), ("RATE_LIMITER_DURATION", "60")]).await;
+    let client = reqwest::Client::new();
+    let statuses = [
+        client.get(app.url("/api/tag/rust/posts.rss")).send().await.unwrap().status(),
+        client.get(app.url("/api/tag/axum/posts.rss")).send().await.unwrap().status(),
+        client.get(app.url("/api/tag/tokio/posts.rss")).send().await.unwrap().status(),
+    ];
+    assert_eq!(statuses[2], StatusCode::TOO_MANY_REQUESTS, "another tag is the same route");
+    assert!(app.redis.get("rate_limit:/api/tag/{name}/posts.rss:ip-127.0.0.1").is_some());
+
+    let unmatched = [
+        client.get(app.url(&format!("/nope/{}", Uuid::new_v4()))).send().await.unwrap().status(),
+        client.get(app.url(&format!("/nope/{}", Uuid::new_v4()))).send().await.unwrap().status(),
+        client.get(app.url(&format!("/nope/{}", Uuid::new_v4()))).send().await.unwrap().status(),
+    ];
+    assert_eq!(unmatched, [StatusCode::NOT_FOUND, StatusCode::NOT_FOUND, StatusCode::TOO_MANY_REQUESTS]);
+}
+
+#[tokio::test]
+async fn secret_signed_tokens_are_refused_once_a_key_pair_is_in_use() {
+    let (private, _) = ed25519_key_files();
+    let hs256_token = jwt::create_token(&Uuid::new_v4().to_string(), &test_config(&[]).jwt_keys, 600).unwrap();
+    assert!(decode_header(&hs256_token).unwrap().kid.is_none());
+    let app = spawn_app(&[("JWT_ALGORITHM", "EdDSA"), ("JWT_PRIVATE_KEY_PATH", &private)]).await;
+    assert!(jwt::parse_token(&hs256_token, &app.app_state.env.jwt_keys, 0).is_err());
+    let diana = app.db.add_user("Princess Diana", "diana@example.com", "diana123", RoleType::User);
+    let forged = jwt::create_token(&diana.id.to_string(), &test_config(&[]).jwt_keys, 600).unwrap();
+    let response = reqwest::Client::new().get(app.url("/api/user/self")).bearer_auth(&forged).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let switching = test_config(&[("JWT_ALGORITHM", "EdDSA"), ("JWT_PRIVATE_KEY_PATH", &private), ("JWT_ACCEPT_HS256", "allow")]);
+    assert!(jwt::parse_token(&hs256_token, &switching.jwt_keys, 0).is_ok());
+    assert!(decode_header(&jwt::create_token("someone", &switching.jwt_keys, 600).unwrap()).unwrap().kid.is_some());
+}
+
+#[tokio::test]
+async fn rate_limits_are_counted_per_client_address() {
+    let app = spawn_app(&[("AVAILABILITY_RATE_LIMIT_MAX", "2"), ("AVAILABILITY_RATE_LIMIT_WINDOW", "60")]).await;
+    let client = |ip: [u8; 4]| reqwest::Client::builder().local_address(std::net::IpAddr::from(ip)).build().unwrap();
+    let (first, second) = (client([127, 0, 0, 1]), client([127, 0, 0, 2]));
+    let check = |client: &reqwest::Client| client.get(app.url("/api/auth/availability?email=diana@example.com")).send();
+
+    for _ in 0..2 {
+        assert_eq!(check(&first).await.unwrap().status(), StatusCode::OK);
+    }
+    assert_eq!(check(&first).await.unwrap().status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(check(&second).await.unwrap().status(), StatusCode::OK, "another address has a budget of its own");
+    assert!(app.redis.get("rate_limit:availability:ip-127.0.0.2").is_some());
+}
+
+#[tokio::test]
+async fn rotated_out_signing_keys_verify_while_listed_in_the_jwks() {
+    let (old_private, old_public) = ed25519_key_files();
+    let (new_private, _) = ed25519_key_files();
+    let old_config = test_config(&[("JWT_ALGORITHM", "EdDSA"), ("JWT_PRIVATE_KEY_PATH", &old_private)]);
+    let old_token = jwt::create_token(&Uuid::new_v4().to_string(), &old_config.jwt_keys, 600).unwrap();
+    let old_kid = decode_header(&old_token).unwrap().kid.expect("key pair tokens carry a kid");
+    let app = spawn_app(&[
+        ("JWT_ALGORITHM", "EdDSA"),
+        ("JWT_PRIVATE_KEY_PATH", &new_private),
+        ("JWT_VERIFY_KEY_PATHS", &old_public),
+    ]).await;
+    let new_token = jwt::create_token(&Uuid::new_v4().to_string(), &app.app_state.env.jwt_keys, 600).unwrap();
+    let new_kid = decode_header(&new_token).unwrap().kid.unwrap();
+    let jwks: Value = reqwest::get(app.url("/.well-known/jwks.json")).await.unwrap().json().await.unwrap();
+    let kids: Vec<&str> = jwks["keys"].as_array().unwrap().iter().map(|key| key["kid"].as_str().unwrap()).collect();
+    assert_eq!(kids, vec![new_kid.as_str(), old_kid.as_str()]);
+    assert!(jwks["keys"].as_array().unwrap().iter().all(|key| key["kty"] == "OKP" && key.get("d").is_none()));
+    assert!(jwt::parse_token(&old_token, &app.app_state.env.jwt_keys, 0).is_ok());
+    let retired = test_config(&[("JWT_ALGORITHM", "EdDSA"), ("JWT_PRIVATE_KEY_PATH", &new_private)]);
+    assert!(jwt::parse_token(&new_token, &retired.jwt_keys, 0).is_ok());
+    assert!(jwt::parse_token(&old_token, &retired.jwt_keys, 0).is_err());
+}
+
+#[tokio::test]
+async fn an_unreachable_redis_trips_the_circuit_breaker_instead_of_failing_requests() {
+    let app = spawn_app(&[("REDIS_URL", "redis://127.0.0.1:1/"), ("REDIS_BREAKER_THRESHOLD", "2")]).await;
+    for _ in 0..4 {
+        let response = reqwest::get(app.url("/api/ping")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    assert!(app.app_state.redis_client.circuit_breaker().unwrap().is_open());
+
+    let app = spawn_app(&[("REDIS_URL", "redis://127.0.0.1:1/"), ("RATE_LIMITER_ON_REDIS_ERROR", "reject")]).await;
+    let response = reqwest::get(app.url("/api/ping")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "SERVICE_UNAVAILABLE");
+}
+
+#[tokio::test]
+async fn emails_the_smtp_server_refuses_are_queued_instead_of_failing() {
+    let app = spawn_app(&[("MAIL_MAX_ATTEMPTS", "2")]).await;
+    app.mailer.set_failing(true);
+    app.app_state.mailer.send("bruce@example.com", "Welcome", "<p>Hi</p>".to_string()).await.unwrap();
+    assert!(app.mailer.sent().is_empty());
+    assert_eq!(app.db.queued_emails(), vec![("bruce@example.com".to_string(), "Welcome".to_string())]);
+    app.mailer.set_failing(false);
+    app.send_queued_emails().await;
+    assert_eq!(app.mailer.sent_to("bruce@example.com").len(), 1);
+    assert!(app.db.queued_emails().is_empty());
+}
+
+#[tokio::test]
+async fn impersonation_acts_as_the_user_and_audits_every_change() {
+    let app = spawn_app(&[]).await;
+    let (diana, admin_token) = app.user_with_token("Princess Diana", "diana@example.com", RoleType::Admin);
+    let bruce = app.db.add_user("Bruce Wayne", "bruce@example.com", "bruce123", RoleType::Admin);
+    let clark = app.db.add_user("Clark Kent", "clark@example.com", "clark123", RoleType::User);
+    let client = reqwest::Client::new();
+    let response = client.post(app.url(&format!("/api/admin/impersonate/{}", bruce.id)))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN, "admins can't be impersonated");
+    let response = client.post(app.url(&format!("/api/admin/impersonate/{}", clark.id)))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await.unwrap();
+    let token = body["data"]["access_token"].as_str().unwrap().to_string();
+    assert_eq!(body["data"]["user"]["id"], clark.id.to_string());
+
+    let response = client.get(app.url("/api/user/self")).bearer_auth(&token).send().await.unwrap();
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["data"]["email"], "clark@example.com");
+    let response = client.put(app.url("/api/user/settings"))
+        .bearer_auth(&token)
+        .json(&json!({ "theme": "dark" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(app.db.audit_actions(clark.id), vec!["admin.impersonation-started", "admin.impersonated-request"]);
+    assert!(app.db.audit_actions(diana.id).is_empty());
+
+    let response = client.post(app.url("/api/auth/refresh")).bearer_auth(&token).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "impersonation tokens can't be refreshed");
+
+    app.db.set_role(diana.id, RoleType::User);
+    app.app_state.redis_client.delete_user(&diana.id).await.unwrap();
+    let response = client.get(app.url("/api/user/self")).bearer_auth(&token).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN, "a demoted admin stops acting as the user");
+}
+
+#[tokio::test]
+async fn feature_flag_changes_apply_on_the_next_check() {
+    let app = spawn_app(&[]).await;
+    let (diana, token) = app.user_with_token("Princess Diana", "diana@example.com", RoleType::Admin);
+    let clark = app.db.add_user("Clark Kent", "clark@example.com", "clark123", RoleType::User);
+    let client = reqwest::Client::new();
+    let flags = app.app_state.flags();
+    let response = client.post(app.url("/api/admin/feature-flags"))
+        .bearer_auth(&token)
+        .json(&json!({ "key": "post.reactions", "enabled": true, "roles": ["Admin"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert!(flags.is_enabled("post.reactions", &diana).await);
+    assert!(!flags.is_enabled("post.reactions", &clark).await, "clark's role isn't in the rollout");
+    assert!(!flags.is_enabled("user.messages", &diana).await, "unknown flags are off");
+
+    let response = client.put(app.url("/api/admin/feature-flags/post.reactions"))
+        .bearer_auth(&token)
+        .json(&json!({ "roles": [], "percentage": 0 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!flags.is_enabled("post.reactions", &diana).await, "0% rolls out to nobody");
+    client.put(app.url("/api/admin/feature-flags/post.reactions"))
+        .bearer_auth(&token)
+        .json(&json!({ "percentage": 100 }))
+        .send()
+        .await
+        .unwrap();
+    assert!(flags.is_enabled("post.reactions", &clark).await);
+
+    let response = client.delete(app.url("/api/admin/feature-flags/post.reactions"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!flags.is_enabled("post.reactions", &clark).await);
+}
+
+#[tokio::test]
+async fn responses_follow_the_accept_header() {
+    let app = spawn_app(&[]).await;
+    let (_, token) = app.user_with_token("Clark Kent", "clark@example.com", RoleType::User);
+    let client = reqwest::Client::new();
+    let response = client.get(app.url("/api/user/self"))
+        .bearer_auth(&token)
+        .header("accept", "application/json;q=0.5, application/msgpack")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["content-type"], "application/msgpack");
+    let body: Value = rmp_serde::from_slice(&response.bytes().await.unwrap()).unwrap();
+    assert_eq!(body["data"]["email"], "clark@example.com");
+
+    let response = client.get(app.url("/api/user/self"))
+        .header("accept", "application/cbor")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(response.headers()["content-type"], "application/cbor");
+    let body: Value = ciborium::from_reader(&response.bytes().await.unwrap()[..]).unwrap();
+    assert_eq!(body["code"], "TOKEN_NOT_PROVIDED");
+
+    let response = client.get(app.url("/api/user/self"))
+        .bearer_auth(&token)
+        .header("accept", "text/html")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["content-type"], "application/json");
+
+    let response = client.get(app.url("/api/nowhere")).header("accept", "application/msgpack").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.headers()["content-type"], "application/msgpack");
+    let body: Value = rmp_serde::from_slice(&response.bytes().await.unwrap()).unwrap();
+    assert_eq!(body["message"], "Route GET /api/nowhere is not exists");
+    let response = client.delete(app.url("/api/ping")).header("accept", "application/cbor").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.headers()["content-type"], "application/cbor");
+    let body: Value = ciborium::from_reader(&response.bytes().await.unwrap()[..]).unwrap();
+    assert_eq!(body["message"], "DELETE /api/ping is not valid");
+}
+
+#[tokio::test]
+async fn routing_and_extractor_errors_carry_a_code() {
+    let app = spawn_app(&[]).await;
+    let (diana, token) = app.user_with_token("Princess Diana", "diana@example.com", RoleType::Admin);
+    let client = reqwest::Client::new();
+    let requests = [
+        (client.get(app.url("/api/nowhere")), StatusCode::NOT_FOUND, "ROUTE_NOT_FOUND"),
+        (client.delete(app.url("/api/ping")), StatusCode::METHOD_NOT_ALLOWED, "METHOD_NOT_ALLOWED"),
+        (client.post(app.url("/api/auth/sign-in")).header("content-type", "application/json").body("{"), StatusCode::BAD_REQUEST, "INVALID_REQUEST"),
+        (client.post(app.url("/api/auth/sign-in")).json(&json!({ "email": "diana@example.com" })), StatusCode::UNPROCESSABLE_ENTITY, "INVALID_REQUEST"),
+        (client.post(app.url("/api/auth/sign-in")).body("email=diana"), StatusCode::UNSUPPORTED_MEDIA_TYPE, "UNSUPPORTED_MEDIA_TYPE"),
+        (client.get(app.url(&format!("/api/admin/users/{}/history?page=first", diana.id))).bearer_auth(&token), StatusCode::BAD_REQUEST, "INVALID_REQUEST"),
+        (client.get(app.url("/api/admin/announcements/not-a-uuid")).bearer_auth(&token), StatusCode::BAD_REQUEST, "INVALID_REQUEST"),
+    ];
+    for (request, status, code) in requests {
+        let response = request.send().await.unwrap();
+        assert_eq!(response.status(), status, "{}", response.url());
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["status"], "error");
+        assert_eq!(body["code"], code, "{}", body);
+        assert!(!body["message"].as_str().unwrap().is_empty());
+    }
+}
+
+#[tokio::test]
+async fn users_are_imported_line_by_line_and_exported_as_csv_and_ndjson() {
+    let app = spawn_app(&[]).await;
+    let (_, token) = app.user_with_token("Princess Diana", "diana@example.com", RoleType::Admin);
+    let client = reqwest::Client::new();
+    let csv = "email,name\r\nclark@example.com,Clark Kent\r\n\"bruce@example.com\",\"Wayne, Bruce\"\r\ndiana@example.com,Princess Diana\r\nnot-an-email,Nobody Here\r\n";
+    let response = client.post(app.url("/api/admin/users/import"))
+        .bearer_auth(&token)
+        .header("content-type", "text/csv")
+        .body(csv)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["data"]["created"], 2);
+    assert_eq!(body["data"]["skipped"], 1);
+    assert_eq!(body["data"]["failed"], 1);
+    assert_eq!(body["data"]["errors"][0]["line"], 4);
+    assert_eq!(body["data"]["errors"][1]["line"], 5);
+    assert_eq!(app.db.queued_emails(), vec![
+        ("clark@example.com".to_string(), "You're Invited".to_string()),
+        ("bruce@example.com".to_string(), "You're Invited".to_string()),
+    ]);
+
+    let response = client.post(app.url("/api/admin/users/import"))
+        .bearer_auth(&token)
+        .header("content-type", "application/x-ndjson")
+        .body("{\"name\": \"Lois Lane\", \"email\": \"lois@example.com\"}\n{\"name\": \"Lex\"}")
+        .send()
+        .await
+        .unwrap();
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["data"]["created"], 1);
+    assert_eq!(body["data"]["failed"], 1);
+
+    let response = client.post(app.url("/api/admin/users/import"))
+        .bearer_auth(&token)
+        .header("content-type", "application/json")
+        .body("[]")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    let response = client.get(app.url("/api/admin/users/export")).bearer_auth(&token).send().await.unwrap();
+    assert_eq!(response.headers()["content-type"], "text/csv; charset=utf-8");
+    let export = response.text().await.unwrap();
+    let lines: Vec<&str> = export.lines().collect();
+    assert_eq!(lines[0], "id,name,email,username,role,is_verified,is_banned,is_private,created_at");
+    assert_eq!(lines.len(), 5);
+    assert!(lines.iter().any(|line| line.contains(",\"Wayne, Bruce\",bruce@example.com,,user,true,")));
+
+    let response = client.get(app.url("/api/admin/users/stream")).bearer_auth(&token).send().await.unwrap();
+    assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+    let stream = response.text().await.unwrap();
+    let users: Vec<Value> = stream.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(users.len(), 4);
+    assert_eq!(users[0]["email"], "diana@example.com");
+    assert!(users.iter().any(|user| user["name"] == "Wayne, Bruce" && user["role"] == "User"));
+}
+
+#[tokio::test]
+async fn invite_only_sign_up_consumes_the_invitation() {
+    let app = spawn_app(&[("REGISTRATION_MODE", "invite_only")]).await;
+    let (_, token) = app.user_with_token("Princess Diana", "diana@example.com", RoleType::Admin);
+    let client = reqwest::Client::new();
+    let sign_up = |invite_token: Option<&str>| {
+        let mut body = json!({
+            "name": "Clark Kent",
+            "email": "clark@example.com",
+            "password": "clark123",
+            "password_confirm": "clark123",
+        });
+        if let Some(invite_token) = invite_token {
+            body["invite_token"] = json!(invite_token);
+        }
+        client.post(app.url("/api/auth/sign-up")).json(&body).send()
+    };
+    let response = sign_up(None).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "INVITATION_REQUIRED");
+
+    let response = client.post(app.url("/api/admin/invitations"))
+        .bearer_auth(&token)
+        .json(&json!({ "email": "clark@example.com" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let body: Value = response.json().await.unwrap();
+    assert!(body["data"].get("token").is_none());
+    app.send_queued_emails().await;
+    let sent = app.mailer.sent_to("clark@example.com");
+    assert_eq!(sent[0].subject, "Your Invitation to Sign Up");
+    let invite_token = sent[0].html.split("/sign-up?token=").nth(1).unwrap().split('"').next().unwrap().to_string();
+
+    let response = sign_up(Some("not-the-token")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = sign_up(Some(&invite_token)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let body: Value = response.json().await.unwrap();
+    let user_id = body["data"]["id"].as_str().unwrap().parse().unwrap();
+    assert_eq!(app.db.audit_actions(user_id), vec!["user.registered", "user.invitation-accepted", "email.verification"]);
+
+    let response = client.post(app.url("/api/auth/sign-up"))
+        .json(&json!({
+            "name": "Lois Lane",
+            "email": "lois@example.com",
+            "password": "lois1234",
+            "password_confirm": "lois1234",
+            "invite_token": invite_token,
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "INVITATION_INVALID");
+}
+
+#[tokio::test]
+async fn the_emailed_verification_link_answers_browsers() {
+    let app = spawn_app(&[]).await;
+    let response = reqwest::get(app.url("/api/auth/verify")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(response.headers()["content-type"], "text/html; charset=utf-8");
+    let page = response.text().await.unwrap();
+    assert!(page.contains("<title>Verification failed</title>"));
+    assert!(page.contains("Token key is invalid."));
+
+    let app = spawn_app(&[("VERIFY_LINK_MODE", "redirect")]).await;
+    let client = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none()).build().unwrap();
+    let response = client.get(app.url("/api/auth/verify?token=")).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(response.headers()["location"], "http://localhost:3000/verify?status=error&code=TOKEN_KEY_INVALID");
+}
+
+/// The token of the `?token=` link in the last email sent to `to`.
+fn emailed_token(app: &axum_restful_api::test_utils::TestApp, to: &str) -> String {
+    let html = app.mailer.sent_to(to).last().expect("an email was sent").html.clone();
+    let start = html.find("?token=").expect("a token link") + "?token=".len();
+    html[start..].chars().take_while(|c| c.is_ascii_alphanumeric()).collect()
+}
+
+#[tokio::test]
+async fn action_tokens_are_stored_hashed_and_used_once_even_concurrently() {
+    let app = spawn_app(&[]).await;
+    let client = reqwest::Client::new();
+    let response = client.post(app.url("/api/auth/sign-up"))
+        .json(&json!({ "name": "Clark Kent", "email": "clark@example.com", "password": "clark123", "password_confirm": "clark123" }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = response.json().await.unwrap();
+    let user_id: Uuid = body["data"]["id"].as_str().unwrap().parse().unwrap();
+    app.send_queued_emails().await;
+    let token = emailed_token(&app, "clark@example.com");
+    assert_eq!(token.len(), 32);
+    let pepper = &app.app_state.env.action_token_pepper;
+    assert_eq!(app.db.action_token_hashes(user_id), vec![Some(action_token::hash(pepper, &token))]);
+    assert_ne!(action_token::hash(pepper, &token), token);
+
+    let verify = || client.post(app.url(&format!("/api/auth/verify?token={}", token))).send();
+    let (first, second) = tokio::join!(verify(), verify());
+    let mut statuses = [first.unwrap().status(), second.unwrap().status()];
+    statuses.sort();
+    assert_eq!(statuses, [StatusCode::OK, StatusCode::BAD_REQUEST]);
+    assert!(app.db.user(user_id).unwrap().is_verified);
+    assert_eq!(app.db.action_token_hashes(user_id), vec![None], "a used token keeps no hash");
+    assert_eq!(verify().await.unwrap().status(), StatusCode::BAD_REQUEST);
+
+    let response = client.post(app.url("/api/auth/forgot-password")).json(&json!({ "email": "clark@example.com" })).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    app.send_queued_emails().await;
+    let token = emailed_token(&app, "clark@example.com");
+    let reset = |password: &'static str| client.post(app.url(&format!("/api/auth/reset-password?token={}", token)))
+        .json(&json!({ "new_password": password, "new_password_confirm": password }))
+        .send();
+    let (first, second) = tokio::join!(reset("superman1"), reset("superman2"));
+    let mut statuses = [first.unwrap().status(), second.unwrap().status()];
+    statuses.sort();
+    assert_eq!(statuses, [StatusCode::OK, StatusCode::BAD_REQUEST]);
+    assert_eq!(reset("superman3").await.unwrap().status(), StatusCode::BAD_REQUEST);
+    let sign_in = |password: &'static str| client.post(app.url("/api/auth/sign-in"))
+        .json(&json!({ "email": "clark@example.com", "password": password }))
+        .send();
+    let signed_in = [sign_in("superman1").await.unwrap().status(), sign_in("superman2").await.unwrap().status()];
+    assert_eq!(signed_in.iter().filter(|status| **status == StatusCode::OK).count(), 1, "{:?}", signed_in);
+}
+
+#[tokio::test]
+async fn account_emails_are_rate_limited_per_address() {
+    let app = spawn_app(&[("EMAIL_RATE_LIMIT_MAX", "2"), ("EMAIL_RATE_LIMIT_WINDOW", "600")]).await;
+    let client = reqwest::Client::new();
+    for path in ["/api/auth/forgot-password", "/api/auth/resend-activation"] {
+        let response = client.post(app.url(path)).json(&json!({ "email": "Nobody@Example.com" })).send().await.unwrap();
+        assert_ne!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+    let response = client.post(app.url("/api/auth/forgot-password"))
+        .json(&json!({ "email": "nobody@example.com" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = response.headers()["retry-after"].to_str().unwrap().parse().unwrap();
+    assert!((1..=600).contains(&retry_after));
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "EMAIL_RATE_LIMITED");
+
+    let response = client.post(app.url("/api/auth/forgot-password"))
+        .json(&json!({ "email": "someone.else@example.com" }))
+        .send()
+        .await
+        .unwrap();
+    assert_ne!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn concealed_accounts_get_the_same_answer_whether_or_not_they_exist() {
+    let app = spawn_app(&[("ACCOUNT_DISCLOSURE", "conceal"), ("ACCOUNT_DISCLOSURE_MIN_RESPONSE_MS", "200")]).await;
+    app.db.add_user("Princess Diana", "diana@example.com", "diana123", RoleType::User);
+    let client = reqwest::Client::new();
+    let mut answers = Vec::new();
+    for email in ["diana@example.com", "clark@example.com"] {
+        let started = std::time::Instant::now();
+        let response = client.post(app.url("/api/auth/sign-up"))
+            .json(&json!({ "name": "Someone", "email": email, "password": "secret123", "password_confirm": "secret123" }))
+            .send()
+            .await
+            .unwrap();
+        assert!(started.elapsed().as_millis() >= 200);
+        answers.push((response.status(), response.json::<Value>().await.unwrap()));
+    }
+    assert_eq!(answers[0].0, StatusCode::ACCEPTED);
+    assert_eq!(answers[0], answers[1]);
+    assert!(answers[0].1["data"].is_null(), "{}", answers[0].1);
+    app.send_queued_emails().await;
+    assert!(app.mailer.sent_to("diana@example.com").is_empty());
+    assert_eq!(app.mailer.sent_to("clark@example.com").len(), 1);
+
+    // Nobody to send to: no account, or one that is already verified.
+    for (path, email) in [("/api/auth/forgot-password", "nobody@example.com"), ("/api/auth/resend-activation", "diana@example.com")] {
+        let response = client.post(app.url(path)).json(&json!({ "email": email })).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["status"], "success", "{}", body);
+        assert!(body["data"].is_null(), "{}", body);
+    }
+}
+
+/// A siteverify endpoint that accepts the token `human` for the secret `captcha-secret`.
+async fn fake_captcha_provider() -> String {
+    async fn siteverify(Form(form): Form<HashMap<String, String>>) -> Json<Value> {
+        let success = form.get("secret").map(String::as_str) == Some("captcha-secret")
+            && form.get("response").map(String::as_str) == Some("human");
+        Json(json!({ "success": success, "error-codes": if success { vec![] } else { vec!["invalid-input-response"] } }))
+    }
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/siteverify", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        axum::serve(listener, Router::new().route("/siteverify", post(siteverify))).await.unwrap();
+    });
+    url
+}
+
+#[tokio::test]
+async fn captcha_is_checked_on_the_endpoints_it_is_enabled_for() {
+    let verify_url = fake_captcha_provider().await;
+    let app = spawn_app(&[
+        ("CAPTCHA_PROVIDER", "hcaptcha"),
+        ("CAPTCHA_SECRET", "captcha-secret"),
+        ("CAPTCHA_VERIFY_URL", &verify_url),
+        ("CAPTCHA_ENDPOINTS", "forgot-password"),
+    ]).await;
+    let client = reqwest::Client::new();
+    let forgot_password = |captcha_token: Option<&str>| {
+        client.post(app.url("/api/auth/forgot-password"))
+            .json(&json!({ "email": "nobody@example.com", "captcha_token": captcha_token }))
+            .send()
+    };
+    for (captcha_token, code) in [(None, "CAPTCHA_REQUIRED"), (Some("robot"), "CAPTCHA_INVALID"), (Some("human"), "NOT_FOUND")] {
+        let response = forgot_password(captcha_token).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["code"], code, "{:?}: {}", captcha_token, body);
+    }
+    let response = client.post(app.url("/api/auth/resend-activation"))
+        .json(&json!({ "email": "nobody@example.com" }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "NOT_FOUND", "{}", body);
+}
+
+#[tokio::test]
+async fn failed_sign_ins_are_slowed_down_per_email_and_address() {
+    let app = spawn_app(&[("SIGN_IN_FREE_ATTEMPTS", "2"), ("SIGN_IN_DELAY_BASE", "60")]).await;
+    app.db.add_user("Princess Diana", "diana@example.com", "diana123", RoleType::User);
+    let client = reqwest::Client::new();
+    let sign_in = |email: &str, password: &str| {
+        client.post(app.url("/api/auth/sign-in")).json(&json!({ "email": email, "password": password })).send()
+    };
+    // Two free failures, then one attempt per delay.
+    for _ in 0..3 {
+        let response = sign_in("diana@example.com", "wrong-password").await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+    let response = sign_in("diana@example.com", "diana123").await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = response.headers()["retry-after"].to_str().unwrap().parse().unwrap();
+    assert!((1..=60).contains(&retry_after), "{}", retry_after);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "SIGN_IN_THROTTLED");
+
+    let response = sign_in("someone.else@example.com", "wrong-password").await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = client.get(app.url("/api/auth/availability?email=diana@example.com")).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn signed_export_links_download_without_a_token_until_they_expire() {
+    let app = spawn_app(&[]).await;
+    let (_, token) = app.user_with_token("Princess Diana", "diana@example.com", RoleType::Admin);
+    let client = reqwest::Client::new();
+    let response = client.post(app.url("/api/admin/users/export/link")).bearer_auth(&token).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await.unwrap();
+    let url = body["data"]["url"].as_str().unwrap().to_string();
+    assert!(url.starts_with("/api/admin/users/export/download?"), "{}", url);
+
+    let response = client.get(app.url(&url)).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/csv; charset=utf-8");
+    assert!(response.text().await.unwrap().contains("diana@example.com"));
+
+    let tampered = url.replace("scope=users:export", "scope=users:delete");
+    let response = client.get(app.url(&tampered)).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "URL_SIGNATURE_INVALID");
+    let response = client.get(app.url("/api/admin/users/export/download")).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let app = spawn_app(&[("SIGNED_URL_AGE", "-1")]).await;
+    let (_, token) = app.user_with_token("Princess Diana", "diana@example.com", RoleType::Admin);
+    let body: Value = client.post(app.url("/api/admin/users/export/link")).bearer_auth(&token).send().await.unwrap().json().await.unwrap();
+    let response = client.get(app.url(body["data"]["url"].as_str().unwrap())).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "URL_EXPIRED");
+}
+
+/// A moderation service that flags any text mentioning "casino".
+async fn fake_moderation_api() -> String {
+    async fn moderate(Json(body): Json<Value>) -> Json<Value> {
+        let flagged = body["text"].as_str().unwrap_or_default().contains("casino");
+        Json(json!({ "flagged": flagged, "reasons": if flagged { vec!["Looks like spam."] } else { vec![] } }))
+    }
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/moderate", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        axum::serve(listener, Router::new().route("/moderate", post(moderate))).await.unwrap();
+    });
+    url
+}
+
+#[tokio::test]
+async fn flagged_posts_and_comments_are_rejected_with_reasons() {
+    let app = spawn_app(&[("CONTENT_MODERATION", "wordlist"), ("MODERATION_WORDLIST", "darn, heck")]).await;
+    let (_, token) = app.user_with_token("Princess Diana", "diana@example.com", RoleType::User);
+    let client = reqwest::Client::new();
+    let post = json!({ "title": "Heck yes", "content": "What a DARN good day, darn it", "tags": ["life"] });
+    let response = client.post(app.url("/api/post")).bearer_auth(&token).json(&post).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "CONTENT_REJECTED");
+    assert_eq!(body["error"], json!([
+        { "field": "title", "messages": ["Contains the blocked word \"heck\"."] },
+        { "field": "content", "messages": ["Contains the blocked word \"darn\"."] },
+    ]));
+    let comment = json!({ "content": "Checking in on the whole darn thread" });
+    let response = client.post(app.url(&format!("/api/comment/{}", Uuid::new_v4()))).bearer_auth(&token).json(&comment).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let app = spawn_app(&[("CONTENT_MODERATION", "api"), ("MODERATION_API_URL", &fake_moderation_api().await)]).await;
+    let (_, token) = app.user_with_token("Princess Diana", "diana@example.com", RoleType::User);
+    let patch = json!({ "content": "Best casino bonuses in town" });
+    let response = client.patch(app.url(&format!("/api/post/{}", Uuid::new_v4()))).bearer_auth(&token).json(&patch).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"], json!([{ "field": "content", "messages": ["Looks like spam."] }]));
+}
+
+#[tokio::test]
+async fn commenting_faster_than_the_velocity_limit_is_throttled() {
+    let app = spawn_app(&[]).await;
+    let (_, token) = app.user_with_token("Princess Diana", "diana@example.com", RoleType::User);
+    let (admin, admin_token) = app.user_with_token("Administrator", "admin@example.com", RoleType::Admin);
+    let client = reqwest::Client::new();
+
+    let response = client.put(app.url("/api/admin/velocity-limits/comment")).bearer_auth(&token)
+        .json(&json!({ "max_count": 2 })).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = client.put(app.url("/api/admin/velocity-limits/comment")).bearer_auth(&admin_token)
+        .json(&json!({ "max_count": 0 })).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = client.put(app.url("/api/admin/velocity-limits/comment")).bearer_auth(&admin_token)
+        .json(&json!({ "max_count": 2, "window_secs": 60 })).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = client.get(app.url("/api/admin/velocity-limits")).bearer_auth(&admin_token).send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"][1]["kind"], "comment");
+    assert_eq!(body["data"][1]["max_count"], 2);
+    assert_eq!(body["data"][1]["updated_by"], admin.id.to_string());
+
+    let post = json!({ "title": "Velocity limits", "content": "Comment on this post as fast as you can.", "tags": ["limits"] });
+    let response = client.post(app.url("/api/post")).bearer_auth(&token).json(&post).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let post_id = response.json::<Value>().await.unwrap()["data"]["id"].as_str().unwrap().to_string();
+    let comment = |token: &str| client.post(app.url(&format!("/api/comment/{}", post_id)))
+        .bearer_auth(token)
+        .json(&json!({ "content": "Another comment on this post" }))
+        .send();
+    for _ in 0..2 {
+        assert_eq!(comment(&token).await.unwrap().status(), StatusCode::OK);
+    }
+    let response = comment(&token).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = response.headers()["retry-after"].to_str().unwrap().parse().unwrap();
+    assert!((1..=60).contains(&retry_after), "{}", retry_after);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "POSTING_TOO_FAST");
+
+    for _ in 0..3 {
+        assert_eq!(comment(&admin_token).await.unwrap().status(), StatusCode::OK);
+    }
+}
+
+/// Serves a 300x200 PNG at `/avatar.png` and garbage at `/broken.png`.
+async fn fake_avatar_host() -> String {
+    let mut png = Vec::new();
+    image::RgbImage::from_pixel(300, 200, image::Rgb([200, 40, 40]))
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let router = Router::new()
+        .route("/avatar.png", get(move || async move { png }))
+        .route("/broken.png", get(|| async { "not an image" }));
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+    url
+}
+
+#[tokio::test]
+async fn avatars_are_resized_cached_and_revalidated() {
+    let host = fake_avatar_host().await;
+    let app = spawn_app(&[("AVATAR_PRIVATE_HOSTS", "allow")]).await;
+    let (diana, token) = app.user_with_token("Princess Diana", "diana@example.com", RoleType::User);
+    let client = reqwest::Client::new();
+    let avatar = |query: &str| client.get(app.url(&format!("/api/user/{}/avatar{}", diana.id, query))).bearer_auth(&token);
+
+    let response = avatar("").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    client.patch(app.url(&format!("/api/user/{}", diana.id))).bearer_auth(&token)
+        .json(&json!({ "avatar_url": format!("{}/avatar.png", host) }))
+        .send().await.unwrap();
+    let response = avatar("?size=64").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "image/png");
+    assert_eq!(response.headers()["cache-control"], "private, max-age=86400");
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+    let resized = image::load_from_memory(&response.bytes().await.unwrap()).unwrap();
+    assert_eq!((resized.width(), resized.height()), (64, 64));
+
+    let response = avatar("?size=64").header("If-None-Match", &etag).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    let response = avatar("").send().await.unwrap();
+    assert_ne!(response.headers()["etag"], etag.as_str());
+    assert_eq!(image::load_from_memory(&response.bytes().await.unwrap()).unwrap().width(), 128);
+    let response = avatar("?size=65").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    client.patch(app.url(&format!("/api/user/{}", diana.id))).bearer_auth(&token)
+        .json(&json!({ "avatar_url": format!("{}/broken.png", host) }))
+        .send().await.unwrap();
+    let response = avatar("").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "AVATAR_UNAVAILABLE");
+
+    let app = spawn_app(&[]).await;
+    let (diana, token) = app.user_with_token("Princess Diana", "diana@example.com", RoleType::User);
+    client.patch(app.url(&format!("/api/user/{}", diana.id))).bearer_auth(&token)
+        .json(&json!({ "avatar_url": format!("{}/avatar.png", host) }))
+        .send().await.unwrap();
+    let response = client.get(app.url(&format!("/api/user/{}/avatar", diana.id))).bearer_auth(&token).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+}
+
+#[tokio::test]
+async fn signing_in_from_a_new_device_sends_an_alert() {
+    let app = spawn_app(&[]).await;
+    let diana = app.db.add_user("Princess Diana", "diana@example.com", "diana123", RoleType::User);
+    let client = reqwest::Client::new();
+    let sign_in = |user_agent: &'static str| {
+        client.post(app.url("/api/auth/sign-in"))
+            .header("User-Agent", user_agent)
+            .json(&json!({ "email": "diana@example.com", "password": "diana123" }))
+            .send()
+    };
+    for user_agent in ["Laptop", "Laptop", "Phone"] {
+        let response = sign_in(user_agent).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    app.send_queued_emails().await;
+    let sent = app.mailer.sent_to("diana@example.com");
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].subject, "New Sign-In to Your Account");
+    assert!(sent[0].html.contains("Phone") && sent[0].html.contains("127.0.0.1"));
+    let revoke_token = sent[0].html.split("/sessions/revoke?token=").nth(1).unwrap().split('"').next().unwrap().to_string();
+
+    let revoke = |token: String| client.get(app.url(&format!("/api/auth/sessions/revoke?token={}", token))).send();
+    let response = revoke(revoke_token.clone()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = revoke(revoke_token).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(app.db.audit_actions(diana.id).contains(&"auth.session-revoked".to_string()));
+
+    let token = app.token_for(&diana);
+    let response = client.put(app.url("/api/user/settings"))
+        .bearer_auth(&token)
+        .json(&json!({ "login_alerts": false }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = sign_in("Tablet").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    app.send_queued_emails().await;
+    assert_eq!(app.mailer.sent_to("diana@example.com").len(), 1);
+}
+
+#[tokio::test]
+async fn requests_the_database_pool_cannot_serve_get_503_with_retry_after() {
+    let app = spawn_app(&[]).await;
+    // The test app keeps its repositories in memory, so the Postgres ones are given a pool that
+    // never connects and the query gives up waiting for a connection.
+    let pool = PgPoolOptions::new()
+        .acquire_timeout(std::time::Duration::from_millis(100))
+        .connect_lazy("postgres://localhost:1/unused")
+        .unwrap();
+    let Err(err) = DBClient::new(pool).get_trending_posts(10, false).await else {
+        panic!("the pool has no database to connect to");
+    };
+    let response = AppError::from(err).into_response();
+    assert_eq!(response.status(), HttpStatus::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers()["retry-after"], "1");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(serde_json::from_slice::<Value>(&body).unwrap()["code"], "DATABASE_BUSY");
+
+    let metrics = reqwest::Client::new().get(app.url("/api/metrics"))
+        .basic_auth(&app.app_state.env.auth_basic_username, Some(&app.app_state.env.auth_basic_password))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(metrics.contains("db_pool_max_connections "), "{}", metrics);
+}
+
+#[tokio::test]
+async fn feed_digests_are_opt_in_and_list_the_news() {
+    let app = spawn_app(&[]).await;
+    let (_, token) = app.user_with_token("Clark Kent", "clark@example.com", RoleType::User);
+    let client = reqwest::Client::new();
+    let response = client.get(app.url("/api/user/settings")).bearer_auth(&token).send().await.unwrap();
+    assert_eq!(response.json::<Value>().await.unwrap()["data"]["email_digest"], "off");
+    let response = client.put(app.url("/api/user/settings")).bearer_auth(&token).json(&json!({ "email_digest": "weekly" })).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = client.put(app.url("/api/user/settings")).bearer_auth(&token).json(&json!({ "email_digest": "hourly" })).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let mailer = FakeMailer::default();
+    let post = |title: &str| DigestPost {
+        id: Uuid::new_v4(),
+        title: title.to_string(),
+        author_name: "Bruce Wayne".to_string(),
+        created_at: Utc.with_ymd_and_hms(2025, 9, 1, 20, 30, 0).unwrap(),
+    };
+    let content = DigestContent {
+        posts: vec![post("<b>Gotham</b>"), post("Arkham")],
+        posts_count: 5,
+        follow_requests: 1,
+        organization_invitations: 0,
+    };
+    let time_zone = "Asia/Jakarta".parse().unwrap();
+    send_digest_email(&mailer, "clark@example.com", "Clark", "http://app.test/", DigestFrequency::Weekly, &content, time_zone).await.unwrap();
+    let sent = mailer.sent_to("clark@example.com");
+    assert_eq!(sent[0].subject, "Your Weekly Digest");
+    let html = &sent[0].html;
+    assert!(html.contains(&format!("http://app.test/post/{}", content.posts[0].id)));
+    assert!(html.contains("&lt;b&gt;Gotham&lt;/b&gt;") && !html.contains("<b>Gotham"));
+    assert!(html.contains("September 2 at 03:30"), "times are in the user's time zone");
+    assert!(html.contains("And 3 more"));
+    assert!(html.contains("1 follow request<"));
+    assert!(!html.contains("organization invitation"));
+    assert!(!html.contains("{{"));
+}
+#[tokio::test]
+async fn post_views_are_counted_once_per_user_and_hour() {
+    let app = spawn_app(&[]).await;
+    let clark = app.db.add_user("Clark Kent", "clark@example.com", "clark123", RoleType::User);
+    let bruce = app.db.add_user("Bruce Wayne", "bruce@example.com", "bruce123", RoleType::User);
+    let post_id = Uuid::new_v4();
+    let client = reqwest::Client::new();
+    for (user, times) in [(&clark, 3), (&bruce, 1)] {
+        let token = app.token_for(user);
+        for _ in 0..times {
+            let response = client.post(app.url(&format!("/api/post/{}/view", post_id)))
+                .bearer_auth(&token)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+    }
+    assert_eq!(app.redis.hget("post_views:pending", &post_id.to_string()).as_deref(), Some(&b"2"[..]));
+
+    // The flush job takes the batch out of Redis and puts it back when the database refuses it.
+    let views = app.app_state.redis_client.take_post_views().await.unwrap();
+    assert_eq!(views, vec![(post_id, 2)]);
+    assert!(app.redis.hget("post_views:pending", &post_id.to_string()).is_none());
+    assert!(app.app_state.redis_client.take_post_views().await.unwrap().is_empty());
+    app.app_state.redis_client.restore_post_views(&views).await.unwrap();
+    assert_eq!(app.app_state.redis_client.take_post_views().await.unwrap(), vec![(post_id, 2)]);
+}
+#[tokio::test]
+async fn post_details_are_served_from_a_compressed_cache() {
+    let app = spawn_app(&[]).await;
+    let clark = app.db.add_user("Clark Kent", "clark@example.com", "clark123", RoleType::User);
+    let post_id = Uuid::new_v4();
+    let content = "It's a bird. It's a plane. ".repeat(100);
+    let post_detail: PostDetail = serde_json::from_value(json!({
+        "id": post_id,
+        "title": "Daily Planet",
+        "content": content,
+        "tags": ["metropolis"],
+        "license": "cc-by",
+        "views_count": 0,
+        "created_at": Utc::now(),
+        "updated_at": Utc::now(),
+        "user": {
+            "id": clark.id,
+            "name": "Clark Kent",
+            "email": "clark@example.com",
+            "role": "User",
+            "is_verified": true,
+            "is_banned": false,
+            "is_private": false,
+            "created_at": Utc::now(),
+            "updated_at": Utc::now(),
+        },
+        "comments": [],
+    })).unwrap();
+    app.app_state.redis_client.set_post_detail(&post_detail, 60).await.unwrap();
+    let cached = app.redis.get(&format!("post:{}", post_id)).expect("post cached");
+    assert!(cached.starts_with(b"\0zs"), "stored zstd-compressed");
+    assert!(cached.len() < content.len() / 4);
+
+    // The post was never saved, so a 200 can only come from the cache.
+    let response = reqwest::Client::new()
+        .get(app.url(&format!("/api/post/{}", post_id)))
+        .bearer_auth(app.token_for(&clark))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["data"]["content"], content.as_str());
+    assert_eq!(body["data"]["user"]["id"], clark.id.to_string());
+
+    app.app_state.redis_client.delete_post_detail(&post_id).await.unwrap();
+    assert!(app.redis.get(&format!("post:{}", post_id)).is_none());
+}
+
+#[tokio::test]
+async fn analytics_events_are_buffered_without_the_user_unless_opted_out() {
+    let app = spawn_app(&[]).await;
+    let (clark, token) = app.user_with_token("Clark Kent", "clark@example.com", RoleType::User);
+    let client = reqwest::Client::new();
+    let now = Utc::now();
+    let events = json!({ "events": [
+        { "kind": "screen_view", "name": "screen.open", "screen": "feed", "occurred_at": now },
+        { "kind": "interaction", "name": "post.like", "screen": "feed", "properties": { "position": 3, "source": "feed" }, "occurred_at": now },
+    ] });
+    let response = client.post(app.url("/api/analytics/events")).bearer_auth(&token).json(&events).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    assert_eq!(response.json::<Value>().await.unwrap()["data"]["accepted"], 2);
+
+    for (event, field) in [
+        (json!({ "kind": "screen_view", "name": "screen.open", "occurred_at": now }), "screen is required"),
+        (json!({ "kind": "interaction", "name": "Post Like", "occurred_at": now }), "name must"),
+        (json!({ "kind": "interaction", "name": "post.like", "properties": { "user": { "id": 1 } }, "occurred_at": now }), "properties must"),
+        (json!({ "kind": "interaction", "name": "post.like", "occurred_at": now - Duration::days(8) }), "occurred_at must"),
+    ] {
+        let response = client.post(app.url("/api/analytics/events")).bearer_auth(&token).json(&json!({ "events": [event] })).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(response.text().await.unwrap().contains(field), "{}", field);
+    }
+
+    let buffered = app.app_state.redis_client.take_analytics_events().await.unwrap();
+    assert_eq!(buffered.len(), 2);
+    assert_eq!(buffered[0].subject, buffered[1].subject);
+    assert!(!buffered[0].subject.contains(&clark.id.to_string()));
+    assert!(!serde_json::to_string(&buffered).unwrap().contains(&clark.id.to_string()));
+
+    // A batch the sink already had, put back after a failed run, isn't saved twice.
+    app.app_state.redis_client.buffer_analytics_events(&buffered).await.unwrap();
+    flush_analytics::flush(&app.app_state).await;
+    assert!(app.app_state.redis_client.take_analytics_events().await.unwrap().is_empty());
+    app.app_state.redis_client.buffer_analytics_events(&buffered).await.unwrap();
+    flush_analytics::flush(&app.app_state).await;
+    assert_eq!(app.db.analytics_events().len(), 2);
+
+    let response = client.put(app.url("/api/user/settings")).bearer_auth(&token).json(&json!({ "analytics": false })).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = client.post(app.url("/api/analytics/events")).bearer_auth(&token).json(&events).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    assert_eq!(response.json::<Value>().await.unwrap()["data"]["accepted"], 0);
+    assert!(app.app_state.redis_client.take_analytics_events().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn active_announcements_are_targeted_by_role_and_cached() {
+    let app = spawn_app(&[]).await;
+    let (_, admin_token) = app.user_with_token("Diana Prince", "diana@example.com", RoleType::Admin);
+    let (_, clark_token) = app.user_with_token("Clark Kent", "clark@example.com", RoleType::User);
+    let client = reqwest::Client::new();
+    let now = Utc::now();
+    let response = client.post(app.url("/api/admin/announcements")).bearer_auth(&clark_token)
+        .json(&json!({ "title": "Hello", "message": "Hi" })).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = client.post(app.url("/api/admin/announcements")).bearer_auth(&admin_token)
+        .json(&json!({ "title": "Backwards", "message": "Ends first", "starts_at": now, "ends_at": now - Duration::hours(1) })).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(response.json::<Value>().await.unwrap()["code"], "ANNOUNCEMENT_SCHEDULE_INVALID");
+    let mut ids = Vec::new();
+    for body in [
+        json!({ "title": "Maintenance", "message": "Tonight at 22:00 UTC" }),
+        json!({ "title": "Moderation queue", "message": "Reports are piling up", "level": "warning", "roles": ["Admin"] }),
+        json!({ "title": "Later", "message": "Not yet", "starts_at": now + Duration::hours(1) }),
+    ] {
+        let response = client.post(app.url("/api/admin/announcements")).bearer_auth(&admin_token).json(&body).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        ids.push(response.json::<Value>().await.unwrap()["data"]["id"].as_str().unwrap().to_string());
+    }
+
+    let titles = |body: Value| body["data"].as_array().unwrap().iter().map(|announcement| announcement["title"].as_str().unwrap().to_string()).collect::<Vec<_>>();
+    let response = client.get(app.url("/api/announcements/active")).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(titles(response.json().await.unwrap()), ["Maintenance"]);
+    assert!(app.redis.get("announcements:current").is_some());
+    let response = client.get(app.url("/api/announcements/active")).bearer_auth(&clark_token).send().await.unwrap();
+    assert_eq!(titles(response.json().await.unwrap()), ["Maintenance"]);
+    let response = client.get(app.url("/api/announcements/active")).bearer_auth(&admin_token).send().await.unwrap();
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(titles(body.clone()), ["Moderation queue", "Maintenance"]);
+    assert!(body["data"][0].get("roles").is_none());
+    let response = client.get(app.url("/api/announcements/active")).bearer_auth("not-a-token").send().await.unwrap();
+    assert_eq!(titles(response.json().await.unwrap()), ["Maintenance"]);
+
+    // Admin changes drop the cached copy, so they show on the next load.
+    let response = client.put(app.url(&format!("/api/admin/announcements/{}", ids[0]))).bearer_auth(&admin_token)
+        .json(&json!({ "ends_at": Utc::now() })).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(app.redis.get("announcements:current").is_none());
+    let response = client.get(app.url("/api/announcements/active")).send().await.unwrap();
+    assert!(titles(response.json().await.unwrap()).is_empty());
+    let response = client.delete(app.url(&format!("/api/admin/announcements/{}", ids[2]))).bearer_auth(&admin_token).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = client.get(app.url("/api/admin/announcements")).bearer_auth(&admin_token).send().await.unwrap();
+    assert_eq!(response.json::<Value>().await.unwrap()["data"].as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn users_accept_a_new_terms_of_service_version_before_writing() {
+    let app = spawn_app(&[]).await;
+    let (_, admin_token) = app.user_with_token("Diana Prince", "diana@example.com", RoleType::Admin);
+    let (clark, clark_token) = app.user_with_token("Clark Kent", "clark@example.com", RoleType::User);
+    let client = reqwest::Client::new();
+    let rename = |token: &str, name: &str| {
+        client.patch(app.url(&format!("/api/user/{}", clark.id))).bearer_auth(token).json(&json!({ "name": name })).send()
+    };
+    let response = client.get(app.url("/api/tos/current")).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = client.post(app.url("/api/admin/tos")).bearer_auth(&clark_token)
+        .json(&json!({ "version": "2025-09", "url": "https://example.com/tos" })).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = client.post(app.url("/api/admin/tos")).bearer_auth(&admin_token)
+        .json(&json!({ "version": "2025 09", "url": "https://example.com/tos" })).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = client.post(app.url("/api/admin/tos")).bearer_auth(&admin_token)
+        .json(&json!({ "version": "2025-09", "url": "https://example.com/tos", "summary": "Clearer data retention" })).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let response = client.get(app.url("/api/tos/current")).send().await.unwrap();
+    assert_eq!(response.json::<Value>().await.unwrap()["data"]["version"], "2025-09");
+
+    // Reads carry the version to accept, writes wait for the acceptance. The publisher accepted it.
+    let response = client.get(app.url("/api/user/self")).bearer_auth(&clark_token).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["x-tos-required"], "2025-09");
+    let response = rename(&clark_token, "Kal-El").await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS);
+    assert_eq!(response.headers()["x-tos-required"], "2025-09");
+    assert_eq!(response.json::<Value>().await.unwrap()["code"], "TOS_ACCEPTANCE_REQUIRED");
+    let response = client.get(app.url("/api/user/self")).bearer_auth(&admin_token).send().await.unwrap();
+    assert!(response.headers().get("x-tos-required").is_none());
+
+    let response = client.post(app.url("/api/user/accept-tos")).bearer_auth(&clark_token)
+        .json(&json!({ "version": "2024-01" })).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    assert_eq!(response.json::<Value>().await.unwrap()["code"], "TOS_VERSION_NOT_CURRENT");
+    let response = client.post(app.url("/api/user/accept-tos")).bearer_auth(&clark_token)
+        .json(&json!({ "version": "2025-09" })).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.json::<Value>().await.unwrap()["data"]["version"], "2025-09");
+    let response = rename(&clark_token, "Kal-El").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get("x-tos-required").is_none());
+
+    // Accounts created after the version was published signed up under it.
+    let (_, bruce_token) = app.user_with_token("Bruce Wayne", "bruce@example.com", RoleType::User);
+    let response = client.get(app.url("/api/user/self")).bearer_auth(&bruce_token).send().await.unwrap();
+    assert!(response.headers().get("x-tos-required").is_none());
+    let response = client.get(app.url("/api/admin/tos")).bearer_auth(&admin_token).send().await.unwrap();
+    assert_eq!(response.json::<Value>().await.unwrap()["data"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn retention_dry_run_matches_what_the_worker_anonymizes() {
+    let app = spawn_app(&[("RETENTION_AUDIT_LOG_DAYS", "30")]).await;
+    let (_, admin_token) = app.user_with_token("Diana Prince", "diana@example.com", RoleType::Admin);
+    let client = reqwest::Client::new();
+    let response = client.post(app.url("/api/auth/sign-up"))
+        .json(&json!({ "name": "Clark Kent", "email": "clark@example.com", "password": "clark123", "password_confirm": "clark123" }))
+        .send().await.unwrap();
+    let clark_id: Uuid = response.json::<Value>().await.unwrap()["data"]["id"].as_str().unwrap().parse().unwrap();
+    let clark_token = jwt::create_token(&clark_id.to_string(), &app.app_state.env.jwt_keys, 600).unwrap();
+    let response = client.get(app.url("/api/admin/retention/dry-run")).bearer_auth(&clark_token).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let dry_run = || async {
+        let response = client.get(app.url("/api/admin/retention/dry-run")).bearer_auth(&admin_token).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        response.json::<Value>().await.unwrap()["data"].clone()
+    };
+
+    let body = dry_run().await;
+    let rules: Vec<&str> = body.as_array().unwrap().iter().map(|preview| preview["rule"].as_str().unwrap()).collect();
+    assert_eq!(rules, ["deleted_comments", "audit_logs"], "analytics events are kept by default");
+    assert_eq!(body[1]["action"], "anonymize");
+    assert_eq!(body[1]["retention_days"], 30);
+    assert_eq!(body[1]["count"], 0);
+
+    let actions = app.db.audit_actions(clark_id);
+    app.db.backdate_audit_logs(clark_id, Duration::days(31));
+    let body = dry_run().await;
+    assert_eq!(body[1]["count"], actions.len());
+    assert!(body[1]["oldest"].is_string());
+    assert_eq!(app.db.audit_actions(clark_id), actions, "the dry run leaves them alone");
+
+    retention::run(&app.app_state).await;
+    assert!(app.db.audit_actions(clark_id).is_empty());
+    assert_eq!(app.db.audit_actions(Uuid::nil()), actions);
+    assert_eq!(dry_run().await[1]["count"], 0);
+}
+
+#[tokio::test]
+async fn data_exports_are_written_by_the_worker_and_downloaded_by_signed_link() {
+    let storage_path = env::temp_dir().join(format!("exports-{}", Uuid::new_v4()));
+    let app = spawn_app(&[("EXPORT_STORAGE_PATH", storage_path.to_str().unwrap())]).await;
+    let (_, admin_token) = app.user_with_token("Diana Prince", "diana@example.com", RoleType::Admin);
+    let (_, clark_token) = app.user_with_token("Clark Kent", "clark@example.com", RoleType::User);
+    let client = reqwest::Client::new();
+    let request = json!({ "tables": ["posts", "users", "users"], "format": "ndjson" });
+    let response = client.post(app.url("/api/admin/exports")).bearer_auth(&clark_token).json(&request).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = client.post(app.url("/api/admin/exports")).bearer_auth(&admin_token)
+        .json(&json!({ "tables": ["users", "refresh_tokens"] }))
+        .send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST, "tokens aren't exported");
+    let response = client.post(app.url("/api/admin/exports")).bearer_auth(&admin_token)
+        .json(&json!({ "tables": ["invitations"] }))
+        .send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST, "invitations hold their tokens as emailed");
+
+    let response = client.post(app.url("/api/admin/exports")).bearer_auth(&admin_token).json(&request).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    let body = response.json::<Value>().await.unwrap();
+    let id = body["data"]["id"].as_str().unwrap().to_string();
+    assert_eq!(body["data"]["status"], "pending");
+    assert_eq!(body["data"]["tables"], json!(["users", "posts"]), "in load order, once each");
+    assert_eq!(body["data"]["progress"], 0);
+    assert!(body["data"]["manifest_url"].is_null());
+    let get = || async {
+        let response = client.get(app.url(&format!("/api/admin/exports/{}", id))).bearer_auth(&admin_token).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        response.json::<Value>().await.unwrap()["data"].clone()
+    };
+
+    data_export::run(&app.app_state).await;
+    let export = get().await;
+    assert_eq!(export["status"], "completed");
+    assert_eq!(export["progress"], 100);
+    assert_eq!(export["rows_total"], 2);
+    assert_eq!(export["rows_exported"], 2);
+    assert_eq!(export["files"][0]["table"], "users");
+    assert_eq!(export["files"][0]["rows"], 2);
+    assert_eq!(export["files"][1]["rows"], 0);
+    let users_url = export["files"][0]["url"].as_str().unwrap();
+    let response = client.get(app.url(users_url)).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+    let users = response.text().await.unwrap();
+    assert_eq!(users.len() as u64, export["files"][0]["bytes"].as_u64().unwrap());
+    let emails: Vec<String> = users.lines().map(|line| serde_json::from_str::<Value>(line).unwrap()["email"].as_str().unwrap().to_string()).collect();
+    assert_eq!(emails, ["diana@example.com", "clark@example.com"]);
+    let manifest = client.get(app.url(export["manifest_url"].as_str().unwrap())).send().await.unwrap()
+        .json::<Value>().await.unwrap();
+    assert_eq!(manifest["files"].as_array().unwrap().len(), 2);
+
+    let unsigned = users_url.split('?').next().unwrap();
+    let response = client.get(app.url(unsigned)).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let other_file = users_url.replace("users.ndjson", "secrets.ndjson");
+    let response = client.get(app.url(&other_file)).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN, "the signature covers the file");
+    let response = client.get(app.url(&format!("/api/admin/exports/{}", Uuid::new_v4()))).bearer_auth(&admin_token).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let _ = fs::remove_dir_all(storage_path);
+}
+
+#[tokio::test]
+async fn organizations_are_kept_in_a_region_and_only_admins_export_or_delete_them() {
+    let storage_path = env::temp_dir().join(format!("exports-{}", Uuid::new_v4()));
+    let app = spawn_app(&[("DATA_REGIONS", "eu, us"), ("EXPORT_STORAGE_PATH", storage_path.to_str().unwrap())]).await;
+    let (_, admin_token) = app.user_with_token("Diana Prince", "diana@example.com", RoleType::Admin);
+    let (_, clark_token) = app.user_with_token("Clark Kent", "clark@example.com", RoleType::User);
+    let client = reqwest::Client::new();
+    let response = client.post(app.url("/api/organization")).bearer_auth(&clark_token)
+        .json(&json!({ "name": "Daily Planet", "region": "mars" }))
+        .send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = response.json::<Value>().await.unwrap();
+    assert_eq!(body["code"], "REGION_UNAVAILABLE");
+    assert_eq!(body["message"], "The region is not available, choose one of: eu, us.");
+
+    let response = client.post(app.url("/api/organization")).bearer_auth(&clark_token)
+        .json(&json!({ "name": "Daily Planet", "region": "eu" }))
+        .send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let organization_id = response.json::<Value>().await.unwrap()["data"]["id"].as_str().unwrap().parse::<Uuid>().unwrap();
+    let export = app.url(&format!("/api/admin/organizations/{}/export", organization_id));
+    let response = client.post(&export).bearer_auth(&clark_token).json(&json!({})).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = client.delete(app.url(&format!("/api/admin/organizations/{}", organization_id))).bearer_auth(&clark_token).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = client.post(&export).bearer_auth(&admin_token).json(&json!({ "format": "parquet" })).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let response = client.post(&export).bearer_auth(&admin_token).json(&json!({})).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    let queued = response.json::<Value>().await.unwrap()["data"].clone();
+    assert_eq!(queued["organization_id"], organization_id.to_string());
+    assert_eq!(queued["tables"], json!([
+        "organizations", "organization_members", "organization_followers", "organization_invitations", "organization_usage",
+        "posts", "post_collaborators", "post_views", "comments",
+    ]));
+    data_export::run(&app.app_state).await;
+    let response = client.get(app.url(&format!("/api/admin/exports/{}", queued["id"].as_str().unwrap()))).bearer_auth(&admin_token).send().await.unwrap();
+    let export = response.json::<Value>().await.unwrap()["data"].clone();
+    assert_eq!(export["status"], "completed");
+    assert_eq!(export["organization_id"], organization_id.to_string());
+    assert_eq!(export["files"].as_array().unwrap().len(), 9);
+    assert_eq!(export["rows_total"], 0, "no users, nor anything else outside the organization");
+    let manifest = client.get(app.url(export["manifest_url"].as_str().unwrap())).send().await.unwrap()
+        .json::<Value>().await.unwrap();
+    assert_eq!(manifest["organization_id"], organization_id.to_string());
+
+    let organization = app.url(&format!("/api/organization/{}", organization_id));
+    let response = client.delete(app.url(&format!("/api/admin/organizations/{}", organization_id))).bearer_auth(&admin_token).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = client.get(&organization).bearer_auth(&clark_token).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let _ = fs::remove_dir_all(storage_path);
+}
+
+/// Serves an RSS feed at `/feed.xml` that gains an entry once it has been fetched, answering 304
+/// to a client that has the latest version, and a redirect to it at `/moved.xml`.
+async fn fake_feed_host() -> String {
+    fn item(guid: &str, title: &str, categories: &[&str]) -> String {
+        let categories: String = categories.iter().map(|category| format!("<category>{}</category>", category)).collect();
+        format!(
+            "<item><guid>{}</guid><title>{}</title><link>https://blog.example.com/{}</link>\
+             <description>&lt;p&gt;All about {}.&lt;/p&gt;</description>{}</item>",
+            guid, title, guid, guid, categories,
+        )
+    }
+    async fn feed(headers: HeaderMap) -> impl IntoResponse {
+        let mut items = vec![item("hello", "Hello world", &["Rust Lang", "web", "Rust Lang"]), item("hi", "Hi", &[])];
+        let etag = match headers.get("if-none-match").and_then(|value| value.to_str().ok()) {
+            None => "\"v1\"",
+            Some("\"v1\"") => {
+                items.push(item("third", "Third &amp; last", &["news"]));
+                "\"v2\""
+            }
+            Some(_) => return HttpStatus::NOT_MODIFIED.into_response(),
+        };
+        let body = format!("<?xml version=\"1.0\"?><rss version=\"2.0\"><channel><title>The Blog</title>{}</channel></rss>", items.concat());
+        ([("etag", etag), ("content-type", "application/rss+xml")], body).into_response()
+    }
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let router = Router::new()
+        .route("/feed.xml", get(feed))
+        .route("/moved.xml", get(|| async { (HttpStatus::MOVED_PERMANENTLY, [("location", "/feed.xml")]) }));
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+    url
+}
+
+#[tokio::test]
+async fn feed_entries_are_imported_once_as_drafts() {
+    let host = fake_feed_host().await;
+    let app = spawn_app(&[("FEED_IMPORT_PRIVATE_HOSTS", "allow"), ("FEED_IMPORT_MAX_SOURCES", "2")]).await;
+    let (_, token) = app.user_with_token("Princess Diana", "diana@example.com", RoleType::User);
+    let client = reqwest::Client::new();
+    let add = |url: String| client.post(app.url("/api/feed-import/sources")).bearer_auth(&token).json(&json!({ "url": url })).send();
+    assert_eq!(add("ftp://example.com/feed.xml".to_string()).await.unwrap().status(), StatusCode::BAD_REQUEST);
+    let response = add(format!("{}/feed.xml", host)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let source_id = response.json::<Value>().await.unwrap()["data"]["id"].as_str().unwrap().to_string();
+    assert_eq!(add(format!("{}/feed.xml", host)).await.unwrap().status(), StatusCode::CONFLICT);
+    assert_eq!(add(format!("{}/moved.xml", host)).await.unwrap().status(), StatusCode::CREATED);
+    let response = add(format!("{}/other.xml", host)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    assert_eq!(response.json::<Value>().await.unwrap()["code"], "FEED_SOURCE_LIMIT");
+
+    let sources = || async {
+        let response = client.get(app.url("/api/feed-import/sources")).bearer_auth(&token).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        response.json::<Value>().await.unwrap()["data"].clone()
+    };
+    let drafts = || async {
+        let response = client.get(app.url("/api/feed-import/drafts")).bearer_auth(&token).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        response.json::<Value>().await.unwrap()["data"].as_array().unwrap().clone()
+    };
+    let titles = |drafts: &[Value]| drafts.iter().map(|draft| draft["title"].as_str().unwrap().to_string()).collect::<Vec<_>>();
+
+    feed_import::run(&app.app_state).await;
+    let listed = sources().await;
+    assert_eq!(listed[0]["title"], "The Blog");
+    assert!(listed[0]["last_error"].is_null());
+    assert!(listed[0].get("etag").is_none());
+    assert!(listed[1]["last_error"].as_str().unwrap().contains("/feed.xml"), "{}", listed[1]);
+    assert_eq!(listed[1]["failure_count"], 1);
+    let first = drafts().await;
+    assert_eq!(titles(&first), ["Hi", "Hello world"]);
+    assert_eq!(first[1]["content"], "All about hello.");
+    assert_eq!(first[1]["tags"], json!(["rust-lang"]));
+    assert_eq!(first[0]["tags"], json!(["imported"]));
+    let draft_url = |draft: &Value, action: &str| app.url(&format!("/api/feed-import/drafts/{}{}", draft["id"].as_str().unwrap(), action));
+
+    // Too short to be a post title, the user has to give one.
+    let response = client.post(draft_url(&first[0], "/publish")).bearer_auth(&token).json(&json!({})).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = client.delete(draft_url(&first[0], "")).bearer_auth(&token).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = client.delete(draft_url(&first[0], "")).bearer_auth(&token).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = client.post(draft_url(&first[1], "/publish")).bearer_auth(&token).json(&json!({})).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let post = response.json::<Value>().await.unwrap()["data"].clone();
+    assert_eq!(post["title"], "Hello world");
+    assert_eq!(post["tags"], json!(["rust-lang"]));
+    let response = client.post(draft_url(&first[1], "/publish")).bearer_auth(&token).json(&json!({})).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    app.db.expire_feed_sources();
+    feed_import::run(&app.app_state).await;
+    let second = drafts().await;
+    assert_eq!(titles(&second), ["Third & last"], "neither the dismissed nor the published entry comes back");
+    app.db.expire_feed_sources();
+    feed_import::run(&app.app_state).await;
+    assert_eq!(drafts().await.len(), 1);
+    assert!(sources().await[0]["last_error"].is_null(), "304 is a successful fetch");
+
+    let response = client.delete(app.url(&format!("/api/feed-import/sources/{}", source_id))).bearer_auth(&token).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(drafts().await.is_empty());
+    let response = client.delete(app.url(&format!("/api/feed-import/sources/{}", source_id))).bearer_auth(&token).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn post_feeds_are_public_but_leave_out_private_accounts() {
+    let app = spawn_app(&[]).await;
+    let clark = app.db.add_user("Clark Kent", "clark@example.com", "clark123", RoleType::User);
+    let client = reqwest::Client::new();
+    let feed = |uri: String| client.get(app.url(&uri)).send();
+
+    let response = feed(format!("/api/user/{}/posts.rss?format=json", clark.id)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = feed(format!("/api/user/{}/posts.rss", Uuid::new_v4())).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    app.db.set_private(clark.id, true);
+    let response = feed(format!("/api/user/{}/posts.rss?format=atom", clark.id)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND, "private accounts have no public feed");
+    let response = feed("/api/tag/rust/posts.rss?format=xml".to_string()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    app.db.set_private(clark.id, false);
+    let response = feed(format!("/api/user/{}/posts.rss", clark.id)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn user_search_ranks_username_and_name_prefixes() {
+    let app = spawn_app(&[]).await;
+    let client = reqwest::Client::new();
+    let mut tokens = Vec::new();
+    for (name, username) in [("Zed Alpha", Some("ken")), ("Yara", Some("kenobi")), ("Kenji Sato", None), ("Clark Kent", None), ("Bruce Wayne", Some("batman"))] {
+        let email = format!("{}@example.com", name.to_lowercase().replace(' ', "."));
+        let user = app.db.add_user(name, &email, "secret123", RoleType::User);
+        let token = app.token_for(&user);
+        if let Some(username) = username {
+            let response = client.patch(app.url(&format!("/api/user/{}", user.id)))
+                .bearer_auth(&token)
+                .json(&json!({ "username": username }))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        tokens.push(token);
+    }
+    let search = |query: &'static [(&'static str, &'static str)]| {
+        client.get(app.url("/api/user/search")).bearer_auth(&tokens[0]).query(query).send()
+    };
+
+    let response = search(&[("q", "@Ken")]).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await.unwrap();
+    let names: Vec<&str> = body["data"].as_array().unwrap().iter().map(|user| user["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["Zed Alpha", "Yara", "Kenji Sato", "Clark Kent"]);
+    assert_eq!(body["data"][0]["username"], "ken");
+    assert!(body["data"][0].get("email").is_none());
+
+    let body: Value = search(&[("q", "ken"), ("limit", "2")]).await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"].as_array().unwrap().len(), 2);
+    let response = search(&[("q", "ken"), ("limit", "500")]).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = search(&[("q", " @ ")]).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+#[tokio::test]
+async fn search_rejects_empty_queries_and_unknown_types() {
+    let app = spawn_app(&[]).await;
+    let (_, token) = app.user_with_token("Searcher", "searcher@example.com", RoleType::User);
+    let client = reqwest::Client::new();
+    let search = |query: &'static [(&'static str, &'static str)]| {
+        client.get(app.url("/api/search")).bearer_auth(&token).query(query).send()
+    };
+
+    for query in [&[][..], &[("q", "")], &[("q", " #@ ")], &[("q", "rust"), ("type", "comments")]] {
+        let response = search(query).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+    let response = reqwest::Client::new().get(app.url("/api/search")).query(&[("q", "rust")]).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+#[tokio::test]
+async fn post_collaborator_requests_are_checked_before_the_database() {
+    let app = spawn_app(&[]).await;
+    let (diana, token) = app.user_with_token("Princess Diana", "diana@example.com", RoleType::User);
+    let client = reqwest::Client::new();
+    let collaborators = app.url(&format!("/api/post/{}/collaborators", Uuid::new_v4()));
+
+    for body in [json!({}), json!({ "user_id": "diana" })] {
+        let response = client.post(&collaborators).bearer_auth(&token).json(&body).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    let response = client.delete(format!("{}/diana", collaborators)).bearer_auth(&token).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = client.post(&collaborators).json(&json!({ "user_id": diana.id })).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+#[tokio::test]
+async fn organization_requests_are_checked_before_the_database() {
+    let app = spawn_app(&[]).await;
+    let (diana, token) = app.user_with_token("Princess Diana", "diana@example.com", RoleType::User);
+    let client = reqwest::Client::new();
+    let organization = app.url(&format!("/api/organization/{}", Uuid::new_v4()));
+
+    for body in [json!({ "name": "" }), json!({ "name": "Themyscira", "description": "x".repeat(256) })] {
+        let response = client.post(app.url("/api/organization")).bearer_auth(&token).json(&body).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+    let response = client.put(&organization).bearer_auth(&token).json(&json!({})).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let invitation = json!({ "user_id": diana.id, "role": "admin" });
+    let response = client.post(format!("{}/invitations", organization)).bearer_auth(&token).json(&invitation).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let response = client.get(app.url("/api/user/feed?organizations=maybe")).bearer_auth(&token).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = client.get(app.url("/api/organization/invitations")).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+#[tokio::test]
+async fn organization_requests_count_against_the_monthly_quota() {
+    let app = spawn_app(&[]).await;
+    let redis_client = &app.app_state.redis_client;
+    let organization_id = Uuid::new_v4();
+    let month = NaiveDate::from_ymd_opt(2025, 9, 1).unwrap();
+
+    // Redis starts from the count the database holds and stops counting at the limit.
+    assert_eq!(redis_client.hit_org_usage(&organization_id, month, 3, Some(5)).await.unwrap(), (true, 4));
+    assert_eq!(redis_client.hit_org_usage(&organization_id, month, 0, Some(5)).await.unwrap(), (true, 5));
+    assert_eq!(redis_client.hit_org_usage(&organization_id, month, 0, Some(5)).await.unwrap(), (false, 5));
+    assert_eq!(redis_client.hit_org_usage(&organization_id, month, 0, None).await.unwrap(), (true, 6));
+    assert_eq!(redis_client.get_org_usage(&organization_id, month).await.unwrap(), Some(6));
+    assert_eq!(redis_client.get_org_usage(&organization_id, NaiveDate::from_ymd_opt(2025, 10, 1).unwrap()).await.unwrap(), None);
+
+    // Only the requests counted here are pending, the database already has the rest.
+    let usage = redis_client.take_org_usage().await.unwrap();
+    assert_eq!(usage, vec![(organization_id, month, 3)]);
+    assert!(redis_client.take_org_usage().await.unwrap().is_empty());
+    redis_client.restore_org_usage(&usage).await.unwrap();
+    assert_eq!(redis_client.take_org_usage().await.unwrap(), usage);
+
+    let (_, token) = app.user_with_token("Princess Diana", "diana@example.com", RoleType::User);
+    let client = reqwest::Client::new();
+    // Usage is attributed from the path, a header naming the organization counts nothing.
+    let response = client.get(app.url("/api/user/self")).bearer_auth(&token).header("X-Organization-Id", organization_id.to_string()).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = client.get(app.url(&format!("/api/organization/{}", organization_id))).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert!(response.headers().get("x-quota-limit").is_none());
+}
+#[tokio::test]
+async fn with_lock_runs_one_holder_at_a_time_and_releases_only_its_own_lock() {
+    let app = spawn_app(&[]).await;
+    let redis_client = &app.app_state.redis_client;
+    let ttl = std::time::Duration::from_secs(10);
+
+    let inner = redis_client.with_lock("job", ttl, async {
+        assert!(app.redis.get("lock:job").is_some());
+        redis_client.with_lock("job", ttl, async { "ran" }).await.unwrap()
+    }).await.unwrap();
+    assert_eq!(inner, Some(None));
+    assert!(app.redis.get("lock:job").is_none());
+
+    // The lock ran out while the task was busy and another replica took it over.
+    let taken_over = redis_client.with_lock("job", ttl, async {
+        let mut conn = redis_client.pool.get().await.unwrap();
+        let _: () = redis::cmd("SET").arg("lock:job").arg("other-token").query_async(&mut conn).await.unwrap();
+    }).await.unwrap();
+    assert_eq!(taken_over, Some(()));
+    assert_eq!(app.redis.get("lock:job").as_deref(), Some(&b"other-token"[..]));
+    assert_eq!(redis_client.with_lock("job", ttl, async {}).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn admins_inherit_the_permissions_of_users() {
+    let app = spawn_app(&[]).await;
+    let db = &app.app_state.db;
+    let role_permissions = |role: RoleType| async move {
+        let role_id = db.roles.get_role_id_by_name(role).await.unwrap().unwrap();
+        db.permissions.get_permission_by_role(&role_id).await.unwrap()
+    };
+    let user_permissions = role_permissions(RoleType::User).await;
+    let admin_permissions = role_permissions(RoleType::Admin).await;
+    assert!(user_permissions.contains(&"user:self".to_string()));
+    assert!(!user_permissions.contains(&"admin:stats".to_string()));
+    assert!(user_permissions.iter().all(|permission| admin_permissions.contains(permission)));
+    assert!(admin_permissions.contains(&"admin:stats".to_string()));
+    assert_eq!(admin_permissions.len(), Permission::ALL.len());
+
+    let (_, token) = app.user_with_token("Root", "root@example.com", RoleType::Admin);
+    let response = reqwest::Client::new().get(app.url("/api/user/self")).bearer_auth(&token).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+/// An endpoint answering with the queued statuses in turn and 200 once they run out, recording
+/// the headers and body of every request.
+async fn fake_http_receiver(statuses: Vec<HttpStatus>) -> (String, Arc<Mutex<Vec<(HeaderMap, Bytes)>>>) {
+    let statuses = Arc::new(Mutex::new(statuses));
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let recorded = received.clone();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let router = Router::new().route("/hook", post(move |headers: HeaderMap, body: Bytes| async move {
+        recorded.lock().unwrap().push((headers, body));
+        let mut statuses = statuses.lock().unwrap();
+        if statuses.is_empty() { HttpStatus::OK } else { statuses.remove(0) }
+    }));
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+    (url, received)
+}
+
+#[tokio::test]
+async fn webhook_deliveries_are_signed_retried_with_backoff_and_dead_lettered() {
+    let secret = "whsec_MfKQ9r8GKYqrTwjUPD8ILPZIo2LaLaSw";
+    let app = spawn_app(&[
+        ("WEBHOOK_MAX_ATTEMPTS", "3"),
+        ("WEBHOOK_RETRY_BACKOFF", "60"),
+        ("EMAIL_WEBHOOK_SECRET", secret),
+    ]).await;
+    let (diana, admin_token) = app.user_with_token("Princess Diana", "diana@example.com", RoleType::Admin);
+    let client = reqwest::Client::new();
+    let webhooks = webhook_delivery::client(&app.app_state);
+    let (url, received) = fake_http_receiver(vec![HttpStatus::INTERNAL_SERVER_ERROR; 4]).await;
+    let response = client.post(app.url("/api/admin/webhooks"))
+        .bearer_auth(&admin_token)
+        .json(&json!({ "url": url, "events": ["user.created"], "secret": secret }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let deliveries = || async {
+        let response = client.get(app.url("/api/admin/webhooks/deliveries")).bearer_auth(&admin_token).send().await.unwrap();
+        let body: Value = response.json().await.unwrap();
+        body["data"]["items"][0].clone()
+    };
+
+    app.app_state.db.webhooks.queue_webhook_event(WebhookEvent::UserCreated, json!({ "id": diana.id })).await.unwrap();
+    let mut backoffs = Vec::new();
+    for attempt in 1..=3 {
+        app.db.expire_webhook_deliveries();
+        let started = Utc::now();
+        webhook_delivery::run(&app.app_state, &webhooks).await;
+        webhook_delivery::run(&app.app_state, &webhooks).await;
+        assert_eq!(received.lock().unwrap().len(), attempt, "a delivery waits out its backoff");
+        let delivery = deliveries().await;
+        assert_eq!(delivery["attempts"], attempt);
+        assert_eq!(delivery["last_status_code"], 500);
+        let next_attempt_at: chrono::DateTime<Utc> = delivery["next_attempt_at"].as_str().unwrap().parse().unwrap();
+        backoffs.push((next_attempt_at - started).num_seconds());
+    }
+    // 60 then 120 seconds, each cut by up to half.
+    assert!((29..=60).contains(&backoffs[0]), "{:?}", backoffs);
+    assert!((59..=120).contains(&backoffs[1]), "{:?}", backoffs);
+    let delivery = deliveries().await;
+    assert_eq!(delivery["status"], "dead");
+    app.db.expire_webhook_deliveries();
+    webhook_delivery::run(&app.app_state, &webhooks).await;
+    assert_eq!(received.lock().unwrap().len(), 3, "dead deliveries are not sent again");
+
+    let response = client.post(app.url(&format!("/api/admin/webhooks/deliveries/{}/retry", delivery["id"].as_str().unwrap())))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    webhook_delivery::run(&app.app_state, &webhooks).await;
+    webhook_delivery::run(&app.app_state, &webhooks).await;
+    let delivery = deliveries().await;
+    assert_eq!(delivery["status"], "pending");
+    assert_eq!(delivery["attempts"], 1);
+    app.db.expire_webhook_deliveries();
+    webhook_delivery::run(&app.app_state, &webhooks).await;
+    let delivery = deliveries().await;
+    assert_eq!(delivery["status"], "delivered");
+    assert_eq!(delivery["last_status_code"], 200);
+
+    let (headers, body) = received.lock().unwrap().last().cloned().unwrap();
+    assert_eq!(headers["webhook-id"], delivery["id"].as_str().unwrap());
+    let payload: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(payload["event"], "user.created");
+    assert_eq!(payload["data"]["id"], diana.id.to_string());
+    verify_webhook(&app.app_state, WebhookProvider::Email, &headers, &body).await.expect("a valid signature");
+    let mut tampered = body.to_vec();
+    tampered.push(b' ');
+    assert!(verify_webhook(&app.app_state, WebhookProvider::Email, &headers, &tampered).await.is_err());
+}
+
+#[tokio::test]
+async fn webhook_deliveries_claimed_by_a_crashed_instance_are_sent_once_the_lease_runs_out() {
+    let app = spawn_app(&[("WEBHOOK_TIMEOUT", "5")]).await;
+    let webhooks = webhook_delivery::client(&app.app_state);
+    let (url, received) = fake_http_receiver(Vec::new()).await;
+    app.app_state.db.webhooks.save_webhook_endpoint(NewWebhookEndpoint {
+        url,
+        secret: "a-long-enough-secret".to_string(),
+        events: vec![WebhookEvent::UserCreated],
+        created_by: Uuid::new_v4(),
+    }).await.unwrap();
+    app.app_state.db.webhooks.queue_webhook_event(WebhookEvent::UserCreated, json!({})).await.unwrap();
+    // Claimed by an instance that died before sending it.
+    let claimed = app.app_state.db.webhooks.claim_webhook_deliveries(10, 35.0).await.unwrap();
+    assert_eq!(claimed.len(), 1);
+    webhook_delivery::run(&app.app_state, &webhooks).await;
+    assert!(received.lock().unwrap().is_empty(), "a leased delivery is left to its claimer");
+    app.db.expire_webhook_deliveries();
+    webhook_delivery::run(&app.app_state, &webhooks).await;
+    assert_eq!(received.lock().unwrap().len(), 1);
+    webhook_delivery::run(&app.app_state, &webhooks).await;
+    assert_eq!(received.lock().unwrap().len(), 1, "a delivered delivery is not claimed again");
+}
+
+#[tokio::test]
+async fn incoming_webhooks_need_a_fresh_valid_signature_and_are_accepted_once() {
+    let secret = "whsec_MfKQ9r8GKYqrTwjUPD8ILPZIo2LaLaSw";
+    let app = spawn_app(&[
+        ("EMAIL_WEBHOOK_SECRET", secret),
+        ("STRIPE_WEBHOOK_SECRET", "stripe-secret"),
+        ("EMAIL_WEBHOOK_TOLERANCE", "60"),
+    ]).await;
+    let body = br#"{"type":"email.bounced"}"#;
+    let email_headers = |id: &str, timestamp: i64, body: &[u8]| {
+        let mut headers = HeaderMap::new();
+        headers.insert("webhook-id", id.parse().unwrap());
+        headers.insert("webhook-timestamp", timestamp.to_string().parse().unwrap());
+        headers.insert("webhook-signature", sign_standard_webhook(secret, id, timestamp, body).parse().unwrap());
+        headers
+    };
+    let code = |result: Result<(), AppError>| result.err().map(|e| e.into_response().status());
+    let now = Utc::now().timestamp();
+
+    let headers = email_headers("msg_1", now, body);
+    assert_eq!(code(verify_webhook(&app.app_state, WebhookProvider::Email, &headers, body).await), None);
+    assert_eq!(code(verify_webhook(&app.app_state, WebhookProvider::Email, &headers, body).await), Some(HttpStatus::CONFLICT), "replayed");
+    let headers = email_headers("msg_2", now, body);
+    assert_eq!(code(verify_webhook(&app.app_state, WebhookProvider::Email, &headers, br#"{"type":"email.opened"}"#).await), Some(HttpStatus::UNAUTHORIZED), "tampered body");
+    let headers = email_headers("msg_3", now - 61, body);
+    assert_eq!(code(verify_webhook(&app.app_state, WebhookProvider::Email, &headers, body).await), Some(HttpStatus::UNAUTHORIZED), "too old");
+    let headers = email_headers("msg_4", now + 61, body);
+    assert_eq!(code(verify_webhook(&app.app_state, WebhookProvider::Email, &headers, body).await), Some(HttpStatus::UNAUTHORIZED), "from the future");
+    let mut headers = email_headers("msg_5", now, body);
+    headers.insert("webhook-signature", sign_standard_webhook("whsec_b3RoZXIgc2VjcmV0", "msg_5", now, body).parse().unwrap());
+    assert_eq!(code(verify_webhook(&app.app_state, WebhookProvider::Email, &headers, body).await), Some(HttpStatus::UNAUTHORIZED), "other secret");
+    assert_eq!(code(verify_webhook(&app.app_state, WebhookProvider::Email, &HeaderMap::new(), body).await), Some(HttpStatus::UNAUTHORIZED), "unsigned");
+
+    let stripe_headers = |timestamp: i64, body: &[u8]| {
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"stripe-secret").unwrap();
+        mac.update(format!("{}.", timestamp).as_bytes());
+        mac.update(body);
+        let mut headers = HeaderMap::new();
+        let signature = format!("t={},v1={}", timestamp, hex::encode(mac.finalize().into_bytes()));
+        headers.insert("stripe-signature", signature.parse().unwrap());
+        headers
+    };
+    let headers = stripe_headers(now, body);
+    assert_eq!(code(verify_webhook(&app.app_state, WebhookProvider::Stripe, &headers, body).await), None);
+    assert_eq!(code(verify_webhook(&app.app_state, WebhookProvider::Stripe, &headers, body).await), Some(HttpStatus::CONFLICT), "replayed");
+    let mut prefixed = HeaderMap::new();
+    let signature = headers["stripe-signature"].to_str().unwrap().replacen(",v1=", &format!(",v1={},v1=", "00".repeat(32)), 1);
+    prefixed.insert("stripe-signature", signature.parse().unwrap());
+    assert_eq!(code(verify_webhook(&app.app_state, WebhookProvider::Stripe, &prefixed, body).await), Some(HttpStatus::CONFLICT), "replayed behind a bogus signature");
+    let headers = stripe_headers(now - 301, body);
+    assert_eq!(code(verify_webhook(&app.app_state, WebhookProvider::Stripe, &headers, body).await), Some(HttpStatus::UNAUTHORIZED), "too old");
+    let headers = stripe_headers(now, b"{}");
+    assert_eq!(code(verify_webhook(&app.app_state, WebhookProvider::Stripe, &headers, body).await), Some(HttpStatus::UNAUTHORIZED), "tampered body");
+
+    let app = spawn_app(&[]).await;
+    let headers = email_headers("msg_6", now, body);
+    assert_eq!(code(verify_webhook(&app.app_state, WebhookProvider::Email, &headers, body).await), Some(HttpStatus::INTERNAL_SERVER_ERROR), "no secret configured");
+}
+
+#[tokio::test]
+async fn audit_logs_are_exported_again_until_the_sink_takes_them() {
+    let app = spawn_app(&[("AUDIT_EXPORT_BATCH_SIZE", "2")]).await;
+    let (url, received) = fake_http_receiver(vec![HttpStatus::SERVICE_UNAVAILABLE]).await;
+    let sink = AuditSink::Http(url);
+    let user_id = Uuid::new_v4();
+    for action in [AuditAction::UserRegistered, AuditAction::AccountVerified, AuditAction::UserUpdated] {
+        app.app_state.db.audit_logs.save_audit_log(NewAuditLog::new(None, user_id, action)).await.unwrap();
+    }
+    let batches = || received.lock().unwrap().iter()
+        .map(|(_, body)| serde_json::from_slice::<Vec<Value>>(body).unwrap().iter().map(|log| log["action"].as_str().unwrap().to_string()).collect())
+        .collect::<Vec<Vec<String>>>();
+
+    audit_export::run(&app.app_state, &sink).await;
+    assert_eq!(batches(), [vec!["user.registered", "user.account-verified"]], "a failed batch stops the run");
+    audit_export::run(&app.app_state, &sink).await;
+    assert_eq!(batches()[1..], [vec!["user.registered", "user.account-verified"], vec!["user.updated"]]);
+    audit_export::run(&app.app_state, &sink).await;
+    assert_eq!(batches().len(), 3, "exported logs are not sent again");
+}#[tokio::test]
+async fn audit_logs_are_exported_over_tcp_syslog_and_to_a_bucket() {
+    let bucket = Arc::new(Mutex::new(Vec::new()));
+    let recorded = bucket.clone();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let router = Router::new().fallback(move |method: axum::http::Method, uri: axum::http::Uri, headers: HeaderMap, body: Bytes| async move {
+        recorded.lock().unwrap().push((method, uri.path().to_string(), headers, body));
+        HttpStatus::OK
+    });
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+    let app = spawn_app(&[
+        ("AUDIT_SINK", "s3"),
+        ("AUDIT_SINK_URL", &endpoint),
+        ("AUDIT_S3_BUCKET", "siem"),
+        ("AUDIT_S3_ACCESS_KEY", "access"),
+        ("AUDIT_S3_SECRET_KEY", "secret"),
+    ]).await;
+    let user_id = Uuid::new_v4();
+    for action in [AuditAction::UserRegistered, AuditAction::AccountVerified] {
+        app.app_state.db.audit_logs.save_audit_log(NewAuditLog::new(None, user_id, action)).await.unwrap();
+    }
+    let logs = app.app_state.db.audit_logs.get_unexported_audit_logs(10).await.unwrap();
+
+    let sink = AuditSink::from_config(&app.app_state.env).unwrap();
+    sink.send(&logs).await.unwrap();
+    sink.send(&logs).await.unwrap();
+    let objects = bucket.lock().unwrap().clone();
+    assert_eq!(objects.len(), 2);
+    let (method, path, headers, body) = &objects[0];
+    assert_eq!(method, "PUT");
+    assert!(path.starts_with("/siem/audit-logs/") && path.ends_with(&format!("-{}.ndjson", logs[0].id)), "{}", path);
+    assert_eq!(objects[1].1, *path, "a batch sent again replaces its object");
+    assert!(headers["authorization"].to_str().unwrap().starts_with("AWS4-HMAC-SHA256 Credential=access/"));
+    let actions: Vec<String> = String::from_utf8(body.to_vec()).unwrap().lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap()["action"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(actions, ["user.registered", "user.account-verified"]);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let sink = AuditSink::Syslog(listener.local_addr().unwrap().to_string());
+    let (sent, frames) = tokio::join!(sink.send(&logs), async {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut frames = String::new();
+        tokio::io::AsyncReadExt::read_to_string(&mut stream, &mut frames).await.unwrap();
+        frames
+    });
+    sent.unwrap();
+    let (length, rest) = frames.split_once(' ').unwrap();
+    let (first, rest) = rest.split_at(length.parse().unwrap());
+    assert!(first.starts_with("<85>1 ") && first.contains(" user.registered - {"), "{}", first);
+    let (length, second) = rest.split_once(' ').unwrap();
+    assert_eq!(second.len(), length.parse::<usize>().unwrap(), "one frame per log");
+    assert!(second.contains(" user.account-verified - {"));
+    drop(listener);
+    assert!(sink.send(&logs).await.is_err(), "a refused connection fails the batch");
+}